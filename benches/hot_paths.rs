@@ -0,0 +1,123 @@
+//! Baselines for the paths a query spends its local time in. Compare a
+//! change against them with `cargo bench -- --save-baseline main` on the
+//! old code and `cargo bench -- --baseline main` on the new.
+
+use std::time::Duration;
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use tempfile::TempDir;
+
+use q::api::openai::OpenAIClient;
+use q::commands::matcher::rank;
+use q::commands::{Category, CommandInfo, Example};
+use q::context::directory::DirectoryProvider;
+use q::context::{ContextConfig, ContextProvider};
+use q::core::cache::QueryCache;
+use q::utils::format_markdown;
+
+/// A long answer mixing prose, lists, code blocks, and links
+fn large_response() -> String {
+    let section = "## Step\n\nRun the command below, then check the **output** for errors.\n\n\
+                   - first, `cargo build`\n- then [the docs](https://doc.rust-lang.org/cargo/)\n\n\
+                   ```bash\ncargo build --release 2>&1 | tee build.log\n```\n\n";
+    section.repeat(500)
+}
+
+fn bench_markdown(c: &mut Criterion) {
+    let response = large_response();
+    let mut group = c.benchmark_group("format_markdown");
+    group.throughput(Throughput::Bytes(response.len() as u64));
+    group.bench_function("large_response", |b| b.iter(|| format_markdown(black_box(&response))));
+    group.finish();
+}
+
+fn bench_sse(c: &mut Criterion) {
+    let chunk: String = (0..64)
+        .map(|i| format!("data: {{\"choices\":[{{\"delta\":{{\"content\":\"token {} \"}}}}]}}\n\n", i))
+        .collect();
+    let mut group = c.benchmark_group("sse");
+    group.throughput(Throughput::Bytes(chunk.len() as u64));
+    group.bench_function("openai_chunk_64_events", |b| {
+        b.iter(|| OpenAIClient::process_stream_chunk(black_box(chunk.as_bytes())).unwrap())
+    });
+    group.finish();
+}
+
+/// 20 directories of 50 files, some of them ignored
+fn project_tree() -> TempDir {
+    let root = TempDir::new().unwrap();
+    std::fs::write(root.path().join(".gitignore"), "target/\n").unwrap();
+    for dir in 0..20 {
+        let path = root.path().join(format!("module_{}", dir));
+        std::fs::create_dir_all(&path).unwrap();
+        for file in 0..50 {
+            std::fs::write(path.join(format!("file_{}.rs", file)), "fn main() {}\n").unwrap();
+        }
+    }
+    std::fs::create_dir_all(root.path().join("target/debug")).unwrap();
+    root
+}
+
+fn bench_directory(c: &mut Criterion) {
+    let tree = project_tree();
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let mut group = c.benchmark_group("directory");
+    for max_entries in [100, 1000] {
+        group.bench_function(format!("walk_1000_files_budget_{}", max_entries), |b| {
+            b.iter(|| {
+                let provider = DirectoryProvider::new(tree.path().to_path_buf(), ContextConfig::default())
+                    .with_max_entries(max_entries)
+                    .with_time_budget(Duration::from_secs(5));
+                runtime.block_on(provider.get_context()).unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+/// `count` generated commands, enough to make scoring cost show
+fn command_db(count: usize) -> Vec<CommandInfo> {
+    let categories = [Category::FileSystem, Category::Process, Category::Network, Category::Performance, Category::Development];
+    (0..count)
+        .map(|i| CommandInfo {
+            name: format!("tool{}", i),
+            description: format!("Inspects {} resources and reports usage", ["disk", "network", "process"][i % 3]),
+            category: categories[i % categories.len()].clone(),
+            examples: vec![Example::any(format!("tool{} --summary", i))],
+            keywords: vec!["inspect".to_string(), format!("keyword{}", i % 50)],
+        })
+        .collect()
+}
+
+fn bench_matcher(c: &mut Criterion) {
+    let commands = command_db(5000);
+    c.bench_function("matcher/rank_5000_commands", |b| {
+        b.iter(|| rank(black_box("tool to monitor network connection usage"), &commands))
+    });
+}
+
+fn bench_cache(c: &mut Criterion) {
+    let cache = QueryCache::new(1000, Duration::from_secs(3600));
+    for i in 0..1000 {
+        cache.insert(format!("prompt {}", i), format!("response {}", i));
+    }
+    let mut group = c.benchmark_group("cache");
+    group.bench_function("hit", |b| b.iter(|| cache.get(black_box("prompt 500"))));
+    group.bench_function("miss", |b| b.iter(|| cache.get(black_box("not cached"))));
+    group.bench_function("insert_evicting", |b| {
+        b.iter_batched(
+            || format!("new prompt {}", rand_suffix()),
+            |prompt| cache.insert(prompt, "response".to_string()),
+            BatchSize::SmallInput,
+        )
+    });
+    group.finish();
+}
+
+/// A distinct key per insert so each one evicts, without pulling in a RNG
+fn rand_suffix() -> u128 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_nanos()
+}
+
+criterion_group!(benches, bench_markdown, bench_sse, bench_directory, bench_matcher, bench_cache);
+criterion_main!(benches);
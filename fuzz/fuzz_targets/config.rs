@@ -0,0 +1,18 @@
+//! Config files, which users edit by hand: parsed, then checked as
+//! `q config validate` does, including the diagnostics for broken TOML
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use q::config::types::{Config, ProjectConfig};
+use q::config::validate::validate_str;
+
+fuzz_target!(|contents: &str| {
+    let _ = validate_str(contents);
+    let _ = toml::from_str::<ProjectConfig>(contents);
+    if let Ok(config) = toml::from_str::<Config>(contents) {
+        // Whatever loads must save and load again
+        let saved = toml::to_string_pretty(&config).expect("config serializes");
+        toml::from_str::<Config>(&saved).expect("saved config loads");
+    }
+});
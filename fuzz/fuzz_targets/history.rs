@@ -0,0 +1,16 @@
+//! Shell history files: zsh (plain or extended, metafied), bash (with or
+//! without timestamps, read by the zsh parser), and PowerShell
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use q::context::history::{parse_history, HistoryFormat};
+
+fuzz_target!(|bytes: &[u8]| {
+    for entry in parse_history(bytes, HistoryFormat::Zsh) {
+        assert!(!entry.trim().is_empty());
+    }
+    for entry in parse_history(bytes, HistoryFormat::PowerShell) {
+        assert!(!entry.trim().is_empty());
+    }
+});
@@ -0,0 +1,11 @@
+//! Model answers, which can hold any text at all
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use q::utils::format::{format_markdown, strip_ansi};
+
+fuzz_target!(|text: &str| {
+    let _ = format_markdown(text);
+    let _ = strip_ansi(text);
+});
@@ -0,0 +1,13 @@
+//! Streamed response chunks as a provider (or anything between it and q) may
+//! send them: split mid-event, mid-character, or not SSE at all
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use q::api::gemini::GeminiClient;
+use q::api::openai::OpenAIClient;
+
+fuzz_target!(|chunk: &[u8]| {
+    let _ = OpenAIClient::process_stream_chunk(chunk);
+    let _ = GeminiClient::process_stream_chunk(chunk);
+});
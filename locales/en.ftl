@@ -0,0 +1,43 @@
+# q's messages in English, the fallback for every other language. Other
+# files translate these ids, and may also override clap's help with
+# help-q (the about line), help-q--<arg>, and help-q-<subcommand>[--<arg>].
+
+## Errors, as shown when a command fails
+error = Error
+hint = Hint
+error-cli = CLI error: { $detail }
+error-config = Configuration error: { $detail }
+error-api = API error: { $detail }
+error-io = IO error: { $detail }
+error-context = Context error: { $detail }
+error-command = Command error: { $detail }
+error-core = Core error: { $detail }
+error-usage = Usage error: { $detail }
+error-unknown = Unknown error: { $detail }
+
+## What to try next after common failures
+hint-invalid-key = Check the key with `q keys test <provider>` and replace it with `q set-key <provider>`.
+hint-context-length = Send less context (fewer context flags, or a smaller `max_bytes` under [context]) or pick a model with a larger context window (`q list-models`).
+hint-quota = Check your plan or billing with the provider, add another key with `q set-key <provider> --add`, or switch providers with --provider.
+hint-model-not-found = Run `q list-models` to see what the provider serves, then `q set-model <provider> <model>`.
+hint-rate-limit = Wait a minute, raise --retries, or add a second key with `q set-key <provider> --add` to rotate between them.
+hint-overloaded = Try again shortly, or use another provider with --provider.
+hint-timeout = Try again; a shorter question or less context also helps slow models.
+hint-content-filter = Rephrase the question or leave out the context that may have triggered the filter.
+hint-network = Check your network connection and proxy settings (HTTPS_PROXY, NO_PROXY).
+
+## Status lines
+no-prompt = No prompt provided. Use --help for usage information.
+status-provider = provider: { $provider }, model: { $model }
+cancelled-nothing-sent = Cancelled; nothing was sent
+watch-started = Watching { $files } (Ctrl-C to stop)
+caveat-cutoff = { $model } knows about events up to about { $cutoff }, and this asks about "{ $trigger }"; add --web for current information.
+caveat-time-sensitive = This asks about "{ $trigger }", which may be newer than what { $model } was trained on; add --web for current information.
+
+## clap's headings and built-in flags
+help-usage = Usage:
+help-arguments = Arguments:
+help-options = Options:
+help-commands = Commands:
+help-flag = Print help
+version-flag = Print version
@@ -0,0 +1,210 @@
+# q のメッセージの日本語訳。ここにない ID は英語 (en.ftl) で表示されます。
+
+## エラー
+error = エラー
+hint = ヒント
+error-cli = CLI エラー: { $detail }
+error-config = 設定エラー: { $detail }
+error-api = API エラー: { $detail }
+error-io = 入出力エラー: { $detail }
+error-context = コンテキストエラー: { $detail }
+error-command = コマンドエラー: { $detail }
+error-core = 実行エラー: { $detail }
+error-usage = 使い方のエラー: { $detail }
+error-unknown = 不明なエラー: { $detail }
+
+## よくある失敗への対処
+hint-invalid-key = `q keys test <provider>` でキーを確認し、`q set-key <provider>` で置き換えてください。
+hint-context-length = 送るコンテキストを減らす (コンテキストのフラグを減らすか、[context] の `max_bytes` を小さくする) か、コンテキストウィンドウの大きいモデルを選んでください (`q list-models`)。
+hint-quota = プロバイダーのプランや請求を確認するか、`q set-key <provider> --add` で別のキーを追加するか、--provider でプロバイダーを切り替えてください。
+hint-model-not-found = `q list-models` でプロバイダーのモデルを確認し、`q set-model <provider> <model>` で設定してください。
+hint-rate-limit = 1 分ほど待つか、--retries を増やすか、`q set-key <provider> --add` で 2 つ目のキーを追加して交互に使ってください。
+hint-overloaded = 少し待ってから再試行するか、--provider で別のプロバイダーを使ってください。
+hint-timeout = もう一度試してください。質問を短くしたりコンテキストを減らしたりすると、遅いモデルでも間に合いやすくなります。
+hint-content-filter = 質問を言い換えるか、フィルターに引っかかった可能性のあるコンテキストを外してください。
+hint-network = ネットワーク接続とプロキシの設定 (HTTPS_PROXY、NO_PROXY) を確認してください。
+
+## ステータス表示
+no-prompt = プロンプトがありません。使い方は --help を参照してください。
+status-provider = プロバイダー: { $provider }、モデル: { $model }
+cancelled-nothing-sent = キャンセルしました。何も送信していません
+watch-started = { $files } を監視しています (Ctrl-C で終了)
+caveat-cutoff = { $model } の知識はおよそ { $cutoff } までですが、「{ $trigger }」について尋ねています。最新の情報には --web を付けてください。
+caveat-time-sensitive = 「{ $trigger }」は { $model } の学習データより新しいかもしれません。最新の情報には --web を付けてください。
+
+## clap の見出しと組み込みフラグ
+help-usage = 使い方:
+help-arguments = 引数:
+help-options = オプション:
+help-commands = コマンド:
+help-flag = ヘルプを表示
+version-flag = バージョンを表示
+
+## q のヘルプ
+help-q = LLM に質問する CLI ツール
+help-q--prompt = LLM に送るプロンプト ('-' で始まる場合は `--` の後に)
+help-q--prompt_file = プロンプトをファイルから、または `-` で標準入力から、そのまま読み込む ('-' で始まっても複数行でもよい)
+help-q--template = 設定の templates/ ディレクトリにあるプロンプトテンプレートを、プロンプトを入力として実行する
+help-q--history = シェルの履歴をコンテキストに含める
+help-q--directory = カレントディレクトリの一覧を含める
+help-q--follow_links = ディレクトリ一覧でシンボリックリンクをたどる (ディレクトリの外へのリンクはたどらない)
+help-q--file = ファイルの内容を含める (複数指定可)
+help-q--log = ログファイルの末尾か最近の範囲を含める: PATH、PATH:200、PATH:since=10m
+help-q--exec = シェルコマンドを実行し、その出力 (標準出力と標準エラー) と終了ステータスを含める
+help-q--no_stdin = パイプされた標準入力をコンテキストとして (プロンプトがないときはプロンプトとして) 読まない
+help-q--man = コマンドのインストール済み man ページ (または --help の出力) を含める
+help-q--outline = パス以下のモジュール、型、関数シグネチャの概要を含める
+help-q--repo = git の概要 (ブランチ、最近のコミット、未コミットの変更、リモート) を含める
+help-q--cargo = Cargo パッケージ (フィーチャー、ロックされたバージョン付きの依存関係、ワークスペース) を含める
+help-q--docker = 実行中のコンテナ (docker ps) と、このディレクトリの Compose ファイルと Dockerfile を含める
+help-q--container = --docker と一緒に使い、このコンテナのログの末尾も含める
+help-q--system = システム情報 (OS、CPU、メモリ、ディスク、負荷、上位プロセス) を含める
+help-q--diff = 未コミットの git diff (ステージ済みと未ステージ) を含める
+help-q--ctx = 複数の種類のコンテキストをまとめて含める (例: --ctx hist,here,diff)
+help-q--audio = 音声ファイルを文字起こしし、プロンプトとして (プロンプトがあればコンテキストとして) 使う
+help-q--web = ウェブを検索して出典を示す (組み込みの検索か [web] の検索 API)
+help-q--speak = 回答を読み上げる (OpenAI の音声合成)
+help-q--speech_file = --speak の音声を再生せずに MP3 として FILE に保存する
+help-q--output = 回答を届いたそばから FILE にも書き込む。サブコマンドのときは標準出力の代わりにそこへ書き込む
+help-q--json = 標準出力を最後に 1 つの JSON オブジェクトとして出力する: {"{"}"answers": [...], "output": "..."{"}"}
+help-q--copy = 回答が揃ったらクリップボードにコピーする
+help-q--quiet = 音声だけを出力し、回答やステータス行を表示しない
+help-q--cmd_suggest = コマンドの候補を表示する
+help-q--why = --cmd と一緒に使い、各候補がクエリにどう一致したかを表示する
+help-q--all_examples = --cmd と一緒に使い、自分の環境だけでなくすべてのシェルと OS の例を表示する
+help-q--stream = 回答をトークンごとにストリーミングする
+help-q--n = 複数の回答を A、B、C … のラベル付きで生成する (ストリーミングなし)
+help-q--pick = --n と一緒に使い、回答を対話的に 1 つ選ぶ (選んだものだけを標準出力へ)
+help-q--consensus = [consensus] のモデルに質問し、審査役のモデルに回答をまとめさせて食い違いを示す
+help-q--runbook = 確認コマンド付きの番号付き手順で回答し、1 つずつ実行するか尋ねる
+help-q--temperature = サンプリングの温度 (0.0〜2.0) [既定: テンプレート、プリセット、設定の値]
+help-q--max_tokens = 回答の最大トークン数 [既定: テンプレートかプリセットの値、なければプロバイダーの既定]
+help-q--seed = このシードと温度 0 でサンプリングし、対応するプロバイダー (OpenAI、Gemini、Mistral、Groq) で同じ回答を再現する
+help-q--review_context = 送信前にコンテキストのブロック (サイズとプレビュー) を一覧し、送るものを選ぶ
+help-q--dry_run = 何も送らずに、プロンプトのサイズ、モデルの料金、見込みのコストを表示する (--verbose でプロンプトも表示)
+help-q--no_follow_up = 回答の後に追加の質問のための `>` プロンプトを出さない
+help-q--incognito = ディスクに痕跡を残さない: 履歴ログと起動時の整理を行わない
+help-q--no_cache = 回答のキャッシュを使わない
+help-q--no_defaults = 設定ファイルの [defaults] セクションを無視する
+help-q--max_retries = 再試行の最大回数
+help-q--debug = デバッグ情報を表示する
+help-q--stats = 回答の後に、モデル、所要時間、プロバイダーのリクエスト ID、システムフィンガープリントを表示する
+help-q--verbose = 詳細な出力を表示する
+help-q--provider = LLM プロバイダーを選ぶ (openai、gemini、openrouter、groq、mistral) [既定: 設定の値]
+help-q--profile = 設定の名前付きプロファイル (キー、既定のプロバイダー、モデル) を使う
+help-q--model = モデル名を選ぶ (例: gemini-pro、gpt-3.5-turbo)
+help-q--verbosity = 回答の詳しさを選ぶ
+
+help-q-set-key = LLM サービスの API キーを設定する
+help-q-set-key--provider = LLM プロバイダー (openai、gemini、openrouter、groq、mistral)
+help-q-set-key--key = 設定する API キー (省略すると伏せ字で入力を求める)
+help-q-set-key--from_stdin = API キーを標準入力から読む
+help-q-set-key--from_file = API キーをファイルから読む
+help-q-set-key--no_verify = 保存前にプロバイダーでキーを確認しない
+help-q-set-key--add = 置き換えずに、プロバイダーのローテーション用のキーとして追加する
+help-q-keys = 設定済みの API キーを一覧、削除、テストする
+help-q-keys-list = 設定済みのプロバイダーを、伏せたキーとその出どころ付きで表示する
+help-q-keys-remove = プロバイダーの保存済みキーを削除する
+help-q-keys-remove--provider = LLM プロバイダー (openai、gemini、openrouter、groq、mistral)
+help-q-keys-test = プロバイダーのキーを実際の API で確認する
+help-q-keys-test--provider = LLM プロバイダー (openai、gemini、openrouter、groq、mistral)
+help-q-config = 設定ファイルを確認、検査する
+help-q-config-validate = config.toml に未知のキー、型の誤り、知らないモデルがないか検査する
+help-q-config-get = 設定値を表示する (例: temperature、defaults.stream)
+help-q-config-get--key = ドット区切りのキー。[defaults] と [profiles] 以外は [settings] から読む
+help-q-config-set = 設定値を変更する (例: `q config set temperature 0.2`)
+help-q-config-set--key = ドット区切りのキー。[defaults] と [profiles] 以外は [settings] に書く
+help-q-config-set--value = 新しい値 (TOML のリテラルか、ただの文字列)
+help-q-config-export = 共有できる設定 (プリセット、既定値、プロファイルなど) を API キーなしで書き出す
+help-q-config-export--bundle = 書き出すファイル (省略すると表示する)
+help-q-config-import = `q config export` で書き出した設定をこの設定にマージする
+help-q-config-import--file = 読み込むファイル
+help-q-profile = 名前付きプロファイルを管理する
+help-q-profile-list = プロファイルを一覧し、使用中のものに印を付ける
+help-q-profile-use = プロファイルを既定にする (トップレベルの設定に戻すには "none")
+help-q-profile-use--name = プロファイル名
+help-q-set-provider = 既定の LLM プロバイダーを設定する
+help-q-set-provider--provider = LLM プロバイダー (openai、gemini、openrouter、groq、mistral)
+help-q-set-model = LLM プロバイダーのモデルを設定する
+help-q-set-model--provider = LLM プロバイダー (openai、gemini、openrouter、groq、mistral)
+help-q-set-model--model = 設定するモデル名
+help-q-list-models = プロバイダーが提供するモデルを一覧する (-P でプロバイダーを選ぶ)
+help-q-list-models--search = ID にこの文字列を含むモデルだけを表示する
+help-q-list-models--page = 表示するページ
+help-q-list-models--per_page = 1 ページあたりのモデル数
+help-q-pr = 現在のブランチのプルリクエストのタイトルと説明を書く
+help-q-pr--base = 比較先のブランチ [既定: リモートの既定ブランチ]
+help-q-pr--create = `gh pr create` で PR を作成する
+help-q-commit = ステージ済みの変更に Conventional Commits 形式のヘッダーを書く
+help-q-commit--kind = コミットの種類 (例: feat、fix)
+help-q-commit--scope = コミットのスコープ (例: api。[commit] の scopes があればその中から)
+help-q-commit--breaking = 破壊的変更として印を付ける (`type!:`)
+help-q-branch-name = イシューのタイトルか未コミットの変更からブランチ名を付ける
+help-q-branch-name--title = イシューのタイトル [既定: 未コミットの diff から考える]
+help-q-changelog = コミットを Keep a Changelog 形式の項目にまとめる
+help-q-changelog--range = コミットの範囲 (例: v1.2.0..HEAD) [既定: 最新のタグから HEAD まで]
+help-q-changelog--diff = 範囲の diff も送る (遅いがより正確)
+help-q-what-changed = diff やコミット範囲を領域ごとに要約し、確認すべきリスクを挙げる
+help-q-what-changed--range = main..feature のようなコミット範囲か 1 つのコミット [既定: 未コミットの変更]
+help-q-fix-build = ビルドを実行し、エラーとその箇所のソースを添えて修正案を求める
+help-q-fix-build--patch = `git apply` 用の unified diff だけを出力する
+help-q-fix-build--command = ビルドのコマンド [既定: cargo build]
+help-q-fix-test = テストを実行し、失敗の原因の見当と修正案を求める
+help-q-fix-test--filter = 名前にこの文字列を含むテストだけを実行する (既定の `cargo test` のとき)
+help-q-fix-test--patch = `git apply` 用の unified diff だけを出力する
+help-q-fix-test--command = テストのコマンド [既定: cargo test]
+help-q-explain = シェルのコマンドを、インストール済みの man ページを使ってフラグごとに説明する
+help-q-explain--command = コマンドライン (例: tar -xzvf backup.tgz)
+help-q-sql = スキーマファイルに基づいて SQL クエリを 1 つ書く
+help-q-sql--question = クエリが返すもの (例: "月間アクティブユーザー")
+help-q-sql--schema = スキーマの DDL (例: pg_dump --schema-only の出力)
+help-q-sql--dialect = SQL の方言 (例: postgres、mysql、sqlite)
+help-q-jq = サンプル入力で確かめた jq フィルターを 1 つ書く
+help-q-jq--question = フィルターがすること (例: "status でグループ化")
+help-q-jq--sample = フィルターを適用する JSON の入力
+help-q-regex = 正規表現を書き、テストケースで確かめる
+help-q-regex--description = パターンが一致するもの (例: "時刻を含まない ISO 形式の日付")
+help-q-regex--test = テストケース (1 行に 1 つ。`+ text` は一致すべきもの、`- text` は一致してはいけないもの)
+help-q-regex--attempts = ケースが通らないときに諦めるまでの試行回数
+help-q-cron = ふつうの言葉で書いたスケジュールから crontab の行 (と systemd の OnCalendar 式) を書く
+help-q-cron--when = スケジュール (例: "平日の午前 7 時")
+help-q-cron--systemd = systemd タイマーの OnCalendar 式も出す
+help-q-cron--command = crontab の行に入れるコマンド
+help-q-alias = エイリアス (`q <alias>` で実行する名前付きの呼び出し) を保存、一覧、削除する
+help-q-alias-add = `--` の後の引数をエイリアスとして保存する (例: `q alias add rev -- --diff "この変更をレビューして"`)
+help-q-alias-add--name = エイリアス名 (`q <name>` で実行する)
+help-q-alias-add--args = エイリアスが表すフラグとプロンプト
+help-q-alias-list = 保存したエイリアスと展開先を表示する
+help-q-alias-remove = エイリアスを削除する
+help-q-ask = 設定の [presets] にあるプリセットを実行する (名前がなければ一覧する)
+help-q-ask--preset = プリセット名 (例: sql)
+help-q-ask--input = プリセットへの入力
+help-q-template = -t で実行するプロンプトテンプレートをインストール、一覧、表示する
+help-q-template-install = URL、gist、ファイルからテンプレートを取得し、チェックサムを確認してからインストールする
+help-q-template-install--source = http(s) の URL、gist (gist:ID かその URL)、またはパス
+help-q-template-install--name = ファイル名の代わりにこの名前でインストールする
+help-q-template-install--sha256 = SHA-256 チェックサムがこれと一致すれば確認なしでインストールし、一致しなければ拒否する
+help-q-template-install--force = 同じ名前のインストール済みテンプレートを置き換える
+help-q-template-list = インストール済みのテンプレートと説明を表示する
+help-q-template-show = テンプレートのファイルを表示する
+help-q-eval = 評価ファイルのプロンプトを 2 つの構成で実行し、回答を比べる
+help-q-eval--file = [a]、[b]、任意の rubric、[[case]] のプロンプトを書いた TOML ファイル
+help-q-compare = 2 つのモデルに同じ質問をし、回答を並べて表示する
+help-q-compare--prompt = プロンプト
+help-q-compare--models = 2 つのモデル (それぞれ `model` か `provider/model`。例: openai/gpt-4o,gemini/gemini-2.5-flash)
+help-q-compare--diff = 2 列の代わりに、回答の単語単位の diff を 1 つ表示する
+help-q-watch = 監視するファイルが変わるたびにクエリを再実行する
+help-q-watch--prompt = プロンプト
+help-q-watch--files = 監視するファイル (コンテキストにも含める。複数指定可)
+help-q-watch--debounce = 再実行までに変更が止むのを待つミリ秒数 (続けて保存しても 1 回だけ実行する)
+help-q-feedback = 直前の回答を評価する (👍 か 👎。`q history stats` 用)
+help-q-feedback--rating = 👍 か 👎 (または up/down)
+help-q-feedback--comment = 理由をひとこと
+help-q-history = クエリの履歴ログを要約する
+help-q-history-stats = モデルごと、プリセットごとのクエリ数と 👍/👎 の評価
+help-q-history-encrypt = `[history] encrypt` を有効にする前に書かれた記録を暗号化する
+help-q-gc = 履歴、セッション、キャッシュを [retention] の上限まで整理する
+help-q-commands = --cmd の候補に使うコマンドデータベースを一覧する
+help-q-commands-list = インストール済みのデータベースのバージョンと、収録コマンドを表示する
+help-q-self-update = q を GitHub の最新リリースに更新する
+help-q-self-update--check = 更新があるかどうかだけを表示する
@@ -0,0 +1,74 @@
+//! A check for prompts that ask about things newer than a model can know.
+//! After such a prompt q notes the model's knowledge cutoff (from the table
+//! in `pricing`) and suggests `--web`; a model missing there gets a caveat
+//! without a date.
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+/// The year and month a model's training data ends
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cutoff {
+    pub year: i32,
+    pub month: u32,
+}
+
+impl std::fmt::Display for Cutoff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}-{:02}", self.year, self.month)
+    }
+}
+
+/// Phrases that ask about recent events, matched whole and case-insensitively;
+/// "latest" and "newest" count before a release word, a word or two apart.
+/// Words like "current" or "today" alone are left out: "the current
+/// directory" and "today's date format" aren't about the news.
+const TIME_WORDS: &[&str] = &[
+    r"(latest|newest)(\s+[\w.-]+){0,2}?\s+(version|release|update)s?",
+    "news", "just released", "this year", "this month", "this week",
+];
+/// The same in Japanese, which has no spaces to match whole words by
+const JAPANESE_TIME_WORDS: &[&str] = &["最新版", "最新バージョン", "最新リリース", "ニュース", "今年"];
+
+lazy_static! {
+    static ref TIME_WORD: Regex = Regex::new(&format!(r"(?i)\b({})\b", TIME_WORDS.join("|").replace(' ', r"\s+"))).expect("valid regex");
+    static ref YEAR: Regex = Regex::new(r"\b(19|20)\d\d\b").expect("valid regex");
+}
+
+/// What makes `prompt` look like it needs newer knowledge than `cutoff`:
+/// a phrase like "latest version", or a year from the cutoff on (from last year on
+/// when the cutoff isn't known)
+pub fn time_sensitive(prompt: &str, cutoff: Option<Cutoff>, this_year: i32) -> Option<String> {
+    if let Some(word) = TIME_WORD.find(prompt) {
+        return Some(word.as_str().to_string());
+    }
+    if let Some(word) = JAPANESE_TIME_WORDS.iter().find(|word| prompt.contains(*word)) {
+        return Some(word.to_string());
+    }
+    let recent = cutoff.map_or(this_year - 1, |cutoff| cutoff.year);
+    YEAR.find_iter(prompt)
+        .find(|year| year.as_str().parse::<i32>().is_ok_and(|year| year >= recent))
+        .map(|year| year.as_str().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_time_sensitive() {
+        let cutoff = Some(Cutoff { year: 2023, month: 10 });
+        assert_eq!(time_sensitive("What's the LATEST version of tokio?", cutoff, 2026).as_deref(), Some("LATEST version"));
+        assert_eq!(time_sensitive("Rust の最新バージョンは？", cutoff, 2026).as_deref(), Some("最新バージョン"));
+        assert_eq!(time_sensitive("Any news on the EU AI Act?", cutoff, 2026).as_deref(), Some("news"));
+        // Everyday uses of "current" and "today" aren't about events
+        assert_eq!(time_sensitive("List the files in the current directory", cutoff, 2026), None);
+        assert_eq!(time_sensitive("Format today's date as ISO 8601 in bash", cutoff, 2026), None);
+        assert_eq!(time_sensitive("What changed recently in this diff?", cutoff, 2026), None);
+        assert_eq!(time_sensitive("What changed in Rust in 2024?", cutoff, 2026).as_deref(), Some("2024"));
+        assert_eq!(time_sensitive("What changed in Rust in 2021?", cutoff, 2026), None);
+        assert_eq!(time_sensitive("What changed in Rust in 2024?", None, 2026), None);
+        // Parts of other words and numbers don't count
+        assert_eq!(time_sensitive("Explain concurrency and port 20250", cutoff, 2026), None);
+    }
+}
@@ -4,72 +4,253 @@ use futures::StreamExt;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 
-use super::{ApiError, ApiResult, LLMApi, ModelConfig, StreamingResponse};
+use super::{append_citations, audio_mime_type, format_citations, ApiError, LastRequestId, LastRetryAfter, ApiResult, Citation, LLMApi, ModelConfig, ModelInfo, StreamingResponse};
 use crate::cli::args::Verbosity;
 
-const DEFAULT_API_URL: &str = "https://generativelanguage.googleapis.com/v1beta/models/gemini-pro:generateContent";
+const API_BASE_URL: &str = "https://generativelanguage.googleapis.com/v1beta/models";
 const DEFAULT_MODEL: &str = "gemini-2.0-flash";
 const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
 
 pub struct GeminiClient {
     client: Client,
-    api_key: String,
+    auth: Auth,
     api_url: String,
     model: String,
     config: ModelConfig,
     verbosity: Verbosity,
+    request_id: LastRequestId,
+    retry_after: LastRetryAfter,
+}
+
+/// How requests are authorized: a Generative Language API key, or an OAuth
+/// access token for Vertex AI
+enum Auth {
+    ApiKey(String),
+    Bearer(String),
 }
 
 #[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
 struct GeminiRequest {
     contents: Vec<Content>,
+    generation_config: GenerationConfig,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tools: Vec<Tool>,
+}
+
+/// Grounding with Google Search, for `--web`
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Tool {
+    google_search: GoogleSearch,
+}
+
+#[derive(Debug, Serialize)]
+struct GoogleSearch {}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GenerationConfig {
+    temperature: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_output_tokens: Option<u32>,
+    /// Gemini takes a 32-bit seed, so larger `--seed` values wrap
     #[serde(skip_serializing_if = "Option::is_none")]
-    max_tokens: Option<u32>,
+    seed: Option<i32>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 struct Content {
+    #[serde(default)]
     parts: Vec<Part>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 struct Part {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     text: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    inline_data: Option<InlineData>,
 }
 
-impl Default for Part {
-    fn default() -> Self {
-        Self {
-            text: String::new(),
-        }
-    }
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct InlineData {
+    mime_type: String,
+    /// Base64-encoded bytes
+    data: String,
 }
 
 #[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
 struct GeminiResponse {
+    #[serde(default)]
     candidates: Vec<Candidate>,
+    #[serde(default)]
+    prompt_feedback: Option<PromptFeedback>,
+}
+
+impl GeminiResponse {
+    fn candidate(&self) -> ApiResult<&Candidate> {
+        let candidate = self.candidates.first().ok_or_else(|| {
+            blocked_prompt(&self.prompt_feedback)
+                .unwrap_or_else(|| ApiError::Other("No response candidates".to_string()))
+        })?;
+        match blocked_answer(candidate.finish_reason.as_deref()) {
+            Some(error) if candidate.content.parts.is_empty() => Err(error),
+            _ => Ok(candidate),
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
 struct Candidate {
+    #[serde(default)]
     content: Content,
+    #[serde(default)]
+    finish_reason: Option<String>,
+    #[serde(default)]
+    grounding_metadata: Option<GroundingMetadata>,
+}
+
+/// Set instead of candidates when the prompt itself was blocked
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PromptFeedback {
+    #[serde(default)]
+    block_reason: Option<String>,
+}
+
+fn blocked_prompt(feedback: &Option<PromptFeedback>) -> Option<ApiError> {
+    let reason = feedback.as_ref()?.block_reason.as_deref()?;
+    Some(ApiError::ContentFiltered(format!("the prompt was blocked ({})", reason)))
+}
+
+/// Finish reasons that mean the safety filters stopped the answer
+fn blocked_answer(finish_reason: Option<&str>) -> Option<ApiError> {
+    let reason = finish_reason.filter(|reason| matches!(*reason, "SAFETY" | "PROHIBITED_CONTENT" | "BLOCKLIST" | "SPII"))?;
+    Some(ApiError::ContentFiltered(format!("the answer was blocked ({})", reason)))
+}
+
+/// Sources from Google Search grounding, and which parts of the text they support
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GroundingMetadata {
+    #[serde(default)]
+    grounding_chunks: Vec<GroundingChunk>,
+    #[serde(default)]
+    grounding_supports: Vec<GroundingSupport>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GroundingChunk {
+    #[serde(default)]
+    web: Option<WebSource>,
+}
+
+#[derive(Debug, Deserialize)]
+struct WebSource {
+    uri: String,
+    #[serde(default)]
+    title: String,
 }
 
 #[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GroundingSupport {
+    segment: Segment,
+    #[serde(default)]
+    grounding_chunk_indices: Vec<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Segment {
+    #[serde(default)]
+    part_index: usize,
+    /// Byte offset into the part's text
+    #[serde(default)]
+    end_index: usize,
+}
+
+impl GroundingMetadata {
+    fn citations(&self) -> Vec<Citation> {
+        self.grounding_chunks.iter()
+            .filter_map(|chunk| chunk.web.as_ref())
+            .map(|web| Citation { title: web.title.clone(), url: web.uri.clone() })
+            .collect()
+    }
+
+    /// Mark each supported segment of a part with its footnote numbers (`[1][3]`)
+    fn mark_part(&self, part_index: usize, text: &str) -> String {
+        let mut marks: Vec<(usize, String)> = self.grounding_supports.iter()
+            .filter(|support| support.segment.part_index == part_index)
+            .filter(|support| support.segment.end_index <= text.len() && text.is_char_boundary(support.segment.end_index))
+            .map(|support| {
+                let numbers: String = support.grounding_chunk_indices.iter()
+                    .filter(|&&i| i < self.grounding_chunks.len())
+                    .map(|i| format!("[{}]", i + 1))
+                    .collect();
+                (support.segment.end_index, numbers)
+            })
+            .collect();
+        marks.sort_by_key(|(end, _)| std::cmp::Reverse(*end));
+
+        let mut marked = text.to_string();
+        for (end, numbers) in marks {
+            marked.insert_str(end, &numbers);
+        }
+        marked
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
 struct StreamResponse {
+    #[serde(default)]
     candidates: Vec<StreamCandidate>,
+    #[serde(default)]
+    prompt_feedback: Option<PromptFeedback>,
 }
 
 #[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
 struct StreamCandidate {
+    #[serde(default)]
     content: StreamContent,
+    #[serde(default)]
+    finish_reason: Option<String>,
+    #[serde(default)]
+    grounding_metadata: Option<GroundingMetadata>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize)]
 struct StreamContent {
+    #[serde(default)]
     parts: Vec<Part>,
 }
 
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ModelList {
+    #[serde(default)]
+    models: Vec<ModelEntry>,
+    #[serde(default)]
+    next_page_token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ModelEntry {
+    name: String,
+    #[serde(default)]
+    input_token_limit: Option<u64>,
+    #[serde(default)]
+    supported_generation_methods: Vec<String>,
+}
+
 #[derive(Debug, Deserialize)]
 struct ErrorResponse {
     error: ErrorDetail,
@@ -78,11 +259,17 @@ struct ErrorResponse {
 #[derive(Debug, Deserialize)]
 struct ErrorDetail {
     message: String,
+    /// The gRPC status name, e.g. `RESOURCE_EXHAUSTED`
+    #[serde(default)]
+    status: String,
 }
 
 pub struct GeminiClientBuilder {
     api_key: String,
-    api_url: String,
+    /// Full endpoint override; derived from the model when unset
+    api_url: Option<String>,
+    /// Vertex AI project and location, when using Vertex instead of the Generative Language API
+    vertex: Option<(String, String)>,
     model: String,
     config: ModelConfig,
     verbosity: Verbosity,
@@ -92,7 +279,8 @@ impl GeminiClientBuilder {
     pub fn new(api_key: String) -> Self {
         Self {
             api_key,
-            api_url: DEFAULT_API_URL.to_string(),
+            api_url: None,
+            vertex: None,
             model: DEFAULT_MODEL.to_string(),
             config: ModelConfig::default(),
             verbosity: Verbosity::default(),
@@ -100,7 +288,7 @@ impl GeminiClientBuilder {
     }
 
     pub fn with_api_url(mut self, url: String) -> Self {
-        self.api_url = url;
+        self.api_url = Some(url);
         self
     }
 
@@ -119,20 +307,43 @@ impl GeminiClientBuilder {
         self
     }
 
-    pub fn build(self) -> GeminiClient {
+    /// Send requests to Vertex AI in `project`/`location`. The credential given
+    /// to `builder` is then an OAuth access token (see `api::vertex`).
+    pub fn with_vertex(mut self, project: String, location: String) -> Self {
+        self.vertex = Some((project, location));
+        self
+    }
+
+    /// Fails when the HTTP client can't be set up
+    pub fn build(self) -> ApiResult<GeminiClient> {
         let client = Client::builder()
             .timeout(DEFAULT_TIMEOUT)
             .build()
-            .expect("Failed to create HTTP client");
-
-        GeminiClient {
+            .map_err(|e| ApiError::Other(format!("Failed to create HTTP client: {}", e)))?;
+
+        let base_url = match &self.vertex {
+            Some((project, location)) => format!(
+                "https://{location}-aiplatform.googleapis.com/v1/projects/{project}/locations/{location}/publishers/google/models"
+            ),
+            None => API_BASE_URL.to_string(),
+        };
+        let api_url = self.api_url
+            .unwrap_or_else(|| format!("{}/{}:generateContent", base_url, self.model));
+        let auth = match self.vertex {
+            Some(_) => Auth::Bearer(self.api_key),
+            None => Auth::ApiKey(self.api_key),
+        };
+
+        Ok(GeminiClient {
             client,
-            api_key: self.api_key,
-            api_url: self.api_url,
+            auth,
+            api_url,
             model: self.model,
             config: self.config,
             verbosity: self.verbosity,
-        }
+            request_id: LastRequestId::default(),
+            retry_after: LastRetryAfter::default(),
+        })
     }
 }
 
@@ -141,12 +352,13 @@ impl GeminiClient {
         GeminiClientBuilder::new(api_key)
     }
 
-    fn get_system_prompt(&self) -> &str {
-        match self.verbosity {
+    fn get_system_prompt(&self) -> String {
+        let instruction = match self.verbosity {
             Verbosity::Concise => "Be concise and to the point. Provide only essential information without unnecessary details or explanations.",
             Verbosity::Normal => "Provide balanced responses with moderate detail.",
             Verbosity::Detailed => "Provide detailed and comprehensive responses with thorough explanations and examples where appropriate.",
-        }
+        };
+        self.config.system_prompt(instruction)
     }
 
     fn build_request(&self, prompt: &str) -> GeminiRequest {
@@ -157,9 +369,42 @@ impl GeminiClient {
             contents: vec![Content {
                 parts: vec![Part {
                     text: combined_prompt,
+                    ..Part::default()
                 }],
             }],
-            max_tokens: self.config.max_tokens,
+            generation_config: GenerationConfig {
+                temperature: self.config.temperature,
+                max_output_tokens: self.config.max_tokens,
+                seed: self.config.seed.map(|seed| seed as i32),
+            },
+            tools: if self.config.web_search {
+                vec![Tool { google_search: GoogleSearch {} }]
+            } else {
+                Vec::new()
+            },
+        }
+    }
+
+    /// Map a failed HTTP response to an `ApiError` from its status and the
+    /// gRPC status name in the body
+    async fn error_from_response(response: reqwest::Response) -> ApiError {
+        let status = response.status().as_u16();
+        let error_text = response.text().await.unwrap_or_default();
+        // Gemini reports bad keys as 400 INVALID_ARGUMENT, naming the reason in `details`
+        if error_text.contains("API_KEY_INVALID") {
+            return ApiError::InvalidKey;
+        }
+        match serde_json::from_str::<ErrorResponse>(&error_text) {
+            Ok(error) => ApiError::classify(status, &error.error.status, error.error.message),
+            Err(_) => ApiError::classify(status, "", error_text),
+        }
+    }
+
+    fn authorize(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match &self.auth {
+            // A header rather than `?key=`, which would end up in logged URLs
+            Auth::ApiKey(key) => request.header("x-goog-api-key", key),
+            Auth::Bearer(token) => request.bearer_auth(token),
         }
     }
 
@@ -167,21 +412,45 @@ impl GeminiClient {
         self.api_url.clone()
     }
 
-    fn process_stream_chunk(chunk: &[u8]) -> ApiResult<Option<String>> {
+    /// The model collection that the configured `.../models/<model>:generateContent` URL belongs to
+    fn models_url(&self) -> String {
+        match self.api_url.rsplit_once('/') {
+            Some((base, _)) => base.to_string(),
+            None => API_BASE_URL.to_string(),
+        }
+    }
+
+    /// The answer text in one chunk of a streamed response; `None` when it
+    /// carries no text
+    pub fn process_stream_chunk(chunk: &[u8]) -> ApiResult<Option<String>> {
         let text = String::from_utf8_lossy(chunk);
         
         // Check for error response
         if let Ok(error) = serde_json::from_str::<ErrorResponse>(&text) {
-            return Err(ApiError::Other(error.error.message));
+            return Err(ApiError::classify(200, &error.error.status, error.error.message));
         }
 
         // Try to parse as stream response
         if let Ok(response) = serde_json::from_str::<StreamResponse>(&text) {
+            if let Some(error) = blocked_prompt(&response.prompt_feedback) {
+                return Err(error);
+            }
             if let Some(candidate) = response.candidates.first() {
-                let content = candidate.content.parts.iter()
+                if let Some(error) = blocked_answer(candidate.finish_reason.as_deref()) {
+                    return Err(error);
+                }
+                let mut content = candidate.content.parts.iter()
                     .map(|part| part.text.as_str())
                     .collect::<Vec<_>>()
                     .join(" ");
+                // Grounding arrives with the last chunk; its sources follow the text
+                let citations = candidate.grounding_metadata.as_ref()
+                    .map(GroundingMetadata::citations)
+                    .unwrap_or_default();
+                if !citations.is_empty() {
+                    content.push_str("\n\n");
+                    content.push_str(&format_citations(&citations));
+                }
                 if !content.is_empty() {
                     return Ok(Some(content));
                 }
@@ -198,22 +467,28 @@ impl LLMApi for GeminiClient {
         &self.model
     }
 
+    fn last_request_id(&self) -> Option<String> {
+        self.request_id.get()
+    }
+
+    fn last_retry_after(&self) -> Option<Duration> {
+        self.retry_after.get()
+    }
+
     async fn send_query(&self, prompt: &str) -> ApiResult<String> {
         let request = self.build_request(prompt);
         let url = self.get_api_url();
         
-        let response = self.client
-            .post(&url)
+        let response = self.authorize(self.client.post(&url))
             .json(&request)
-            .query(&[("key", self.api_key.clone())])
             .send()
             .await
-            .map_err(ApiError::Network)?;
+            .map_err(ApiError::from_transport)?;
+        self.request_id.record(&response);
+        self.retry_after.record(&response);
 
         if !response.status().is_success() {
-            let error_text = response.text().await.unwrap_or_default();
-            eprintln!("Gemini API error response: {}", error_text);
-            return Err(ApiError::Other(error_text));
+            return Err(Self::error_from_response(response).await);
         }
 
         let gemini_response: GeminiResponse = response
@@ -221,43 +496,44 @@ impl LLMApi for GeminiClient {
             .await
             .map_err(|e| ApiError::Other(format!("Failed to parse response: {}", e)))?;
 
-        let content = gemini_response
-            .candidates
-            .first()
-            .ok_or_else(|| ApiError::Other("No response candidates".to_string()))?
-            .content
+        let candidate = gemini_response.candidate()?;
+        let grounding = candidate.grounding_metadata.as_ref();
+        let content = candidate.content
             .parts
             .iter()
-            .map(|part| part.text.as_str())
+            .enumerate()
+            .map(|(i, part)| match grounding {
+                Some(grounding) => grounding.mark_part(i, &part.text),
+                None => part.text.clone(),
+            })
             .collect::<Vec<_>>()
             .join(" ");
 
-        Ok(content)
+        let citations = grounding.map(GroundingMetadata::citations).unwrap_or_default();
+        Ok(append_citations(&content, &citations))
     }
 
     async fn send_streaming_query(&self, prompt: &str) -> ApiResult<StreamingResponse> {
         let request = self.build_request(prompt);
         let url = self.get_api_url();
 
-        let response = self.client
-            .post(&url)
+        let response = self.authorize(self.client.post(&url))
             .json(&request)
-            .query(&[("key", self.api_key.clone())])
             .send()
             .await
-            .map_err(ApiError::Network)?;
+            .map_err(ApiError::from_transport)?;
+        self.request_id.record(&response);
+        self.retry_after.record(&response);
 
         if !response.status().is_success() {
-            let error_text = response.text().await.unwrap_or_default();
-            eprintln!("Gemini API error response (streaming): {}", error_text);
-            return Err(ApiError::Other(error_text));
+            return Err(Self::error_from_response(response).await);
         }
 
         let stream = response
             .bytes_stream()
             .map(|result| {
                 result
-                    .map_err(ApiError::Network)
+                    .map_err(ApiError::from_transport)
                     .and_then(|bytes| {
                         Self::process_stream_chunk(&bytes)
                             .map(|opt_content| opt_content.unwrap_or_default())
@@ -278,24 +554,109 @@ impl LLMApi for GeminiClient {
         let request = self.build_request("test");
         let url = self.get_api_url();
         
-        let response = self.client
-            .post(&url)
+        let response = self.authorize(self.client.post(&url))
             .json(&request)
-            .query(&[("key", self.api_key.clone())])
             .send()
             .await
-            .map_err(ApiError::Network)?;
-
-        match response.status().as_u16() {
-            200 => Ok(()),
-            401 => Err(ApiError::InvalidKey),
-            429 => Err(ApiError::RateLimit),
-            _ => {
-                let error_text = response.text().await.unwrap_or_default();
-                eprintln!("Gemini API error response: {}", error_text);
-                Err(ApiError::Other(error_text))
+            .map_err(ApiError::from_transport)?;
+
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(Self::error_from_response(response).await)
+        }
+    }
+
+    /// Gemini models accept audio directly, so transcription is a prompt with the audio inline
+    async fn transcribe(&self, audio: &[u8], file_name: &str) -> ApiResult<String> {
+        use base64::Engine;
+
+        let mime_type = audio_mime_type(file_name)
+            .ok_or_else(|| ApiError::Other(format!("Unsupported audio format: {}", file_name)))?;
+        let request = GeminiRequest {
+            contents: vec![Content {
+                parts: vec![
+                    Part {
+                        text: "Transcribe this audio verbatim. Reply with the transcript only.".to_string(),
+                        ..Part::default()
+                    },
+                    Part {
+                        inline_data: Some(InlineData {
+                            mime_type: mime_type.to_string(),
+                            data: base64::engine::general_purpose::STANDARD.encode(audio),
+                        }),
+                        ..Part::default()
+                    },
+                ],
+            }],
+            generation_config: GenerationConfig { temperature: 0.0, max_output_tokens: None, seed: None },
+            tools: Vec::new(),
+        };
+
+        let response = self.authorize(self.client.post(self.get_api_url()))
+            .json(&request)
+            .timeout(Duration::from_secs(300))
+            .send()
+            .await
+            .map_err(ApiError::from_transport)?;
+
+        if !response.status().is_success() {
+            return Err(Self::error_from_response(response).await);
+        }
+
+        let gemini_response: GeminiResponse = response
+            .json()
+            .await
+            .map_err(|e| ApiError::Other(format!("Failed to parse response: {}", e)))?;
+        let candidate = gemini_response.candidate()?;
+        Ok(candidate.content.parts.iter().map(|part| part.text.as_str()).collect::<String>().trim().to_string())
+    }
+
+    async fn list_models(&self) -> ApiResult<Vec<ModelInfo>> {
+        if matches!(self.auth, Auth::Bearer(_)) {
+            return Err(ApiError::Other("Listing models is not supported for Vertex AI".to_string()));
+        }
+
+        let mut models = Vec::new();
+        let mut page_token: Option<String> = None;
+
+        loop {
+            let mut query = vec![("pageSize", "1000".to_string())];
+            if let Some(token) = page_token.take() {
+                query.push(("pageToken", token));
+            }
+
+            let response = self.authorize(self.client.get(self.models_url()))
+                .query(&query)
+                .send()
+                .await
+                .map_err(ApiError::from_transport)?;
+
+            if !response.status().is_success() {
+                return Err(Self::error_from_response(response).await);
+            }
+
+            let page: ModelList = response
+                .json()
+                .await
+                .map_err(|e| ApiError::Other(format!("Failed to parse model list: {}", e)))?;
+
+            // Embedding and other non-chat models can't answer queries
+            models.extend(page.models.into_iter()
+                .filter(|entry| entry.supported_generation_methods.iter().any(|m| m == "generateContent"))
+                .map(|entry| ModelInfo {
+                    id: entry.name.trim_start_matches("models/").to_string(),
+                    context_length: entry.input_token_limit,
+                    ..ModelInfo::default()
+                }));
+
+            match page.next_page_token {
+                Some(token) if !token.is_empty() => page_token = Some(token),
+                _ => break,
             }
         }
+
+        Ok(models)
     }
 }
 
@@ -303,7 +664,7 @@ impl LLMApi for GeminiClient {
 mod tests {
     use super::*;
     use wiremock::{Mock, MockServer, ResponseTemplate};
-    use wiremock::matchers::{method, path};
+    use wiremock::matchers::{body_partial_json, header, method, path};
     use serde_json::json;
 
     #[tokio::test]
@@ -311,7 +672,8 @@ mod tests {
         let mock_server = MockServer::start().await;
         
         Mock::given(method("POST"))
-            .and(path(format!("/v1beta/models/gemini-pro:generateContent")))
+            .and(path("/v1beta/models/gemini-pro:generateContent"))
+            .and(header("x-goog-api-key", "test_key"))
             .respond_with(ResponseTemplate::new(200).set_body_json(json!({
                 "candidates": [{
                     "content": {
@@ -325,8 +687,8 @@ mod tests {
             .await;
 
         let client = GeminiClient::builder("test_key".to_string())
-            .with_api_url(mock_server.uri())
-            .build();
+            .with_api_url(format!("{}/v1beta/models/gemini-pro:generateContent", mock_server.uri()))
+            .build().unwrap();
 
         let response = client.send_query("Hi").await.unwrap();
         assert_eq!(response, "Hello, world!");
@@ -337,16 +699,275 @@ mod tests {
         let mock_server = MockServer::start().await;
         
         Mock::given(method("POST"))
-            .and(path(format!("/v1beta/models/gemini-pro:generateContent")))
+            .and(path("/v1beta/models/gemini-pro:generateContent"))
             .respond_with(ResponseTemplate::new(401))
             .mount(&mock_server)
             .await;
 
         let client = GeminiClient::builder("invalid_key".to_string())
-            .with_api_url(mock_server.uri())
-            .build();
+            .with_api_url(format!("{}/v1beta/models/gemini-pro:generateContent", mock_server.uri()))
+            .build().unwrap();
+
+        let result = client.validate_key().await;
+        assert!(matches!(result, Err(ApiError::InvalidKey)));
+    }
+
+    #[tokio::test]
+    async fn test_error_status() {
+        let mock_server = MockServer::start().await;
+        let client = GeminiClient::builder("test_key".to_string())
+            .with_api_url(format!("{}/v1beta/models/gemini-pro:generateContent", mock_server.uri()))
+            .build().unwrap();
+
+        let cases = [
+            (404, json!({"error": {"code": 404, "message": "models/gemini-9 is not found for API version v1beta", "status": "NOT_FOUND"}})),
+            (400, json!({"error": {"code": 400, "message": "The input token count (2000000) exceeds the maximum number of tokens allowed (1048576).", "status": "INVALID_ARGUMENT"}})),
+            (504, json!({"error": {"code": 504, "message": "Deadline expired before operation could complete.", "status": "DEADLINE_EXCEEDED"}})),
+            (200, json!({"promptFeedback": {"blockReason": "SAFETY"}})),
+            (200, json!({"candidates": [{"finishReason": "SAFETY"}]})),
+        ];
+        let mut errors = Vec::new();
+        for (status, body) in cases {
+            mock_server.reset().await;
+            Mock::given(method("POST"))
+                .and(path("/v1beta/models/gemini-pro:generateContent"))
+                .respond_with(ResponseTemplate::new(status).set_body_json(body))
+                .mount(&mock_server)
+                .await;
+            errors.push(client.send_query("Hi").await.unwrap_err());
+        }
+
+        assert!(matches!(errors[0], ApiError::ModelNotFound(_)));
+        assert!(matches!(errors[1], ApiError::ContextLengthExceeded(_)));
+        assert!(matches!(errors[2], ApiError::Timeout));
+        assert_eq!(errors[3].to_string(), "Blocked by the provider's content filter: the prompt was blocked (SAFETY)");
+        assert!(matches!(errors[4], ApiError::ContentFiltered(_)));
+    }
+
+    #[tokio::test]
+    async fn test_validate_key_invalid_argument() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/v1beta/models/gemini-pro:generateContent"))
+            .respond_with(ResponseTemplate::new(400).set_body_json(json!({
+                "error": {
+                    "code": 400,
+                    "message": "API key not valid. Please pass a valid API key.",
+                    "status": "INVALID_ARGUMENT",
+                    "details": [{ "reason": "API_KEY_INVALID" }]
+                }
+            })))
+            .mount(&mock_server)
+            .await;
+
+        let client = GeminiClient::builder("invalid_key".to_string())
+            .with_api_url(format!("{}/v1beta/models/gemini-pro:generateContent", mock_server.uri()))
+            .build().unwrap();
 
         let result = client.validate_key().await;
         assert!(matches!(result, Err(ApiError::InvalidKey)));
     }
+
+    #[test]
+    fn test_api_url_follows_model() {
+        let client = GeminiClient::builder("test_key".to_string())
+            .with_model("gemini-1.5-pro".to_string())
+            .build().unwrap();
+        assert_eq!(
+            client.get_api_url(),
+            "https://generativelanguage.googleapis.com/v1beta/models/gemini-1.5-pro:generateContent"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_request_carries_generation_config() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/v1beta/models/gemini-pro:generateContent"))
+            .and(body_partial_json(json!({
+                "generationConfig": { "temperature": 0.25 }
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "candidates": [{ "content": { "parts": [{ "text": "ok" }] } }]
+            })))
+            .mount(&mock_server)
+            .await;
+
+        let client = GeminiClient::builder("test_key".to_string())
+            .with_api_url(format!("{}/v1beta/models/gemini-pro:generateContent", mock_server.uri()))
+            .with_config(ModelConfig { temperature: 0.25, ..ModelConfig::default() })
+            .build().unwrap();
+
+        assert_eq!(client.send_query("Hi").await.unwrap(), "ok");
+    }
+
+    #[tokio::test]
+    async fn test_web_search_adds_google_search_tool() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(body_partial_json(json!({ "tools": [{ "googleSearch": {} }] })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "candidates": [{ "content": { "parts": [{ "text": "grounded" }] } }]
+            })))
+            .mount(&mock_server)
+            .await;
+
+        let client = GeminiClient::builder("test_key".to_string())
+            .with_api_url(format!("{}/v1beta/models/gemini-pro:generateContent", mock_server.uri()))
+            .with_config(ModelConfig { web_search: true, ..ModelConfig::default() })
+            .build().unwrap();
+
+        assert_eq!(client.send_query("Hi").await.unwrap(), "grounded");
+    }
+
+    #[tokio::test]
+    async fn test_grounding_metadata_becomes_footnotes() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "candidates": [{
+                    "content": { "parts": [{ "text": "Rust 1.80 shipped. It adds LazyLock." }] },
+                    "groundingMetadata": {
+                        "groundingChunks": [
+                            { "web": { "uri": "https://blog.rust-lang.org", "title": "Rust Blog" } },
+                            { "web": { "uri": "https://doc.rust-lang.org", "title": "Docs" } }
+                        ],
+                        "groundingSupports": [
+                            { "segment": { "startIndex": 0, "endIndex": 18 }, "groundingChunkIndices": [0] },
+                            { "segment": { "startIndex": 19, "endIndex": 36 }, "groundingChunkIndices": [0, 1] }
+                        ]
+                    }
+                }]
+            })))
+            .mount(&mock_server)
+            .await;
+
+        let client = GeminiClient::builder("test_key".to_string())
+            .with_api_url(format!("{}/v1beta/models/gemini-pro:generateContent", mock_server.uri()))
+            .build().unwrap();
+
+        assert_eq!(
+            client.send_query("Hi").await.unwrap(),
+            "Rust 1.80 shipped.[1] It adds LazyLock.[1][2]\n\n\
+             [1]: https://blog.rust-lang.org \"Rust Blog\"\n\
+             [2]: https://doc.rust-lang.org \"Docs\"\n"
+        );
+    }
+
+    #[test]
+    fn test_stream_chunk_appends_grounding_sources() {
+        let chunk = json!({
+            "candidates": [{
+                "content": { "parts": [{ "text": "Done." }] },
+                "groundingMetadata": {
+                    "groundingChunks": [{ "web": { "uri": "https://example.com", "title": "Example" } }]
+                }
+            }]
+        }).to_string();
+        assert_eq!(
+            GeminiClient::process_stream_chunk(chunk.as_bytes()).unwrap().unwrap(),
+            "Done.\n\n[1]: https://example.com \"Example\"\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_list_models_follows_pages() {
+        use wiremock::matchers::query_param;
+
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path("/v1beta/models"))
+            .and(query_param("pageToken", "next"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "models": [{
+                    "name": "models/gemini-1.5-pro",
+                    "inputTokenLimit": 2000000,
+                    "supportedGenerationMethods": ["generateContent"]
+                }]
+            })))
+            .mount(&mock_server)
+            .await;
+
+        Mock::given(method("GET"))
+            .and(path("/v1beta/models"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "models": [
+                    { "name": "models/gemini-2.0-flash", "supportedGenerationMethods": ["generateContent"] },
+                    { "name": "models/text-embedding-004", "supportedGenerationMethods": ["embedContent"] }
+                ],
+                "nextPageToken": "next"
+            })))
+            .mount(&mock_server)
+            .await;
+
+        let client = GeminiClient::builder("test_key".to_string())
+            .with_api_url(format!("{}/v1beta/models/gemini-pro:generateContent", mock_server.uri()))
+            .build().unwrap();
+
+        let ids: Vec<String> = client.list_models().await.unwrap().into_iter().map(|m| m.id).collect();
+        assert_eq!(ids, vec!["gemini-2.0-flash", "gemini-1.5-pro"]);
+    }
+
+    #[tokio::test]
+    async fn test_vertex_uses_bearer_token() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/v1/projects/p/locations/l/publishers/google/models/gemini-1.5-pro:generateContent"))
+            .and(header("Authorization", "Bearer ya29.token"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "candidates": [{ "content": { "parts": [{ "text": "from vertex" }] } }]
+            })))
+            .mount(&mock_server)
+            .await;
+
+        let client = GeminiClient::builder("ya29.token".to_string())
+            .with_vertex("p".to_string(), "l".to_string())
+            .with_api_url(format!(
+                "{}/v1/projects/p/locations/l/publishers/google/models/gemini-1.5-pro:generateContent",
+                mock_server.uri()
+            ))
+            .build().unwrap();
+
+        assert_eq!(client.send_query("Hi").await.unwrap(), "from vertex");
+    }
+
+    #[test]
+    fn test_vertex_api_url() {
+        let client = GeminiClient::builder("ya29.token".to_string())
+            .with_vertex("my-project".to_string(), "europe-west4".to_string())
+            .with_model("gemini-1.5-pro".to_string())
+            .build().unwrap();
+        assert_eq!(
+            client.get_api_url(),
+            "https://europe-west4-aiplatform.googleapis.com/v1/projects/my-project/locations/europe-west4/publishers/google/models/gemini-1.5-pro:generateContent"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_transcribe_sends_inline_audio() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/v1beta/models/gemini-pro:generateContent"))
+            .and(body_partial_json(json!({
+                "contents": [{ "parts": [{}, { "inlineData": { "mimeType": "audio/mpeg", "data": "YXVkaW8=" } }] }]
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "candidates": [{ "content": { "parts": [{ "text": "hello there\n" }] } }]
+            })))
+            .mount(&mock_server)
+            .await;
+
+        let client = GeminiClient::builder("test_key".to_string())
+            .with_api_url(format!("{}/v1beta/models/gemini-pro:generateContent", mock_server.uri()))
+            .build().unwrap();
+
+        assert_eq!(client.transcribe(b"audio", "note.mp3").await.unwrap(), "hello there");
+    }
 }
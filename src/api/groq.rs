@@ -0,0 +1,46 @@
+//! Groq serves open models on low-latency hardware behind an OpenAI-compatible
+//! API, so it is an `OpenAIClient` pointed at Groq's endpoint.
+
+use super::openai::{OpenAIClient, OpenAIClientBuilder};
+
+pub const API_URL: &str = "https://api.groq.com/openai/v1/chat/completions";
+pub const DEFAULT_MODEL: &str = "llama-3.3-70b-versatile";
+pub const TRANSCRIPTION_MODEL: &str = "whisper-large-v3";
+
+/// A builder for a Groq client; the API URL and model can still be overridden
+pub fn builder(api_key: String) -> OpenAIClientBuilder {
+    OpenAIClient::builder(api_key)
+        .with_api_url(API_URL.to_string())
+        .with_model(DEFAULT_MODEL.to_string())
+        .with_transcription_model(TRANSCRIPTION_MODEL.to_string())
+        .with_multiple_choices(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{ApiError, LLMApi};
+    use serde_json::json;
+    use wiremock::matchers::{header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_invalid_key() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/openai/v1/chat/completions"))
+            .and(header("Authorization", "Bearer gsk_test"))
+            .respond_with(ResponseTemplate::new(401).set_body_json(json!({
+                "error": { "message": "Invalid API Key", "type": "invalid_request_error", "code": "invalid_api_key" }
+            })))
+            .mount(&mock_server)
+            .await;
+
+        let client = builder("gsk_test".to_string())
+            .with_api_url(format!("{}/openai/v1/chat/completions", mock_server.uri()))
+            .build().unwrap();
+
+        assert_eq!(client.model(), DEFAULT_MODEL);
+        assert!(matches!(client.send_query("Hi").await, Err(ApiError::InvalidKey)));
+    }
+}
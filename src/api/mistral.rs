@@ -0,0 +1,68 @@
+//! Mistral's chat API (hosted in the EU) follows the OpenAI request format, so it
+//! is an `OpenAIClient` pointed at Mistral's endpoint. Its flat error bodies are
+//! handled by `OpenAIClient` as well.
+
+use super::openai::{OpenAIClient, OpenAIClientBuilder};
+
+pub const API_URL: &str = "https://api.mistral.ai/v1/chat/completions";
+pub const DEFAULT_MODEL: &str = "mistral-small-latest";
+
+/// A builder for a Mistral client; the API URL and model can still be overridden
+pub fn builder(api_key: String) -> OpenAIClientBuilder {
+    OpenAIClient::builder(api_key)
+        .with_api_url(API_URL.to_string())
+        .with_model(DEFAULT_MODEL.to_string())
+        .with_random_seed(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{ApiError, LLMApi};
+    use serde_json::json;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn client(server: &MockServer) -> OpenAIClient {
+        builder("test-mistral-key".to_string())
+            .with_api_url(format!("{}/v1/chat/completions", server.uri()))
+            .build().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_flat_error_message() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .respond_with(ResponseTemplate::new(400).set_body_json(json!({
+                "object": "error",
+                "message": "Invalid model: mistral-huge",
+                "type": "invalid_model",
+                "code": "1500"
+            })))
+            .mount(&mock_server)
+            .await;
+
+        match client(&mock_server).send_query("Hi").await {
+            Err(ApiError::ModelNotFound(message)) => assert_eq!(message, "Invalid model: mistral-huge"),
+            other => panic!("unexpected result: {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_streaming() {
+        use futures::StreamExt;
+
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                "data: {\"choices\":[{\"delta\":{\"content\":\"Bonjour\"}}]}\n\ndata: [DONE]\n\n",
+            ))
+            .mount(&mock_server)
+            .await;
+
+        let mut stream = client(&mock_server).send_streaming_query("Hi").await.unwrap();
+        assert_eq!(stream.next().await.unwrap().unwrap(), "Bonjour");
+    }
+}
@@ -1,21 +1,47 @@
 use std::pin::Pin;
+use std::time::Duration;
 use futures::Stream;
 use async_trait::async_trait;
 use thiserror::Error;
 
+pub mod cutoff;
 pub mod openai;
 pub mod gemini;
+pub mod groq;
+pub mod mistral;
+pub mod openrouter;
+pub mod pricing;
+pub mod rotation;
+pub mod vertex;
 
 #[derive(Debug, Error)]
 pub enum ApiError {
     #[error("Network error: {0}")]
-    Network(#[from] reqwest::Error),
+    Network(reqwest::Error),
     
     #[error("Rate limit exceeded")]
     RateLimit,
     
     #[error("Invalid API key")]
     InvalidKey,
+
+    #[error("Model not found: {0}")]
+    ModelNotFound(String),
+
+    #[error("Prompt is too long for the model: {0}")]
+    ContextLengthExceeded(String),
+
+    #[error("Quota exceeded: {0}")]
+    QuotaExceeded(String),
+
+    #[error("Blocked by the provider's content filter: {0}")]
+    ContentFiltered(String),
+
+    #[error("Provider is overloaded: {0}")]
+    ServerOverloaded(String),
+
+    #[error("Request timed out")]
+    Timeout,
     
     #[error("API error: {0}")]
     Other(String),
@@ -26,13 +52,116 @@ impl ApiError {
         match self {
             ApiError::Network(_) => true,
             ApiError::RateLimit => true,
-            ApiError::InvalidKey => false,
-            ApiError::Other(_) => false,
+            ApiError::ServerOverloaded(_) => true,
+            ApiError::Timeout => true,
+            ApiError::InvalidKey
+            | ApiError::ModelNotFound(_)
+            | ApiError::ContextLengthExceeded(_)
+            | ApiError::QuotaExceeded(_)
+            | ApiError::ContentFiltered(_)
+            | ApiError::Other(_) => false,
+        }
+    }
+
+    /// A transport failure, with timeouts told apart from other network errors.
+    /// The URL is dropped so no credential in it reaches an error message.
+    pub fn from_transport(error: reqwest::Error) -> Self {
+        if error.is_timeout() {
+            ApiError::Timeout
+        } else {
+            ApiError::Network(error.without_url())
+        }
+    }
+
+    /// Classify a provider error from its HTTP status, the error code or
+    /// status string in its body (`context_length_exceeded`, `NOT_FOUND`, ...),
+    /// and its message. Mid-stream errors carry no status; pass 200.
+    pub fn classify(status: u16, code: &str, message: String) -> Self {
+        let code = code.to_ascii_lowercase();
+        let text = message.to_lowercase();
+        if status == 401 || status == 403 || code.contains("api_key") || text.contains("api key not valid") {
+            ApiError::InvalidKey
+        } else if code.contains("context_length")
+            || text.contains("maximum context length")
+            || text.contains("context window")
+            || (text.contains("token") && text.contains("exceeds the maximum"))
+        {
+            ApiError::ContextLengthExceeded(message)
+        } else if code == "insufficient_quota" || text.contains("exceeded your current quota") || text.contains("billing") {
+            ApiError::QuotaExceeded(message)
+        } else if code.contains("content_filter") || code.contains("content_policy") || code == "safety" {
+            ApiError::ContentFiltered(message)
+        } else if code == "model_not_found" || code == "invalid_model" || (status == 404 || code == "not_found") && text.contains("model") {
+            ApiError::ModelNotFound(message)
+        } else if status == 429 || code == "rate_limit_exceeded" {
+            ApiError::RateLimit
+        } else if status == 408 || status == 504 || code == "deadline_exceeded" {
+            ApiError::Timeout
+        } else if matches!(status, 500 | 502 | 503 | 529) || code == "unavailable" || code.contains("overloaded") || text.contains("overloaded") {
+            ApiError::ServerOverloaded(message)
+        } else {
+            ApiError::Other(message)
         }
     }
 }
 
 pub type ApiResult<T> = Result<T, ApiError>;
+
+/// Response headers providers put their request ID in, the reference
+/// support asks for
+const REQUEST_ID_HEADERS: &[&str] = &["x-request-id", "request-id", "x-goog-request-id", "x-kong-request-id"];
+
+/// The request ID of a client's most recent query
+#[derive(Debug, Default)]
+pub(crate) struct LastRequestId(std::sync::Mutex<Option<String>>);
+
+impl LastRequestId {
+    pub(crate) fn record(&self, response: &reqwest::Response) {
+        let id = REQUEST_ID_HEADERS.iter()
+            .find_map(|name| response.headers().get(*name)?.to_str().ok())
+            .map(str::to_string);
+        *self.0.lock().expect("Failed to lock request ID") = id;
+    }
+
+    pub(crate) fn get(&self) -> Option<String> {
+        self.0.lock().expect("Failed to lock request ID").clone()
+    }
+}
+/// How long the provider asked to wait after its most recent response: the
+/// `Retry-After` (or OpenAI's `retry-after-ms`) of a 429 or 503
+#[derive(Debug, Default)]
+pub(crate) struct LastRetryAfter(std::sync::Mutex<Option<Duration>>);
+
+impl LastRetryAfter {
+    pub(crate) fn record(&self, response: &reqwest::Response) {
+        let wait = match response.status().as_u16() {
+            429 | 503 => retry_after(response.headers()),
+            _ => None,
+        };
+        *self.0.lock().expect("Failed to lock Retry-After") = wait;
+    }
+
+    pub(crate) fn get(&self) -> Option<Duration> {
+        *self.0.lock().expect("Failed to lock Retry-After")
+    }
+}
+
+/// A `Retry-After` of seconds or an HTTP date, or `retry-after-ms`
+fn retry_after(headers: &reqwest::header::HeaderMap) -> Option<Duration> {
+    let header = |name: &str| headers.get(name)?.to_str().ok().map(str::trim);
+    if let Some(millis) = header("retry-after-ms").and_then(|value| value.parse::<f64>().ok()) {
+        return Duration::try_from_secs_f64(millis / 1000.0).ok();
+    }
+    let value = header("retry-after")?;
+    match value.parse::<f64>() {
+        Ok(seconds) => Duration::try_from_secs_f64(seconds).ok(),
+        Err(_) => {
+            let at = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+            Some((at.with_timezone(&chrono::Utc) - chrono::Utc::now()).to_std().unwrap_or_default())
+        }
+    }
+}
+
 pub type StreamingResponse = Pin<Box<dyn Stream<Item = ApiResult<String>> + Send>>;
 
 #[async_trait]
@@ -40,6 +169,12 @@ pub trait LLMApi: Send + Sync {
     /// Sends a query to the LLM and returns the complete response
     async fn send_query(&self, prompt: &str) -> ApiResult<String>;
 
+    /// Sends a query and returns `n` independent answers. Providers without a
+    /// native option for this get one request per answer.
+    async fn send_query_n(&self, prompt: &str, n: usize) -> ApiResult<Vec<String>> {
+        repeated_queries(self, prompt, n).await
+    }
+
     /// Sends a query to the LLM and returns a stream of response tokens
     async fn send_streaming_query(&self, prompt: &str) -> ApiResult<StreamingResponse>;
 
@@ -48,6 +183,97 @@ pub trait LLMApi: Send + Sync {
 
     /// Returns the model name being used
     fn model(&self) -> &str;
+
+    /// The provider's ID for the most recent query, when it sends one
+    fn last_request_id(&self) -> Option<String> {
+        None
+    }
+
+    /// How long the provider asked to wait before retrying its most recent
+    /// failed query (`Retry-After`), when it said
+    fn last_retry_after(&self) -> Option<Duration> {
+        None
+    }
+
+    /// The backend configuration that served the most recent query (OpenAI's
+    /// `system_fingerprint`). A seeded answer only repeats while it stays the same.
+    fn last_system_fingerprint(&self) -> Option<String> {
+        None
+    }
+
+    /// Lists the models the provider serves
+    async fn list_models(&self) -> ApiResult<Vec<ModelInfo>> {
+        Err(ApiError::Other("This provider does not support listing models".to_string()))
+    }
+
+    /// Transcribes an audio file; `file_name` tells the provider its format
+    async fn transcribe(&self, _audio: &[u8], _file_name: &str) -> ApiResult<String> {
+        Err(ApiError::Other("This provider does not support audio transcription".to_string()))
+    }
+
+    /// Reads `text` aloud, returning MP3 audio
+    async fn speak(&self, _text: &str) -> ApiResult<Vec<u8>> {
+        Err(ApiError::Other("This provider does not support text-to-speech".to_string()))
+    }
+}
+
+/// `n` answers from `n` concurrent queries
+pub async fn repeated_queries<A: LLMApi + ?Sized>(api: &A, prompt: &str, n: usize) -> ApiResult<Vec<String>> {
+    futures::future::try_join_all((0..n).map(|_| api.send_query(prompt))).await
+}
+
+/// MIME type for an audio file name, from its extension
+pub fn audio_mime_type(file_name: &str) -> Option<&'static str> {
+    let extension = std::path::Path::new(file_name).extension()?.to_str()?.to_lowercase();
+    Some(match extension.as_str() {
+        "mp3" | "mpga" | "mpeg" => "audio/mpeg",
+        "m4a" | "mp4" => "audio/mp4",
+        "wav" => "audio/wav",
+        "ogg" | "oga" | "opus" => "audio/ogg",
+        "flac" => "audio/flac",
+        "webm" => "audio/webm",
+        "aac" => "audio/aac",
+        _ => return None,
+    })
+}
+
+/// A source the provider cited for its answer (web search grounding)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Citation {
+    pub title: String,
+    pub url: String,
+}
+
+/// Append citations to a response as numbered markdown reference definitions
+/// (`[1]: https://... "Title"`), which `format_markdown` renders as footnote links
+pub fn append_citations(text: &str, citations: &[Citation]) -> String {
+    if citations.is_empty() {
+        return text.to_string();
+    }
+    let mut output = format!("{}\n\n", text.trim_end());
+    output.push_str(&format_citations(citations));
+    output
+}
+
+/// The reference definitions appended by `append_citations`
+pub fn format_citations(citations: &[Citation]) -> String {
+    citations.iter()
+        .enumerate()
+        .map(|(i, citation)| {
+            let title = citation.title.replace('"', "'");
+            format!("[{}]: {} \"{}\"\n", i + 1, citation.url, title)
+        })
+        .collect()
+}
+
+/// A model from a provider's catalog. Pricing is in USD per token, when the
+/// provider publishes it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ModelInfo {
+    pub id: String,
+    pub context_length: Option<u64>,
+    pub prompt_price: Option<f64>,
+    pub completion_price: Option<f64>,
 }
 
 /// Common configuration for LLM models
@@ -55,6 +281,14 @@ pub trait LLMApi: Send + Sync {
 pub struct ModelConfig {
     pub temperature: f32,
     pub max_tokens: Option<u32>,
+    /// Let the model search the web with the provider's built-in search (`--web`)
+    pub web_search: bool,
+    /// Where q is running (OS, shell, ...), appended to the system prompt
+    pub environment: Option<String>,
+    /// Extra instructions from a `q ask` preset or `--runbook`, after the verbosity instruction
+    pub instructions: Option<String>,
+    /// Sampling seed for repeatable answers where the provider supports one (`--seed`)
+    pub seed: Option<u64>,
 }
 
 impl Default for ModelConfig {
@@ -62,10 +296,26 @@ impl Default for ModelConfig {
         Self {
             temperature: 0.7,
             max_tokens: None,
+            web_search: false,
+            environment: None,
+            instructions: None,
+            seed: None,
         }
     }
 }
 
+impl ModelConfig {
+    /// The verbosity instruction followed by any preset instructions and
+    /// the environment preamble
+    pub fn system_prompt(&self, instruction: &str) -> String {
+        [Some(instruction), self.instructions.as_deref(), self.environment.as_deref()]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
 /// Helper function to read API key from file
 pub fn read_api_key(path: &str) -> std::io::Result<String> {
     std::fs::read_to_string(path)
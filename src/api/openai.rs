@@ -5,23 +5,38 @@ use reqwest::{Client, header};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 
-use super::{ApiError, ApiResult, LLMApi, ModelConfig, StreamingResponse};
+use super::{append_citations, audio_mime_type, format_citations, repeated_queries, ApiError, LastRequestId, LastRetryAfter, Citation, ApiResult, LLMApi, ModelConfig, ModelInfo, StreamingResponse};
 use crate::cli::args::Verbosity;
 
 const DEFAULT_API_URL: &str = "https://api.openai.com/v1/chat/completions";
 const DEFAULT_MODEL: &str = "gpt-3.5-turbo";
 const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+const DEFAULT_TRANSCRIPTION_MODEL: &str = "whisper-1";
+/// Uploading and transcribing a long voice note takes longer than a chat reply
+const TRANSCRIPTION_TIMEOUT: Duration = Duration::from_secs(300);
+const DEFAULT_SPEECH_MODEL: &str = "tts-1";
+const DEFAULT_VOICE: &str = "alloy";
+/// Longest input `audio/speech` accepts, in characters
+pub const MAX_SPEECH_INPUT: usize = 4096;
 
 pub struct OpenAIClient {
     client: Client,
-    api_key: String,
     api_url: String,
     model: String,
+    transcription_model: String,
+    speech_model: String,
+    voice: String,
+    web_search: Option<WebSearch>,
+    multiple_choices: bool,
+    random_seed: bool,
     config: ModelConfig,
     verbosity: Verbosity,
+    request_id: LastRequestId,
+    retry_after: LastRetryAfter,
+    system_fingerprint: std::sync::Mutex<Option<String>>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize)]
 struct ChatMessage {
     role: String,
     content: String,
@@ -31,20 +46,69 @@ struct ChatMessage {
 struct ChatRequest {
     model: String,
     messages: Vec<ChatMessage>,
-    temperature: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     max_tokens: Option<u32>,
+    /// Number of choices to generate; omitted for the usual one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    n: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<u64>,
+    /// Mistral's name for `seed`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    random_seed: Option<u64>,
     stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    web_search_options: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    plugins: Option<Vec<serde_json::Value>>,
+}
+
+/// How an OpenAI-compatible service searches the web for `--web`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WebSearch {
+    /// `web_search_options`, for OpenAI's search models (which take no temperature)
+    SearchOptions,
+    /// OpenRouter's `web` plugin, which works with any model
+    Plugin,
 }
 
 #[derive(Debug, Deserialize)]
 struct ChatResponse {
     choices: Vec<ChatChoice>,
+    #[serde(default)]
+    system_fingerprint: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
 struct ChatChoice {
-    message: ChatMessage,
+    message: ResponseMessage,
+    #[serde(default)]
+    finish_reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ResponseMessage {
+    /// Null when moderation removed the answer
+    #[serde(default)]
+    content: Option<String>,
+    #[serde(default)]
+    annotations: Vec<Annotation>,
+}
+
+/// Web search citations on a message (`{"type": "url_citation", "url_citation": {...}}`)
+#[derive(Debug, Deserialize)]
+struct Annotation {
+    #[serde(default)]
+    url_citation: Option<UrlCitation>,
+}
+
+#[derive(Debug, Deserialize)]
+struct UrlCitation {
+    url: String,
+    #[serde(default)]
+    title: String,
 }
 
 #[derive(Debug, Deserialize)]
@@ -55,24 +119,108 @@ struct ChatStreamResponse {
 #[derive(Debug, Deserialize)]
 struct StreamChoice {
     delta: DeltaContent,
+    #[serde(default)]
+    finish_reason: Option<String>,
 }
 
+/// `finish_reason` when the provider's moderation stopped the answer
+const CONTENT_FILTER: &str = "content_filter";
+
 #[derive(Debug, Deserialize)]
 struct DeltaContent {
-    #[serde(default)]
-    role: Option<String>,
     #[serde(default)]
     content: Option<String>,
+    #[serde(default)]
+    annotations: Vec<Annotation>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Transcription {
+    text: String,
 }
 
 #[derive(Debug, Deserialize)]
-struct ErrorResponse {
-    error: ErrorDetail,
+struct ModelList {
+    data: Vec<ModelEntry>,
+}
+
+/// An entry from `/models`; `context_length` and `pricing` are OpenRouter extensions
+#[derive(Debug, Deserialize)]
+struct ModelEntry {
+    id: String,
+    #[serde(default)]
+    context_length: Option<u64>,
+    #[serde(default)]
+    pricing: Option<ModelPricing>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ModelPricing {
+    prompt: String,
+    completion: String,
+}
+
+/// Error bodies: OpenAI, OpenRouter, and Groq nest the message under `error`;
+/// Mistral returns it at the top level (`{"object": "error", "message": ...}`)
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum ErrorResponse {
+    Nested {
+        error: ErrorDetail,
+    },
+    Flat {
+        message: String,
+        #[serde(default)]
+        object: Option<String>,
+        #[serde(default, rename = "type")]
+        kind: Option<String>,
+        #[serde(default)]
+        code: Option<serde_json::Value>,
+    },
+}
+
+impl ErrorResponse {
+    /// The most specific machine-readable code: `code` when it is a name
+    /// (`context_length_exceeded`), else `type` (Mistral's codes are numbers)
+    fn code(&self) -> String {
+        let (kind, code) = match self {
+            ErrorResponse::Nested { error } => (&error.kind, &error.code),
+            ErrorResponse::Flat { kind, code, .. } => (kind, code),
+        };
+        match code {
+            Some(serde_json::Value::String(code)) if code.parse::<u64>().is_err() => code.clone(),
+            _ => kind.clone().unwrap_or_default(),
+        }
+    }
+
+    /// Flat bodies only count as errors when marked `"object": "error"`
+    fn is_error(&self) -> bool {
+        match self {
+            ErrorResponse::Nested { .. } => true,
+            ErrorResponse::Flat { object, .. } => object.as_deref() == Some("error"),
+        }
+    }
+
+    fn message(self) -> String {
+        match self {
+            ErrorResponse::Nested { error } => error.message,
+            ErrorResponse::Flat { message, .. } => message,
+        }
+    }
+
+    fn into_error(self, status: u16) -> ApiError {
+        let code = self.code();
+        ApiError::classify(status, &code, self.message())
+    }
 }
 
 #[derive(Debug, Deserialize)]
 struct ErrorDetail {
     message: String,
+    #[serde(default, rename = "type")]
+    kind: Option<String>,
+    #[serde(default)]
+    code: Option<serde_json::Value>,
 }
 
 pub struct OpenAIClientBuilder {
@@ -81,6 +229,13 @@ pub struct OpenAIClientBuilder {
     model: String,
     config: ModelConfig,
     verbosity: Verbosity,
+    headers: Vec<(&'static str, String)>,
+    transcription_model: String,
+    speech_model: String,
+    voice: String,
+    web_search: Option<WebSearch>,
+    multiple_choices: bool,
+    random_seed: bool,
 }
 
 impl OpenAIClientBuilder {
@@ -91,6 +246,13 @@ impl OpenAIClientBuilder {
             model: DEFAULT_MODEL.to_string(),
             config: ModelConfig::default(),
             verbosity: Verbosity::default(),
+            headers: Vec::new(),
+            transcription_model: DEFAULT_TRANSCRIPTION_MODEL.to_string(),
+            speech_model: DEFAULT_SPEECH_MODEL.to_string(),
+            voice: DEFAULT_VOICE.to_string(),
+            web_search: None,
+            multiple_choices: true,
+            random_seed: false,
         }
     }
 
@@ -114,28 +276,82 @@ impl OpenAIClientBuilder {
         self
     }
 
-    pub fn build(self) -> OpenAIClient {
+    /// Model used by `transcribe` (the service's Whisper deployment)
+    pub fn with_transcription_model(mut self, model: String) -> Self {
+        self.transcription_model = model;
+        self
+    }
+
+    /// Model and voice used by `speak`
+    pub fn with_voice(mut self, model: String, voice: String) -> Self {
+        self.speech_model = model;
+        self.voice = voice;
+        self
+    }
+
+    /// How the service searches the web when `ModelConfig::web_search` is set
+    pub fn with_web_search(mut self, web_search: WebSearch) -> Self {
+        self.web_search = Some(web_search);
+        self
+    }
+
+    /// Whether the service honors `n` (several choices from one request).
+    /// Without it, `send_query_n` sends one request per answer.
+    pub fn with_multiple_choices(mut self, supported: bool) -> Self {
+        self.multiple_choices = supported;
+        self
+    }
+
+    /// Send `ModelConfig::seed` as `random_seed` rather than `seed` (Mistral)
+    pub fn with_random_seed(mut self, random_seed: bool) -> Self {
+        self.random_seed = random_seed;
+        self
+    }
+
+    /// Send an extra header with every request, for OpenAI-compatible services
+    pub fn with_header(mut self, name: &'static str, value: String) -> Self {
+        self.headers.push((name, value));
+        self
+    }
+
+    /// Fails when the key or a header holds characters HTTP headers can't
+    /// carry (a pasted newline, say) or the HTTP client can't be set up
+    pub fn build(self) -> ApiResult<OpenAIClient> {
         let mut headers = header::HeaderMap::new();
-        headers.insert(
-            header::AUTHORIZATION,
-            header::HeaderValue::from_str(&format!("Bearer {}", self.api_key))
-                .expect("Invalid API key format"),
-        );
+        let mut authorization = header::HeaderValue::from_str(&format!("Bearer {}", self.api_key))
+            .map_err(|_| ApiError::Other(
+                "The API key contains characters that can't be sent in an HTTP header; check for stray whitespace or quotes".to_string()
+            ))?;
+        authorization.set_sensitive(true);
+        headers.insert(header::AUTHORIZATION, authorization);
+        for (name, value) in self.headers {
+            let value = header::HeaderValue::from_str(&value)
+                .map_err(|_| ApiError::Other(format!("Invalid value for the {} header", name)))?;
+            headers.insert(name, value);
+        }
 
         let client = Client::builder()
             .timeout(DEFAULT_TIMEOUT)
             .default_headers(headers)
             .build()
-            .expect("Failed to create HTTP client");
+            .map_err(|e| ApiError::Other(format!("Failed to create HTTP client: {}", e)))?;
 
-        OpenAIClient {
+        Ok(OpenAIClient {
             client,
-            api_key: self.api_key,
             api_url: self.api_url,
             model: self.model,
+            transcription_model: self.transcription_model,
+            speech_model: self.speech_model,
+            voice: self.voice,
+            web_search: self.web_search,
+            multiple_choices: self.multiple_choices,
+            random_seed: self.random_seed,
             config: self.config,
             verbosity: self.verbosity,
-        }
+            request_id: LastRequestId::default(),
+            retry_after: LastRetryAfter::default(),
+            system_fingerprint: Default::default(),
+        })
     }
 }
 
@@ -148,34 +364,113 @@ impl OpenAIClient {
         &self.model
     }
 
-    fn get_system_prompt(&self) -> &str {
-        match self.verbosity {
+    /// Map a failed HTTP response to an `ApiError` from its status and the
+    /// provider's error code, keeping only the provider's message
+    async fn error_from_response(response: reqwest::Response) -> ApiError {
+        let status = response.status().as_u16();
+        let error_text = response.text().await.unwrap_or_default();
+        match serde_json::from_str::<ErrorResponse>(&error_text) {
+            Ok(error) => error.into_error(status),
+            Err(_) => ApiError::classify(status, "", error_text),
+        }
+    }
+
+    /// Send a buffered chat request and return its choices
+    async fn send_chat(&self, request: ChatRequest) -> ApiResult<Vec<ChatChoice>> {
+        let response = self.client
+            .post(&self.api_url)
+            .json(&request)
+            .send()
+            .await
+            .map_err(ApiError::from_transport)?;
+        self.request_id.record(&response);
+        self.retry_after.record(&response);
+
+        if !response.status().is_success() {
+            return Err(Self::error_from_response(response).await);
+        }
+
+        let chat_response: ChatResponse = response
+            .json()
+            .await
+            .map_err(|e| ApiError::Other(format!("Failed to parse response: {}", e)))?;
+        *self.system_fingerprint.lock().expect("Failed to lock system fingerprint") = chat_response.system_fingerprint;
+        Ok(chat_response.choices)
+    }
+
+    /// A choice's text with its citations, or an error if moderation emptied it
+    fn choice_text(choice: &ChatChoice) -> ApiResult<String> {
+        let content = choice.message.content.as_deref().unwrap_or_default();
+        if choice.finish_reason.as_deref() == Some(CONTENT_FILTER) && content.is_empty() {
+            return Err(ApiError::ContentFiltered("the prompt or answer was flagged by moderation".to_string()));
+        }
+        Ok(append_citations(content, &Self::citations(&choice.message.annotations)))
+    }
+
+    /// An endpoint next to the configured chat completions URL, e.g. `models`
+    fn endpoint_url(&self, endpoint: &str) -> String {
+        let base = self.api_url.trim_end_matches("/chat/completions");
+        format!("{}/{}", base, endpoint)
+    }
+
+    fn get_system_prompt(&self) -> String {
+        let instruction = match self.verbosity {
             Verbosity::Concise => "You are a helpful assistant. Be concise and to the point. Provide only essential information without unnecessary details or explanations.",
             Verbosity::Normal => "You are a helpful assistant. Provide balanced responses with moderate detail.",
             Verbosity::Detailed => "You are a helpful assistant. Provide detailed and comprehensive responses with thorough explanations and examples where appropriate.",
-        }
+        };
+        self.config.system_prompt(instruction)
     }
 
     fn build_request(&self, prompt: &str, stream: bool) -> ChatRequest {
-        ChatRequest {
+        let mut request = ChatRequest {
             model: self.model.clone(),
             messages: vec![
                 ChatMessage {
                     role: "system".to_string(),
-                    content: self.get_system_prompt().to_string(),
+                    content: self.get_system_prompt(),
                 },
                 ChatMessage {
                     role: "user".to_string(),
                     content: prompt.to_string(),
                 },
             ],
-            temperature: self.config.temperature,
+            temperature: Some(self.config.temperature),
             max_tokens: self.config.max_tokens,
+            n: None,
+            seed: self.config.seed.filter(|_| !self.random_seed),
+            random_seed: self.config.seed.filter(|_| self.random_seed),
             stream,
+            web_search_options: None,
+            plugins: None,
+        };
+        if self.config.web_search {
+            match self.web_search {
+                Some(WebSearch::SearchOptions) => {
+                    request.web_search_options = Some(json!({}));
+                    request.temperature = None;
+                }
+                Some(WebSearch::Plugin) => request.plugins = Some(vec![json!({"id": "web"})]),
+                None => {}
+            }
+        }
+        request
+    }
+
+    /// Cited URLs, in order of first appearance
+    fn citations(annotations: &[Annotation]) -> Vec<Citation> {
+        let mut citations: Vec<Citation> = Vec::new();
+        for citation in annotations.iter().filter_map(|a| a.url_citation.as_ref()) {
+            if !citations.iter().any(|c| c.url == citation.url) {
+                citations.push(Citation { title: citation.title.clone(), url: citation.url.clone() });
+            }
         }
+        citations
     }
 
-    fn process_stream_chunk(chunk: &[u8]) -> ApiResult<Option<String>> {
+    /// The answer text in one chunk of a streamed response, which may hold
+    /// several `data:` events; `None` when it carries no text
+    pub fn process_stream_chunk(chunk: &[u8]) -> ApiResult<Option<String>> {
         let text = String::from_utf8_lossy(chunk);
         let mut content = String::new();
 
@@ -191,7 +486,9 @@ impl OpenAIClient {
 
             // Check for error response
             if let Ok(error) = serde_json::from_str::<ErrorResponse>(data) {
-                return Err(ApiError::Other(error.error.message));
+                if error.is_error() {
+                    return Err(error.into_error(200));
+                }
             }
 
             // Try to parse as stream response
@@ -200,6 +497,14 @@ impl OpenAIClient {
                     if let Some(token) = &choice.delta.content {
                         content.push_str(token);
                     }
+                    if choice.finish_reason.as_deref() == Some(CONTENT_FILTER) {
+                        return Err(ApiError::ContentFiltered("the answer was cut off by moderation".to_string()));
+                    }
+                    let citations = Self::citations(&choice.delta.annotations);
+                    if !citations.is_empty() {
+                        content.push_str("\n\n");
+                        content.push_str(&format_citations(&citations));
+                    }
                 }
             }
         }
@@ -218,39 +523,37 @@ impl LLMApi for OpenAIClient {
         &self.model
     }
 
-    async fn send_query(&self, prompt: &str) -> ApiResult<String> {
-        let request = self.build_request(prompt, false);
-        
-        let response = self.client
-            .post(&self.api_url)
-            .json(&request)
-            .send()
-            .await
-            .map_err(ApiError::Network)?;
+    fn last_request_id(&self) -> Option<String> {
+        self.request_id.get()
+    }
 
-        if !response.status().is_success() {
-            match response.status().as_u16() {
-                401 => return Err(ApiError::InvalidKey),
-                429 => return Err(ApiError::RateLimit),
-                _ => {
-                    let error_text = response.text().await.unwrap_or_default();
-                    return Err(ApiError::Other(error_text));
-                }
-            }
-        }
+    fn last_retry_after(&self) -> Option<Duration> {
+        self.retry_after.get()
+    }
 
-        let chat_response: ChatResponse = response
-            .json()
-            .await
-            .map_err(|e| ApiError::Other(format!("Failed to parse response: {}", e)))?;
+    fn last_system_fingerprint(&self) -> Option<String> {
+        self.system_fingerprint.lock().expect("Failed to lock system fingerprint").clone()
+    }
 
-        Ok(chat_response
-            .choices
+    async fn send_query(&self, prompt: &str) -> ApiResult<String> {
+        let choices = self.send_chat(self.build_request(prompt, false)).await?;
+        let choice = choices
             .first()
-            .ok_or_else(|| ApiError::Other("No response choices".to_string()))?
-            .message
-            .content
-            .clone())
+            .ok_or_else(|| ApiError::Other("No response choices".to_string()))?;
+        Self::choice_text(choice)
+    }
+
+    async fn send_query_n(&self, prompt: &str, n: usize) -> ApiResult<Vec<String>> {
+        if !self.multiple_choices || n <= 1 {
+            return repeated_queries(self, prompt, n).await;
+        }
+        let mut request = self.build_request(prompt, false);
+        request.n = Some(n as u32);
+        let choices = self.send_chat(request).await?;
+        if choices.is_empty() {
+            return Err(ApiError::Other("No response choices".to_string()));
+        }
+        choices.iter().map(Self::choice_text).collect()
     }
 
     async fn send_streaming_query(&self, prompt: &str) -> ApiResult<StreamingResponse> {
@@ -261,24 +564,19 @@ impl LLMApi for OpenAIClient {
             .json(&request)
             .send()
             .await
-            .map_err(ApiError::Network)?;
+            .map_err(ApiError::from_transport)?;
+        self.request_id.record(&response);
+        self.retry_after.record(&response);
 
         if !response.status().is_success() {
-            match response.status().as_u16() {
-                401 => return Err(ApiError::InvalidKey),
-                429 => return Err(ApiError::RateLimit),
-                _ => {
-                    let error_text = response.text().await.unwrap_or_default();
-                    return Err(ApiError::Other(error_text));
-                }
-            }
+            return Err(Self::error_from_response(response).await);
         }
 
         let stream = response
             .bytes_stream()
             .map(|result| {
                 result
-                    .map_err(ApiError::Network)
+                    .map_err(ApiError::from_transport)
                     .and_then(|bytes| {
                         Self::process_stream_chunk(&bytes)
                             .map(|opt_content| opt_content.unwrap_or_default())
@@ -311,17 +609,90 @@ impl LLMApi for OpenAIClient {
             .json(&request)
             .send()
             .await
-            .map_err(ApiError::Network)?;
-
-        match response.status().as_u16() {
-            200 => Ok(()),
-            401 => Err(ApiError::InvalidKey),
-            429 => Err(ApiError::RateLimit),
-            _ => {
-                let error_text = response.text().await.unwrap_or_default();
-                Err(ApiError::Other(error_text))
-            }
+            .map_err(ApiError::from_transport)?;
+
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(Self::error_from_response(response).await)
+        }
+    }
+
+    async fn transcribe(&self, audio: &[u8], file_name: &str) -> ApiResult<String> {
+        let mut part = reqwest::multipart::Part::bytes(audio.to_vec()).file_name(file_name.to_string());
+        if let Some(mime) = audio_mime_type(file_name) {
+            part = part.mime_str(mime).map_err(ApiError::from_transport)?;
+        }
+        let form = reqwest::multipart::Form::new()
+            .text("model", self.transcription_model.clone())
+            .text("response_format", "json")
+            .part("file", part);
+
+        let response = self.client
+            .post(self.endpoint_url("audio/transcriptions"))
+            .multipart(form)
+            .timeout(TRANSCRIPTION_TIMEOUT)
+            .send()
+            .await
+            .map_err(ApiError::from_transport)?;
+
+        if !response.status().is_success() {
+            return Err(Self::error_from_response(response).await);
         }
+
+        let transcription: Transcription = response
+            .json()
+            .await
+            .map_err(|e| ApiError::Other(format!("Failed to parse transcription: {}", e)))?;
+        Ok(transcription.text)
+    }
+
+    async fn speak(&self, text: &str) -> ApiResult<Vec<u8>> {
+        let response = self.client
+            .post(self.endpoint_url("audio/speech"))
+            .json(&json!({
+                "model": self.speech_model,
+                "voice": self.voice,
+                "input": text,
+                "response_format": "mp3",
+            }))
+            .timeout(TRANSCRIPTION_TIMEOUT)
+            .send()
+            .await
+            .map_err(ApiError::from_transport)?;
+
+        if !response.status().is_success() {
+            return Err(Self::error_from_response(response).await);
+        }
+
+        let audio = response.bytes().await.map_err(ApiError::from_transport)?;
+        Ok(audio.to_vec())
+    }
+
+    async fn list_models(&self) -> ApiResult<Vec<ModelInfo>> {
+        let response = self.client
+            .get(self.endpoint_url("models"))
+            .send()
+            .await
+            .map_err(ApiError::from_transport)?;
+
+        if !response.status().is_success() {
+            return Err(Self::error_from_response(response).await);
+        }
+
+        let models: ModelList = response
+            .json()
+            .await
+            .map_err(|e| ApiError::Other(format!("Failed to parse model list: {}", e)))?;
+
+        Ok(models.data.into_iter()
+            .map(|entry| ModelInfo {
+                id: entry.id,
+                context_length: entry.context_length,
+                prompt_price: entry.pricing.as_ref().and_then(|p| p.prompt.parse().ok()),
+                completion_price: entry.pricing.as_ref().and_then(|p| p.completion.parse().ok()),
+            })
+            .collect())
     }
 }
 
@@ -329,7 +700,7 @@ impl LLMApi for OpenAIClient {
 mod tests {
     use super::*;
     use wiremock::{Mock, MockServer, ResponseTemplate};
-    use wiremock::matchers::{method, path};
+    use wiremock::matchers::{body_partial_json, method, path};
 
     #[tokio::test]
     async fn test_send_query_success() {
@@ -349,13 +720,117 @@ mod tests {
             .await;
 
         let client = OpenAIClient::builder("test_key".to_string())
-            .with_api_url(mock_server.uri())
-            .build();
+            .with_api_url(format!("{}/v1/chat/completions", mock_server.uri()))
+            .build().unwrap();
 
         let response = client.send_query("Hi").await.unwrap();
         assert_eq!(response, "Hello, world!");
     }
 
+    #[tokio::test]
+    async fn test_send_query_n() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .and(body_partial_json(json!({ "n": 2 })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "choices": [
+                    { "message": { "content": "first" } },
+                    { "message": { "content": "second" } }
+                ]
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let client = OpenAIClient::builder("test_key".to_string())
+            .with_api_url(format!("{}/v1/chat/completions", mock_server.uri()))
+            .build().unwrap();
+        assert_eq!(client.send_query_n("Hi", 2).await.unwrap(), vec!["first", "second"]);
+    }
+
+    #[tokio::test]
+    async fn test_seed_and_fingerprint() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .and(body_partial_json(json!({ "seed": 42, "temperature": 0.0 })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "choices": [{ "message": { "content": "stable" } }],
+                "system_fingerprint": "fp_44709d6fcb"
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let client = OpenAIClient::builder("test_key".to_string())
+            .with_api_url(format!("{}/v1/chat/completions", mock_server.uri()))
+            .with_config(ModelConfig { temperature: 0.0, seed: Some(42), ..ModelConfig::default() })
+            .build().unwrap();
+        assert_eq!(client.last_system_fingerprint(), None);
+        assert_eq!(client.send_query("Hi").await.unwrap(), "stable");
+        assert_eq!(client.last_system_fingerprint().as_deref(), Some("fp_44709d6fcb"));
+
+        let mistral = crate::api::mistral::builder("test_key".to_string())
+            .with_config(ModelConfig { seed: Some(7), ..ModelConfig::default() })
+            .build().unwrap();
+        let request = serde_json::to_value(mistral.build_request("Hi", false)).unwrap();
+        assert_eq!(request["random_seed"], 7);
+        assert!(request.get("seed").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_send_query_n_without_native_support() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "choices": [{ "message": { "content": "again" } }]
+            })))
+            .expect(3)
+            .mount(&mock_server)
+            .await;
+
+        let client = OpenAIClient::builder("test_key".to_string())
+            .with_api_url(format!("{}/v1/chat/completions", mock_server.uri()))
+            .with_multiple_choices(false)
+            .build().unwrap();
+        assert_eq!(client.send_query_n("Hi", 3).await.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_records_request_id() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .respond_with(ResponseTemplate::new(429).insert_header("x-request-id", "req_abc123"))
+            .mount(&mock_server)
+            .await;
+
+        let client = OpenAIClient::builder("test_key".to_string())
+            .with_api_url(format!("{}/v1/chat/completions", mock_server.uri()))
+            .build().unwrap();
+        assert_eq!(client.last_request_id(), None);
+        assert!(client.send_query("Hi").await.is_err());
+        assert_eq!(client.last_request_id().as_deref(), Some("req_abc123"));
+    }
+
+    #[tokio::test]
+    async fn test_records_retry_after() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .respond_with(ResponseTemplate::new(429).insert_header("retry-after", "7"))
+            .mount(&mock_server)
+            .await;
+
+        let client = OpenAIClient::builder("test_key".to_string())
+            .with_api_url(format!("{}/v1/chat/completions", mock_server.uri()))
+            .build().unwrap();
+        assert!(matches!(client.send_query("Hi").await, Err(ApiError::RateLimit)));
+        assert_eq!(client.last_retry_after(), Some(Duration::from_secs(7)));
+    }
+
     #[tokio::test]
     async fn test_invalid_api_key() {
         let mock_server = MockServer::start().await;
@@ -367,13 +842,73 @@ mod tests {
             .await;
 
         let client = OpenAIClient::builder("invalid_key".to_string())
-            .with_api_url(mock_server.uri())
-            .build();
+            .with_api_url(format!("{}/v1/chat/completions", mock_server.uri()))
+            .build().unwrap();
 
         let result = client.send_query("Hi").await;
         assert!(matches!(result, Err(ApiError::InvalidKey)));
     }
 
+    #[tokio::test]
+    async fn test_error_codes() {
+        let mock_server = MockServer::start().await;
+        let client = OpenAIClient::builder("test_key".to_string())
+            .with_api_url(format!("{}/v1/chat/completions", mock_server.uri()))
+            .build().unwrap();
+
+        let cases = [
+            (400, json!({"error": {"message": "This model's maximum context length is 8192 tokens", "type": "invalid_request_error", "code": "context_length_exceeded"}})),
+            (429, json!({"error": {"message": "You exceeded your current quota", "type": "insufficient_quota", "code": "insufficient_quota"}})),
+            (404, json!({"error": {"message": "The model `gpt-9` does not exist", "type": "invalid_request_error", "code": "model_not_found"}})),
+            (503, json!({"error": {"message": "The engine is currently overloaded", "type": "server_error", "code": null}})),
+        ];
+        let mut errors = Vec::new();
+        for (status, body) in cases {
+            mock_server.reset().await;
+            Mock::given(method("POST"))
+                .and(path("/v1/chat/completions"))
+                .respond_with(ResponseTemplate::new(status).set_body_json(body))
+                .mount(&mock_server)
+                .await;
+            errors.push(client.send_query("Hi").await.unwrap_err());
+        }
+
+        assert!(matches!(errors[0], ApiError::ContextLengthExceeded(_)));
+        assert!(matches!(errors[1], ApiError::QuotaExceeded(_)));
+        assert!(matches!(errors[2], ApiError::ModelNotFound(_)));
+        assert!(matches!(errors[3], ApiError::ServerOverloaded(_)));
+        assert!(errors[3].is_retryable() && !errors[1].is_retryable());
+    }
+
+    #[tokio::test]
+    async fn test_content_filter() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "choices": [{ "message": { "content": null }, "finish_reason": "content_filter" }]
+            })))
+            .mount(&mock_server)
+            .await;
+
+        let client = OpenAIClient::builder("test_key".to_string())
+            .with_api_url(format!("{}/v1/chat/completions", mock_server.uri()))
+            .build().unwrap();
+        assert!(matches!(client.send_query("Hi").await, Err(ApiError::ContentFiltered(_))));
+
+        let chunk = b"data: {\"choices\":[{\"delta\":{},\"finish_reason\":\"content_filter\"}]}\n\n";
+        assert!(matches!(OpenAIClient::process_stream_chunk(chunk), Err(ApiError::ContentFiltered(_))));
+    }
+
+    #[test]
+    fn test_build_rejects_unsendable_key() {
+        let Err(err) = OpenAIClient::builder("sk-secret\n".to_string()).build() else {
+            panic!("a key with a newline should be rejected");
+        };
+        assert!(err.to_string().contains("can't be sent in an HTTP header"));
+        assert!(!err.to_string().contains("sk-secret"));
+    }
+
     #[tokio::test]
     async fn test_process_stream_chunk() {
         // Test regular content
@@ -393,11 +928,168 @@ mod tests {
         assert!(OpenAIClient::process_stream_chunk(chunk).is_err());
         assert_eq!(
             OpenAIClient::process_stream_chunk(chunk).unwrap_err().to_string(),
-            "Stream error"
+            "API error: Stream error"
         );
 
         // Test multiple chunks in one message
         let chunk = b"data: {\"choices\":[{\"delta\":{\"content\":\"Hello\"}}]}\n\ndata: {\"choices\":[{\"delta\":{\"content\":\" World\"}}]}\n\n";
         assert_eq!(OpenAIClient::process_stream_chunk(chunk).unwrap(), Some("Hello World".to_string()));
     }
+
+    #[tokio::test]
+    async fn test_list_models() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path("/v1/models"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "data": [
+                    { "id": "gpt-4o", "object": "model" },
+                    {
+                        "id": "anthropic/claude-3.5-sonnet",
+                        "context_length": 200000,
+                        "pricing": { "prompt": "0.000003", "completion": "0.000015" }
+                    }
+                ]
+            })))
+            .mount(&mock_server)
+            .await;
+
+        let client = OpenAIClient::builder("test_key".to_string())
+            .with_api_url(format!("{}/v1/chat/completions", mock_server.uri()))
+            .build().unwrap();
+
+        let models = client.list_models().await.unwrap();
+        assert_eq!(models.len(), 2);
+        assert_eq!(models[0].id, "gpt-4o");
+        assert_eq!(models[0].context_length, None);
+        assert_eq!(models[1].context_length, Some(200000));
+        assert_eq!(models[1].prompt_price, Some(0.000003));
+    }
+
+    #[tokio::test]
+    async fn test_transcribe() {
+        use wiremock::matchers::body_string_contains;
+
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/v1/audio/transcriptions"))
+            .and(body_string_contains("whisper-1"))
+            .and(body_string_contains("filename=\"memo.m4a\""))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "text": "remind me to rotate the keys"
+            })))
+            .mount(&mock_server)
+            .await;
+
+        let client = OpenAIClient::builder("test_key".to_string())
+            .with_api_url(format!("{}/v1/chat/completions", mock_server.uri()))
+            .build().unwrap();
+
+        let text = client.transcribe(b"fake audio", "memo.m4a").await.unwrap();
+        assert_eq!(text, "remind me to rotate the keys");
+    }
+
+    #[tokio::test]
+    async fn test_web_search_options_replace_temperature() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(body_partial_json(json!({ "web_search_options": {} })))
+            .and(|request: &wiremock::Request| {
+                let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+                body.get("temperature").is_none()
+            })
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "choices": [{ "message": { "role": "assistant", "content": "searched" } }]
+            })))
+            .mount(&mock_server)
+            .await;
+
+        let client = OpenAIClient::builder("test_key".to_string())
+            .with_api_url(format!("{}/v1/chat/completions", mock_server.uri()))
+            .with_web_search(WebSearch::SearchOptions)
+            .with_config(ModelConfig { web_search: true, ..ModelConfig::default() })
+            .build().unwrap();
+
+        assert_eq!(client.send_query("Hi").await.unwrap(), "searched");
+    }
+
+    #[tokio::test]
+    async fn test_url_citations_become_footnotes() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "choices": [{ "message": {
+                    "role": "assistant",
+                    "content": "It rained in Tokyo.",
+                    "annotations": [
+                        { "type": "url_citation", "url_citation": { "url": "https://weather.example", "title": "Weather", "start_index": 0, "end_index": 19 } },
+                        { "type": "url_citation", "url_citation": { "url": "https://weather.example", "title": "Weather", "start_index": 0, "end_index": 5 } }
+                    ]
+                } }]
+            })))
+            .mount(&mock_server)
+            .await;
+
+        let client = OpenAIClient::builder("test_key".to_string())
+            .with_api_url(format!("{}/v1/chat/completions", mock_server.uri()))
+            .build().unwrap();
+
+        assert_eq!(
+            client.send_query("Hi").await.unwrap(),
+            "It rained in Tokyo.\n\n[1]: https://weather.example \"Weather\"\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_environment_joins_system_prompt() {
+        use wiremock::matchers::body_string_contains;
+
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(body_string_contains("to the point. Provide only essential information without unnecessary details or explanations.\\nEnvironment: Linux (x86_64), shell: zsh"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "choices": [{ "message": { "role": "assistant", "content": "ok" } }]
+            })))
+            .mount(&mock_server)
+            .await;
+
+        let client = OpenAIClient::builder("test_key".to_string())
+            .with_api_url(format!("{}/v1/chat/completions", mock_server.uri()))
+            .with_config(ModelConfig {
+                environment: Some("Environment: Linux (x86_64), shell: zsh".to_string()),
+                ..ModelConfig::default()
+            })
+            .build().unwrap();
+
+        assert_eq!(client.send_query("Hi").await.unwrap(), "ok");
+    }
+
+    #[tokio::test]
+    async fn test_speak() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/v1/audio/speech"))
+            .and(body_partial_json(json!({
+                "model": "tts-1",
+                "voice": "alloy",
+                "input": "Hello there",
+                "response_format": "mp3"
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(b"ID3 fake mp3".to_vec()))
+            .mount(&mock_server)
+            .await;
+
+        let client = OpenAIClient::builder("test_key".to_string())
+            .with_api_url(format!("{}/v1/chat/completions", mock_server.uri()))
+            .build().unwrap();
+
+        let audio = client.speak("Hello there").await.unwrap();
+        assert_eq!(audio, b"ID3 fake mp3");
+    }
 }
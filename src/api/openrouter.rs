@@ -0,0 +1,77 @@
+//! OpenRouter serves hundreds of models behind the OpenAI chat API, so it is
+//! an `OpenAIClient` pointed at OpenRouter with the attribution headers it requires.
+
+use super::openai::{OpenAIClient, OpenAIClientBuilder, WebSearch};
+
+pub const API_URL: &str = "https://openrouter.ai/api/v1/chat/completions";
+pub const DEFAULT_MODEL: &str = "openrouter/auto";
+
+/// Identifies q to OpenRouter (shown in their app rankings)
+const REFERER: &str = "https://github.com/rfushimi/q";
+const TITLE: &str = "q";
+
+/// A builder for an OpenRouter client; the API URL and model can still be overridden
+pub fn builder(api_key: String) -> OpenAIClientBuilder {
+    OpenAIClient::builder(api_key)
+        .with_api_url(API_URL.to_string())
+        .with_model(DEFAULT_MODEL.to_string())
+        .with_header("HTTP-Referer", REFERER.to_string())
+        .with_header("X-Title", TITLE.to_string())
+        .with_web_search(WebSearch::Plugin)
+        .with_multiple_choices(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::LLMApi;
+    use serde_json::json;
+    use wiremock::matchers::{header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_sends_attribution_headers() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/api/v1/chat/completions"))
+            .and(header("HTTP-Referer", REFERER))
+            .and(header("X-Title", TITLE))
+            .and(header("Authorization", "Bearer sk-or-test"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "choices": [{ "message": { "role": "assistant", "content": "routed" } }]
+            })))
+            .mount(&mock_server)
+            .await;
+
+        let client = builder("sk-or-test".to_string())
+            .with_api_url(format!("{}/api/v1/chat/completions", mock_server.uri()))
+            .build().unwrap();
+
+        assert_eq!(client.model(), DEFAULT_MODEL);
+        assert_eq!(client.send_query("Hi").await.unwrap(), "routed");
+    }
+
+    #[tokio::test]
+    async fn test_web_search_uses_plugin() {
+        use crate::api::ModelConfig;
+        use wiremock::matchers::body_partial_json;
+
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(body_partial_json(json!({ "plugins": [{ "id": "web" }], "temperature": 0.7 })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "choices": [{ "message": { "role": "assistant", "content": "searched" } }]
+            })))
+            .mount(&mock_server)
+            .await;
+
+        let client = builder("sk-or-test".to_string())
+            .with_api_url(format!("{}/api/v1/chat/completions", mock_server.uri()))
+            .with_config(ModelConfig { web_search: true, ..ModelConfig::default() })
+            .build().unwrap();
+
+        assert_eq!(client.send_query("Hi").await.unwrap(), "searched");
+    }
+}
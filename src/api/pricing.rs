@@ -0,0 +1,159 @@
+//! Rough token counts and list prices for `--dry-run` cost estimates, and
+//! the knowledge cutoffs behind the time-sensitive caveat (see `cutoff`),
+//! kept in one table of known models.
+//!
+//! Prices are the providers' published rates in USD per 1M tokens and drift
+//! over time; they are only meant to catch a query that costs far more than
+//! expected. OpenRouter reports its own prices through `list_models`.
+//! Cutoffs are the providers' published training-data dates, to the month.
+
+use super::cutoff::Cutoff;
+
+/// USD per 1M tokens
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Price {
+    pub input: f64,
+    pub output: f64,
+}
+
+/// What q knows about a model: its list price and knowledge cutoff, each
+/// when known
+struct Model {
+    name: &'static str,
+    /// USD per 1M input and output tokens
+    price: Option<(f64, f64)>,
+    /// The year and month its training data ends
+    cutoff: Option<(i32, u32)>,
+}
+
+const fn model(name: &'static str, price: Option<(f64, f64)>, cutoff: Option<(i32, u32)>) -> Model {
+    Model { name, price, cutoff }
+}
+
+/// Known models. An entry also covers the dated and other versions named
+/// after it (`gpt-4o-2024-08-06`), but not another tier of it (`o1-pro`),
+/// which needs its own entry. The longest match wins.
+const MODELS: &[Model] = &[
+    model("gpt-3.5-turbo", Some((0.50, 1.50)), Some((2021, 9))),
+    model("gpt-4-turbo", Some((10.00, 30.00)), Some((2023, 12))),
+    model("gpt-4o", Some((2.50, 10.00)), Some((2023, 10))),
+    model("gpt-4o-mini", Some((0.15, 0.60)), Some((2023, 10))),
+    model("gpt-4.1", Some((2.00, 8.00)), Some((2024, 6))),
+    model("gpt-4.1-mini", Some((0.40, 1.60)), Some((2024, 6))),
+    model("gpt-4.1-nano", Some((0.10, 0.40)), Some((2024, 6))),
+    model("o1", Some((15.00, 60.00)), Some((2023, 10))),
+    model("o1-mini", Some((1.10, 4.40)), Some((2023, 10))),
+    model("o1-pro", Some((150.00, 600.00)), Some((2023, 10))),
+    model("o3", Some((2.00, 8.00)), Some((2024, 6))),
+    model("o3-mini", Some((1.10, 4.40)), Some((2023, 10))),
+    model("o3-pro", Some((20.00, 80.00)), Some((2024, 6))),
+    model("o4-mini", Some((1.10, 4.40)), Some((2024, 6))),
+    model("gemini-1.5-flash", Some((0.075, 0.30)), Some((2023, 11))),
+    model("gemini-1.5-pro", Some((1.25, 5.00)), Some((2023, 11))),
+    model("gemini-2.0-flash", Some((0.10, 0.40)), Some((2024, 8))),
+    model("gemini-2.0-flash-lite", Some((0.075, 0.30)), Some((2024, 8))),
+    model("gemini-2.5-flash", Some((0.30, 2.50)), Some((2025, 1))),
+    model("gemini-2.5-flash-lite", Some((0.10, 0.40)), Some((2025, 1))),
+    model("gemini-2.5-pro", Some((1.25, 10.00)), Some((2025, 1))),
+    model("claude-3-5-sonnet", None, Some((2024, 4))),
+    model("claude-sonnet-4", None, Some((2025, 3))),
+    model("mistral-small", Some((0.10, 0.30)), None),
+    model("mistral-medium", Some((0.40, 2.00)), None),
+    model("mistral-large", Some((2.00, 6.00)), None),
+    model("codestral", Some((0.30, 0.90)), None),
+    model("llama-3.1", None, Some((2023, 12))),
+    model("llama-3.1-8b", Some((0.05, 0.08)), Some((2023, 12))),
+    model("llama-3.3", None, Some((2023, 12))),
+    model("llama-3.3-70b", Some((0.59, 0.79)), Some((2023, 12))),
+];
+
+/// Name segments that mark another tier of a model rather than a version of it
+const TIERS: &[&str] = &["mini", "nano", "lite", "pro", "max", "turbo", "ultra"];
+
+/// Answer lengths assumed for the low and high end of a projection
+pub const ANSWER_TOKENS: (usize, usize) = (100, 2000);
+
+/// The list price for `model`, with or without a `provider/` prefix
+pub fn list_price(model: &str) -> Option<Price> {
+    let (input, output) = known(model)?.price?;
+    Some(Price { input, output })
+}
+
+/// The knowledge cutoff of `model`, with or without a `provider/` prefix
+pub fn knowledge_cutoff(model: &str) -> Option<Cutoff> {
+    let (year, month) = known(model)?.cutoff?;
+    Some(Cutoff { year, month })
+}
+
+/// The entry for `model`, with or without a `provider/` prefix
+fn known(model: &str) -> Option<&'static Model> {
+    let id = model.rsplit('/').next().unwrap_or(model);
+    MODELS.iter()
+        .filter(|entry| covers(entry.name, id))
+        .max_by_key(|entry| entry.name.len())
+}
+
+/// Whether the entry `name` prices model `id`: the same name, or the name
+/// followed by `-` and a version rather than a tier
+fn covers(name: &str, id: &str) -> bool {
+    match id.strip_prefix(name) {
+        Some("") => true,
+        Some(rest) => rest.strip_prefix('-')
+            .is_some_and(|rest| !TIERS.contains(&rest.split('-').next().unwrap_or_default())),
+        None => false,
+    }
+}
+
+/// A low and high guess at the tokens in `text`: about four characters per
+/// token for English prose, nearer three for code and other languages
+pub fn estimate_tokens(text: &str) -> (usize, usize) {
+    let chars = text.chars().count();
+    (chars.div_ceil(4), chars.div_ceil(3))
+}
+
+/// USD for `input` prompt tokens and `output` answer tokens
+pub fn cost(price: Price, input: usize, output: usize) -> f64 {
+    (input as f64 * price.input + output as f64 * price.output) / 1e6
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_list_price_prefers_longest_name() {
+        assert_eq!(list_price("gpt-4o-mini-2024-07-18"), Some(Price { input: 0.15, output: 0.60 }));
+        assert_eq!(list_price("gpt-4o"), Some(Price { input: 2.50, output: 10.00 }));
+        assert_eq!(list_price("openai/o3-mini"), Some(Price { input: 1.10, output: 4.40 }));
+        assert_eq!(list_price("llama-3.3-70b-versatile").map(|price| price.output), Some(0.79));
+        assert_eq!(list_price("some-new-model"), None);
+        // Another tier isn't priced as the model it's named after
+        assert_eq!(list_price("o1-pro-2025-03-19").map(|price| price.input), Some(150.00));
+        assert_eq!(list_price("o3-pro").map(|price| price.output), Some(80.00));
+        assert_eq!(list_price("o1-mini").map(|price| price.input), Some(1.10));
+        assert_eq!(list_price("gpt-4.1-mini-2025-04-14").map(|price| price.input), Some(0.40));
+        assert_eq!(list_price("o4-pro"), None);
+        assert_eq!(list_price("gpt-4o-ultra"), None);
+        assert_eq!(list_price("o10"), None);
+    }
+
+    #[test]
+    fn test_knowledge_cutoff() {
+        assert_eq!(knowledge_cutoff("gpt-4o-mini"), Some(Cutoff { year: 2023, month: 10 }));
+        assert_eq!(knowledge_cutoff("openai/o3-mini"), Some(Cutoff { year: 2023, month: 10 }));
+        assert_eq!(knowledge_cutoff("o3").map(|cutoff| cutoff.to_string()).as_deref(), Some("2024-06"));
+        assert_eq!(knowledge_cutoff("llama-3.1-70b-versatile").map(|cutoff| cutoff.year), Some(2023));
+        // Known for one thing but not the other
+        assert_eq!(knowledge_cutoff("mistral-large-latest"), None);
+        assert_eq!(list_price("claude-sonnet-4-20250514"), None);
+        assert_eq!(knowledge_cutoff("some-new-model"), None);
+    }
+
+    #[test]
+    fn test_estimate_and_cost() {
+        assert_eq!(estimate_tokens(&"a".repeat(1200)), (300, 400));
+        assert_eq!(estimate_tokens(""), (0, 0));
+        let price = Price { input: 2.50, output: 10.00 };
+        assert!((cost(price, 1_000_000, 100_000) - 3.5).abs() < 1e-9);
+    }
+}
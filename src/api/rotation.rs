@@ -0,0 +1,241 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use async_trait::async_trait;
+
+use super::{ApiError, ApiResult, LLMApi, ModelInfo, StreamingResponse};
+
+const DEFAULT_COOLDOWN: Duration = Duration::from_secs(60);
+
+/// Client that spreads requests over several keys for the same provider.
+///
+/// When a key is rate limited it is put on cooldown and the request is
+/// retried with the next key that is not cooling down.
+pub struct RotatingClient {
+    clients: Vec<Arc<dyn LLMApi>>,
+    state: Mutex<RotationState>,
+    cooldown: Duration,
+}
+
+struct RotationState {
+    current: usize,
+    cooling_until: Vec<Option<Instant>>,
+}
+
+impl RotatingClient {
+    pub fn new(clients: Vec<Arc<dyn LLMApi>>) -> Self {
+        let count = clients.len();
+        Self {
+            clients,
+            state: Mutex::new(RotationState {
+                current: 0,
+                cooling_until: vec![None; count],
+            }),
+            cooldown: DEFAULT_COOLDOWN,
+        }
+    }
+
+    pub fn with_cooldown(mut self, cooldown: Duration) -> Self {
+        self.cooldown = cooldown;
+        self
+    }
+
+    /// Indices of keys to try, starting at the current one and skipping
+    /// any that are still cooling down
+    fn candidates(&self) -> Vec<usize> {
+        let state = self.state.lock().expect("Failed to lock rotation state");
+        let now = Instant::now();
+        (0..self.clients.len())
+            .map(|offset| (state.current + offset) % self.clients.len())
+            .filter(|&i| state.cooling_until[i].is_none_or(|until| until <= now))
+            .collect()
+    }
+
+    fn mark_rate_limited(&self, index: usize) {
+        let mut state = self.state.lock().expect("Failed to lock rotation state");
+        state.cooling_until[index] = Some(Instant::now() + self.cooldown);
+        state.current = (index + 1) % self.clients.len();
+    }
+
+    fn mark_success(&self, index: usize) {
+        let mut state = self.state.lock().expect("Failed to lock rotation state");
+        state.current = index;
+    }
+
+    fn current(&self) -> &Arc<dyn LLMApi> {
+        let index = self.state.lock().expect("Failed to lock rotation state").current;
+        &self.clients[index]
+    }
+
+    /// Rate limits and quotas are per key, so another key may still work
+    fn should_rotate(error: &ApiError) -> bool {
+        matches!(error, ApiError::RateLimit | ApiError::QuotaExceeded(_))
+    }
+}
+
+#[async_trait]
+impl LLMApi for RotatingClient {
+    async fn send_query(&self, prompt: &str) -> ApiResult<String> {
+        let mut last_error = ApiError::RateLimit;
+        for index in self.candidates() {
+            match self.clients[index].send_query(prompt).await {
+                Ok(response) => {
+                    self.mark_success(index);
+                    return Ok(response);
+                }
+                Err(e) if Self::should_rotate(&e) => {
+                    self.mark_rate_limited(index);
+                    last_error = e;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Err(last_error)
+    }
+
+    async fn send_query_n(&self, prompt: &str, n: usize) -> ApiResult<Vec<String>> {
+        let mut last_error = ApiError::RateLimit;
+        for index in self.candidates() {
+            match self.clients[index].send_query_n(prompt, n).await {
+                Ok(responses) => {
+                    self.mark_success(index);
+                    return Ok(responses);
+                }
+                Err(e) if Self::should_rotate(&e) => {
+                    self.mark_rate_limited(index);
+                    last_error = e;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Err(last_error)
+    }
+
+    async fn send_streaming_query(&self, prompt: &str) -> ApiResult<StreamingResponse> {
+        let mut last_error = ApiError::RateLimit;
+        for index in self.candidates() {
+            match self.clients[index].send_streaming_query(prompt).await {
+                Ok(stream) => {
+                    self.mark_success(index);
+                    return Ok(stream);
+                }
+                Err(e) if Self::should_rotate(&e) => {
+                    self.mark_rate_limited(index);
+                    last_error = e;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Err(last_error)
+    }
+
+    async fn validate_key(&self) -> ApiResult<()> {
+        self.current().validate_key().await
+    }
+
+    fn model(&self) -> &str {
+        self.clients[0].model()
+    }
+
+    /// The key that answered (or failed) last is the current one
+    fn last_request_id(&self) -> Option<String> {
+        self.current().last_request_id()
+    }
+
+    fn last_retry_after(&self) -> Option<std::time::Duration> {
+        self.current().last_retry_after()
+    }
+
+    fn last_system_fingerprint(&self) -> Option<String> {
+        self.current().last_system_fingerprint()
+    }
+
+    async fn list_models(&self) -> ApiResult<Vec<ModelInfo>> {
+        self.current().list_models().await
+    }
+
+    async fn transcribe(&self, audio: &[u8], file_name: &str) -> ApiResult<String> {
+        self.current().transcribe(audio, file_name).await
+    }
+
+    async fn speak(&self, text: &str) -> ApiResult<Vec<u8>> {
+        self.current().speak(text).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    struct MockClient {
+        rate_limited: bool,
+        reply: &'static str,
+        calls: AtomicU32,
+    }
+
+    impl MockClient {
+        fn new(rate_limited: bool, reply: &'static str) -> Arc<Self> {
+            Arc::new(Self { rate_limited, reply, calls: AtomicU32::new(0) })
+        }
+    }
+
+    #[async_trait]
+    impl LLMApi for MockClient {
+        async fn send_query(&self, _prompt: &str) -> ApiResult<String> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            if self.rate_limited {
+                Err(ApiError::RateLimit)
+            } else {
+                Ok(self.reply.to_string())
+            }
+        }
+
+        async fn send_streaming_query(&self, _prompt: &str) -> ApiResult<StreamingResponse> {
+            unimplemented!()
+        }
+
+        async fn validate_key(&self) -> ApiResult<()> {
+            Ok(())
+        }
+
+        fn model(&self) -> &str {
+            "mock"
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rotates_on_rate_limit() {
+        let limited = MockClient::new(true, "first");
+        let healthy = MockClient::new(false, "second");
+        let client = RotatingClient::new(vec![limited.clone(), healthy.clone()]);
+
+        assert_eq!(client.send_query("hi").await.unwrap(), "second");
+        // The limited key is cooling down, so it is skipped on the next call
+        assert_eq!(client.send_query("hi").await.unwrap(), "second");
+        assert_eq!(limited.calls.load(Ordering::SeqCst), 1);
+        assert_eq!(healthy.calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_all_keys_rate_limited() {
+        let client = RotatingClient::new(vec![
+            MockClient::new(true, "a"),
+            MockClient::new(true, "b"),
+        ]);
+
+        assert!(matches!(client.send_query("hi").await, Err(ApiError::RateLimit)));
+    }
+
+    #[tokio::test]
+    async fn test_cooldown_expires() {
+        let limited = MockClient::new(true, "first");
+        let client = RotatingClient::new(vec![limited.clone(), MockClient::new(false, "second")])
+            .with_cooldown(Duration::from_millis(10));
+
+        client.send_query("hi").await.unwrap();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        client.send_query("hi").await.unwrap();
+        // Current moved to the healthy key, but the limited key is eligible again
+        assert_eq!(client.candidates().len(), 2);
+    }
+}
@@ -0,0 +1,190 @@
+//! OAuth access tokens for Vertex AI, from a service-account JSON file or
+//! Google application-default credentials (`gcloud auth application-default login`).
+
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+
+use super::{ApiError, ApiResult};
+
+const TOKEN_URI: &str = "https://oauth2.googleapis.com/token";
+const SCOPE: &str = "https://www.googleapis.com/auth/cloud-platform";
+
+/// The credential files gcloud and the GCP console produce
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Credentials {
+    ServiceAccount {
+        client_email: String,
+        private_key: String,
+        #[serde(default)]
+        token_uri: Option<String>,
+    },
+    AuthorizedUser {
+        client_id: String,
+        client_secret: String,
+        refresh_token: String,
+        #[serde(default)]
+        token_uri: Option<String>,
+    },
+}
+
+#[derive(Debug, Serialize)]
+struct Claims<'a> {
+    iss: &'a str,
+    scope: &'a str,
+    aud: &'a str,
+    iat: u64,
+    exp: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+}
+
+/// Fetch an access token using `credentials`, or the application-default
+/// credentials when no path is configured
+pub async fn access_token(credentials: Option<&Path>) -> ApiResult<String> {
+    let path = match credentials {
+        Some(path) => path.to_path_buf(),
+        None => default_credentials_path().ok_or_else(|| ApiError::Other(
+            "No Google credentials found. Run 'gcloud auth application-default login' \
+             or set vertex.credentials to a service-account JSON file.".to_string()
+        ))?,
+    };
+    let contents = std::fs::read_to_string(&path)
+        .map_err(|e| ApiError::Other(format!("Failed to read credentials {}: {}", path.display(), e)))?;
+    let credentials: Credentials = serde_json::from_str(&contents)
+        .map_err(|e| ApiError::Other(format!("Unsupported credentials file {}: {}", path.display(), e)))?;
+
+    let client = Client::new();
+    let request = match &credentials {
+        Credentials::ServiceAccount { client_email, private_key, token_uri } => {
+            let token_uri = token_uri.as_deref().unwrap_or(TOKEN_URI);
+            let assertion = sign_assertion(client_email, private_key, token_uri)?;
+            client.post(token_uri).form(&[
+                ("grant_type", "urn:ietf:params:oauth:grant-type:jwt-bearer"),
+                ("assertion", assertion.as_str()),
+            ])
+        }
+        Credentials::AuthorizedUser { client_id, client_secret, refresh_token, token_uri } => {
+            client.post(token_uri.as_deref().unwrap_or(TOKEN_URI)).form(&[
+                ("grant_type", "refresh_token"),
+                ("client_id", client_id.as_str()),
+                ("client_secret", client_secret.as_str()),
+                ("refresh_token", refresh_token.as_str()),
+            ])
+        }
+    };
+
+    let response = request.send().await.map_err(ApiError::from_transport)?;
+    match response.status().as_u16() {
+        200 => {}
+        400 | 401 => return Err(ApiError::InvalidKey),
+        _ => return Err(ApiError::Other(response.text().await.unwrap_or_default())),
+    }
+    let token: TokenResponse = response
+        .json()
+        .await
+        .map_err(|e| ApiError::Other(format!("Failed to parse token response: {}", e)))?;
+    Ok(token.access_token)
+}
+
+/// `GOOGLE_APPLICATION_CREDENTIALS`, then gcloud's application-default file
+fn default_credentials_path() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os("GOOGLE_APPLICATION_CREDENTIALS") {
+        return Some(PathBuf::from(path));
+    }
+    let gcloud_dir = match std::env::var_os("CLOUDSDK_CONFIG") {
+        Some(dir) => PathBuf::from(dir),
+        None if cfg!(windows) => PathBuf::from(std::env::var_os("APPDATA")?).join("gcloud"),
+        None => PathBuf::from(std::env::var_os("HOME")?).join(".config/gcloud"),
+    };
+    let path = gcloud_dir.join("application_default_credentials.json");
+    path.exists().then_some(path)
+}
+
+/// A signed JWT asking `token_uri` for a cloud-platform token on behalf of the service account
+fn sign_assertion(client_email: &str, private_key: &str, token_uri: &str) -> ApiResult<String> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let claims = Claims { iss: client_email, scope: SCOPE, aud: token_uri, iat: now, exp: now + 3600 };
+    let key = EncodingKey::from_rsa_pem(private_key.as_bytes())
+        .map_err(|e| ApiError::Other(format!("Invalid service-account private key: {}", e)))?;
+    jsonwebtoken::encode(&Header::new(Algorithm::RS256), &claims, &key)
+        .map_err(|e| ApiError::Other(format!("Failed to sign token request: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::TempDir;
+    use wiremock::matchers::{body_string_contains, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_authorized_user_refresh() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/token"))
+            .and(body_string_contains("grant_type=refresh_token"))
+            .and(body_string_contains("refresh_token=1%2Frefresh"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "access_token": "ya29.fresh",
+                "expires_in": 3599,
+                "token_type": "Bearer"
+            })))
+            .mount(&mock_server)
+            .await;
+
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("adc.json");
+        std::fs::write(&file, json!({
+            "type": "authorized_user",
+            "client_id": "id",
+            "client_secret": "secret",
+            "refresh_token": "1/refresh",
+            "token_uri": format!("{}/token", mock_server.uri())
+        }).to_string()).unwrap();
+
+        assert_eq!(access_token(Some(&file)).await.unwrap(), "ya29.fresh");
+    }
+
+    #[tokio::test]
+    async fn test_rejected_credentials() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(400).set_body_json(json!({ "error": "invalid_grant" })))
+            .mount(&mock_server)
+            .await;
+
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("adc.json");
+        std::fs::write(&file, json!({
+            "type": "authorized_user",
+            "client_id": "id",
+            "client_secret": "secret",
+            "refresh_token": "revoked",
+            "token_uri": mock_server.uri()
+        }).to_string()).unwrap();
+
+        assert!(matches!(access_token(Some(&file)).await, Err(ApiError::InvalidKey)));
+    }
+
+    #[tokio::test]
+    async fn test_bad_service_account_key() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("sa.json");
+        std::fs::write(&file, json!({
+            "type": "service_account",
+            "client_email": "q@project.iam.gserviceaccount.com",
+            "private_key": "not a pem"
+        }).to_string()).unwrap();
+
+        let error = access_token(Some(&file)).await.unwrap_err();
+        assert!(error.to_string().contains("Invalid service-account private key"));
+    }
+}
@@ -1,25 +1,18 @@
-use clap::{Parser, Subcommand, ValueEnum};
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory, Parser, Subcommand, ValueEnum};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::net::SocketAddr;
 use std::path::PathBuf;
-use std::env;
-use std::sync::Arc;
-use std::time::Duration;
-use colored::*;
-
-use crate::utils::errors::QError;
-use crate::utils::format::format_markdown;
-use crate::config::types::Provider;
-use crate::api::{openai::OpenAIClient, gemini::GeminiClient, LLMApi};
-use crate::context::{ContextConfig, ContextProvider};
-use crate::context::directory::DirectoryProvider;
-use crate::context::file::FileProvider;
-use crate::context::history::HistoryProvider;
-use crate::commands::suggest::process_command_query;
-use crate::core::{QueryEngine, QueryConfig};
-use crate::config::ConfigManager;
-
-#[derive(Debug, Clone, Copy, ValueEnum)]
+
+use crate::context::log::LogSpec;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Verbosity {
     /// Concise responses with essential information only
+    #[default]
     Concise,
     /// Balanced responses with moderate detail
     Normal,
@@ -27,20 +20,48 @@ pub enum Verbosity {
     Detailed,
 }
 
-impl Default for Verbosity {
-    fn default() -> Self {
-        Self::Concise
-    }
+/// Context sources for `--ctx` and `default_context`, named after their flags
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContextFlag {
+    /// Shell history (--hist)
+    Hist,
+    /// Current directory listing (--here)
+    Here,
+    /// Git repository summary (--repo)
+    Repo,
+    /// Uncommitted git diff (--diff)
+    Diff,
+    /// Cargo package, features, and dependencies (--cargo)
+    Cargo,
+    /// Running containers and Compose files (--docker)
+    Docker,
+    /// System information (--sys)
+    Sys,
 }
 
-#[derive(Parser)]
+impl ContextFlag {
+    /// The kinds a repository's `.q.toml` may turn on: they only read the
+    /// project, never the user's shell history or machine
+    pub const PROJECT_SCOPED: &'static [ContextFlag] = &[Self::Here, Self::Repo, Self::Diff, Self::Cargo, Self::Docker];
+}
+
+#[derive(Parser, Clone)]
 #[command(name = "q")]
 #[command(author, version, about = "CLI tool for querying LLMs", long_about = None)]
 pub struct Cli {
     /// The prompt to send to the LLM
-    #[arg(help = "The prompt to send to the LLM", value_parser = validate_prompt)]
+    #[arg(help = "The prompt to send to the LLM (after `--` when it starts with '-')")]
     pub prompt: Option<String>,
 
+    /// Read the prompt from a file, or from stdin with `-`, as is: it may start with '-' or span lines
+    #[arg(long = "prompt-file", value_name = "PATH|-", conflicts_with = "prompt")]
+    pub prompt_file: Option<PathBuf>,
+
+    /// Run a prompt template from the config's templates/ directory, with the prompt as its input
+    #[arg(long = "template", short = 't', value_name = "NAME", conflicts_with_all = ["audio", "cmd_suggest"])]
+    pub template: Option<String>,
+
     /// Include shell history context
     #[arg(long = "hist", short = 'H')]
     pub history: bool,
@@ -49,18 +70,175 @@ pub struct Cli {
     #[arg(long = "here", short = 'D')]
     pub directory: bool,
 
-    /// Include file content
+    /// Follow symlinks in the directory listing (links leaving the directory are never followed)
+    #[arg(long = "follow-links", requires = "directory")]
+    pub follow_links: bool,
+
+    /// Include file content (repeat for more files)
     #[arg(long = "file", short = 'F', value_name = "FILE")]
-    pub file: Option<PathBuf>,
+    pub file: Vec<PathBuf>,
+
+    /// Include a log file's last lines or a recent window: PATH, PATH:200, or PATH:since=10m
+    #[arg(long = "log", value_name = "PATH[:N|:since=DUR]")]
+    pub log: Option<LogSpec>,
+
+    /// Run a shell command and include its output (stdout and stderr) and exit status
+    #[arg(long = "exec", value_name = "CMD")]
+    pub exec: Option<String>,
+
+    /// Don't read piped stdin as context, or as the prompt when none is given
+    #[arg(long = "no-stdin")]
+    pub no_stdin: bool,
+
+    /// Include a command's installed man page (or --help output), for questions about its flags
+    #[arg(long = "man", value_name = "CMD")]
+    pub man: Option<String>,
+
+    /// Include an outline of the modules, types, and function signatures under a path
+    #[arg(long = "outline", value_name = "PATH")]
+    pub outline: Option<PathBuf>,
+
+    /// Include a git summary (branch, recent commits, uncommitted changes, remotes)
+    #[arg(long = "repo")]
+    pub repo: bool,
+
+    /// Include the Cargo package: features, dependencies with locked versions, and workspace
+    #[arg(long = "cargo")]
+    pub cargo: bool,
+
+    /// Include running containers (docker ps) and the directory's Compose file and Dockerfile
+    #[arg(long = "docker")]
+    pub docker: bool,
+
+    /// With --docker, also include the last log lines of this container
+    #[arg(long = "container", value_name = "NAME", requires = "docker")]
+    pub container: Option<String>,
+
+    /// Include system information (OS, CPU, memory, disks, load, top processes)
+    #[arg(long = "sys")]
+    pub system: bool,
+
+    /// Include the uncommitted git diff (staged and unstaged)
+    #[arg(long = "diff")]
+    pub diff: bool,
+
+    /// Include several kinds of context at once, e.g. --ctx hist,here,diff
+    #[arg(long = "ctx", value_enum, value_delimiter = ',', value_name = "LIST")]
+    pub ctx: Vec<ContextFlag>,
+
+    /// Transcribe an audio file and use it as the prompt (or as context when a prompt is given)
+    #[arg(long = "audio", value_name = "FILE")]
+    pub audio: Option<PathBuf>,
+
+    /// Search the web and cite sources (built-in search, or the [web] search API)
+    #[arg(long = "web")]
+    pub web: bool,
+
+    /// Read the response aloud (OpenAI text-to-speech)
+    #[arg(long = "speak")]
+    pub speak: bool,
+
+    /// Save --speak's speech to FILE as MP3 instead of playing it
+    #[arg(long = "speech-file", value_name = "FILE", requires = "speak")]
+    pub speech_file: Option<PathBuf>,
+
+    /// Also write the answer to FILE as it arrives; with a subcommand, write its output there
+    /// instead of stdout
+    #[arg(long = "output", short = 'o', value_name = "FILE", global = true)]
+    pub output: Option<PathBuf>,
+
+    /// Print stdout as one JSON object at the end: {"answers": [...], "output": "..."}
+    #[arg(long = "json", global = true)]
+    pub json: bool,
+
+    /// Stable output for scripts and launchers: versioned `key: value` lines (JSON objects with
+    /// --json), each flushed at once; never prompts, and no spinner or colors
+    #[arg(long = "porcelain", global = true)]
+    pub porcelain: bool,
+
+    /// Copy the answer to the clipboard once it's complete
+    #[arg(long = "copy")]
+    pub copy: bool,
+
+    /// Only produce audio: don't print the response or status lines
+    #[arg(long = "quiet", short = 'q', requires = "speak")]
+    pub quiet: bool,
 
     /// Get command suggestions
     #[arg(long = "cmd", short = 'C')]
     pub cmd_suggest: bool,
 
+    /// With --cmd, show how each suggestion scored against the query
+    #[arg(long = "why", requires = "cmd_suggest")]
+    pub why: bool,
+
+    /// With --cmd, show examples for every shell and system, not just yours
+    #[arg(long = "all-examples", requires = "cmd_suggest")]
+    pub all_examples: bool,
+
+    /// Stream the response token by token
+    #[arg(long = "stream")]
+    pub stream: bool,
+
+    /// Generate several answers, labeled A, B, C, ... (not streamed)
+    #[arg(long = "n", value_name = "N", default_value = "1", value_parser = clap::value_parser!(u8).range(1..=10))]
+    pub n: u8,
+
+    /// With --n, choose one answer interactively; only it goes to stdout
+    #[arg(long = "pick", requires = "n")]
+    pub pick: bool,
+
+    /// Ask the [consensus] models, then have the judge merge their answers and note disagreements
+    #[arg(long = "consensus", conflicts_with_all = ["n", "stream", "runbook"])]
+    pub consensus: bool,
+
+    /// Read context too big for one prompt in parts, asking each part the
+    /// question, then combine the answers in a last query
+    #[arg(long = "map-reduce", conflicts_with = "consensus")]
+    pub map_reduce: bool,
+
+    /// Answer as numbered steps with a verification command each, then offer to run them one by one
+    #[arg(long = "runbook", conflicts_with = "n")]
+    pub runbook: bool,
+
+    /// Sampling temperature, 0.0-2.0 [default: from the template, preset, or config]
+    #[arg(long = "temperature", value_name = "T", value_parser = parse_temperature)]
+    pub temperature: Option<f64>,
+
+    /// Longest answer, in tokens [default: from the template or preset, else the provider's]
+    #[arg(long = "max-tokens", value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    pub max_tokens: Option<u32>,
+
+    /// Sample with this seed at temperature 0 so repeated runs give the same
+    /// answer where the provider supports it (OpenAI, Gemini, Mistral, Groq)
+    #[arg(long = "seed", value_name = "SEED")]
+    pub seed: Option<u64>,
+
+    /// List the context blocks (sizes and previews) and choose which to send before sending
+    #[arg(long = "review-context")]
+    pub review_context: bool,
+
+    /// Show the prompt size, the model's prices, and a projected cost without
+    /// sending anything (with --verbose, print the prompt too)
+    #[arg(long = "dry-run")]
+    pub dry_run: bool,
+
+    /// Don't offer the `>` prompt for follow-up questions after the answer
+    #[arg(long = "no-follow-up")]
+    pub no_follow_up: bool,
+
+    /// Leave no trace on disk: skip the history log and the startup cleanup
+    #[arg(long = "incognito")]
+    pub incognito: bool,
+
     /// Disable response caching
     #[arg(long = "no-cache")]
     pub no_cache: bool,
 
+    /// Ignore the [defaults] section of the config file
+    #[arg(long = "no-defaults")]
+    pub no_defaults: bool,
+
     /// Maximum retry attempts
     #[arg(long = "retries", default_value = "3")]
     pub max_retries: u32,
@@ -69,13 +247,22 @@ pub struct Cli {
     #[arg(long = "debug")]
     pub debug: bool,
 
+    /// After the answer, print the model, time taken, the provider's request
+    /// ID, and its system fingerprint
+    #[arg(long = "stats")]
+    pub stats: bool,
+
     /// Show verbose output
     #[arg(long = "verbose", short = 'v')]
     pub verbose: bool,
 
-    /// Select LLM provider (openai or gemini)
-    #[arg(long = "provider", short = 'P', default_value = "gemini")]
-    pub provider: String,
+    /// Select LLM provider (openai, gemini, openrouter, groq, mistral) [default: from config]
+    #[arg(long = "provider", short = 'P', global = true)]
+    pub provider: Option<String>,
+
+    /// Use a named profile from the config (keys, default provider, models)
+    #[arg(long = "profile", global = true, value_name = "NAME")]
+    pub profile: Option<String>,
 
     /// Select model name (e.g., gemini-pro, gpt-3.5-turbo)
     #[arg(long = "model", short = 'M')]
@@ -89,199 +276,607 @@ pub struct Cli {
     pub command: Option<Commands>,
 }
 
-#[derive(Subcommand)]
+#[derive(Subcommand, Clone)]
 pub enum Commands {
     /// Set API key for LLM service
     SetKey {
-        /// The LLM provider (openai or gemini)
-        #[arg(help = "The LLM provider (openai or gemini)")]
+        /// The LLM provider (openai, gemini, openrouter, groq, mistral)
+        #[arg(help = "The LLM provider (openai, gemini, openrouter, groq, mistral)")]
         provider: String,
 
-        /// The API key to set
-        #[arg(help = "The API key to set")]
-        key: String,
+        /// The API key to set (prompted for with hidden input when omitted)
+        #[arg(help = "The API key to set (prompted for with hidden input when omitted)")]
+        key: Option<String>,
+
+        /// Read the API key from stdin
+        #[arg(long = "from-stdin", conflicts_with_all = ["key", "from_file"])]
+        from_stdin: bool,
+
+        /// Read the API key from a file
+        #[arg(long = "from-file", value_name = "FILE", conflicts_with = "key")]
+        from_file: Option<PathBuf>,
+
+        /// Skip verifying the key against the provider before saving
+        #[arg(long = "no-verify")]
+        no_verify: bool,
+
+        /// Add the key to the provider's rotation list instead of replacing it
+        #[arg(long = "add")]
+        add: bool,
+    },
+
+    /// List, remove, or test configured API keys
+    Keys {
+        #[command(subcommand)]
+        action: KeysCommand,
+    },
+
+    /// Inspect and check the config file
+    Config {
+        #[command(subcommand)]
+        action: ConfigCommand,
+    },
+
+    /// Manage named profiles
+    Profile {
+        #[command(subcommand)]
+        action: ProfileCommand,
     },
 
     /// Set default LLM provider
     SetProvider {
-        /// The LLM provider (openai or gemini)
-        #[arg(help = "The LLM provider (openai or gemini)")]
+        /// The LLM provider (openai, gemini, openrouter, groq, mistral)
+        #[arg(help = "The LLM provider (openai, gemini, openrouter, groq, mistral)")]
         provider: String,
     },
 
     /// Set model for LLM provider
     SetModel {
-        /// The LLM provider (openai or gemini)
-        #[arg(help = "The LLM provider (openai or gemini)")]
+        /// The LLM provider (openai, gemini, openrouter, groq, mistral)
+        #[arg(help = "The LLM provider (openai, gemini, openrouter, groq, mistral)")]
         provider: String,
 
         /// The model name to set
         #[arg(help = "The model name to set")]
         model: String,
     },
+
+    /// List the models a provider serves (use -P to pick the provider)
+    ListModels {
+        /// Only show models whose id contains this text
+        #[arg(long = "search", short = 's')]
+        search: Option<String>,
+
+        /// Page of results to show
+        #[arg(long = "page", default_value_t = 1)]
+        page: usize,
+
+        /// Models per page
+        #[arg(long = "per-page", default_value_t = 50)]
+        per_page: usize,
+    },
+
+    /// Write a pull request title and description for the current branch
+    Pr {
+        /// Branch to compare against [default: the remote's default branch]
+        base: Option<String>,
+
+        /// Open the PR with `gh pr create`
+        #[arg(long = "create")]
+        create: bool,
+    },
+
+    /// Write a conventional-commit header for the staged changes
+    Commit {
+        /// Commit type, e.g. feat or fix
+        #[arg(long = "type", value_name = "TYPE")]
+        kind: Option<String>,
+
+        /// Commit scope, e.g. api (one of [commit] scopes when set)
+        #[arg(long = "scope")]
+        scope: Option<String>,
+
+        /// Mark the change as breaking (`type!:`)
+        #[arg(long = "breaking")]
+        breaking: bool,
+    },
+
+    /// Name a branch after an issue title or the uncommitted changes
+    BranchName {
+        /// The issue title [default: describe the uncommitted diff]
+        title: Option<String>,
+    },
+
+    /// Summarize commits as Keep a Changelog entries
+    Changelog {
+        /// Commit range, e.g. v1.2.0..HEAD [default: latest tag to HEAD]
+        range: Option<String>,
+
+        /// Also send the diff for the range (slower, more precise)
+        #[arg(long = "diff")]
+        diff: bool,
+    },
+
+    /// Summarize a diff or commit range by area, with risks to review
+    WhatChanged {
+        /// Commit range such as main..feature, or one commit [default: uncommitted changes]
+        range: Option<String>,
+    },
+
+    /// Run the build and ask for a fix for its errors, with the source they point at
+    FixBuild {
+        /// Print only a unified diff, for `git apply`
+        #[arg(long = "patch")]
+        patch: bool,
+
+        /// The build command [default: cargo build]
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        command: Vec<String>,
+    },
+
+    /// Run the tests and ask for likely causes and a fix for the failures
+    FixTest {
+        /// Only run tests whose names contain this (with the default `cargo test`)
+        #[arg(long = "filter", value_name = "NAME")]
+        filter: Option<String>,
+
+        /// Print only a unified diff, for `git apply`
+        #[arg(long = "patch")]
+        patch: bool,
+
+        /// The test command [default: cargo test]
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        command: Vec<String>,
+    },
+
+    /// Explain a shell command flag by flag, using its installed man page
+    Explain {
+        /// The command line, e.g. tar -xzvf backup.tgz
+        #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+        command: Vec<String>,
+    },
+
+    /// Write one SQL query, grounded in a schema file
+    Sql {
+        /// What the query should return, e.g. "monthly active users"
+        question: String,
+
+        /// Schema DDL (e.g. pg_dump --schema-only output)
+        #[arg(long = "schema", value_name = "FILE")]
+        schema: Option<PathBuf>,
+
+        /// SQL dialect, e.g. postgres, mysql, sqlite
+        #[arg(long = "dialect")]
+        dialect: Option<String>,
+    },
+
+    /// Write one jq filter, checked against a sample input
+    Jq {
+        /// What the filter should do, e.g. "group by status"
+        question: String,
+
+        /// JSON input the filter should work on
+        #[arg(long = "sample", value_name = "FILE")]
+        sample: Option<PathBuf>,
+    },
+
+    /// Write a regular expression and check it against test cases
+    Regex {
+        /// What the pattern should match, e.g. "ISO dates but not times"
+        description: String,
+
+        /// Test cases, one per line: `+ text` must match, `- text` must not
+        #[arg(long = "test", value_name = "FILE")]
+        test: Option<PathBuf>,
+
+        /// Tries before giving up when cases fail
+        #[arg(long = "attempts", default_value_t = 4, value_parser = clap::value_parser!(u32).range(1..=20))]
+        attempts: u32,
+    },
+
+    /// Write a crontab line (and a systemd OnCalendar expression) for a schedule in plain words
+    Cron {
+        /// The schedule, e.g. "every weekday at 7am"
+        when: String,
+
+        /// Also give the systemd timer OnCalendar expression
+        #[arg(long = "systemd")]
+        systemd: bool,
+
+        /// The command to put in the crontab line
+        #[arg(long = "command", value_name = "CMD")]
+        command: Option<String>,
+    },
+
+    /// Save, list, or remove aliases: named invocations run as `q <alias>`
+    Alias {
+        #[command(subcommand)]
+        action: AliasCommand,
+    },
+
+    /// Run a preset from the config's [presets] table (lists them when no name is given)
+    Ask {
+        /// The preset name, e.g. sql
+        preset: Option<String>,
+
+        /// The input for the preset
+        input: Option<String>,
+    },
+
+    /// Install, list, or show the prompt templates run with -t
+    Template {
+        #[command(subcommand)]
+        action: TemplateCommand,
+    },
+
+    /// Run an eval file's prompts against two configurations and compare the answers
+    Eval {
+        /// TOML file with [a], [b], an optional rubric, and [[case]] prompts
+        file: PathBuf,
+    },
+
+    /// Ask two models the same question and show the answers side by side
+    Compare {
+        /// The prompt
+        prompt: String,
+
+        /// The two models, each `model` or `provider/model`, e.g. openai/gpt-4o,gemini/gemini-2.5-flash
+        #[arg(long = "models", value_delimiter = ',', required = true, value_name = "A,B")]
+        models: Vec<String>,
+
+        /// Show one word-level diff of the answers instead of two columns
+        #[arg(long = "diff")]
+        diff: bool,
+    },
+
+    /// Re-run a query whenever the watched files change
+    Watch {
+        /// The prompt
+        prompt: String,
+
+        /// A file to watch and include as context (repeat for more files)
+        #[arg(long = "file", short = 'F', required = true, value_name = "FILE")]
+        files: Vec<PathBuf>,
+
+        /// Milliseconds without changes to wait before re-running, so a burst of saves runs once
+        #[arg(long = "debounce", default_value_t = 300, value_name = "MS")]
+        debounce: u64,
+    },
+
+    /// Answer requests from editor plugins and other local tools
+    Serve {
+        /// Read JSON-RPC requests from stdin and write replies to stdout, one per line
+        #[arg(long = "stdio", required_unless_present = "http", conflicts_with = "http")]
+        stdio: bool,
+
+        /// Listen for HTTP requests, OpenAI's /v1/chat/completions among them, e.g. 127.0.0.1:8787
+        #[arg(long = "http", value_name = "ADDR")]
+        http: Option<SocketAddr>,
+    },
+
+    /// Save prompts while offline and send them once the network is back
+    Queue {
+        #[command(subcommand)]
+        action: QueueCommand,
+    },
+
+    /// Save queries to send on a schedule; `q schedule run` from cron sends the ones due
+    Schedule {
+        #[command(subcommand)]
+        action: ScheduleCommand,
+    },
+
+    /// Rate the last answer (👍 or 👎) for `q history stats`
+    Feedback {
+        /// 👍 or 👎 (or up/down)
+        #[arg(allow_hyphen_values = true)]
+        rating: String,
+
+        /// Why, in a few words
+        #[arg(trailing_var_arg = true)]
+        comment: Vec<String>,
+    },
+
+    /// Summarize the query history log
+    History {
+        #[command(subcommand)]
+        action: HistoryCommand,
+    },
+
+    /// Prune history, sessions, and the cache to their [retention] limits
+    Gc,
+
+    /// List the command database behind --cmd suggestions
+    Commands {
+        #[command(subcommand)]
+        action: CommandsCommand,
+    },
+
+    /// Update q to the latest GitHub release
+    SelfUpdate {
+        /// Only report whether an update is available
+        #[arg(long = "check")]
+        check: bool,
+    },
 }
 
-impl Cli {
-    pub async fn run(&self) -> Result<(), QError> {
-        if let Some(cmd) = &self.command {
-            cmd.execute(&self)?;
-            return Ok(());
-        }
+#[derive(Subcommand, Clone)]
+pub enum AliasCommand {
+    /// Save the arguments after `--` as an alias, e.g. `q alias add rev -- --diff "review this change"`
+    Add {
+        /// The alias, run as `q <name>`
+        name: String,
 
-        // Handle the prompt if present
-        if let Some(prompt) = &self.prompt {
-            // Handle command suggestions
-            if self.cmd_suggest {
-                let suggestions = process_command_query(prompt)
-                    .await
-                    .map_err(|e| QError::Command(format!("Failed to get command suggestions: {}", e)))?;
-                println!("{}", format_markdown(&suggestions));
-                return Ok(());
-            }
-
-            // Get provider from command line
-            let provider = Provider::try_from(self.provider.as_str())
-                .map_err(|e| QError::Config(format!("Invalid provider: {}", e)))?;
-
-            // Get API key from config
-            let config = ConfigManager::new(self.verbose)?;
-            let api_key = config.get_api_key(provider)
-                .ok_or_else(|| QError::Config(format!("{} API key not found. Use 'q set-key {} <key>' to set it.", provider, provider)))?;
-
-            // Gather context if requested
-            let mut context = String::new();
-            let context_config = ContextConfig::default();
-
-            // Add shell history context
-            if self.history {
-                let provider = HistoryProvider::new(context_config.clone());
-                let history_context = provider.get_context().await
-                    .map_err(|e| QError::Context(format!("Failed to get history context: {}", e)))?;
-                context.push_str(&history_context.content);
-                context.push_str("\n\n");
-            }
-
-            // Add directory listing context
-            if self.directory {
-                let current_dir = env::current_dir()
-                    .map_err(|e| QError::Context(format!("Failed to get current directory: {}", e)))?;
-                let provider = DirectoryProvider::new(current_dir, context_config.clone());
-                let dir_context = provider.get_context().await
-                    .map_err(|e| QError::Context(format!("Failed to get directory context: {}", e)))?;
-                context.push_str(&dir_context.content);
-                context.push_str("\n\n");
-            }
-
-            // Add file content context
-            if let Some(file_path) = &self.file {
-                let provider = FileProvider::new(file_path.clone(), context_config.clone());
-                let file_context = provider.get_context().await
-                    .map_err(|e| QError::Context(format!("Failed to get file context: {}", e)))?;
-                context.push_str(&file_context.content);
-                context.push_str("\n\n");
-            }
-
-            // Build the final prompt with context
-            let final_prompt = if context.is_empty() {
-                prompt.clone()
-            } else {
-                format!("Context:\n{}\nPrompt: {}", context.trim(), prompt)
-            };
-
-            // Create client based on provider
-            let client: Arc<dyn LLMApi> = match provider {
-                Provider::OpenAI => {
-                    let mut builder = OpenAIClient::builder(api_key.to_string());
-                    if let Some(model) = &self.model {
-                        builder = builder.with_model(model.clone());
-                    }
-                    builder = builder.with_verbosity(self.verbosity);
-                    Arc::new(builder.build())
-                }
-                Provider::Gemini => {
-                    let mut builder = GeminiClient::builder(api_key.to_string());
-                    if let Some(model) = &self.model {
-                        builder = builder.with_model(model.clone());
-                    }
-                    builder = builder.with_verbosity(self.verbosity);
-                    Arc::new(builder.build())
-                }
-            };
-
-            // Show connecting message with provider and model info
-            eprintln!("{}", format!("provider: {}, model: {}", provider, client.model()).dimmed());
-
-            // Create query engine config
-            let config = QueryConfig {
-                max_retries: self.max_retries,
-                show_progress: !self.debug,
-                cache_ttl: Duration::from_secs(3600),
-                max_cache_size: 1000,
-                retry_delay: Duration::from_secs(1),
-                max_retry_delay: Duration::from_secs(30),
-                verbosity: self.verbosity,
-            };
-
-            // Create query engine
-            let mut engine = QueryEngine::new(client, config);
-
-            // Send the query through the engine
-            let response = engine.query(&final_prompt)
-                .await
-                .map_err(|e| QError::Core(format!("Query failed: {}", e)))?;
-
-            println!("{}", format_markdown(&response));
-            return Ok(());
-        }
+        /// The flags and prompt it stands for
+        #[arg(last = true, required = true, value_name = "ARGS")]
+        args: Vec<String>,
+    },
+
+    /// Show saved aliases and what they expand to
+    List,
+
+    /// Delete an alias
+    Remove {
+        name: String,
+    },
+}
+
+#[derive(Subcommand, Clone)]
+pub enum TemplateCommand {
+    /// Fetch a template from a URL, a gist, or a file, and install it once its checksum is confirmed
+    Install {
+        /// An http(s) URL, a gist (gist:ID or its URL), or a path
+        source: String,
+
+        /// Install it under this name instead of its file name
+        #[arg(long = "name", value_name = "NAME")]
+        name: Option<String>,
+
+        /// Install without asking when its SHA-256 checksum is this, and refuse it otherwise
+        #[arg(long = "sha256", value_name = "HEX")]
+        sha256: Option<String>,
+
+        /// Replace an installed template with the same name
+        #[arg(long = "force")]
+        force: bool,
+    },
+
+    /// Show the installed templates and their descriptions
+    List,
+
+    /// Print a template's file
+    Show {
+        name: String,
+    },
+}
+
+#[derive(Subcommand, Clone)]
+pub enum CommandsCommand {
+    /// Show the installed database version and the commands it knows
+    List,
+}
+
+#[derive(Subcommand, Clone)]
+pub enum KeysCommand {
+    /// Show configured providers with masked keys and where each key comes from
+    List,
+
+    /// Remove the stored key for a provider
+    Remove {
+        /// The LLM provider (openai, gemini, openrouter, groq, mistral)
+        provider: String,
+    },
+
+    /// Check a provider's key against the live API
+    Test {
+        /// The LLM provider (openai, gemini, openrouter, groq, mistral)
+        provider: String,
+    },
+}
+
+#[derive(Subcommand, Clone)]
+pub enum ConfigCommand {
+    /// Check config.toml for unknown keys, type errors, and unrecognized models
+    Validate,
+    /// Print a config value (e.g. temperature, defaults.stream)
+    Get {
+        /// Dotted key; keys outside [defaults] and [profiles] are read from [settings]
+        key: String,
+    },
+    /// Set a config value (e.g. `q config set temperature 0.2`)
+    Set {
+        /// Dotted key; keys outside [defaults] and [profiles] are written to [settings]
+        key: String,
+        /// New value, as a TOML literal or a plain string
+        value: String,
+    },
+    /// Write the shareable settings (presets, defaults, profiles, ...) without API keys
+    Export {
+        /// File to write the bundle to; prints it when omitted
+        #[arg(long = "bundle")]
+        bundle: Option<PathBuf>,
+    },
+    /// Merge a bundle from `q config export` into this config
+    Import {
+        /// The bundle file
+        file: PathBuf,
+    },
+}
+
+#[derive(Subcommand, Clone)]
+pub enum QueueCommand {
+    /// Save a prompt to send later, with the provider and model given now
+    Add {
+        /// The prompt
+        prompt: String,
+    },
+
+    /// Show the queued prompts and answers not yet shown
+    List,
+
+    /// Send the queued prompts and print their answers, stopping while the network is down
+    Flush,
+
+    /// Drop a queued prompt, numbered as in `q queue list`
+    Remove {
+        number: usize,
+    },
+
+    /// Drop every queued prompt and unshown answer
+    Clear,
+}
+
+#[derive(Subcommand, Clone)]
+pub enum ScheduleCommand {
+    /// Save a prompt to send on a crontab schedule, with the provider and model given now
+    Add {
+        /// The prompt
+        prompt: String,
+
+        /// When to send it: five crontab fields or an @nickname, e.g. "0 8 * * *"
+        #[arg(long)]
+        cron: String,
+
+        /// The schedule's name (default: from the prompt's first words)
+        #[arg(long)]
+        name: Option<String>,
+
+        /// Show the start of each answer in a desktop notification
+        #[arg(long)]
+        notify: bool,
+
+        /// Append each answer to FILE
+        #[arg(long, value_name = "FILE")]
+        save: Option<PathBuf>,
+    },
+
+    /// Show saved schedules and their next runs
+    List,
 
-        // If we get here, no prompt was provided
-        Err(QError::Usage("No prompt provided. Use --help for usage information.".into()))
+    /// Delete a schedule
+    Remove {
+        name: String,
+    },
+
+    /// Send the schedules that are due (for cron or a systemd timer), or the one named now
+    Run {
+        name: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Clone)]
+pub enum HistoryCommand {
+    /// Queries and 👍/👎 ratings per model and per preset
+    Stats,
+
+    /// Encrypt records written before `[history] encrypt` was turned on
+    Encrypt,
+}
+
+#[derive(Subcommand, Clone)]
+pub enum ProfileCommand {
+    /// List profiles, marking the active one
+    List,
+
+    /// Make a profile the default (use "none" to go back to top-level settings)
+    Use {
+        /// The profile name
+        name: String,
+    },
+}
+
+/// Subcommand names, which are never accepted as a prompt
+const SUBCOMMANDS: &[&str] = &[
+    "set-key", "keys", "config", "profile", "set-provider", "set-model", "list-models", "pr", "commit", "branch-name", "changelog", "what-changed", "fix-build", "fix-test", "explain", "sql", "jq", "regex", "cron", "alias", "ask", "template", "eval", "compare", "watch", "serve", "queue", "schedule", "feedback", "history", "gc", "commands", "self-update",
+];
+
+/// Whether `name` is one of q's subcommands
+pub(crate) fn is_subcommand(name: &str) -> bool {
+    SUBCOMMANDS.contains(&name)
+}
+
+/// The command line with an alias in first position replaced by the
+/// arguments it stands for; the arguments after it are kept. Subcommands
+/// can't be shadowed, and an alias's own arguments aren't expanded again.
+pub(crate) fn expand_alias(mut args: Vec<OsString>, aliases: &HashMap<String, Vec<String>>) -> Vec<OsString> {
+    let expansion = args.get(1)
+        .and_then(|first| first.to_str())
+        .filter(|first| !is_subcommand(first))
+        .and_then(|first| aliases.get(first));
+    if let Some(expansion) = expansion {
+        let expansion: Vec<OsString> = expansion.iter().map(OsString::from).collect();
+        args.splice(1..2, expansion);
     }
+    args
 }
 
-impl Commands {
-    pub fn execute(&self, cli: &Cli) -> Result<(), QError> {
-        match self {
-            Commands::SetKey { provider, key } => {
-                let provider = Provider::try_from(provider.as_str())
-                    .map_err(|e| QError::Config(e))?;
-                
-                let mut config = ConfigManager::new(cli.verbose)?;
-                config.set_api_key(provider, key.clone())?;
-                
-                println!("{}", format_markdown(&format!("# API key for {} has been set successfully", provider)));
-                Ok(())
-            }
-            Commands::SetProvider { provider } => {
-                let provider = Provider::try_from(provider.as_str())
-                    .map_err(|e| QError::Config(e))?;
-                
-                let mut config = ConfigManager::new(cli.verbose)?;
-                config.set_default_provider(provider)?;
-                
-                println!("{}", format_markdown(&format!("# Default provider has been set to {}", provider)));
-                Ok(())
-            }
-            Commands::SetModel { provider, model } => {
-                let provider = Provider::try_from(provider.as_str())
-                    .map_err(|e| QError::Config(e))?;
-                
-                let mut config = ConfigManager::new(cli.verbose)?;
-                config.set_model(provider, model.clone())?;
-                
-                println!("{}", format_markdown(&format!("# Model for {} has been set to {}", provider, model)));
-                Ok(())
-            }
-        }
+/// Reject a prompt that looks like a mistyped flag or subcommand: one that
+/// starts with '-', or a subcommand's name as the first argument. A prompt
+/// after the `--` separator is taken as is.
+pub(crate) fn validate_prompt(args: &[OsString], prompt: &str) -> Result<(), String> {
+    let after_separator = args.iter()
+        .skip(1)
+        .position(|arg| arg == "--")
+        .is_some_and(|separator| args.get(separator + 2).is_some_and(|arg| arg == prompt));
+    if after_separator {
+        return Ok(());
+    }
+    let first = args.get(1).is_some_and(|arg| arg == prompt);
+    if prompt.starts_with('-') || (first && is_subcommand(prompt)) {
+        return Err(format!(
+            "'{}' is not a valid prompt. Use --help to see available commands, or put the prompt after `--` to send it as is.",
+            prompt
+        ));
     }
+    Ok(())
 }
 
-fn validate_prompt(s: &str) -> Result<String, String> {
-    // If the input looks like a command (starts with '-' or contains subcommand names),
-    // reject it to ensure proper error handling
-    if s.starts_with('-') || s == "set-key" || s == "set-provider" || s == "set-model" {
-        Err(format!("'{}' is not a valid prompt. Use --help to see available commands.", s))
-    } else {
-        Ok(s.to_string())
+/// The query flags `q schedule add` takes: a schedule saves only its prompt,
+/// provider, and model, and the rest only shape what `add` itself prints
+const SCHEDULED_FLAGS: &[&str] = &["provider", "model", "verbose", "debug", "no_defaults", "output", "json", "porcelain"];
+
+/// Reject flags given with `q schedule add` that its schedule wouldn't keep
+pub(crate) fn validate_schedule(matches: &ArgMatches) -> Result<(), String> {
+    let command = Cli::command();
+    let given = command.get_arguments().find(|arg| {
+        let id = arg.get_id().as_str();
+        !SCHEDULED_FLAGS.contains(&id) && matches.value_source(id) == Some(ValueSource::CommandLine)
+    });
+    let Some(arg) = given else { return Ok(()) };
+    let flag = arg.get_long().unwrap_or(arg.get_id().as_str());
+    Err(format!(
+        "--{} isn't saved with a schedule, which sends only its prompt (with -P/-M); put what it needs in the prompt",
+        flag
+    ))
+}
+
+fn parse_temperature(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(temperature) if (0.0..=2.0).contains(&temperature) => Ok(temperature),
+        _ => Err(format!("'{}' isn't a temperature from 0.0 to 2.0", s)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn validate(args: &[&str]) -> Result<(), String> {
+        let schedule = ["schedule", "add", "a git tip", "--cron", "0 8 * * *"];
+        let matches = Cli::command().try_get_matches_from(["q"].iter().chain(args).chain(&schedule)).unwrap();
+        validate_schedule(&matches)
+    }
+
+    #[test]
+    fn test_validate_schedule() {
+        assert_eq!(validate(&[]), Ok(()));
+        assert_eq!(validate(&["-P", "openai", "-M", "gpt-4o", "--json", "-v"]), Ok(()));
+        for flags in [
+            &["--web"][..], &["--speak"], &["--prompt-file", "p.txt"], &["--docker", "--container", "db"],
+            &["--here", "--follow-links"], &["--incognito"], &["--profile", "work"], &["--stream"],
+        ] {
+            let error = validate(flags).unwrap_err();
+            assert!(error.starts_with(&format!("{} isn't saved with a schedule", flags[0])), "{}", error);
+        }
     }
 }
@@ -0,0 +1,90 @@
+use colored::*;
+
+use super::load_config;
+use crate::cli::args::{is_subcommand, AliasCommand, Cli};
+use crate::utils::errors::QError;
+use crate::utils::output;
+
+pub fn run(cli: &Cli, action: &AliasCommand) -> Result<(), QError> {
+    match action {
+        AliasCommand::Add { name, args } => add(cli, name, args),
+        AliasCommand::List => list(cli),
+        AliasCommand::Remove { name } => {
+            load_config(cli)?.remove_alias(name)?;
+            output::line(format!("Removed alias {}", name));
+            Ok(())
+        }
+    }
+}
+
+fn add(cli: &Cli, name: &str, args: &[String]) -> Result<(), QError> {
+    check_name(name)?;
+    let mut config = load_config(cli)?;
+    let replaced = config.set_alias(name, args.to_vec())?;
+    output::line(format!(
+        "{} alias {}: q {}",
+        if replaced { "Replaced" } else { "Saved" },
+        name.green(),
+        shell_words(args)
+    ));
+    Ok(())
+}
+
+fn list(cli: &Cli) -> Result<(), QError> {
+    let config = load_config(cli)?;
+    let mut aliases: Vec<_> = config.aliases().iter().collect();
+    if aliases.is_empty() {
+        output::line("No aliases. Save one with 'q alias add <name> -- <flags and prompt>'".dimmed());
+        return Ok(());
+    }
+    aliases.sort();
+    let width = aliases.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+    for (name, args) in aliases {
+        output::line(format!("{:<width$}  q {}", name.green(), shell_words(args), width = width));
+    }
+    Ok(())
+}
+
+/// An alias must be one plain word that can't be mistaken for a flag or a subcommand
+fn check_name(name: &str) -> Result<(), QError> {
+    if name.is_empty() || name.starts_with('-') || name.chars().any(char::is_whitespace) {
+        return Err(QError::Usage(format!("'{}' can't be an alias; use one word that doesn't start with '-'", name)));
+    }
+    if is_subcommand(name) {
+        return Err(QError::Usage(format!("'{}' is a q subcommand and can't be an alias", name)));
+    }
+    Ok(())
+}
+
+/// `args` as they'd be typed, quoting any with spaces or quotes in them
+fn shell_words(args: &[String]) -> String {
+    args.iter()
+        .map(|arg| {
+            if !arg.is_empty() && !arg.contains(|c: char| c.is_whitespace() || "'\"\\$`".contains(c)) {
+                arg.clone()
+            } else {
+                format!("'{}'", arg.replace('\'', "'\\''"))
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_name() {
+        assert!(check_name("rev").is_ok());
+        assert!(check_name("--rev").is_err());
+        assert!(check_name("two words").is_err());
+        assert!(check_name("commit").is_err());
+    }
+
+    #[test]
+    fn test_shell_words() {
+        let args = ["--diff", "review this change", "it's", ""].map(String::from);
+        assert_eq!(shell_words(&args), r#"--diff 'review this change' 'it'\''s' ''"#);
+    }
+}
@@ -0,0 +1,93 @@
+use colored::*;
+
+use super::{load_config, query};
+use crate::cli::args::Cli;
+use crate::config::types::Preset;
+use crate::config::validate::suggest;
+use crate::config::ConfigManager;
+use crate::utils::errors::QError;
+use crate::utils::output;
+
+/// `q ask <preset> <input>`: a query with the preset's instructions, model,
+/// temperature, and context. Flags given on the command line still win.
+pub async fn run(cli: &Cli, name: Option<&str>, input: Option<&str>) -> Result<(), QError> {
+    let config = load_config(cli)?;
+    let Some(name) = name else {
+        return list(config.presets().iter().collect());
+    };
+
+    let preset = find_preset(&config, name)?;
+    let input = input.ok_or_else(|| QError::Usage(format!("q ask {} needs input, e.g. q ask {} \"...\"", name, name)))?;
+
+    let cli = with_preset(cli, preset);
+    query::run_with_preset(&cli, Some(input), Some((name, preset))).await
+}
+
+/// The preset called `name`, or an error suggesting the closest name
+pub(crate) fn find_preset<'a>(config: &'a ConfigManager, name: &str) -> Result<&'a Preset, QError> {
+    config.presets().get(name).ok_or_else(|| {
+        let names: Vec<&str> = config.presets().keys().map(String::as_str).collect();
+        let mut message = format!("No preset named '{}' in [presets]", name);
+        if let Some(suggestion) = suggest(name, &names) {
+            message.push_str(&format!("; did you mean '{}'?", suggestion));
+        }
+        QError::Usage(message)
+    })
+}
+
+/// `cli` with the preset's provider, model, and context filled in where the
+/// command line left them unset
+pub(crate) fn with_preset(cli: &Cli, preset: &Preset) -> Cli {
+    let mut cli = cli.clone();
+    cli.command = None;
+    if cli.provider.is_none() {
+        cli.provider = preset.provider.map(|provider| provider.to_string());
+    }
+    if cli.model.is_none() {
+        cli.model = preset.model.clone();
+    }
+    cli.ctx.extend(preset.context.iter().copied());
+    cli.apply_ctx();
+    cli
+}
+
+fn list(mut presets: Vec<(&String, &Preset)>) -> Result<(), QError> {
+    if presets.is_empty() {
+        output::line("No presets defined. Add one under [presets.<name>] in config.toml".dimmed());
+        return Ok(());
+    }
+    presets.sort_by_key(|(name, _)| name.as_str());
+    for (name, preset) in presets {
+        match &preset.description {
+            Some(description) => output::line(format!("{}  {}", name.green(), description)),
+            None => output::line(name.green()),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::args::ContextFlag;
+    use crate::config::types::Provider;
+    use clap::Parser;
+
+    #[test]
+    fn test_with_preset_fills_unset_flags() {
+        let preset = Preset {
+            provider: Some(Provider::OpenAI),
+            model: Some("gpt-4o".to_string()),
+            context: vec![ContextFlag::Here],
+            ..Preset::default()
+        };
+
+        let cli = with_preset(&Cli::parse_from(["q", "ask", "sql"]), &preset);
+        assert_eq!(cli.provider.as_deref(), Some("openai"));
+        assert_eq!(cli.model.as_deref(), Some("gpt-4o"));
+        assert!(cli.directory && cli.command.is_none());
+
+        let cli = with_preset(&Cli::parse_from(["q", "-M", "gpt-4o-mini", "ask", "sql"]), &preset);
+        assert_eq!(cli.model.as_deref(), Some("gpt-4o-mini"));
+    }
+}
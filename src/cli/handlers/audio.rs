@@ -0,0 +1,70 @@
+use std::path::Path;
+use std::sync::Arc;
+use colored::*;
+
+use super::connect;
+use crate::api::{audio_mime_type, LLMApi, ModelConfig};
+use crate::cli::args::Verbosity;
+use crate::config::types::Provider;
+use crate::config::ConfigManager;
+use crate::utils::errors::QError;
+
+/// Whisper's upload limit; Gemini's inline request limit is a little lower
+const MAX_AUDIO_BYTES: u64 = 25 * 1024 * 1024;
+const MAX_INLINE_AUDIO_BYTES: u64 = 20 * 1024 * 1024;
+
+/// Transcribe `path` with the query's provider when it can (OpenAI and Groq
+/// Whisper, Gemini audio input), otherwise with OpenAI Whisper.
+pub async fn transcribe(
+    config: &ConfigManager,
+    provider: Provider,
+    client: &Arc<dyn LLMApi>,
+    path: &Path,
+    quiet: bool,
+) -> Result<String, QError> {
+    let file_name = path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .ok_or_else(|| QError::Usage(format!("Not an audio file: {}", path.display())))?;
+    if audio_mime_type(&file_name).is_none() {
+        return Err(QError::Usage(format!(
+            "Unsupported audio format: {} (use mp3, m4a, wav, ogg, flac, webm, or aac)",
+            file_name
+        )));
+    }
+
+    let transcriber = match provider {
+        Provider::OpenAI | Provider::Gemini | Provider::Groq => client.clone(),
+        _ => connect(Provider::OpenAI, config, None, ModelConfig::default(), Verbosity::default())
+            .await
+            .map_err(|e| QError::Config(format!("{} can't transcribe audio and OpenAI isn't available: {}", provider, e)))?,
+    };
+
+    let limit = if provider == Provider::Gemini { MAX_INLINE_AUDIO_BYTES } else { MAX_AUDIO_BYTES };
+    let size = std::fs::metadata(path)
+        .map_err(|e| QError::Context(format!("Failed to read {}: {}", path.display(), e)))?
+        .len();
+    if size > limit {
+        return Err(QError::Usage(format!(
+            "{} is {:.1} MB; audio must be under {} MB",
+            file_name,
+            size as f64 / (1024.0 * 1024.0),
+            limit / (1024 * 1024)
+        )));
+    }
+    let audio = std::fs::read(path)
+        .map_err(|e| QError::Context(format!("Failed to read {}: {}", path.display(), e)))?;
+
+    if !quiet {
+        eprintln!("{}", format!("Transcribing {}...", file_name).dimmed());
+    }
+    let transcript = transcriber.transcribe(&audio, &file_name)
+        .await
+        .map_err(|e| QError::Api(format!("Transcription failed: {}", e)))?;
+    if transcript.trim().is_empty() {
+        return Err(QError::Api(format!("No speech found in {}", file_name)));
+    }
+    if !quiet {
+        eprintln!("{}", format!("transcript: {}", transcript.trim()).dimmed());
+    }
+    Ok(transcript.trim().to_string())
+}
@@ -0,0 +1,121 @@
+use std::env;
+use std::path::Path;
+
+use super::pr::truncate_diff;
+use super::{ask, check_context_allowed, diff_budget, load_config, Diff};
+use crate::cli::args::Cli;
+use crate::context::git::git;
+use crate::utils::errors::QError;
+use crate::utils::output;
+
+/// Section order from Keep a Changelog
+const SECTIONS: &[&str] = &["Added", "Changed", "Deprecated", "Removed", "Fixed", "Security"];
+
+/// Summarize the commits in `range` (the latest tag to HEAD when omitted) as
+/// Keep a Changelog entries
+pub async fn run(cli: &Cli, range: Option<&str>, with_diff: bool) -> Result<(), QError> {
+    let config = load_config(cli)?;
+    config.ensure_profile_exists()?;
+    if with_diff {
+        check_context_allowed(cli, &config, &["diff"])?;
+    }
+
+    let dir = env::current_dir()
+        .map_err(|e| QError::Context(format!("Failed to get current directory: {}", e)))?;
+    let range = match range {
+        Some(range) => range.to_string(),
+        None => default_range(&dir)?,
+    };
+
+    let log = git(&dir, &["log", "--no-merges", "--reverse", "--format=- %s%n%b", &range])
+        .map_err(|e| QError::Context(e.to_string()))?;
+    if log.trim().is_empty() {
+        return Err(QError::Usage(format!("No commits in {}", range)));
+    }
+
+    let mut prompt = format!(
+        "Turn these commits into changelog entries for users of the project.\n\
+         Group them under Keep a Changelog headings ({}), in that order, as `### Heading` \
+         followed by `- entry` lines. Leave out empty headings and purely internal changes \
+         (refactors, CI, tests) unless they affect users. Merge related commits into one entry. \
+         Reply with the markdown only.\n\nCommits ({}):\n{}",
+        SECTIONS.join(", "),
+        range,
+        log
+    );
+    if with_diff {
+        let diff = Diff::load(&config, &dir, &[&diff_range(&range)])?;
+        prompt.push_str(&format!("\nDiff:\n{}", truncate_diff(&diff.text, diff_budget(&config))));
+    }
+
+    let response = ask(cli, &config, &prompt).await?;
+
+    let entries = extract_sections(&response)
+        .ok_or_else(|| QError::Api("The model didn't return any changelog sections".to_string()))?;
+    output::line(format!("## [Unreleased]\n\n{}", entries));
+    Ok(())
+}
+
+/// From the latest tag to HEAD, or all of history when there are no tags
+fn default_range(dir: &Path) -> Result<String, QError> {
+    match git(dir, &["describe", "--tags", "--abbrev=0"]) {
+        Ok(tag) => Ok(format!("{}..HEAD", tag.trim())),
+        Err(_) => Ok("HEAD".to_string()),
+    }
+}
+
+/// `git diff` takes `a..b` too, but a bare revision means "against the
+/// working tree", so a single ref diffs its whole history from the empty tree
+fn diff_range(range: &str) -> String {
+    if range.contains("..") {
+        range.to_string()
+    } else {
+        format!("4b825dc642cb6eb9a060e54bf8d69288fbee4904..{}", range)
+    }
+}
+
+/// Keep only the `### Section` blocks, dropping any preamble or code fence
+fn extract_sections(response: &str) -> Option<String> {
+    let mut output = String::new();
+    let mut in_sections = false;
+    for line in response.lines() {
+        let trimmed = line.trim_end();
+        if let Some(heading) = trimmed.strip_prefix("### ") {
+            in_sections = SECTIONS.contains(&heading.trim());
+            if in_sections {
+                if !output.is_empty() {
+                    output.push('\n');
+                }
+                output.push_str(trimmed);
+                output.push('\n');
+            }
+            continue;
+        }
+        if in_sections && trimmed.trim_start().starts_with("- ") {
+            output.push_str(trimmed);
+            output.push('\n');
+        }
+    }
+    (!output.is_empty()).then_some(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_sections() {
+        let response = "Here is the changelog:\n```markdown\n### Added\n- --repo context\n\n### Fixed\n- Gemini citations\n### Notes\n- internal\n```\n";
+        assert_eq!(
+            extract_sections(response).unwrap(),
+            "### Added\n- --repo context\n\n### Fixed\n- Gemini citations\n"
+        );
+        assert_eq!(extract_sections("Nothing user-facing changed."), None);
+    }
+
+    #[test]
+    fn test_diff_range() {
+        assert_eq!(diff_range("v1.2.0..HEAD"), "v1.2.0..HEAD");
+        assert!(diff_range("HEAD").ends_with("..HEAD"));
+    }
+}
@@ -0,0 +1,43 @@
+use colored::*;
+
+use crate::cli::args::CommandsCommand;
+use crate::commands::database::{get_all_commands, installed_bundle};
+use crate::commands::Category;
+use crate::utils::errors::QError;
+use crate::utils::output;
+
+// `q commands update` stays out until releases publish signed bundles (ADR 0007)
+pub async fn run(action: &CommandsCommand) -> Result<(), QError> {
+    match action {
+        CommandsCommand::List => list(),
+    }
+}
+
+/// The installed database version, then every command by category
+fn list() -> Result<(), QError> {
+    match installed_bundle() {
+        Some(bundle) => output::line(format!(
+            "Command database {} ({} commands, {} tldr pages), plus built-in commands",
+            bundle.version.green(), bundle.commands.len(), bundle.tldr.len()
+        )),
+        None => output::line("Built-in command database only"),
+    }
+
+    let mut commands = get_all_commands();
+    commands.sort_by(|a, b| a.name.cmp(&b.name));
+    let width = commands.iter().map(|command| command.name.len()).max().unwrap_or(0);
+    for category in [
+        Category::Development, Category::FileSystem, Category::Network, Category::Performance,
+        Category::Process, Category::System, Category::Other,
+    ] {
+        let in_category: Vec<_> = commands.iter().filter(|command| command.category == category).collect();
+        if in_category.is_empty() {
+            continue;
+        }
+        output::line(format!("\n{}", category.to_string().blue().bold()));
+        for command in in_category {
+            output::line(format!("  {:<width$}  {}", command.name.green(), command.description, width = width));
+        }
+    }
+    Ok(())
+}
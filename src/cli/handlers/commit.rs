@@ -0,0 +1,240 @@
+use std::env;
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use super::pr::truncate_diff;
+use super::{ask, check_context_allowed, diff_budget, load_config, Diff};
+use crate::cli::args::Cli;
+use crate::config::types::CommitConfig;
+use crate::config::validate::suggest;
+use crate::utils::errors::QError;
+use crate::utils::output;
+
+/// Longest commit header; most tools cut or wrap past this
+const MAX_HEADER_CHARS: usize = 72;
+/// Longest branch name
+const MAX_BRANCH_CHARS: usize = 50;
+
+/// Types from the Conventional Commits spec and the Angular convention it grew from
+const DEFAULT_TYPES: &[&str] = &["feat", "fix", "docs", "style", "refactor", "perf", "test", "build", "ci", "chore", "revert"];
+
+lazy_static! {
+    /// `type(scope)!: description`
+    static ref HEADER: Regex = Regex::new(r"^\s*`?([a-z]+)(?:\(([\w./-]+)\))?(!)?:\s*(.+?)`?\s*$").expect("valid regex");
+}
+
+/// A conventional-commit header's parts
+#[derive(Debug, PartialEq)]
+struct Header {
+    kind: String,
+    scope: Option<String>,
+    breaking: bool,
+    description: String,
+}
+
+impl std::fmt::Display for Header {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.kind)?;
+        if let Some(scope) = &self.scope {
+            write!(f, "({})", scope)?;
+        }
+        write!(f, "{}: {}", if self.breaking { "!" } else { "" }, self.description)
+    }
+}
+
+/// `q commit [--type t] [--scope s] [--breaking]`: a conventional-commit
+/// header for the staged changes, printed alone for `git commit -m "$(q commit)"`
+pub async fn commit(cli: &Cli, kind: Option<&str>, scope: Option<&str>, breaking: bool) -> Result<(), QError> {
+    let config = load_config(cli)?;
+    config.ensure_profile_exists()?;
+    let rules = config.commit();
+    check_context_allowed(cli, &config, &["diff"])?;
+    let types = allowed_types(rules);
+    if let Some(kind) = kind {
+        check_allowed(kind, &types, "type", "[commit] types")?;
+    }
+    if let Some(scope) = scope.filter(|_| !rules.scopes.is_empty()) {
+        let scopes: Vec<&str> = rules.scopes.iter().map(String::as_str).collect();
+        check_allowed(scope, &scopes, "scope", "[commit] scopes")?;
+    }
+
+    let dir = env::current_dir()
+        .map_err(|e| QError::Context(format!("Failed to get current directory: {}", e)))?;
+    let diff = Diff::load(&config, &dir, &["--cached"])?.text;
+    if diff.trim().is_empty() {
+        return Err(QError::Usage("Nothing is staged; stage changes with git add first".to_string()));
+    }
+
+    let mut prompt = format!(
+        "Write a Conventional Commits header, `type(scope): description`, for this staged diff. \
+         The type is one of: {}. {}The description is imperative, lowercase, without a final \
+         period, and the whole header is at most {} characters. Reply with the header only.",
+        types.join(", "),
+        if rules.scopes.is_empty() {
+            "The scope is optional: the area of the code changed, one word. ".to_string()
+        } else {
+            format!("The scope is one of: {}, or left out if none fits. ", rules.scopes.join(", "))
+        },
+        MAX_HEADER_CHARS
+    );
+    if let Some(kind) = kind {
+        prompt.push_str(&format!(" Use the type {}.", kind));
+    }
+    if let Some(scope) = scope {
+        prompt.push_str(&format!(" Use the scope {}.", scope));
+    }
+    prompt.push_str(&format!("\n\nDiff:\n{}", truncate_diff(&diff, diff_budget(&config))));
+
+    let response = ask(cli, &config, &prompt).await?;
+    let mut header = parse_header(&response)
+        .ok_or_else(|| QError::Command(format!("The reply isn't a conventional-commit header: {}", response.trim())))?;
+
+    // Flags win over what the model chose; a scope outside the list is dropped
+    if let Some(kind) = kind {
+        header.kind = kind.to_string();
+    }
+    if !types.contains(&header.kind.as_str()) {
+        return Err(QError::Command(format!("The reply used the type '{}', which isn't allowed", header.kind)));
+    }
+    header.scope = match scope {
+        Some(scope) => Some(scope.to_string()),
+        None => header.scope.filter(|scope| rules.scopes.is_empty() || rules.scopes.contains(scope)),
+    };
+    header.breaking |= breaking;
+    output::line(&header);
+    Ok(())
+}
+
+/// `q branch-name [title]`: a branch name such as `fix/upload-retry-timeout`
+/// for an issue title or, without one, the uncommitted changes
+pub async fn branch_name(cli: &Cli, title: Option<&str>) -> Result<(), QError> {
+    let config = load_config(cli)?;
+    config.ensure_profile_exists()?;
+    let types = allowed_types(config.commit());
+
+    let subject = match title {
+        Some(title) => format!("Issue title: {}", title),
+        None => {
+            check_context_allowed(cli, &config, &["diff"])?;
+            let dir = env::current_dir()
+                .map_err(|e| QError::Context(format!("Failed to get current directory: {}", e)))?;
+            let diff = Diff::load(&config, &dir, &["HEAD"])?.text;
+            if diff.trim().is_empty() {
+                return Err(QError::Usage("No changes to name a branch after; give an issue title instead".to_string()));
+            }
+            format!("Diff:\n{}", truncate_diff(&diff, diff_budget(&config)))
+        }
+    };
+    let prompt = format!(
+        "Name a git branch for this work as `type/short-summary`: the type is one of {}, and the \
+         summary is two to five lowercase words joined by hyphens. Reply with the branch name only.\n\n{}",
+        types.join(", "),
+        subject
+    );
+
+    let response = ask(cli, &config, &prompt).await?;
+    let name = branch_slug(&response, &types)
+        .ok_or_else(|| QError::Command(format!("The reply isn't a branch name: {}", response.trim())))?;
+    output::line(&name);
+    Ok(())
+}
+
+fn allowed_types(rules: &CommitConfig) -> Vec<&str> {
+    if rules.types.is_empty() {
+        DEFAULT_TYPES.to_vec()
+    } else {
+        rules.types.iter().map(String::as_str).collect()
+    }
+}
+
+fn check_allowed(value: &str, allowed: &[&str], what: &str, setting: &str) -> Result<(), QError> {
+    if allowed.contains(&value) {
+        return Ok(());
+    }
+    let mut message = format!("'{}' isn't an allowed {} ({}: {})", value, what, setting, allowed.join(", "));
+    if let Some(suggestion) = suggest(value, allowed) {
+        message.push_str(&format!("; did you mean '{}'?", suggestion));
+    }
+    Err(QError::Usage(message))
+}
+
+/// The first line of a reply that reads as a conventional-commit header
+fn parse_header(response: &str) -> Option<Header> {
+    response.lines().find_map(|line| {
+        let captures = HEADER.captures(line)?;
+        let description = captures[4].trim().trim_end_matches('.');
+        let mut chars = description.chars();
+        let first = chars.next()?;
+        Some(Header {
+            kind: captures[1].to_string(),
+            scope: captures.get(2).map(|scope| scope.as_str().to_string()),
+            breaking: captures.get(3).is_some(),
+            // Lowercase the first word unless it's an acronym or identifier
+            description: match chars.next() {
+                Some(second) if second.is_lowercase() => first.to_lowercase().chain(std::iter::once(second)).chain(chars).collect(),
+                _ => description.to_string(),
+            },
+        })
+    })
+}
+
+/// `type/summary` from a reply, made safe for git: lowercase ASCII words
+/// joined by hyphens, at most `MAX_BRANCH_CHARS` long. An unknown type prefix
+/// is kept as part of the summary.
+fn branch_slug(response: &str, types: &[&str]) -> Option<String> {
+    let line = response.lines().map(|line| line.trim().trim_matches('`')).find(|line| !line.is_empty())?;
+    let (kind, summary) = match line.split_once('/') {
+        Some((kind, summary)) if types.contains(&kind.to_lowercase().as_str()) => (Some(kind.to_lowercase()), summary),
+        _ => (None, line),
+    };
+    let words: Vec<String> = summary
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+
+    let mut name = kind.map(|kind| format!("{}/", kind)).unwrap_or_default();
+    let prefix = name.len();
+    for word in words {
+        let separator = if name.len() > prefix { 1 } else { 0 };
+        if name.len() + separator + word.len() > MAX_BRANCH_CHARS {
+            break;
+        }
+        if separator == 1 {
+            name.push('-');
+        }
+        name.push_str(&word);
+    }
+    (name.len() > prefix).then_some(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_header() {
+        assert_eq!(parse_header("feat(api): Add retry to uploads.").unwrap().to_string(), "feat(api): add retry to uploads");
+        assert_eq!(parse_header("Sure:\n`fix!: HTTP client leaks sockets`").unwrap().to_string(), "fix!: HTTP client leaks sockets");
+        assert_eq!(parse_header("Here is a summary of the change"), None);
+    }
+
+    #[test]
+    fn test_branch_slug() {
+        let types = DEFAULT_TYPES;
+        assert_eq!(branch_slug("fix/Upload retry_timeout", types).as_deref(), Some("fix/upload-retry-timeout"));
+        assert_eq!(branch_slug("`feature/new-login`", types).as_deref(), Some("feature-new-login"));
+        let long = format!("feat/{}", "word-".repeat(20));
+        assert!(branch_slug(&long, types).unwrap().len() <= MAX_BRANCH_CHARS);
+        assert_eq!(branch_slug("///", types), None);
+    }
+
+    #[test]
+    fn test_check_allowed() {
+        let scopes = ["api", "cli", "config"];
+        assert!(check_allowed("cli", &scopes, "scope", "[commit] scopes").is_ok());
+        let error = check_allowed("confg", &scopes, "scope", "[commit] scopes").unwrap_err();
+        assert!(error.to_string().contains("did you mean 'config'?"));
+    }
+}
@@ -0,0 +1,177 @@
+use colored::*;
+
+use super::eval::{runner, side_by_side, terminal_width, Variant};
+use super::load_config;
+use crate::cli::args::Cli;
+use crate::utils::errors::QError;
+use crate::utils::output;
+
+/// Word-diff table cells (tokens of A times tokens of B) before falling back
+/// to comparing whole lines; a few thousand words a side stays well under it
+const MAX_DIFF_CELLS: usize = 4_000_000;
+
+/// Which answer a run of text belongs to
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Side {
+    Both,
+    /// Only in A
+    Removed,
+    /// Only in B
+    Added,
+}
+
+/// `q compare --models a,b <prompt> [--diff]`: ask two models the same
+/// question and show the answers side by side, or with `--diff` as one
+/// word-level diff that marks where they disagree
+pub async fn run(cli: &Cli, prompt: &str, models: &[String], diff: bool) -> Result<(), QError> {
+    let [model_a, model_b] = models else {
+        return Err(QError::Usage("q compare needs exactly two models, e.g. --models openai/gpt-4o,gemini/gemini-2.5-flash".to_string()));
+    };
+    let config = load_config(cli)?;
+    config.ensure_profile_exists()?;
+    let mut a = runner(cli, &config, &Variant::model(model_a)).await?;
+    let mut b = runner(cli, &config, &Variant::model(model_b)).await?;
+
+    eprintln!("{}", format!("Asking {} and {}...", a.label, b.label).dimmed());
+    let (answer_a, answer_b) = tokio::join!(a.answer(prompt), b.answer(prompt));
+    let answer_a = answer_a.map_err(|e| QError::Api(format!("{}: {}", a.label, e)))?;
+    let answer_b = answer_b.map_err(|e| QError::Api(format!("{}: {}", b.label, e)))?;
+
+    if !diff {
+        output::text(side_by_side(&a.label, &answer_a, &b.label, &answer_b, terminal_width()));
+        return Ok(());
+    }
+    let changes = word_diff(answer_a.trim(), answer_b.trim());
+    output::line(format!("{} {}", "[-A-]".red(), a.label));
+    output::line(format!("{} {}\n", "{+B+}".green(), b.label));
+    output::line(render(&changes));
+    eprintln!("{}", similarity(&changes).dimmed());
+    Ok(())
+}
+
+/// Runs of whitespace and of everything else, in order
+fn tokens(text: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut start = 0;
+    let mut in_space = None;
+    for (i, c) in text.char_indices() {
+        let space = c.is_whitespace();
+        if in_space.is_some_and(|in_space| in_space != space) {
+            tokens.push(&text[start..i]);
+            start = i;
+        }
+        in_space = Some(space);
+    }
+    if start < text.len() {
+        tokens.push(&text[start..]);
+    }
+    tokens
+}
+
+/// The two texts as runs that are in both, only in A, or only in B, from the
+/// longest common subsequence of their words. Any whitespace matches any other,
+/// so reflowed text isn't a difference; the shared text is shown as B wraps it.
+fn word_diff(a: &str, b: &str) -> Vec<(Side, String)> {
+    let a = tokens(a);
+    let b = tokens(b);
+    let same = |x: &str, y: &str| x == y || (x.trim().is_empty() && y.trim().is_empty());
+
+    // The common start and end don't need the table
+    let prefix = a.iter().zip(&b).take_while(|(x, y)| same(x, y)).count();
+    let suffix = a[prefix..].iter().rev().zip(b[prefix..].iter().rev()).take_while(|(x, y)| same(x, y)).count();
+    let (middle_a, middle_b) = (&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+
+    let mut sides: Vec<(Side, &str)> = b[..prefix].iter().map(|token| (Side::Both, *token)).collect();
+    if (middle_a.len() + 1) * (middle_b.len() + 1) > MAX_DIFF_CELLS {
+        sides.extend(middle_a.iter().map(|token| (Side::Removed, *token)));
+        sides.extend(middle_b.iter().map(|token| (Side::Added, *token)));
+    } else {
+        // lengths[i][j]: longest common subsequence of middle_a[i..] and middle_b[j..]
+        let columns = middle_b.len() + 1;
+        let mut lengths = vec![0u32; (middle_a.len() + 1) * columns];
+        for i in (0..middle_a.len()).rev() {
+            for j in (0..middle_b.len()).rev() {
+                lengths[i * columns + j] = if same(middle_a[i], middle_b[j]) {
+                    lengths[(i + 1) * columns + j + 1] + 1
+                } else {
+                    lengths[(i + 1) * columns + j].max(lengths[i * columns + j + 1])
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < middle_a.len() || j < middle_b.len() {
+            if i < middle_a.len() && j < middle_b.len() && same(middle_a[i], middle_b[j]) {
+                sides.push((Side::Both, middle_b[j]));
+                i += 1;
+                j += 1;
+            } else if i < middle_a.len() && (j == middle_b.len() || lengths[(i + 1) * columns + j] >= lengths[i * columns + j + 1]) {
+                // On a tie, what A said comes before what B says instead
+                sides.push((Side::Removed, middle_a[i]));
+                i += 1;
+            } else {
+                sides.push((Side::Added, middle_b[j]));
+                j += 1;
+            }
+        }
+    }
+    sides.extend(b[b.len() - suffix..].iter().map(|token| (Side::Both, *token)));
+
+    // Merge consecutive tokens from the same side
+    let mut runs: Vec<(Side, String)> = Vec::new();
+    for (side, token) in sides {
+        match runs.last_mut() {
+            Some((last, text)) if *last == side => text.push_str(token),
+            _ => runs.push((side, token.to_string())),
+        }
+    }
+    runs
+}
+
+/// `[-only in A-]` and `{+only in B+}`, as `git diff --word-diff` marks them,
+/// colored when the terminal allows
+fn render(changes: &[(Side, String)]) -> String {
+    changes.iter()
+        .map(|(side, text)| match side {
+            Side::Both => text.clone(),
+            Side::Removed => format!("[-{}-]", text).red().strikethrough().to_string(),
+            Side::Added => format!("{{+{}+}}", text).green().to_string(),
+        })
+        .collect()
+}
+
+/// How many of the words the answers share
+fn similarity(changes: &[(Side, String)]) -> String {
+    let words = |wanted: &[Side]| -> usize {
+        changes.iter()
+            .filter(|(side, _)| wanted.contains(side))
+            .map(|(_, text)| text.split_whitespace().count())
+            .sum()
+    };
+    let shared = words(&[Side::Both]);
+    let a = shared + words(&[Side::Removed]);
+    let b = shared + words(&[Side::Added]);
+    let percent = if a.max(b) == 0 { 100 } else { shared * 100 / a.max(b) };
+    format!("{} of {} words in A and {} in B are shared ({}%)", shared, a, b, percent)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokens() {
+        assert_eq!(tokens("Use  tar -xzf\nfile"), ["Use", "  ", "tar", " ", "-xzf", "\n", "file"]);
+    }
+
+    #[test]
+    fn test_word_diff() {
+        colored::control::set_override(false);
+        let changes = word_diff("Rust 1.80 was released in July 2024.", "Rust 1.80 was released\nin June 2024.");
+        assert_eq!(render(&changes), "Rust 1.80 was released\nin [-July-]{+June+} 2024.");
+        assert_eq!(similarity(&changes), "6 of 7 words in A and 7 in B are shared (85%)");
+
+        let changes = word_diff("same", "same");
+        assert_eq!(changes, [(Side::Both, "same".to_string())]);
+        assert_eq!(render(&word_diff("", "new text")), "{+new text+}");
+    }
+}
@@ -0,0 +1,188 @@
+use std::io::BufRead;
+use std::path::Path;
+use colored::*;
+
+use super::{build_client, load_config};
+use crate::api::{read_api_key, ApiError, ModelConfig};
+use crate::cli::args::{Cli, ConfigCommand, Verbosity};
+use crate::config::paths::ConfigPaths;
+use crate::config::keys;
+use crate::config::types::Provider;
+use crate::config::validate::{self, Severity};
+use crate::utils::errors::QError;
+use crate::utils::format::format_markdown;
+use crate::utils::output;
+
+pub fn run(cli: &Cli, action: &ConfigCommand) -> Result<(), QError> {
+    match action {
+        ConfigCommand::Validate => validate_config(cli),
+        ConfigCommand::Get { key } => {
+            output::line(&load_config(cli)?.get_value(key)?);
+            Ok(())
+        }
+        ConfigCommand::Set { key, value } => {
+            let mut config = load_config(cli)?;
+            config.set_value(key, value)?;
+            output::line(format!("{} = {}", key, config.get_value(key)?));
+            Ok(())
+        }
+        ConfigCommand::Export { bundle } => {
+            let text = load_config(cli)?.export_bundle()?;
+            match bundle {
+                Some(path) => {
+                    std::fs::write(path, text)?;
+                    output::line(format!("Wrote {} (API keys are never exported)", path.display()));
+                }
+                None => output::text(&text),
+            }
+            Ok(())
+        }
+        ConfigCommand::Import { file } => {
+            let text = std::fs::read_to_string(file)
+                .map_err(|e| QError::Config(format!("Failed to read {}: {}", file.display(), e)))?;
+            let sections = load_config(cli)?.import_bundle(&text)?;
+            if sections.is_empty() {
+                output::line(format!("{} has no settings to import", file.display()).dimmed());
+            } else {
+                output::line(format!("Imported {} from {}", sections.join(", "), file.display()));
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Report every problem in the config file, and in the project's `.q.toml`
+/// if there is one, without loading them through `ConfigManager`, which would
+/// stop at the first error.
+fn validate_config(cli: &Cli) -> Result<(), QError> {
+    let paths = ConfigPaths::new(cli.verbose)?;
+    let mut errors = 0;
+    let path = paths.config_file();
+    if path.exists() {
+        errors += report(path, &validate::validate_str(&std::fs::read_to_string(path)?));
+    } else {
+        output::line(format!("No config file at {} (built-in defaults are in use)", path.display()));
+    }
+    if let Some(project) = std::env::current_dir().ok().and_then(|dir| ConfigPaths::find_project_config(&dir)) {
+        errors += report(&project, &validate::validate_project_str(&std::fs::read_to_string(&project)?));
+    }
+
+    if errors > 0 {
+        return Err(QError::Config(format!("{} error(s) in the config", errors)));
+    }
+    Ok(())
+}
+
+/// Print `path`'s diagnostics, or that it's valid, returning how many are errors
+fn report(path: &Path, diagnostics: &[validate::Diagnostic]) -> usize {
+    for diagnostic in diagnostics {
+        let line = format!("{}:{}", path.display(), diagnostic);
+        match diagnostic.severity {
+            Severity::Error => eprintln!("{}", line.red()),
+            Severity::Warning => eprintln!("{}", line.yellow()),
+        }
+    }
+    let errors = diagnostics.iter().filter(|d| d.severity == Severity::Error).count();
+    if errors == 0 {
+        output::line(format!("{} is valid", path.display()));
+    }
+    errors
+}
+
+/// Resolve the key to store from the positional argument, `--from-stdin`,
+/// `--from-file`, or an interactive hidden prompt, in that order.
+pub fn read_key_input(key: Option<&str>, from_stdin: bool, from_file: Option<&Path>) -> Result<String, QError> {
+    let key = if let Some(key) = key {
+        key.to_string()
+    } else if from_stdin {
+        let mut line = String::new();
+        std::io::stdin().lock().read_line(&mut line)?;
+        line
+    } else if let Some(path) = from_file {
+        read_api_key(&path.to_string_lossy())
+            .map_err(|e| QError::Config(format!("Failed to read key from {}: {}", path.display(), e)))?
+    } else if output::can_prompt() {
+        rpassword::prompt_password("API key: ")?
+    } else {
+        return Err(QError::Usage(
+            "No API key given and stdin is not a terminal. Pass the key, --from-stdin, or --from-file.".into(),
+        ));
+    };
+
+    let key = key.trim().to_string();
+    if key.is_empty() {
+        return Err(QError::Config("API key is empty".into()));
+    }
+    Ok(key)
+}
+
+pub async fn set_key(cli: &Cli, provider: &str, key: &str, verify: bool, add: bool) -> Result<(), QError> {
+    let provider = Provider::try_from(provider)
+        .map_err(QError::Config)?;
+
+    // Check the format first so obviously malformed keys never hit the network
+    if let Some(warning) = keys::validate_api_key(provider, key).map_err(QError::Config)? {
+        eprintln!("{}", format!("Warning: {}", warning).yellow());
+    }
+
+    if verify {
+        verify_key(provider, key).await?;
+    }
+
+    let mut config = load_config(cli)?;
+    if add {
+        config.add_api_key(provider, key.to_string())?;
+        let count = config.resolve_api_keys(provider).len();
+        output::line(format_markdown(&format!("# API key added for {} ({} keys in rotation)", provider, count)));
+    } else {
+        config.set_api_key(provider, key.to_string())?;
+        output::line(format_markdown(&format!("# API key for {} has been set successfully", provider)));
+    }
+    Ok(())
+}
+
+pub fn set_provider(cli: &Cli, provider: &str) -> Result<(), QError> {
+    let provider = Provider::try_from(provider)
+        .map_err(QError::Config)?;
+
+    let mut config = load_config(cli)?;
+    config.set_default_provider(provider)?;
+
+    output::line(format_markdown(&format!("# Default provider has been set to {}", provider)));
+    Ok(())
+}
+
+pub fn set_model(cli: &Cli, provider: &str, model: &str) -> Result<(), QError> {
+    let provider = Provider::try_from(provider)
+        .map_err(QError::Config)?;
+
+    let mut config = load_config(cli)?;
+    config.set_model(provider, model.to_string())?;
+
+    output::line(format_markdown(&format!("# Model for {} has been set to {}", provider, model)));
+    Ok(())
+}
+
+/// Check a key against the live API before it is saved.
+///
+/// A key the provider explicitly rejects is an error; anything else that
+/// prevents verification (network, rate limits) only produces a warning.
+async fn verify_key(provider: Provider, key: &str) -> Result<(), QError> {
+    eprintln!("{}", format!("Verifying {} API key...", provider).dimmed());
+
+    let client = build_client(provider, key, None, ModelConfig::default(), Verbosity::default())?;
+    match client.validate_key().await {
+        Ok(()) => {
+            eprintln!("{}", "API key verified".green());
+            Ok(())
+        }
+        Err(ApiError::InvalidKey) => Err(QError::Config(format!(
+            "The {} API rejected this key. Use --no-verify to save it anyway.",
+            provider
+        ))),
+        Err(e) => {
+            eprintln!("{}", format!("Warning: could not verify API key ({}); saving anyway", e).yellow());
+            Ok(())
+        }
+    }
+}
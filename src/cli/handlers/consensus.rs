@@ -0,0 +1,58 @@
+use colored::*;
+
+use super::eval::{runner, Variant};
+use super::{query_failed, resolve_provider_and_model};
+use crate::cli::args::Cli;
+use crate::config::types::Provider;
+use crate::config::ConfigManager;
+use crate::core::consensus::{consensus, Panelist, DEFAULT_JUDGE_TOKENS};
+use crate::utils::errors::QError;
+
+/// `--consensus`: ask every `[consensus]` model the prompt at once, then have
+/// the judge merge their answers into one that ends with where they disagreed.
+/// Returns the merged answer and the judge's label.
+pub async fn run(cli: &Cli, config: &ConfigManager, prompt: &str) -> Result<(String, String), QError> {
+    let settings = config.consensus();
+    if settings.models.len() < 2 {
+        return Err(QError::Config(
+            "--consensus needs at least two models in [consensus], e.g. models = [\"openai/gpt-4o\", \"gemini/gemini-2.5-flash\"]".to_string()
+        ));
+    }
+    let mut panel = Vec::new();
+    for model in &settings.models {
+        let runner = runner(cli, config, &Variant::model(model)).await?;
+        panel.push(Panelist { label: runner.label, engine: runner.engine });
+    }
+    let judge = runner(cli, config, &settings.judge.as_deref().map(Variant::model).unwrap_or_default()).await?;
+    let mut judge_engine = judge.engine;
+
+    if !cli.quiet {
+        let labels: Vec<&str> = panel.iter().map(|panelist| panelist.label.as_str()).collect();
+        eprintln!("{}", format!("consensus: {}; judge: {}", labels.join(", "), judge.label).dimmed());
+    }
+    let max_tokens = settings.max_judge_tokens.unwrap_or(DEFAULT_JUDGE_TOKENS);
+    let result = consensus(&mut panel, &mut judge_engine, prompt, max_tokens)
+        .await
+        .map_err(|e| query_failed(e, &judge_engine))?;
+    for (label, answer) in &result.answers {
+        if let Err(e) = answer {
+            eprintln!("{}", format!("{} didn't answer: {}", label, e).yellow());
+        }
+    }
+    Ok((result.merged, judge.label))
+}
+
+/// Every provider `--consensus` sends the prompt to, judge included, so the
+/// `[context.allow]` policy can be checked against each
+pub(super) fn providers(cli: &Cli, config: &ConfigManager) -> Result<Vec<Provider>, QError> {
+    let settings = config.consensus();
+    settings.models.iter().map(Some).chain([settings.judge.as_ref()])
+        .map(|model| {
+            let mut selection = cli.clone();
+            if let Some(model) = model {
+                selection.model = Some(model.clone());
+            }
+            Ok(resolve_provider_and_model(&selection, config)?.0)
+        })
+        .collect()
+}
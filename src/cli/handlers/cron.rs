@@ -0,0 +1,145 @@
+use std::io::ErrorKind;
+use std::process::Command;
+
+use chrono::Local;
+use colored::*;
+
+use super::{ask_with, load_config, query_engine};
+use crate::cli::args::Cli;
+use crate::core::schedule;
+use crate::utils::errors::QError;
+use crate::utils::output;
+
+/// Upcoming runs listed to confirm the schedule
+const NEXT_RUNS: usize = 3;
+/// Stands in for the command when `--command` isn't given
+const PLACEHOLDER_COMMAND: &str = "/path/to/command";
+
+/// The schedules in a reply; `on_calendar` only when asked for
+#[derive(Debug, Default, PartialEq)]
+struct Schedules {
+    cron: Option<String>,
+    on_calendar: Option<String>,
+}
+
+/// `q cron <when> [--systemd] [--command cmd]`: a crontab line (and a
+/// systemd OnCalendar expression) for a schedule in plain words. The cron
+/// schedule is parsed locally and sent back once for a fix if invalid;
+/// its next runs are printed to confirm it means what was asked.
+pub async fn run(cli: &Cli, when: &str, systemd: bool, command: Option<&str>) -> Result<(), QError> {
+    let config = load_config(cli)?;
+    config.ensure_profile_exists()?;
+
+    let mut prompt = format!(
+        "Turn this schedule into a standard 5-field crontab schedule (minute hour day-of-month \
+         month day-of-week, or an @nickname): {}\n\
+         Reply with exactly one line `cron: <schedule>`{} and nothing else. Don't include a command.",
+        when,
+        if systemd { ", then one line `oncalendar: <expression>` with the equivalent systemd OnCalendar expression" } else { "" }
+    );
+
+    let engine = query_engine(cli, &config).await?;
+
+    let mut retried = false;
+    let (schedules, cron) = loop {
+        let response = ask_with(&engine, &prompt).await?;
+        let schedules = parse_reply(&response);
+        let result = schedules.cron.as_deref()
+            .ok_or_else(|| "There was no `cron:` line".to_string())
+            .and_then(|schedule| schedule::parse(schedule).map_err(|e| format!("`{}` is not a valid crontab schedule: {}", schedule, e)));
+        match result {
+            Ok(cron) => break (schedules, cron),
+            Err(problem) if !retried => {
+                eprintln!("{}", format!("{}; asking for a fix", problem).yellow());
+                prompt.push_str(&format!("\n\nYour reply was:\n{}\n{}. Try again.", response.trim(), problem));
+                retried = true;
+            }
+            Err(problem) => return Err(QError::Command(problem)),
+        }
+    };
+
+    let schedule = schedules.cron.as_deref().unwrap_or_default();
+    output::line(format!("{} {}", schedule, command.unwrap_or(PLACEHOLDER_COMMAND)));
+    if systemd {
+        match &schedules.on_calendar {
+            Some(expression) => output::line(format!("\n[Timer]\nOnCalendar={}\nPersistent=true", expression)),
+            None => eprintln!("{}", "The reply had no OnCalendar expression".yellow()),
+        }
+    }
+
+    eprintln!("{}", format!("Schedule: {}", cron.describe()).dimmed());
+    let runs: Vec<String> = cron.iter_after(Local::now())
+        .take(NEXT_RUNS)
+        .map(|time| time.format("%a %Y-%m-%d %H:%M").to_string())
+        .collect();
+    eprintln!("{}", format!("Next runs: {}", runs.join(", ")).dimmed());
+    if let Some(expression) = schedules.on_calendar.as_deref().filter(|_| systemd) {
+        match check_on_calendar(expression) {
+            Some(Ok(next)) => eprintln!("{}", format!("systemd-analyze: next elapse {}", next).dimmed()),
+            Some(Err(error)) => eprintln!("{}", format!("systemd-analyze rejected the OnCalendar expression: {}", error).yellow()),
+            None => eprintln!("{}", "systemd-analyze isn't available, so OnCalendar wasn't checked".dimmed()),
+        }
+    }
+    if command.is_none() {
+        eprintln!("{}", format!("Replace {} with the command to run", PLACEHOLDER_COMMAND).dimmed());
+    }
+    Ok(())
+}
+
+/// The `cron:` and `oncalendar:` lines of a reply, with or without backticks,
+/// bold, or a code fence around them
+fn parse_reply(response: &str) -> Schedules {
+    let mut schedules = Schedules::default();
+    for line in response.lines() {
+        // `**` is markdown bold; it never appears in either kind of schedule
+        let line = line.replace("**", "");
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim().trim_matches('`').trim().to_string();
+        match key.trim().trim_matches('`').to_lowercase().as_str() {
+            "cron" => schedules.cron = schedules.cron.or(Some(value)),
+            "oncalendar" => schedules.on_calendar = schedules.on_calendar.or(Some(value)),
+            _ => {}
+        }
+    }
+    schedules
+}
+
+/// `systemd-analyze calendar`'s next elapse for `expression`, its error, or
+/// `None` where systemd isn't installed
+fn check_on_calendar(expression: &str) -> Option<Result<String, String>> {
+    let output = match Command::new("systemd-analyze").args(["calendar", expression]).output() {
+        Ok(output) => output,
+        Err(e) if e.kind() == ErrorKind::NotFound => return None,
+        Err(e) => return Some(Err(e.to_string())),
+    };
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Some(Err(stderr.lines().next().unwrap_or("invalid").trim().to_string()));
+    }
+    let next = stdout.lines()
+        .find_map(|line| line.trim().strip_prefix("Next elapse:"))
+        .unwrap_or("unknown")
+        .trim()
+        .to_string();
+    Some(Ok(next))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_reply() {
+        assert_eq!(
+            parse_reply("```\ncron: 0 7 * * 1-5\noncalendar: Mon..Fri *-*-* 07:00:00\n```"),
+            Schedules { cron: Some("0 7 * * 1-5".to_string()), on_calendar: Some("Mon..Fri *-*-* 07:00:00".to_string()) }
+        );
+        assert_eq!(
+            parse_reply("**cron:** `@daily`"),
+            Schedules { cron: Some("@daily".to_string()), on_calendar: None }
+        );
+    }
+}
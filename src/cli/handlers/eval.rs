@@ -0,0 +1,335 @@
+//! `q eval`: run the prompts in an eval file against two configurations (A and
+//! B) and show the answers side by side, optionally scored by a judge model
+//! against a rubric, so a prompt or model change can be measured before it ships.
+//!
+//! ```toml
+//! rubric = "Correct, runnable, and no longer than needed"
+//! judge = "openai/gpt-4o"
+//!
+//! [a]
+//! model = "openai/gpt-4o-mini"
+//!
+//! [b]
+//! model = "openai/gpt-4o-mini"
+//! system_prompt = "Answer with the command first, then one line of explanation."
+//!
+//! [[case]]
+//! name = "tar"
+//! prompt = "How do I extract a .tar.gz into another directory?"
+//! ```
+
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use colored::*;
+use serde::Deserialize;
+
+use super::ask::find_preset;
+use super::{connect, load_config, resolve_provider_and_model};
+use crate::api::ModelConfig;
+use crate::cli::args::Cli;
+use crate::config::ConfigManager;
+use crate::context::environment;
+use crate::core::{QueryConfig, QueryEngine};
+use crate::utils::errors::QError;
+use crate::utils::output;
+
+/// Terminal width used when `COLUMNS` isn't set
+const DEFAULT_WIDTH: usize = 120;
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct EvalFile {
+    a: Variant,
+    b: Variant,
+    /// What the judge scores the answers against; no grading without one
+    rubric: Option<String>,
+    /// The judge's model [default: the configured one]
+    judge: Option<String>,
+    #[serde(default, rename = "case")]
+    cases: Vec<Case>,
+}
+
+/// One side of the comparison. Unset fields come from the preset, then the config.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(super) struct Variant {
+    /// Column heading [default: provider/model]
+    label: Option<String>,
+    /// A preset from [presets] to start from
+    preset: Option<String>,
+    /// `model` or `provider/model`
+    model: Option<String>,
+    system_prompt: Option<String>,
+    temperature: Option<f64>,
+    /// Wraps each case's prompt; `{prompt}` marks where it goes
+    template: Option<String>,
+}
+
+impl Variant {
+    /// `model` with everything else from the config
+    pub(super) fn model(model: &str) -> Self {
+        Self { model: Some(model.to_string()), ..Self::default() }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Case {
+    name: Option<String>,
+    prompt: String,
+}
+
+/// A judge's scores for one case, 1 to 10
+#[derive(Debug, PartialEq, Deserialize)]
+struct Grade {
+    a: f64,
+    b: f64,
+    #[serde(default)]
+    reason: String,
+}
+
+/// A configuration ready to answer cases
+pub(super) struct Runner {
+    pub(super) label: String,
+    template: Option<String>,
+    pub(super) engine: QueryEngine,
+}
+
+impl Runner {
+    pub(super) async fn answer(&mut self, prompt: &str) -> Result<String, String> {
+        let prompt = match &self.template {
+            Some(template) => template.replace("{prompt}", prompt),
+            None => prompt.to_string(),
+        };
+        self.engine.query(&prompt).await.map_err(|e| e.to_string())
+    }
+}
+
+pub async fn run(cli: &Cli, path: &Path) -> Result<(), QError> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| QError::Usage(format!("Failed to read {}: {}", path.display(), e)))?;
+    let eval: EvalFile = toml::from_str(&text)
+        .map_err(|e| QError::Usage(format!("Invalid eval file {}: {}", path.display(), e)))?;
+    if eval.cases.is_empty() {
+        return Err(QError::Usage(format!("{} has no [[case]] entries", path.display())));
+    }
+
+    let config = load_config(cli)?;
+    config.ensure_profile_exists()?;
+    let mut a = runner(cli, &config, &eval.a).await?;
+    let mut b = runner(cli, &config, &eval.b).await?;
+    let mut judge = match &eval.rubric {
+        Some(_) => {
+            let variant = Variant { model: eval.judge.clone(), temperature: Some(0.0), ..Variant::default() };
+            Some(runner(cli, &config, &variant).await?)
+        }
+        None => None,
+    };
+
+    let width = terminal_width();
+    let mut grades = Vec::new();
+    let mut identical = 0;
+
+    for (i, case) in eval.cases.iter().enumerate() {
+        let name = case.name.clone().unwrap_or_else(|| format!("case {}", i + 1));
+        output::line(format!("[{}/{}] {}", i + 1, eval.cases.len(), name).bold());
+
+        let (answer_a, answer_b) = tokio::join!(a.answer(&case.prompt), b.answer(&case.prompt));
+        let show = |answer: &Result<String, String>| match answer {
+            Ok(text) => text.clone(),
+            Err(e) => format!("Error: {}", e),
+        };
+        output::line(side_by_side(&a.label, &show(&answer_a), &b.label, &show(&answer_b), width));
+
+        let (Ok(answer_a), Ok(answer_b)) = (answer_a, answer_b) else {
+            continue;
+        };
+        if answer_a.trim() == answer_b.trim() {
+            identical += 1;
+        }
+        if let (Some(judge), Some(rubric)) = (judge.as_mut(), &eval.rubric) {
+            let reply = judge.answer(&grading_prompt(rubric, &case.prompt, &answer_a, &answer_b)).await;
+            match reply.as_deref().map(parse_grade) {
+                Ok(Some(grade)) => {
+                    output::line(format!("{}\n", format!("Judge: A {}, B {}. {}", grade.a, grade.b, grade.reason).cyan()));
+                    grades.push(grade);
+                }
+                Ok(None) => eprintln!("{}\n", "Judge: couldn't read the scores in its reply".yellow()),
+                Err(e) => eprintln!("{}\n", format!("Judge failed: {}", e).yellow()),
+            }
+        }
+    }
+
+    output::line(summary(&a.label, &b.label, &grades, identical, eval.cases.len()).bold());
+    Ok(())
+}
+
+/// Connect a variant, filling unset fields from its preset and then the config
+pub(super) async fn runner(cli: &Cli, config: &ConfigManager, variant: &Variant) -> Result<Runner, QError> {
+    let preset = variant.preset.as_deref().map(|name| find_preset(config, name)).transpose()?;
+
+    let mut selection = cli.clone();
+    if let Some(model) = variant.model.clone().or_else(|| preset.and_then(|preset| preset.model.clone())) {
+        selection.model = Some(model);
+    }
+    if let (None, Some(provider)) = (&selection.provider, preset.and_then(|preset| preset.provider)) {
+        selection.provider = Some(provider.to_string());
+    }
+    let (provider, model) = resolve_provider_and_model(&selection, config)?;
+
+    let temperature = match cli.seed {
+        Some(_) => 0.0,
+        None => variant.temperature
+            .or_else(|| preset.and_then(|preset| preset.temperature))
+            .unwrap_or_else(|| config.get_temperature(provider)),
+    };
+    let model_config = ModelConfig {
+        temperature: temperature as f32,
+        environment: config.include_environment().then(environment::preamble),
+        instructions: variant.system_prompt.clone()
+            .or_else(|| preset.and_then(|preset| preset.system_prompt.clone())),
+        seed: cli.seed,
+        ..ModelConfig::default()
+    };
+    let client = connect(provider, config, Some(&model), model_config, cli.verbosity).await?;
+
+    Ok(Runner {
+        label: variant.label.clone().unwrap_or_else(|| format!("{}/{}", provider, client.model())),
+        template: variant.template.clone(),
+        engine: QueryEngine::new(client, QueryConfig {
+            max_retries: cli.max_retries,
+            show_progress: false,
+            use_cache: false,
+            ..QueryConfig::default()
+        }),
+    })
+}
+
+fn grading_prompt(rubric: &str, prompt: &str, a: &str, b: &str) -> String {
+    format!(
+        "Grade two answers to the same prompt against the rubric.\n\n\
+         Rubric:\n{}\n\nPrompt:\n{}\n\nAnswer A:\n{}\n\nAnswer B:\n{}\n\n\
+         Score each answer from 1 to 10. Reply with JSON only: \
+         {{\"a\": <score>, \"b\": <score>, \"reason\": \"<one sentence>\"}}",
+        rubric, prompt, a, b
+    )
+}
+
+/// The scores in a judge's reply, tolerating a code fence or a preamble
+fn parse_grade(reply: &str) -> Option<Grade> {
+    let start = reply.find('{')?;
+    let end = reply.rfind('}')?;
+    serde_json::from_str(reply.get(start..=end)?).ok()
+}
+
+fn summary(label_a: &str, label_b: &str, grades: &[Grade], identical: usize, cases: usize) -> String {
+    let mut summary = format!("{} cases, {} with identical answers", cases, identical);
+    if !grades.is_empty() {
+        let mean = |score: fn(&Grade) -> f64| grades.iter().map(score).sum::<f64>() / grades.len() as f64;
+        let a_wins = grades.iter().filter(|grade| grade.a > grade.b).count();
+        let b_wins = grades.iter().filter(|grade| grade.b > grade.a).count();
+        summary.push_str(&format!(
+            "\nMean score: A ({}) {:.1}, B ({}) {:.1}; A better in {}, B better in {}, tied in {}",
+            label_a,
+            mean(|grade| grade.a),
+            label_b,
+            mean(|grade| grade.b),
+            a_wins,
+            b_wins,
+            grades.len() - a_wins - b_wins
+        ));
+    }
+    summary
+}
+
+/// Columns available for output, from `COLUMNS`
+pub(super) fn terminal_width() -> usize {
+    env::var("COLUMNS").ok()
+        .and_then(|columns| columns.parse().ok())
+        .unwrap_or(DEFAULT_WIDTH)
+        .max(40)
+}
+
+/// Two answers in columns under their labels, wrapped to fit `width`
+pub(super) fn side_by_side(label_a: &str, a: &str, label_b: &str, b: &str, width: usize) -> String {
+    let column = (width.saturating_sub(3) / 2).max(1);
+    let left = [wrap(&format!("A: {}", label_a), column), vec!["─".repeat(column)], wrap(a, column)].concat();
+    let right = [wrap(&format!("B: {}", label_b), column), vec!["─".repeat(column)], wrap(b, column)].concat();
+
+    let mut output = String::new();
+    for i in 0..left.len().max(right.len()) {
+        let l = left.get(i).map(String::as_str).unwrap_or("");
+        let r = right.get(i).map(String::as_str).unwrap_or("");
+        output.push_str(format!("{:<width$} │ {}", l, r, width = column).trim_end());
+        output.push('\n');
+    }
+    output
+}
+
+/// Split `text` into lines of at most `width` characters, at spaces where possible
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    for line in text.trim_end().lines() {
+        let mut current = String::new();
+        for word in line.split(' ') {
+            if !current.is_empty() && current.chars().count() + 1 + word.chars().count() > width {
+                lines.push(std::mem::take(&mut current));
+            } else if !current.is_empty() {
+                current.push(' ');
+            }
+            current.push_str(word);
+            // A word longer than the column is split wherever it has to be
+            while current.chars().count() > width {
+                let split = current.char_indices().nth(width).map(|(i, _)| i).unwrap_or(current.len());
+                lines.push(current[..split].to_string());
+                current = current[split..].to_string();
+            }
+        }
+        lines.push(current);
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_eval_file() {
+        let eval: EvalFile = toml::from_str(
+            "rubric = \"Correct\"\n[a]\nmodel = \"gpt-4o-mini\"\n[b]\npreset = \"terse\"\ntemplate = \"Briefly: {prompt}\"\n\
+             [[case]]\nprompt = \"List files\"\n[[case]]\nname = \"tar\"\nprompt = \"Extract a tarball\"\n",
+        ).unwrap();
+        assert_eq!(eval.cases.len(), 2);
+        assert_eq!(eval.b.template.as_deref(), Some("Briefly: {prompt}"));
+        assert!(toml::from_str::<EvalFile>("[a]\n[b]\nmodle = \"x\"\n").is_err());
+    }
+
+    #[test]
+    fn test_parse_grade() {
+        let reply = "```json\n{\"a\": 6, \"b\": 8.5, \"reason\": \"B runs as written\"}\n```";
+        assert_eq!(parse_grade(reply), Some(Grade { a: 6.0, b: 8.5, reason: "B runs as written".to_string() }));
+        assert_eq!(parse_grade("Both are fine."), None);
+    }
+
+    #[test]
+    fn test_side_by_side() {
+        let output = side_by_side("x", "one two three", "y", "four", 23);
+        assert_eq!(output, "A: x       │ B: y\n────────── │ ──────────\none two    │ four\nthree      │\n");
+        assert_eq!(wrap("abcdefghij", 4), vec!["abcd", "efgh", "ij"]);
+    }
+
+    #[test]
+    fn test_summary() {
+        let grades = [
+            Grade { a: 6.0, b: 8.0, reason: String::new() },
+            Grade { a: 7.0, b: 7.0, reason: String::new() },
+        ];
+        assert_eq!(
+            summary("old", "new", &grades, 1, 3),
+            "3 cases, 1 with identical answers\nMean score: A (old) 6.5, B (new) 7.5; A better in 0, B better in 1, tied in 1"
+        );
+    }
+}
@@ -0,0 +1,49 @@
+use std::path::Path;
+
+use super::query;
+use crate::cli::args::Cli;
+use crate::utils::errors::QError;
+
+/// Words that run the command after them rather than being the command
+const WRAPPERS: &[&str] = &["sudo", "doas", "env", "time", "nohup", "nice", "exec", "command", "xargs"];
+
+/// `q explain <command line>`: a query asking what the command does, with
+/// the installed man page (or `--help`) of the program it runs as context
+pub async fn run(cli: &Cli, command: &[String]) -> Result<(), QError> {
+    // One quoted argument or several words; either way the same command line
+    let line = command.join(" ");
+    let mut cli = cli.clone();
+    cli.command = None;
+    if cli.man.is_none() {
+        cli.man = program(&line);
+    }
+
+    let prompt = format!(
+        "Explain what this shell command does, part by part: each program, flag, and argument, \
+         then any pipes or redirections. Use the documentation provided for the installed version \
+         where it applies, and point out anything risky.\n\n```sh\n{}\n```",
+        line
+    );
+    query::run(&cli, Some(&prompt)).await
+}
+
+/// The program a command line runs, past `sudo`, `env`, and variable assignments
+fn program(line: &str) -> Option<String> {
+    line.split_whitespace()
+        .find(|word| !WRAPPERS.contains(word) && !word.starts_with('-') && !word.contains('='))
+        .and_then(|word| Path::new(word).file_name())
+        .map(|name| name.to_string_lossy().into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_program() {
+        assert_eq!(program("tar -xzvf backup.tgz").as_deref(), Some("tar"));
+        assert_eq!(program("sudo -E LANG=C /usr/bin/find . -name '*.rs'").as_deref(), Some("find"));
+        assert_eq!(program("env FOO=1 time rsync -av a/ b/").as_deref(), Some("rsync"));
+        assert_eq!(program("").as_deref(), None);
+    }
+}
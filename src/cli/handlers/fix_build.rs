@@ -0,0 +1,198 @@
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use colored::*;
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use super::{ask, check_context_allowed, code_block, load_config};
+use crate::cli::args::Cli;
+use crate::context::output::clean;
+use crate::utils::errors::QError;
+use crate::utils::output;
+
+/// Build output sent with the prompt; the first errors matter most
+const MAX_OUTPUT_BYTES: usize = 32 * 1024;
+/// Lines of source shown on each side of a referenced line
+const SPAN_RADIUS: usize = 6;
+/// Files whose referenced lines are included
+const MAX_FILES: usize = 10;
+
+lazy_static! {
+    /// `path:line[:col]` (rustc, gcc, clang, go, eslint) or `path(line,col)` (tsc, MSBuild)
+    static ref LOCATION: Regex = Regex::new(r"([\w./\\-]+\.[A-Za-z]\w*)(?::(\d+)(?::\d+)?|\((\d+),\d+\))")
+        .expect("valid regex");
+}
+
+/// `q fix-build [command]`: run the build (`cargo build` by default) and, if
+/// it fails, ask for a fix with the diagnostics and the source they point at
+pub async fn run(cli: &Cli, command: &[String], patch: bool) -> Result<(), QError> {
+    let config = load_config(cli)?;
+    config.ensure_profile_exists()?;
+    // The build output and the source it points at
+    check_context_allowed(cli, &config, &["exec", "file"])?;
+    let command = if command.is_empty() { vec!["cargo".to_string(), "build".to_string()] } else { command.to_vec() };
+    let shown = command.join(" ");
+
+    let Some(diagnostics) = capture(&command)? else {
+        output::line(format!("{} succeeded; nothing to fix", shown));
+        return Ok(());
+    };
+    let sources = referenced_source(&diagnostics)?;
+
+    let instructions = if patch {
+        "Reply with only a unified diff, with paths relative to the current directory, that \
+         `git apply` accepts. No explanation."
+    } else {
+        "For each error, explain the cause in a sentence or two and show the corrected code."
+    };
+    let prompt = format!(
+        "The build command `{}` failed. Find the cause of each error and fix it, changing as \
+         little as possible. {}\n\nBuild output:\n```\n{}```\n\nReferenced source (line numbers on the left):\n{}",
+        shown,
+        instructions,
+        clean(&diagnostics, MAX_OUTPUT_BYTES),
+        sources,
+    );
+
+    let response = ask(cli, &config, &prompt).await?;
+
+    if patch {
+        output::text(extract_patch(&response));
+    } else {
+        output::answer(&response);
+    }
+    Ok(())
+}
+
+/// Runs `command` and returns its output, or `None` when it succeeds.
+/// Compilers write diagnostics to stderr; some build tools use stdout.
+pub(crate) fn capture(command: &[String]) -> Result<Option<String>, QError> {
+    eprintln!("{}", format!("Running {}", command.join(" ")).dimmed());
+    let output = Command::new(&command[0])
+        .args(&command[1..])
+        .output()
+        .map_err(|e| QError::Command(format!("Couldn't run {}: {}", command[0], e)))?;
+    if output.status.success() {
+        return Ok(None);
+    }
+    Ok(Some(format!("{}{}", String::from_utf8_lossy(&output.stderr), String::from_utf8_lossy(&output.stdout))))
+}
+
+/// Numbered source around each location the output mentions, for files
+/// under the current directory
+pub(crate) fn referenced_source(output: &str) -> Result<String, QError> {
+    let dir = env::current_dir()
+        .map_err(|e| QError::Context(format!("Failed to get current directory: {}", e)))?;
+    let sources: String = locations(output, &dir).iter()
+        .filter_map(|(path, lines)| {
+            let source = std::fs::read_to_string(dir.join(path)).ok()?;
+            Some(format!("{}:\n{}\n", path.display(), snippet(&source, lines, SPAN_RADIUS)))
+        })
+        .collect();
+    Ok(if sources.is_empty() { "(none found)\n".to_string() } else { sources })
+}
+
+/// Files under `dir` that the diagnostics point at, in order of first
+/// mention, with the lines referenced in each
+fn locations(diagnostics: &str, dir: &Path) -> Vec<(PathBuf, Vec<usize>)> {
+    let root = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+    let mut files: Vec<(PathBuf, Vec<usize>)> = Vec::new();
+    for captures in LOCATION.captures_iter(diagnostics) {
+        let Some(line) = captures.get(2).or(captures.get(3)).and_then(|line| line.as_str().parse().ok()) else {
+            continue;
+        };
+        let path = PathBuf::from(&captures[1]);
+        let inside = dir.join(&path).canonicalize().is_ok_and(|full| full.starts_with(&root) && full.is_file());
+        if !inside {
+            continue;
+        }
+        match files.iter().position(|(known, _)| *known == path) {
+            Some(index) if !files[index].1.contains(&line) => files[index].1.push(line),
+            Some(_) => {}
+            None if files.len() < MAX_FILES => files.push((path, vec![line])),
+            None => {}
+        }
+    }
+    files
+}
+
+/// The lines within `radius` of each of `lines` (1-based), numbered, with
+/// overlapping spans merged and gaps marked
+fn snippet(source: &str, lines: &[usize], radius: usize) -> String {
+    let source: Vec<&str> = source.lines().collect();
+    let mut spans: Vec<(usize, usize)> = lines.iter()
+        .filter(|&&line| line >= 1 && line <= source.len())
+        .map(|&line| (line.saturating_sub(radius).max(1), (line + radius).min(source.len())))
+        .collect();
+    spans.sort();
+
+    let mut merged: Vec<(usize, usize)> = Vec::new();
+    for (start, end) in spans {
+        match merged.last_mut() {
+            Some((_, last_end)) if start <= *last_end + 1 => *last_end = (*last_end).max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+
+    let width = merged.last().map(|(_, end)| end.to_string().len()).unwrap_or(1);
+    merged.iter()
+        .map(|&(start, end)| {
+            (start..=end)
+                .map(|number| format!("{:>width$} | {}\n", number, source[number - 1], width = width))
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("...\n")
+}
+
+/// The diff from a reply, without the code fence around it
+pub(crate) fn extract_patch(response: &str) -> String {
+    format!("{}\n", code_block(response))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_locations() {
+        let temp_dir = tempdir().unwrap();
+        fs::create_dir(temp_dir.path().join("src")).unwrap();
+        fs::write(temp_dir.path().join("src/main.rs"), "fn main() {}\n").unwrap();
+        fs::write(temp_dir.path().join("app.ts"), "let x = 1;\n").unwrap();
+
+        let diagnostics = "error[E0308]: mismatched types\n  --> src/main.rs:10:5\n\
+                           warning: unused\n  --> src/main.rs:3:1\n  --> src/main.rs:10:9\n\
+                           app.ts(4,7): error TS2322\n\
+                           /home/me/.cargo/registry/src/serde-1.0/src/de.rs:5:1\n";
+        assert_eq!(
+            locations(diagnostics, temp_dir.path()),
+            vec![(PathBuf::from("src/main.rs"), vec![10, 3]), (PathBuf::from("app.ts"), vec![4])]
+        );
+    }
+
+    #[test]
+    fn test_snippet_merges_spans() {
+        let source: String = (1..=30).map(|i| format!("line {}\n", i)).collect();
+        let snippet = snippet(&source, &[10, 3, 28, 99], 2);
+        assert_eq!(
+            snippet,
+            concat!(
+                " 1 | line 1\n 2 | line 2\n 3 | line 3\n 4 | line 4\n 5 | line 5\n...\n",
+                " 8 | line 8\n 9 | line 9\n10 | line 10\n11 | line 11\n12 | line 12\n...\n",
+                "26 | line 26\n27 | line 27\n28 | line 28\n29 | line 29\n30 | line 30\n",
+            )
+        );
+    }
+
+    #[test]
+    fn test_extract_patch() {
+        let response = "Here's the fix:\n```diff\n--- a/src/main.rs\n+++ b/src/main.rs\n@@ -1 +1 @@\n-a\n+b\n```\n";
+        assert_eq!(extract_patch(response), "--- a/src/main.rs\n+++ b/src/main.rs\n@@ -1 +1 @@\n-a\n+b\n");
+        assert_eq!(extract_patch("--- a/x\n+++ b/x"), "--- a/x\n+++ b/x\n");
+    }
+}
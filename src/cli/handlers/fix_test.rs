@@ -0,0 +1,187 @@
+use std::path::Path;
+
+use ignore::WalkBuilder;
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use super::fix_build::{capture, extract_patch, referenced_source};
+use super::{ask, check_context_allowed, load_config};
+use crate::cli::args::Cli;
+use crate::context::code::Language;
+use crate::context::output::clean;
+use crate::utils::errors::QError;
+use crate::utils::output;
+
+/// Test output sent with the prompt, from the first failure on
+const MAX_OUTPUT_BYTES: usize = 32 * 1024;
+/// Failing tests whose source is looked up
+const MAX_TESTS: usize = 10;
+/// Longest test body included, in lines
+const MAX_TEST_LINES: usize = 80;
+
+lazy_static! {
+    /// A failing test's name as cargo test, pytest, and go test report it
+    static ref FAILED_TEST: Regex = Regex::new(r"(?m)^(?:test (\S+) \.\.\. FAILED|FAILED \S+::(\w+)|\s*--- FAIL: (\w+))")
+        .expect("valid regex");
+}
+
+/// `q fix-test [--filter name] [command]`: run the tests (`cargo test` by
+/// default) and, if some fail, ask for likely causes and a fix with the
+/// failures, the tests' source, and the lines the failures point at
+pub async fn run(cli: &Cli, filter: Option<&str>, command: &[String], patch: bool) -> Result<(), QError> {
+    let config = load_config(cli)?;
+    config.ensure_profile_exists()?;
+    // The test output and the source it points at
+    check_context_allowed(cli, &config, &["exec", "file"])?;
+    let command = match (command.is_empty(), filter) {
+        (true, _) => ["cargo", "test"].into_iter().chain(filter).map(str::to_string).collect(),
+        (false, None) => command.to_vec(),
+        (false, Some(_)) => {
+            return Err(QError::Usage(
+                "--filter only applies to the default `cargo test`; put the filter in the command instead".to_string()
+            ));
+        }
+    };
+    let shown = command.join(" ");
+
+    let Some(output) = capture(&command)? else {
+        output::line(format!("{} passed; nothing to fix", shown));
+        return Ok(());
+    };
+    // cargo test prints every passing test first; the failures follow `failures:`
+    let failures = output.find("\nfailures:\n").map(|start| &output[start + 1..]).unwrap_or(&output);
+
+    let names = failed_tests(&output);
+    let dir = std::env::current_dir()
+        .map_err(|e| QError::Context(format!("Failed to get current directory: {}", e)))?;
+    let tests: String = names.iter()
+        .filter_map(|name| find_test(&dir, name))
+        .collect();
+
+    let instructions = if patch {
+        "Reply with only a unified diff, with paths relative to the current directory, that \
+         `git apply` accepts. No explanation."
+    } else {
+        "For each failing test, give the most likely cause, say whether the code or the test is \
+         wrong, and show the fix."
+    };
+    let prompt = format!(
+        "The test command `{}` failed ({}). {}\n\nTest output:\n```\n{}```\n\nFailing tests:\n{}\n\
+         Source the failures point at (line numbers on the left):\n{}",
+        shown,
+        match names.len() {
+            0 => "no failing test names recognized".to_string(),
+            1 => "1 failing test".to_string(),
+            n => format!("{} failing tests", n),
+        },
+        instructions,
+        clean(failures, MAX_OUTPUT_BYTES),
+        if tests.is_empty() { "(source not found)\n".to_string() } else { tests },
+        referenced_source(failures)?,
+    );
+
+    let response = ask(cli, &config, &prompt).await?;
+
+    if patch {
+        output::text(extract_patch(&response));
+    } else {
+        output::answer(&response);
+    }
+    Ok(())
+}
+
+/// Names of the failing tests in `output`, without module paths, in order
+fn failed_tests(output: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for captures in FAILED_TEST.captures_iter(output) {
+        let Some(full) = captures.get(1).or(captures.get(2)).or(captures.get(3)) else {
+            continue;
+        };
+        let name = full.as_str().rsplit("::").next().unwrap_or_default().to_string();
+        if !names.contains(&name) && names.len() < MAX_TESTS {
+            names.push(name);
+        }
+    }
+    names
+}
+
+/// The first definition of test `name` in a source file under `dir`, with
+/// its path and line number
+fn find_test(dir: &Path, name: &str) -> Option<String> {
+    WalkBuilder::new(dir)
+        .build()
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_some_and(|kind| kind.is_file()))
+        .filter_map(|entry| Some((Language::from_path(entry.path())?, entry.into_path())))
+        .find_map(|(language, path)| {
+            let source = std::fs::read_to_string(&path).ok()?;
+            let (line, body) = test_body(&source, language, name)?;
+            let relative = path.strip_prefix(dir).unwrap_or(&path);
+            Some(format!("{}:{}:\n```\n{}\n```\n", relative.display(), line, body))
+        })
+}
+
+/// The 1-based line where test `name` is defined in `source`, and its text:
+/// up to the closing brace, or for Python, the end of the indented block
+fn test_body(source: &str, language: Language, name: &str) -> Option<(usize, String)> {
+    let keyword = match language {
+        Language::Rust => "fn",
+        Language::Python => "def",
+        Language::Go => "func",
+        Language::JavaScript | Language::TypeScript | Language::Tsx => return None,
+    };
+    let lines: Vec<&str> = source.lines().collect();
+    let definition = format!("{} {}(", keyword, name);
+    let start = lines.iter().position(|line| line.contains(&definition))?;
+    let indent = |line: &str| line.len() - line.trim_start().len();
+
+    let mut end = start;
+    let mut depth = 0i32;
+    for (index, line) in lines.iter().enumerate().skip(start).take(MAX_TEST_LINES) {
+        end = index;
+        if language == Language::Python {
+            let next_ends = lines.get(index + 1)
+                .is_none_or(|next| !next.trim().is_empty() && indent(next) <= indent(lines[start]));
+            if next_ends {
+                break;
+            }
+        } else {
+            depth += line.matches('{').count() as i32 - line.matches('}').count() as i32;
+            if depth <= 0 && line.contains('}') {
+                break;
+            }
+        }
+    }
+    Some((start + 1, lines[start..=end].join("\n").trim_end().to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failed_tests() {
+        let output = "running 3 tests\ntest config::tests::test_load ... ok\n\
+                      test config::tests::test_save ... FAILED\n\
+                      FAILED tests/test_api.py::test_fetch - AssertionError\n\
+                      --- FAIL: TestServer (0.00s)\n    --- FAIL: TestServer/port (0.00s)\n\
+                      test config::tests::test_save ... FAILED\n";
+        assert_eq!(failed_tests(output), vec!["test_save", "test_fetch", "TestServer"]);
+    }
+
+    #[test]
+    fn test_test_body() {
+        let rust = "mod tests {\n    #[test]\n    fn test_add() {\n        assert_eq!(add(1, 1), 2);\n    }\n\n    fn other() {}\n}\n";
+        assert_eq!(
+            test_body(rust, Language::Rust, "test_add"),
+            Some((3, "    fn test_add() {\n        assert_eq!(add(1, 1), 2);\n    }".to_string()))
+        );
+
+        let python = "def test_fetch():\n    data = fetch()\n\n    assert data\n\ndef test_other():\n    pass\n";
+        assert_eq!(
+            test_body(python, Language::Python, "test_fetch"),
+            Some((1, "def test_fetch():\n    data = fetch()\n\n    assert data".to_string()))
+        );
+        assert_eq!(test_body(python, Language::Python, "test_missing"), None);
+    }
+}
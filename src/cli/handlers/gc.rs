@@ -0,0 +1,83 @@
+use std::fs;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use colored::*;
+
+use super::history::open_log;
+use super::load_config;
+use crate::cli::args::Cli;
+use crate::config::paths::{CachePaths, DataPaths};
+use crate::config::types::Retention;
+use crate::config::ConfigManager;
+use crate::context::system::format_bytes;
+use crate::core::history;
+use crate::core::retention::{self, Pruned};
+use crate::utils::errors::QError;
+use crate::utils::output;
+
+/// How often queries prune the stores on their own
+const STARTUP_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+/// When the last startup cleanup ran, in seconds since the epoch, under the state dir
+const LAST_RUN_FILE: &str = "last-gc";
+
+/// `q gc`: prune every store with limits and report what went
+pub fn run(cli: &Cli) -> Result<(), QError> {
+    let config = load_config(cli)?;
+    if config.retention().is_empty() {
+        output::line("No [retention] limits are set, so everything is kept".dimmed());
+        output::line("Set one with e.g. 'q config set retention.history.max_age_days 90'".dimmed());
+        return Ok(());
+    }
+    for (store, pruned) in prune(&config)? {
+        match pruned.removed {
+            0 => output::line(format!("{:<8} {}", store, "nothing to remove".dimmed())),
+            removed => output::line(format!("{:<8} removed {} ({})", store, removed, format_bytes(pruned.bytes))),
+        }
+    }
+    Ok(())
+}
+
+/// Prune the stores before a query, at most once a day. Cleanup never stands
+/// in the way of the query, so failures are only shown with `--verbose`.
+pub(crate) fn on_startup(cli: &Cli) {
+    let Ok(config) = load_config(cli) else { return };
+    if config.retention().is_empty() {
+        return;
+    }
+    let Ok(paths) = DataPaths::new() else { return };
+    let stamp = paths.state_dir().join(LAST_RUN_FILE);
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let last_run = fs::read_to_string(&stamp).ok().and_then(|text| text.trim().parse::<u64>().ok());
+    if last_run.is_some_and(|last_run| now.saturating_sub(last_run) < STARTUP_INTERVAL.as_secs()) {
+        return;
+    }
+
+    let result = prune(&config).and_then(|_| {
+        paths.ensure_state_dir()?;
+        fs::write(&stamp, now.to_string()).map_err(QError::Io)
+    });
+    if let (Err(e), true) = (result, cli.verbose) {
+        eprintln!("{}", format!("Couldn't prune local data: {}", e).yellow());
+    }
+}
+
+/// Each store with limits and what pruning it removed
+fn prune(config: &ConfigManager) -> Result<Vec<(&'static str, Pruned)>, QError> {
+    let limits = config.retention();
+    let mut report = Vec::new();
+    if !limits.history.is_empty() {
+        let pruned = open_log(config)?.prune(limits.history.max_age(), limits.history.max_bytes(), history::now_id())?;
+        report.push(("history", pruned));
+    }
+    if !limits.sessions.is_empty() {
+        report.push(("sessions", prune_dir(&DataPaths::new()?.sessions_dir(), &limits.sessions)?));
+    }
+    if !limits.cache.is_empty() {
+        report.push(("cache", prune_dir(CachePaths::new()?.cache_dir(), &limits.cache)?));
+    }
+    Ok(report)
+}
+
+fn prune_dir(dir: &std::path::Path, limits: &Retention) -> Result<Pruned, QError> {
+    Ok(retention::prune_dir(dir, limits.max_age(), limits.max_bytes(), SystemTime::now())?)
+}
@@ -0,0 +1,161 @@
+use colored::*;
+
+use super::configured_model;
+use crate::cli::args::Cli;
+use crate::config::paths::DataPaths;
+use crate::config::types::Provider;
+use crate::config::ConfigManager;
+use crate::core::health::{self, Health, Outage};
+use crate::core::CoreError;
+
+/// Where a query goes once recent outages are taken into account
+#[derive(Debug, PartialEq)]
+pub(crate) struct Route {
+    pub provider: Provider,
+    pub model: String,
+    /// The provider has been failing and nothing else could take over, so
+    /// try it once rather than retrying into another long timeout
+    pub fail_fast: bool,
+}
+
+/// Check `provider` against the outages recorded by earlier runs. While it
+/// has been failing, warn right away and switch to the first healthy
+/// provider in `[settings] failover` that has a key, unless the provider or
+/// model was chosen on the command line.
+pub(crate) fn route(cli: &Cli, config: &ConfigManager, provider: Provider, model: String) -> Route {
+    let Ok(paths) = DataPaths::new() else {
+        return Route { provider, model, fail_fast: false };
+    };
+    let route = choose(cli, config, &Health::load(&paths.provider_health()), health::now(), provider, model);
+    if !cli.quiet {
+        if let Some(warning) = &route.1 {
+            eprintln!("{}", warning.yellow());
+        }
+    }
+    route.0
+}
+
+/// `route`'s choice and the warning to show, given the recorded health
+fn choose(
+    cli: &Cli,
+    config: &ConfigManager,
+    health: &Health,
+    now: u64,
+    provider: Provider,
+    model: String,
+) -> (Route, Option<String>) {
+    let Some(outage) = health.failing(provider.as_str(), now) else {
+        return (Route { provider, model, fail_fast: false }, None);
+    };
+    let chosen = cli.provider.is_some() || cli.model.is_some();
+    let backup = config.failover().iter().copied().find(|&backup| {
+        backup != provider && health.failing(backup.as_str(), now).is_none() && has_credentials(config, backup)
+    });
+    match backup {
+        Some(backup) if !chosen => {
+            let warning = format!("{}; using {} from [settings] failover", describe(provider, outage), backup);
+            (Route { provider: backup, model: configured_model(config, backup), fail_fast: false }, Some(warning))
+        }
+        _ => {
+            let mut warning = format!("{}; trying once without retries", describe(provider, outage));
+            if !chosen && config.failover().is_empty() {
+                warning.push_str(" (list backups under [settings] failover to switch automatically)");
+            }
+            (Route { provider, model, fail_fast: true }, Some(warning))
+        }
+    }
+}
+
+fn describe(provider: Provider, outage: &Outage) -> String {
+    format!(
+        "{} failed the last {} times in the past {} minutes ({})",
+        provider,
+        outage.failures,
+        health::OUTAGE_WINDOW.as_secs() / 60,
+        outage.reason
+    )
+}
+
+fn has_credentials(config: &ConfigManager, provider: Provider) -> bool {
+    (provider == Provider::Gemini && config.vertex().is_some()) || !config.resolve_api_keys(provider).is_empty()
+}
+
+/// Record how a query to `provider` ended for later runs. Nothing is written
+/// with --incognito, and a file that can't be written is only noted with
+/// --verbose; the answer matters more.
+pub(crate) fn note(cli: &Cli, provider: Provider, error: Option<&CoreError>) {
+    if cli.incognito {
+        return;
+    }
+    let Ok(paths) = DataPaths::new() else { return };
+    let path = paths.provider_health();
+    let mut health = Health::load(&path);
+    if !health.record(provider.as_str(), error, health::now()) {
+        return;
+    }
+    let result = paths.ensure_state_dir().and_then(|_| Ok(health.save(&path)?));
+    if let (Err(e), true) = (result, cli.verbose) {
+        eprintln!("{}", format!("Couldn't record provider health: {}", e).yellow());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::ApiError;
+    use clap::Parser;
+    use tempfile::TempDir;
+
+    fn failing_openai() -> Health {
+        let mut health = Health::default();
+        for now in [100, 110] {
+            health.record("openai", Some(&CoreError::Api(ApiError::Timeout)), now);
+        }
+        health
+    }
+
+    fn config(temp_dir: &TempDir, toml: &str) -> ConfigManager {
+        let root = temp_dir.path().join("q");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("config.toml"), toml).unwrap();
+        ConfigManager::with_root(root, false).unwrap()
+    }
+
+    #[test]
+    fn test_fails_over_to_a_backup_with_a_key() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = config(
+            &temp_dir,
+            "[settings]\ndefault_provider = \"openai\"\nfailover = [\"mistral\", \"groq\"]\n\n\
+             [settings.models]\ngroq = \"llama-3.3-70b-versatile\"\n\n[api_keys]\ngroq = \"gsk_test\"\n",
+        );
+        let cli = Cli::parse_from(["q", "hi"]);
+        let (route, warning) = choose(&cli, &config, &failing_openai(), 120, Provider::OpenAI, "gpt-4o".to_string());
+        assert_eq!(
+            route,
+            Route { provider: Provider::Groq, model: "llama-3.3-70b-versatile".to_string(), fail_fast: false }
+        );
+        let warning = warning.unwrap();
+        assert!(warning.starts_with("openai failed the last 2 times"), "{}", warning);
+        assert!(warning.ends_with("using groq from [settings] failover"));
+
+        // A provider picked on the command line is kept, but tried only once
+        let cli = Cli::parse_from(["q", "-P", "openai", "hi"]);
+        let (route, _) = choose(&cli, &config, &failing_openai(), 120, Provider::OpenAI, "gpt-4o".to_string());
+        assert_eq!(route.provider, Provider::OpenAI);
+        assert!(route.fail_fast);
+    }
+
+    #[test]
+    fn test_healthy_or_no_backup() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = config(&temp_dir, "[settings]\ndefault_provider = \"openai\"\n");
+        let cli = Cli::parse_from(["q", "hi"]);
+        let (route, warning) = choose(&cli, &config, &Health::default(), 120, Provider::OpenAI, "gpt-4o".to_string());
+        assert!(!route.fail_fast && warning.is_none());
+
+        let (route, warning) = choose(&cli, &config, &failing_openai(), 120, Provider::OpenAI, "gpt-4o".to_string());
+        assert!(route.fail_fast);
+        assert!(warning.unwrap().contains("list backups under [settings] failover"));
+    }
+}
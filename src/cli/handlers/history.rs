@@ -0,0 +1,151 @@
+use colored::*;
+
+use super::load_config;
+use crate::cli::args::{Cli, HistoryCommand};
+use crate::config::encryption::history_key;
+use crate::config::paths::DataPaths;
+use crate::config::ConfigManager;
+use crate::core::crypto::Cipher;
+use crate::core::history::{self, FeedbackRecord, HistoryLog, QueryRecord, Rating, Record, Tally};
+use crate::utils::errors::QError;
+use crate::utils::output;
+
+/// Prompt characters quoted when confirming feedback
+const PROMPT_PREVIEW_CHARS: usize = 60;
+
+/// The history log, encrypted when `[history] encrypt` is on
+pub(crate) fn open_log(config: &ConfigManager) -> Result<HistoryLog, QError> {
+    let paths = DataPaths::new()?;
+    paths.ensure_data_dir()?;
+    let log = HistoryLog::new(paths.history_log());
+    Ok(if config.history().encrypt {
+        log.with_cipher(Cipher::new(&history_key()?))
+    } else {
+        log
+    })
+}
+
+/// Append an answered query to the history log. A log that can't be written
+/// only earns a warning; the answer has already been printed.
+pub(crate) fn record(
+    config: &ConfigManager,
+    provider: &str,
+    model: &str,
+    preset: Option<&str>,
+    prompt: &str,
+    response: &str,
+) {
+    let record = Record::Query(QueryRecord {
+        id: history::now_id(),
+        provider: provider.to_string(),
+        model: model.to_string(),
+        preset: preset.map(str::to_string),
+        prompt: prompt.to_string(),
+        response: response.to_string(),
+    });
+    if let Err(e) = open_log(config).and_then(|log| log.append(&record).map_err(QError::Io)) {
+        eprintln!("{}", format!("Couldn't write the history log: {}", e).yellow());
+    }
+}
+
+/// `q feedback 👍|👎 [comment]`: rate the last answer
+pub fn feedback(cli: &Cli, rating: &str, comment: &[String]) -> Result<(), QError> {
+    let rating = Rating::parse(rating)
+        .ok_or_else(|| QError::Usage(format!("'{}' isn't a rating; use 👍 or 👎 (or up/down)", rating)))?;
+    let log = open_log(&load_config(cli)?)?;
+    let query = log.last_query()?
+        .ok_or_else(|| QError::Usage("No answers in the history log to rate yet".to_string()))?;
+
+    let comment = comment.join(" ");
+    log.append(&Record::Feedback(FeedbackRecord {
+        query: query.id,
+        rating,
+        comment: (!comment.trim().is_empty()).then_some(comment),
+    }))?;
+
+    let mut preview: String = query.prompt.chars().take(PROMPT_PREVIEW_CHARS).collect();
+    if preview.len() < query.prompt.len() {
+        preview.push_str("...");
+    }
+    let thumb = match rating {
+        Rating::Up => "👍",
+        Rating::Down => "👎",
+    };
+    output::line(format!("{} {}/{}: {}", thumb, query.provider, query.model, preview.dimmed()));
+    Ok(())
+}
+
+pub fn run(cli: &Cli, action: &HistoryCommand) -> Result<(), QError> {
+    let config = load_config(cli)?;
+    match action {
+        HistoryCommand::Stats => stats(&config),
+        HistoryCommand::Encrypt => encrypt(&config),
+    }
+}
+
+/// Queries and ratings per model and per preset
+fn stats(config: &ConfigManager) -> Result<(), QError> {
+    let records = open_log(config)?.records()?;
+    if !records.iter().any(|record| matches!(record, Record::Query(_))) {
+        output::line("No queries in the history log yet".dimmed());
+        return Ok(());
+    }
+
+    let by_model = history::tally(&records, |query| Some(format!("{}/{}", query.provider, query.model)));
+    output::line("By model".bold());
+    output::text(format_tallies(&by_model));
+
+    let by_preset = history::tally(&records, |query| query.preset.clone());
+    if !by_preset.is_empty() {
+        output::line(format!("\n{}", "By preset".bold()));
+        output::text(format_tallies(&by_preset));
+    }
+    Ok(())
+}
+
+/// Seal the records written before encryption was turned on
+fn encrypt(config: &ConfigManager) -> Result<(), QError> {
+    if !config.history().encrypt {
+        return Err(QError::Usage(
+            "Turn on encryption first with 'q config set history.encrypt true'".to_string()
+        ));
+    }
+    match open_log(config)?.encrypt_existing()? {
+        0 => output::line("The history log has no unencrypted records".dimmed()),
+        sealed => output::line(format!("Encrypted {} history record{}", sealed, if sealed == 1 { "" } else { "s" })),
+    }
+    Ok(())
+}
+
+/// `name  queries  👍 up  👎 down  satisfaction`, one line per group
+fn format_tallies(tallies: &[(String, Tally)]) -> String {
+    let width = tallies.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+    tallies.iter()
+        .map(|(name, tally)| {
+            let satisfaction = tally.satisfaction()
+                .map(|share| format!("{:.0}% satisfied", share * 100.0))
+                .unwrap_or_else(|| "not rated".to_string());
+            format!(
+                "  {:<width$}  {:>4} queries  👍 {:<3} 👎 {:<3} {}\n",
+                name, tally.queries, tally.up, tally.down, satisfaction, width = width
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_tallies() {
+        let tallies = vec![
+            ("openai/gpt-4o".to_string(), Tally { queries: 12, up: 3, down: 1 }),
+            ("groq/llama".to_string(), Tally { queries: 2, up: 0, down: 0 }),
+        ];
+        assert_eq!(
+            format_tallies(&tallies),
+            "  openai/gpt-4o    12 queries  👍 3   👎 1   75% satisfied\n  groq/llama        2 queries  👍 0   👎 0   not rated\n"
+        );
+    }
+}
@@ -0,0 +1,127 @@
+use std::io::ErrorKind;
+use std::path::Path;
+use std::process::Command;
+
+use colored::*;
+
+use super::pr::truncate_diff;
+use super::{ask_with, check_context_allowed, code_block, load_config, query_engine};
+use crate::cli::args::Cli;
+use crate::utils::errors::QError;
+use crate::utils::output;
+
+/// Sample bytes sent with the prompt; the shape matters, not every record
+const MAX_SAMPLE_BYTES: usize = 16 * 1024;
+/// Lines of jq output shown as a preview after a successful check
+const PREVIEW_LINES: usize = 10;
+
+/// How running an expression against the sample went
+enum Check {
+    Passed(String),
+    Failed(String),
+    /// jq isn't installed
+    Skipped,
+}
+
+/// `q jq [--sample file] <question>`: one jq expression, printed bare.
+/// With a sample, the expression is run against it and, if jq rejects it,
+/// sent back once with the error to be fixed.
+pub async fn run(cli: &Cli, question: &str, sample: Option<&Path>) -> Result<(), QError> {
+    let config = load_config(cli)?;
+    config.ensure_profile_exists()?;
+
+    let mut prompt = format!(
+        "Write one jq filter that does this: {}\n\
+         Reply with a single ```jq code block containing only the filter (no `jq` command, no \
+         quotes around it) and nothing else.",
+        question
+    );
+    if let Some(path) = sample {
+        check_context_allowed(cli, &config, &["file"])?;
+        let data = std::fs::read_to_string(path)
+            .map_err(|e| QError::Context(format!("Failed to read sample {}: {}", path.display(), e)))?;
+        prompt.push_str(&format!(
+            " It runs on input shaped like this sample ({}):\n```json\n{}\n```",
+            path.display(),
+            truncate_diff(&data, MAX_SAMPLE_BYTES).trim_end()
+        ));
+    }
+
+    let engine = query_engine(cli, &config).await?;
+    let response = ask_with(&engine, &prompt).await?;
+    let mut filter = code_block(&response).trim().to_string();
+
+    let Some(path) = sample else {
+        output::line(&filter);
+        return Ok(());
+    };
+    let mut result = check(&filter, path)?;
+    if let Check::Failed(error) = &result {
+        eprintln!("{}", format!("jq rejected the filter ({}); asking for a fix", first_line(error)).yellow());
+        let retry = format!(
+            "{}\n\nThis filter failed on the sample:\n```jq\n{}\n```\njq said:\n```\n{}\n```\nFix it.",
+            prompt, filter, error.trim_end()
+        );
+        let response = ask_with(&engine, &retry).await?;
+        filter = code_block(&response).trim().to_string();
+        result = check(&filter, path)?;
+    }
+
+    output::line(&filter);
+    match result {
+        Check::Passed(output) => {
+            let lines: Vec<&str> = output.lines().collect();
+            eprintln!("{}", format!("Checked against {}:", path.display()).green());
+            for line in lines.iter().take(PREVIEW_LINES) {
+                eprintln!("  {}", line.dimmed());
+            }
+            if lines.len() > PREVIEW_LINES {
+                eprintln!("  {}", format!("... {} more lines", lines.len() - PREVIEW_LINES).dimmed());
+            }
+            Ok(())
+        }
+        Check::Failed(error) => Err(QError::Command(format!("jq still rejects the filter: {}", error.trim_end()))),
+        Check::Skipped => {
+            eprintln!("{}", "jq isn't installed, so the filter wasn't checked against the sample".dimmed());
+            Ok(())
+        }
+    }
+}
+
+/// Run `filter` on the sample with the local jq
+fn check(filter: &str, sample: &Path) -> Result<Check, QError> {
+    let output = match Command::new("jq").arg(filter).arg(sample).output() {
+        Ok(output) => output,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Check::Skipped),
+        Err(e) => return Err(QError::Command(format!("Couldn't run jq: {}", e))),
+    };
+    Ok(if output.status.success() {
+        Check::Passed(String::from_utf8_lossy(&output.stdout).into_owned())
+    } else {
+        Check::Failed(String::from_utf8_lossy(&output.stderr).into_owned())
+    })
+}
+
+fn first_line(text: &str) -> &str {
+    text.lines().next().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_check_against_sample() {
+        let temp_dir = tempdir().unwrap();
+        let sample = temp_dir.path().join("data.json");
+        std::fs::write(&sample, r#"[{"status": "ok"}, {"status": "failed"}, {"status": "ok"}]"#).unwrap();
+
+        match check("group_by(.status) | map({status: .[0].status, count: length})", &sample).unwrap() {
+            Check::Passed(output) => assert!(output.contains("\"count\": 2")),
+            Check::Failed(error) => panic!("{}", error),
+            Check::Skipped => {}
+        }
+        assert!(!matches!(check(".[] | groupby(", &sample).unwrap(), Check::Passed(_)));
+    }
+}
@@ -0,0 +1,98 @@
+use colored::*;
+
+use super::{build_client, load_config};
+use crate::api::ModelConfig;
+use crate::cli::args::{Cli, KeysCommand, Verbosity};
+use crate::config::types::{mask_api_key, Provider};
+use crate::utils::errors::QError;
+use crate::utils::output;
+
+pub async fn run(cli: &Cli, action: &KeysCommand) -> Result<(), QError> {
+    match action {
+        KeysCommand::List => list(cli),
+        KeysCommand::Remove { provider } => remove(cli, provider),
+        KeysCommand::Test { provider } => test(cli, provider).await,
+    }
+}
+
+fn list(cli: &Cli) -> Result<(), QError> {
+    let config = load_config(cli)?;
+    config.ensure_profile_exists()?;
+    if let Some(profile) = config.active_profile() {
+        output::line(format!("profile: {}", profile).dimmed());
+    }
+
+    for provider in Provider::ALL {
+        if let (Provider::Gemini, Some(vertex)) = (provider, config.vertex()) {
+            output::line(format!(
+                "{:<10} {:<20} {}",
+                provider.as_str(),
+                format!("vertex:{}", vertex.project),
+                format!("({}, oauth)", vertex.location).dimmed()
+            ));
+            continue;
+        }
+        let keys = config.resolve_api_keys(provider);
+        if keys.is_empty() {
+            output::line(format!("{:<10} {}", provider.as_str(), "not set".dimmed()));
+        }
+        for resolved in keys {
+            output::line(format!(
+                "{:<10} {:<20} {}",
+                provider.as_str(),
+                mask_api_key(&resolved.key),
+                format!("({})", resolved.source).dimmed()
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn remove(cli: &Cli, provider: &str) -> Result<(), QError> {
+    let provider = Provider::try_from(provider)
+        .map_err(QError::Config)?;
+
+    let mut config = load_config(cli)?;
+    if config.remove_api_key(provider)? {
+        output::line(format!("Removed API key for {}", provider));
+    } else {
+        output::line(format!("No stored API key for {}", provider));
+    }
+
+    if config.active_profile().is_none() && std::env::var(provider.env_var()).is_ok() {
+        eprintln!("{}", format!("Note: {} is still set in the environment", provider.env_var()).yellow());
+    }
+    Ok(())
+}
+
+async fn test(cli: &Cli, provider: &str) -> Result<(), QError> {
+    let provider = Provider::try_from(provider)
+        .map_err(QError::Config)?;
+
+    let config = load_config(cli)?;
+    let keys = config.resolve_api_keys(provider);
+    if keys.is_empty() {
+        return Err(QError::Config(format!("No API key configured for {}", provider)));
+    }
+
+    let mut failures = 0;
+    for resolved in &keys {
+        let label = format!("{} key {} ({})", provider, mask_api_key(&resolved.key), resolved.source);
+        let result = match build_client(provider, &resolved.key, None, ModelConfig::default(), Verbosity::default()) {
+            Ok(client) => client.validate_key().await.map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+        match result {
+            Ok(()) => output::line(format!("{} is valid", label)),
+            Err(e) => {
+                output::line(format!("{} failed: {}", label, e));
+                failures += 1;
+            }
+        }
+    }
+
+    if failures > 0 {
+        return Err(QError::Api(format!("{} of {} {} keys failed", failures, keys.len(), provider)));
+    }
+    Ok(())
+}
@@ -0,0 +1,359 @@
+//! Handlers for the query path and each subcommand, dispatched from `cli::run`.
+
+pub mod alias;
+pub mod ask;
+pub mod audio;
+pub mod changelog;
+pub mod commands;
+pub mod commit;
+pub mod compare;
+pub mod consensus;
+pub mod config;
+pub mod cron;
+pub mod eval;
+pub mod explain;
+pub mod fix_build;
+pub mod fix_test;
+pub mod gc;
+pub mod health;
+pub mod history;
+pub mod jq;
+pub mod keys;
+pub mod models;
+pub mod pr;
+pub mod profile;
+pub mod query;
+pub mod queue;
+pub mod regex;
+pub mod review;
+pub mod runbook;
+pub mod schedule;
+pub mod self_update;
+pub mod serve;
+pub mod speech;
+pub mod sql;
+pub mod template;
+pub mod watch;
+pub mod what_changed;
+
+use std::path::Path;
+use std::sync::Arc;
+use colored::*;
+
+use crate::api::{gemini::GeminiClient, groq, mistral, openai::{OpenAIClient, WebSearch}, openrouter, vertex, LLMApi, ModelConfig};
+use crate::api::rotation::RotatingClient;
+use crate::cli::args::{Cli, Verbosity};
+use crate::config::types::Provider;
+use crate::config::ConfigManager;
+use crate::context::assembler::{ContextAssembler, DEFAULT_MAX_BYTES};
+use crate::context::environment;
+use crate::context::git::{git, without_ignored};
+use crate::context::qignore::QIgnore;
+use crate::core::{CoreError, QueryConfig, QueryEngine};
+use crate::utils::errors::QError;
+use crate::utils::i18n;
+
+/// Load the config with the profile selected on the command line
+pub(crate) fn load_config(cli: &Cli) -> Result<ConfigManager, QError> {
+    Ok(ConfigManager::new(cli.verbose)?.with_profile(cli.profile.as_deref()))
+}
+
+/// A client for the provider and model selected on the command line (or in
+/// the config), with the configured temperature and environment preamble
+pub(crate) async fn query_client(cli: &Cli, config: &ConfigManager) -> Result<Arc<dyn LLMApi>, QError> {
+    let (provider, model) = resolve_provider_and_model(cli, config)?;
+    let model_config = ModelConfig {
+        temperature: config.get_temperature(provider) as f32,
+        environment: config.include_environment().then(environment::preamble),
+        ..ModelConfig::default()
+    };
+    let client = connect(provider, config, Some(&model), model_config, cli.verbosity).await?;
+    eprintln!("{}", i18n::message_with("status-provider", &[("provider", &provider.to_string()), ("model", client.model())]).dimmed());
+    Ok(client)
+}
+
+/// An engine for subcommands that ask the model themselves: the selected
+/// client with `--retries` and a spinner
+pub(crate) async fn query_engine(cli: &Cli, config: &ConfigManager) -> Result<QueryEngine, QError> {
+    let client = query_client(cli, config).await?;
+    Ok(QueryEngine::new(client, QueryConfig {
+        max_retries: cli.max_retries,
+        show_progress: cli.show_progress(),
+        ..QueryConfig::default()
+    }))
+}
+
+/// The answer to `prompt` from a fresh `query_engine`
+pub(crate) async fn ask(cli: &Cli, config: &ConfigManager, prompt: &str) -> Result<String, QError> {
+    ask_with(&query_engine(cli, config).await?, prompt).await
+}
+
+/// The answer to `prompt` from `engine`, for subcommands that ask again
+/// with what was wrong with the first answer
+pub(crate) async fn ask_with(engine: &QueryEngine, prompt: &str) -> Result<String, QError> {
+    engine.query(prompt).await.map_err(|e| query_failed(e, engine))
+}
+
+/// Refuse when the `[context] allow` policy keeps any of `kinds` from the
+/// selected provider, for subcommands that gather their own context
+pub(crate) fn check_context_allowed(cli: &Cli, config: &ConfigManager, kinds: &[&str]) -> Result<(), QError> {
+    let (provider, _) = resolve_provider_and_model(cli, config)?;
+    ContextAssembler::from_settings(config.context())
+        .check_kinds(kinds, provider.as_str())
+        .map_err(|e| QError::Context(e.to_string()))
+}
+
+/// A `git diff` for subcommands that send one themselves, without the files
+/// `.qignore` and `[context] ignore` leave out
+pub(crate) struct Diff {
+    pub text: String,
+    /// Repository-relative paths of the files left out
+    ignored: Vec<String>,
+}
+
+impl Diff {
+    /// `git diff ARGS` in `dir`
+    pub(crate) fn load(config: &ConfigManager, dir: &Path, args: &[&str]) -> Result<Self, QError> {
+        let context = |e: crate::context::ContextError| QError::Context(e.to_string());
+        let diff = git(dir, &[&["diff"], args].concat()).map_err(context)?;
+        let root = git(dir, &["rev-parse", "--show-toplevel"]).map_err(context)?;
+        let ignore = QIgnore::load(dir, &config.context().ignore).map_err(context)?;
+        let (text, ignored) = without_ignored(&ignore, Path::new(root.trim()), &diff);
+        if !ignored.is_empty() {
+            let count = ignored.len();
+            eprintln!("{}", format!("{} ignored file{} left out of the diff", count, if count == 1 { "" } else { "s" }).dimmed());
+        }
+        Ok(Self { text, ignored: ignored.into_iter().map(String::from).collect() })
+    }
+
+    /// `git diff --stat ARGS` in `dir`, without the files left out of the diff
+    pub(crate) fn stat(&self, dir: &Path, args: &[&str]) -> Result<String, QError> {
+        let excludes: Vec<String> = self.ignored.iter()
+            .map(|path| format!(":(top,exclude,literal){}", path))
+            .collect();
+        let mut stat_args = vec!["diff", "--stat"];
+        stat_args.extend(args);
+        if !excludes.is_empty() {
+            stat_args.extend(["--", ":/"]);
+            stat_args.extend(excludes.iter().map(String::as_str));
+        }
+        git(dir, &stat_args).map_err(|e| QError::Context(e.to_string()))
+    }
+}
+
+/// Bytes of diff a subcommand sends: the `[context] max_bytes` budget, or
+/// the `diff` quota when that's smaller
+pub(crate) fn diff_budget(config: &ConfigManager) -> usize {
+    let settings = config.context();
+    let budget = settings.max_bytes.unwrap_or(DEFAULT_MAX_BYTES);
+    settings.quotas.get("diff").map_or(budget, |quota| budget.min(*quota))
+}
+
+/// A failed query, citing the provider's request ID for support tickets when there is one
+pub(crate) fn query_failed(error: CoreError, engine: &QueryEngine) -> QError {
+    match engine.last_request_id() {
+        Some(id) => QError::Core(format!("Query failed: {} (request ID: {})", error, id)),
+        None => QError::Core(format!("Query failed: {}", error)),
+    }
+}
+
+/// The contents of the first fenced code block in a reply, or the whole
+/// reply when it has none
+pub(crate) fn code_block(response: &str) -> &str {
+    let fenced = response.split("```").nth(1)
+        .map(|block| block.split_once('\n').map(|(_, rest)| rest).unwrap_or(block));
+    fenced.unwrap_or(response).trim_matches('\n')
+}
+
+/// The provider from `--provider` (or the config) and the model from
+/// `--model` (or the config). A `provider/model` name such as
+/// `openrouter/anthropic/claude-3.5-sonnet` picks its provider when
+/// `--provider` isn't given.
+pub(crate) fn resolve_provider_and_model(cli: &Cli, config: &ConfigManager) -> Result<(Provider, String), QError> {
+    let flag_provider = cli.provider.as_deref()
+        .map(|name| Provider::try_from(name).map_err(|e| QError::Config(format!("Invalid provider: {}", e))))
+        .transpose()?;
+
+    if let Some(model) = &cli.model {
+        return Ok(match (Provider::split_model(model), flag_provider) {
+            (Some((provider, id)), None) => (provider, id.to_string()),
+            (Some((provider, id)), Some(flag)) if provider == flag => (provider, id.to_string()),
+            (_, provider) => (provider.unwrap_or_else(|| config.default_provider()), model.clone()),
+        });
+    }
+
+    let provider = flag_provider.unwrap_or_else(|| config.default_provider());
+    Ok((provider, configured_model(config, provider)))
+}
+
+/// The model configured for `provider`, without a `provider/` prefix naming it
+pub(crate) fn configured_model(config: &ConfigManager, provider: Provider) -> String {
+    let model = config.get_model(provider);
+    let model = match Provider::split_model(model) {
+        Some((prefix, id)) if prefix == provider => id,
+        _ => model,
+    };
+    model.to_string()
+}
+
+/// Connect to `provider` as configured: Vertex AI when `[vertex]` is set for
+/// Gemini, otherwise one client per API key, rotating when there are several.
+pub(crate) async fn connect(
+    provider: Provider,
+    config: &ConfigManager,
+    model: Option<&str>,
+    model_config: ModelConfig,
+    verbosity: Verbosity,
+) -> Result<Arc<dyn LLMApi>, QError> {
+    if let (Provider::Gemini, Some(vertex)) = (provider, config.vertex()) {
+        let token = vertex::access_token(vertex.credentials.as_deref())
+            .await
+            .map_err(|e| QError::Config(format!("Vertex AI authentication failed: {}", e)))?;
+        let mut builder = GeminiClient::builder(token)
+            .with_vertex(vertex.project.clone(), vertex.location.clone());
+        if let Some(model) = model {
+            builder = builder.with_model(model.to_string());
+        }
+        let client = builder.with_config(model_config).with_verbosity(verbosity).build()
+            .map_err(|e| QError::Config(e.to_string()))?;
+        return Ok(Arc::new(client));
+    }
+
+    let api_keys = config.resolve_api_keys(provider);
+    if api_keys.is_empty() {
+        return Err(QError::Config(match config.active_profile() {
+            Some(profile) => format!(
+                "{} API key not found in profile '{}'. Use 'q --profile {} set-key {}' ({} isn't used with a profile).",
+                provider, profile, profile, provider, provider.env_var()
+            ),
+            None => format!("{} API key not found. Use 'q set-key {}' or set {}.", provider, provider, provider.env_var()),
+        }));
+    }
+
+    let mut clients = api_keys.iter()
+        .map(|resolved| build_client(provider, &resolved.key, model, model_config.clone(), verbosity))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(if clients.len() == 1 {
+        clients.remove(0)
+    } else {
+        Arc::new(RotatingClient::new(clients))
+    })
+}
+
+/// Build an API client for `provider`, optionally overriding the model
+pub(crate) fn build_client(
+    provider: Provider,
+    api_key: &str,
+    model: Option<&str>,
+    model_config: ModelConfig,
+    verbosity: Verbosity,
+) -> Result<Arc<dyn LLMApi>, QError> {
+    let api_key = api_key.to_string();
+    if provider == Provider::Gemini {
+        let mut builder = GeminiClient::builder(api_key);
+        if let Some(model) = model {
+            builder = builder.with_model(model.to_string());
+        }
+        let client = builder.with_config(model_config).with_verbosity(verbosity).build()
+            .map_err(|e| QError::Config(e.to_string()))?;
+        return Ok(Arc::new(client));
+    }
+
+    // Every other provider speaks the OpenAI chat API
+    let mut builder = match provider {
+        Provider::OpenRouter => openrouter::builder(api_key),
+        Provider::Groq => groq::builder(api_key),
+        Provider::Mistral => mistral::builder(api_key),
+        _ => OpenAIClient::builder(api_key).with_web_search(WebSearch::SearchOptions),
+    };
+    // Q_OPENAI_API_URL points OpenAI at a compatible server (or a mock server in tests)
+    if let (Provider::OpenAI, Ok(url)) = (provider, std::env::var("Q_OPENAI_API_URL")) {
+        builder = builder.with_api_url(url);
+    }
+    if let Some(model) = model {
+        builder = builder.with_model(model.to_string());
+    }
+    let client = builder.with_config(model_config).with_verbosity(verbosity).build()
+        .map_err(|e| QError::Config(e.to_string()))?;
+    Ok(Arc::new(client))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use tempfile::TempDir;
+
+    fn resolve(args: &[&str]) -> (Provider, String) {
+        let temp_dir = TempDir::new().unwrap();
+        let config = ConfigManager::with_root(temp_dir.path().join("q"), false).unwrap();
+        let cli = Cli::parse_from(args);
+        resolve_provider_and_model(&cli, &config).unwrap()
+    }
+
+    #[test]
+    fn test_diff_leaves_out_ignored_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = ConfigManager::with_root(temp_dir.path().join("q"), false).unwrap();
+        let dir = temp_dir.path().join("repo");
+        std::fs::create_dir(&dir).unwrap();
+        let run = |args: &[&str]| git(&dir, &[&["-c", "user.name=Test", "-c", "user.email=test@example.com"], args].concat()).unwrap();
+        run(&["init", "-q"]);
+        std::fs::write(dir.join(".qignore"), "secrets.env\n").unwrap();
+        std::fs::write(dir.join("main.rs"), "fn main() {}\n").unwrap();
+        std::fs::write(dir.join("secrets.env"), "TOKEN=1\n").unwrap();
+        run(&["add", "."]);
+        run(&["commit", "-q", "-m", "Initial commit"]);
+        std::fs::write(dir.join("main.rs"), "fn main() { run() }\n").unwrap();
+        std::fs::write(dir.join("secrets.env"), "TOKEN=2\n").unwrap();
+
+        let diff = Diff::load(&config, &dir, &["HEAD"]).unwrap();
+        assert!(diff.text.contains("+fn main() { run() }"));
+        assert!(!diff.text.contains("TOKEN"));
+        let stat = diff.stat(&dir, &["HEAD"]).unwrap();
+        assert!(stat.contains("main.rs"));
+        assert!(!stat.contains("secrets.env"));
+    }
+
+    #[test]
+    fn test_diff_budget() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = ConfigManager::with_root(temp_dir.path().join("q"), false).unwrap();
+        assert_eq!(diff_budget(&config), DEFAULT_MAX_BYTES);
+        config.set_value("context.max_bytes", "4096").unwrap();
+        assert_eq!(diff_budget(&config), 4096);
+        config.set_value("context.quotas.diff", "1024").unwrap();
+        assert_eq!(diff_budget(&config), 1024);
+    }
+
+    #[test]
+    fn test_code_block() {
+        assert_eq!(code_block("Here it is:\n```sql\nSELECT 1;\n```\nDone."), "SELECT 1;");
+        assert_eq!(code_block(".items | length\n"), ".items | length");
+    }
+
+    #[test]
+    fn test_model_prefix_selects_provider() {
+        assert_eq!(
+            resolve(&["q", "-M", "openrouter/anthropic/claude-3.5-sonnet", "hi"]),
+            (Provider::OpenRouter, "anthropic/claude-3.5-sonnet".to_string())
+        );
+        assert_eq!(
+            resolve(&["q", "-M", "openrouter/auto", "hi"]),
+            (Provider::OpenRouter, "openrouter/auto".to_string())
+        );
+        // Under OpenRouter, `openai/...` is an OpenRouter model id
+        assert_eq!(
+            resolve(&["q", "-P", "openrouter", "-M", "openai/gpt-4o", "hi"]),
+            (Provider::OpenRouter, "openai/gpt-4o".to_string())
+        );
+        assert_eq!(
+            resolve(&["q", "-M", "gemini-1.5-pro", "hi"]),
+            (Provider::Gemini, "gemini-1.5-pro".to_string())
+        );
+        assert_eq!(
+            resolve(&["q", "-P", "openrouter", "hi"]),
+            (Provider::OpenRouter, "openrouter/auto".to_string())
+        );
+    }
+}
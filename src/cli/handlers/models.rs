@@ -0,0 +1,79 @@
+use colored::*;
+
+use super::{connect, load_config, resolve_provider_and_model};
+use crate::api::{ModelConfig, ModelInfo};
+use crate::cli::args::{Cli, Verbosity};
+use crate::utils::errors::QError;
+use crate::utils::output;
+
+/// Print one page of the provider's model catalog, optionally filtered by `search`
+pub async fn list(cli: &Cli, search: Option<&str>, page: usize, per_page: usize) -> Result<(), QError> {
+    let config = load_config(cli)?;
+    config.ensure_profile_exists()?;
+    let (provider, _) = resolve_provider_and_model(cli, &config)?;
+
+    let client = connect(provider, &config, None, ModelConfig::default(), Verbosity::default()).await?;
+
+    let mut models = client.list_models()
+        .await
+        .map_err(|e| QError::Api(format!("Failed to list {} models: {}", provider, e)))?;
+    if let Some(search) = search {
+        let search = search.to_lowercase();
+        models.retain(|model| model.id.to_lowercase().contains(&search));
+    }
+    models.sort_by(|a, b| a.id.cmp(&b.id));
+
+    let per_page = per_page.max(1);
+    let pages = models.len().div_ceil(per_page).max(1);
+    if page == 0 || page > pages {
+        return Err(QError::Usage(format!("Page {} is out of range (1-{})", page, pages)));
+    }
+
+    let width = models.iter().map(|m| m.id.len()).max().unwrap_or(0);
+    for model in models.iter().skip((page - 1) * per_page).take(per_page) {
+        output::line(format_model(model, width));
+    }
+
+    if pages > 1 {
+        let mut footer = format!("page {} of {} ({} models)", page, pages, models.len());
+        if page < pages {
+            footer.push_str(&format!(", next: --page {}", page + 1));
+        }
+        eprintln!("{}", footer.dimmed());
+    }
+    Ok(())
+}
+
+/// `id  context  $prompt/$completion per 1M tokens`, omitting what the provider doesn't report
+fn format_model(model: &ModelInfo, width: usize) -> String {
+    let mut line = format!("{:<width$}", model.id, width = width);
+    if let Some(context) = model.context_length {
+        line.push_str(&format!("  {:>9} ctx", context));
+    }
+    if let (Some(prompt), Some(completion)) = (model.prompt_price, model.completion_price) {
+        line.push_str(&format!("  ${:.2}/${:.2} per 1M tokens", prompt * 1e6, completion * 1e6));
+    }
+    line.trim_end().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_model() {
+        let model = ModelInfo {
+            id: "anthropic/claude-3.5-sonnet".to_string(),
+            context_length: Some(200000),
+            prompt_price: Some(0.000003),
+            completion_price: Some(0.000015),
+        };
+        assert_eq!(
+            format_model(&model, 30),
+            "anthropic/claude-3.5-sonnet        200000 ctx  $3.00/$15.00 per 1M tokens"
+        );
+
+        let bare = ModelInfo { id: "gpt-4o".to_string(), ..ModelInfo::default() };
+        assert_eq!(format_model(&bare, 10), "gpt-4o");
+    }
+}
@@ -0,0 +1,156 @@
+use std::env;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use colored::*;
+
+use super::{ask, check_context_allowed, diff_budget, load_config, Diff};
+use crate::cli::args::Cli;
+use crate::context::git::git;
+use crate::utils::errors::QError;
+use crate::utils::output;
+
+const DEFAULT_TEMPLATE: &str = "## Summary\n\n## Changes\n\n## Testing\n";
+
+/// A generated pull request
+#[derive(Debug, PartialEq)]
+pub struct PullRequest {
+    pub title: String,
+    pub body: String,
+}
+
+/// Write a PR title and description for the current branch against `base`
+/// (the remote's default branch when omitted), optionally opening it with `gh`
+pub async fn run(cli: &Cli, base: Option<&str>, create: bool) -> Result<(), QError> {
+    let config = load_config(cli)?;
+    config.ensure_profile_exists()?;
+    check_context_allowed(cli, &config, &["diff"])?;
+
+    let dir = env::current_dir()
+        .map_err(|e| QError::Context(format!("Failed to get current directory: {}", e)))?;
+    let base = match base {
+        Some(base) => base.to_string(),
+        None => default_base(&dir)?,
+    };
+    let range = format!("{}...HEAD", base);
+
+    let log = git_output(&dir, &["log", "--reverse", "--format=- %s%n%b", &format!("{}..HEAD", base)])?;
+    if log.trim().is_empty() {
+        return Err(QError::Usage(format!("No commits on this branch since {}", base)));
+    }
+    let diff = Diff::load(&config, &dir, &[&range])?;
+    let stat = diff.stat(&dir, &[&range])?;
+    let diff = truncate_diff(&diff.text, diff_budget(&config));
+
+    let template = config.pr().template.as_deref().unwrap_or(DEFAULT_TEMPLATE);
+    let prompt = format!(
+        "Write a pull request title and description for these changes against {}.\n\
+         Reply with the title on the first line (no prefix or quotes, under 72 characters), \
+         a blank line, then the description in markdown following this template:\n\n{}\n\n\
+         Commits:\n{}\nFiles changed:\n{}\nDiff:\n{}",
+        base, template, log, stat, diff
+    );
+
+    let response = ask(cli, &config, &prompt).await?;
+    let pr = parse_response(&response)
+        .ok_or_else(|| QError::Api("The model returned an empty PR description".to_string()))?;
+
+    if !create {
+        output::line(format!("{}\n\n{}", pr.title, pr.body));
+        return Ok(());
+    }
+    create_pr(&pr, &base)
+}
+
+/// Hand the PR to `gh pr create`, with the body on stdin
+fn create_pr(pr: &PullRequest, base: &str) -> Result<(), QError> {
+    let base = base.strip_prefix("origin/").unwrap_or(base);
+    let mut child = Command::new("gh")
+        .args(["pr", "create", "--base", base, "--title", &pr.title, "--body-file", "-"])
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => QError::Command(
+                "gh is not installed; run without --create and paste the description".to_string()
+            ),
+            _ => QError::Command(format!("Failed to run gh: {}", e)),
+        })?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(pr.body.as_bytes())?;
+    }
+    let status = child.wait()?;
+    if !status.success() {
+        return Err(QError::Command(format!("gh pr create exited with {}", status)));
+    }
+    Ok(())
+}
+
+fn git_output(dir: &Path, args: &[&str]) -> Result<String, QError> {
+    git(dir, args).map_err(|e| QError::Context(e.to_string()))
+}
+
+/// The branch `origin/HEAD` points at, falling back to main or master
+fn default_base(dir: &Path) -> Result<String, QError> {
+    if let Ok(head) = git(dir, &["symbolic-ref", "--short", "refs/remotes/origin/HEAD"]) {
+        return Ok(head.trim().to_string());
+    }
+    for candidate in ["main", "master"] {
+        if git(dir, &["rev-parse", "--verify", "--quiet", candidate]).is_ok() {
+            return Ok(candidate.to_string());
+        }
+    }
+    Err(QError::Usage("Can't tell the base branch; pass it, e.g. q pr main".to_string()))
+}
+
+/// Cut the diff at a line boundary, noting how much was left out
+pub(crate) fn truncate_diff(diff: &str, max: usize) -> String {
+    if diff.len() <= max {
+        return diff.to_string();
+    }
+    let mut end = max;
+    while !diff.is_char_boundary(end) {
+        end -= 1;
+    }
+    let cut = diff[..end].rfind('\n').map(|i| i + 1).unwrap_or(0);
+    eprintln!("{}", format!("diff truncated to {} of {} bytes", cut, diff.len()).dimmed());
+    format!("{}[... {} more bytes of diff omitted]\n", &diff[..cut], diff.len() - cut)
+}
+
+/// Split the reply into the title (first non-empty line) and body
+fn parse_response(response: &str) -> Option<PullRequest> {
+    let mut lines = response.trim().lines();
+    let title = lines.next()?
+        .trim()
+        .trim_start_matches('#')
+        .trim()
+        .trim_start_matches("Title:")
+        .trim()
+        .trim_matches(|c| c == '"' || c == '*' || c == '`')
+        .to_string();
+    if title.is_empty() {
+        return None;
+    }
+    let body = lines.collect::<Vec<_>>().join("\n").trim().to_string();
+    Some(PullRequest { title, body })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_response() {
+        let pr = parse_response("Title: **Add --repo context**\n\n## Summary\nAdds it.\n").unwrap();
+        assert_eq!(pr, PullRequest {
+            title: "Add --repo context".to_string(),
+            body: "## Summary\nAdds it.".to_string(),
+        });
+        assert_eq!(parse_response("  \n"), None);
+    }
+
+    #[test]
+    fn test_truncate_diff() {
+        assert_eq!(truncate_diff("a\nb\n", 10), "a\nb\n");
+        assert_eq!(truncate_diff("line one\nline two\n", 12), "line one\n[... 9 more bytes of diff omitted]\n");
+    }
+}
@@ -0,0 +1,45 @@
+use colored::*;
+
+use super::load_config;
+use crate::cli::args::{Cli, ProfileCommand};
+use crate::utils::errors::QError;
+use crate::utils::output;
+
+pub fn run(cli: &Cli, action: &ProfileCommand) -> Result<(), QError> {
+    match action {
+        ProfileCommand::List => list(cli),
+        ProfileCommand::Use { name } => use_profile(cli, name),
+    }
+}
+
+fn list(cli: &Cli) -> Result<(), QError> {
+    let config = load_config(cli)?;
+    let active = config.active_profile();
+    let names = config.profile_names();
+
+    if names.is_empty() {
+        output::line("No profiles defined. Create one with 'q --profile <name> set-key <provider>'".dimmed());
+        return Ok(());
+    }
+
+    for name in names {
+        if Some(name) == active {
+            output::line(format!("* {}", name.green()));
+        } else {
+            output::line(format!("  {}", name));
+        }
+    }
+    Ok(())
+}
+
+fn use_profile(cli: &Cli, name: &str) -> Result<(), QError> {
+    let mut config = load_config(cli)?;
+    if name == "none" {
+        config.set_default_profile(None)?;
+        output::line("Default profile cleared");
+    } else {
+        config.set_default_profile(Some(name))?;
+        output::line(format!("Default profile set to {}", name));
+    }
+    Ok(())
+}
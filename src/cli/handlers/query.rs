@@ -0,0 +1,768 @@
+use std::env;
+use std::io::{BufRead, IsTerminal, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use chrono::{Datelike, Local};
+use colored::*;
+
+use super::{audio, connect, consensus, health, history, query_failed, queue, review, runbook, speech, load_config, resolve_provider_and_model};
+use crate::api::{LLMApi, ModelConfig};
+use crate::api::cutoff;
+use crate::api::pricing::{self, Price};
+use crate::cli::args::Cli;
+use crate::config::paths::CachePaths;
+use crate::config::encryption::history_key;
+use crate::config::ConfigManager;
+use crate::config::types::{Preset, Provider};
+use crate::commands::suggest::process_command_query;
+use crate::commands::Platform;
+use crate::context::{ContextConfig, ContextProvider};
+use crate::context::assembler::ContextAssembler;
+use crate::context::cargo::CargoProvider;
+use crate::context::docker::DockerProvider;
+use crate::context::directory::DirectoryProvider;
+use crate::context::environment;
+use crate::context::file::FileProvider;
+use crate::context::git::{GitDiffProvider, GitRepoProvider};
+use crate::context::history::HistoryProvider;
+use crate::context::log::LogProvider;
+use crate::context::man::ManProvider;
+use crate::context::output::{has_piped_stdin, read_stdin, OutputProvider};
+use crate::context::outline::OutlineProvider;
+use crate::context::qignore::QIgnore;
+use crate::context::system::SystemProvider;
+use crate::context::web::WebProvider;
+use crate::core::{CacheMode, CoreError, QueryEngine, QueryConfig};
+use crate::core::cache::DiskCache;
+use crate::core::crypto::Cipher;
+use crate::core::map_reduce::{self, with_context, Input};
+use crate::core::stream::{self, Clipboard, FileSink, OnFinish, Sink, Terminal};
+use crate::utils::errors::QError;
+use crate::utils::format::format_markdown;
+use crate::utils::i18n;
+use crate::utils::output;
+
+/// OpenAI model for `--web` when the configured one can't search
+const OPENAI_SEARCH_MODEL: &str = "gpt-4o-mini-search-preview";
+
+/// Run a single query (or command suggestion lookup) for the given prompt.
+/// Without a prompt, the `--audio` transcript is the prompt.
+pub async fn run(cli: &Cli, prompt: Option<&str>) -> Result<(), QError> {
+    run_with_preset(cli, prompt, None).await
+}
+
+/// `run`, with a `q ask` preset's temperature and instructions. Its provider,
+/// model, and context are already applied to `cli`; its name goes in the history log.
+pub async fn run_with_preset(cli: &Cli, prompt: Option<&str>, named_preset: Option<(&str, &Preset)>) -> Result<(), QError> {
+    let preset = named_preset.map(|(_, preset)| preset);
+    // Handle command suggestions
+    if cli.cmd_suggest {
+        let prompt = prompt.ok_or_else(|| QError::Usage("--cmd needs a prompt".to_string()))?;
+        let suggestions = process_command_query(prompt, cli.why, (!cli.all_examples).then(Platform::detect).as_ref())
+            .await
+            .map_err(|e| QError::Command(format!("Failed to get command suggestions: {}", e)))?;
+        output::answer(&suggestions);
+        return Ok(());
+    }
+
+    let config = load_config(cli)?;
+    config.ensure_profile_exists()?;
+
+    // Provider and model from the command line, then project/user config, then built-in defaults
+    let (provider, model) = resolve_provider_and_model(cli, &config)?;
+    // Skip a provider earlier runs saw failing, or at least don't wait on its retries
+    let health::Route { provider, mut model, fail_fast } = health::route(cli, &config, provider, model);
+
+    // --web uses the [web] search API when one is configured, otherwise the provider's own search
+    let native_search = cli.web && config.web().is_none();
+    if native_search {
+        match provider {
+            Provider::OpenAI if !model.contains("search") => {
+                model = OPENAI_SEARCH_MODEL.to_string();
+                if !cli.quiet {
+                    eprintln!("{}", format!("--web: using {} (OpenAI searches only with its search models)", model).dimmed());
+                }
+            }
+            Provider::OpenAI | Provider::Gemini | Provider::OpenRouter => {}
+            _ => return Err(QError::Config(format!(
+                "{} has no built-in web search; configure a [web] search API (SearxNG or Brave) to use --web",
+                provider
+            ))),
+        }
+    }
+
+    // A seed only makes answers repeatable without temperature sampling
+    let temperature = match cli.seed {
+        Some(_) => 0.0,
+        None => cli.temperature
+            .or_else(|| preset.and_then(|preset| preset.temperature))
+            .unwrap_or_else(|| config.get_temperature(provider)),
+    };
+    let model_config = ModelConfig {
+        temperature: temperature as f32,
+        max_tokens: cli.max_tokens.or_else(|| preset.and_then(|preset| preset.max_tokens)),
+        web_search: native_search,
+        environment: config.include_environment().then(environment::preamble),
+        instructions: instructions(preset, cli.runbook),
+        seed: cli.seed,
+    };
+    // Answers differ by provider, model, and settings, so they're cached apart
+    let cache_scope = format!("{}/{}\n{:?}", provider, model, model_config);
+    // --dry-run reports without a key, so the client is made once something needs it
+    let mut client: Option<Arc<dyn LLMApi>> = None;
+
+    // Gather context if requested; the assembler fits it into the [context] budget
+    let mut context = String::new();
+    let mut assembler = ContextAssembler::from_settings(config.context());
+    let context_config = ContextConfig::default();
+
+    // Transcribe the voice note: it is the prompt, or context for the given prompt
+    let transcript = match &cli.audio {
+        Some(_) if cli.dry_run => {
+            eprintln!("{}", "--dry-run: the voice note isn't transcribed, since that's a paid request too, so the estimate leaves it out".dimmed());
+            None
+        }
+        Some(path) => {
+            let connected = connect(provider, &config, Some(&model), model_config.clone(), cli.verbosity).await?;
+            let transcript = audio::transcribe(&config, provider, &connected, path, cli.quiet).await?;
+            client = Some(connected);
+            Some(transcript)
+        }
+        None => None,
+    };
+    let prompt = match (prompt, transcript) {
+        (Some(prompt), Some(transcript)) => {
+            context.push_str(&format!("Audio transcript:\n{}\n\n", transcript));
+            prompt.to_string()
+        }
+        (None, Some(transcript)) => transcript,
+        (Some(prompt), None) => prompt.to_string(),
+        (None, None) if cli.dry_run && cli.audio.is_some() => String::new(),
+        (None, None) => return Err(QError::Usage(i18n::message("no-prompt"))),
+    };
+
+    // Add web search results
+    if cli.web {
+        if let Some(web) = config.web() {
+            let provider = WebProvider::new(prompt.clone(), web.clone(), config.brave_api_key(), context_config.clone());
+            let web_context = provider.get_context().await
+                .map_err(|e| QError::Context(format!("Failed to get web context: {}", e)))?;
+            assembler.add(web_context);
+        }
+    }
+
+    // Add shell history context
+    if cli.history {
+        let provider = HistoryProvider::new(context_config.clone());
+        let history_context = provider.get_context().await
+            .map_err(|e| QError::Context(format!("Failed to get history context: {}", e)))?;
+        assembler.add(history_context);
+    }
+
+    // Add directory listing context
+    if cli.directory {
+        let current_dir = env::current_dir()
+            .map_err(|e| QError::Context(format!("Failed to get current directory: {}", e)))?;
+        let ignore = QIgnore::load(&current_dir, &config.context().ignore)
+            .map_err(|e| QError::Context(e.to_string()))?;
+        let provider = DirectoryProvider::new(current_dir, ContextConfig { ignore, ..context_config.clone() })
+            .with_follow_links(cli.follow_links);
+        let dir_context = provider.get_context().await
+            .map_err(|e| QError::Context(format!("Failed to get directory context: {}", e)))?;
+        assembler.add(dir_context);
+    }
+
+    // Add file content context
+    for file_path in &cli.file {
+        let provider = FileProvider::new(file_path.clone(), context_config.clone());
+        let file_context = provider.get_context().await
+            .map_err(|e| QError::Context(format!("Failed to get file context: {}", e)))?;
+        assembler.add(file_context);
+    }
+
+    // Add piped input
+    if !cli.no_stdin && has_piped_stdin() {
+        let input = read_stdin()
+            .map_err(|e| QError::Context(format!("Failed to read stdin: {}", e)))?;
+        if !input.trim().is_empty() {
+            let stdin_context = OutputProvider::stdin(input, context_config.clone()).get_context().await
+                .map_err(|e| QError::Context(format!("Failed to get stdin context: {}", e)))?;
+            assembler.add(stdin_context);
+        }
+    }
+
+    // Add a command's output
+    if let Some(command) = &cli.exec {
+        if !cli.quiet {
+            eprintln!("{}", format!("Running {}", command).dimmed());
+        }
+        let exec_context = OutputProvider::exec(command.clone(), context_config.clone()).get_context().await
+            .map_err(|e| QError::Context(format!("Failed to get output of {}: {}", command, e)))?;
+        assembler.add(exec_context);
+    }
+
+    // Add the end of a log file
+    if let Some(spec) = &cli.log {
+        let provider = LogProvider::new(spec.clone(), context_config.clone());
+        let log_context = provider.get_context().await
+            .map_err(|e| QError::Context(format!("Failed to get log context: {}", e)))?;
+        assembler.add(log_context);
+    }
+
+    // Add a command's installed documentation
+    if let Some(command) = &cli.man {
+        let mut provider = ManProvider::new(command.clone(), context_config.clone());
+        if let Ok(cache) = CachePaths::new() {
+            provider = provider.with_cache_dir(cache.man_dir());
+        }
+        let man_context = provider.get_context().await
+            .map_err(|e| QError::Context(format!("Failed to get documentation for {}: {}", command, e)))?;
+        assembler.add(man_context);
+    }
+
+    // Add a symbol outline
+    if let Some(path) = &cli.outline {
+        let dir = if path.is_dir() { path.as_path() } else { path.parent().unwrap_or(path) };
+        let ignore = QIgnore::load(dir, &config.context().ignore)
+            .map_err(|e| QError::Context(e.to_string()))?;
+        let provider = OutlineProvider::new(path.clone(), ContextConfig { ignore, ..context_config.clone() });
+        let outline_context = provider.get_context().await
+            .map_err(|e| QError::Context(format!("Failed to get outline context: {}", e)))?;
+        assembler.add(outline_context);
+    }
+
+    // Add git repository summary
+    if cli.repo {
+        let current_dir = env::current_dir()
+            .map_err(|e| QError::Context(format!("Failed to get current directory: {}", e)))?;
+        let provider = GitRepoProvider::new(current_dir, context_config.clone());
+        let repo_context = provider.get_context().await
+            .map_err(|e| QError::Context(format!("Failed to get repository context: {}", e)))?;
+        assembler.add(repo_context);
+    }
+
+    // Add the Cargo package summary
+    if cli.cargo {
+        let current_dir = env::current_dir()
+            .map_err(|e| QError::Context(format!("Failed to get current directory: {}", e)))?;
+        let provider = CargoProvider::new(current_dir, context_config.clone());
+        let cargo_context = provider.get_context().await
+            .map_err(|e| QError::Context(format!("Failed to get Cargo context: {}", e)))?;
+        assembler.add(cargo_context);
+    }
+
+    // Add containers, logs, and Compose files
+    if cli.docker {
+        let current_dir = env::current_dir()
+            .map_err(|e| QError::Context(format!("Failed to get current directory: {}", e)))?;
+        let provider = DockerProvider::new(current_dir, context_config.clone())
+            .with_container(cli.container.clone());
+        let docker_context = provider.get_context().await
+            .map_err(|e| QError::Context(format!("Failed to get Docker context: {}", e)))?;
+        assembler.add(docker_context);
+    }
+
+    // Add uncommitted changes
+    if cli.diff {
+        let current_dir = env::current_dir()
+            .map_err(|e| QError::Context(format!("Failed to get current directory: {}", e)))?;
+        let ignore = QIgnore::load(&current_dir, &config.context().ignore)
+            .map_err(|e| QError::Context(e.to_string()))?;
+        let provider = GitDiffProvider::new(current_dir, ContextConfig { ignore, ..context_config.clone() });
+        let diff_context = provider.get_context().await
+            .map_err(|e| QError::Context(format!("Failed to get diff context: {}", e)))?;
+        assembler.add(diff_context);
+    }
+
+    // Add system information context
+    if cli.system {
+        let provider = SystemProvider::new(context_config.clone());
+        let system_context = provider.get_context().await
+            .map_err(|e| QError::Context(format!("Failed to get system context: {}", e)))?;
+        assembler.add(system_context);
+    }
+
+    if cli.review_context && !assembler.is_empty() && !review::review(&mut assembler)? {
+        eprintln!("{}", i18n::message("cancelled-nothing-sent"));
+        return Ok(());
+    }
+    let recipients = if cli.consensus { consensus::providers(cli, &config)? } else { vec![provider] };
+    for recipient in recipients {
+        assembler.check_allowed(recipient.as_str())
+            .map_err(|e| QError::Context(e.to_string()))?;
+    }
+    // With --map-reduce, blocks too big to send whole are read in parts once the engine is ready
+    let max_bytes = assembler.max_bytes();
+    let oversized: Vec<Input> = if cli.map_reduce {
+        assembler.take_oversized().iter().map(|block| Input::new(block)).collect()
+    } else {
+        Vec::new()
+    };
+    if cli.map_reduce && oversized.is_empty() && !cli.quiet {
+        eprintln!("{}", "--map-reduce: the context fits in one prompt, so it's sent whole".dimmed());
+    }
+    if !assembler.is_empty() {
+        let assembled = assembler.assemble();
+        if !cli.quiet {
+            for note in assembled.compressed.iter().chain(&assembled.truncated) {
+                eprintln!("{}", note.dimmed());
+            }
+        }
+        context.push_str(&assembled.content);
+    }
+
+    // Build the final prompt with context
+    let mut final_prompt = with_context(&context, &prompt);
+
+    if cli.dry_run {
+        if !oversized.is_empty() {
+            let mut parts = 0;
+            for input in &oversized {
+                let part_bytes = map_reduce::part_bytes(&prompt, &context, &input.header, max_bytes)
+                    .map_err(|e| QError::Usage(e.to_string()))?;
+                parts += map_reduce::split(&input.body, part_bytes).len();
+            }
+            eprintln!("{}", format!("--map-reduce: {} parts would be read first, a query each, before the one below", parts).dimmed());
+        }
+        let price = model_price(cli, &config, provider, &model).await;
+        eprintln!("{}", dry_run_report(provider, &model, &final_prompt, price, cli.n.into()));
+        if cli.verbose {
+            output::line(&final_prompt);
+        }
+        return Ok(());
+    }
+
+    let client = match client {
+        Some(client) => client,
+        None => connect(provider, &config, Some(&model), model_config, cli.verbosity).await?,
+    };
+
+    if cli.consensus {
+        let (response, judge) = consensus::run(cli, &config, &final_prompt).await?;
+        if !cli.quiet {
+            output::answer(&response);
+        }
+        stream::deliver(outputs(cli)?, &response);
+        if !cli.incognito {
+            history::record(&config, "consensus", &judge, named_preset.map(|(name, _)| name), &prompt, &response);
+        }
+        return Ok(());
+    }
+
+    // Show connecting message with provider and model info
+    if !cli.quiet {
+        eprintln!("{}", i18n::message_with("status-provider", &[("provider", &provider.to_string()), ("model", client.model())]).dimmed());
+    }
+
+    // Create query engine config
+    let query_config = QueryConfig {
+        max_retries: if fail_fast { 0 } else { cli.max_retries },
+        show_progress: cli.show_progress(),
+        cache_ttl: Duration::from_secs(3600),
+        max_cache_size: 1000,
+        retry_delay: Duration::from_secs(1),
+        max_retry_delay: Duration::from_secs(30),
+        retry_budget: Some(Duration::from_secs(120)),
+        verbosity: cli.verbosity,
+        use_cache: !cli.no_cache,
+        stream_recovery: config.stream_recovery(),
+        cache_mode: config.response_cache(),
+    };
+
+    // Create query engine; streamed and buffered responses share its cache
+    let cache_ttl = query_config.cache_ttl;
+    let mut engine = QueryEngine::new(client.clone(), query_config);
+    if config.response_cache() != CacheMode::Memory && !cli.incognito {
+        if let Some(disk) = disk_cache(&config, CachePaths::responses_dir, cache_scope.clone(), cache_ttl) {
+            engine = engine.with_disk_cache(disk);
+        }
+    }
+
+    if !oversized.is_empty() {
+        let status = |status: &str| {
+            if !cli.quiet {
+                eprintln!("{}", status.dimmed());
+            }
+        };
+        let options = map_reduce::Options {
+            max_bytes,
+            jobs: config.map_reduce_jobs(),
+            round_delay: map_reduce::ROUND_DELAY,
+            show_progress: cli.show_progress(),
+            cache: if cli.incognito || cli.no_cache {
+                None
+            } else {
+                disk_cache(&config, CachePaths::parts_dir, cache_scope, map_reduce::CACHE_TTL).map(Arc::new)
+            },
+        };
+        let combined = map_reduce::map_reduce(&engine, &prompt, &context, &oversized, &options, status).await;
+        if let Err(e) = &combined {
+            health::note(cli, provider, Some(e));
+        }
+        final_prompt = with_context(&context, &combined.map_err(|e| query_failed(e, &engine))?);
+    }
+
+    if cli.n > 1 {
+        let answers = engine.query_n(&final_prompt, cli.n.into()).await;
+        health::note(cli, provider, answers.as_ref().err());
+        let answers = answers.map_err(|e| query_failed(e, &engine))?;
+        return if cli.pick { pick(&answers) } else { print_variations(&answers, false) };
+    }
+
+    let model = engine.model().to_string();
+    let record = |prompt: &str, response: &str| {
+        if cli.incognito {
+            return;
+        }
+        history::record(&config, provider.as_str(), &model, named_preset.map(|(name, _)| name), prompt, response)
+    };
+
+    let started = Instant::now();
+    let mut sinks = outputs(cli)?;
+    sinks.push(Box::new(OnFinish(|response: &str| record(&prompt, response))));
+    let response = answer(cli, &mut engine, &final_prompt, sinks).await;
+    health::note(cli, provider, response.as_ref().err());
+    let response = response.map_err(|e| query_failed(e, &engine))?;
+    if !cli.quiet && !cli.web {
+        if let Some(caveat) = cutoff_caveat(engine.model(), &prompt, Local::now().year()) {
+            eprintln!("{}", caveat.dimmed());
+        }
+    }
+
+    let request_id = engine.last_request_id();
+    if cli.stats {
+        let fingerprint = engine.last_system_fingerprint();
+        eprintln!("{}", stats_line(provider, engine.model(), started.elapsed(), &response, request_id.as_deref(), fingerprint.as_deref()).dimmed());
+    } else if let (true, Some(id)) = (cli.debug, &request_id) {
+        eprintln!("{}", format!("request ID: {}", id).dimmed());
+    }
+
+    if cli.speak {
+        speech::speak(&config, provider, &client, &response, cli.speech_file.as_deref(), cli.quiet).await?;
+    }
+
+    let interactive = output::can_prompt() && std::io::stdout().is_terminal();
+    if cli.runbook {
+        let steps = runbook::parse(&response);
+        if interactive && steps.iter().any(|step| step.command.is_some()) {
+            runbook::walk(&steps)?;
+        }
+    } else if interactive && !cli.quiet && !cli.no_follow_up && config.follow_ups() > 0 {
+        follow_up(cli, &mut engine, final_prompt, response, config.follow_ups(), record).await?;
+    }
+    engine.settle().await;
+    queue::after_query(cli, &config).await;
+    Ok(())
+}
+
+/// A disk cache in `dir` under the cache dir, sealed with the history key
+/// when the history is encrypted; none when the directory can't be made or
+/// the key can't be had
+pub(crate) fn disk_cache(config: &ConfigManager, dir: fn(&CachePaths) -> PathBuf, scope: String, ttl: Duration) -> Option<DiskCache> {
+    let paths = CachePaths::new().ok()?;
+    paths.ensure_cache_dir().ok()?;
+    let cache = DiskCache::new(dir(&paths), scope, ttl);
+    Some(if config.history().encrypt {
+        cache.with_cipher(Cipher::new(&history_key().ok()?))
+    } else {
+        cache
+    })
+}
+
+/// Where the answer goes besides the terminal: the `-o` file and the
+/// clipboard with `--copy`
+fn outputs(cli: &Cli) -> Result<Vec<Box<dyn Sink>>, QError> {
+    let mut sinks: Vec<Box<dyn Sink>> = Vec::new();
+    if let Some(path) = &cli.output {
+        let file = FileSink::create(path)
+            .map_err(|e| QError::Usage(format!("Can't write {}: {}", path.display(), e)))?;
+        sinks.push(Box::new(file));
+    }
+    if cli.copy {
+        sinks.push(Box::new(Clipboard));
+    }
+    Ok(sinks)
+}
+
+/// Send `prompt` through the engine and print the answer (streamed with
+/// `--stream`; with --quiet there is nothing to stream to), handing it to
+/// `sinks` too
+async fn answer(cli: &Cli, engine: &mut QueryEngine, prompt: &str, mut sinks: Vec<Box<dyn Sink + '_>>) -> Result<String, CoreError> {
+    if cli.stream && !cli.quiet && !cli.runbook {
+        sinks.insert(0, Box::new(Terminal));
+        return stream::stream_to(engine, prompt, sinks).await;
+    }
+    let response = engine.query(prompt).await?;
+    if !cli.quiet {
+        let steps = if cli.runbook { runbook::parse(&response) } else { Vec::new() };
+        if steps.is_empty() {
+            output::answer(&response);
+        } else {
+            output::text(runbook::render(&steps));
+        }
+    }
+    stream::deliver(sinks, &response);
+    Ok(response)
+}
+
+/// A preset's instructions, followed by the runbook format with `--runbook`
+fn instructions(preset: Option<&Preset>, runbook: bool) -> Option<String> {
+    let preset = preset.and_then(|preset| preset.system_prompt.as_deref());
+    let runbook = runbook.then_some(runbook::INSTRUCTIONS);
+    let parts: Vec<&str> = [preset, runbook].into_iter().flatten().collect();
+    (!parts.is_empty()).then(|| parts.join("\n\n"))
+}
+
+/// Offer a `>` prompt for up to `turns` follow-up questions, each sent with
+/// the conversation so far and passed to `record` once answered. Empty input
+/// (or end of input) finishes.
+async fn follow_up(
+    cli: &Cli,
+    engine: &mut QueryEngine,
+    prompt: String,
+    response: String,
+    turns: usize,
+    record: impl Fn(&str, &str),
+) -> Result<(), QError> {
+    let mut conversation = vec![(prompt, response)];
+    eprintln!("{}", "Ask a follow-up, or press Enter to finish".dimmed());
+    for _ in 0..turns {
+        eprint!("> ");
+        std::io::stderr().flush()?;
+        let mut line = String::new();
+        if std::io::stdin().lock().read_line(&mut line)? == 0 || line.trim().is_empty() {
+            return Ok(());
+        }
+        let question = line.trim().to_string();
+        let response = answer(cli, engine, &conversation_prompt(&conversation, &question), Vec::new())
+            .await
+            .map_err(|e| query_failed(e, engine))?;
+        record(&question, &response);
+        conversation.push((question, response));
+    }
+    eprintln!("{}", format!("That was the last of {} follow-ups (settings.follow_ups)", turns).dimmed());
+    Ok(())
+}
+
+/// The earlier turns, oldest first, followed by the new question
+pub(crate) fn conversation_prompt(conversation: &[(String, String)], question: &str) -> String {
+    let mut prompt = String::from("Continue this conversation.\n\n");
+    for (asked, answered) in conversation {
+        prompt.push_str(&format!("User:\n{}\n\nAssistant:\n{}\n\n", asked, answered));
+    }
+    prompt.push_str(&format!("User:\n{}", question));
+    prompt
+}
+
+/// A, B, C, ... for the answers of `--n`
+fn label(index: usize) -> char {
+    (b'A' + index as u8) as char
+}
+
+/// Print each answer under its label, to stderr when `--pick` will print the choice
+fn print_variations(answers: &[String], to_stderr: bool) -> Result<(), QError> {
+    for (i, answer) in answers.iter().enumerate() {
+        let block = format!("{}\n{}\n", format!("{})", label(i)).bold(), format_markdown(answer));
+        if to_stderr {
+            eprintln!("{}", block);
+        } else {
+            output::line(&block);
+        }
+    }
+    Ok(())
+}
+
+/// `--pick`: show the answers, ask for one, and print only it so it can be
+/// piped to a file or the clipboard
+fn pick(answers: &[String]) -> Result<(), QError> {
+    if !output::can_prompt() {
+        return Err(QError::Usage("--pick reads the choice from a terminal".to_string()));
+    }
+    print_variations(answers, true)?;
+    let last = label(answers.len() - 1);
+    loop {
+        eprint!("Pick A-{} (Enter to skip): ", last);
+        std::io::stderr().flush()?;
+        let mut line = String::new();
+        if std::io::stdin().lock().read_line(&mut line)? == 0 || line.trim().is_empty() {
+            return Ok(());
+        }
+        match parse_pick(&line, answers.len()) {
+            Some(index) => {
+                output::line(&answers[index]);
+                return Ok(());
+            }
+            None => eprintln!("{}", format!("Enter a letter from A to {}", last).yellow()),
+        }
+    }
+}
+
+/// The answer a `--pick` reply names: a letter (any case) or a 1-based number
+fn parse_pick(reply: &str, count: usize) -> Option<usize> {
+    let reply = reply.trim();
+    let index = match reply.parse::<usize>() {
+        Ok(number) => number.checked_sub(1)?,
+        Err(_) => {
+            let mut chars = reply.chars();
+            let letter = chars.next()?.to_ascii_uppercase();
+            if chars.next().is_some() || !letter.is_ascii_uppercase() {
+                return None;
+            }
+            (letter as u8 - b'A') as usize
+        }
+    };
+    (index < count).then_some(index)
+}
+
+/// The model's list price, or what OpenRouter reports for it when a key is
+/// set up to ask with
+async fn model_price(cli: &Cli, config: &ConfigManager, provider: Provider, model: &str) -> Option<Price> {
+    if let Some(price) = pricing::list_price(model) {
+        return Some(price);
+    }
+    if provider != Provider::OpenRouter {
+        return None;
+    }
+    let client = connect(provider, config, Some(model), ModelConfig::default(), cli.verbosity).await.ok()?;
+    let models = client.list_models().await.ok()?;
+    let model = models.iter().find(|listed| listed.id == client.model())?;
+    Some(Price { input: model.prompt_price? * 1e6, output: model.completion_price? * 1e6 })
+}
+
+/// `--dry-run`: the prompt's size in tokens and what `answers` answers to it
+/// would cost, at the low and high end
+fn dry_run_report(provider: Provider, model: &str, prompt: &str, price: Option<Price>, answers: usize) -> String {
+    let (low, high) = pricing::estimate_tokens(prompt);
+    let mut report = format!(
+        "Dry run: nothing was sent
+model: {}/{}
+prompt: {} bytes, about {}
+",
+        provider, model, prompt.len(), match (low, high) {
+            (1, 1) => "1 token".to_string(),
+            _ if low == high => format!("{} tokens", low),
+            _ => format!("{}-{} tokens", low, high),
+        }
+    );
+    let Some(price) = price else {
+        report.push_str("prices: unknown for this model, so no cost projection");
+        return report;
+    };
+    let (short, long) = pricing::ANSWER_TOKENS;
+    let cheapest = pricing::cost(price, low, short) * answers as f64;
+    let dearest = pricing::cost(price, high, long) * answers as f64;
+    report.push_str(&format!(
+        "prices: ${:.2} input, ${:.2} output per 1M tokens
+projected cost: ${:.4}-${:.4} ({} answer{} of {}-{} tokens)",
+        price.input,
+        price.output,
+        cheapest,
+        dearest,
+        answers,
+        if answers == 1 { "" } else { "s" },
+        short,
+        long
+    ));
+    report
+}
+
+/// A footnote for a prompt that asks about things newer than the model may
+/// know, with its knowledge cutoff when known and a pointer to `--web`
+fn cutoff_caveat(model: &str, prompt: &str, this_year: i32) -> Option<String> {
+    let knowledge = pricing::knowledge_cutoff(model);
+    let trigger = cutoff::time_sensitive(prompt, knowledge, this_year)?;
+    Some(match knowledge {
+        Some(knowledge) => i18n::message_with(
+            "caveat-cutoff",
+            &[("model", model), ("cutoff", &knowledge.to_string()), ("trigger", &trigger)],
+        ),
+        None => i18n::message_with("caveat-time-sensitive", &[("model", model), ("trigger", &trigger)]),
+    })
+}
+
+/// `--stats`: who answered, how long it took, the ID to quote to the provider,
+/// and the backend fingerprint that seeded answers depend on
+fn stats_line(
+    provider: Provider,
+    model: &str,
+    elapsed: Duration,
+    response: &str,
+    request_id: Option<&str>,
+    fingerprint: Option<&str>,
+) -> String {
+    let mut line = format!(
+        "provider: {}, model: {}, time: {:.2}s, answer: {} chars, request ID: {}",
+        provider,
+        model,
+        elapsed.as_secs_f64(),
+        response.chars().count(),
+        request_id.unwrap_or("none (cached or not reported)")
+    );
+    if let Some(fingerprint) = fingerprint {
+        line.push_str(&format!(", fingerprint: {}", fingerprint));
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_pick() {
+        assert_eq!(parse_pick("b\n", 3), Some(1));
+        assert_eq!(parse_pick(" C ", 3), Some(2));
+        assert_eq!(parse_pick("1", 3), Some(0));
+        assert_eq!(parse_pick("D", 3), None);
+        assert_eq!(parse_pick("0", 3), None);
+        assert_eq!(parse_pick("AB", 3), None);
+        assert_eq!(label(2), 'C');
+    }
+
+    #[test]
+    fn test_dry_run_report() {
+        let price = Some(Price { input: 2.50, output: 10.00 });
+        let report = dry_run_report(Provider::OpenAI, "gpt-4o", &"x".repeat(40_000), price, 1);
+        assert_eq!(
+            report,
+            "Dry run: nothing was sent\nmodel: openai/gpt-4o\nprompt: 40000 bytes, about 10000-13334 tokens\n\
+             prices: $2.50 input, $10.00 output per 1M tokens\nprojected cost: $0.0260-$0.0533 (1 answer of 100-2000 tokens)"
+        );
+        let report = dry_run_report(Provider::Groq, "new-model", "hi", None, 3);
+        assert!(report.contains("prompt: 2 bytes, about 1 token\n"), "{}", report);
+        assert!(dry_run_report(Provider::Groq, "new-model", "abcdefghijkl", None, 1).contains("about 3-4 tokens\n"));
+        assert!(dry_run_report(Provider::Groq, "new-model", "abcde", None, 1).contains("about 2 tokens\n"));
+        assert!(report.ends_with("unknown for this model, so no cost projection"));
+    }
+
+    #[test]
+    fn test_conversation_prompt() {
+        let conversation = vec![
+            ("List files by size".to_string(), "ls -lS".to_string()),
+            ("Reversed?".to_string(), "ls -lSr".to_string()),
+        ];
+        assert_eq!(
+            conversation_prompt(&conversation, "Only the first 5"),
+            "Continue this conversation.\n\nUser:\nList files by size\n\nAssistant:\nls -lS\n\n\
+             User:\nReversed?\n\nAssistant:\nls -lSr\n\nUser:\nOnly the first 5"
+        );
+    }
+
+    #[test]
+    fn test_cutoff_caveat() {
+        let caveat = cutoff_caveat("gpt-4o", "What's the latest tokio release?", 2026).unwrap();
+        assert!(caveat.contains("up to about 2023-10"), "{}", caveat);
+        assert!(caveat.contains("\"latest tokio release\"") && caveat.contains("--web"));
+        assert!(cutoff_caveat("my-model", "Who won in 2026?", 2026).unwrap().starts_with("This asks about \"2026\""));
+        assert_eq!(cutoff_caveat("gpt-4o", "How do I untar a file?", 2026), None);
+        assert_eq!(cutoff_caveat("gpt-4o", "Show the current git branch", 2026), None);
+    }
+
+    #[test]
+    fn test_stats_line() {
+        let line = stats_line(Provider::OpenAI, "gpt-4o", Duration::from_millis(1234), "héllo", Some("req_123"), Some("fp_44709d6fcb"));
+        assert_eq!(line, "provider: openai, model: gpt-4o, time: 1.23s, answer: 5 chars, request ID: req_123, fingerprint: fp_44709d6fcb");
+        assert!(stats_line(Provider::Gemini, "gemini-pro", Duration::ZERO, "", None, None).ends_with("request ID: none (cached or not reported)"));
+    }
+}
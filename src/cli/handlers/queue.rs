@@ -0,0 +1,265 @@
+//! `q queue add|list|flush|remove|clear`: prompts written while the network
+//! is down, sent once it's back. `flush` prints each answer and drops its
+//! prompt; a prompt the provider refuses is marked failed and skipped rather
+//! than holding up the rest. With `settings.auto_flush_queue`, a query that
+//! gets through also sends what's waiting, keeping the answers for the next
+//! `flush` to print rather than mixing them into that query's output.
+
+use colored::*;
+
+use super::{connect, health, history, load_config, query_failed, resolve_provider_and_model};
+use crate::api::ModelConfig;
+use crate::cli::args::{Cli, QueueCommand};
+use crate::config::encryption::history_key;
+use crate::config::paths::DataPaths;
+use crate::config::ConfigManager;
+use crate::context::environment;
+use crate::core::crypto::Cipher;
+use crate::core::health::is_outage;
+use crate::core::history::now_id;
+use crate::core::queue::{Answer, Queue, Queued};
+use crate::core::{QueryConfig, QueryEngine};
+use crate::utils::errors::QError;
+use crate::utils::output;
+
+/// Prompt characters shown when listing the queue
+const PROMPT_PREVIEW_CHARS: usize = 60;
+
+pub async fn run(cli: &Cli, action: &QueueCommand) -> Result<(), QError> {
+    let config = load_config(cli)?;
+    match action {
+        QueueCommand::Add { prompt } => add(cli, &config, prompt),
+        QueueCommand::List => list(&config),
+        QueueCommand::Flush => flush(cli, &config).await,
+        QueueCommand::Remove { number } => remove(&config, *number),
+        QueueCommand::Clear => {
+            open_queue(&config)?.clear()?;
+            output::line("Queue cleared");
+            Ok(())
+        }
+    }
+}
+
+/// The queue, sealed with the history log's key when the log is encrypted
+fn open_queue(config: &ConfigManager) -> Result<Queue, QError> {
+    let paths = DataPaths::new()?;
+    paths.ensure_data_dir()?;
+    let queue = Queue::new(paths.queue());
+    Ok(if config.history().encrypt {
+        queue.with_cipher(Cipher::new(&history_key()?))
+    } else {
+        queue
+    })
+}
+
+fn add(cli: &Cli, config: &ConfigManager, prompt: &str) -> Result<(), QError> {
+    if cli.incognito {
+        return Err(QError::Usage("--incognito keeps prompts off the disk, so they can't be queued".to_string()));
+    }
+    if prompt.trim().is_empty() {
+        return Err(QError::Usage("The prompt is empty".to_string()));
+    }
+    // A provider or model that can't be used is better found out now
+    resolve_provider_and_model(cli, config)?;
+    let queue = open_queue(config)?;
+    queue.add(&Queued {
+        id: now_id(),
+        prompt: prompt.to_string(),
+        provider: cli.provider.clone(),
+        model: cli.model.clone(),
+        answer: None,
+        failed: None,
+    })?;
+
+    let waiting = queue.entries()?.iter().filter(|entry| entry.answer.is_none()).count();
+    let when = if config.auto_flush_queue() { "; they're sent by `q queue flush` or the next query that gets through" } else { "; `q queue flush` sends them" };
+    output::line(format!("Queued ({} waiting{})", waiting, when));
+    Ok(())
+}
+
+fn list(config: &ConfigManager) -> Result<(), QError> {
+    let entries = open_queue(config)?.entries()?;
+    if entries.is_empty() {
+        output::line("Nothing queued".dimmed());
+        return Ok(());
+    }
+    for (index, entry) in entries.iter().enumerate() {
+        let status = match (&entry.answer, &entry.failed) {
+            (Some(answer), _) => format!("answered by {}/{}", answer.provider, answer.model).green(),
+            (None, Some(reason)) => format!("failed: {}", reason).red(),
+            (None, None) => "waiting".yellow(),
+        };
+        output::line(format!("{:>3}  {}  {}", index + 1, preview(&entry.prompt), status));
+    }
+    Ok(())
+}
+
+fn remove(config: &ConfigManager, number: usize) -> Result<(), QError> {
+    let queue = open_queue(config)?;
+    let entries = queue.entries()?;
+    let entry = number.checked_sub(1).and_then(|index| entries.get(index)).ok_or_else(|| {
+        QError::Usage(format!("There's no queued prompt {}; `q queue list` numbers them", number))
+    })?;
+    queue.update(entry.id, None)?;
+    output::line(format!("Removed {}", preview(&entry.prompt)));
+    Ok(())
+}
+
+/// Print the answers kept by earlier runs, then send the rest in order,
+/// stopping at the first that can't get through. A prompt the provider
+/// refuses is marked failed and the rest still go.
+async fn flush(cli: &Cli, config: &ConfigManager) -> Result<(), QError> {
+    let queue = open_queue(config)?;
+    let entries = queue.entries()?;
+    if entries.is_empty() {
+        output::line("Nothing queued".dimmed());
+        return Ok(());
+    }
+    let count = entries.len();
+    let mut printed = false;
+    let mut failed = 0;
+    for (index, entry) in entries.into_iter().enumerate() {
+        let answer = match entry.answer.clone() {
+            Some(answer) => answer,
+            None => match send(cli, config, &entry, cli.show_progress()).await {
+                Ok(Sent::Answered(answer)) => answer,
+                Ok(Sent::Offline(reason)) => {
+                    let waiting = count - index;
+                    eprintln!(
+                        "{}",
+                        format!("Still offline ({}); {} prompt{} stay queued", reason, waiting, if waiting == 1 { "" } else { "s" }).yellow()
+                    );
+                    return Ok(());
+                }
+                Err(e) => {
+                    eprintln!("{}", format!("Couldn't send queued prompt {}: {}", index + 1, e).yellow());
+                    queue.update(entry.id, Some(&Queued { failed: Some(e.to_string()), ..entry }))?;
+                    failed += 1;
+                    continue;
+                }
+            },
+        };
+        if printed {
+            output::line("");
+        }
+        printed = true;
+        output::line(format!("{} ({}/{})", preview(&entry.prompt), answer.provider, answer.model).bold());
+        output::answer(&answer.response);
+        queue.update(entry.id, None)?;
+    }
+    if failed > 0 {
+        let prompts = if failed == 1 { "prompt stays" } else { "prompts stay" };
+        eprintln!("{}", format!("{} failed {} queued; `q queue remove N` drops one", failed, prompts).yellow());
+    }
+    Ok(())
+}
+
+/// After a query got through: send the prompts waiting in the queue, keeping
+/// their answers for `q queue flush`. Problems only earn a warning.
+pub(crate) async fn after_query(cli: &Cli, config: &ConfigManager) {
+    if cli.incognito || !config.auto_flush_queue() {
+        return;
+    }
+    // Nothing to do, and no data dir to create, when nothing was ever queued
+    if !DataPaths::new().is_ok_and(|paths| paths.queue().exists()) {
+        return;
+    }
+    let Ok(queue) = open_queue(config) else { return };
+    // Prompts that failed wait for `q queue flush`, which shows why
+    let waiting: Vec<Queued> = queue.entries().unwrap_or_default().into_iter()
+        .filter(|entry| entry.answer.is_none() && entry.failed.is_none())
+        .collect();
+    if waiting.is_empty() {
+        return;
+    }
+
+    let mut answered = 0;
+    for entry in waiting {
+        match send(cli, config, &entry, false).await {
+            Ok(Sent::Answered(answer)) => {
+                if let Err(e) = queue.update(entry.id, Some(&Queued { answer: Some(answer), ..entry })) {
+                    eprintln!("{}", format!("Couldn't save a queued prompt's answer: {}", e).yellow());
+                    break;
+                }
+                answered += 1;
+            }
+            Ok(Sent::Offline(_)) => break,
+            Err(e) => {
+                eprintln!("{}", format!("Couldn't send a queued prompt: {}", e).yellow());
+                if queue.update(entry.id, Some(&Queued { failed: Some(e.to_string()), ..entry })).is_err() {
+                    break;
+                }
+            }
+        }
+    }
+    if answered > 0 && !cli.quiet {
+        let prompts = if answered == 1 { "prompt was" } else { "prompts were" };
+        eprintln!("{}", format!("{} queued {} answered; `q queue flush` shows the answers", answered, prompts).dimmed());
+    }
+}
+
+enum Sent {
+    Answered(Answer),
+    /// The provider couldn't be reached, so the prompt should wait
+    Offline(String),
+}
+
+/// Ask `entry`'s prompt of its provider and model, else the configured ones
+async fn send(cli: &Cli, config: &ConfigManager, entry: &Queued, show_progress: bool) -> Result<Sent, QError> {
+    let mut cli = cli.clone();
+    if entry.provider.is_some() {
+        cli.provider = entry.provider.clone();
+        cli.model = None;
+    }
+    if entry.model.is_some() {
+        cli.model = entry.model.clone();
+    }
+    let (provider, model) = resolve_provider_and_model(&cli, config)?;
+    let model_config = ModelConfig {
+        temperature: config.get_temperature(provider) as f32,
+        environment: config.include_environment().then(environment::preamble),
+        ..ModelConfig::default()
+    };
+    let client = connect(provider, config, Some(&model), model_config, cli.verbosity).await?;
+    let engine = QueryEngine::new(client, QueryConfig {
+        max_retries: cli.max_retries,
+        show_progress,
+        ..QueryConfig::default()
+    });
+
+    let response = engine.query(&entry.prompt).await;
+    health::note(&cli, provider, response.as_ref().err());
+    match response {
+        Ok(response) => {
+            if !cli.incognito {
+                history::record(config, provider.as_str(), engine.model(), None, &entry.prompt, &response);
+            }
+            Ok(Sent::Answered(Answer { provider: provider.to_string(), model: engine.model().to_string(), response }))
+        }
+        Err(e) if is_outage(&e) => Ok(Sent::Offline(e.to_string())),
+        Err(e) => Err(query_failed(e, &engine)),
+    }
+}
+
+/// The prompt's first line, shortened for a listing
+pub(crate) fn preview(prompt: &str) -> String {
+    let line = prompt.trim().lines().next().unwrap_or_default();
+    match line.char_indices().nth(PROMPT_PREVIEW_CHARS) {
+        Some((end, _)) => format!("{}…", &line[..end]),
+        None if prompt.trim().lines().nth(1).is_some() => format!("{}…", line),
+        None => line.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preview() {
+        assert_eq!(preview("  list files by size\n"), "list files by size");
+        assert_eq!(preview("first line\nsecond"), "first line…");
+        let long = "x".repeat(PROMPT_PREVIEW_CHARS + 5);
+        assert_eq!(preview(&long), format!("{}…", "x".repeat(PROMPT_PREVIEW_CHARS)));
+    }
+}
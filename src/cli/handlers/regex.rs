@@ -0,0 +1,128 @@
+use std::path::Path;
+
+use colored::*;
+use regex::Regex;
+
+use super::{ask_with, code_block, load_config, query_engine};
+use crate::cli::args::Cli;
+use crate::utils::errors::QError;
+use crate::utils::output;
+
+/// Failing cases quoted back to the model in each retry
+const MAX_REPORTED_FAILURES: usize = 10;
+
+/// A line of the test file: text the pattern must, or must not, match
+#[derive(Debug, PartialEq)]
+struct Case {
+    text: String,
+    matches: bool,
+}
+
+/// `q regex <description> [--test file]`: ask for a pattern, check it with
+/// the `regex` crate against the test cases, and send failures back until
+/// every case passes or `attempts` run out
+pub async fn run(cli: &Cli, description: &str, tests: Option<&Path>, attempts: u32) -> Result<(), QError> {
+    let config = load_config(cli)?;
+    config.ensure_profile_exists()?;
+    let cases = match tests {
+        Some(path) => parse_cases(&std::fs::read_to_string(path)
+            .map_err(|e| QError::Context(format!("Failed to read {}: {}", path.display(), e)))?),
+        None => Vec::new(),
+    };
+
+    let mut prompt = format!(
+        "Write one regular expression that does this: {}\n\
+         It must compile with Rust's `regex` crate, which has no lookaround or backreferences. \
+         It is tested as a search within each line, so anchor it with ^ and $ if the whole line \
+         must match. Reply with a single code block containing only the pattern, unquoted and \
+         unescaped for any programming language.",
+        description
+    );
+    if !cases.is_empty() {
+        prompt.push_str("\n\nTest cases:\n");
+        for case in &cases {
+            prompt.push_str(&format!("{} {}\n", if case.matches { "must match:    " } else { "must not match:" }, case.text));
+        }
+    }
+
+    let engine = query_engine(cli, &config).await?;
+
+    let mut pattern = String::new();
+    let mut problem = String::new();
+    for attempt in 1..=attempts {
+        let response = ask_with(&engine, &prompt).await?;
+        pattern = code_block(&response).trim().to_string();
+
+        problem = match Regex::new(&pattern) {
+            Err(e) => format!("It doesn't compile:\n{}", e),
+            Ok(regex) => {
+                let failures: Vec<&Case> = cases.iter().filter(|case| regex.is_match(&case.text) != case.matches).collect();
+                if failures.is_empty() {
+                    output::line(&pattern);
+                    if !cases.is_empty() {
+                        eprintln!("{}", format!("Passes all {} test cases", cases.len()).green());
+                    }
+                    return Ok(());
+                }
+                describe_failures(&failures)
+            }
+        };
+        if attempt < attempts {
+            eprintln!("{}", format!("Attempt {}: {}", attempt, problem.lines().next().unwrap_or_default()).yellow());
+        }
+        prompt.push_str(&format!("\n\nYour pattern `{}` is wrong. {}\nTry again.", pattern, problem));
+    }
+
+    output::line(&pattern);
+    Err(QError::Command(format!("No pattern passed every case in {} attempts. {}", attempts, problem.trim_end())))
+}
+
+/// Test cases, one per line: `+ text` must match, `- text` must not, and
+/// unmarked lines must match. Blank lines and `#` comments are skipped.
+fn parse_cases(text: &str) -> Vec<Case> {
+    text.lines()
+        .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let (matches, text) = match (line.strip_prefix("+ "), line.strip_prefix("- ")) {
+                (Some(text), _) => (true, text),
+                (_, Some(text)) => (false, text),
+                _ => (true, line),
+            };
+            Case { text: text.to_string(), matches }
+        })
+        .collect()
+}
+
+fn describe_failures(failures: &[&Case]) -> String {
+    let mut description = format!("It gets {} test case{} wrong:\n", failures.len(), if failures.len() == 1 { "" } else { "s" });
+    for case in failures.iter().take(MAX_REPORTED_FAILURES) {
+        let expected = if case.matches { "should match but doesn't" } else { "matches but shouldn't" };
+        description.push_str(&format!("- {:?} {}\n", case.text, expected));
+    }
+    if failures.len() > MAX_REPORTED_FAILURES {
+        description.push_str(&format!("- and {} more\n", failures.len() - MAX_REPORTED_FAILURES));
+    }
+    description
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cases() {
+        let cases = parse_cases("# ISO dates\n+ 2024-01-31\n- 12:30:00\n\n1999-12-01\n-  leading space\n");
+        assert_eq!(cases, vec![
+            Case { text: "2024-01-31".to_string(), matches: true },
+            Case { text: "12:30:00".to_string(), matches: false },
+            Case { text: "1999-12-01".to_string(), matches: true },
+            Case { text: " leading space".to_string(), matches: false },
+        ]);
+    }
+
+    #[test]
+    fn test_describe_failures() {
+        let case = Case { text: "12:30".to_string(), matches: false };
+        assert_eq!(describe_failures(&[&case]), "It gets 1 test case wrong:\n- \"12:30\" matches but shouldn't\n");
+    }
+}
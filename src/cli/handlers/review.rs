@@ -0,0 +1,138 @@
+use std::io::{BufRead, BufReader, IsTerminal, Write};
+
+use colored::*;
+
+use crate::context::assembler::ContextAssembler;
+use crate::context::system::format_bytes;
+use crate::utils::errors::QError;
+
+/// Lines of each block shown under its summary
+const PREVIEW_LINES: usize = 3;
+/// Characters of each preview line shown
+const PREVIEW_WIDTH: usize = 100;
+
+/// `--review-context`: list the context blocks about to be sent, with their
+/// sizes and first lines, and let the user switch blocks off before anything
+/// leaves the machine. Returns `false` when the user cancels.
+pub fn review(assembler: &mut ContextAssembler) -> Result<bool, QError> {
+    let mut input = terminal_input()?;
+    let count = assembler.blocks().count();
+    let mut included = vec![true; count];
+    loop {
+        eprintln!("{}", render(assembler, &included));
+        eprint!("Toggle blocks by number (e.g. 1 3, all, none), Enter to send, q to cancel: ");
+        std::io::stderr().flush()?;
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(false);
+        }
+        let line = line.trim();
+        if line.is_empty() {
+            break;
+        }
+        if line.eq_ignore_ascii_case("q") {
+            return Ok(false);
+        }
+        match parse_toggles(line, count) {
+            Ok(Toggle::All(on)) => included.iter_mut().for_each(|block| *block = on),
+            Ok(Toggle::Blocks(blocks)) => blocks.into_iter().for_each(|block| included[block] = !included[block]),
+            Err(message) => eprintln!("{}", message.yellow()),
+        }
+    }
+    let dropped: Vec<usize> = (0..count).filter(|block| !included[*block]).collect();
+    assembler.remove(&dropped);
+    Ok(true)
+}
+
+/// The answer typed at the review prompt: stdin, or the terminal itself when
+/// stdin carries piped context
+fn terminal_input() -> Result<Box<dyn BufRead>, QError> {
+    if std::io::stdin().is_terminal() {
+        return Ok(Box::new(BufReader::new(std::io::stdin())));
+    }
+    #[cfg(unix)]
+    if let Ok(tty) = std::fs::File::open("/dev/tty") {
+        return Ok(Box::new(BufReader::new(tty)));
+    }
+    Err(QError::Usage("--review-context needs a terminal to ask on".to_string()))
+}
+
+fn render(assembler: &ContextAssembler, included: &[bool]) -> String {
+    let mut output = format!("{}\n", "Context to send:".bold());
+    let mut total = 0;
+    for (number, ((kind, content), included)) in assembler.blocks().zip(included).enumerate() {
+        if *included {
+            total += content.len();
+        }
+        let mark = if *included { "[x]".green() } else { "[ ]".red() };
+        output.push_str(&format!("  {} {:>2}  {:<9} {:>10}\n", mark, number + 1, kind, format_bytes(content.len() as u64)));
+        for line in content.lines().filter(|line| !line.trim().is_empty()).take(PREVIEW_LINES) {
+            let preview: String = line.chars().take(PREVIEW_WIDTH).collect();
+            output.push_str(&format!("          {}\n", preview.dimmed()));
+        }
+    }
+    let budget = assembler.max_bytes();
+    output.push_str(&format!("  {} of the {} budget", format_bytes(total as u64), format_bytes(budget as u64)));
+    if total > budget {
+        output.push_str("; blocks will be cut to fit");
+    }
+    output
+}
+
+#[derive(Debug, PartialEq)]
+enum Toggle {
+    /// Switch every block on or off
+    All(bool),
+    /// Flip these blocks, by position
+    Blocks(Vec<usize>),
+}
+
+/// `1 3`, `2,4`, `all`, or `none`
+fn parse_toggles(line: &str, count: usize) -> Result<Toggle, String> {
+    match line.to_ascii_lowercase().as_str() {
+        "all" => return Ok(Toggle::All(true)),
+        "none" => return Ok(Toggle::All(false)),
+        _ => {}
+    }
+    line.split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|part| !part.is_empty())
+        .map(|part| match part.parse::<usize>() {
+            Ok(number) if (1..=count).contains(&number) => Ok(number - 1),
+            _ => Err(format!("'{}' isn't a block number from 1 to {}", part, count)),
+        })
+        .collect::<Result<Vec<usize>, String>>()
+        .map(Toggle::Blocks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::{ContextData, ContextType};
+
+    #[test]
+    fn test_parse_toggles() {
+        assert_eq!(parse_toggles("1 3", 3), Ok(Toggle::Blocks(vec![0, 2])));
+        assert_eq!(parse_toggles("2,3", 3), Ok(Toggle::Blocks(vec![1, 2])));
+        assert_eq!(parse_toggles("NONE", 3), Ok(Toggle::All(false)));
+        assert!(parse_toggles("4", 3).is_err());
+        assert!(parse_toggles("x", 3).is_err());
+    }
+
+    #[test]
+    fn test_render() {
+        colored::control::set_override(false);
+        let mut assembler = ContextAssembler::new(1024);
+        assembler.add(ContextData { context_type: ContextType::History, content: "git status\ncargo test\n".to_string() });
+        assembler.add(ContextData { context_type: ContextType::System, content: "OS: Linux\n".to_string() });
+        assert_eq!(
+            render(&assembler, &[true, false]),
+            "Context to send:\n\
+             \x20 [x]  1  history         22 B\n\
+             \x20         git status\n\
+             \x20         cargo test\n\
+             \x20 [ ]  2  system          10 B\n\
+             \x20         OS: Linux\n\
+             \x20 22 B of the 1.0 KiB budget"
+        );
+    }
+}
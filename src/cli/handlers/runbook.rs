@@ -0,0 +1,206 @@
+//! `--runbook`: answers as numbered steps, each with a command to run and one
+//! to verify it worked, which can then be run one at a time.
+
+use std::io::{BufRead, Write};
+use std::process::{Command, ExitStatus};
+
+use colored::*;
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::utils::errors::QError;
+use crate::utils::safety;
+
+/// Added to the system instructions so answers come back in the shape `parse` reads
+pub const INSTRUCTIONS: &str = "Answer as a runbook of numbered steps. Start each step with a \
+heading line `### Step N: <title>`, then one or two sentences on what it does and why. Put the \
+shell command to run in a ```sh block after a line `Run:`, and a command that checks the step \
+worked in a ```sh block after a line `Verify:`. Leave out Run or Verify when a step has no \
+command. Use one command per block, with no prompts or placeholders left to fill in unless unavoidable.";
+
+lazy_static! {
+    static ref STEP_HEADING: Regex = Regex::new(r"^#{1,4}\s*Step\s+\d+\s*[:.)-]?\s*(.*)$").expect("valid regex");
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Step {
+    pub title: String,
+    pub description: String,
+    pub command: Option<String>,
+    pub verify: Option<String>,
+}
+
+/// The steps of a runbook answer; empty when the answer isn't one
+pub fn parse(response: &str) -> Vec<Step> {
+    let mut steps: Vec<Step> = Vec::new();
+    let mut fence: Option<(bool, Vec<&str>)> = None;
+    let mut verifying = false;
+    for line in response.lines() {
+        let trimmed = line.trim();
+        if let Some((is_verify, lines)) = fence.as_mut() {
+            if trimmed.starts_with("```") {
+                let block = lines.join("\n").trim().to_string();
+                let is_verify = *is_verify;
+                fence = None;
+                if let (Some(step), false) = (steps.last_mut(), block.is_empty()) {
+                    let slot = if is_verify { &mut step.verify } else { &mut step.command };
+                    slot.get_or_insert(block);
+                }
+            } else {
+                lines.push(line);
+            }
+            continue;
+        }
+        if let Some(captures) = STEP_HEADING.captures(trimmed) {
+            steps.push(Step { title: captures[1].trim().to_string(), ..Step::default() });
+            verifying = false;
+        } else if trimmed.starts_with("```") {
+            fence = Some((verifying, Vec::new()));
+        } else if let Some(step) = steps.last_mut() {
+            let label = trimmed.trim_matches('*').trim_end_matches(':').to_lowercase();
+            match label.as_str() {
+                "run" => verifying = false,
+                "verify" => verifying = true,
+                "" => {}
+                _ if step.command.is_none() => {
+                    if !step.description.is_empty() {
+                        step.description.push(' ');
+                    }
+                    step.description.push_str(trimmed);
+                }
+                _ => {}
+            }
+        }
+    }
+    steps
+}
+
+/// The steps with their indices, commands, and any risks the safety check sees
+pub fn render(steps: &[Step]) -> String {
+    let mut output = String::new();
+    for (index, step) in steps.iter().enumerate() {
+        output.push_str(&format!("{}  {}\n", format!("Step {}/{}", index + 1, steps.len()).bold(), step.title.bold()));
+        if !step.description.is_empty() {
+            output.push_str(&format!("  {}\n", step.description));
+        }
+        if let Some(command) = &step.command {
+            for (i, line) in command.lines().enumerate() {
+                output.push_str(&format!("  {} {}\n", if i == 0 { "$" } else { " " }, line.green()));
+            }
+            for risk in safety::risks(command) {
+                output.push_str(&format!("  {}\n", format!("! {}", risk).yellow()));
+            }
+        }
+        if let Some(verify) = &step.verify {
+            output.push_str(&format!("  {} {}\n", "verify:".dimmed(), verify.replace('\n', "; ").cyan()));
+            for risk in safety::risks(verify) {
+                output.push_str(&format!("  {}\n", format!("! its check {}", risk).yellow()));
+            }
+        }
+        output.push('\n');
+    }
+    output
+}
+
+/// Offer each step's command to run, then its verification. A risky command
+/// or verification needs `yes` spelled out; a failed step asks before going on.
+pub fn walk(steps: &[Step]) -> Result<(), QError> {
+    for (index, step) in steps.iter().enumerate() {
+        let Some(command) = &step.command else {
+            continue;
+        };
+        let label = format!("Step {}/{}", index + 1, steps.len());
+        let mut risks: Vec<String> = safety::risks(command).into_iter().map(String::from).collect();
+        if let Some(verify) = &step.verify {
+            risks.extend(safety::risks(verify).into_iter().map(|risk| format!("its check {}", risk)));
+        }
+        let question = if risks.is_empty() {
+            format!("{} `{}`: run it? [y/N/q] ", label, first_line(command))
+        } else {
+            format!("{} `{}` {}. Type yes to run it, or q to stop: ", label, first_line(command), risks.join(" and "))
+        };
+        match ask(&question)?.as_str() {
+            "q" | "quit" => return Ok(()),
+            "yes" => {}
+            "y" if risks.is_empty() => {}
+            _ => {
+                eprintln!("{}", "skipped".dimmed());
+                continue;
+            }
+        }
+
+        let status = run(command)?;
+        let mut ok = status.success();
+        if !ok {
+            eprintln!("{}", format!("{} failed ({})", label, status).red());
+        } else if let Some(verify) = &step.verify {
+            ok = run(verify)?.success();
+            eprintln!("{}", if ok { format!("{} verified", label).green() } else { format!("{} did not verify", label).red() });
+        }
+        if !ok && index + 1 < steps.len() && !matches!(ask("Continue with the next step? [y/N] ")?.as_str(), "y" | "yes") {
+            return Ok(());
+        }
+    }
+    Ok(())
+}
+
+fn first_line(command: &str) -> &str {
+    command.lines().next().unwrap_or_default()
+}
+
+/// The lowercased reply to `question`; empty at end of input
+fn ask(question: &str) -> Result<String, QError> {
+    eprint!("{}", question);
+    std::io::stderr().flush()?;
+    let mut line = String::new();
+    std::io::stdin().lock().read_line(&mut line)?;
+    Ok(line.trim().to_lowercase())
+}
+
+/// Run `command` in the shell with the terminal attached
+fn run(command: &str) -> Result<ExitStatus, QError> {
+    let (shell, flag) = if cfg!(windows) { ("cmd", "/C") } else { ("sh", "-c") };
+    Command::new(shell)
+        .arg(flag)
+        .arg(command)
+        .status()
+        .map_err(|e| QError::Command(format!("Couldn't run {}: {}", shell, e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let response = "Here's how:\n\n### Step 1: Check the service\nSee whether nginx is running.\n\nRun:\n```sh\nsystemctl status nginx\n```\n\n\
+                        ### Step 2: Clear the cache\nRemove stale entries.\n**Run:**\n```bash\nrm -rf /var/cache/nginx/*\n```\nVerify:\n```sh\nls /var/cache/nginx | wc -l\n```\n\
+                        ## Step 3 - Done\nNothing else to do.\n";
+        assert_eq!(parse(response), vec![
+            Step {
+                title: "Check the service".to_string(),
+                description: "See whether nginx is running.".to_string(),
+                command: Some("systemctl status nginx".to_string()),
+                verify: None,
+            },
+            Step {
+                title: "Clear the cache".to_string(),
+                description: "Remove stale entries.".to_string(),
+                command: Some("rm -rf /var/cache/nginx/*".to_string()),
+                verify: Some("ls /var/cache/nginx | wc -l".to_string()),
+            },
+            Step { title: "Done".to_string(), description: "Nothing else to do.".to_string(), ..Step::default() },
+        ]);
+        assert!(parse("Just restart it.").is_empty());
+    }
+
+    #[test]
+    fn test_render() {
+        colored::control::set_override(false);
+        let steps = parse("### Step 1: Clean\nRun:\n```sh\nrm -rf build\n```\nVerify:\n```sh\ntest ! -d build\n```\n");
+        assert_eq!(render(&steps), "Step 1/1  Clean\n  $ rm -rf build\n  ! deletes files recursively\n  verify: test ! -d build\n\n");
+
+        let steps = parse("### Step 1: Build\nRun:\n```sh\nmake\n```\nVerify:\n```sh\nrm -rf build && make\n```\n");
+        assert_eq!(render(&steps), "Step 1/1  Build\n  $ make\n  verify: rm -rf build && make\n  ! its check deletes files recursively\n\n");
+    }
+}
@@ -0,0 +1,390 @@
+//! `q schedule add|list|remove|run`: queries saved in `[schedules]` with a
+//! crontab schedule. q doesn't run in the background; `q schedule run`, from
+//! cron or a systemd timer, sends the schedules that are due, printing each
+//! answer and recording it in the history, a file, or a notification.
+
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+use std::process::Command;
+
+use chrono::{Local, TimeZone};
+use colored::*;
+
+use super::queue::preview;
+use super::{health, history, load_config, query_client, query_failed, resolve_provider_and_model};
+use crate::cli::args::{Cli, ScheduleCommand};
+use crate::config::paths::DataPaths;
+use crate::config::types::{Provider, Schedule};
+use crate::config::ConfigManager;
+use crate::core::health::is_outage;
+use crate::core::schedule::{self, is_due, Runs};
+use crate::core::{QueryConfig, QueryEngine};
+use crate::utils::errors::QError;
+use crate::utils::output;
+
+/// Upcoming runs listed when a schedule is saved
+const NEXT_RUNS: usize = 3;
+/// Words of the prompt that make up a schedule's default name
+const NAME_WORDS: usize = 4;
+/// Characters of the answer shown in a notification
+const NOTIFICATION_CHARS: usize = 200;
+
+pub async fn run(cli: &Cli, action: &ScheduleCommand) -> Result<(), QError> {
+    let mut config = load_config(cli)?;
+    match action {
+        ScheduleCommand::Add { prompt, cron, name, notify, save } => {
+            add(cli, &mut config, prompt, cron, name.as_deref(), *notify, save.as_deref())
+        }
+        ScheduleCommand::List => list(&config),
+        ScheduleCommand::Remove { name } => {
+            config.remove_schedule(name)?;
+            let paths = DataPaths::new()?;
+            // Only a stale entry is left behind if this fails
+            let _ = Runs::update(&paths.schedule_runs(), |runs| runs.forget(name));
+            output::line(format!("Removed schedule {}", name));
+            Ok(())
+        }
+        ScheduleCommand::Run { name: Some(name) } => run_now(cli, &config, name).await,
+        ScheduleCommand::Run { name: None } => run_due(cli, &config).await,
+    }
+}
+
+fn add(
+    cli: &Cli,
+    config: &mut ConfigManager,
+    prompt: &str,
+    cron: &str,
+    name: Option<&str>,
+    notify: bool,
+    save: Option<&Path>,
+) -> Result<(), QError> {
+    if prompt.trim().is_empty() {
+        return Err(QError::Usage("The prompt is empty".to_string()));
+    }
+    let parsed = schedule::parse(cron)
+        .map_err(|e| QError::Usage(format!("`{}` is not a valid crontab schedule: {}", cron, e)))?;
+    let provider = cli.provider.as_deref()
+        .map(|name| Provider::try_from(name).map_err(|e| QError::Config(format!("Invalid provider: {}", e))))
+        .transpose()?;
+    let name = match name {
+        Some(name) => {
+            check_name(name)?;
+            name.to_string()
+        }
+        None => unused_name(config, &default_name(prompt)),
+    };
+    // cron starts commands in the home directory, where a relative path means something else
+    let save = save.map(std::path::absolute).transpose()?;
+
+    let replaced = config.set_schedule(&name, Schedule {
+        prompt: prompt.to_string(),
+        cron: cron.to_string(),
+        provider,
+        model: cli.model.clone(),
+        notify,
+        save,
+    })?;
+    // Its times count from now, not from whenever `q schedule run` last looked
+    let paths = DataPaths::new()?;
+    paths.ensure_state_dir()?;
+    Runs::update(&paths.schedule_runs(), |runs| runs.record(&name, Local::now().timestamp()))?;
+
+    output::line(format!(
+        "{} schedule {}: {}",
+        if replaced { "Replaced" } else { "Saved" },
+        name.green(),
+        parsed.describe()
+    ));
+    let next: Vec<String> = parsed.iter_after(Local::now())
+        .take(NEXT_RUNS)
+        .map(|time| time.format("%a %Y-%m-%d %H:%M").to_string())
+        .collect();
+    eprintln!("{}", format!("Next runs: {}", next.join(", ")).dimmed());
+    if config.schedules().len() == 1 {
+        let q = std::env::current_exe().map(|path| path.display().to_string()).unwrap_or_else(|_| "q".to_string());
+        eprintln!(
+            "{}",
+            format!("`q schedule run` sends the schedules that are due; add it to `crontab -e`, e.g. `*/5 * * * * {} schedule run`", q).dimmed()
+        );
+    }
+    Ok(())
+}
+
+fn list(config: &ConfigManager) -> Result<(), QError> {
+    let mut schedules: Vec<_> = config.schedules().iter().collect();
+    if schedules.is_empty() {
+        output::line("No schedules. Save one with 'q schedule add <prompt> --cron <schedule>'".dimmed());
+        return Ok(());
+    }
+    schedules.sort_by(|a, b| a.0.cmp(b.0));
+    let name_width = schedules.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+    let cron_width = schedules.iter().map(|(_, schedule)| schedule.cron.len()).max().unwrap_or(0);
+    let now = Local::now();
+    for (name, schedule) in schedules {
+        let next = match schedule::parse(&schedule.cron).and_then(|cron| cron.find_next_occurrence(&now, false)) {
+            Ok(next) => format!("next {}", next.format("%a %Y-%m-%d %H:%M")),
+            Err(_) => "invalid schedule".red().to_string(),
+        };
+        let mut line = format!(
+            "{:<name_width$}  {:<cron_width$}  {}  {}",
+            name.green(),
+            schedule.cron,
+            next,
+            preview(&schedule.prompt),
+            name_width = name_width,
+            cron_width = cron_width
+        );
+        if schedule.notify {
+            line.push_str(&" (notify)".dimmed().to_string());
+        }
+        if let Some(path) = &schedule.save {
+            line.push_str(&format!(" → {}", path.display()).dimmed().to_string());
+        }
+        output::line(line);
+    }
+    Ok(())
+}
+
+/// Send schedule `name` now, whether or not it's due
+async fn run_now(cli: &Cli, config: &ConfigManager, name: &str) -> Result<(), QError> {
+    let schedule = config.schedules().get(name)
+        .ok_or_else(|| QError::Usage(format!("Unknown schedule '{}'; `q schedule list` shows them", name)))?;
+    let started = Local::now().timestamp();
+    send(cli, config, name, schedule).await.map_err(Failed::into_error)?;
+    let paths = DataPaths::new()?;
+    paths.ensure_state_dir()?;
+    Runs::update(&paths.schedule_runs(), |runs| runs.record(name, started))?;
+    Ok(())
+}
+
+/// Send every schedule with a time since its last run, in name order.
+/// A run is recorded before it's sent, so an overlapping `q schedule run`
+/// leaves it alone; one that can't reach its provider is due again next time.
+async fn run_due(cli: &Cli, config: &ConfigManager) -> Result<(), QError> {
+    let paths = DataPaths::new()?;
+    paths.ensure_state_dir()?;
+    let path = paths.schedule_runs();
+    let now = Local::now();
+    let mut names: Vec<&String> = config.schedules().keys().collect();
+    names.sort();
+
+    let mut sent = 0;
+    let mut failed = 0;
+    for name in names {
+        let schedule = &config.schedules()[name];
+        let cron = match schedule::parse(&schedule.cron) {
+            Ok(cron) => cron,
+            Err(e) => {
+                eprintln!("{}", format!("Skipping {}: `{}` is not a valid crontab schedule: {}", name, schedule.cron, e).yellow());
+                continue;
+            }
+        };
+        let due = Runs::update(&path, |runs| {
+            match runs.last(name).and_then(|secs| Local.timestamp_opt(secs, 0).single()) {
+                Some(last) if !is_due(&cron, &last, &now) => None,
+                Some(last) => {
+                    runs.record(name, now.timestamp());
+                    Some(last)
+                }
+                None => {
+                    // Added by editing the config: its times count from now
+                    runs.record(name, now.timestamp());
+                    None
+                }
+            }
+        })?;
+        let Some(last) = due else { continue };
+
+        if sent > 0 {
+            output::line("");
+        }
+        sent += 1;
+        match send(cli, config, name, schedule).await {
+            Ok(()) => {}
+            Err(Failed::Offline(e)) => {
+                eprintln!("{}", format!("{}: {}; it's sent again next time", name, e).yellow());
+                Runs::update(&path, |runs| runs.record(name, last.timestamp()))?;
+            }
+            Err(Failed::Error(e)) => {
+                eprintln!("{}", format!("{}: {}", name, e).red());
+                failed += 1;
+            }
+        }
+    }
+    match failed {
+        0 => Ok(()),
+        1 => Err(QError::Command("A scheduled query failed".to_string())),
+        n => Err(QError::Command(format!("{} scheduled queries failed", n))),
+    }
+}
+
+/// Why a scheduled query didn't get an answer
+enum Failed {
+    /// The provider couldn't be reached
+    Offline(QError),
+    Error(QError),
+}
+
+impl Failed {
+    fn into_error(self) -> QError {
+        match self {
+            Failed::Offline(e) | Failed::Error(e) => e,
+        }
+    }
+}
+
+/// Ask `schedule`'s prompt, then print the answer, record it in the history,
+/// and save or show it as the schedule says
+async fn send(cli: &Cli, config: &ConfigManager, name: &str, schedule: &Schedule) -> Result<(), Failed> {
+    let mut cli = cli.clone();
+    if let Some(provider) = schedule.provider {
+        cli.provider = Some(provider.to_string());
+        cli.model = None;
+    }
+    if schedule.model.is_some() {
+        cli.model = schedule.model.clone();
+    }
+    let (provider, _) = resolve_provider_and_model(&cli, config).map_err(Failed::Error)?;
+    let client = query_client(&cli, config).await.map_err(Failed::Error)?;
+    let engine = QueryEngine::new(client, QueryConfig {
+        max_retries: cli.max_retries,
+        show_progress: cli.show_progress(),
+        ..QueryConfig::default()
+    });
+
+    let response = engine.query(&schedule.prompt).await;
+    health::note(&cli, provider, response.as_ref().err());
+    let response = match response {
+        Ok(response) => response,
+        Err(e) if is_outage(&e) => return Err(Failed::Offline(query_failed(e, &engine))),
+        Err(e) => return Err(Failed::Error(query_failed(e, &engine))),
+    };
+    if !cli.incognito {
+        history::record(config, provider.as_str(), engine.model(), None, &schedule.prompt, &response);
+    }
+
+    output::line(format!("{} ({})", name, Local::now().format("%a %Y-%m-%d %H:%M")).bold());
+    output::answer(&response);
+    if let Some(path) = &schedule.save {
+        append(path, name, &response)
+            .map_err(|e| Failed::Error(QError::Command(format!("Couldn't save the answer to {}: {}", path.display(), e))))?;
+    }
+    if schedule.notify {
+        notify(name, &response);
+    }
+    Ok(())
+}
+
+/// Add the answer to the end of `path` under a heading with the schedule and time
+fn append(path: &Path, name: &str, response: &str) -> std::io::Result<()> {
+    let entry = format!("## {} ({})\n\n{}\n\n", name, Local::now().format("%Y-%m-%d %H:%M"), response.trim());
+    OpenOptions::new().create(true).append(true).open(path)?.write_all(entry.as_bytes())
+}
+
+/// Show the start of the answer in a desktop notification: osascript on
+/// macOS, notify-send elsewhere. One that can't be shown only earns a warning.
+fn notify(name: &str, response: &str) {
+    let body = notification_text(response);
+    let result = if cfg!(target_os = "macos") {
+        Command::new("osascript")
+            .args(["-e", "on run argv", "-e", "display notification (item 2 of argv) with title (item 1 of argv)", "-e", "end run"])
+            .args([name, body.as_str()])
+            .output()
+    } else {
+        let mut command = Command::new("notify-send");
+        if std::env::var_os("DBUS_SESSION_BUS_ADDRESS").is_none() {
+            if let Some(bus) = session_bus() {
+                command.env("DBUS_SESSION_BUS_ADDRESS", bus);
+            }
+        }
+        command.args(["--app-name=q", name, &body]).output()
+    };
+    let problem = match result {
+        Ok(output) if output.status.success() => return,
+        Ok(output) => String::from_utf8_lossy(&output.stderr).lines().next().unwrap_or("failed").trim().to_string(),
+        Err(e) => e.to_string(),
+    };
+    eprintln!("{}", format!("Couldn't show a notification: {}", problem).yellow());
+}
+
+/// The user's session bus, which cron leaves out of the environment:
+/// `/run/user/<uid>/bus` when it exists, the uid taken from the home dir
+#[cfg(unix)]
+fn session_bus() -> Option<String> {
+    use std::os::unix::fs::MetadataExt;
+    let uid = std::fs::metadata(std::env::var_os("HOME")?).ok()?.uid();
+    let bus = format!("/run/user/{}/bus", uid);
+    Path::new(&bus).exists().then(|| format!("unix:path={}", bus))
+}
+
+#[cfg(not(unix))]
+fn session_bus() -> Option<String> {
+    None
+}
+
+/// The answer on one line, cut to fit a notification
+fn notification_text(response: &str) -> String {
+    let text = response.split_whitespace().collect::<Vec<_>>().join(" ");
+    match text.char_indices().nth(NOTIFICATION_CHARS) {
+        Some((end, _)) => format!("{}…", &text[..end]),
+        None => text,
+    }
+}
+
+/// A schedule name must be one plain word that can't be mistaken for a flag
+fn check_name(name: &str) -> Result<(), QError> {
+    if name.is_empty() || name.starts_with('-') || name.chars().any(|c| c.is_whitespace() || c == '.') {
+        return Err(QError::Usage(format!("'{}' can't be a schedule name; use one word that doesn't start with '-'", name)));
+    }
+    Ok(())
+}
+
+/// The prompt's first few words, lowercased and joined with dashes
+fn default_name(prompt: &str) -> String {
+    let words: Vec<String> = prompt.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .take(NAME_WORDS)
+        .map(str::to_lowercase)
+        .collect();
+    if words.is_empty() {
+        "schedule".to_string()
+    } else {
+        words.join("-")
+    }
+}
+
+/// `name`, or `name-2`, `name-3`, ... if a schedule already has it
+fn unused_name(config: &ConfigManager, name: &str) -> String {
+    std::iter::once(name.to_string())
+        .chain((2..).map(|n| format!("{}-{}", name, n)))
+        .find(|candidate| !config.schedules().contains_key(candidate))
+        .expect("an unused name")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_name() {
+        assert_eq!(default_name("Summarize the HN front page, briefly"), "summarize-the-hn-front");
+        assert_eq!(default_name("a git tip"), "a-git-tip");
+        assert_eq!(default_name("???"), "schedule");
+    }
+
+    #[test]
+    fn test_notification_text() {
+        assert_eq!(notification_text("# Tip\n\nUse `git switch -`\n"), "# Tip Use `git switch -`");
+        let long = "word ".repeat(100);
+        assert_eq!(notification_text(&long).chars().count(), NOTIFICATION_CHARS + 1);
+    }
+
+    #[test]
+    fn test_check_name() {
+        assert!(check_name("news").is_ok());
+        assert!(check_name("--news").is_err());
+        assert!(check_name("hn news").is_err());
+        assert!(check_name("hn.news").is_err());
+    }
+}
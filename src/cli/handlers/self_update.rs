@@ -0,0 +1,223 @@
+use std::path::Path;
+use colored::*;
+use reqwest::Client;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::utils::errors::QError;
+use crate::utils::output;
+
+const RELEASES_URL: &str = "https://api.github.com/repos/rfushimi/q/releases/latest";
+const CURRENT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+#[derive(Debug, Deserialize)]
+pub(super) struct Release {
+    pub(super) tag_name: String,
+    pub(super) assets: Vec<Asset>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(super) struct Asset {
+    pub(super) name: String,
+    pub(super) browser_download_url: String,
+}
+
+/// Check GitHub for a newer release and, unless `check_only`, install it over
+/// the running binary after verifying its published SHA-256 checksum.
+pub async fn run(check_only: bool) -> Result<(), QError> {
+    let client = http_client()?;
+    let release = latest_release(&client).await?;
+
+    let latest = release.tag_name.trim_start_matches('v');
+    if !is_newer(latest, CURRENT_VERSION) {
+        output::line(format!("q is up to date ({})", CURRENT_VERSION));
+        return Ok(());
+    }
+    output::line(format!("Update available: {} -> {}", CURRENT_VERSION, latest.green()));
+    if check_only {
+        return Ok(());
+    }
+
+    let exe = std::env::current_exe()?;
+    if exe.components().any(|c| c.as_os_str() == "Cellar") {
+        return Err(QError::Usage("q was installed with Homebrew. Run 'brew upgrade q' instead.".to_string()));
+    }
+
+    let target = platform_target().ok_or_else(|| QError::Usage(format!(
+        "No release builds for {}-{}. Build from source with 'cargo install --git https://github.com/rfushimi/q'.",
+        std::env::consts::ARCH, std::env::consts::OS
+    )))?;
+    let binary = find_asset(&release.assets, target)
+        .ok_or_else(|| QError::Api(format!("Release {} has no build for {}", release.tag_name, target)))?;
+    let checksum = release.assets.iter()
+        .find(|asset| asset.name == format!("{}.sha256", binary.name))
+        .ok_or_else(|| QError::Api(format!("Release {} has no checksum for {}; refusing to install", release.tag_name, binary.name)))?;
+
+    eprintln!("{}", format!("Downloading {}...", binary.name).dimmed());
+    let expected = download(&client, &checksum.browser_download_url).await?;
+    let expected = parse_checksum(&String::from_utf8_lossy(&expected))
+        .ok_or_else(|| QError::Api(format!("Malformed checksum file {}", checksum.name)))?;
+    let bytes = download(&client, &binary.browser_download_url).await?;
+    verify_checksum(&bytes, &expected)?;
+
+    replace_binary(&exe, &bytes)?;
+    output::line(format!("Updated q to {}", latest).green());
+    Ok(())
+}
+
+pub(super) fn http_client() -> Result<Client, QError> {
+    Client::builder()
+        .user_agent(concat!("q/", env!("CARGO_PKG_VERSION")))
+        .build()
+        .map_err(|e| QError::Api(format!("Failed to create HTTP client: {}", e)))
+}
+
+/// The latest GitHub release
+pub(super) async fn latest_release(client: &Client) -> Result<Release, QError> {
+    // Q_RELEASES_URL points the updater at a mirror (or a mock server in tests)
+    let url = std::env::var("Q_RELEASES_URL").unwrap_or_else(|_| RELEASES_URL.to_string());
+    client.get(&url)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| QError::Api(format!("Failed to check for updates: {}", e)))?
+        .json()
+        .await
+        .map_err(|e| QError::Api(format!("Unexpected release data: {}", e)))
+}
+
+pub(super) async fn download(client: &Client, url: &str) -> Result<Vec<u8>, QError> {
+    let response = client.get(url)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| QError::Api(format!("Download failed: {}", e)))?;
+    let bytes = response.bytes()
+        .await
+        .map_err(|e| QError::Api(format!("Download failed: {}", e)))?;
+    Ok(bytes.to_vec())
+}
+
+/// Whether `latest` is a higher `major.minor.patch` than `current`
+pub(super) fn is_newer(latest: &str, current: &str) -> bool {
+    fn parse(version: &str) -> Vec<u64> {
+        // Pre-release and build suffixes are ignored
+        version.split(['-', '+']).next().unwrap_or_default()
+            .split('.')
+            .map(|part| part.parse().unwrap_or(0))
+            .collect()
+    }
+    parse(latest) > parse(current)
+}
+
+/// Target triple used to name release assets (`q-<target>`)
+fn platform_target() -> Option<&'static str> {
+    match (std::env::consts::ARCH, std::env::consts::OS) {
+        ("x86_64", "macos") => Some("x86_64-apple-darwin"),
+        ("aarch64", "macos") => Some("aarch64-apple-darwin"),
+        ("x86_64", "linux") => Some("x86_64-unknown-linux-gnu"),
+        ("aarch64", "linux") => Some("aarch64-unknown-linux-gnu"),
+        _ => None,
+    }
+}
+
+fn find_asset<'a>(assets: &'a [Asset], target: &str) -> Option<&'a Asset> {
+    let name = format!("q-{}", target);
+    assets.iter().find(|asset| asset.name == name).or_else(|| {
+        // Releases before per-target assets only shipped an Intel macOS `q`
+        (target == "x86_64-apple-darwin")
+            .then(|| assets.iter().find(|asset| asset.name == "q"))
+            .flatten()
+    })
+}
+
+/// Extract the hex digest from `shasum`/`sha256sum` output
+fn parse_checksum(contents: &str) -> Option<String> {
+    let digest = contents.split_whitespace().next()?.to_lowercase();
+    (digest.len() == 64 && digest.chars().all(|c| c.is_ascii_hexdigit())).then_some(digest)
+}
+
+fn verify_checksum(bytes: &[u8], expected: &str) -> Result<(), QError> {
+    let actual: String = Sha256::digest(bytes).iter().map(|b| format!("{:02x}", b)).collect();
+    if actual != expected {
+        return Err(QError::Api(format!(
+            "Checksum mismatch (expected {}, got {}); the download was not installed",
+            expected, actual
+        )));
+    }
+    Ok(())
+}
+
+/// Write the new binary next to the current one and rename it into place, so
+/// an interrupted update never leaves a half-written executable.
+fn replace_binary(exe: &Path, bytes: &[u8]) -> Result<(), QError> {
+    let staged = exe.with_extension("new");
+    std::fs::write(&staged, bytes).map_err(|e| permission_hint(exe, e))?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(0o755))?;
+    }
+
+    std::fs::rename(&staged, exe).map_err(|e| {
+        let _ = std::fs::remove_file(&staged);
+        permission_hint(exe, e)
+    })
+}
+
+fn permission_hint(exe: &Path, error: std::io::Error) -> QError {
+    if error.kind() == std::io::ErrorKind::PermissionDenied {
+        QError::Usage(format!("No permission to replace {}. Re-run with sudo or reinstall.", exe.display()))
+    } else {
+        QError::Io(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn asset(name: &str) -> Asset {
+        Asset { name: name.to_string(), browser_download_url: format!("https://example.com/{}", name) }
+    }
+
+    #[test]
+    fn test_is_newer() {
+        assert!(is_newer("0.2.0", "0.1.0"));
+        assert!(is_newer("0.10.0", "0.9.3"));
+        assert!(is_newer("1.0.0", "0.1.0-beta"));
+        assert!(!is_newer("0.1.0", "0.1.0"));
+        assert!(!is_newer("0.0.9", "0.1.0"));
+    }
+
+    #[test]
+    fn test_find_asset() {
+        let assets = vec![asset("q"), asset("q-aarch64-apple-darwin"), asset("q-aarch64-apple-darwin.sha256")];
+        assert_eq!(find_asset(&assets, "aarch64-apple-darwin").unwrap().name, "q-aarch64-apple-darwin");
+        assert_eq!(find_asset(&assets, "x86_64-apple-darwin").unwrap().name, "q");
+        assert!(find_asset(&assets, "x86_64-unknown-linux-gnu").is_none());
+    }
+
+    #[test]
+    fn test_checksum() {
+        let digest = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+        assert_eq!(parse_checksum(&format!("{}  q\n", digest.to_uppercase())).as_deref(), Some(digest));
+        assert_eq!(parse_checksum("not-a-digest q"), None);
+
+        assert!(verify_checksum(b"hello", digest).is_ok());
+        assert!(verify_checksum(b"hello!", digest).is_err());
+    }
+
+    #[test]
+    fn test_replace_binary() {
+        let temp_dir = TempDir::new().unwrap();
+        let exe = temp_dir.path().join("q");
+        std::fs::write(&exe, b"old").unwrap();
+
+        replace_binary(&exe, b"new").unwrap();
+        assert_eq!(std::fs::read(&exe).unwrap(), b"new");
+        assert!(!exe.with_extension("new").exists());
+    }
+}
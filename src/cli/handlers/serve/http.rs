@@ -0,0 +1,478 @@
+//! `q serve --http ADDR`: the methods over HTTP, plus a minimal
+//! OpenAI-compatible API so local tools that speak it can go through q.
+//!
+//! - `POST /v1/chat/completions` takes OpenAI's request (`model`,
+//!   `messages`, `temperature`, `max_tokens`, `stream`) and answers in its
+//!   format. `model` is a model name, `provider/model`, a provider, or `q`
+//!   for the configured default; system messages become instructions and
+//!   earlier turns are sent as the conversation so far.
+//! - `GET /v1/models` lists the default model
+//! - `POST /q/query`, `/q/context`, and `/q/suggest` take the JSON-RPC
+//!   method's params as the body and answer with its result
+//! - `GET /q/templates` lists the templates; `POST /q/templates/NAME` with
+//!   `{input?, provider?, model?, context?}` answers as `q -t NAME` would
+//!
+//! Errors are `{"error": {"message", "type", "hint"?}}` with a 4xx status
+//! for a bad request and 502 when the provider failed.
+//!
+//! Every request needs `Authorization: Bearer TOKEN`, so other users and
+//! processes on the machine can't read your files through `context` or spend
+//! your keys. The token is `Q_SERVE_TOKEN`, else a new one written to
+//! `DataPaths::serve_token` (readable only by you) at startup. Listening
+//! beyond loopback needs `Q_SERVE_TOKEN`. Requests a browser sends from
+//! another site are refused as well, and bodies are capped at `MAX_BODY_BYTES`.
+
+use std::convert::Infallible;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use colored::*;
+use hyper::body::HttpBody;
+use hyper::header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_LENGTH, CONTENT_TYPE, ORIGIN, WWW_AUTHENTICATE};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, StatusCode};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use super::{Ask, ContextRequest, RpcError, Server, INVALID_PARAMS, METHOD_NOT_FOUND, PARSE_ERROR};
+use crate::cli::args::ContextFlag;
+use crate::cli::handlers::query::conversation_prompt;
+use crate::config::paths::{ConfigPaths, DataPaths};
+use crate::config::template::{self, Template};
+use crate::config::types::Provider;
+use crate::core::crypto::Cipher;
+use crate::core::history::private_file;
+use crate::utils::errors::QError;
+
+/// `model` values that mean q's configured default
+const DEFAULT_MODELS: &[&str] = &["", "q", "default"];
+/// Largest request body read; a chat with a long file attached fits
+const MAX_BODY_BYTES: usize = 8 * 1024 * 1024;
+
+/// Serve until Ctrl-C, answering requests that carry `token` (the
+/// configured one, else one made now)
+pub(super) async fn run(server: Arc<Server>, address: SocketAddr, token: Option<String>) -> Result<(), QError> {
+    if !address.ip().is_loopback() && token.is_none() {
+        return Err(QError::Usage(format!(
+            "Listening on {} would let others on the network use your API keys; set Q_SERVE_TOKEN to a secret they must send, or listen on 127.0.0.1",
+            address
+        )));
+    }
+    let token: Arc<str> = match token {
+        Some(token) => token.into(),
+        None => new_token()?.into(),
+    };
+    let builder = hyper::Server::try_bind(&address)
+        .map_err(|e| QError::Usage(format!("Can't listen on {}: {}", address, e)))?;
+    let services = make_service_fn(move |_| {
+        let server = server.clone();
+        let token = token.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                let server = server.clone();
+                let token = token.clone();
+                async move { Ok::<_, Infallible>(respond(&server, &token, request).await) }
+            }))
+        }
+    });
+    let http = builder.serve(services);
+    if !address.ip().is_loopback() {
+        eprintln!(
+            "{}",
+            format!("q serve is listening beyond this machine; anyone with Q_SERVE_TOKEN who can reach {} can use your API keys", address).yellow()
+        );
+    }
+    eprintln!("q serve: listening on http://{}", http.local_addr());
+    http.with_graceful_shutdown(async {
+        let _ = tokio::signal::ctrl_c().await;
+    })
+    .await
+    .map_err(|e| QError::Io(std::io::Error::other(e)))
+}
+
+/// A random token, written where only the user can read it
+fn new_token() -> Result<String, QError> {
+    let token: String = Cipher::generate_key().iter().map(|byte| format!("{:02x}", byte)).collect();
+    let paths = DataPaths::new()?;
+    paths.ensure_state_dir()?;
+    let path = paths.serve_token();
+    // A token left by an earlier run may be readable by others if it predates the mode
+    let _ = std::fs::remove_file(&path);
+    private_file(OpenOptions::new().create_new(true).write(true))
+        .open(&path)?
+        .write_all(token.as_bytes())?;
+    eprintln!("q serve: send `Authorization: Bearer <token>` with the token in {}", path.display());
+    Ok(token)
+}
+
+async fn respond(server: &Server, token: &str, request: Request<Body>) -> Response<Body> {
+    if foreign_origin(request.headers()) {
+        return failure(StatusCode::FORBIDDEN, &RpcError::new(INVALID_PARAMS, "q serve doesn't answer other sites' pages"));
+    }
+    if !authorized(request.headers(), token) {
+        let mut response = failure(
+            StatusCode::UNAUTHORIZED,
+            &RpcError::new(INVALID_PARAMS, "Send `Authorization: Bearer <token>` with the token q serve printed the path of"),
+        );
+        response.headers_mut().insert(WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+        return response;
+    }
+    let (parts, body) = request.into_parts();
+    let body = match read_body(body, parts.headers.get(CONTENT_LENGTH)).await {
+        Ok(body) => body,
+        Err((status, message)) => return failure(status, &RpcError::new(PARSE_ERROR, message)),
+    };
+    let body = || -> Result<Value, RpcError> {
+        if body.iter().all(u8::is_ascii_whitespace) {
+            return Ok(Value::Null);
+        }
+        serde_json::from_slice(&body).map_err(|e| RpcError::new(PARSE_ERROR, format!("The body isn't JSON: {}", e)))
+    };
+
+    let path = parts.uri.path().trim_end_matches('/');
+    let result = match (&parts.method, path) {
+        (&Method::POST, "/v1/chat/completions") => match body() {
+            Ok(body) => return chat(server, body).await,
+            Err(e) => Err(e),
+        },
+        (&Method::GET, "/v1/models") => models(server),
+        (&Method::POST, "/q/query" | "/q/context" | "/q/suggest") => match body() {
+            Ok(body) => server.call(&path["/q/".len()..], body).await,
+            Err(e) => Err(e),
+        },
+        (&Method::GET, "/q/templates") => templates(server).map_err(RpcError::failed),
+        (&Method::POST, _) if path.starts_with("/q/templates/") => match body() {
+            Ok(body) => run_template(server, &path["/q/templates/".len()..], body).await,
+            Err(e) => Err(e),
+        },
+        _ => Err(RpcError::new(METHOD_NOT_FOUND, format!("No {} {}", parts.method, parts.uri.path()))),
+    };
+    match result {
+        Ok(result) => reply(StatusCode::OK, &result),
+        Err(error) => failure(status(&error), &error),
+    }
+}
+
+/// Whether the request carries `token` as its bearer token
+fn authorized(headers: &HeaderMap, token: &str) -> bool {
+    let Some(sent) = headers.get(AUTHORIZATION).and_then(|value| value.to_str().ok()) else { return false };
+    let Some(sent) = sent.strip_prefix("Bearer ").or_else(|| sent.strip_prefix("bearer ")) else { return false };
+    // Every byte is compared, so the time taken doesn't say how much matched
+    let sent = sent.trim().as_bytes();
+    sent.len() == token.len() && sent.iter().zip(token.as_bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// The request body, refused once it's larger than `MAX_BODY_BYTES`
+async fn read_body(mut body: Body, length: Option<&HeaderValue>) -> Result<Vec<u8>, (StatusCode, String)> {
+    let too_large = || (StatusCode::PAYLOAD_TOO_LARGE, format!("The body is larger than {} bytes", MAX_BODY_BYTES));
+    let length = length.and_then(|value| value.to_str().ok()).and_then(|value| value.parse::<usize>().ok());
+    if length.is_some_and(|length| length > MAX_BODY_BYTES) {
+        return Err(too_large());
+    }
+    let mut bytes = Vec::with_capacity(length.unwrap_or_default());
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+        if bytes.len() + chunk.len() > MAX_BODY_BYTES {
+            return Err(too_large());
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+    Ok(bytes)
+}
+
+/// Whether a browser sent the request from a page that isn't on this machine
+fn foreign_origin(headers: &HeaderMap) -> bool {
+    let Some(origin) = headers.get(ORIGIN) else { return false };
+    let host = origin.to_str().unwrap_or_default()
+        .split_once("://")
+        .map(|(_, rest)| rest)
+        .unwrap_or_default();
+    let host = match host.strip_prefix('[') {
+        Some(ipv6) => ipv6.split(']').next().unwrap_or_default(),
+        None => host.split(':').next().unwrap_or_default(),
+    };
+    !matches!(host, "localhost" | "127.0.0.1" | "::1")
+}
+
+fn status(error: &RpcError) -> StatusCode {
+    match error.code {
+        METHOD_NOT_FOUND => StatusCode::NOT_FOUND,
+        _ if error.upstream => StatusCode::BAD_GATEWAY,
+        _ => StatusCode::BAD_REQUEST,
+    }
+}
+
+fn reply(status: StatusCode, body: &Value) -> Response<Body> {
+    let mut response = Response::new(Body::from(body.to_string()));
+    *response.status_mut() = status;
+    response.headers_mut().insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    response
+}
+
+/// An error in OpenAI's shape, which its clients know how to show
+fn failure(status: StatusCode, error: &RpcError) -> Response<Body> {
+    let kind = if error.upstream { "api_error" } else { "invalid_request_error" };
+    let mut body = json!({"error": {"message": error.message, "type": kind}});
+    if let Some(hint) = &error.hint {
+        body["error"]["hint"] = json!(hint);
+    }
+    reply(status, &body)
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatRequest {
+    #[serde(default)]
+    model: String,
+    messages: Vec<Message>,
+    temperature: Option<f64>,
+    max_tokens: Option<u32>,
+    #[serde(default)]
+    stream: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct Message {
+    role: String,
+    content: Content,
+}
+
+/// A message's text, or its parts, of which q reads the text ones
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Content {
+    Text(String),
+    Parts(Vec<Part>),
+}
+
+#[derive(Debug, Deserialize)]
+struct Part {
+    #[serde(default)]
+    text: Option<String>,
+}
+
+impl Content {
+    fn text(&self) -> String {
+        match self {
+            Content::Text(text) => text.clone(),
+            Content::Parts(parts) => parts.iter().filter_map(|part| part.text.as_deref()).collect::<Vec<_>>().join("\n"),
+        }
+    }
+}
+
+/// `POST /v1/chat/completions`
+async fn chat(server: &Server, body: Value) -> Response<Body> {
+    let request: ChatRequest = match serde_json::from_value(body) {
+        Ok(request) => request,
+        Err(e) => return failure(StatusCode::BAD_REQUEST, &RpcError::new(INVALID_PARAMS, e.to_string())),
+    };
+    let (instructions, prompt) = match chat_prompt(&request.messages) {
+        Ok(prompt) => prompt,
+        Err(e) => return failure(StatusCode::BAD_REQUEST, &RpcError::new(INVALID_PARAMS, e)),
+    };
+    let model = request.model.trim();
+    let mut ask = Ask {
+        temperature: request.temperature,
+        max_tokens: request.max_tokens,
+        instructions,
+        ..Ask::default()
+    };
+    if Provider::try_from(model).is_ok() {
+        ask.provider = Some(model.to_string());
+    } else if !DEFAULT_MODELS.contains(&model) {
+        ask.model = Some(model.to_string());
+    }
+
+    let answer = match server.ask(&ask, &prompt, &ContextRequest::default()).await {
+        Ok(answer) => answer,
+        Err(e) => {
+            let error = RpcError::failed(e);
+            return failure(status(&error), &error);
+        }
+    };
+    let id = format!("chatcmpl-q{}", now_nanos());
+    let created = now_nanos() / 1_000_000_000;
+    let model = format!("{}/{}", answer.provider, answer.model);
+    if !request.stream {
+        return reply(StatusCode::OK, &json!({
+            "id": id,
+            "object": "chat.completion",
+            "created": created,
+            "model": model,
+            "choices": [{"index": 0, "message": {"role": "assistant", "content": answer.answer}, "finish_reason": "stop"}],
+        }));
+    }
+
+    // Streaming clients get the whole answer as one chunk
+    let chunk = |delta: Value, finish_reason: Value| {
+        let chunk = json!({
+            "id": id,
+            "object": "chat.completion.chunk",
+            "created": created,
+            "model": model,
+            "choices": [{"index": 0, "delta": delta, "finish_reason": finish_reason}],
+        });
+        format!("data: {}\n\n", chunk)
+    };
+    let events = [
+        chunk(json!({"role": "assistant", "content": answer.answer}), Value::Null),
+        chunk(json!({}), json!("stop")),
+        "data: [DONE]\n\n".to_string(),
+    ];
+    let mut response = Response::new(Body::from(events.concat()));
+    response.headers_mut().insert(CONTENT_TYPE, HeaderValue::from_static("text/event-stream"));
+    response
+}
+
+/// The instructions from the system messages, and the prompt: the last
+/// user message, after the conversation before it
+fn chat_prompt(messages: &[Message]) -> Result<(Option<String>, String), String> {
+    let mut system = Vec::new();
+    let mut conversation = Vec::new();
+    let mut asked: Vec<String> = Vec::new();
+    for message in messages {
+        match message.role.as_str() {
+            "system" | "developer" => system.push(message.content.text()),
+            "user" => asked.push(message.content.text()),
+            "assistant" => conversation.push((std::mem::take(&mut asked).join("\n\n"), message.content.text())),
+            role => return Err(format!("q serve doesn't take {} messages", role)),
+        }
+    }
+    if asked.is_empty() {
+        return Err("messages must end with a user message".to_string());
+    }
+    let question = asked.join("\n\n");
+    let prompt = if conversation.is_empty() { question } else { conversation_prompt(&conversation, &question) };
+    Ok(((!system.is_empty()).then(|| system.join("\n\n")), prompt))
+}
+
+/// `GET /v1/models`: the model a query without one gets
+fn models(server: &Server) -> Result<Value, RpcError> {
+    let (provider, model) = super::resolve_provider_and_model(&server.cli, &server.config).map_err(RpcError::failed)?;
+    Ok(json!({
+        "object": "list",
+        "data": [{"id": format!("{}/{}", provider, model), "object": "model", "owned_by": provider.as_str()}],
+    }))
+}
+
+/// `GET /q/templates`
+fn templates(server: &Server) -> Result<Value, QError> {
+    let dir = ConfigPaths::new(server.cli.verbose)?.templates_dir();
+    let templates: Vec<Value> = template::names(&dir)?
+        .into_iter()
+        .map(|name| {
+            let description = Template::load(&dir, &name).ok().and_then(|template| template.settings.description);
+            json!({"name": name, "description": description})
+        })
+        .collect();
+    Ok(json!({"templates": templates}))
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct TemplateParams {
+    input: Option<String>,
+    provider: Option<String>,
+    model: Option<String>,
+    context: ContextRequest,
+}
+
+/// `POST /q/templates/NAME`: the template's prompt, sent with its settings
+/// unless the request names a provider or model
+async fn run_template(server: &Server, name: &str, body: Value) -> Result<Value, RpcError> {
+    let params: TemplateParams = super::parse(body)?;
+    let failed = RpcError::failed;
+    let dir = ConfigPaths::new(server.cli.verbose).map_err(failed)?.templates_dir();
+    let template = Template::load(&dir, name).map_err(failed)?;
+    let prompt = template.render(params.input.as_deref()).map_err(failed)?;
+    let settings = template.settings;
+    let ask = Ask {
+        provider: params.provider.or_else(|| settings.provider.map(|provider| provider.to_string())),
+        model: params.model.or(settings.model),
+        temperature: settings.temperature,
+        max_tokens: settings.max_tokens,
+        instructions: settings.system_prompt,
+        preset: Some(name.to_string()),
+    };
+    let mut context = params.context;
+    for flag in &settings.context {
+        match flag {
+            ContextFlag::Hist => context.history = true,
+            ContextFlag::Here => context.directory = true,
+            ContextFlag::Repo => context.repo = true,
+            ContextFlag::Diff => context.diff = true,
+            ContextFlag::Cargo => context.cargo = true,
+            ContextFlag::Docker => context.docker = true,
+            ContextFlag::Sys => context.system = true,
+        }
+    }
+    Ok(server.ask(&ask, &prompt, &context).await.map_err(failed)?.to_json())
+}
+
+fn now_nanos() -> u128 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(role: &str, content: &str) -> Message {
+        Message { role: role.to_string(), content: Content::Text(content.to_string()) }
+    }
+
+    #[test]
+    fn test_chat_prompt() {
+        let (instructions, prompt) = chat_prompt(&[message("system", "Be brief."), message("user", "List files by size")]).unwrap();
+        assert_eq!(instructions.as_deref(), Some("Be brief."));
+        assert_eq!(prompt, "List files by size");
+
+        let messages = [message("user", "List files by size"), message("assistant", "ls -lS"), message("user", "Reversed?")];
+        let (instructions, prompt) = chat_prompt(&messages).unwrap();
+        assert_eq!(instructions, None);
+        assert_eq!(prompt, "Continue this conversation.\n\nUser:\nList files by size\n\nAssistant:\nls -lS\n\nUser:\nReversed?");
+
+        assert!(chat_prompt(&[message("user", "hi"), message("assistant", "hello")]).is_err());
+        assert!(chat_prompt(&[message("tool", "{}"), message("user", "hi")]).is_err());
+    }
+
+    #[test]
+    fn test_authorized() {
+        let headers = |value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(AUTHORIZATION, HeaderValue::from_str(value).unwrap());
+            headers
+        };
+        assert!(authorized(&headers("Bearer s3cret"), "s3cret"));
+        assert!(!authorized(&headers("Bearer s3cre"), "s3cret"));
+        assert!(!authorized(&headers("Bearer s3cret0"), "s3cret"));
+        assert!(!authorized(&headers("Basic s3cret"), "s3cret"));
+        assert!(!authorized(&HeaderMap::new(), "s3cret"));
+    }
+
+    #[tokio::test]
+    async fn test_read_body_is_capped() {
+        assert_eq!(read_body(Body::from("{}"), None).await.unwrap(), b"{}");
+        let large = vec![b' '; MAX_BODY_BYTES + 1];
+        assert_eq!(read_body(Body::from(large), None).await.unwrap_err().0, StatusCode::PAYLOAD_TOO_LARGE);
+        let claimed = HeaderValue::from(MAX_BODY_BYTES + 1);
+        assert_eq!(read_body(Body::from("{}"), Some(&claimed)).await.unwrap_err().0, StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[test]
+    fn test_foreign_origin() {
+        let headers = |origin: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(ORIGIN, HeaderValue::from_str(origin).unwrap());
+            headers
+        };
+        assert!(!foreign_origin(&HeaderMap::new()));
+        assert!(!foreign_origin(&headers("http://localhost:3000")));
+        assert!(!foreign_origin(&headers("http://[::1]:8080")));
+        assert!(foreign_origin(&headers("https://example.com")));
+        assert!(foreign_origin(&headers("http://localhost.example.com")));
+        assert!(foreign_origin(&headers("null")));
+    }
+}
@@ -0,0 +1,484 @@
+//! `q serve`: q's config, profiles, caches, and providers for other
+//! programs, so editor plugins and local tools need no API client of their
+//! own. `--stdio` speaks JSON-RPC 2.0 over stdin and stdout, one message per
+//! line; `--http` serves the same methods over HTTP (see `http`). The methods:
+//!
+//! - `query` `{prompt, provider?, model?, context?}` answers
+//!   `{answer, provider, model}`
+//! - `context` `{context?, provider?}` answers `{content, compressed,
+//!   truncated}`, the context a query would send
+//! - `suggest` `{prompt}` answers `{suggestions}`, as `q --cmd` would
+//! - `shutdown` answers `null` and stops the server once the requests in
+//!   flight are answered, as the end of input does
+//!
+//! `context` takes the kinds the flags do: `{files, text, history,
+//! directory, diff, repo, cargo, docker, system, cwd}`, where `text` is the
+//! editor's selection or buffer. Requests are answered as they finish, so
+//! several can be in flight at once. Queries go through the same failover,
+//! caches, and history as `q` itself.
+
+mod http;
+
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::sync::Mutex;
+use tokio::task::JoinSet;
+use tokio_util::sync::CancellationToken;
+
+use super::{connect, health, history, load_config, query, resolve_provider_and_model};
+use crate::api::ModelConfig;
+use crate::cli::args::Cli;
+use crate::commands::suggest::process_command_query;
+use crate::commands::Platform;
+use crate::config::paths::CachePaths;
+use crate::config::types::Provider;
+use crate::config::ConfigManager;
+use crate::context::assembler::ContextAssembler;
+use crate::context::cargo::CargoProvider;
+use crate::context::directory::DirectoryProvider;
+use crate::context::docker::DockerProvider;
+use crate::context::environment;
+use crate::context::file::FileProvider;
+use crate::context::git::{GitDiffProvider, GitRepoProvider};
+use crate::context::history::HistoryProvider;
+use crate::context::output::OutputProvider;
+use crate::context::qignore::QIgnore;
+use crate::context::system::SystemProvider;
+use crate::context::{ContextConfig, ContextError, ContextProvider};
+use crate::core::{CacheMode, QueryConfig, QueryEngine};
+use crate::utils::errors::{self, QError};
+
+/// JSON-RPC error codes
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// The request was understood but q couldn't carry it out
+const FAILED: i64 = -32000;
+
+/// Context to gather for a request, like the context flags
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct ContextRequest {
+    files: Vec<PathBuf>,
+    /// The editor's selection or buffer, sent as piped input would be
+    text: Option<String>,
+    history: bool,
+    directory: bool,
+    diff: bool,
+    repo: bool,
+    cargo: bool,
+    docker: bool,
+    system: bool,
+    /// The directory `directory`, `diff`, `repo`, `cargo`, and `docker` look at [default: q's own]
+    cwd: Option<PathBuf>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct QueryParams {
+    prompt: String,
+    provider: Option<String>,
+    model: Option<String>,
+    #[serde(default)]
+    context: ContextRequest,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ContextParams {
+    #[serde(default)]
+    context: ContextRequest,
+    /// Checked against `[context.allow]` when given
+    provider: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SuggestParams {
+    prompt: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Request {
+    jsonrpc: String,
+    /// Absent for notifications, which get no reply
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Debug, PartialEq)]
+struct RpcError {
+    code: i64,
+    message: String,
+    hint: Option<String>,
+    /// Whether the provider, rather than the request, is to blame
+    upstream: bool,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Self { code, message: message.into(), hint: None, upstream: false }
+    }
+
+    fn failed(error: QError) -> Self {
+        let upstream = matches!(error, QError::Api(_) | QError::Core(_));
+        Self { code: FAILED, message: error.to_string(), hint: errors::hint(&error), upstream }
+    }
+
+    fn to_json(&self) -> Value {
+        let mut error = json!({"code": self.code, "message": self.message});
+        if let Some(hint) = &self.hint {
+            error["data"] = json!({"hint": hint});
+        }
+        error
+    }
+}
+
+/// `q serve --stdio` or `q serve --http ADDR`
+pub async fn run(cli: &Cli, http: Option<SocketAddr>) -> Result<(), QError> {
+    if cli.json {
+        return Err(QError::Usage("q serve answers in JSON already; drop --json".to_string()));
+    }
+    let config = load_config(cli)?;
+    let token = config.serve_token();
+    let server = Arc::new(Server::new(cli.clone(), config));
+    match http {
+        Some(address) => http::run(server, address, token).await,
+        None => stdio(server).await,
+    }
+}
+
+/// Answer requests on stdin until `shutdown` or the end of input
+async fn stdio(server: Arc<Server>) -> Result<(), QError> {
+    // Answers and suggestions go into JSON, not to a terminal
+    colored::control::set_override(false);
+    eprintln!("q serve: reading JSON-RPC requests on stdin, one per line");
+
+    // Stdin is read on a thread of its own so requests keep being answered while it waits
+    let (sender, mut lines) = tokio::sync::mpsc::unbounded_channel();
+    std::thread::spawn(move || {
+        for line in std::io::stdin().lock().lines() {
+            let Ok(line) = line else { break };
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut requests = JoinSet::new();
+    loop {
+        let line = tokio::select! {
+            line = lines.recv() => line,
+            _ = server.stopping.cancelled() => None,
+        };
+        let Some(line) = line else { break };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let server = server.clone();
+        requests.spawn(async move {
+            if let Some(reply) = server.handle(&line).await {
+                write(&reply);
+            }
+        });
+    }
+    while requests.join_next().await.is_some() {}
+    Ok(())
+}
+
+/// One message to stdout, on a line of its own
+fn write(message: &Value) {
+    let mut stdout = std::io::stdout().lock();
+    let _ = writeln!(stdout, "{}", message);
+    let _ = stdout.flush();
+}
+
+/// Who answers a query and how: a provider and model (else the ones `q serve`
+/// was started with or the config's), and settings like a preset's
+#[derive(Debug, Default)]
+struct Ask {
+    provider: Option<String>,
+    /// A model name, or `provider/model`
+    model: Option<String>,
+    temperature: Option<f64>,
+    max_tokens: Option<u32>,
+    instructions: Option<String>,
+    /// The preset or template, for the history log
+    preset: Option<String>,
+}
+
+/// An answer and who gave it
+struct Answer {
+    answer: String,
+    provider: Provider,
+    model: String,
+}
+
+impl Answer {
+    fn to_json(&self) -> Value {
+        json!({"answer": self.answer, "provider": self.provider.as_str(), "model": self.model})
+    }
+}
+
+/// What the methods share: the config, and an engine per provider and model
+/// so repeated queries hit the engine's cache
+pub(crate) struct Server {
+    cli: Cli,
+    config: ConfigManager,
+    /// Keyed by provider, model, and settings, as the response cache is
+    engines: Mutex<HashMap<String, Arc<QueryEngine>>>,
+    stopping: CancellationToken,
+}
+
+impl Server {
+    pub(crate) fn new(cli: Cli, config: ConfigManager) -> Self {
+        Self { cli, config, engines: Mutex::new(HashMap::new()), stopping: CancellationToken::new() }
+    }
+
+    /// The reply to one message; `None` for a notification
+    pub(crate) async fn handle(&self, message: &str) -> Option<Value> {
+        let request: Request = match serde_json::from_str::<Value>(message) {
+            Err(e) => return Some(reply(Value::Null, Err(RpcError::new(PARSE_ERROR, e.to_string())))),
+            Ok(value) => {
+                let id = value.get("id").cloned().unwrap_or(Value::Null);
+                match serde_json::from_value(value) {
+                    Ok(request) => request,
+                    Err(e) => return Some(reply(id, Err(RpcError::new(INVALID_REQUEST, e.to_string())))),
+                }
+            }
+        };
+        if request.jsonrpc != "2.0" {
+            let id = request.id.unwrap_or(Value::Null);
+            return Some(reply(id, Err(RpcError::new(INVALID_REQUEST, "jsonrpc must be \"2.0\""))));
+        }
+        let result = self.call(&request.method, request.params).await;
+        request.id.map(|id| reply(id, result))
+    }
+
+    async fn call(&self, method: &str, params: Value) -> Result<Value, RpcError> {
+        match method {
+            "query" => self.query(parse(params)?).await.map_err(RpcError::failed),
+            "context" => self.context(parse(params)?).await.map_err(RpcError::failed),
+            "suggest" => {
+                let SuggestParams { prompt } = parse(params)?;
+                let suggestions = process_command_query(&prompt, false, Some(&Platform::detect()))
+                    .await
+                    .map_err(|e| RpcError::failed(QError::Command(e.to_string())))?;
+                Ok(json!({"suggestions": suggestions}))
+            }
+            "shutdown" => {
+                self.stopping.cancel();
+                Ok(Value::Null)
+            }
+            _ => Err(RpcError::new(METHOD_NOT_FOUND, format!("no method `{}`; q serves query, context, suggest, and shutdown", method))),
+        }
+    }
+
+    async fn query(&self, params: QueryParams) -> Result<Value, QError> {
+        let ask = Ask { provider: params.provider, model: params.model, ..Ask::default() };
+        Ok(self.ask(&ask, &params.prompt, &params.context).await?.to_json())
+    }
+
+    /// `prompt` with `context`, answered as `ask` says and recorded in the history log
+    async fn ask(&self, ask: &Ask, prompt: &str, context: &ContextRequest) -> Result<Answer, QError> {
+        let mut cli = self.cli.clone();
+        if ask.provider.is_some() {
+            cli.provider = ask.provider.clone();
+            cli.model = None;
+        }
+        if ask.model.is_some() {
+            cli.model = ask.model.clone();
+        }
+        let (provider, model) = resolve_provider_and_model(&cli, &self.config)?;
+        let health::Route { provider, model, fail_fast } = health::route(&cli, &self.config, provider, model);
+
+        let assembler = self.gather(context).await?;
+        assembler.check_allowed(provider.as_str()).map_err(|e| QError::Context(e.to_string()))?;
+        let final_prompt = if assembler.is_empty() {
+            prompt.to_string()
+        } else {
+            format!("Context:\n{}\nPrompt: {}", assembler.assemble().content.trim(), prompt)
+        };
+
+        let engine = self.engine(provider, &model, ask, fail_fast).await?;
+        let answer = engine.query(&final_prompt).await;
+        health::note(&self.cli, provider, answer.as_ref().err());
+        let answer = answer.map_err(|e| super::query_failed(e, &engine))?;
+        if !self.cli.incognito {
+            history::record(&self.config, provider.as_str(), engine.model(), ask.preset.as_deref(), prompt, &answer);
+        }
+        Ok(Answer { answer, provider, model: engine.model().to_string() })
+    }
+
+    async fn context(&self, params: ContextParams) -> Result<Value, QError> {
+        let assembler = self.gather(&params.context).await?;
+        if let Some(provider) = &params.provider {
+            let provider = Provider::try_from(provider.as_str()).map_err(|e| QError::Usage(format!("Invalid provider: {}", e)))?;
+            assembler.check_allowed(provider.as_str()).map_err(|e| QError::Context(e.to_string()))?;
+        }
+        let assembled = assembler.assemble();
+        Ok(json!({"content": assembled.content, "compressed": assembled.compressed, "truncated": assembled.truncated}))
+    }
+
+    /// The request's context, fitted to the `[context]` budget
+    async fn gather(&self, request: &ContextRequest) -> Result<ContextAssembler, QError> {
+        let config = ContextConfig::default();
+        let cwd = match &request.cwd {
+            Some(cwd) => cwd.clone(),
+            None => std::env::current_dir().map_err(|e| QError::Context(format!("Failed to get current directory: {}", e)))?,
+        };
+        let mut assembler = ContextAssembler::from_settings(self.config.context());
+        if let Some(text) = request.text.as_ref().filter(|text| !text.trim().is_empty()) {
+            assembler.add(OutputProvider::stdin(text.clone(), config.clone()).get_context().await.map_err(gathered("editor"))?);
+        }
+        for file in &request.files {
+            let file = if file.is_relative() { cwd.join(file) } else { file.clone() };
+            assembler.add(FileProvider::new(file, config.clone()).get_context().await.map_err(gathered("file"))?);
+        }
+        if request.history {
+            assembler.add(HistoryProvider::new(config.clone()).get_context().await.map_err(gathered("history"))?);
+        }
+        if request.directory {
+            let ignore = QIgnore::load(&cwd, &self.config.context().ignore).map_err(|e| QError::Context(e.to_string()))?;
+            let provider = DirectoryProvider::new(cwd.clone(), ContextConfig { ignore, ..config.clone() });
+            assembler.add(provider.get_context().await.map_err(gathered("directory"))?);
+        }
+        if request.cargo {
+            assembler.add(CargoProvider::new(cwd.clone(), config.clone()).get_context().await.map_err(gathered("Cargo"))?);
+        }
+        if request.docker {
+            assembler.add(DockerProvider::new(cwd.clone(), config.clone()).get_context().await.map_err(gathered("Docker"))?);
+        }
+        if request.repo {
+            assembler.add(GitRepoProvider::new(cwd.clone(), config.clone()).get_context().await.map_err(gathered("repository"))?);
+        }
+        if request.diff {
+            let ignore = QIgnore::load(&cwd, &self.config.context().ignore).map_err(|e| QError::Context(e.to_string()))?;
+            let provider = GitDiffProvider::new(cwd.clone(), ContextConfig { ignore, ..config.clone() });
+            assembler.add(provider.get_context().await.map_err(gathered("diff"))?);
+        }
+        if request.system {
+            assembler.add(SystemProvider::new(config.clone()).get_context().await.map_err(gathered("system"))?);
+        }
+        Ok(assembler)
+    }
+
+    /// The engine for `provider` and `model` with `ask`'s settings, connecting on first use
+    async fn engine(&self, provider: Provider, model: &str, ask: &Ask, fail_fast: bool) -> Result<Arc<QueryEngine>, QError> {
+        let model_config = ModelConfig {
+            temperature: ask.temperature
+                .or(self.cli.temperature)
+                .unwrap_or_else(|| self.config.get_temperature(provider)) as f32,
+            max_tokens: ask.max_tokens.or(self.cli.max_tokens),
+            environment: self.config.include_environment().then(environment::preamble),
+            instructions: ask.instructions.clone(),
+            ..ModelConfig::default()
+        };
+        let scope = format!("{}/{}\n{:?}", provider, model, model_config);
+        let key = format!("{}\nfail_fast: {}", scope, fail_fast);
+        let mut engines = self.engines.lock().await;
+        if let Some(engine) = engines.get(&key) {
+            return Ok(engine.clone());
+        }
+        let client = connect(provider, &self.config, Some(model), model_config, self.cli.verbosity).await?;
+        let query_config = QueryConfig {
+            max_retries: if fail_fast { 0 } else { self.cli.max_retries },
+            show_progress: false,
+            cache_ttl: Duration::from_secs(3600),
+            use_cache: !self.cli.no_cache,
+            stream_recovery: self.config.stream_recovery(),
+            cache_mode: self.config.response_cache(),
+            ..QueryConfig::default()
+        };
+        let cache_ttl = query_config.cache_ttl;
+        let mut engine = QueryEngine::new(client, query_config);
+        if self.config.response_cache() != CacheMode::Memory && !self.cli.incognito {
+            if let Some(disk) = query::disk_cache(&self.config, CachePaths::responses_dir, scope, cache_ttl) {
+                engine = engine.with_disk_cache(disk);
+            }
+        }
+        let engine = Arc::new(engine);
+        engines.insert(key, engine.clone());
+        Ok(engine)
+    }
+}
+
+/// A context provider's error as q reports it
+fn gathered(kind: &'static str) -> impl Fn(ContextError) -> QError {
+    move |e| QError::Context(format!("Failed to get {} context: {}", kind, e))
+}
+
+/// Method parameters, or an invalid-params error saying what's wrong with them
+fn parse<T: for<'de> Deserialize<'de>>(params: Value) -> Result<T, RpcError> {
+    let params = if params.is_null() { json!({}) } else { params };
+    serde_json::from_value(params).map_err(|e| RpcError::new(INVALID_PARAMS, e.to_string()))
+}
+
+fn reply(id: Value, result: Result<Value, RpcError>) -> Value {
+    match result {
+        Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+        Err(error) => json!({"jsonrpc": "2.0", "id": id, "error": error.to_json()}),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use tempfile::TempDir;
+
+    fn server(temp_dir: &TempDir) -> Server {
+        let root = temp_dir.path().join("q");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("config.toml"), "[settings]\ndefault_provider = \"openai\"\n").unwrap();
+        let config = ConfigManager::with_root(root, false).unwrap();
+        Server::new(Cli::parse_from(["q", "serve", "--stdio"]), config)
+    }
+
+    #[tokio::test]
+    async fn test_protocol_errors() {
+        let temp_dir = TempDir::new().unwrap();
+        let server = server(&temp_dir);
+        let code = |reply: Option<Value>| reply.unwrap()["error"]["code"].as_i64();
+
+        assert_eq!(code(server.handle("{not json").await), Some(PARSE_ERROR));
+        assert_eq!(code(server.handle(r#"{"jsonrpc": "1.0", "id": 1, "method": "query"}"#).await), Some(INVALID_REQUEST));
+        let reply = server.handle(r#"{"jsonrpc": "2.0", "id": 2, "method": "ask"}"#).await.unwrap();
+        assert_eq!(reply["id"], 2);
+        assert_eq!(reply["error"]["code"], METHOD_NOT_FOUND);
+        assert_eq!(code(server.handle(r#"{"jsonrpc": "2.0", "id": 3, "method": "query", "params": {"promt": "hi"}}"#).await), Some(INVALID_PARAMS));
+        // Notifications get no reply, even when they fail
+        assert_eq!(server.handle(r#"{"jsonrpc": "2.0", "method": "ask"}"#).await, None);
+    }
+
+    #[tokio::test]
+    async fn test_context_and_shutdown() {
+        let temp_dir = TempDir::new().unwrap();
+        let server = server(&temp_dir);
+        let reply = server.handle(r#"{"jsonrpc": "2.0", "id": "a", "method": "context", "params": {"context": {"text": "fn main() {}"}}}"#)
+            .await
+            .unwrap();
+        assert!(reply["result"]["content"].as_str().unwrap().contains("fn main() {}"), "{}", reply);
+
+        // q's own errors come back with its message
+        let reply = server.handle(r#"{"jsonrpc": "2.0", "id": 4, "method": "query", "params": {"prompt": "hi", "provider": "acme"}}"#)
+            .await
+            .unwrap();
+        assert_eq!(reply["error"]["code"], FAILED);
+        assert!(reply["error"]["message"].as_str().unwrap().contains("acme"), "{}", reply);
+
+        let reply = server.handle(r#"{"jsonrpc": "2.0", "id": 5, "method": "shutdown"}"#).await.unwrap();
+        assert_eq!(reply["result"], Value::Null);
+        assert!(server.stopping.is_cancelled());
+    }
+}
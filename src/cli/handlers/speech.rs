@@ -0,0 +1,174 @@
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+use colored::*;
+
+use super::connect;
+use crate::api::openai::MAX_SPEECH_INPUT;
+use crate::api::{LLMApi, ModelConfig};
+use crate::cli::args::Verbosity;
+use crate::config::types::Provider;
+use crate::config::ConfigManager;
+use crate::utils::errors::QError;
+
+/// Command-line players tried in order, with flags that keep them quiet and windowless
+#[cfg(target_os = "macos")]
+const PLAYERS: &[(&str, &[&str])] = &[("afplay", &[])];
+#[cfg(not(target_os = "macos"))]
+const PLAYERS: &[(&str, &[&str])] = &[
+    ("mpv", &["--no-video", "--really-quiet"]),
+    ("ffplay", &["-nodisp", "-autoexit", "-loglevel", "quiet"]),
+    ("mpg123", &["-q"]),
+];
+
+/// Read `text` aloud with OpenAI text-to-speech (the query client when the
+/// query went to OpenAI), then play it or write it to `output` as MP3.
+pub async fn speak(
+    config: &ConfigManager,
+    provider: Provider,
+    client: &Arc<dyn LLMApi>,
+    text: &str,
+    output: Option<&Path>,
+    quiet: bool,
+) -> Result<(), QError> {
+    let speaker = match provider {
+        Provider::OpenAI => client.clone(),
+        _ => connect(Provider::OpenAI, config, None, ModelConfig::default(), Verbosity::default())
+            .await
+            .map_err(|e| QError::Config(format!("--speak uses OpenAI text-to-speech, which isn't available: {}", e)))?,
+    };
+
+    let text = speakable(text);
+    if text.trim().is_empty() {
+        return Err(QError::Usage("The response has nothing to read aloud".to_string()));
+    }
+
+    if !quiet {
+        eprintln!("{}", "Generating speech...".dimmed());
+    }
+    // MP3 frames can be concatenated, so long responses are spoken in pieces
+    let mut audio = Vec::new();
+    for chunk in split_text(&text, MAX_SPEECH_INPUT) {
+        let part = speaker.speak(&chunk)
+            .await
+            .map_err(|e| QError::Api(format!("Text-to-speech failed: {}", e)))?;
+        audio.extend_from_slice(&part);
+    }
+
+    match output {
+        Some(path) => {
+            std::fs::write(path, &audio)
+                .map_err(|e| QError::Command(format!("Failed to write {}: {}", path.display(), e)))?;
+            if !quiet {
+                eprintln!("{}", format!("Saved speech to {}", path.display()).dimmed());
+            }
+            Ok(())
+        }
+        None => play(&audio),
+    }
+}
+
+/// Play MP3 audio through the first available player
+fn play(audio: &[u8]) -> Result<(), QError> {
+    let path = std::env::temp_dir().join(format!("q-speech-{}.mp3", std::process::id()));
+    std::fs::write(&path, audio)?;
+
+    let mut result = Err(QError::Command(format!(
+        "No audio player found (tried {}); use -o to save the speech instead",
+        PLAYERS.iter().map(|(name, _)| *name).collect::<Vec<_>>().join(", ")
+    )));
+    for (player, args) in PLAYERS {
+        let status = Command::new(player)
+            .args(*args)
+            .arg(&path)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .status();
+        match status {
+            Ok(status) if status.success() => {
+                result = Ok(());
+                break;
+            }
+            Ok(status) => {
+                result = Err(QError::Command(format!("{} exited with {}", player, status)));
+                break;
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => {
+                result = Err(QError::Command(format!("Failed to run {}: {}", player, e)));
+                break;
+            }
+        }
+    }
+
+    let _ = std::fs::remove_file(&path);
+    result
+}
+
+/// The response as it should be read: code blocks and citations are skipped
+/// and markdown markers dropped so the voice doesn't spell them out
+fn speakable(text: &str) -> String {
+    let mut lines = Vec::new();
+    let mut in_code = false;
+    for line in text.lines() {
+        if line.trim_start().starts_with("```") {
+            if !in_code {
+                lines.push("(code omitted)".to_string());
+            }
+            in_code = !in_code;
+            continue;
+        }
+        // Citation footnotes (`[1]: https://...`) aren't worth reading out
+        if in_code || (line.starts_with('[') && line.contains("]: http")) {
+            continue;
+        }
+        let line = line.trim_start().trim_start_matches('#').trim_start();
+        let line = line.strip_prefix("- ").or_else(|| line.strip_prefix("* ")).unwrap_or(line);
+        lines.push(line.replace(['*', '`'], ""));
+    }
+    lines.join("\n")
+}
+
+/// Split `text` into pieces of at most `max` characters, preferring paragraph,
+/// then sentence, then word boundaries
+fn split_text(text: &str, max: usize) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut rest = text.trim();
+    while rest.chars().count() > max {
+        let limit = rest.char_indices().nth(max).map(|(i, _)| i).unwrap_or(rest.len());
+        let window = &rest[..limit];
+        let cut = window.rfind("\n\n")
+            .or_else(|| window.rfind(". ").map(|i| i + 1))
+            .or_else(|| window.rfind(char::is_whitespace))
+            .filter(|&i| i > 0)
+            .unwrap_or(limit);
+        chunks.push(rest[..cut].trim().to_string());
+        rest = rest[cut..].trim_start();
+    }
+    if !rest.is_empty() {
+        chunks.push(rest.to_string());
+    }
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_speakable_skips_code_and_markup() {
+        let text = "# Steps\n\n- Run **this**:\n```bash\nls -la\n```\nThen `cd` in.\n[1]: https://example.com \"Example\"";
+        assert_eq!(speakable(text), "Steps\n\nRun this:\n(code omitted)\nThen cd in.");
+    }
+
+    #[test]
+    fn test_split_text() {
+        assert_eq!(split_text("short", 10), vec!["short"]);
+        assert_eq!(
+            split_text("One two. Three four five.", 12),
+            vec!["One two.", "Three four", "five."]
+        );
+        assert_eq!(split_text("para one\n\npara two", 15), vec!["para one", "para two"]);
+        assert!(split_text(&"x".repeat(25), 10).iter().all(|chunk| chunk.len() <= 10));
+    }
+}
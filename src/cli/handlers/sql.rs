@@ -0,0 +1,40 @@
+use std::path::Path;
+
+use super::pr::truncate_diff;
+use super::{ask, check_context_allowed, code_block, load_config};
+use crate::cli::args::Cli;
+use crate::utils::errors::QError;
+use crate::utils::output;
+
+/// Schema bytes sent with the prompt; DDL for a few hundred tables fits
+const MAX_SCHEMA_BYTES: usize = 64 * 1024;
+
+/// `q sql [--schema file] [--dialect name] <question>`: one SQL query,
+/// printed bare so it can be piped to a database client
+pub async fn run(cli: &Cli, question: &str, schema: Option<&Path>, dialect: Option<&str>) -> Result<(), QError> {
+    let config = load_config(cli)?;
+    config.ensure_profile_exists()?;
+
+    let mut prompt = format!(
+        "Write one {} query that answers: {}\n\
+         Reply with a single ```sql code block and nothing else. Put any assumption you had to \
+         make in a `--` comment inside the block.",
+        dialect.unwrap_or("SQL"),
+        question
+    );
+    if let Some(path) = schema {
+        check_context_allowed(cli, &config, &["file"])?;
+        let schema = std::fs::read_to_string(path)
+            .map_err(|e| QError::Context(format!("Failed to read schema {}: {}", path.display(), e)))?;
+        prompt.push_str(&format!(
+            " Use only the tables and columns in this schema, and their exact names.\n\nSchema ({}):\n```sql\n{}\n```",
+            path.display(),
+            truncate_diff(&schema, MAX_SCHEMA_BYTES).trim_end()
+        ));
+    }
+
+    let response = ask(cli, &config, &prompt).await?;
+
+    output::line(code_block(&response));
+    Ok(())
+}
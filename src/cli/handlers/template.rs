@@ -0,0 +1,312 @@
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+
+use colored::*;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use super::self_update::{download, http_client};
+use super::{ask, query};
+use crate::cli::args::{Cli, TemplateCommand};
+use crate::config::paths::ConfigPaths;
+use crate::config::template::{self, check_name, Template};
+use crate::utils::errors::QError;
+use crate::utils::output;
+
+const GISTS_URL: &str = "https://api.github.com/gists";
+/// Lines of a template shown before asking to install it
+const PREVIEW_LINES: usize = 20;
+
+/// `q -t <name> [prompt]`: the template's text with the prompt as its input,
+/// sent with the model, temperature, token limit, and context from its
+/// frontmatter. Flags given on the command line still win.
+pub async fn run(cli: &Cli, name: &str, input: Option<&str>) -> Result<(), QError> {
+    let template = Template::load(&templates_dir(cli)?, name)?;
+    let prompt = template.render(input)?;
+    let cli = ask::with_preset(cli, &template.settings);
+    query::run_with_preset(&cli, Some(&prompt), Some((name, &template.settings))).await
+}
+
+/// `q template install|list|show`
+pub async fn manage(cli: &Cli, action: &TemplateCommand) -> Result<(), QError> {
+    let dir = templates_dir(cli)?;
+    match action {
+        TemplateCommand::Install { source, name, sha256, force } => {
+            install(&dir, source, name.as_deref(), sha256.as_deref(), *force).await
+        }
+        TemplateCommand::List => list(&dir),
+        TemplateCommand::Show { name } => {
+            Template::load(&dir, name)?;
+            let path = template::path(&dir, name);
+            output::text(std::fs::read_to_string(&path)?);
+            Ok(())
+        }
+    }
+}
+
+fn templates_dir(cli: &Cli) -> Result<PathBuf, QError> {
+    Ok(ConfigPaths::new(cli.verbose)?.templates_dir())
+}
+
+/// Where a template is installed from
+#[derive(Debug, PartialEq)]
+enum Source {
+    Url(String),
+    /// A gist's ID
+    Gist(String),
+    Path(PathBuf),
+}
+
+impl Source {
+    /// `gist:ID`, a gist's URL, another http(s) URL, or else a path
+    fn parse(source: &str) -> Self {
+        if let Some(id) = source.strip_prefix("gist:") {
+            return Source::Gist(id.to_string());
+        }
+        if let Some(rest) = source.strip_prefix("https://gist.github.com/") {
+            // gist.github.com/ID or gist.github.com/USER/ID
+            if let Some(id) = rest.trim_end_matches('/').rsplit('/').next().filter(|id| !id.is_empty()) {
+                return Source::Gist(id.to_string());
+            }
+        }
+        if source.starts_with("https://") || source.starts_with("http://") {
+            return Source::Url(source.to_string());
+        }
+        Source::Path(PathBuf::from(source))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Gist {
+    files: std::collections::BTreeMap<String, GistFile>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GistFile {
+    content: Option<String>,
+    /// Set when `content` was cut short; the whole file is at `raw_url`
+    #[serde(default)]
+    truncated: bool,
+    raw_url: String,
+}
+
+/// The template's file name and bytes
+async fn fetch(source: &Source, name: Option<&str>) -> Result<(String, Vec<u8>), QError> {
+    match source {
+        Source::Path(path) => {
+            let bytes = std::fs::read(path)
+                .map_err(|e| QError::Usage(format!("Couldn't read {}: {}", path.display(), e)))?;
+            Ok((file_name(&path.to_string_lossy()), bytes))
+        }
+        Source::Url(url) => {
+            let bytes = download(&http_client()?, url).await?;
+            let path = url.split(['?', '#']).next().unwrap_or(url);
+            Ok((file_name(path), bytes))
+        }
+        Source::Gist(id) => {
+            let client = http_client()?;
+            // Q_GISTS_URL points at a GitHub Enterprise API (or a mock server in tests)
+            let url = format!("{}/{}", std::env::var("Q_GISTS_URL").unwrap_or_else(|_| GISTS_URL.to_string()), id);
+            let gist: Gist = serde_json::from_slice(&download(&client, &url).await?)
+                .map_err(|e| QError::Api(format!("Unexpected reply for gist {}: {}", id, e)))?;
+            let (gist_file_name, file) = pick_gist_file(id, gist.files, name)?;
+            let bytes = match file.content {
+                Some(content) if !file.truncated => content.into_bytes(),
+                _ => download(&client, &file.raw_url).await?,
+            };
+            Ok((file_name(&gist_file_name), bytes))
+        }
+    }
+}
+
+/// The gist's only Markdown file, or with several, the one `name` picks
+fn pick_gist_file(
+    id: &str,
+    files: std::collections::BTreeMap<String, GistFile>,
+    name: Option<&str>,
+) -> Result<(String, GistFile), QError> {
+    let mut markdown: Vec<(String, GistFile)> = files.into_iter().filter(|(file, _)| file.ends_with(".md")).collect();
+    if let Some(name) = name {
+        if let Some(index) = markdown.iter().position(|(file, _)| file_name(file) == name) {
+            return Ok(markdown.swap_remove(index));
+        }
+    }
+    match markdown.len() {
+        1 => Ok(markdown.remove(0)),
+        0 => Err(QError::Usage(format!("Gist {} has no Markdown (.md) file", id))),
+        _ => {
+            let names: Vec<String> = markdown.iter().map(|(file, _)| file_name(file)).collect();
+            Err(QError::Usage(format!(
+                "Gist {} has several templates ({}); choose one with --name",
+                id,
+                names.join(", ")
+            )))
+        }
+    }
+}
+
+/// The last part of a path or URL without its `.md`
+fn file_name(path: &str) -> String {
+    let last = path.trim_end_matches('/').rsplit(['/', '\\']).next().unwrap_or(path);
+    last.strip_suffix(".md").unwrap_or(last).to_string()
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+async fn install(dir: &Path, source: &str, name: Option<&str>, expected: Option<&str>, force: bool) -> Result<(), QError> {
+    let source = Source::parse(source);
+    let (file_name, bytes) = fetch(&source, name).await?;
+    let name = name.map(str::to_string).unwrap_or(file_name);
+    check_name(&name)?;
+    let text = String::from_utf8(bytes)
+        .map_err(|_| QError::Usage("That file isn't UTF-8 text, so it can't be a template".to_string()))?;
+    let template = Template::parse(&name, &text)?;
+    let path = template::path(dir, &name);
+    if path.exists() && !force {
+        return Err(QError::Usage(format!(
+            "A template named '{}' is already installed; add --force to replace it or --name to choose another name",
+            name
+        )));
+    }
+
+    let checksum = sha256_hex(text.as_bytes());
+    match expected {
+        Some(expected) if !expected.trim().eq_ignore_ascii_case(&checksum) => {
+            return Err(QError::Usage(format!(
+                "Checksum mismatch (expected {}, got {}); the template was not installed",
+                expected.trim(),
+                checksum
+            )));
+        }
+        Some(_) => {}
+        None => {
+            if !output::can_prompt() {
+                return Err(QError::Usage(format!(
+                    "Not installing without confirmation; its SHA-256 is {}. Check it, then pass --sha256 {} to install it",
+                    checksum, checksum
+                )));
+            }
+            eprintln!("{}", summary(&template, &text, &checksum));
+            eprint!("Trust and install it as '{}'? [y/N] ", name);
+            std::io::stderr().flush()?;
+            let mut answer = String::new();
+            std::io::stdin().lock().read_line(&mut answer)?;
+            if !matches!(answer.trim().to_lowercase().as_str(), "y" | "yes") {
+                eprintln!("Not installed");
+                return Ok(());
+            }
+        }
+    }
+
+    std::fs::create_dir_all(dir)?;
+    std::fs::write(&path, &text)?;
+    output::line(format!("Installed template {} ({}); run it with q -t {}", name.green(), path.display(), name));
+    Ok(())
+}
+
+/// What the user is asked to trust: the template's settings, its start, and its checksum
+fn summary(template: &Template, text: &str, checksum: &str) -> String {
+    let settings = &template.settings;
+    let mut summary = format!("{}\n", template.name.bold());
+    if let Some(description) = &settings.description {
+        summary.push_str(&format!("  {}\n", description));
+    }
+    let fields = [
+        ("provider", settings.provider.map(|provider| provider.to_string())),
+        ("model", settings.model.clone()),
+        ("temperature", settings.temperature.map(|temperature| temperature.to_string())),
+        ("max_tokens", settings.max_tokens.map(|tokens| tokens.to_string())),
+    ];
+    for (field, value) in fields {
+        if let Some(value) = value {
+            summary.push_str(&format!("  {}: {}\n", field, value));
+        }
+    }
+    if !settings.context.is_empty() {
+        let context: Vec<String> = settings.context.iter().map(|flag| format!("{:?}", flag).to_lowercase()).collect();
+        summary.push_str(&format!("  context: {}\n", context.join(", ")));
+    }
+    if settings.system_prompt.is_some() {
+        summary.push_str("  system_prompt: set\n");
+    }
+    summary.push('\n');
+    let lines: Vec<&str> = text.lines().collect();
+    for line in lines.iter().take(PREVIEW_LINES) {
+        summary.push_str(&format!("  {}\n", line.dimmed()));
+    }
+    if lines.len() > PREVIEW_LINES {
+        summary.push_str(&format!("  {}\n", format!("... {} more lines", lines.len() - PREVIEW_LINES).dimmed()));
+    }
+    summary.push_str(&format!("\nSHA-256: {}", checksum));
+    summary
+}
+
+fn list(dir: &Path) -> Result<(), QError> {
+    let names = template::names(dir)?;
+    if names.is_empty() {
+        output::line(format!(
+            "No templates. Install one with 'q template install <url|gist|path>' or add <name>.md to {}",
+            dir.display()
+        ).dimmed());
+        return Ok(());
+    }
+    let width = names.iter().map(String::len).max().unwrap_or(0);
+    for name in names {
+        match Template::load(dir, &name) {
+            Ok(template) => match template.settings.description {
+                Some(description) => output::line(format!("{:<width$}  {}", name.green(), description, width = width)),
+                None => output::line(name.green()),
+            },
+            Err(e) => output::line(format!("{:<width$}  {}", name.red(), e, width = width)),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gist_file(content: &str) -> GistFile {
+        GistFile { content: Some(content.to_string()), truncated: false, raw_url: String::new() }
+    }
+
+    #[test]
+    fn test_source_parse() {
+        assert_eq!(Source::parse("gist:aa5a3"), Source::Gist("aa5a3".to_string()));
+        assert_eq!(Source::parse("https://gist.github.com/rfushimi/aa5a3/"), Source::Gist("aa5a3".to_string()));
+        assert_eq!(
+            Source::parse("https://example.com/t/review.md"),
+            Source::Url("https://example.com/t/review.md".to_string())
+        );
+        assert_eq!(Source::parse("./review.md"), Source::Path(PathBuf::from("./review.md")));
+    }
+
+    #[test]
+    fn test_file_name() {
+        assert_eq!(file_name("https://example.com/t/translate-ja.md"), "translate-ja");
+        assert_eq!(file_name("templates/review"), "review");
+    }
+
+    #[test]
+    fn test_pick_gist_file() {
+        let files = [("review.md".to_string(), gist_file("a")), ("notes.txt".to_string(), gist_file("b"))].into();
+        assert_eq!(pick_gist_file("1", files, None).unwrap().0, "review.md");
+
+        let several = || [("review.md".to_string(), gist_file("a")), ("sql.md".to_string(), gist_file("b"))].into();
+        assert!(pick_gist_file("1", several(), None).unwrap_err().to_string().contains("review, sql"));
+        assert_eq!(pick_gist_file("1", several(), Some("sql")).unwrap().0, "sql.md");
+    }
+
+    #[test]
+    fn test_summary() {
+        colored::control::set_override(false);
+        let text = "+++\ndescription = \"Translate\"\nmodel = \"gpt-4o\"\n+++\n{input}\n";
+        let template = Template::parse("translate-ja", text).unwrap();
+        let summary = summary(&template, text, "abc123");
+        assert!(summary.starts_with("translate-ja\n  Translate\n  model: gpt-4o\n\n  +++\n"), "{}", summary);
+        assert!(summary.ends_with("\nSHA-256: abc123"));
+    }
+}
@@ -0,0 +1,156 @@
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use chrono::{DateTime, Local};
+use colored::*;
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+
+use super::{load_config, query};
+use crate::cli::args::Cli;
+use crate::utils::errors::QError;
+use crate::utils::i18n;
+use crate::utils::output;
+
+/// `q watch -F FILE <prompt>`: answer the prompt with the files as context,
+/// then again each time one of them changes, until Ctrl-C. Each run starts
+/// with a separator line saying when it ran and which files changed.
+pub async fn run(cli: &Cli, prompt: &str, files: &[PathBuf], debounce: u64) -> Result<(), QError> {
+    if cli.json {
+        return Err(QError::Usage("q watch runs until stopped, so it can't print one JSON object; drop --json".to_string()));
+    }
+    load_config(cli)?.ensure_profile_exists()?;
+    let watched = watched_paths(files)?;
+
+    // Editors often save by writing a new file and renaming it over the old
+    // one, which ends a watch on the file itself, so watch the directories
+    let (sender, mut events) = unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |event| {
+        let _ = sender.send(event);
+    }).map_err(|e| QError::Unknown(format!("Couldn't start watching files: {}", e)))?;
+    let directories: BTreeSet<&Path> = watched.iter().filter_map(|path| path.parent()).collect();
+    for directory in directories {
+        watcher.watch(directory, RecursiveMode::NonRecursive)
+            .map_err(|e| QError::Unknown(format!("Couldn't watch {}: {}", directory.display(), e)))?;
+    }
+
+    // Stdin can only be read once, so piped input isn't context for every run
+    let mut cli = cli.clone();
+    cli.command = None;
+    cli.file = files.to_vec();
+    cli.no_stdin = true;
+
+    let names: Vec<String> = files.iter().map(|file| file.display().to_string()).collect();
+    eprintln!("{}", i18n::message_with("watch-started", &[("files", &names.join(", "))]).dimmed());
+    let mut changed = Vec::new();
+    loop {
+        output::line(separator(Local::now(), &changed).dimmed());
+        if let Err(e) = query::run(&cli, Some(prompt)).await {
+            eprintln!("{}", e.to_string().red());
+        }
+        changed = next_change(&mut events, &watched, Duration::from_millis(debounce)).await?;
+    }
+}
+
+/// The files as absolute paths in their real directories, as the watcher
+/// reports them
+fn watched_paths(files: &[PathBuf]) -> Result<BTreeSet<PathBuf>, QError> {
+    files.iter()
+        .map(|file| {
+            let name = file.file_name()
+                .ok_or_else(|| QError::Usage(format!("{} isn't a file", file.display())))?;
+            let directory = match file.parent() {
+                Some(parent) if !parent.as_os_str().is_empty() => parent,
+                _ => Path::new("."),
+            };
+            let directory = directory.canonicalize()
+                .map_err(|e| QError::Usage(format!("Can't watch {}: {}", file.display(), e)))?;
+            Ok(directory.join(name))
+        })
+        .collect()
+}
+
+/// The watched files changed by the next burst of changes: waits for the
+/// first change to one of them, then until `debounce` passes without another
+async fn next_change(
+    events: &mut UnboundedReceiver<notify::Result<Event>>,
+    watched: &BTreeSet<PathBuf>,
+    debounce: Duration,
+) -> Result<Vec<PathBuf>, QError> {
+    let mut changed = BTreeSet::new();
+    loop {
+        let event = if changed.is_empty() {
+            events.recv().await
+        } else {
+            match tokio::time::timeout(debounce, events.recv()).await {
+                Ok(event) => event,
+                Err(_) => break,
+            }
+        };
+        let Some(event) = event else {
+            return Err(QError::Unknown("The file watcher stopped".to_string()));
+        };
+        match event {
+            Ok(event) => changed.extend(changes(event, watched)),
+            Err(e) => eprintln!("{}", format!("Watching files: {}", e).yellow()),
+        }
+    }
+    Ok(changed.into_iter().collect())
+}
+
+/// The watched files an event changed; reading them (as each run does) isn't a change
+fn changes(event: Event, watched: &BTreeSet<PathBuf>) -> Vec<PathBuf> {
+    if !matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)) {
+        return Vec::new();
+    }
+    event.paths.into_iter().filter(|path| watched.contains(path)).collect()
+}
+
+/// `── 14:03:27 · lib.rs changed ──────`, or just the time for the first run
+fn separator(time: DateTime<Local>, changed: &[PathBuf]) -> String {
+    let mut label = time.format("%H:%M:%S").to_string();
+    if !changed.is_empty() {
+        let names: Vec<String> = changed.iter()
+            .map(|path| path.file_name().unwrap_or(path.as_os_str()).to_string_lossy().into_owned())
+            .collect();
+        label.push_str(&format!(" · {} changed", names.join(", ")));
+    }
+    format!("── {} {}", label, "─".repeat(60usize.saturating_sub(label.chars().count())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use notify::event::{AccessKind, CreateKind, ModifyKind};
+
+    fn event(kind: EventKind, path: &str) -> notify::Result<Event> {
+        Ok(Event::new(kind).add_path(PathBuf::from(path)))
+    }
+
+    #[test]
+    fn test_separator() {
+        let time = Local.with_ymd_and_hms(2026, 3, 1, 14, 3, 27).unwrap();
+        assert_eq!(separator(time, &[]), format!("── 14:03:27 {}", "─".repeat(52)));
+        let changed = [PathBuf::from("/src/lib.rs"), PathBuf::from("/src/main.rs")];
+        assert!(separator(time, &changed).starts_with("── 14:03:27 · lib.rs, main.rs changed ──"));
+    }
+
+    #[tokio::test]
+    async fn test_next_change_collects_a_burst() {
+        let watched: BTreeSet<PathBuf> = [PathBuf::from("/src/lib.rs"), PathBuf::from("/src/main.rs")].into();
+        let (sender, mut events) = unbounded_channel();
+        sender.send(event(EventKind::Access(AccessKind::Any), "/src/lib.rs")).unwrap();
+        sender.send(event(EventKind::Modify(ModifyKind::Any), "/src/other.rs")).unwrap();
+        sender.send(event(EventKind::Modify(ModifyKind::Any), "/src/main.rs")).unwrap();
+        sender.send(event(EventKind::Create(CreateKind::File), "/src/lib.rs")).unwrap();
+        sender.send(event(EventKind::Modify(ModifyKind::Any), "/src/main.rs")).unwrap();
+
+        let changed = next_change(&mut events, &watched, Duration::from_millis(20)).await.unwrap();
+        assert_eq!(changed, [PathBuf::from("/src/lib.rs"), PathBuf::from("/src/main.rs")]);
+
+        drop(sender);
+        assert!(next_change(&mut events, &watched, Duration::from_millis(20)).await.is_err());
+    }
+}
@@ -0,0 +1,80 @@
+use std::env;
+
+use super::pr::truncate_diff;
+use super::{ask, check_context_allowed, diff_budget, load_config, Diff};
+use crate::cli::args::Cli;
+use crate::context::git::git;
+use crate::utils::errors::QError;
+use crate::utils::output;
+
+/// `q what-changed [range]`: a plain-language summary of a diff, grouped by
+/// area, with the changes a reviewer should look at closely called out.
+/// Without a range it covers the uncommitted changes.
+pub async fn run(cli: &Cli, range: Option<&str>) -> Result<(), QError> {
+    let config = load_config(cli)?;
+    config.ensure_profile_exists()?;
+    check_context_allowed(cli, &config, &["diff"])?;
+
+    let dir = env::current_dir()
+        .map_err(|e| QError::Context(format!("Failed to get current directory: {}", e)))?;
+    let revisions = range.map(revisions);
+    let (described, log) = match &revisions {
+        Some(revisions) => {
+            let log = git(&dir, &["log", "--no-merges", "--reverse", "--format=- %s", revisions])
+                .map_err(|e| QError::Context(e.to_string()))?;
+            (format!("the commits in {}", range.unwrap_or_default()), log)
+        }
+        None => ("the uncommitted changes in the working tree".to_string(), String::new()),
+    };
+    let target = revisions.as_deref().unwrap_or("HEAD");
+    let diff = Diff::load(&config, &dir, &[target])?;
+    let stat = diff.stat(&dir, &[target])?;
+    let diff = diff.text;
+    if diff.trim().is_empty() {
+        return Err(QError::Usage(match range {
+            Some(range) => format!("Nothing changed in {}", range),
+            None => "No uncommitted changes; give a range such as main..feature".to_string(),
+        }));
+    }
+
+    let mut prompt = format!(
+        "Explain what changed in {} for someone about to review it or write release notes.\n\
+         Group the changes by area of the code as `### Area` headings with `- change` lines, \
+         saying what each change does and why it seems to have been made, not restating the \
+         diff line by line. End with a `### Risks` section listing anything that deserves a \
+         close look (behavior changes, removed checks, migrations, concurrency, error handling, \
+         security-sensitive code, missing tests), or `- None spotted` if nothing stands out.\n\n",
+        described
+    );
+    if !log.trim().is_empty() {
+        prompt.push_str(&format!("Commits:\n{}\n", log));
+    }
+    prompt.push_str(&format!("Files changed:\n{}\nDiff:\n{}", stat, truncate_diff(&diff, diff_budget(&config))));
+
+    let response = ask(cli, &config, &prompt).await?;
+
+    output::answer(&response);
+    Ok(())
+}
+
+/// The revisions `git log` and `git diff` take for a range: `a..b` and
+/// `a...b` as given, and a single revision as just that commit
+fn revisions(range: &str) -> String {
+    if range.contains("..") {
+        range.to_string()
+    } else {
+        format!("{}^!", range)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_revisions() {
+        assert_eq!(revisions("main..feature"), "main..feature");
+        assert_eq!(revisions("main...HEAD"), "main...HEAD");
+        assert_eq!(revisions("abc123"), "abc123^!");
+    }
+}
@@ -1 +1,362 @@
 pub mod args;
+mod handlers;
+
+use std::path::Path;
+
+use clap::parser::ValueSource;
+use clap::error::ErrorKind;
+use clap::{ArgMatches, CommandFactory, FromArgMatches};
+
+use crate::config::types::Defaults;
+use crate::config::ConfigManager;
+use crate::context::output::{has_piped_stdin, read_stdin};
+use crate::utils::errors::QError;
+use crate::utils::i18n;
+use crate::utils::output::{self, Json, OutputWriter, Plain, Porcelain, Quiet, Terminal};
+use args::{Cli, Commands, ContextFlag, ScheduleCommand};
+
+impl Cli {
+    /// Parse the command line, then fill any flag not given there from the
+    /// config's `[defaults]` section
+    pub fn parse_with_config() -> Self {
+        // An unreadable config is reported by the command that needs it
+        let config = ConfigManager::new(false).ok();
+        let mut args: Vec<_> = std::env::args_os().collect();
+        if let Some(config) = &config {
+            args = args::expand_alias(args, config.aliases());
+        }
+        i18n::init(i18n::Language::detect(config.as_ref().and_then(|config| config.language())));
+        let matches = i18n::localize_command(Cli::command()).get_matches_from(&args);
+        let mut cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+        if let Some(prompt) = &cli.prompt {
+            if let Err(message) = args::validate_prompt(&args, prompt) {
+                i18n::localize_command(Cli::command()).error(ErrorKind::ValueValidation, message).exit();
+            }
+        }
+        if let Some(Commands::Schedule { action: ScheduleCommand::Add { .. } }) = &cli.command {
+            if let Err(message) = args::validate_schedule(&matches) {
+                i18n::localize_command(Cli::command()).error(ErrorKind::ArgumentConflict, message).exit();
+            }
+        }
+
+        if !cli.no_defaults {
+            if let Some(config) = &config {
+                cli.apply_defaults(config.defaults(), &matches);
+                if cli.ctx.is_empty() {
+                    cli.ctx = config.default_context().to_vec();
+                }
+            }
+        }
+        cli.apply_ctx();
+        cli
+    }
+
+    /// Turn on the individual context flags named by `--ctx`
+    pub(crate) fn apply_ctx(&mut self) {
+        for flag in &self.ctx {
+            match flag {
+                ContextFlag::Hist => self.history = true,
+                ContextFlag::Here => self.directory = true,
+                ContextFlag::Repo => self.repo = true,
+                ContextFlag::Diff => self.diff = true,
+                ContextFlag::Cargo => self.cargo = true,
+                ContextFlag::Docker => self.docker = true,
+                ContextFlag::Sys => self.system = true,
+            }
+        }
+    }
+
+    fn apply_defaults(&mut self, defaults: &Defaults, matches: &ArgMatches) {
+        let unset = |id: &str| matches.value_source(id) != Some(ValueSource::CommandLine);
+
+        if let (Some(detail), true) = (defaults.detail, unset("verbosity")) {
+            self.verbosity = detail;
+        }
+        if let (Some(retries), true) = (defaults.retries, unset("max_retries")) {
+            self.max_retries = retries;
+        }
+        if unset("stream") {
+            self.stream = defaults.stream.unwrap_or(self.stream);
+        }
+        if unset("history") {
+            self.history = defaults.hist.unwrap_or(self.history);
+        }
+        if unset("directory") {
+            self.directory = defaults.here.unwrap_or(self.directory);
+        }
+        if unset("no_cache") {
+            self.no_cache = defaults.no_cache.unwrap_or(self.no_cache);
+        }
+    }
+
+    /// Run the command line, writing stdout through the `OutputWriter` its flags select
+    pub async fn run(&self) -> Result<(), QError> {
+        if self.porcelain {
+            colored::control::set_override(false);
+        }
+        output::init(self.output_writer()?);
+        let result = match self.porcelain && (self.pick || self.review_context) {
+            true => Err(QError::Usage("--porcelain never prompts, so --pick and --review-context can't be used with it".to_string())),
+            false => self.dispatch().await,
+        };
+        if let Err(e) = result {
+            output::error(&e);
+            return Err(e);
+        }
+        output::finish()?;
+        Ok(())
+    }
+
+    /// Whether to show spinners and progress bars: not among --debug's
+    /// output, with --quiet, or for scripts reading --porcelain
+    pub(crate) fn show_progress(&self) -> bool {
+        !self.debug && !self.quiet && !self.porcelain
+    }
+
+    /// `--porcelain`, `--json`, `-o FILE` with a subcommand, `--quiet`, or the
+    /// terminal. A query's `-o` is one of its streaming sinks instead, next to
+    /// the terminal.
+    fn output_writer(&self) -> Result<Box<dyn OutputWriter>, QError> {
+        Ok(match (&self.output, &self.command) {
+            _ if self.porcelain => Box::new(Porcelain::new(std::io::stdout(), self.json)),
+            _ if self.json => Box::new(Json::default()),
+            (Some(path), Some(_)) => Box::new(
+                Plain::file(path).map_err(|e| QError::Usage(format!("Can't write {}: {}", path.display(), e)))?
+            ),
+            _ if self.quiet => Box::new(Quiet),
+            _ => Box::new(Terminal::default()),
+        })
+    }
+
+    /// Dispatch to a subcommand handler, or run a query when a prompt (or `--audio`) is given
+    async fn dispatch(&self) -> Result<(), QError> {
+        if let Some(cmd) = &self.command {
+            return cmd.execute(self).await;
+        }
+
+        let mut cli = self.clone();
+        if let Some(path) = &self.prompt_file {
+            cli.prompt = Some(read_prompt_file(path)?);
+            if path.as_os_str() == "-" {
+                cli.no_stdin = true;
+            }
+        } else if self.prompt_from_stdin(has_piped_stdin()) {
+            let input = read_stdin().map_err(|e| QError::Context(format!("Failed to read stdin: {}", e)))?;
+            let input = input.trim();
+            if !input.is_empty() {
+                cli.prompt = Some(input.to_string());
+            }
+            // It's been read, so there's none left to be context
+            cli.no_stdin = true;
+        }
+
+        if cli.prompt.is_none() && cli.audio.is_none() && cli.template.is_none() {
+            return Err(QError::Usage(i18n::message("no-prompt")));
+        }
+        if !cli.incognito {
+            handlers::gc::on_startup(&cli);
+        }
+        if let Some(name) = &cli.template {
+            return handlers::template::run(&cli, name, cli.prompt.as_deref()).await;
+        }
+        handlers::query::run(&cli, cli.prompt.as_deref()).await
+    }
+
+    /// Whether piped stdin is the prompt rather than context: when no prompt
+    /// is given, and neither `--audio` (whose transcript is the prompt) nor
+    /// `--no-stdin` is. With a template, it's the template's input.
+    fn prompt_from_stdin(&self, stdin_is_piped: bool) -> bool {
+        stdin_is_piped && self.prompt.is_none() && self.prompt_file.is_none() && self.audio.is_none() && !self.no_stdin
+    }
+}
+
+/// The prompt in `path` (`-` for stdin), without its trailing newline
+fn read_prompt_file(path: &Path) -> Result<String, QError> {
+    let text = if path.as_os_str() == "-" {
+        read_stdin().map_err(|e| QError::Context(format!("Failed to read stdin: {}", e)))?
+    } else {
+        std::fs::read_to_string(path)
+            .map_err(|e| QError::Usage(format!("Couldn't read the prompt from {}: {}", path.display(), e)))?
+    };
+    let prompt = text.trim_end();
+    if prompt.trim().is_empty() {
+        return Err(QError::Usage(format!("The prompt file {} is empty", path.display())));
+    }
+    Ok(prompt.to_string())
+}
+
+impl Commands {
+    pub async fn execute(&self, cli: &Cli) -> Result<(), QError> {
+        match self {
+            Commands::SetKey { provider, key, from_stdin, from_file, no_verify, add } => {
+                let key = handlers::config::read_key_input(key.as_deref(), *from_stdin, from_file.as_deref())?;
+                handlers::config::set_key(cli, provider, &key, !no_verify, *add).await
+            }
+            Commands::Keys { action } => handlers::keys::run(cli, action).await,
+            Commands::Config { action } => handlers::config::run(cli, action),
+            Commands::Profile { action } => handlers::profile::run(cli, action),
+            Commands::SetProvider { provider } => handlers::config::set_provider(cli, provider),
+            Commands::SetModel { provider, model } => handlers::config::set_model(cli, provider, model),
+            Commands::ListModels { search, page, per_page } => {
+                handlers::models::list(cli, search.as_deref(), *page, *per_page).await
+            }
+            Commands::Pr { base, create } => handlers::pr::run(cli, base.as_deref(), *create).await,
+            Commands::Commit { kind, scope, breaking } => {
+                handlers::commit::commit(cli, kind.as_deref(), scope.as_deref(), *breaking).await
+            }
+            Commands::BranchName { title } => handlers::commit::branch_name(cli, title.as_deref()).await,
+            Commands::Changelog { range, diff } => handlers::changelog::run(cli, range.as_deref(), *diff).await,
+            Commands::WhatChanged { range } => handlers::what_changed::run(cli, range.as_deref()).await,
+            Commands::FixBuild { patch, command } => handlers::fix_build::run(cli, command, *patch).await,
+            Commands::FixTest { filter, patch, command } => {
+                handlers::fix_test::run(cli, filter.as_deref(), command, *patch).await
+            }
+            Commands::Explain { command } => handlers::explain::run(cli, command).await,
+            Commands::Sql { question, schema, dialect } => {
+                handlers::sql::run(cli, question, schema.as_deref(), dialect.as_deref()).await
+            }
+            Commands::Jq { question, sample } => handlers::jq::run(cli, question, sample.as_deref()).await,
+            Commands::Regex { description, test, attempts } => {
+                handlers::regex::run(cli, description, test.as_deref(), *attempts).await
+            }
+            Commands::Cron { when, systemd, command } => handlers::cron::run(cli, when, *systemd, command.as_deref()).await,
+            Commands::Alias { action } => handlers::alias::run(cli, action),
+            Commands::Ask { preset, input } => handlers::ask::run(cli, preset.as_deref(), input.as_deref()).await,
+            Commands::Template { action } => handlers::template::manage(cli, action).await,
+            Commands::Eval { file } => handlers::eval::run(cli, file).await,
+            Commands::Compare { prompt, models, diff } => handlers::compare::run(cli, prompt, models, *diff).await,
+            Commands::Watch { prompt, files, debounce } => handlers::watch::run(cli, prompt, files, *debounce).await,
+            Commands::Serve { http, .. } => handlers::serve::run(cli, *http).await,
+            Commands::Queue { action } => handlers::queue::run(cli, action).await,
+            Commands::Schedule { action } => handlers::schedule::run(cli, action).await,
+            Commands::Feedback { rating, comment } => handlers::history::feedback(cli, rating, comment),
+            Commands::History { action } => handlers::history::run(cli, action),
+            Commands::Gc => handlers::gc::run(cli),
+            Commands::Commands { action } => handlers::commands::run(action).await,
+            Commands::SelfUpdate { check } => handlers::self_update::run(*check).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use args::Verbosity;
+    use std::collections::HashMap;
+    use std::ffi::OsString;
+
+    fn parse(args: &[&str], defaults: &Defaults) -> Cli {
+        let matches = Cli::command().try_get_matches_from(args).unwrap();
+        let mut cli = Cli::from_arg_matches(&matches).unwrap();
+        cli.apply_defaults(defaults, &matches);
+        cli
+    }
+
+    #[test]
+    fn test_prompt_from_stdin() {
+        let cli = |args: &[&str]| parse(args, &Defaults::default());
+        assert!(cli(&["q"]).prompt_from_stdin(true));
+        assert!(cli(&["q", "-t", "review"]).prompt_from_stdin(true));
+        assert!(!cli(&["q"]).prompt_from_stdin(false));
+        // A prompt on the command line makes piped stdin context
+        assert!(!cli(&["q", "why did this fail?"]).prompt_from_stdin(true));
+        assert!(!cli(&["q", "--no-stdin"]).prompt_from_stdin(true));
+        assert!(!cli(&["q", "--audio", "note.m4a"]).prompt_from_stdin(true));
+        assert!(!cli(&["q", "--prompt-file", "-"]).prompt_from_stdin(true));
+    }
+
+    #[test]
+    fn test_read_prompt_file() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("prompt.txt");
+        std::fs::write(&path, "-v means verbose, right?\n  Explain:\n\n").unwrap();
+        assert_eq!(read_prompt_file(&path).unwrap(), "-v means verbose, right?\n  Explain:");
+        std::fs::write(&path, "\n \n").unwrap();
+        assert!(read_prompt_file(&path).unwrap_err().to_string().contains("is empty"));
+        assert!(read_prompt_file(&dir.path().join("missing")).is_err());
+    }
+
+    #[test]
+    fn test_validate_prompt() {
+        let check = |args: &[&str]| -> Result<(), String> {
+            let os_args: Vec<OsString> = args.iter().map(OsString::from).collect();
+            let matches = Cli::command().try_get_matches_from(&os_args).map_err(|e| e.to_string())?;
+            let cli = Cli::from_arg_matches(&matches).unwrap();
+            args::validate_prompt(&os_args, cli.prompt.as_deref().unwrap())
+        };
+        assert!(check(&["q", "--", "-v means verbose, right?"]).is_ok());
+        assert!(check(&["q", "--stream", "--", "--force: what does it skip?"]).is_ok());
+        assert!(check(&["q", "--", "history"]).is_ok());
+        assert!(check(&["q", "--", "--"]).is_ok());
+        // Only a subcommand's name in first position looks like a mistyped subcommand
+        assert!(check(&["q", "--stream", "what does gc do?"]).is_ok());
+        assert!(check(&["q", "-"]).unwrap_err().contains("not a valid prompt"));
+        assert!(check(&["q", "--stream", "-"]).unwrap_err().contains("after `--`"));
+    }
+
+    #[test]
+    fn test_expand_alias() {
+        let aliases = HashMap::from([
+            ("rev".to_string(), vec!["--diff".to_string(), "review this change".to_string()]),
+            ("commit".to_string(), vec!["--cmd".to_string()]),
+        ]);
+        let expand = |args: &[&str]| -> Vec<String> {
+            args::expand_alias(args.iter().map(OsString::from).collect(), &aliases)
+                .into_iter()
+                .map(|arg| arg.into_string().unwrap())
+                .collect()
+        };
+        assert_eq!(expand(&["q", "rev", "--detail", "detailed"]), ["q", "--diff", "review this change", "--detail", "detailed"]);
+        // Only the first token, and never a subcommand
+        assert_eq!(expand(&["q", "--stream", "rev"]), ["q", "--stream", "rev"]);
+        assert_eq!(expand(&["q", "commit"]), ["q", "commit"]);
+
+        let cli = parse(&expand(&["q", "rev"]).iter().map(String::as_str).collect::<Vec<_>>(), &Defaults::default());
+        assert!(cli.diff);
+        assert_eq!(cli.prompt.as_deref(), Some("review this change"));
+    }
+
+    #[test]
+    fn test_defaults_fill_unset_flags() {
+        let defaults = Defaults {
+            detail: Some(Verbosity::Detailed),
+            stream: Some(true),
+            hist: Some(true),
+            retries: Some(5),
+            ..Defaults::default()
+        };
+        let cli = parse(&["q", "hello"], &defaults);
+
+        assert_eq!(cli.verbosity, Verbosity::Detailed);
+        assert!(cli.stream);
+        assert!(cli.history);
+        assert!(!cli.directory);
+        assert_eq!(cli.max_retries, 5);
+    }
+
+    #[test]
+    fn test_command_line_overrides_defaults() {
+        let defaults = Defaults {
+            detail: Some(Verbosity::Detailed),
+            retries: Some(5),
+            ..Defaults::default()
+        };
+        let cli = parse(&["q", "--detail", "normal", "--retries", "1", "hello"], &defaults);
+
+        assert_eq!(cli.verbosity, Verbosity::Normal);
+        assert_eq!(cli.max_retries, 1);
+    }
+
+    #[test]
+    fn test_ctx_turns_on_flags() {
+        let mut cli = parse(&["q", "--ctx", "hist,here,diff", "hello"], &Defaults::default());
+        cli.apply_ctx();
+
+        assert!(cli.history);
+        assert!(cli.directory);
+        assert!(cli.diff);
+        assert!(!cli.repo);
+        assert!(!cli.system);
+        assert!(Cli::command().try_get_matches_from(["q", "--ctx", "hist,nope", "hello"]).is_err());
+    }
+}
@@ -0,0 +1,144 @@
+//! Command database bundles published with releases, so the suggestion
+//! corpus can grow between binary releases. A bundle is a
+//! JSON file of commands plus tldr pages for them, signed with the project's
+//! release key; only bundles that verify are installed into the data dir.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use ring::signature::{UnparsedPublicKey, ED25519};
+use serde::{Deserialize, Serialize};
+
+use super::{CommandError, CommandInfo, CommandResult, Example};
+
+/// Release asset holding the bundle
+pub const BUNDLE_ASSET: &str = "commands.json";
+/// Release asset holding the base64 ed25519 signature of `BUNDLE_ASSET`
+pub const SIGNATURE_ASSET: &str = "commands.json.sig";
+/// The release signing key, base64 of the raw 32-byte ed25519 public key.
+/// None until the maintainers publish one; until then no bundle is installed.
+pub const PUBLIC_KEY: Option<&str> = None;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Bundle {
+    /// Release-style version, e.g. `2026.10.1`
+    pub version: String,
+    pub commands: Vec<CommandInfo>,
+    /// tldr pages by command name, as Markdown
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub tldr: BTreeMap<String, String>,
+}
+
+impl Bundle {
+    /// The bundle in `bytes`, if `signature` is `key`'s signature of them
+    pub fn verify(bytes: &[u8], signature: &str, key: &str) -> CommandResult<Self> {
+        let key = STANDARD.decode(key.trim())
+            .map_err(|e| CommandError::Bundle(format!("malformed public key: {}", e)))?;
+        let signature = STANDARD.decode(signature.trim())
+            .map_err(|_| CommandError::Bundle("malformed signature".to_string()))?;
+        UnparsedPublicKey::new(&ED25519, key)
+            .verify(bytes, &signature)
+            .map_err(|_| CommandError::Bundle("signature doesn't match the release key; not installed".to_string()))?;
+        serde_json::from_slice(bytes).map_err(|e| CommandError::Bundle(format!("unreadable bundle: {}", e)))
+    }
+
+    /// The installed bundle, if any. It was verified before it was installed.
+    pub fn load(path: &Path) -> CommandResult<Option<Self>> {
+        let bytes = match std::fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(CommandError::Bundle(format!("couldn't read {}: {}", path.display(), e))),
+        };
+        serde_json::from_slice(&bytes)
+            .map(Some)
+            .map_err(|e| CommandError::Bundle(format!("unreadable bundle {}: {}", path.display(), e)))
+    }
+
+    /// Write verified bundle `bytes` to `path`, through a temporary file so a
+    /// failed write leaves the previous bundle in place
+    pub fn install(path: &Path, bytes: &[u8]) -> CommandResult<()> {
+        let staged = path.with_extension("json.new");
+        std::fs::write(&staged, bytes)
+            .and_then(|_| std::fs::rename(&staged, path))
+            .map_err(|e| {
+                let _ = std::fs::remove_file(&staged);
+                CommandError::Bundle(format!("couldn't install {}: {}", path.display(), e))
+            })
+    }
+
+    /// The bundle's commands, with the examples of their tldr pages added
+    pub fn into_commands(self) -> Vec<CommandInfo> {
+        let tldr = self.tldr;
+        self.commands.into_iter()
+            .map(|mut command| {
+                for example in tldr.get(&command.name).map(|page| tldr_examples(page)).unwrap_or_default() {
+                    let example = Example::any(example);
+                    if !command.examples.contains(&example) {
+                        command.examples.push(example);
+                    }
+                }
+                command
+            })
+            .collect()
+    }
+}
+
+/// The commands in a tldr page, which writes each as a line in backticks with
+/// `{{placeholders}}`
+fn tldr_examples(page: &str) -> Vec<String> {
+    page.lines()
+        .filter_map(|line| line.trim().strip_prefix('`')?.strip_suffix('`'))
+        .filter(|example| !example.is_empty())
+        .map(|example| example.replace("{{", "").replace("}}", ""))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ring::signature::{Ed25519KeyPair, KeyPair};
+    use tempfile::TempDir;
+
+    const BUNDLE: &str = r##"{
+        "version": "2026.10.1",
+        "commands": [
+            {"name": "dust", "description": "A more intuitive du", "category": "filesystem", "keywords": ["disk", "usage"]},
+            {"name": "bandwhich", "description": "Bandwidth use by process", "category": "telemetry"}
+        ],
+        "tldr": {"dust": "# dust\n\n> Show disk usage.\n\n- Show the biggest directories:\n\n`dust {{path/to/directory}}`\n"}
+    }"##;
+
+    fn signed(bytes: &[u8]) -> (String, String) {
+        let pair = Ed25519KeyPair::from_seed_unchecked(&[7; 32]).unwrap();
+        (STANDARD.encode(pair.sign(bytes)), STANDARD.encode(pair.public_key()))
+    }
+
+    #[test]
+    fn test_verify() {
+        let (signature, key) = signed(BUNDLE.as_bytes());
+        let bundle = Bundle::verify(BUNDLE.as_bytes(), &signature, &key).unwrap();
+        assert_eq!(bundle.version, "2026.10.1");
+
+        let tampered = BUNDLE.replace("dust", "rm -rf");
+        assert!(Bundle::verify(tampered.as_bytes(), &signature, &key).is_err());
+        let other_key = STANDARD.encode(Ed25519KeyPair::from_seed_unchecked(&[8; 32]).unwrap().public_key());
+        assert!(Bundle::verify(BUNDLE.as_bytes(), &signature, &other_key).is_err());
+        assert!(Bundle::verify(BUNDLE.as_bytes(), "not base64!", &key).is_err());
+    }
+
+    #[test]
+    fn test_install_and_load() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join(BUNDLE_ASSET);
+        assert!(Bundle::load(&path).unwrap().is_none());
+
+        Bundle::install(&path, BUNDLE.as_bytes()).unwrap();
+        let commands = Bundle::load(&path).unwrap().unwrap().into_commands();
+        assert_eq!(commands[0].examples, [Example::any("dust path/to/directory")]);
+        assert_eq!(commands[0].category, super::super::Category::FileSystem);
+        // Categories newer than this binary still load
+        assert_eq!(commands[1].category, super::super::Category::Other);
+        assert!(!path.with_extension("json.new").exists());
+    }
+}
@@ -1,6 +1,8 @@
 use lazy_static::lazy_static;
 use std::collections::HashMap;
-use super::{Category, CommandInfo};
+use super::bundle::Bundle;
+use super::{Category, CommandInfo, Example};
+use crate::config::paths::DataPaths;
 
 lazy_static! {
     pub static ref COMMAND_DATABASE: HashMap<String, CommandInfo> = {
@@ -14,8 +16,9 @@ lazy_static! {
                 description: "A command-line benchmarking tool that measures command execution time with statistical analysis".to_string(),
                 category: Category::Performance,
                 examples: vec![
-                    "hyperfine 'sleep 0.3'".to_string(),
-                    "hyperfine --warmup 3 'grep -R TODO ./'".to_string(),
+                    Example::shells("hyperfine 'sleep 0.3'", &["posix", "fish"]),
+                    Example::shells("hyperfine 'Start-Sleep -Milliseconds 300'", &["powershell"]),
+                    Example::any("hyperfine --warmup 3 'grep -R TODO ./'"),
                 ],
                 keywords: vec![
                     "benchmark".to_string(),
@@ -34,8 +37,8 @@ lazy_static! {
                 description: "An interactive process viewer and system monitor".to_string(),
                 category: Category::Process,
                 examples: vec![
-                    "htop".to_string(),
-                    "htop -u username".to_string(),
+                    Example::any("htop"),
+                    Example::any("htop -u username"),
                 ],
                 keywords: vec![
                     "process".to_string(),
@@ -55,8 +58,8 @@ lazy_static! {
                 description: "NCurses Disk Usage - a disk usage analyzer with an ncurses interface".to_string(),
                 category: Category::FileSystem,
                 examples: vec![
-                    "ncdu /home".to_string(),
-                    "ncdu -x /".to_string(),
+                    Example::any("ncdu /home"),
+                    Example::any("ncdu -x /"),
                 ],
                 keywords: vec![
                     "disk".to_string(),
@@ -76,8 +79,8 @@ lazy_static! {
                 description: "A network diagnostic tool that combines ping and traceroute".to_string(),
                 category: Category::Network,
                 examples: vec![
-                    "mtr google.com".to_string(),
-                    "mtr --report example.com".to_string(),
+                    Example::any("mtr google.com"),
+                    Example::any("mtr --report example.com"),
                 ],
                 keywords: vec![
                     "network".to_string(),
@@ -96,8 +99,8 @@ lazy_static! {
                 description: "A simple, fast and user-friendly alternative to find".to_string(),
                 category: Category::FileSystem,
                 examples: vec![
-                    "fd pattern".to_string(),
-                    "fd -e txt".to_string(),
+                    Example::any("fd pattern"),
+                    Example::any("fd -e txt"),
                 ],
                 keywords: vec![
                     "find".to_string(),
@@ -116,8 +119,8 @@ lazy_static! {
                 description: "An extremely fast alternative to grep that respects gitignore rules".to_string(),
                 category: Category::Development,
                 examples: vec![
-                    "rg pattern".to_string(),
-                    "rg -t rust 'fn main'".to_string(),
+                    Example::any("rg pattern"),
+                    Example::any("rg -t rust 'fn main'"),
                 ],
                 keywords: vec![
                     "search".to_string(),
@@ -136,8 +139,9 @@ lazy_static! {
                 description: "A command-line fuzzy finder".to_string(),
                 category: Category::Process,
                 examples: vec![
-                    "fzf".to_string(),
-                    "vim $(fzf)".to_string(),
+                    Example::any("fzf"),
+                    Example::shells("vim $(fzf)", &["posix"]),
+                    Example::shells("vim (fzf)", &["fish", "powershell"]),
                 ],
                 keywords: vec![
                     "search".to_string(),
@@ -152,10 +156,31 @@ lazy_static! {
     };
 }
 
+lazy_static! {
+    /// Commands from the installed bundle, by name
+    pub static ref INSTALLED_COMMANDS: HashMap<String, CommandInfo> = installed_bundle()
+        .map(|bundle| bundle.into_commands().into_iter().map(|command| (command.name.clone(), command)).collect())
+        .unwrap_or_default();
+}
+
+/// The installed bundle, if there is one that loads
+pub fn installed_bundle() -> Option<Bundle> {
+    let path = DataPaths::new().ok()?.commands_bundle();
+    Bundle::load(&path).unwrap_or_else(|e| {
+        eprintln!("Warning: {}; using the built-in command database", e);
+        None
+    })
+}
+
+/// Built-in commands and installed ones; an installed command replaces the
+/// built-in one of the same name
 pub fn get_all_commands() -> Vec<&'static CommandInfo> {
-    COMMAND_DATABASE.values().collect()
+    COMMAND_DATABASE.values()
+        .filter(|command| !INSTALLED_COMMANDS.contains_key(&command.name))
+        .chain(INSTALLED_COMMANDS.values())
+        .collect()
 }
 
 pub fn get_command(name: &str) -> Option<&'static CommandInfo> {
-    COMMAND_DATABASE.get(name)
+    INSTALLED_COMMANDS.get(name).or_else(|| COMMAND_DATABASE.get(name))
 }
@@ -3,86 +3,120 @@ use super::{CommandInfo, CommandResult};
 use super::database::get_all_commands;
 use super::Category;
 
-/// Score for a command match
-#[derive(Debug)]
-struct MatchScore {
-    command: &'static CommandInfo,
-    score: u32,
+/// Points for each way a command can match a query
+const NAME_POINTS: u32 = 100;
+const CATEGORY_POINTS: u32 = 50;
+const PATTERN_POINTS: u32 = 40;
+const KEYWORD_POINTS: u32 = 30;
+const DESCRIPTION_POINTS: u32 = 20;
+
+/// Words in a query that point at a category
+const PATTERNS: [(&str, Category); 5] = [
+    (r"(?i)\b(profile|benchmark|time)", Category::Performance),
+    (r"(?i)\b(monitor|process|cpu|memory)", Category::Process),
+    (r"(?i)\b(disk|storage|space|file)", Category::FileSystem),
+    (r"(?i)\b(network|ping|connection)", Category::Network),
+    (r"(?i)\b(develop|code|program)", Category::Development),
+];
+
+/// Why a command matched a query, part by part (`--cmd --why`)
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Breakdown {
+    /// The query is part of the command's name
+    pub name: bool,
+    /// The query names the command's category
+    pub category: bool,
+    /// The command's keywords found in the query
+    pub keywords: Vec<String>,
+    /// The query is part of the description; only counted without keyword hits
+    pub description: bool,
+    /// The word in the query that points at the command's category
+    pub pattern: Option<String>,
 }
 
-/// Find matching commands for a given query
-pub fn find_matches(query: &str) -> CommandResult<Vec<CommandInfo>> {
-    let query = query.to_lowercase();
-    let mut scores: Vec<MatchScore> = Vec::new();
-
-    // Get all commands
-    let commands = get_all_commands();
-
-    // Score each command
-    for command in commands {
-        let score = calculate_match_score(command, &query);
-        if score > 0 {
-            scores.push(MatchScore { command, score });
+impl Breakdown {
+    pub fn score(&self) -> u32 {
+        let mut score = KEYWORD_POINTS * self.keywords.len() as u32;
+        if self.name {
+            score += NAME_POINTS;
         }
+        if self.category {
+            score += CATEGORY_POINTS;
+        }
+        if self.description {
+            score += DESCRIPTION_POINTS;
+        }
+        if self.pattern.is_some() {
+            score += PATTERN_POINTS;
+        }
+        score
     }
 
-    // Sort by score in descending order
-    scores.sort_by(|a, b| b.score.cmp(&a.score));
-
-    // Take top 3 matches
-    let matches: Vec<CommandInfo> = scores
-        .into_iter()
-        .take(3)
-        .map(|ms| ms.command.clone())
-        .collect();
-
-    Ok(matches)
+    /// One line per part that scored, with its points
+    pub fn describe(&self, command: &CommandInfo) -> String {
+        let mut lines = vec![format!("Why: {} points", self.score())];
+        if self.name {
+            lines.push(format!("  +{:<4} the query is part of the name `{}`", NAME_POINTS, command.name));
+        }
+        if self.category {
+            lines.push(format!("  +{:<4} the query names its category, {}", CATEGORY_POINTS, command.category));
+        }
+        if let Some(word) = &self.pattern {
+            lines.push(format!("  +{:<4} \"{}\" suggests the {} category", PATTERN_POINTS, word, command.category));
+        }
+        for keyword in &self.keywords {
+            lines.push(format!("  +{:<4} keyword \"{}\"", KEYWORD_POINTS, keyword));
+        }
+        if self.description {
+            lines.push(format!("  +{:<4} the query is part of the description", DESCRIPTION_POINTS));
+        }
+        lines.join("\n")
+    }
 }
 
-/// Calculate how well a command matches a query
-fn calculate_match_score(command: &CommandInfo, query: &str) -> u32 {
-    let mut score = 0;
+/// Find matching commands for a given query
+pub fn find_matches(query: &str) -> CommandResult<Vec<CommandInfo>> {
+    Ok(rank(query, get_all_commands()).into_iter().cloned().collect())
+}
 
-    // Direct name match
-    if command.name.to_lowercase().contains(query) {
-        score += 100;
-    }
+/// The top 3 of `commands` for `query`, best first
+pub fn rank<'a>(query: &str, commands: impl IntoIterator<Item = &'a CommandInfo>) -> Vec<&'a CommandInfo> {
+    explain(query, commands).into_iter().map(|(command, _)| command).collect()
+}
 
-    // Category match
-    if command.category.to_string().to_lowercase().contains(query) {
-        score += 50;
-    }
+/// `rank`, with why each command matched
+pub fn explain<'a>(query: &str, commands: impl IntoIterator<Item = &'a CommandInfo>) -> Vec<(&'a CommandInfo, Breakdown)> {
+    let query = query.to_lowercase();
+    let mut scores: Vec<(&CommandInfo, Breakdown)> = commands.into_iter()
+        .map(|command| (command, breakdown(command, &query)))
+        .filter(|(_, breakdown)| breakdown.score() > 0)
+        .collect();
 
-    // Keyword matches
-    for keyword in &command.keywords {
-        if query.contains(&keyword.to_lowercase()) {
-            score += 30;
-        }
-    }
+    // Sort by score in descending order, breaking ties by name for stable output
+    scores.sort_by(|(a, a_score), (b, b_score)| b_score.score().cmp(&a_score.score()).then_with(|| a.name.cmp(&b.name)));
 
-    // Description match
-    if command.description.to_lowercase().contains(query) {
-        score += 20;
-    }
+    // Take top 3 matches
+    scores.truncate(3);
+    scores
+}
 
-    // Pattern matching for common queries
-    let patterns = [
-        (r"(?i)profile|benchmark|time", Category::Performance),
-        (r"(?i)monitor|process|cpu|memory", Category::Process),
-        (r"(?i)disk|storage|space|file", Category::FileSystem),
-        (r"(?i)network|ping|connection", Category::Network),
-        (r"(?i)develop|code|program", Category::Development),
-    ];
-
-    for (pattern, category) in patterns.iter() {
-        if let Ok(re) = Regex::new(pattern) {
-            if re.is_match(query) && command.category == *category {
-                score += 40;
-            }
-        }
+/// How `command` matches a lowercase `query`
+fn breakdown(command: &CommandInfo, query: &str) -> Breakdown {
+    let keywords: Vec<String> = command.keywords.iter()
+        .filter(|keyword| query.contains(&keyword.to_lowercase()))
+        .cloned()
+        .collect();
+    let pattern = PATTERNS.iter()
+        .filter(|(_, category)| command.category == *category)
+        .find_map(|(pattern, _)| Regex::new(pattern).ok()?.find(query).map(|word| word.as_str().to_string()));
+
+    Breakdown {
+        name: command.name.to_lowercase().contains(query),
+        category: query.contains(&command.category.to_string().to_lowercase()),
+        description: keywords.is_empty() && command.description.to_lowercase().contains(query),
+        keywords,
+        pattern,
     }
-
-    score
 }
 
 #[cfg(test)]
@@ -90,6 +124,10 @@ mod tests {
     use super::*;
     use super::super::database::COMMAND_DATABASE;
 
+    fn calculate_match_score(command: &CommandInfo, query: &str) -> u32 {
+        breakdown(command, query).score()
+    }
+
     #[test]
     fn test_find_matches_performance() {
         let matches = find_matches("tool to profile execution time").unwrap();
@@ -131,4 +169,25 @@ mod tests {
         assert!(score1 > score2); // Direct name match should score higher
         assert!(score2 > score3); // Category match should score higher than keyword
     }
+
+    #[test]
+    fn test_breakdown() {
+        let command = COMMAND_DATABASE.get("hyperfine").unwrap();
+        let breakdown = breakdown(command, "benchmark for performance");
+        assert_eq!(breakdown, Breakdown {
+            name: false,
+            category: true,
+            keywords: vec!["benchmark".to_string(), "performance".to_string()],
+            description: false,
+            pattern: Some("benchmark".to_string()),
+        });
+        assert_eq!(
+            breakdown.describe(command),
+            "Why: 150 points\n\
+             \x20 +50   the query names its category, Performance\n\
+             \x20 +40   \"benchmark\" suggests the Performance category\n\
+             \x20 +30   keyword \"benchmark\"\n\
+             \x20 +30   keyword \"performance\""
+        );
+    }
 }
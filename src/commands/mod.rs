@@ -1,8 +1,12 @@
+pub mod bundle;
 pub mod database;
 pub mod matcher;
 pub mod suggest;
 
 use colored::Colorize;
+use serde::{Deserialize, Serialize};
+
+use crate::context::environment;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -13,13 +17,17 @@ pub enum CommandError {
     #[error("No matching commands found")]
     NoMatch,
 
+    #[error("Command database bundle: {0}")]
+    Bundle(String),
+
     #[error("Other error: {0}")]
     Other(String),
 }
 
 pub type CommandResult<T> = Result<T, CommandError>;
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Category {
     System,
     Network,
@@ -27,6 +35,8 @@ pub enum Category {
     Process,
     Performance,
     Development,
+    /// Also any category this version doesn't know
+    #[serde(other)]
     Other,
 }
 
@@ -44,17 +54,120 @@ impl std::fmt::Display for Category {
     }
 }
 
-#[derive(Debug, Clone)]
+/// Shells that run POSIX sh syntax, tagged `posix` in examples
+const POSIX_SHELLS: &[&str] = &["sh", "bash", "zsh", "dash", "ksh", "ash"];
+
+/// An example command, and where it works. Untagged examples work anywhere.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(from = "ExampleSpec")]
+pub struct Example {
+    pub command: String,
+    /// Shells whose syntax it uses: `posix`, `fish`, `powershell`, `cmd`, or a
+    /// shell's own name
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub shells: Vec<String>,
+    /// Systems it runs on, as `std::env::consts::OS` names them, e.g. `macos`
+    /// for BSD flags and `linux` for GNU ones
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub os: Vec<String>,
+}
+
+/// An example as bundles write it: a plain command, or one with tags
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ExampleSpec {
+    Any(String),
+    Tagged {
+        command: String,
+        #[serde(default)]
+        shells: Vec<String>,
+        #[serde(default)]
+        os: Vec<String>,
+    },
+}
+
+impl From<ExampleSpec> for Example {
+    fn from(spec: ExampleSpec) -> Self {
+        match spec {
+            ExampleSpec::Any(command) => Example::any(command),
+            ExampleSpec::Tagged { command, shells, os } => Example { command, shells, os },
+        }
+    }
+}
+
+impl Example {
+    /// An example that works in any shell, anywhere
+    pub fn any(command: impl Into<String>) -> Self {
+        Self { command: command.into(), shells: Vec::new(), os: Vec::new() }
+    }
+
+    /// An example in the syntax of these shells
+    pub fn shells(command: impl Into<String>, shells: &[&str]) -> Self {
+        Self { shells: shells.iter().map(|shell| shell.to_string()).collect(), ..Self::any(command) }
+    }
+
+    /// An example for these systems
+    pub fn os(command: impl Into<String>, os: &[&str]) -> Self {
+        Self { os: os.iter().map(|os| os.to_string()).collect(), ..Self::any(command) }
+    }
+
+    /// Whether it works in `platform`. An unknown shell accepts any syntax.
+    pub fn fits(&self, platform: &Platform) -> bool {
+        let shell_fits = match &platform.shell {
+            Some(shell) => self.shells.is_empty() || self.shells.iter().any(|tag| {
+                tag == shell || (tag == "posix" && POSIX_SHELLS.contains(&shell.as_str()))
+            }),
+            None => true,
+        };
+        shell_fits && (self.os.is_empty() || self.os.iter().any(|os| os == &platform.os))
+    }
+
+    /// `[powershell]`, `[macos]`, or nothing for untagged examples
+    fn tags(&self) -> String {
+        let tags: Vec<&str> = self.shells.iter().chain(&self.os).map(String::as_str).collect();
+        if tags.is_empty() { String::new() } else { format!("[{}]", tags.join(", ")) }
+    }
+}
+
+/// The shell and system examples are shown for
+#[derive(Debug, Clone, PartialEq)]
+pub struct Platform {
+    /// Lowercase shell name, with `pwsh` as `powershell`
+    pub shell: Option<String>,
+    pub os: String,
+}
+
+impl Platform {
+    /// The user's shell and system
+    pub fn detect() -> Self {
+        Self::new(environment::shell().as_deref(), std::env::consts::OS)
+    }
+
+    pub fn new(shell: Option<&str>, os: &str) -> Self {
+        let shell = shell.map(|shell| {
+            let shell = shell.to_lowercase();
+            let shell = shell.strip_suffix(".exe").unwrap_or(&shell);
+            if shell == "pwsh" { "powershell".to_string() } else { shell.to_string() }
+        });
+        Self { shell, os: os.to_string() }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CommandInfo {
     pub name: String,
     pub description: String,
     pub category: Category,
-    pub examples: Vec<String>,
+    #[serde(default)]
+    pub examples: Vec<Example>,
+    #[serde(default)]
     pub keywords: Vec<String>,
 }
 
 impl CommandInfo {
-    pub fn format_suggestion(&self) -> String {
+    /// The suggestion with the examples that fit `platform`, or with every
+    /// example and its tags when there's no platform to fit
+    pub fn format_suggestion(&self, platform: Option<&Platform>) -> String {
         let mut output = String::new();
 
         // Tool name in green
@@ -67,13 +180,70 @@ impl CommandInfo {
         output.push_str(&format!("{}\n", self.description));
         
         // Examples in yellow
-        if !self.examples.is_empty() {
+        let shown: Vec<&Example> = self.examples.iter()
+            .filter(|example| platform.is_none_or(|platform| example.fits(platform)))
+            .collect();
+        if !shown.is_empty() {
             output.push_str("\nExamples:\n");
-            for example in &self.examples {
-                output.push_str(&format!("  {}\n", example.yellow()));
+            for example in &shown {
+                let tags = if platform.is_none() { example.tags() } else { String::new() };
+                if tags.is_empty() {
+                    output.push_str(&format!("  {}\n", example.command.yellow()));
+                } else {
+                    output.push_str(&format!("  {} {}\n", example.command.yellow(), tags.dimmed()));
+                }
             }
         }
+        let hidden = self.examples.len() - shown.len();
+        if hidden > 0 {
+            output.push_str(&format!(
+                "{}\n",
+                format!("  ({} more for other shells or systems; --all-examples shows them)", hidden).dimmed()
+            ));
+        }
 
         output
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_example_fits() {
+        let posix = Example::shells("vim $(fzf)", &["posix"]);
+        let fish = Example::shells("vim (fzf)", &["fish"]);
+        let bsd = Example::os("sed -i '' s/a/b/ file", &["macos"]);
+        let zsh_on_mac = Platform::new(Some("zsh"), "macos");
+        let pwsh_on_linux = Platform::new(Some("pwsh.exe"), "linux");
+        assert!(posix.fits(&zsh_on_mac) && !fish.fits(&zsh_on_mac) && bsd.fits(&zsh_on_mac));
+        assert!(!posix.fits(&pwsh_on_linux) && !bsd.fits(&pwsh_on_linux));
+        assert!(Example::any("fzf").fits(&pwsh_on_linux));
+        assert!(fish.fits(&Platform::new(None, "linux")));
+    }
+
+    #[test]
+    fn test_examples_load_plain_or_tagged() {
+        let examples: Vec<Example> = serde_json::from_str(
+            r#"["fzf", {"command": "vim (fzf)", "shells": ["fish"]}, {"command": "du -sh", "os": ["linux"]}]"#
+        ).unwrap();
+        assert_eq!(examples, [Example::any("fzf"), Example::shells("vim (fzf)", &["fish"]), Example::os("du -sh", &["linux"])]);
+    }
+
+    #[test]
+    fn test_format_suggestion_filters_examples() {
+        colored::control::set_override(false);
+        let command = CommandInfo {
+            name: "fzf".to_string(),
+            description: "A command-line fuzzy finder".to_string(),
+            category: Category::Process,
+            examples: vec![Example::any("fzf"), Example::shells("vim $(fzf)", &["posix"]), Example::shells("vim (fzf)", &["fish"])],
+            keywords: Vec::new(),
+        };
+        let fish = command.format_suggestion(Some(&Platform::new(Some("fish"), "linux")));
+        assert!(fish.ends_with("Examples:\n  fzf\n  vim (fzf)\n  (1 more for other shells or systems; --all-examples shows them)\n"));
+        let all = command.format_suggestion(None);
+        assert!(all.ends_with("Examples:\n  fzf\n  vim $(fzf) [posix]\n  vim (fzf) [fish]\n"));
+    }
+}
@@ -1,9 +1,16 @@
 use colored::Colorize;
-use super::{CommandError, CommandInfo, CommandResult};
-use super::matcher::find_matches;
+use super::{CommandError, CommandInfo, CommandResult, Platform};
+use super::database::get_all_commands;
+use super::matcher::explain;
 
-/// Format a list of command suggestions into a colored string
-pub fn format_suggestions(commands: &[CommandInfo]) -> String {
+/// Format a list of command suggestions into a colored string, with the
+/// examples that fit `platform` (every example when it's `None`)
+pub fn format_suggestions(commands: &[CommandInfo], platform: Option<&Platform>) -> String {
+    format_list(&commands.iter().map(|command| (command, None)).collect::<Vec<_>>(), platform)
+}
+
+/// Suggestions, each followed by its note (why it matched) when it has one
+fn format_list(commands: &[(&CommandInfo, Option<String>)], platform: Option<&Platform>) -> String {
     if commands.is_empty() {
         return format!("{}", "No matching commands found.".red());
     }
@@ -16,8 +23,11 @@ pub fn format_suggestions(commands: &[CommandInfo]) -> String {
         output.push_str(&format!("Found {} relevant tools:\n\n", commands.len()));
     }
 
-    for (i, command) in commands.iter().enumerate() {
-        output.push_str(&command.format_suggestion());
+    for (i, (command, note)) in commands.iter().enumerate() {
+        output.push_str(&command.format_suggestion(platform));
+        if let Some(note) = note {
+            output.push_str(&format!("\n{}\n", note.dimmed()));
+        }
         if i < commands.len() - 1 {
             output.push_str("\n---\n\n");
         }
@@ -26,21 +36,26 @@ pub fn format_suggestions(commands: &[CommandInfo]) -> String {
     output
 }
 
-/// Process a command query and return formatted suggestions
-pub async fn process_command_query(query: &str) -> CommandResult<String> {
-    let matches = find_matches(query)?;
+/// Process a command query and return formatted suggestions, with the
+/// scoring behind each one when `why` is set, and only the examples that
+/// fit `platform` when there is one
+pub async fn process_command_query(query: &str, why: bool, platform: Option<&Platform>) -> CommandResult<String> {
+    let matches = explain(query, get_all_commands());
     
     if matches.is_empty() {
         return Err(CommandError::NoMatch);
     }
 
-    Ok(format_suggestions(&matches))
+    let suggestions: Vec<(&CommandInfo, Option<String>)> = matches.iter()
+        .map(|(command, breakdown)| (*command, why.then(|| breakdown.describe(command))))
+        .collect();
+    Ok(format_list(&suggestions, platform))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::commands::Category;
+    use crate::commands::{Category, Example};
 
     #[test]
     fn test_format_suggestions() {
@@ -48,11 +63,11 @@ mod tests {
             name: "test".to_string(),
             description: "A test command".to_string(),
             category: Category::Development,
-            examples: vec!["test example".to_string()],
+            examples: vec![Example::any("test example")],
             keywords: vec!["test".to_string()],
         };
 
-        let suggestions = format_suggestions(&[command]);
+        let suggestions = format_suggestions(&[command], None);
         assert!(suggestions.contains("test"));
         assert!(suggestions.contains("A test command"));
         assert!(suggestions.contains("Development"));
@@ -61,21 +76,25 @@ mod tests {
 
     #[test]
     fn test_format_empty_suggestions() {
-        let suggestions = format_suggestions(&[]);
+        let suggestions = format_suggestions(&[], None);
         assert!(suggestions.contains("No matching commands found"));
     }
 
     #[tokio::test]
     async fn test_process_command_query() {
-        let result = process_command_query("profile execution time").await;
+        let result = process_command_query("profile execution time", false, None).await;
         assert!(result.is_ok());
         let output = result.unwrap();
         assert!(output.contains("hyperfine"));
+        assert!(!output.contains("Why:"));
+
+        let output = process_command_query("profile execution time", true, None).await.unwrap();
+        assert!(output.contains("\"profile\" suggests the Performance category"));
     }
 
     #[tokio::test]
     async fn test_process_invalid_query() {
-        let result = process_command_query("xyzabc123").await;
+        let result = process_command_query("xyzabc123", false, None).await;
         assert!(matches!(result, Err(CommandError::NoMatch)));
     }
 }
@@ -0,0 +1,168 @@
+//! Config bundles a team can share: `q config export --bundle` writes the
+//! config cut down to settings, defaults, presets, and the other sections
+//! worth standardizing, and `q config import` merges one into the local config.
+//! API keys and credential paths are never exported, and a bundle that
+//! carries them is refused rather than imported.
+
+use toml::{Table, Value};
+
+use super::types::Config;
+use super::validate;
+use crate::utils::errors::QError;
+
+/// Top-level sections a bundle can carry, in the order they are written
+const SECTIONS: &[&str] = &["settings", "defaults", "presets", "aliases", "context", "pr", "commit", "consensus", "web", "profiles", "vertex"];
+/// Keys, at any depth, that hold secrets or point at them
+const SECRET_KEYS: &[&str] = &["api_keys", "credentials"];
+/// Settings that only make sense on one machine
+const PERSONAL_SETTINGS: &[&str] = &["default_profile"];
+
+/// The shareable part of `config` as TOML
+pub fn export(config: &Config) -> Result<String, QError> {
+    let Value::Table(mut document) = Value::try_from(config).map_err(serialize_error)? else {
+        unreachable!("config serializes to a table");
+    };
+    let mut bundle = Table::new();
+    for section in SECTIONS {
+        if let Some(mut value) = document.remove(*section) {
+            strip_secrets(&mut value);
+            bundle.insert(section.to_string(), value);
+        }
+    }
+    if let Some(Value::Table(settings)) = bundle.get_mut("settings") {
+        settings.retain(|key, _| !PERSONAL_SETTINGS.contains(&key));
+    }
+    toml::to_string_pretty(&bundle).map_err(serialize_error)
+}
+
+/// `config` with the bundle in `text` merged over it, and the sections the
+/// bundle set. Tables merge key by key; anything else in the bundle wins.
+pub fn import(config: &Config, text: &str) -> Result<(Config, Vec<String>), QError> {
+    let bundle: Table = text.parse()
+        .map_err(|e| QError::Config(format!("Invalid bundle: {}", e)))?;
+    if let Some(section) = bundle.keys().find(|key| !SECTIONS.contains(&key.as_str())) {
+        return Err(QError::Config(format!("[{}] can't be imported from a bundle", section)));
+    }
+    if let Some(key) = bundle.values().find_map(find_secret) {
+        return Err(QError::Config(format!(
+            "The bundle contains '{}'; bundles can't carry secrets. Add keys with 'q set-key'.", key
+        )));
+    }
+
+    let Value::Table(mut document) = Value::try_from(config).map_err(serialize_error)? else {
+        unreachable!("config serializes to a table");
+    };
+    let sections: Vec<String> = SECTIONS.iter()
+        .filter(|section| bundle.contains_key(**section))
+        .map(|section| section.to_string())
+        .collect();
+    merge(&mut document, bundle);
+
+    let contents = toml::to_string(&document).map_err(serialize_error)?;
+    if let Some(error) = validate::validate_str(&contents)
+        .into_iter()
+        .find(|d| d.severity == validate::Severity::Error)
+    {
+        return Err(QError::Config(format!("Invalid bundle: {}", error.message)));
+    }
+    let merged = toml::from_str(&contents).map_err(|e| QError::Config(format!("Invalid bundle: {}", e)))?;
+    Ok((merged, sections))
+}
+
+fn merge(into: &mut Table, from: Table) {
+    for (key, value) in from {
+        match (into.get_mut(&key), value) {
+            (Some(Value::Table(existing)), Value::Table(table)) => merge(existing, table),
+            (_, value) => {
+                into.insert(key, value);
+            }
+        }
+    }
+}
+
+fn strip_secrets(value: &mut Value) {
+    if let Value::Table(table) = value {
+        table.retain(|key, _| !SECRET_KEYS.contains(&key));
+        table.iter_mut().for_each(|(_, value)| strip_secrets(value));
+    }
+}
+
+fn find_secret(value: &Value) -> Option<&str> {
+    let Value::Table(table) = value else { return None };
+    table.iter().find_map(|(key, value)| {
+        if SECRET_KEYS.contains(&key.as_str()) {
+            Some(key.as_str())
+        } else {
+            find_secret(value)
+        }
+    })
+}
+
+fn serialize_error(e: impl std::fmt::Display) -> QError {
+    QError::Config(format!("Failed to serialize config: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+[api_keys]
+openai = "sk-secret"
+
+[settings]
+default_provider = "openai"
+default_profile = "work"
+temperature = 0.2
+
+[profiles.work]
+default_provider = "gemini"
+
+[profiles.work.api_keys]
+gemini = "AIza-secret"
+
+[vertex]
+project = "acme"
+credentials = "/home/me/sa.json"
+
+[presets.sql]
+system_prompt = "Write SQL"
+
+[history]
+encrypt = true
+"#;
+
+    #[test]
+    fn test_export_leaves_out_secrets() {
+        let config: Config = toml::from_str(CONFIG).unwrap();
+        let bundle = export(&config).unwrap();
+
+        assert!(!bundle.contains("secret") && !bundle.contains("sa.json"));
+        assert!(!bundle.contains("default_profile") && !bundle.contains("encrypt"));
+        assert!(bundle.contains("temperature = 0.2") && bundle.contains("system_prompt = \"Write SQL\""));
+        assert!(bundle.contains("[profiles.work]") && bundle.contains("project = \"acme\""));
+    }
+
+    #[test]
+    fn test_import_round_trip() {
+        let team: Config = toml::from_str(CONFIG).unwrap();
+        let mine: Config = toml::from_str("[api_keys]\nopenai = \"sk-mine\"\n\n[presets.commit]\nmodel = \"gpt-4o\"\n").unwrap();
+
+        let (merged, sections) = import(&mine, &export(&team).unwrap()).unwrap();
+        assert!(sections.contains(&"presets".to_string()));
+        assert_eq!(merged.settings.temperature, 0.2);
+        assert_eq!(merged.presets.len(), 2);
+        assert!(merged.api_keys.openai.is_some());
+        assert_eq!(merged.settings.default_profile, None);
+    }
+
+    #[test]
+    fn test_import_refuses_secrets_and_unknown_sections() {
+        let config = Config::default();
+        let error = import(&config, "[profiles.ci.api_keys]\nopenai = \"sk-1\"\n").unwrap_err();
+        assert!(error.to_string().contains("'api_keys'"));
+        assert!(import(&config, "[api_keys]\nopenai = \"sk-1\"\n").is_err());
+        assert!(import(&config, "[history]\nencrypt = false\n").unwrap_err().to_string().contains("[history]"));
+        assert!(import(&config, "[settings]\ntemperature = \"hot\"\n").is_err());
+    }
+}
@@ -0,0 +1,58 @@
+//! The key that encrypts stored history when `[history] encrypt` is on:
+//! `Q_HISTORY_KEY` when set, otherwise one kept in the OS keyring (Keychain,
+//! Credential Manager, or Secret Service) and created on first use.
+//! The key is never written to the config file or printed.
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+use crate::core::crypto::{Cipher, KEY_LEN};
+use crate::utils::errors::QError;
+
+/// Base64 of a 32-byte key, for machines without a keyring (servers, CI)
+pub const KEY_ENV_VAR: &str = "Q_HISTORY_KEY";
+const KEYRING_SERVICE: &str = "q";
+const KEYRING_USER: &str = "history-encryption-key";
+
+/// The history key from the environment or the keyring, creating one in the
+/// keyring if there is none yet
+pub fn history_key() -> Result<[u8; KEY_LEN], QError> {
+    if let Ok(encoded) = std::env::var(KEY_ENV_VAR) {
+        return decode(&encoded)
+            .ok_or_else(|| QError::Config(format!("{} must be base64 of a 32-byte key", KEY_ENV_VAR)));
+    }
+
+    let entry = keyring::Entry::new(KEYRING_SERVICE, KEYRING_USER).map_err(keyring_error)?;
+    match entry.get_password() {
+        Ok(encoded) => decode(&encoded)
+            .ok_or_else(|| QError::Config("The history key in the OS keyring is damaged".to_string())),
+        Err(keyring::Error::NoEntry) => {
+            let key = Cipher::generate_key();
+            entry.set_password(&STANDARD.encode(key)).map_err(keyring_error)?;
+            Ok(key)
+        }
+        Err(e) => Err(keyring_error(e)),
+    }
+}
+
+fn decode(encoded: &str) -> Option<[u8; KEY_LEN]> {
+    STANDARD.decode(encoded.trim()).ok()?.try_into().ok()
+}
+
+fn keyring_error(e: keyring::Error) -> QError {
+    QError::Config(format!(
+        "Couldn't use the OS keyring for the history encryption key ({}); set {} to a base64 32-byte key instead",
+        e, KEY_ENV_VAR
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(decode(&format!("{}\n", STANDARD.encode([1u8; KEY_LEN]))), Some([1; KEY_LEN]));
+        assert_eq!(decode(&STANDARD.encode([1u8; 16])), None);
+        assert_eq!(decode("not base64!"), None);
+    }
+}
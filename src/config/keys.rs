@@ -0,0 +1,153 @@
+//! Offline checks that an API key looks like one its provider issues.
+//!
+//! A key that can't be right (too short, the wrong family, an ID pasted by
+//! mistake) is an error. One that is merely unfamiliar only earns a warning,
+//! since providers add formats over time (OpenAI's `sk-proj-` and
+//! `sk-svcacct-` keys, for example).
+
+use super::types::Provider;
+
+/// Format rules for one provider's keys
+pub trait KeyValidator {
+    /// `Err` for a key that can't be valid, `Ok(Some(warning))` for one in
+    /// an unfamiliar format
+    fn check(&self, key: &str) -> Result<Option<String>, String>;
+}
+
+/// The validator for `provider`'s keys
+pub fn validator(provider: Provider) -> &'static dyn KeyValidator {
+    match provider {
+        Provider::OpenAI => &OpenAIKeys,
+        Provider::Gemini => &GeminiKeys,
+        Provider::OpenRouter => &OpenRouterKeys,
+        Provider::Groq => &GroqKeys,
+        Provider::Mistral => &MistralKeys,
+    }
+}
+
+/// Check `key` against `provider`'s formats, returning a warning for unfamiliar ones
+pub fn validate_api_key(provider: Provider, key: &str) -> Result<Option<String>, String> {
+    validator(provider).check(key)
+}
+
+/// Rules every provider shares: no whitespace and a minimum length
+fn check_common(name: &str, key: &str, min_len: usize) -> Result<(), String> {
+    if key.chars().any(char::is_whitespace) {
+        return Err(format!("{} API key contains whitespace", name));
+    }
+    if key.len() < min_len {
+        return Err(format!("{} API key is too short", name));
+    }
+    Ok(())
+}
+
+fn unfamiliar(name: &str) -> Option<String> {
+    Some(format!("This doesn't look like a {} API key format q knows; saving it anyway", name))
+}
+
+/// `sk-proj-` (project), `sk-svcacct-` (service account), `sk-admin-`, and
+/// legacy user keys (`sk-` or `sk-None-`)
+pub struct OpenAIKeys;
+
+impl KeyValidator for OpenAIKeys {
+    fn check(&self, key: &str) -> Result<Option<String>, String> {
+        if key.starts_with("org-") || key.starts_with("proj_") {
+            return Err("That is an OpenAI organization or project ID, not an API key".to_string());
+        }
+        if !key.starts_with("sk-") {
+            return Err("OpenAI API key must start with 'sk-'".to_string());
+        }
+        check_common("OpenAI", key, 20)?;
+
+        if key.starts_with("sk-admin-") {
+            return Ok(Some("OpenAI admin keys manage the organization and can't run queries".to_string()));
+        }
+        let known = ["sk-proj-", "sk-svcacct-", "sk-None-"].iter().any(|prefix| key.starts_with(prefix))
+            // Legacy user keys: `sk-` and 48 letters and digits
+            || (key.len() == 51 && key[3..].chars().all(|c| c.is_ascii_alphanumeric()));
+        Ok(if known { None } else { unfamiliar("OpenAI") })
+    }
+}
+
+/// Google API keys: `AIza` and 35 more characters
+pub struct GeminiKeys;
+
+impl KeyValidator for GeminiKeys {
+    fn check(&self, key: &str) -> Result<Option<String>, String> {
+        check_common("Gemini", key, 20)?;
+        Ok(if key.starts_with("AIza") && key.len() == 39 { None } else { unfamiliar("Gemini") })
+    }
+}
+
+pub struct OpenRouterKeys;
+
+impl KeyValidator for OpenRouterKeys {
+    fn check(&self, key: &str) -> Result<Option<String>, String> {
+        if !key.starts_with("sk-or-") {
+            return Err("OpenRouter API key must start with 'sk-or-'".to_string());
+        }
+        check_common("OpenRouter", key, 20)?;
+        Ok(if key.starts_with("sk-or-v1-") { None } else { unfamiliar("OpenRouter") })
+    }
+}
+
+pub struct GroqKeys;
+
+impl KeyValidator for GroqKeys {
+    fn check(&self, key: &str) -> Result<Option<String>, String> {
+        if !key.starts_with("gsk_") {
+            return Err("Groq API key must start with 'gsk_'".to_string());
+        }
+        check_common("Groq", key, 20)?;
+        Ok(None)
+    }
+}
+
+/// 32 letters and digits
+pub struct MistralKeys;
+
+impl KeyValidator for MistralKeys {
+    fn check(&self, key: &str) -> Result<Option<String>, String> {
+        check_common("Mistral", key, 32)?;
+        let known = key.len() == 32 && key.chars().all(|c| c.is_ascii_alphanumeric());
+        Ok(if known { None } else { unfamiliar("Mistral") })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_openai_formats() {
+        let project = format!("sk-proj-{}", "a".repeat(156));
+        assert_eq!(validate_api_key(Provider::OpenAI, &project), Ok(None));
+        let service = format!("sk-svcacct-{}", "b".repeat(100));
+        assert_eq!(validate_api_key(Provider::OpenAI, &service), Ok(None));
+        let legacy = format!("sk-{}", "c1".repeat(24));
+        assert_eq!(validate_api_key(Provider::OpenAI, &legacy), Ok(None));
+
+        let admin = format!("sk-admin-{}", "d".repeat(60));
+        assert!(validate_api_key(Provider::OpenAI, &admin).unwrap().unwrap().contains("can't run queries"));
+        let unusual = format!("sk-new_{}", "e".repeat(30));
+        assert!(validate_api_key(Provider::OpenAI, &unusual).unwrap().is_some());
+
+        assert_eq!(validate_api_key(Provider::OpenAI, "invalid-key").unwrap_err(), "OpenAI API key must start with 'sk-'");
+        assert!(validate_api_key(Provider::OpenAI, "org-abc123def456ghi789").unwrap_err().contains("organization"));
+        assert_eq!(validate_api_key(Provider::OpenAI, "sk-short").unwrap_err(), "OpenAI API key is too short");
+    }
+
+    #[test]
+    fn test_other_providers() {
+        let gemini = format!("AIza{}", "x".repeat(35));
+        assert_eq!(validate_api_key(Provider::Gemini, &gemini), Ok(None));
+        assert!(validate_api_key(Provider::Gemini, &"y".repeat(40)).unwrap().is_some());
+        assert_eq!(validate_api_key(Provider::Gemini, "short").unwrap_err(), "Gemini API key is too short");
+
+        let openrouter = format!("sk-or-v1-{}", "0".repeat(64));
+        assert_eq!(validate_api_key(Provider::OpenRouter, &openrouter), Ok(None));
+        assert!(validate_api_key(Provider::Groq, "sk-not-a-groq-key-at-all").is_err());
+        assert_eq!(validate_api_key(Provider::Mistral, &"M".repeat(32)), Ok(None));
+        assert!(validate_api_key(Provider::Mistral, &format!("{} ", "M".repeat(32))).unwrap_err().contains("whitespace"));
+    }
+}
@@ -1,14 +1,51 @@
+pub mod bundle;
+pub mod encryption;
+pub mod keys;
 pub mod paths;
+pub mod template;
 pub mod types;
+pub mod validate;
 
+use std::collections::HashMap;
+use std::fmt;
 use std::fs;
+use colored::*;
+use crate::cli::args::ContextFlag;
+use crate::core::{CacheMode, StreamRecovery};
 use crate::utils::errors::QError;
 use paths::ConfigPaths;
-use types::{Config, Provider};
+use types::{ApiKeys, CommitConfig, ConsensusConfig, Config, ContextSettings, Defaults, HistorySettings, KeyList, Preset, Profile, ProjectConfig, PrConfig, Provider, RetentionSettings, Schedule, VertexConfig, WebConfig};
+
+/// Where a resolved API key came from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KeySource {
+    Env,
+    Config,
+}
+
+impl fmt::Display for KeySource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeySource::Env => write!(f, "env"),
+            KeySource::Config => write!(f, "config"),
+        }
+    }
+}
+
+/// An API key together with the place it was found
+#[derive(Debug, Clone)]
+pub struct ResolvedKey {
+    pub key: String,
+    pub source: KeySource,
+}
 
 pub struct ConfigManager {
     paths: ConfigPaths,
     config: Config,
+    /// Profile selected with `--profile`, overriding `settings.default_profile`
+    profile: Option<String>,
+    /// `.q.toml` from the working directory or one of its parents
+    project: Option<ProjectConfig>,
 }
 
 impl ConfigManager {
@@ -17,23 +54,253 @@ impl ConfigManager {
         // Ensure the config directory exists immediately upon creation
         paths.ensure_config_dir()?;
         let config = Self::load_or_create_config(&paths, verbose)?;
-        
-        Ok(Self { paths, config })
+        let project = match std::env::current_dir() {
+            Ok(dir) => Self::load_project_config(&dir, verbose),
+            Err(_) => None,
+        };
+
+        Ok(Self { paths, config, profile: None, project })
+    }
+
+    /// Flag defaults from the `[defaults]` section
+    pub fn defaults(&self) -> &Defaults {
+        &self.config.defaults
+    }
+
+    /// Select a profile for this invocation (the `--profile` flag)
+    pub fn with_profile(mut self, profile: Option<&str>) -> Self {
+        if let Some(profile) = profile {
+            self.profile = Some(profile.to_string());
+        }
+        self
+    }
+
+    /// The profile in effect: `--profile`, then `settings.default_profile`
+    pub fn active_profile(&self) -> Option<&str> {
+        self.profile.as_deref()
+            .or(self.config.settings.default_profile.as_deref())
+    }
+
+    /// Error if the active profile is not defined in the config
+    pub fn ensure_profile_exists(&self) -> Result<(), QError> {
+        match self.active_profile() {
+            Some(name) if !self.config.profiles.contains_key(name) => Err(QError::Config(format!(
+                "Unknown profile '{}'. Create it with 'q --profile {} set-key <provider>'",
+                name, name
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn profile_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.config.profiles.keys().map(String::as_str).collect();
+        names.sort();
+        names
+    }
+
+    /// Make `name` the default profile, or clear it with `None`
+    pub fn set_default_profile(&mut self, name: Option<&str>) -> Result<(), QError> {
+        if let Some(name) = name {
+            if !self.config.profiles.contains_key(name) {
+                return Err(QError::Config(format!("Unknown profile '{}'", name)));
+            }
+        }
+        self.config.settings.default_profile = name.map(String::from);
+        Self::save_config(&self.paths, &self.config)
+    }
+
+    fn profile(&self) -> Option<&Profile> {
+        self.active_profile().and_then(|name| self.config.profiles.get(name))
+    }
+
+    /// The profile to write to, created on first use
+    fn profile_mut(&mut self) -> Option<&mut Profile> {
+        let name = self.active_profile()?.to_string();
+        Some(self.config.profiles.entry(name).or_default())
+    }
+
+    /// Keys for the active profile, or the top-level keys when none is active.
+    /// Profiles never fall back to top-level keys to avoid billing the wrong account.
+    fn api_keys(&self) -> Option<&ApiKeys> {
+        match self.active_profile() {
+            Some(_) => self.profile().map(|profile| &profile.api_keys),
+            None => Some(&self.config.api_keys),
+        }
+    }
+
+    /// Vertex AI settings for Gemini. Like keys, a profile's settings never
+    /// fall back to the top-level ones.
+    pub fn vertex(&self) -> Option<&VertexConfig> {
+        match self.active_profile() {
+            Some(_) => self.profile().and_then(|profile| profile.vertex.as_ref()),
+            None => self.config.vertex.as_ref(),
+        }
+    }
+
+    /// The `[web]` search API for `--web`, when one is configured
+    pub fn web(&self) -> Option<&WebConfig> {
+        self.config.web.as_ref()
+    }
+
+    /// `[pr]` settings for `q pr`
+    pub fn pr(&self) -> &PrConfig {
+        &self.config.pr
+    }
+
+    /// `[consensus]` panel and judge for `--consensus`
+    pub fn consensus(&self) -> &ConsensusConfig {
+        &self.config.consensus
+    }
+
+    /// `[commit]` types and scopes: the project's `.q.toml` table when it has
+    /// one, otherwise the user config's
+    pub fn commit(&self) -> &CommitConfig {
+        match self.project.as_ref() {
+            Some(project) if !project.commit.is_empty() => &project.commit,
+            _ => &self.config.commit,
+        }
+    }
+
+    /// Context to include when `--ctx` isn't given: the project's `.q.toml`
+    /// list, otherwise `default_context` from the user config
+    pub fn default_context(&self) -> &[ContextFlag] {
+        match self.project.as_ref() {
+            Some(project) if !project.default_context.is_empty() => &project.default_context,
+            _ => &self.config.settings.default_context,
+        }
+    }
+
+    /// `[context]` budget, priority, quotas, compression, and provider policy for combined context
+    pub fn context(&self) -> &ContextSettings {
+        &self.config.context
+    }
+
+    /// `[history]`: how the query history log is stored
+    pub fn history(&self) -> &HistorySettings {
+        &self.config.history
+    }
+
+    /// `[retention]` limits for the local stores
+    pub fn retention(&self) -> &RetentionSettings {
+        &self.config.retention
+    }
+
+    /// The `[presets]` table for `q ask`
+    pub fn presets(&self) -> &HashMap<String, Preset> {
+        &self.config.presets
+    }
+
+    /// The `[aliases]` table: argument lists by name
+    pub fn aliases(&self) -> &HashMap<String, Vec<String>> {
+        &self.config.aliases
+    }
+
+    /// Save `args` as alias `name`, replacing any alias of that name.
+    /// Returns whether one was replaced.
+    pub fn set_alias(&mut self, name: &str, args: Vec<String>) -> Result<bool, QError> {
+        let replaced = self.config.aliases.insert(name.to_string(), args).is_some();
+        Self::save_config(&self.paths, &self.config)?;
+        Ok(replaced)
+    }
+
+    /// Delete alias `name`
+    pub fn remove_alias(&mut self, name: &str) -> Result<(), QError> {
+        if self.config.aliases.remove(name).is_none() {
+            return Err(QError::Config(format!("Unknown alias '{}'", name)));
+        }
+        Self::save_config(&self.paths, &self.config)
+    }
+
+    /// The `[schedules]` table for `q schedule`
+    pub fn schedules(&self) -> &HashMap<String, Schedule> {
+        &self.config.schedules
+    }
+
+    /// Save `schedule` as `name`, replacing any schedule of that name.
+    /// Returns whether one was replaced.
+    pub fn set_schedule(&mut self, name: &str, schedule: Schedule) -> Result<bool, QError> {
+        let replaced = self.config.schedules.insert(name.to_string(), schedule).is_some();
+        Self::save_config(&self.paths, &self.config)?;
+        Ok(replaced)
+    }
+
+    /// Delete schedule `name`
+    pub fn remove_schedule(&mut self, name: &str) -> Result<(), QError> {
+        if self.config.schedules.remove(name).is_none() {
+            return Err(QError::Config(format!("Unknown schedule '{}'", name)));
+        }
+        Self::save_config(&self.paths, &self.config)
+    }
+
+    /// The token `q serve --http` requires, from the environment only so it
+    /// never lands in the config file; without one a token is made per run
+    pub fn serve_token(&self) -> Option<String> {
+        std::env::var("Q_SERVE_TOKEN").ok().filter(|token| !token.trim().is_empty())
+    }
+
+    /// Brave Search key, from the environment only so it never lands in the config file
+    pub fn brave_api_key(&self) -> Option<String> {
+        std::env::var("BRAVE_API_KEY").ok().filter(|key| !key.trim().is_empty())
+    }
+
+    fn api_keys_mut(&mut self) -> &mut ApiKeys {
+        if self.active_profile().is_some() {
+            let profile = self.profile_mut().expect("active profile");
+            &mut profile.api_keys
+        } else {
+            &mut self.config.api_keys
+        }
     }
 
     fn load_or_create_config(paths: &ConfigPaths, verbose: bool) -> Result<Config, QError> {
         if paths.config_file().exists() {
+            if verbose {
+                eprintln!("Debug: Loading config from {:?}", paths.config_file());
+            }
             let contents = fs::read_to_string(paths.config_file())
-                .map_err(|e| QError::Io(e))?;
-            toml::from_str(&contents)
-                .map_err(|e| QError::Config(format!("Failed to parse config: {}", e)))
+                .map_err(QError::Io)?;
+            toml::from_str(&contents).map_err(|e| {
+                let diagnostic = validate::describe_parse_error(&contents, &e);
+                QError::Config(format!(
+                    "Invalid config {}:{}\nRun 'q config validate' for details.",
+                    paths.config_file().display(),
+                    diagnostic
+                ))
+            })
         } else {
+            if verbose {
+                eprintln!("Debug: Creating default config at {:?}", paths.config_file());
+            }
             let config = Config::default();
             Self::save_config(paths, &config)?;
             Ok(config)
         }
     }
 
+    /// The nearest `.q.toml`. It comes with whatever repository the user is
+    /// in, so one that can't be read or isn't valid is warned about and
+    /// ignored rather than stopping q; `q config validate` reports it in full.
+    fn load_project_config(dir: &std::path::Path, verbose: bool) -> Option<ProjectConfig> {
+        let path = ConfigPaths::find_project_config(dir)?;
+        if verbose {
+            eprintln!("Debug: Loading project config from {:?}", path);
+        }
+        let problem = match fs::read_to_string(&path) {
+            Ok(contents) => match validate::validate_project_str(&contents).into_iter().find(|d| d.severity == validate::Severity::Error) {
+                Some(diagnostic) => format!("{}:{}", path.display(), diagnostic),
+                None => match toml::from_str(&contents) {
+                    Ok(project) => return Some(project),
+                    Err(e) => format!("{}:{}", path.display(), validate::describe_parse_error(&contents, &e)),
+                },
+            },
+            Err(e) => format!("{}: {}", path.display(), e),
+        };
+        // The config is loaded more than once per run; say it the first time
+        static WARNED: std::sync::Once = std::sync::Once::new();
+        WARNED.call_once(|| eprintln!("{}", format!("Ignoring the project config {}", problem).yellow()));
+        None
+    }
+
     fn save_config(paths: &ConfigPaths, config: &Config) -> Result<(), QError> {
         // Double-check that the directory exists
         paths.ensure_config_dir()?;
@@ -43,43 +310,55 @@ impl ConfigManager {
         
         if paths.verbose {
             eprintln!("Debug: Saving config to {:?}", paths.config_file());
-            eprintln!("Debug: Config content:\n{}", toml);
         }
         
         // Create parent directories if they don't exist
         if let Some(parent) = paths.config_file().parent() {
             fs::create_dir_all(parent)
-                .map_err(|e| QError::Io(e))?;
+                .map_err(QError::Io)?;
         }
 
         fs::write(paths.config_file(), toml)
-            .map_err(|e| QError::Io(e))?;
+            .map_err(QError::Io)?;
 
         #[cfg(unix)]
         {
             use std::os::unix::fs::PermissionsExt;
             let mut perms = fs::metadata(paths.config_file())
-                .map_err(|e| QError::Io(e))?
+                .map_err(QError::Io)?
                 .permissions();
             perms.set_mode(0o600); // User read/write only
             fs::set_permissions(paths.config_file(), perms)
-                .map_err(|e| QError::Io(e))?;
+                .map_err(QError::Io)?;
         }
 
         Ok(())
     }
 
+    /// Store `key` as the only key for `provider`
     pub fn set_api_key(&mut self, provider: Provider, key: String) -> Result<(), QError> {
-        eprintln!("Debug: Setting {} API key", provider);
-        
-        // Validate key format
-        types::validate_api_key(provider, &key)
-            .map_err(|e| QError::Config(e))?;
+        self.store_api_key(provider, key, false)
+    }
+
+    /// Add `key` to the rotation list for `provider`
+    pub fn add_api_key(&mut self, provider: Provider, key: String) -> Result<(), QError> {
+        self.store_api_key(provider, key, true)
+    }
+
+    fn store_api_key(&mut self, provider: Provider, key: String, append: bool) -> Result<(), QError> {
+        if self.paths.verbose {
+            eprintln!("Debug: Setting {} API key", provider);
+        }
+
+        // Validate key format; callers warn about unfamiliar formats
+        keys::validate_api_key(provider, &key)
+            .map_err(QError::Config)?;
 
         // Update the key
-        match provider {
-            Provider::OpenAI => self.config.api_keys.openai = Some(key),
-            Provider::Gemini => self.config.api_keys.gemini = Some(key),
+        let slot = self.api_keys_mut().get_mut(provider);
+        match slot {
+            Some(keys) if append => keys.push(key),
+            _ => *slot = Some(KeyList::Single(key)),
         }
 
         // Save the updated config
@@ -87,37 +366,362 @@ impl ConfigManager {
     }
 
     pub fn get_api_key(&self, provider: Provider) -> Option<&str> {
-        match provider {
-            Provider::OpenAI => self.config.api_keys.openai.as_deref(),
-            Provider::Gemini => self.config.api_keys.gemini.as_deref(),
+        self.api_keys()?.get(provider).and_then(KeyList::first)
+    }
+
+    /// Resolve the key to use for `provider`: the environment variable wins
+    /// over the config file so CI and one-off overrides work without editing it.
+    /// A profile uses only its own keys; the environment's would bill whatever
+    /// account the shell happens to have.
+    pub fn resolve_api_key(&self, provider: Provider) -> Option<ResolvedKey> {
+        if self.active_profile().is_none() {
+            if let Ok(key) = std::env::var(provider.env_var()) {
+                if !key.trim().is_empty() {
+                    return Some(ResolvedKey { key: key.trim().to_string(), source: KeySource::Env });
+                }
+            }
+        }
+        self.get_api_key(provider).map(|key| ResolvedKey {
+            key: key.to_string(),
+            source: KeySource::Config,
+        })
+    }
+
+    /// All keys available for `provider`, in rotation order. An environment
+    /// key replaces the configured list rather than joining it.
+    pub fn resolve_api_keys(&self, provider: Provider) -> Vec<ResolvedKey> {
+        match self.resolve_api_key(provider) {
+            Some(resolved) if resolved.source == KeySource::Env => vec![resolved],
+            _ => self.api_keys()
+                .and_then(|keys| keys.get(provider))
+                .map(|keys| keys.keys().into_iter()
+                    .map(|key| ResolvedKey { key: key.to_string(), source: KeySource::Config })
+                    .collect())
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Remove the stored key for `provider`, returning whether one was present
+    pub fn remove_api_key(&mut self, provider: Provider) -> Result<bool, QError> {
+        let removed = self.api_keys_mut().get_mut(provider).take();
+        if removed.is_some() {
+            Self::save_config(&self.paths, &self.config)?;
         }
+        Ok(removed.is_some())
     }
 
     pub fn set_default_provider(&mut self, provider: Provider) -> Result<(), QError> {
-        self.config.settings.default_provider = provider;
+        match self.profile_mut() {
+            Some(profile) => profile.default_provider = Some(provider),
+            None => self.config.settings.default_provider = provider,
+        }
         Self::save_config(&self.paths, &self.config)
     }
 
+    /// Default provider for the active profile, falling back to the global setting
+    pub fn default_provider(&self) -> Provider {
+        self.profile()
+            .and_then(|profile| profile.default_provider)
+            .unwrap_or(self.config.settings.default_provider)
+    }
+
     pub fn set_model(&mut self, provider: Provider, model: String) -> Result<(), QError> {
-        self.config.settings.models.insert(provider.as_str().to_string(), model);
+        match self.profile_mut() {
+            Some(profile) => profile.models.insert(provider.as_str().to_string(), model),
+            None => self.config.settings.models.insert(provider.as_str().to_string(), model),
+        };
         Self::save_config(&self.paths, &self.config)
     }
 
+    /// Model for `provider`: the project's `.q.toml`, then the active profile,
+    /// then `[settings.models]`, then the built-in default
     pub fn get_model(&self, provider: Provider) -> &str {
-        self.config.settings.models
-            .get(provider.as_str())
+        let key = provider.as_str();
+        self.project.as_ref()
+            .and_then(|project| project.models.get(key))
+            .or_else(|| self.profile().and_then(|profile| profile.models.get(key)))
+            .or_else(|| self.config.settings.models.get(key))
             .map(String::as_str)
-            .unwrap_or_else(|| match provider {
-                Provider::OpenAI => "gpt-3.5-turbo",
-                Provider::Gemini => "gemini-pro",
-            })
+            .unwrap_or_else(|| provider.default_model())
+    }
+
+    /// Sampling temperature for `provider`: `[settings.temperatures]`, then `settings.temperature`
+    pub fn get_temperature(&self, provider: Provider) -> f64 {
+        self.config.settings.temperatures.get(provider.as_str())
+            .copied()
+            .unwrap_or(self.config.settings.temperature)
+    }
+
+    /// Whether queries carry the environment preamble (`settings.include_environment`)
+    pub fn include_environment(&self) -> bool {
+        self.config.settings.include_environment
+    }
+
+    /// How a stream that breaks mid-answer is recovered (`settings.stream_recovery`)
+    pub fn stream_recovery(&self) -> StreamRecovery {
+        self.config.settings.stream_recovery
+    }
+
+    /// Whether answers are cached on disk between runs (`settings.response_cache`)
+    pub fn response_cache(&self) -> CacheMode {
+        self.config.settings.response_cache
+    }
+
+    /// Follow-up turns offered after an answer (`settings.follow_ups`)
+    pub fn follow_ups(&self) -> usize {
+        self.config.settings.follow_ups
+    }
+
+    /// Parts `--map-reduce` reads at once (`settings.map_reduce_jobs`)
+    pub fn map_reduce_jobs(&self) -> usize {
+        self.config.settings.map_reduce_jobs
+    }
+
+    /// Whether queued prompts run after a query gets through (`settings.auto_flush_queue`)
+    pub fn auto_flush_queue(&self) -> bool {
+        self.config.settings.auto_flush_queue
+    }
+
+    /// Providers to fall back on while the default one is failing (`settings.failover`)
+    pub fn failover(&self) -> &[Provider] {
+        &self.config.settings.failover
+    }
+
+    /// The language set for help and messages (`settings.language`)
+    pub fn language(&self) -> Option<&str> {
+        self.config.settings.language.as_deref()
+    }
+
+    /// The shareable part of the config, without keys; see `bundle`
+    pub fn export_bundle(&self) -> Result<String, QError> {
+        bundle::export(&self.config)
+    }
+
+    /// Merge a bundle into the config and save it. Returns the sections it set.
+    pub fn import_bundle(&mut self, text: &str) -> Result<Vec<String>, QError> {
+        let (config, sections) = bundle::import(&self.config, text)?;
+        self.config = config;
+        Self::save_config(&self.paths, &self.config)?;
+        Ok(sections)
+    }
+
+    /// Read a dotted config key; see `set_value` for how keys are resolved
+    pub fn get_value(&self, key: &str) -> Result<String, QError> {
+        let path = Self::value_path(key)?;
+        let document = toml::Value::try_from(&self.config)
+            .map_err(|e| QError::Config(format!("Failed to serialize config: {}", e)))?;
+        let mut value = path.iter()
+            .try_fold(&document, |value, part| value.get(part))
+            .ok_or_else(|| QError::Config(format!("'{}' is not set", key)))?
+            .clone();
+        // A profile's table holds its keys, which are never echoed
+        hide_api_keys(&mut value);
+        Ok(match &value {
+            toml::Value::String(s) => s.clone(),
+            other => other.to_string(),
+        })
+    }
+
+    /// Set a dotted config key (e.g. `temperature`, `defaults.stream`) to a
+    /// TOML value, or to a plain string when `raw` isn't valid TOML. Keys
+    /// outside a top-level section are looked up under `[settings]`. The
+    /// result is checked like `q config validate` before it is saved.
+    pub fn set_value(&mut self, key: &str, raw: &str) -> Result<(), QError> {
+        let path = Self::value_path(key)?;
+        let value = format!("value = {}", raw)
+            .parse::<toml::Table>()
+            .ok()
+            .and_then(|mut table| table.remove("value"))
+            .unwrap_or_else(|| toml::Value::String(raw.to_string()));
+
+        let mut document = toml::Value::try_from(&self.config)
+            .map_err(|e| QError::Config(format!("Failed to serialize config: {}", e)))?;
+        let current = path.iter().try_fold(&document, |value, part| value.get(part));
+        if has_api_keys(&value, false) || current.is_some_and(|current| has_api_keys(current, true)) {
+            return Err(QError::Config(format!(
+                "{} holds API keys, which can't be read or changed with 'q config'. Use 'q set-key' or 'q keys', or set the other values one at a time.",
+                key
+            )));
+        }
+        let (last, parents) = path.split_last().expect("non-empty key");
+        let mut table = document.as_table_mut().expect("config serializes to a table");
+        for part in parents {
+            table = table.entry(part.as_str())
+                .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+                .as_table_mut()
+                .ok_or_else(|| QError::Config(format!("'{}' is not a table", part)))?;
+        }
+        table.insert(last.clone(), value);
+
+        let contents = toml::to_string(&document)
+            .map_err(|e| QError::Config(format!("Failed to serialize config: {}", e)))?;
+        if let Some(error) = validate::validate_str(&contents)
+            .into_iter()
+            .find(|d| d.severity == validate::Severity::Error)
+        {
+            return Err(QError::Config(format!("Cannot set {}: {}", key, error.message)));
+        }
+
+        self.config = toml::from_str(&contents)
+            .map_err(|e| QError::Config(format!("Cannot set {}: {}", key, e)))?;
+        Self::save_config(&self.paths, &self.config)
+    }
+
+    fn value_path(key: &str) -> Result<Vec<String>, QError> {
+        let mut path: Vec<String> = key.split('.').map(String::from).collect();
+        if path.iter().any(String::is_empty) {
+            return Err(QError::Config(format!("Invalid config key '{}'", key)));
+        }
+        match path[0].as_str() {
+            // Keys only go through set-key so they are format-checked and never echoed
+            "api_keys" => return Err(QError::Config(
+                "API keys can't be read or changed with 'q config'. Use 'q set-key' or 'q keys'.".to_string()
+            )),
+            "settings" | "profiles" | "defaults" | "web" | "pr" | "commit" | "consensus" | "context" | "presets" | "aliases" | "schedules" | "history" | "retention" => {}
+            _ => path.insert(0, "settings".to_string()),
+        }
+        if path.len() > 2 && path[0] == "profiles" && path[2] == "api_keys" {
+            return Err(QError::Config(
+                "API keys can't be read or changed with 'q config'. Use 'q set-key' or 'q keys'.".to_string()
+            ));
+        }
+        Ok(path)
     }
 
     #[cfg(test)]
     pub fn with_root(root: std::path::PathBuf, verbose: bool) -> Result<Self, QError> {
-        let paths = ConfigPaths::with_root(root);
+        let paths = ConfigPaths::with_root(root.clone());
         paths.ensure_config_dir()?;
         let config = Self::load_or_create_config(&paths, verbose)?;
-        Ok(Self { paths, config })
+        let project = Self::load_project_config(&root, verbose);
+        Ok(Self { paths, config, profile: None, project })
+    }
+}
+
+/// Whether `value` has an `api_keys` table at any depth; with `stored`, only
+/// one holding a key counts, since every profile has the (maybe empty) table
+fn has_api_keys(value: &toml::Value, stored: bool) -> bool {
+    match value {
+        toml::Value::Table(table) => table.iter().any(|(name, value)| {
+            (name == "api_keys" && !(stored && value.as_table().is_some_and(|keys| keys.is_empty())))
+                || has_api_keys(value, stored)
+        }),
+        toml::Value::Array(values) => values.iter().any(|value| has_api_keys(value, stored)),
+        _ => false,
+    }
+}
+
+/// Replace every `api_keys` table holding a key with a pointer to `q keys`
+fn hide_api_keys(value: &mut toml::Value) {
+    match value {
+        toml::Value::Table(table) => {
+            for (name, value) in table.iter_mut() {
+                if name == "api_keys" && value.as_table().is_some_and(|keys| !keys.is_empty()) {
+                    *value = toml::Value::String("(hidden; see 'q keys')".to_string());
+                } else {
+                    hide_api_keys(value);
+                }
+            }
+        }
+        toml::Value::Array(values) => values.iter_mut().for_each(hide_api_keys),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_get_model_precedence() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("q");
+
+        let mut config = ConfigManager::with_root(root.clone(), false).unwrap();
+        assert_eq!(config.get_model(Provider::Gemini), Provider::Gemini.default_model());
+
+        config.set_model(Provider::Gemini, "gemini-1.5-pro".to_string()).unwrap();
+        assert_eq!(config.get_model(Provider::Gemini), "gemini-1.5-pro");
+        assert_eq!(config.get_model(Provider::OpenAI), "gpt-3.5-turbo");
+
+        // A project file takes precedence over the user config
+        fs::write(root.join(".q.toml"), "[models]\ngemini = \"gemini-2.5-pro\"\n").unwrap();
+        let config = ConfigManager::with_root(root, false).unwrap();
+        assert_eq!(config.get_model(Provider::Gemini), "gemini-2.5-pro");
+    }
+
+    #[test]
+    fn test_default_context_precedence() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("q");
+
+        let mut config = ConfigManager::with_root(root.clone(), false).unwrap();
+        assert!(config.default_context().is_empty());
+        config.set_value("default_context", "[\"hist\"]").unwrap();
+        assert_eq!(config.default_context(), [ContextFlag::Hist]);
+
+        fs::write(root.join(".q.toml"), "default_context = [\"diff\", \"here\"]\n").unwrap();
+        let config = ConfigManager::with_root(root.clone(), false).unwrap();
+        assert_eq!(config.default_context(), [ContextFlag::Diff, ContextFlag::Here]);
+
+        // A cloned repository can't send the user's shell history: its file
+        // is ignored, as one that doesn't parse is
+        fs::write(root.join(".q.toml"), "default_context = [\"diff\", \"hist\"]\n").unwrap();
+        let config = ConfigManager::with_root(root.clone(), false).unwrap();
+        assert_eq!(config.default_context(), [ContextFlag::Hist]);
+        fs::write(root.join(".q.toml"), "default_context = \"diff\n").unwrap();
+        let config = ConfigManager::with_root(root, false).unwrap();
+        assert_eq!(config.default_context(), [ContextFlag::Hist]);
+    }
+
+    #[test]
+    fn test_project_config_found_in_parent() {
+        let temp_dir = TempDir::new().unwrap();
+        let nested = temp_dir.path().join("a/b");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(temp_dir.path().join(".q.toml"), "[models]\n").unwrap();
+
+        assert_eq!(
+            ConfigPaths::find_project_config(&nested),
+            Some(temp_dir.path().join(".q.toml"))
+        );
+    }
+
+    #[test]
+    fn test_set_value() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = ConfigManager::with_root(temp_dir.path().join("q"), false).unwrap();
+
+        config.set_value("temperature", "0.2").unwrap();
+        assert_eq!(config.get_temperature(Provider::Gemini), 0.2);
+        assert_eq!(config.get_value("settings.temperature").unwrap(), "0.2");
+
+        config.set_value("temperatures.openai", "1").unwrap();
+        assert_eq!(config.get_temperature(Provider::OpenAI), 1.0);
+        assert_eq!(config.get_temperature(Provider::Gemini), 0.2);
+
+        config.set_value("defaults.detail", "detailed").unwrap();
+        assert_eq!(config.defaults().detail, Some(crate::cli::args::Verbosity::Detailed));
+
+        assert!(config.set_value("temperature", "5").is_err());
+        assert!(config.set_value("temprature", "0.2").is_err());
+        assert!(config.set_value("api_keys.openai", "sk-x").is_err());
+        assert!(config.get_value("api_keys.openai").is_err());
+
+        // A profile's keys stay out of reach through the tables holding them
+        config.profile = Some("work".to_string());
+        config.set_api_key(Provider::OpenAI, "sk-work-0123456789abcdef0123456789abcdef".to_string()).unwrap();
+        config.set_value("profiles.work.default_provider", "openai").unwrap();
+        for key in ["profiles", "profiles.work"] {
+            let value = config.get_value(key).unwrap();
+            assert!(!value.contains("sk-work"), "{}", value);
+            assert!(value.contains("default_provider"), "{}", value);
+        }
+        assert!(config.get_value("profiles.work.api_keys").is_err());
+        assert!(config.set_value("profiles.work", "{ api_keys = { openai = \"sk-other\" } }").is_err());
+        assert!(config.set_value("profiles", "{}").is_err());
+        assert!(config.set_value("profiles.home", "{ api_keys = { openai = \"sk-other\" } }").is_err());
+        assert_eq!(config.get_api_key(Provider::OpenAI), Some("sk-work-0123456789abcdef0123456789abcdef"));
     }
 }
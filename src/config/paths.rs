@@ -1,7 +1,10 @@
 use directories::ProjectDirs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use crate::utils::errors::QError;
 
+/// Name of the per-project config file
+pub const PROJECT_CONFIG_FILE: &str = ".q.toml";
+
 pub struct ConfigPaths {
     config_dir: PathBuf,
     config_file: PathBuf,
@@ -45,17 +48,17 @@ impl ConfigPaths {
                 eprintln!("Debug: Creating config directory: {:?}", self.config_dir);
             }
             std::fs::create_dir_all(&self.config_dir)
-                .map_err(|e| QError::Io(e))?;
+                .map_err(QError::Io)?;
             
             #[cfg(unix)]
             {
                 use std::os::unix::fs::PermissionsExt;
                 let mut perms = std::fs::metadata(&self.config_dir)
-                    .map_err(|e| QError::Io(e))?
+                    .map_err(QError::Io)?
                     .permissions();
                 perms.set_mode(0o700); // User read/write/execute only
                 std::fs::set_permissions(&self.config_dir, perms)
-                    .map_err(|e| QError::Io(e))?;
+                    .map_err(QError::Io)?;
             }
         }
         Ok(())
@@ -65,6 +68,18 @@ impl ConfigPaths {
         &self.config_file
     }
 
+    /// Prompt templates for `-t`, one Markdown file each
+    pub fn templates_dir(&self) -> PathBuf {
+        self.config_dir.join("templates")
+    }
+
+    /// Find the nearest `.q.toml`, searching `start` and then its parents
+    pub fn find_project_config(start: &Path) -> Option<PathBuf> {
+        start.ancestors()
+            .map(|dir| dir.join(PROJECT_CONFIG_FILE))
+            .find(|path| path.is_file())
+    }
+
     #[cfg(test)]
     pub fn with_root(root: PathBuf) -> Self {
         let config_dir = root.clone();
@@ -76,3 +91,189 @@ impl ConfigPaths {
         }
     }
 }
+
+/// Resolve `$VAR/q`, falling back to the platform directory from `ProjectDirs`
+fn xdg_dir(var: &str, fallback: impl FnOnce(&ProjectDirs) -> PathBuf) -> Result<PathBuf, QError> {
+    match std::env::var_os(var) {
+        Some(dir) if !dir.is_empty() => Ok(PathBuf::from(dir).join("q")),
+        _ => ProjectDirs::from("com", "ryohei", "q")
+            .map(|dirs| fallback(&dirs))
+            .ok_or_else(|| QError::Config(format!("Could not determine directory for {}", var))),
+    }
+}
+
+/// Create `dir` (user-only on unix) if it doesn't exist yet
+fn ensure_private_dir(dir: &Path) -> Result<(), QError> {
+    if !dir.exists() {
+        std::fs::create_dir_all(dir).map_err(QError::Io)?;
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o700))
+                .map_err(QError::Io)?;
+        }
+    }
+    Ok(())
+}
+
+/// Files q accumulates over time: sessions, history logs, and usage ledgers
+/// under `XDG_DATA_HOME`, and small bits of runtime state under `XDG_STATE_HOME`.
+/// Directories are created on first use rather than at startup.
+#[derive(Debug, Clone)]
+pub struct DataPaths {
+    data_dir: PathBuf,
+    state_dir: PathBuf,
+}
+
+impl DataPaths {
+    pub fn new() -> Result<Self, QError> {
+        Ok(Self {
+            data_dir: xdg_dir("XDG_DATA_HOME", |dirs| dirs.data_dir().to_path_buf())?,
+            // Only Linux has a state dir; elsewhere keep state next to local data
+            state_dir: xdg_dir("XDG_STATE_HOME", |dirs| {
+                dirs.state_dir()
+                    .map(Path::to_path_buf)
+                    .unwrap_or_else(|| dirs.data_local_dir().join("state"))
+            })?,
+        })
+    }
+
+    pub fn data_dir(&self) -> &Path {
+        &self.data_dir
+    }
+
+    pub fn state_dir(&self) -> &Path {
+        &self.state_dir
+    }
+
+    pub fn sessions_dir(&self) -> PathBuf {
+        self.data_dir.join("sessions")
+    }
+
+    pub fn history_log(&self) -> PathBuf {
+        self.data_dir.join("history.jsonl")
+    }
+
+    /// Where the command database bundle is installed (ADR 0007)
+    pub fn commands_bundle(&self) -> PathBuf {
+        self.data_dir.join("commands.json")
+    }
+
+    /// Recent provider outages, so later runs can fail over without waiting
+    pub fn provider_health(&self) -> PathBuf {
+        self.state_dir.join("provider-health.json")
+    }
+
+    /// Prompts saved by `q queue add` until they can be sent
+    pub fn queue(&self) -> PathBuf {
+        self.data_dir.join("queue.jsonl")
+    }
+
+    /// The token `q serve --http` made for this run, readable only by the user
+    pub fn serve_token(&self) -> PathBuf {
+        self.state_dir.join("serve-token")
+    }
+
+    /// When each `q schedule` entry last ran
+    pub fn schedule_runs(&self) -> PathBuf {
+        self.state_dir.join("schedule-runs.json")
+    }
+
+    pub fn usage_ledger(&self) -> PathBuf {
+        self.data_dir.join("usage.jsonl")
+    }
+
+    pub fn ensure_data_dir(&self) -> Result<(), QError> {
+        ensure_private_dir(&self.data_dir)
+    }
+
+    pub fn ensure_state_dir(&self) -> Result<(), QError> {
+        ensure_private_dir(&self.state_dir)
+    }
+
+    #[cfg(test)]
+    pub fn with_root(root: PathBuf) -> Self {
+        Self {
+            data_dir: root.join("data"),
+            state_dir: root.join("state"),
+        }
+    }
+}
+
+/// Disposable files under `XDG_CACHE_HOME`; anything here may be deleted at any time
+#[derive(Debug, Clone)]
+pub struct CachePaths {
+    cache_dir: PathBuf,
+}
+
+impl CachePaths {
+    pub fn new() -> Result<Self, QError> {
+        Ok(Self {
+            cache_dir: xdg_dir("XDG_CACHE_HOME", |dirs| dirs.cache_dir().to_path_buf())?,
+        })
+    }
+
+    pub fn cache_dir(&self) -> &Path {
+        &self.cache_dir
+    }
+
+    /// Cached query responses
+    pub fn responses_dir(&self) -> PathBuf {
+        self.cache_dir.join("responses")
+    }
+
+    /// Notes on `--map-reduce` parts, by the hashes of their content and question
+    pub fn parts_dir(&self) -> PathBuf {
+        self.cache_dir.join("parts")
+    }
+
+    /// Captured man pages and `--help` output for `--man`
+    pub fn man_dir(&self) -> PathBuf {
+        self.cache_dir.join("man")
+    }
+
+    pub fn ensure_cache_dir(&self) -> Result<(), QError> {
+        ensure_private_dir(&self.cache_dir)
+    }
+
+    #[cfg(test)]
+    pub fn with_root(root: PathBuf) -> Self {
+        Self { cache_dir: root.join("cache") }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_data_and_cache_paths_are_separate() {
+        let temp_dir = TempDir::new().unwrap();
+        let data = DataPaths::with_root(temp_dir.path().to_path_buf());
+        let cache = CachePaths::with_root(temp_dir.path().to_path_buf());
+
+        assert!(data.sessions_dir().starts_with(data.data_dir()));
+        assert!(data.history_log().starts_with(data.data_dir()));
+        assert!(cache.responses_dir().starts_with(cache.cache_dir()));
+        assert!(cache.man_dir().starts_with(cache.cache_dir()));
+        assert!(cache.parts_dir().starts_with(cache.cache_dir()));
+        assert_ne!(data.data_dir(), data.state_dir());
+        assert_ne!(data.data_dir(), cache.cache_dir());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_directories_are_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let data = DataPaths::with_root(temp_dir.path().to_path_buf());
+        assert!(!data.data_dir().exists());
+
+        data.ensure_data_dir().unwrap();
+        let mode = std::fs::metadata(data.data_dir()).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o700);
+    }
+}
@@ -0,0 +1,193 @@
+//! Prompt templates (`-t <name>`): Markdown files in the config directory's
+//! `templates/`. Optional TOML frontmatter between `+++` lines picks the
+//! model and settings, with the same keys as a `[presets.<name>]` table plus
+//! `max_tokens`; the rest is the prompt, with `{input}` where the prompt given
+//! on the command line goes.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::types::Preset;
+use super::validate::suggest;
+use crate::utils::errors::QError;
+
+/// Where the command line's prompt goes in a template's text
+pub const INPUT_PLACEHOLDER: &str = "{input}";
+/// The line above and below the frontmatter
+const FENCE: &str = "+++";
+const EXTENSION: &str = "md";
+
+#[derive(Debug, Clone)]
+pub struct Template {
+    pub name: String,
+    /// Model, temperature, token limit, and context from the frontmatter
+    pub settings: Preset,
+    /// The prompt text
+    pub body: String,
+}
+
+impl Template {
+    /// A template from its file's contents
+    pub fn parse(name: &str, text: &str) -> Result<Self, QError> {
+        let invalid = |message: String| QError::Config(format!("template '{}': {}", name, message));
+        let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+        let (settings, body) = match text.strip_prefix(FENCE).filter(|rest| rest.starts_with(['\n', '\r'])) {
+            Some(rest) => {
+                let (frontmatter, body) = split_at_fence(rest)
+                    .ok_or_else(|| invalid(format!("the frontmatter has no closing {} line", FENCE)))?;
+                let settings: Preset = toml::from_str(frontmatter).map_err(|e| invalid(e.message().to_string()))?;
+                (settings, body)
+            }
+            None => (Preset::default(), text),
+        };
+        if let Some(temperature) = settings.temperature.filter(|t| !(0.0..=2.0).contains(t)) {
+            return Err(invalid(format!("temperature {} is outside the supported range 0.0-2.0", temperature)));
+        }
+        if settings.max_tokens == Some(0) {
+            return Err(invalid("max_tokens must be at least 1".to_string()));
+        }
+        Ok(Self { name: name.to_string(), settings, body: body.trim().to_string() })
+    }
+
+    /// The template called `name` in `dir`, or an error suggesting the closest name
+    pub fn load(dir: &Path, name: &str) -> Result<Self, QError> {
+        check_name(name)?;
+        let path = path(dir, name);
+        match fs::read_to_string(&path) {
+            Ok(text) => Self::parse(name, &text),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                let names = names(dir)?;
+                let names: Vec<&str> = names.iter().map(String::as_str).collect();
+                let mut message = format!("No template named '{}' in {}", name, dir.display());
+                if let Some(suggestion) = suggest(name, &names) {
+                    message.push_str(&format!("; did you mean '{}'?", suggestion));
+                }
+                Err(QError::Usage(message))
+            }
+            Err(e) => Err(QError::Config(format!("Couldn't read {}: {}", path.display(), e))),
+        }
+    }
+
+    /// The prompt to send: the text with `input` in place of `{input}`, or
+    /// after it when the text has no placeholder
+    pub fn render(&self, input: Option<&str>) -> Result<String, QError> {
+        match input {
+            Some(input) if self.body.contains(INPUT_PLACEHOLDER) => Ok(self.body.replace(INPUT_PLACEHOLDER, input)),
+            Some(input) if self.body.is_empty() => Ok(input.to_string()),
+            Some(input) => Ok(format!("{}\n\n{}", self.body, input)),
+            None if self.body.contains(INPUT_PLACEHOLDER) => Err(QError::Usage(format!(
+                "template '{}' needs input, e.g. q -t {} \"...\"",
+                self.name, self.name
+            ))),
+            None if self.body.is_empty() => Err(QError::Usage(format!("template '{}' has no prompt text", self.name))),
+            None => Ok(self.body.clone()),
+        }
+    }
+}
+
+/// Where the template called `name` is kept in `dir`
+pub fn path(dir: &Path, name: &str) -> PathBuf {
+    dir.join(format!("{}.{}", name, EXTENSION))
+}
+
+/// The names of the templates in `dir`, sorted
+pub fn names(dir: &Path) -> Result<Vec<String>, QError> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(QError::Config(format!("Couldn't read {}: {}", dir.display(), e))),
+    };
+    let mut names: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == EXTENSION))
+        .filter_map(|path| Some(path.file_stem()?.to_str()?.to_string()))
+        .filter(|name| check_name(name).is_ok())
+        .collect();
+    names.sort();
+    Ok(names)
+}
+
+/// Template names are file names, so only letters, digits, `-`, and `_`
+pub fn check_name(name: &str) -> Result<(), QError> {
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err(QError::Usage(format!(
+            "'{}' isn't a valid template name; use letters, digits, '-', and '_'",
+            name
+        )));
+    }
+    Ok(())
+}
+
+/// The frontmatter before the closing fence line, and the text after it
+fn split_at_fence(text: &str) -> Option<(&str, &str)> {
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        if line.trim_end() == FENCE {
+            return Some((&text[..offset], &text[offset + line.len()..]));
+        }
+        offset += line.len();
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::args::ContextFlag;
+    use tempfile::TempDir;
+
+    const TRANSLATE: &str = "+++\n\
+        description = \"Translate into Japanese\"\n\
+        model = \"openai/gpt-4o\"\n\
+        temperature = 0.3\n\
+        max_tokens = 2000\n\
+        context = [\"here\"]\n\
+        +++\n\
+        Translate into natural Japanese:\n\n{input}\n";
+
+    #[test]
+    fn test_parse_frontmatter() {
+        let template = Template::parse("translate-ja", TRANSLATE).unwrap();
+        assert_eq!(template.settings.model.as_deref(), Some("openai/gpt-4o"));
+        assert_eq!(template.settings.temperature, Some(0.3));
+        assert_eq!(template.settings.max_tokens, Some(2000));
+        assert_eq!(template.settings.context, [ContextFlag::Here]);
+        assert_eq!(template.body, "Translate into natural Japanese:\n\n{input}");
+        assert_eq!(template.render(Some("Good morning")).unwrap(), "Translate into natural Japanese:\n\nGood morning");
+        assert!(template.render(None).is_err());
+    }
+
+    #[test]
+    fn test_parse_without_frontmatter() {
+        let template = Template::parse("review", "Review this code for bugs.\n").unwrap();
+        assert!(template.settings.model.is_none());
+        assert_eq!(template.render(None).unwrap(), "Review this code for bugs.");
+        assert_eq!(template.render(Some("fn main() {}")).unwrap(), "Review this code for bugs.\n\nfn main() {}");
+        // A rule line made of `+++` later in the text isn't frontmatter
+        assert_eq!(Template::parse("plus", "a\n+++\nb").unwrap().body, "a\n+++\nb");
+    }
+
+    #[test]
+    fn test_parse_rejects_bad_frontmatter() {
+        let error = |text: &str| Template::parse("t", text).unwrap_err().to_string();
+        assert!(error("+++\nmodel = \"gpt-4o\"\n").contains("no closing +++ line"));
+        assert!(error("+++\nmodle = \"gpt-4o\"\n+++\nHi").contains("unknown field `modle`"));
+        assert!(error("+++\ntemperature = 3.0\n+++\nHi").contains("outside the supported range"));
+        assert!(error("+++\nmax_tokens = 0\n+++\nHi").contains("at least 1"));
+    }
+
+    #[test]
+    fn test_load_and_names() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("translate-ja.md"), TRANSLATE).unwrap();
+        fs::write(dir.path().join("notes.txt"), "not a template").unwrap();
+        assert_eq!(names(dir.path()).unwrap(), ["translate-ja"]);
+        assert_eq!(Template::load(dir.path(), "translate-ja").unwrap().settings.max_tokens, Some(2000));
+
+        let error = Template::load(dir.path(), "translate-jp").unwrap_err().to_string();
+        assert!(error.contains("did you mean 'translate-ja'?"), "{}", error);
+        assert!(Template::load(dir.path(), "../config").is_err());
+        assert!(names(&dir.path().join("missing")).unwrap().is_empty());
+    }
+}
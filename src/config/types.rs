@@ -1,48 +1,459 @@
 use serde::{Deserialize, Serialize};
 use std::fmt;
 use std::collections::HashMap;
+use std::path::PathBuf;
 
-#[derive(Debug, Serialize, Deserialize)]
+use crate::cli::args::{ContextFlag, Verbosity};
+use crate::context::compress::Compression;
+use crate::core::{CacheMode, StreamRecovery};
+
+/// Per-project overrides from `.q.toml`. It usually lives in a repository,
+/// so it can't hold API keys.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProjectConfig {
+    #[serde(default)]
+    pub models: HashMap<String, String>,
+    /// Context always included for queries in this project, e.g. `["diff", "here"]`
+    #[serde(default)]
+    pub default_context: Vec<ContextFlag>,
+    /// Commit types and scopes for this repository; replaces the user's `[commit]`
+    #[serde(default)]
+    pub commit: CommitConfig,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct Config {
     #[serde(default)]
     pub api_keys: ApiKeys,
     #[serde(default)]
     pub settings: Settings,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub profiles: HashMap<String, Profile>,
+    #[serde(default, skip_serializing_if = "Defaults::is_empty")]
+    pub defaults: Defaults,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vertex: Option<VertexConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub web: Option<WebConfig>,
+    #[serde(default, skip_serializing_if = "PrConfig::is_empty")]
+    pub pr: PrConfig,
+    #[serde(default, skip_serializing_if = "CommitConfig::is_empty")]
+    pub commit: CommitConfig,
+    #[serde(default, skip_serializing_if = "ContextSettings::is_empty")]
+    pub context: ContextSettings,
+    #[serde(default, skip_serializing_if = "ConsensusConfig::is_empty")]
+    pub consensus: ConsensusConfig,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub presets: HashMap<String, Preset>,
+    /// Named invocations for `q <alias>`: `rev = ["--diff", "review this change"]`
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub aliases: HashMap<String, Vec<String>>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub schedules: HashMap<String, Schedule>,
+    #[serde(default, skip_serializing_if = "HistorySettings::is_empty")]
+    pub history: HistorySettings,
+    #[serde(default, skip_serializing_if = "RetentionSettings::is_empty")]
+    pub retention: RetentionSettings,
 }
 
-impl Default for Config {
-    fn default() -> Self {
-        Self {
-            api_keys: ApiKeys::default(),
-            settings: Settings::default(),
-        }
+/// How the query history log is stored (`[history]`)
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HistorySettings {
+    /// Encrypt new records with a key from the OS keyring (or `Q_HISTORY_KEY`)
+    #[serde(default)]
+    pub encrypt: bool,
+}
+
+impl HistorySettings {
+    pub fn is_empty(&self) -> bool {
+        *self == HistorySettings::default()
     }
 }
 
+/// How much q keeps in each local store (`[retention]`), enforced by `q gc`
+/// and once a day before a query. Stores without limits are kept in full.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RetentionSettings {
+    /// The query history log
+    #[serde(default, skip_serializing_if = "Retention::is_empty")]
+    pub history: Retention,
+    #[serde(default, skip_serializing_if = "Retention::is_empty")]
+    pub sessions: Retention,
+    /// Everything under the cache directory
+    #[serde(default, skip_serializing_if = "Retention::is_empty")]
+    pub cache: Retention,
+}
+
+impl RetentionSettings {
+    pub fn is_empty(&self) -> bool {
+        *self == RetentionSettings::default()
+    }
+}
+
+/// Limits for one store: older entries go first, then the oldest until the rest fit
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Retention {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_age_days: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_size_mb: Option<u64>,
+}
+
+impl Retention {
+    pub fn is_empty(&self) -> bool {
+        *self == Retention::default()
+    }
+
+    pub fn max_age(&self) -> Option<std::time::Duration> {
+        self.max_age_days.map(|days| std::time::Duration::from_secs(days * 24 * 60 * 60))
+    }
+
+    pub fn max_bytes(&self) -> Option<u64> {
+        self.max_size_mb.map(|mb| mb * 1024 * 1024)
+    }
+}
+
+/// A query `q schedule run` sends on a crontab schedule (`[schedules.news]`)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Schedule {
+    pub prompt: String,
+    /// Five crontab fields or an @nickname, in local time
+    pub cron: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<Provider>,
+    /// A model name, or `provider/model`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Show the start of each answer in a desktop notification
+    #[serde(default)]
+    pub notify: bool,
+    /// Append each answer to this file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub save: Option<PathBuf>,
+}
+
+/// A named query setup for `q ask <name>` (`[presets.sql]`): instructions,
+/// model, temperature, and context to use instead of the defaults
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Preset {
+    /// Shown by `q ask` when listing presets
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Instructions added to the system prompt
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system_prompt: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<Provider>,
+    /// A model name, or `provider/model`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+    /// Longest answer, in tokens
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    /// Context to include, like `--ctx`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub context: Vec<ContextFlag>,
+}
+
+/// How combined context blocks share the prompt (`[context]`)
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ContextSettings {
+    /// Total bytes of context sent with a prompt (about 4 bytes per token)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_bytes: Option<usize>,
+    /// Context kinds from highest to lowest priority, e.g. `["history", "file"]`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub order: Vec<String>,
+    /// Byte limit for individual kinds, e.g. `directory = 8192`
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub quotas: HashMap<String, usize>,
+    /// Gitignore-style patterns kept out of context, on top of `.qignore`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ignore: Vec<String>,
+    /// Providers allowed to receive each kind, e.g. `file = ["openai"]`; `"*"`
+    /// means any. Kinds not listed go to every provider.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub allow: HashMap<String, Vec<String>>,
+    /// How far to compress each kind before fitting it, e.g. `log = "lines"`
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub compress: HashMap<String, Compression>,
+}
+
+impl ContextSettings {
+    pub fn is_empty(&self) -> bool {
+        *self == ContextSettings::default()
+    }
+}
+
+/// Settings for `q pr`
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PrConfig {
+    /// Markdown the description should follow (e.g. the repo's PR template sections)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+}
+
+impl PrConfig {
+    pub fn is_empty(&self) -> bool {
+        *self == PrConfig::default()
+    }
+}
+
+/// Conventional-commit rules for `q commit` and `q branch-name`
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CommitConfig {
+    /// Allowed types [default: the Conventional Commits set, feat, fix, docs, ...]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub types: Vec<String>,
+    /// Allowed scopes; any scope when empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub scopes: Vec<String>,
+}
+
+impl CommitConfig {
+    pub fn is_empty(&self) -> bool {
+        *self == CommitConfig::default()
+    }
+}
+
+/// The panel and judge for `--consensus`
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConsensusConfig {
+    /// Models that each answer, as `model` or `provider/model`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub models: Vec<String>,
+    /// The model that merges their answers [default: the configured model]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub judge: Option<String>,
+    /// Tokens the judge's prompt may take; longer answers are cut to fit [default: 32000]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_judge_tokens: Option<usize>,
+}
+
+impl ConsensusConfig {
+    pub fn is_empty(&self) -> bool {
+        *self == ConsensusConfig::default()
+    }
+}
+
+/// A search API for `--web`, used for every provider instead of their
+/// built-in search. Brave's key comes from `BRAVE_API_KEY`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WebConfig {
+    pub engine: SearchEngine,
+    /// Instance URL, required for SearxNG
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// Number of results added to the prompt
+    #[serde(default = "default_web_results")]
+    pub results: usize,
+}
+
+fn default_web_results() -> usize {
+    5
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchEngine {
+    Searxng,
+    Brave,
+}
+
+/// Send Gemini requests to Vertex AI with OAuth instead of using an API key
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct VertexConfig {
+    pub project: String,
+    #[serde(default = "default_vertex_location")]
+    pub location: String,
+    /// Service-account JSON; application-default credentials are used when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub credentials: Option<PathBuf>,
+}
+
+fn default_vertex_location() -> String {
+    "us-central1".to_string()
+}
+
+/// Preferred CLI flags (`[defaults]`), applied when the flag isn't given on
+/// the command line
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Defaults {
+    pub detail: Option<Verbosity>,
+    pub stream: Option<bool>,
+    pub hist: Option<bool>,
+    pub here: Option<bool>,
+    pub retries: Option<u32>,
+    pub no_cache: Option<bool>,
+}
+
+impl Defaults {
+    pub fn is_empty(&self) -> bool {
+        *self == Defaults::default()
+    }
+}
+
+/// A named set of keys and defaults (e.g. `[profiles.work]`), kept separate
+/// from the top-level keys so work and personal accounts never mix.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    #[serde(default)]
+    pub api_keys: ApiKeys,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_provider: Option<Provider>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub models: HashMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vertex: Option<VertexConfig>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Default)]
+#[serde(deny_unknown_fields)]
 pub struct ApiKeys {
-    pub openai: Option<String>,
-    pub gemini: Option<String>,
+    pub openai: Option<KeyList>,
+    pub gemini: Option<KeyList>,
+    pub openrouter: Option<KeyList>,
+    pub groq: Option<KeyList>,
+    pub mistral: Option<KeyList>,
+}
+
+impl ApiKeys {
+    pub fn get(&self, provider: Provider) -> Option<&KeyList> {
+        match provider {
+            Provider::OpenAI => self.openai.as_ref(),
+            Provider::Gemini => self.gemini.as_ref(),
+            Provider::OpenRouter => self.openrouter.as_ref(),
+            Provider::Groq => self.groq.as_ref(),
+            Provider::Mistral => self.mistral.as_ref(),
+        }
+    }
+
+    pub fn get_mut(&mut self, provider: Provider) -> &mut Option<KeyList> {
+        match provider {
+            Provider::OpenAI => &mut self.openai,
+            Provider::Gemini => &mut self.gemini,
+            Provider::OpenRouter => &mut self.openrouter,
+            Provider::Groq => &mut self.groq,
+            Provider::Mistral => &mut self.mistral,
+        }
+    }
+}
+
+/// One or more keys for a provider; written as a plain string when there is
+/// only one so existing configs keep their shape.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum KeyList {
+    Single(String),
+    Multiple(Vec<String>),
+}
+
+impl KeyList {
+    pub fn keys(&self) -> Vec<&str> {
+        match self {
+            KeyList::Single(key) => vec![key.as_str()],
+            KeyList::Multiple(keys) => keys.iter().map(String::as_str).collect(),
+        }
+    }
+
+    pub fn first(&self) -> Option<&str> {
+        self.keys().into_iter().next()
+    }
+
+    /// Append a key, skipping duplicates
+    pub fn push(&mut self, key: String) {
+        let mut keys: Vec<String> = self.keys().into_iter().map(String::from).collect();
+        if !keys.contains(&key) {
+            keys.push(key);
+        }
+        *self = KeyList::from(keys);
+    }
+}
+
+impl From<Vec<String>> for KeyList {
+    fn from(mut keys: Vec<String>) -> Self {
+        if keys.len() == 1 {
+            KeyList::Single(keys.remove(0))
+        } else {
+            KeyList::Multiple(keys)
+        }
+    }
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum Provider {
     OpenAI,
+    #[default]
     Gemini,
-}
-
-impl Default for Provider {
-    fn default() -> Self {
-        Self::Gemini
-    }
+    OpenRouter,
+    Groq,
+    Mistral,
 }
 
 impl Provider {
+    pub const ALL: [Provider; 5] = [
+        Provider::OpenAI, Provider::Gemini, Provider::OpenRouter, Provider::Groq, Provider::Mistral,
+    ];
+
     pub fn as_str(&self) -> &'static str {
         match self {
             Provider::OpenAI => "openai",
             Provider::Gemini => "gemini",
+            Provider::OpenRouter => "openrouter",
+            Provider::Groq => "groq",
+            Provider::Mistral => "mistral",
+        }
+    }
+
+    /// Environment variable that can supply this provider's API key
+    pub fn env_var(&self) -> &'static str {
+        match self {
+            Provider::OpenAI => "OPENAI_API_KEY",
+            Provider::Gemini => "GEMINI_API_KEY",
+            Provider::OpenRouter => "OPENROUTER_API_KEY",
+            Provider::Groq => "GROQ_API_KEY",
+            Provider::Mistral => "MISTRAL_API_KEY",
+        }
+    }
+
+    /// Model used when neither the command line nor any config names one
+    pub fn default_model(&self) -> &'static str {
+        match self {
+            Provider::OpenAI => "gpt-3.5-turbo",
+            Provider::Gemini => "gemini-2.0-flash",
+            Provider::OpenRouter => "openrouter/auto",
+            Provider::Groq => "llama-3.3-70b-versatile",
+            Provider::Mistral => "mistral-small-latest",
+        }
+    }
+
+    /// Split a `provider/model` name such as `openrouter/anthropic/claude-3.5-sonnet`
+    /// into the provider and the model id that provider expects. OpenRouter's own
+    /// models (`openrouter/auto`) keep their prefix.
+    pub fn split_model(model: &str) -> Option<(Provider, &str)> {
+        let (prefix, rest) = model.split_once('/')?;
+        let provider = Provider::try_from(prefix).ok()?;
+        match provider {
+            Provider::OpenRouter if !rest.contains('/') => Some((provider, model)),
+            _ => Some((provider, rest)),
         }
     }
 }
@@ -57,56 +468,137 @@ impl TryFrom<&str> for Provider {
     type Error = String;
 
     fn try_from(s: &str) -> Result<Self, Self::Error> {
-        match s.to_lowercase().as_str() {
-            "openai" => Ok(Provider::OpenAI),
-            "gemini" => Ok(Provider::Gemini),
-            _ => Err(format!("Unknown provider: {}. Valid providers are: openai, gemini", s)),
-        }
+        let name = s.to_lowercase();
+        Provider::ALL.into_iter()
+            .find(|provider| provider.as_str() == name)
+            .ok_or_else(|| {
+                let names: Vec<&str> = Provider::ALL.iter().map(Provider::as_str).collect();
+                format!("Unknown provider: {}. Valid providers are: {}", s, names.join(", "))
+            })
     }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct Settings {
     #[serde(default)]
     pub default_provider: Provider,
-    #[serde(default = "default_models")]
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
     pub models: HashMap<String, String>,
-    pub temperature: f32,
+    #[serde(default = "default_temperature")]
+    pub temperature: f64,
+    /// Per-provider overrides of `temperature`, keyed by provider name
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub temperatures: HashMap<String, f64>,
+    /// Profile used when `--profile` is not given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_profile: Option<String>,
+    /// Tell the model the OS, shell, directory name, and git branch
+    #[serde(default = "default_include_environment")]
+    pub include_environment: bool,
+    /// Context included when `--ctx` isn't given, e.g. `["hist"]`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub default_context: Vec<ContextFlag>,
+    /// What `--stream` does when the connection drops mid-answer: fail, resume, or buffered
+    #[serde(default)]
+    pub stream_recovery: StreamRecovery,
+    /// Follow-up questions offered at a `>` prompt after an answer in a terminal; 0 turns it off
+    #[serde(default = "default_follow_ups")]
+    pub follow_ups: usize,
+    /// Whether answers are also cached on disk between runs: memory, disk, or race
+    #[serde(default)]
+    pub response_cache: CacheMode,
+    /// Providers to ask, in order, while the default one has been failing
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub failover: Vec<Provider>,
+    /// Language for help and messages (`en` or `ja`) instead of the one LANG names
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    /// Parts `--map-reduce` reads at once
+    #[serde(default = "default_map_reduce_jobs")]
+    pub map_reduce_jobs: usize,
+    /// Run prompts waiting in `q queue` once a query gets through
+    #[serde(default = "default_auto_flush_queue")]
+    pub auto_flush_queue: bool,
 }
 
 impl Default for Settings {
     fn default() -> Self {
         Self {
             default_provider: Provider::Gemini,
-            models: default_models(),
-            temperature: 0.7,
+            models: HashMap::new(),
+            temperature: default_temperature(),
+            temperatures: HashMap::new(),
+            default_profile: None,
+            include_environment: default_include_environment(),
+            default_context: Vec::new(),
+            stream_recovery: StreamRecovery::default(),
+            follow_ups: default_follow_ups(),
+            response_cache: CacheMode::default(),
+            failover: Vec::new(),
+            language: None,
+            map_reduce_jobs: default_map_reduce_jobs(),
+            auto_flush_queue: default_auto_flush_queue(),
         }
     }
 }
 
-fn default_models() -> HashMap<String, String> {
-    let mut models = HashMap::new();
-    models.insert("openai".to_string(), "gpt-3.5-turbo".to_string());
-    models.insert("gemini".to_string(), "gemini-pro".to_string());
-    models
+fn default_temperature() -> f64 {
+    0.7
+}
+
+fn default_include_environment() -> bool {
+    true
+}
+
+fn default_follow_ups() -> usize {
+    3
+}
+
+fn default_map_reduce_jobs() -> usize {
+    4
+}
+
+fn default_auto_flush_queue() -> bool {
+    false
 }
 
-// Basic key format validation
-pub fn validate_api_key(provider: Provider, key: &str) -> Result<(), String> {
+/// Models we know each provider serves; others are allowed but flagged by `q config validate`.
+/// Empty for catalogs too large and fast-moving to list (see `q list-models`).
+pub fn known_models(provider: Provider) -> &'static [&'static str] {
     match provider {
-        Provider::OpenAI => {
-            if !key.starts_with("sk-") {
-                return Err("OpenAI API key must start with 'sk-'".to_string());
-            }
-            if key.len() < 40 {
-                return Err("OpenAI API key is too short".to_string());
-            }
-        }
-        Provider::Gemini => {
-            if key.len() < 20 {
-                return Err("Gemini API key is too short".to_string());
-            }
-        }
+        Provider::OpenAI => &[
+            "gpt-3.5-turbo", "gpt-4", "gpt-4-turbo", "gpt-4o", "gpt-4o-mini",
+            "gpt-4.1", "gpt-4.1-mini", "gpt-4.1-nano", "o1", "o1-mini", "o3", "o3-mini", "o4-mini",
+        ],
+        Provider::Gemini => &[
+            "gemini-pro", "gemini-1.5-pro", "gemini-1.5-flash", "gemini-2.0-flash",
+            "gemini-2.0-flash-lite", "gemini-2.5-pro", "gemini-2.5-flash",
+        ],
+        Provider::OpenRouter => &[],
+        Provider::Groq => &[
+            "llama-3.3-70b-versatile", "llama-3.1-8b-instant", "llama3-70b-8192", "llama3-8b-8192",
+            "mixtral-8x7b-32768", "gemma2-9b-it", "deepseek-r1-distill-llama-70b",
+        ],
+        Provider::Mistral => &[
+            "mistral-large-latest", "mistral-medium-latest", "mistral-small-latest",
+            "codestral-latest", "ministral-8b-latest", "ministral-3b-latest",
+            "open-mistral-nemo", "pixtral-large-latest",
+        ],
+    }
+}
+
+/// Mask a key for display, keeping a recognizable prefix and the last 4 characters
+pub fn mask_api_key(key: &str) -> String {
+    let chars: Vec<char> = key.chars().collect();
+    if chars.len() < 12 {
+        return "****".to_string();
     }
-    Ok(())
+    let prefix: String = if key.starts_with("sk-") {
+        "sk-".to_string()
+    } else {
+        chars[..4].iter().collect()
+    };
+    let suffix: String = chars[chars.len() - 4..].iter().collect();
+    format!("{}****{}", prefix, suffix)
 }
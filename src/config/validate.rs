@@ -0,0 +1,423 @@
+//! Diagnostics for `config.toml`: parse errors with line/column and
+//! near-miss suggestions, plus semantic checks serde can't express.
+
+use std::fmt;
+use regex::Regex;
+
+use super::types::{known_models, Config, ProjectConfig, Provider, SearchEngine};
+use crate::cli::args::ContextFlag;
+use crate::context::{compress, CONTEXT_KINDS};
+use crate::core::schedule;
+use crate::utils::i18n::Language;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// 1-based line and column, when the problem can be located
+    pub position: Option<(usize, usize)>,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        match self.position {
+            Some((line, column)) => write!(f, "{}:{}: {}: {}", line, column, severity, self.message),
+            None => write!(f, "{}: {}", severity, self.message),
+        }
+    }
+}
+
+/// Validate config file contents, returning every problem found
+pub fn validate_str(contents: &str) -> Vec<Diagnostic> {
+    match toml::from_str::<Config>(contents) {
+        Ok(config) => check_values(&config, contents),
+        Err(e) => vec![describe_parse_error(contents, &e)],
+    }
+}
+
+/// Validate a project's `.q.toml`, which comes with a repository and so
+/// can't turn on context from outside it
+pub fn validate_project_str(contents: &str) -> Vec<Diagnostic> {
+    let project = match toml::from_str::<ProjectConfig>(contents) {
+        Ok(project) => project,
+        Err(e) => return vec![describe_parse_error(contents, &e)],
+    };
+    project.default_context.iter()
+        .filter(|flag| !ContextFlag::PROJECT_SCOPED.contains(flag))
+        .map(|flag| {
+            let name = serde_json::to_value(flag).ok().and_then(|value| value.as_str().map(str::to_string)).unwrap_or_default();
+            Diagnostic {
+                severity: Severity::Error,
+                position: locate(contents, "default_context"),
+                message: format!(
+                    "`{}` can't be in a project's default_context, since it reads from outside the repository; \
+                     a project can turn on here, repo, diff, cargo, and docker. Put it in your own config's default_context instead",
+                    name
+                ),
+            }
+        })
+        .collect()
+}
+
+/// Turn a toml/serde error into a located diagnostic with a suggestion
+pub fn describe_parse_error(contents: &str, error: &toml::de::Error) -> Diagnostic {
+    let position = error.span().map(|span| line_column(contents, span.start));
+    let mut message = error.message().trim().to_string();
+
+    let unknown = Regex::new(r"unknown (?:field|variant) `([^`]*)`, expected (?:one of )?(.*)")
+        .expect("valid regex");
+    if let Some(caps) = unknown.captures(&message) {
+        let expected: Vec<&str> = caps[2]
+            .split(", ")
+            .flat_map(|s| s.split(" or "))
+            .map(|s| s.trim_matches('`'))
+            .collect();
+        if let Some(suggestion) = suggest(&caps[1], &expected) {
+            message.push_str(&format!(" (did you mean `{}`?)", suggestion));
+        }
+    }
+
+    Diagnostic { severity: Severity::Error, position, message }
+}
+
+fn check_values(config: &Config, contents: &str) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+
+    let mut model_tables = vec![("settings.models".to_string(), &config.settings.models)];
+    for (name, profile) in &config.profiles {
+        model_tables.push((format!("profiles.{}.models", name), &profile.models));
+    }
+
+    for (table, models) in model_tables {
+        for (provider, model) in models {
+            let position = locate(contents, provider);
+            match Provider::try_from(provider.as_str()) {
+                Err(_) => {
+                    let names: Vec<&str> = Provider::ALL.iter().map(|p| p.as_str()).collect();
+                    let mut message = format!("unknown provider `{}` in [{}]", provider, table);
+                    if let Some(suggestion) = suggest(provider, &names) {
+                        message.push_str(&format!(" (did you mean `{}`?)", suggestion));
+                    }
+                    diagnostics.push(Diagnostic { severity: Severity::Error, position, message });
+                }
+                Ok(p) if !known_models(p).is_empty() && !known_models(p).contains(&model.as_str()) => {
+                    let mut message = format!("unrecognized {} model `{}` in [{}]", p, model, table);
+                    if let Some(suggestion) = suggest(model, known_models(p)) {
+                        message.push_str(&format!(" (did you mean `{}`?)", suggestion));
+                    }
+                    diagnostics.push(Diagnostic { severity: Severity::Warning, position, message });
+                }
+                Ok(_) => {}
+            }
+        }
+    }
+
+    if !(0.0..=2.0).contains(&config.settings.temperature) {
+        diagnostics.push(Diagnostic {
+            severity: Severity::Error,
+            position: locate(contents, "temperature"),
+            message: format!("temperature {} is outside the supported range 0.0-2.0", config.settings.temperature),
+        });
+    }
+
+    for (provider, temperature) in &config.settings.temperatures {
+        let position = locate(contents, provider);
+        if Provider::try_from(provider.as_str()).is_err() {
+            let names: Vec<&str> = Provider::ALL.iter().map(|p| p.as_str()).collect();
+            let mut message = format!("unknown provider `{}` in [settings.temperatures]", provider);
+            if let Some(suggestion) = suggest(provider, &names) {
+                message.push_str(&format!(" (did you mean `{}`?)", suggestion));
+            }
+            diagnostics.push(Diagnostic { severity: Severity::Error, position, message });
+        } else if !(0.0..=2.0).contains(temperature) {
+            diagnostics.push(Diagnostic {
+                severity: Severity::Error,
+                position,
+                message: format!("{} temperature {} is outside the supported range 0.0-2.0", provider, temperature),
+            });
+        }
+    }
+
+    if let Some(profile) = &config.settings.default_profile {
+        if !config.profiles.contains_key(profile) {
+            diagnostics.push(Diagnostic {
+                severity: Severity::Error,
+                position: locate(contents, "default_profile"),
+                message: format!("default_profile `{}` is not defined under [profiles]", profile),
+            });
+        }
+    }
+
+    if let Some(language) = &config.settings.language {
+        if !Language::CODES.contains(&language.as_str()) {
+            diagnostics.push(Diagnostic {
+                severity: Severity::Error,
+                position: locate(contents, "language"),
+                message: format!("language `{}` is not one of {}", language, Language::CODES.join(", ")),
+            });
+        }
+    }
+
+    if config.settings.map_reduce_jobs == 0 {
+        diagnostics.push(Diagnostic {
+            severity: Severity::Error,
+            position: locate(contents, "map_reduce_jobs"),
+            message: "map_reduce_jobs must be at least 1".to_string(),
+        });
+    }
+
+    if let Some(web) = &config.web {
+        if web.engine == SearchEngine::Searxng && web.url.is_none() {
+            diagnostics.push(Diagnostic {
+                severity: Severity::Error,
+                position: locate(contents, "engine"),
+                message: "[web] engine `searxng` needs the instance `url`".to_string(),
+            });
+        }
+    }
+
+    if config.consensus.models.len() == 1 {
+        diagnostics.push(Diagnostic {
+            severity: Severity::Error,
+            position: locate(contents, "models"),
+            message: "[consensus] models needs at least two models to compare".to_string(),
+        });
+    }
+
+    let kinds = config.context.order.iter().map(|kind| (kind, "order"))
+        .chain(config.context.quotas.keys().map(|kind| (kind, kind.as_str())))
+        .chain(config.context.allow.keys().map(|kind| (kind, kind.as_str())))
+        .chain(config.context.compress.keys().map(|kind| (kind, kind.as_str())));
+    for (kind, key) in kinds.filter(|(kind, _)| !CONTEXT_KINDS.contains(&kind.as_str())) {
+        let mut message = format!("unknown context kind `{}` in [context]", kind);
+        if let Some(suggestion) = suggest(kind, CONTEXT_KINDS) {
+            message.push_str(&format!(" (did you mean `{}`?)", suggestion));
+        }
+        diagnostics.push(Diagnostic { severity: Severity::Error, position: locate(contents, key), message });
+    }
+
+    for (kind, level) in &config.context.compress {
+        if CONTEXT_KINDS.contains(&kind.as_str()) && compress::level_for(kind, *level) != *level {
+            diagnostics.push(Diagnostic {
+                severity: Severity::Error,
+                position: locate(contents, kind),
+                message: format!(
+                    "[context.compress] {} can only be \"whitespace\": dropping repeated lines or words would change what it says. \
+                     \"lines\" and \"words\" are for {}",
+                    kind,
+                    compress::LOSSY_KINDS.join(", ")
+                ),
+            });
+        }
+    }
+
+    for (name, schedule) in &config.schedules {
+        if let Err(e) = schedule::parse(&schedule.cron) {
+            diagnostics.push(Diagnostic {
+                severity: Severity::Error,
+                position: locate(contents, "cron"),
+                message: format!("[schedules.{}] cron `{}` is not a valid crontab schedule: {}", name, schedule.cron, e),
+            });
+        }
+    }
+
+    let names: Vec<&str> = Provider::ALL.iter().map(|p| p.as_str()).collect();
+    for (kind, providers) in &config.context.allow {
+        for provider in providers.iter().filter(|p| *p != "*" && Provider::try_from(p.as_str()).is_err()) {
+            let mut message = format!("unknown provider `{}` in [context.allow] {}", provider, kind);
+            if let Some(suggestion) = suggest(provider, &names) {
+                message.push_str(&format!(" (did you mean `{}`?)", suggestion));
+            }
+            diagnostics.push(Diagnostic { severity: Severity::Error, position: locate(contents, kind), message });
+        }
+    }
+
+    diagnostics
+}
+
+/// Closest candidate within a small edit distance, if any
+pub fn suggest<'a>(input: &str, candidates: &[&'a str]) -> Option<&'a str> {
+    candidates.iter()
+        .map(|candidate| (edit_distance(input, candidate), *candidate))
+        .filter(|(distance, candidate)| *distance > 0 && *distance <= (candidate.len() / 3).max(2))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == *cb { 0 } else { 1 };
+            current.push((previous[j] + cost).min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+fn line_column(contents: &str, offset: usize) -> (usize, usize) {
+    let before = &contents[..offset.min(contents.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.len() - before.rfind('\n').map_or(0, |i| i + 1) + 1;
+    (line, column)
+}
+
+/// Position of the first line that assigns `key`
+fn locate(contents: &str, key: &str) -> Option<(usize, usize)> {
+    contents.lines().enumerate().find_map(|(i, line)| {
+        let trimmed = line.trim_start();
+        let rest = trimmed.strip_prefix(key)
+            .or_else(|| trimmed.strip_prefix(&format!("\"{}\"", key)))?;
+        rest.trim_start().starts_with('=')
+            .then(|| (i + 1, line.len() - trimmed.len() + 1))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_valid_config() {
+        let contents = "[settings]\ndefault_provider = \"openai\"\ntemperature = 0.2\n\n[settings.models]\nopenai = \"gpt-4o\"\n";
+        assert!(validate_str(contents).is_empty());
+    }
+
+    #[test]
+    fn test_unknown_key_suggestion() {
+        let contents = "[settings]\ntemprature = 0.2\n";
+        let diagnostics = validate_str(contents);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+        assert_eq!(diagnostics[0].position, Some((2, 1)));
+        assert!(diagnostics[0].message.contains("did you mean `temperature`?"));
+    }
+
+    #[test]
+    fn test_unknown_key_with_two_candidates() {
+        let diagnostics = validate_str("[api_keys]\nopenia = \"x\"\n");
+        assert!(diagnostics[0].message.contains("did you mean `openai`?"));
+    }
+
+    #[test]
+    fn test_type_mismatch_is_located() {
+        let contents = "[settings]\ntemperature = \"hot\"\n";
+        let diagnostics = validate_str(contents);
+        assert_eq!(diagnostics[0].position.map(|(line, _)| line), Some(2));
+    }
+
+    #[test]
+    fn test_unknown_model_warns() {
+        let contents = "[settings]\ntemperature = 0.7\n\n[settings.models]\nopenai = \"gpt-4oo\"\n";
+        let diagnostics = validate_str(contents);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Warning);
+        assert_eq!(diagnostics[0].position, Some((5, 1)));
+        assert!(diagnostics[0].message.contains("did you mean `gpt-4o`?"));
+    }
+
+    #[test]
+    fn test_unknown_provider_in_models() {
+        let contents = "[settings]\ntemperature = 0.7\n\n[settings.models]\nopanai = \"gpt-4o\"\n";
+        let diagnostics = validate_str(contents);
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+        assert!(diagnostics[0].message.contains("did you mean `openai`?"));
+    }
+
+    #[test]
+    fn test_unknown_language() {
+        assert!(validate_str("[settings]\nlanguage = \"ja\"\n").is_empty());
+        let diagnostics = validate_str("[settings]\nlanguage = \"jp\"\n");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].position, Some((2, 1)));
+        assert!(diagnostics[0].message.contains("not one of en, ja"));
+    }
+
+    #[test]
+    fn test_map_reduce_jobs() {
+        assert!(validate_str("[settings]\nmap_reduce_jobs = 8\n").is_empty());
+        let diagnostics = validate_str("[settings]\nmap_reduce_jobs = 0\n");
+        assert_eq!(diagnostics[0].message, "map_reduce_jobs must be at least 1");
+        assert_eq!(diagnostics[0].position, Some((2, 1)));
+    }
+
+    #[test]
+    fn test_searxng_needs_url() {
+        let diagnostics = validate_str("[web]\nengine = \"searxng\"\n");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].position, Some((2, 1)));
+        assert!(diagnostics[0].message.contains("needs the instance `url`"));
+        assert!(validate_str("[web]\nengine = \"brave\"\n").is_empty());
+    }
+
+    #[test]
+    fn test_unknown_context_kind() {
+        let diagnostics = validate_str("[context]\norder = [\"file\", \"histroy\"]\n");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].position, Some((2, 1)));
+        assert!(diagnostics[0].message.contains("unknown context kind `histroy`"));
+        assert!(diagnostics[0].message.contains("did you mean `history`?"));
+        assert!(validate_str("[context]\nmax_bytes = 65536\n[context.quotas]\ndirectory = 8192\n").is_empty());
+        assert!(validate_str("[context.compress]\nlog = \"lines\"\nweb = \"words\"\n").is_empty());
+        assert_eq!(validate_str("[context.compress]\nlogs = \"lines\"\n")[0].message, "unknown context kind `logs` in [context] (did you mean `log`?)");
+        assert!(validate_str("[context.compress]\nfile = \"whitespace\"\n").is_empty());
+        let diagnostics = validate_str("[context.compress]\ndiff = \"lines\"\n");
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].message.starts_with("[context.compress] diff can only be \"whitespace\""));
+    }
+
+    #[test]
+    fn test_context_allow_policy() {
+        assert!(validate_str("[context.allow]\nfile = [\"openai\"]\nhistory = [\"*\"]\n").is_empty());
+        let diagnostics = validate_str("[context.allow]\nfile = [\"opneai\"]\n");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].position, Some((2, 1)));
+        assert!(diagnostics[0].message.contains("did you mean `openai`?"));
+    }
+
+    #[test]
+    fn test_consensus_needs_two_models() {
+        let diagnostics = validate_str("[consensus]\nmodels = [\"openai/gpt-4o\"]\n");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].position, Some((2, 1)));
+        assert!(validate_str("[consensus]\nmodels = [\"openai/gpt-4o\", \"gemini/gemini-2.5-flash\"]\njudge = \"gpt-4o\"\n").is_empty());
+    }
+
+    #[test]
+    fn test_schedule_cron() {
+        assert!(validate_str("[schedules.tips]\nprompt = \"a git tip\"\ncron = \"0 8 * * 1-5\"\n").is_empty());
+        let diagnostics = validate_str("[schedules.tips]\nprompt = \"a git tip\"\ncron = \"every morning\"\n");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].position, Some((3, 1)));
+        assert!(diagnostics[0].message.starts_with("[schedules.tips] cron `every morning` is not a valid crontab schedule"));
+    }
+
+    #[test]
+    fn test_project_context_stays_in_the_repository() {
+        assert!(validate_project_str("default_context = [\"here\", \"repo\", \"diff\", \"cargo\", \"docker\"]\n").is_empty());
+        let diagnostics = validate_project_str("# shared defaults\n\ndefault_context = [\"diff\", \"hist\", \"sys\"]\n");
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].position, Some((3, 1)));
+        assert!(diagnostics[0].message.starts_with("`hist` can't be in a project's default_context"));
+        assert!(diagnostics[1].message.starts_with("`sys` can't"));
+    }
+
+    #[test]
+    fn test_suggest() {
+        assert_eq!(suggest("temprature", &["temperature", "models"]), Some("temperature"));
+        assert_eq!(suggest("xyz", &["temperature", "models"]), None);
+    }
+}
@@ -0,0 +1,480 @@
+//! Combines the context blocks gathered for a prompt under one byte budget.
+//!
+//! Every block first gets an equal floor so none is starved, then the rest
+//! of the budget goes to blocks in priority order. Per-kind quotas cap a
+//! block regardless of how much budget is left. An `allow` policy keeps
+//! kinds of context from providers that may not receive them. Kinds listed
+//! in `compress` are compressed (see `compress`) before any of this. Source files are cut at
+//! item boundaries where possible (see `code`), logs keep their newest
+//! lines, command output loses its middle, and everything else is cut at a line.
+//! A block without room for even the note saying it was cut is left out.
+
+use std::collections::HashMap;
+
+use super::compress::{self, Compression};
+use super::{file, log, output, ContextData, ContextError, ContextResult, CONTEXT_KINDS};
+use crate::config::types::ContextSettings;
+
+/// Total context bytes sent with a prompt (about 32k tokens)
+pub const DEFAULT_MAX_BYTES: usize = 128 * 1024;
+
+pub struct ContextAssembler {
+    max_bytes: usize,
+    order: Vec<String>,
+    quotas: HashMap<String, usize>,
+    allow: HashMap<String, Vec<String>>,
+    compression: HashMap<String, Compression>,
+    blocks: Vec<(&'static str, String)>,
+}
+
+/// The combined context, plus a note for every block that was compressed or cut
+#[derive(Debug, PartialEq)]
+pub struct AssembledContext {
+    pub content: String,
+    pub compressed: Vec<String>,
+    pub truncated: Vec<String>,
+}
+
+impl ContextAssembler {
+    pub fn new(max_bytes: usize) -> Self {
+        Self {
+            max_bytes,
+            order: CONTEXT_KINDS.iter().map(|kind| kind.to_string()).collect(),
+            quotas: HashMap::new(),
+            allow: HashMap::new(),
+            compression: HashMap::new(),
+            blocks: Vec::new(),
+        }
+    }
+
+    /// Budget, priority, and quotas from the `[context]` config section
+    pub fn from_settings(settings: &ContextSettings) -> Self {
+        let mut assembler = Self::new(settings.max_bytes.unwrap_or(DEFAULT_MAX_BYTES));
+        if !settings.order.is_empty() {
+            assembler = assembler.with_order(&settings.order);
+        }
+        for (kind, quota) in &settings.quotas {
+            assembler = assembler.with_quota(kind, *quota);
+        }
+        for (kind, providers) in &settings.allow {
+            assembler = assembler.with_allowed(kind, providers);
+        }
+        for (kind, level) in &settings.compress {
+            assembler = assembler.with_compression(kind, *level);
+        }
+        assembler
+    }
+
+    /// Kinds from highest to lowest priority. Kinds left out keep their
+    /// default order after the listed ones.
+    pub fn with_order(mut self, order: &[String]) -> Self {
+        let mut combined = order.to_vec();
+        combined.extend(self.order.into_iter().filter(|kind| !order.contains(kind)));
+        self.order = combined;
+        self
+    }
+
+    /// Cap one kind of context at `bytes`
+    pub fn with_quota(mut self, kind: &str, bytes: usize) -> Self {
+        self.quotas.insert(kind.to_string(), bytes);
+        self
+    }
+
+    /// Send `kind` only to these providers (`*` for any)
+    pub fn with_allowed(mut self, kind: &str, providers: &[String]) -> Self {
+        self.allow.insert(kind.to_string(), providers.to_vec());
+        self
+    }
+
+    /// Compress `kind` to `level` before fitting it, or only its whitespace
+    /// unless it's one of `compress::LOSSY_KINDS`
+    pub fn with_compression(mut self, kind: &str, level: Compression) -> Self {
+        self.compression.insert(kind.to_string(), compress::level_for(kind, level));
+        self
+    }
+
+    /// Refuse, naming each kind, when blocks may not go to `provider`
+    pub fn check_allowed(&self, provider: &str) -> ContextResult<()> {
+        let kinds: Vec<&str> = self.blocks.iter().map(|(kind, _)| *kind).collect();
+        self.check_kinds(&kinds, provider)
+    }
+
+    /// Refuse, naming each kind, when any of `kinds` may not go to `provider`:
+    /// for subcommands that gather their own context rather than adding blocks
+    pub fn check_kinds(&self, kinds: &[&str], provider: &str) -> ContextResult<()> {
+        let mut refused: Vec<String> = Vec::new();
+        for kind in kinds {
+            let Some(allowed) = self.allow.get(*kind) else {
+                continue;
+            };
+            if allowed.iter().any(|name| name == "*" || name.eq_ignore_ascii_case(provider)) {
+                continue;
+            }
+            let reason = if allowed.is_empty() {
+                format!("{} context may not be sent to any provider", kind)
+            } else {
+                format!("{} context may only be sent to {}", kind, allowed.join(", "))
+            };
+            if !refused.contains(&reason) {
+                refused.push(reason);
+            }
+        }
+        if refused.is_empty() {
+            return Ok(());
+        }
+        Err(ContextError::NotAllowed(format!(
+            "{}, not {}. Leave that context out or choose an allowed provider with -P.",
+            refused.join("; "),
+            provider
+        )))
+    }
+
+    pub fn add(&mut self, data: ContextData) {
+        self.blocks.push((data.context_type.name(), data.content));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    /// The total byte budget
+    pub fn max_bytes(&self) -> usize {
+        self.max_bytes
+    }
+
+    /// Each block's kind and content, in the order they were added
+    pub fn blocks(&self) -> impl Iterator<Item = (&'static str, &str)> {
+        self.blocks.iter().map(|(kind, content)| (*kind, content.as_str()))
+    }
+
+    /// Take out the blocks too big to send whole even on their own: over their
+    /// kind's quota or the whole budget once compressed. Returns their content.
+    pub fn take_oversized(&mut self) -> Vec<String> {
+        let mut oversized = Vec::new();
+        self.blocks.retain(|(kind, content)| {
+            let level = self.compression.get(*kind).copied().unwrap_or_default();
+            let compressed = compress::compress(content, level);
+            let room = self.quotas.get(*kind).copied().unwrap_or(usize::MAX).min(self.max_bytes);
+            if compressed.len() <= room {
+                return true;
+            }
+            oversized.push(compressed);
+            false
+        });
+        oversized
+    }
+
+    /// Drop the blocks at these positions in `blocks()`
+    pub fn remove(&mut self, positions: &[usize]) {
+        let mut position = 0;
+        self.blocks.retain(|_| {
+            position += 1;
+            !positions.contains(&(position - 1))
+        });
+    }
+
+    pub fn assemble(mut self) -> AssembledContext {
+        let rank = |kind: &str| self.order.iter().position(|k| k == kind).unwrap_or(usize::MAX);
+        self.blocks.sort_by_key(|(kind, _)| rank(kind));
+
+        let mut compressed = Vec::new();
+        for (kind, block) in &mut self.blocks {
+            let level = self.compression.get(*kind).copied().unwrap_or_default();
+            let smaller = compress::compress(block, level);
+            if smaller.len() < block.len() {
+                compressed.push(format!("{} context compressed from {} to {} bytes", kind, block.len(), smaller.len()));
+                *block = smaller;
+            }
+        }
+
+        let caps: Vec<usize> = self.blocks.iter()
+            .map(|(kind, content)| content.len().min(self.quotas.get(*kind).copied().unwrap_or(usize::MAX)))
+            .collect();
+
+        // An equal floor for every block, then the rest by priority
+        let floor = self.max_bytes / (2 * self.blocks.len().max(1));
+        let mut allocations: Vec<usize> = caps.iter().map(|cap| (*cap).min(floor)).collect();
+        let mut left = self.max_bytes.saturating_sub(allocations.iter().sum());
+        for (allocation, cap) in allocations.iter_mut().zip(&caps) {
+            let extra = (cap - *allocation).min(left);
+            *allocation += extra;
+            left -= extra;
+        }
+
+        let mut content = String::new();
+        let mut truncated = Vec::new();
+        for ((kind, block), allocation) in self.blocks.iter().zip(allocations) {
+            // Specialized cuts have fixed-size headers and notes; past a point
+            // they can't shrink any further, so fall back to a plain cut
+            let fits = |fitted: &String| fitted.len() <= allocation;
+            if block.len() <= allocation {
+                content.push_str(block);
+            } else if let Some(fitted) = (*kind == "file").then(|| file::fit_block(block, allocation)).flatten().filter(fits) {
+                content.push_str(&fitted);
+                truncated.push(format!(
+                    "{} context cut to {} of {} bytes at item boundaries, with an outline of the rest",
+                    kind, fitted.len(), block.len()
+                ));
+            } else if let Some(fitted) = matches!(*kind, "stdin" | "exec").then(|| output::fit_block(block, allocation)).flatten().filter(fits) {
+                content.push_str(&fitted);
+                truncated.push(format!("{} context cut to {} of {} bytes, keeping its start and end", kind, fitted.len(), block.len()));
+            } else if let Some(fitted) = (*kind == "log").then(|| log::fit_block(block, allocation)).flatten().filter(fits) {
+                content.push_str(&fitted);
+                truncated.push(format!("log context cut to its newest {} of {} bytes", fitted.len(), block.len()));
+            } else if let Some(cut) = truncate_block(block, allocation, kind) {
+                content.push_str(&cut);
+                truncated.push(format!("{} context cut to {} of {} bytes", kind, allocation, block.len()));
+            } else {
+                truncated.push(format!("{} context left out: its {} bytes had only {} bytes of room", kind, block.len(), allocation));
+                continue;
+            }
+            content.push_str("\n\n");
+        }
+        AssembledContext { content, compressed, truncated }
+    }
+}
+
+/// Cut `block` at a line boundary so it and the omission note fit in `max`
+/// bytes; `None` when not even the note fits
+fn truncate_block(block: &str, max: usize, kind: &str) -> Option<String> {
+    let note = |omitted: usize| format!("[... {} more bytes of {} context omitted]\n", omitted, kind);
+    let mut end = max.checked_sub(note(block.len()).len())?;
+    while !block.is_char_boundary(end) {
+        end -= 1;
+    }
+    let cut = block[..end].rfind('\n').map(|i| i + 1).unwrap_or(0);
+    Some(format!("{}{}", &block[..cut], note(block.len() - cut)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::ContextType;
+    use std::path::PathBuf;
+
+    fn data(context_type: ContextType, content: &str) -> ContextData {
+        ContextData { context_type, content: content.to_string() }
+    }
+
+    fn lines(count: usize) -> String {
+        (0..count).map(|i| format!("line {:04}\n", i)).collect()
+    }
+
+    #[test]
+    fn test_fits_in_priority_order() {
+        let mut assembler = ContextAssembler::new(DEFAULT_MAX_BYTES);
+        assembler.add(data(ContextType::Directory, "src/main.rs"));
+        assembler.add(data(ContextType::History, "cargo test"));
+        assembler.add(data(ContextType::File(PathBuf::from("a.rs")), "fn main() {}"));
+
+        let assembled = assembler.assemble();
+        assert_eq!(assembled.content, "fn main() {}\n\ncargo test\n\nsrc/main.rs\n\n");
+        assert!(assembled.truncated.is_empty());
+    }
+
+    #[test]
+    fn test_large_block_does_not_starve_others() {
+        let mut assembler = ContextAssembler::new(1000);
+        assembler.add(data(ContextType::File(PathBuf::from("big.log")), &lines(500)));
+        assembler.add(data(ContextType::Directory, &lines(30)));
+
+        let assembled = assembler.assemble();
+        // The directory gets its 250-byte floor; the file takes the rest
+        let (file, directory) = assembled.content.split_once("\n\n\n").unwrap();
+        assert!(file.len() < 750 && file.ends_with("more bytes of file context omitted]"));
+        assert!(directory.starts_with("line 0000\n") && directory.contains("more bytes of directory context omitted]"));
+        assert_eq!(assembled.truncated.len(), 2);
+    }
+
+    #[test]
+    fn test_source_file_cut_at_items() {
+        let source: String = (0..40).map(|i| format!("fn step_{:02}() {{\n    run({});\n}}\n\n", i, i)).collect();
+        let block = format!("File: src/steps.rs\nSize: {} bytes\n\nContent:\n{}\n", source.len(), source);
+        let mut assembler = ContextAssembler::new(1000);
+        assembler.add(data(ContextType::File(PathBuf::from("src/steps.rs")), &block));
+
+        let assembled = assembler.assemble();
+        assert!(assembled.content.len() <= 1002);
+        assert!(assembled.content.contains("fn step_05() {\n    run(5);\n}\n"));
+        assert!(assembled.content.contains("omitted; their signatures:]\n"));
+        assert!(assembled.content.ends_with("fn step_39()\n\n\n"));
+        assert!(assembled.truncated[0].contains("at item boundaries"));
+    }
+
+    #[test]
+    fn test_order_and_quotas() {
+        let settings = ContextSettings {
+            max_bytes: None,
+            order: vec!["history".to_string()],
+            quotas: HashMap::from([("history".to_string(), 100)]),
+            ..ContextSettings::default()
+        };
+        let mut assembler = ContextAssembler::from_settings(&settings);
+        assembler.add(data(ContextType::File(PathBuf::from("a.rs")), "fn main() {}"));
+        assembler.add(data(ContextType::History, &lines(50)));
+
+        let assembled = assembler.assemble();
+        assert!(assembled.content.starts_with("line 0000\n"));
+        assert!(assembled.content.ends_with("fn main() {}\n\n"));
+        assert_eq!(assembled.truncated, vec!["history context cut to 100 of 500 bytes"]);
+    }
+
+    #[test]
+    fn test_compressed_before_fitting() {
+        let repeated = "2024-05-01T10:00:00Z WARN pool exhausted\n".repeat(40);
+        let settings = ContextSettings {
+            compress: HashMap::from([("log".to_string(), Compression::Lines)]),
+            ..ContextSettings::default()
+        };
+        let mut assembler = ContextAssembler::from_settings(&settings).with_quota("log", 200);
+        assembler.add(data(ContextType::Log(PathBuf::from("app.log")), &format!("Log: app.log\n{}", repeated)));
+        assembler.add(data(ContextType::History, "ls   -la"));
+
+        let assembled = assembler.assemble();
+        assert_eq!(assembled.content, "Log: app.log\n2024-05-01T10:00:00Z WARN pool exhausted [seen 40 times]\n\n\nls   -la\n\n");
+        assert_eq!(assembled.compressed, vec!["log context compressed from 1653 to 70 bytes"]);
+        assert!(assembled.truncated.is_empty());
+    }
+
+    #[test]
+    fn test_truncate_block_keeps_whole_lines() {
+        let truncated = truncate_block(&lines(10), 60, "file");
+        assert_eq!(truncated.as_deref(), Some("line 0000\n[... 90 more bytes of file context omitted]\n"));
+        assert_eq!(truncate_block(&lines(10), 20, "file"), None);
+    }
+
+    #[test]
+    fn test_allow_policy() {
+        let mut assembler = ContextAssembler::new(1000)
+            .with_allowed("file", &["openai".to_string()])
+            .with_allowed("history", &["*".to_string()])
+            .with_allowed("system", &[]);
+        assembler.add(data(ContextType::File(PathBuf::from("a.rs")), "fn a() {}"));
+        assembler.add(data(ContextType::History, "ls"));
+        assembler.add(data(ContextType::Directory, "src/"));
+        assert!(assembler.check_allowed("openai").is_ok());
+        assert_eq!(
+            assembler.check_allowed("gemini").unwrap_err().to_string(),
+            "Not allowed by [context] allow: file context may only be sent to openai, not gemini. \
+             Leave that context out or choose an allowed provider with -P."
+        );
+
+        assembler.add(data(ContextType::System, "Linux"));
+        let error = assembler.check_allowed("openai").unwrap_err().to_string();
+        assert!(error.contains("system context may not be sent to any provider"));
+        assert!(assembler.check_kinds(&["directory", "history"], "gemini").is_ok());
+        assert!(assembler.check_kinds(&["file"], "gemini").is_err());
+    }
+
+    #[test]
+    fn test_remove_blocks() {
+        let mut assembler = ContextAssembler::new(1000);
+        assembler.add(data(ContextType::History, "ls"));
+        assembler.add(data(ContextType::Directory, "src/"));
+        assembler.add(data(ContextType::System, "Linux"));
+        assembler.remove(&[0, 2]);
+        assert_eq!(assembler.blocks().collect::<Vec<_>>(), [("directory", "src/")]);
+    }
+
+    mod properties {
+        use super::*;
+        use proptest::prelude::*;
+
+        /// Kinds generated, with the header each block starts with; `stdin`,
+        /// `exec`, and `log` get their own cuts
+        const KINDS: &[&str] = &["history", "directory", "system", "stdin", "exec", "log", "file"];
+
+        fn block(kind: &str, index: usize, lines: &[String]) -> ContextData {
+            let (context_type, header) = match kind {
+                "history" => (ContextType::History, format!("Recent shell history {}:", index)),
+                "directory" => (ContextType::Directory, format!("Directory listing {}:", index)),
+                "system" => (ContextType::System, format!("System information {}:", index)),
+                "stdin" => (ContextType::Stdin, format!("Piped input: {}", index)),
+                "exec" => (ContextType::Exec(format!("make {}", index)), format!("Output of `make {}` (exit status 0):", index)),
+                "log" => (ContextType::Log(PathBuf::from(format!("app{}.log", index))), format!("Log: app{}.log", index)),
+                _ => (ContextType::File(PathBuf::from(format!("notes{}.txt", index))), format!("File: notes{}.txt", index)),
+            };
+            let mut content = header;
+            for line in lines {
+                content.push('\n');
+                content.push_str(line);
+            }
+            ContextData { context_type, content }
+        }
+
+        /// The blocks in assembled content; they're joined by blank lines and
+        /// have none of their own
+        fn segments(content: &str) -> Vec<&str> {
+            content.split("\n\n").map(|segment| segment.trim_matches('\n')).filter(|segment| !segment.is_empty()).collect()
+        }
+
+        fn blocks() -> impl Strategy<Value = Vec<(usize, Vec<String>)>> {
+            prop::collection::vec((0..KINDS.len(), prop::collection::vec("[a-z][a-z ]{0,70}", 0..40)), 0..8)
+        }
+
+        fn order() -> impl Strategy<Value = Vec<String>> {
+            (Just(CONTEXT_KINDS.iter().map(|kind| kind.to_string()).collect::<Vec<_>>()).prop_shuffle(), 0..=CONTEXT_KINDS.len())
+                .prop_map(|(kinds, listed)| kinds[..listed].to_vec())
+        }
+
+        fn quotas() -> impl Strategy<Value = HashMap<String, usize>> {
+            prop::collection::hash_map(prop::sample::select(KINDS).prop_map(str::to_string), 0..2000usize, 0..3)
+        }
+
+        proptest! {
+            #[test]
+            fn test_assembled_context_invariants(max_bytes in 0..6000usize, generated in blocks(), order in order(), quotas in quotas()) {
+                let mut assembler = ContextAssembler::new(max_bytes).with_order(&order);
+                for (kind, quota) in &quotas {
+                    assembler = assembler.with_quota(kind, *quota);
+                }
+                let originals: Vec<ContextData> = generated.iter()
+                    .enumerate()
+                    .map(|(index, (kind, lines))| block(KINDS[*kind], index, lines))
+                    .collect();
+                for data in &originals {
+                    assembler.add(ContextData { context_type: data.context_type.clone(), content: data.content.clone() });
+                }
+                let priority = assembler.order.clone();
+                let rank = |kind: &str| priority.iter().position(|k| k == kind).unwrap();
+
+                let assembled = assembler.assemble();
+                let segments = segments(&assembled.content);
+
+                // Within budget, not counting the blank lines between blocks
+                prop_assert!(assembled.content.len() <= max_bytes + 2 * segments.len());
+
+                let mut last_rank = 0;
+                let mut verbatim = 0;
+                for segment in &segments {
+                    // Labeled: the block's own header, or a note naming what was cut
+                    let kind = match originals.iter().find(|data| segment.starts_with(data.content.lines().next().unwrap())) {
+                        Some(original) => {
+                            if *segment == original.content {
+                                verbatim += 1;
+                            } else {
+                                prop_assert!(segment.contains(" omitted"), "cut without a marker: {:?}", segment);
+                            }
+                            original.context_type.name()
+                        }
+                        None => {
+                            let kind = KINDS.iter().find(|kind| segment.ends_with(&format!(" of {} context omitted]", kind)));
+                            prop_assert!(kind.is_some(), "unlabeled block: {:?}", segment);
+                            kind.unwrap()
+                        }
+                    };
+                    prop_assert!(segment.len() <= quotas.get(kind).copied().unwrap_or(usize::MAX));
+
+                    // Higher-priority kinds first
+                    prop_assert!(rank(kind) >= last_rank, "{} out of order in {:?}", kind, order);
+                    last_rank = rank(kind);
+                }
+
+                // Every block not sent whole, and only those, is reported
+                prop_assert_eq!(assembled.truncated.len(), originals.len() - verbatim);
+                let left_out = assembled.truncated.iter().filter(|note| note.contains(" left out: ")).count();
+                prop_assert_eq!(segments.len() + left_out, originals.len());
+            }
+        }
+    }
+}
@@ -0,0 +1,269 @@
+use async_trait::async_trait;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use toml::{Table, Value};
+
+use super::{ContextConfig, ContextData, ContextError, ContextProvider, ContextResult, ContextType};
+use super::{format_path_for_display, validate_size};
+
+/// The Rust package around a directory: its name, features, dependencies
+/// (with the versions `Cargo.lock` resolved), and the workspace it belongs to
+pub struct CargoProvider {
+    path: PathBuf,
+    config: ContextConfig,
+}
+
+impl CargoProvider {
+    pub fn new(path: PathBuf, config: ContextConfig) -> Self {
+        Self { path, config }
+    }
+
+    fn format_summary(&self) -> ContextResult<String> {
+        let manifest_path = self.path.ancestors()
+            .map(|dir| dir.join("Cargo.toml"))
+            .find(|path| path.is_file())
+            .ok_or_else(|| ContextError::InvalidPath(format!(
+                "{} is not inside a Cargo package",
+                format_path_for_display(&self.path)
+            )))?;
+        let manifest = read_toml(&manifest_path)?;
+
+        // A member's workspace is the nearest manifest above it with [workspace]
+        let manifest_dir = manifest_path.parent().unwrap_or(Path::new("."));
+        let workspace = if manifest.contains_key("workspace") {
+            Some((manifest_path.clone(), manifest.clone()))
+        } else {
+            manifest_dir.ancestors().skip(1)
+                .map(|dir| dir.join("Cargo.toml"))
+                .filter(|path| path.is_file())
+                .find_map(|path| read_toml(&path).ok().filter(|toml| toml.contains_key("workspace")).map(|toml| (path, toml)))
+        };
+        let lock_dir = workspace.as_ref()
+            .and_then(|(path, _)| path.parent())
+            .unwrap_or(manifest_dir);
+        let locked = read_toml(&lock_dir.join("Cargo.lock")).map(|lock| locked_versions(&lock)).unwrap_or_default();
+
+        let output = summarize(&manifest_path, &manifest, workspace.as_ref().map(|(path, toml)| (path.as_path(), toml)), &locked);
+        validate_size(output.len(), self.config.max_size, "Cargo summary")?;
+        Ok(output)
+    }
+}
+
+fn read_toml(path: &Path) -> ContextResult<Table> {
+    std::fs::read_to_string(path)?
+        .parse()
+        .map_err(|e| ContextError::Other(format!("Failed to parse {}: {}", format_path_for_display(path), e)))
+}
+
+/// Versions of every package in a lockfile, by name
+fn locked_versions(lock: &Table) -> BTreeMap<String, Vec<String>> {
+    let mut versions: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for package in lock.get("package").and_then(Value::as_array).into_iter().flatten() {
+        if let (Some(name), Some(version)) = (
+            package.get("name").and_then(Value::as_str),
+            package.get("version").and_then(Value::as_str),
+        ) {
+            versions.entry(name.to_string()).or_default().push(version.to_string());
+        }
+    }
+    versions
+}
+
+fn summarize(
+    manifest_path: &Path,
+    manifest: &Table,
+    workspace: Option<(&Path, &Table)>,
+    locked: &BTreeMap<String, Vec<String>>,
+) -> String {
+    let mut output = String::new();
+    match manifest.get("package") {
+        Some(package) => {
+            let field = |key: &str| package.get(key).and_then(Value::as_str);
+            output.push_str(&format!(
+                "Cargo package: {} {}",
+                field("name").unwrap_or("?"),
+                field("version").unwrap_or("(version from workspace)")
+            ));
+            if let Some(edition) = field("edition") {
+                output.push_str(&format!(" (edition {})", edition));
+            }
+            output.push('\n');
+        }
+        None => output.push_str("Cargo workspace (virtual manifest)\n"),
+    }
+    output.push_str(&format!("Manifest: {}\n", format_path_for_display(manifest_path)));
+
+    if let Some((path, workspace)) = workspace {
+        let members: Vec<&str> = workspace.get("workspace")
+            .and_then(|workspace| workspace.get("members"))
+            .and_then(Value::as_array)
+            .map(|members| members.iter().filter_map(Value::as_str).collect())
+            .unwrap_or_default();
+        output.push_str(&format!("Workspace: {}", format_path_for_display(path)));
+        if !members.is_empty() {
+            output.push_str(&format!(" (members: {})", members.join(", ")));
+        }
+        output.push('\n');
+        if let Some(shared) = workspace.get("workspace").and_then(|workspace| workspace.get("dependencies")).and_then(Value::as_table) {
+            push_dependencies(&mut output, "Workspace dependencies", shared, locked);
+        }
+    }
+
+    if let Some(features) = manifest.get("features").and_then(Value::as_table) {
+        output.push_str("\nFeatures:\n");
+        for (name, enables) in features {
+            let enables: Vec<&str> = enables.as_array().into_iter().flatten().filter_map(Value::as_str).collect();
+            output.push_str(&format!("  {} = [{}]\n", name, enables.join(", ")));
+        }
+    }
+    for (key, title) in [
+        ("dependencies", "Dependencies"),
+        ("dev-dependencies", "Dev dependencies"),
+        ("build-dependencies", "Build dependencies"),
+    ] {
+        if let Some(dependencies) = manifest.get(key).and_then(Value::as_table) {
+            push_dependencies(&mut output, title, dependencies, locked);
+        }
+    }
+    output
+}
+
+/// `name requirement (details) -> locked version`, one per line
+fn push_dependencies(output: &mut String, title: &str, dependencies: &Table, locked: &BTreeMap<String, Vec<String>>) {
+    if dependencies.is_empty() {
+        return;
+    }
+    output.push_str(&format!("\n{}:\n", title));
+    for (name, spec) in dependencies {
+        let mut line = format!("  {}", name);
+        let mut details = Vec::new();
+        let mut package = name.as_str();
+        match spec {
+            Value::String(version) => line.push_str(&format!(" {}", version)),
+            Value::Table(spec) => {
+                let get = |key: &str| spec.get(key).and_then(Value::as_str);
+                if let Some(version) = get("version") {
+                    line.push_str(&format!(" {}", version));
+                }
+                if let Some(renamed) = get("package") {
+                    package = renamed;
+                    details.push(format!("package {}", renamed));
+                }
+                if spec.get("workspace").and_then(Value::as_bool) == Some(true) {
+                    details.push("from workspace".to_string());
+                }
+                if let Some(path) = get("path") {
+                    details.push(format!("path {}", path));
+                }
+                if let Some(git) = get("git") {
+                    details.push(format!("git {}", git));
+                }
+                if spec.get("optional").and_then(Value::as_bool) == Some(true) {
+                    details.push("optional".to_string());
+                }
+                if spec.get("default-features").and_then(Value::as_bool) == Some(false) {
+                    details.push("no default features".to_string());
+                }
+                let features: Vec<&str> = spec.get("features").and_then(Value::as_array)
+                    .into_iter().flatten().filter_map(Value::as_str).collect();
+                if !features.is_empty() {
+                    details.push(format!("features: {}", features.join(", ")));
+                }
+            }
+            _ => {}
+        }
+        if !details.is_empty() {
+            line.push_str(&format!(" ({})", details.join("; ")));
+        }
+        if let Some(versions) = locked.get(package) {
+            line.push_str(&format!(" -> {}", versions.join(", ")));
+        }
+        output.push_str(&line);
+        output.push('\n');
+    }
+}
+
+#[async_trait]
+impl ContextProvider for CargoProvider {
+    fn context_type(&self) -> ContextType {
+        ContextType::Cargo
+    }
+
+    async fn get_context(&self) -> ContextResult<ContextData> {
+        let content = self.format_summary()?;
+
+        Ok(ContextData {
+            context_type: self.context_type(),
+            content,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_workspace_member() {
+        let temp_dir = tempdir().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("crates/app/src")).unwrap();
+        fs::write(root.join("Cargo.toml"), r#"
+[workspace]
+members = ["crates/app", "crates/core"]
+
+[workspace.dependencies]
+serde = { version = "1", features = ["derive"] }
+"#).unwrap();
+        fs::write(root.join("crates/app/Cargo.toml"), r#"
+[package]
+name = "app"
+version = "0.2.0"
+edition = "2021"
+
+[features]
+default = ["tls"]
+tls = ["dep:rustls"]
+
+[dependencies]
+serde = { workspace = true }
+rustls = { version = "0.23", optional = true, default-features = false }
+core = { path = "../core" }
+
+[dev-dependencies]
+tempfile = "3"
+"#).unwrap();
+        fs::write(root.join("Cargo.lock"), r#"
+version = 3
+
+[[package]]
+name = "serde"
+version = "1.0.210"
+
+[[package]]
+name = "rustls"
+version = "0.23.12"
+"#).unwrap();
+
+        let provider = CargoProvider::new(root.join("crates/app/src"), ContextConfig::default());
+        let content = provider.get_context().await.unwrap().content;
+
+        assert!(content.starts_with("Cargo package: app 0.2.0 (edition 2021)\n"));
+        assert!(content.contains("(members: crates/app, crates/core)\n"));
+        assert!(content.contains("\nWorkspace dependencies:\n  serde 1 (features: derive) -> 1.0.210\n"));
+        assert!(content.contains("\nFeatures:\n  default = [tls]\n  tls = [dep:rustls]\n"));
+        assert!(content.contains("  rustls 0.23 (optional; no default features) -> 0.23.12\n"));
+        assert!(content.contains("  core (path ../core)\n"));
+        assert!(content.contains("\nDev dependencies:\n  tempfile 3\n"));
+    }
+
+    #[tokio::test]
+    async fn test_not_a_package() {
+        let temp_dir = tempdir().unwrap();
+        let provider = CargoProvider::new(temp_dir.path().to_path_buf(), ContextConfig::default());
+        assert!(matches!(provider.get_context().await, Err(ContextError::InvalidPath(_))));
+    }
+}
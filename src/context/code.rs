@@ -0,0 +1,236 @@
+//! Language-aware cutting of source files that don't fit the context budget.
+//!
+//! tree-sitter splits a file into its top-level items (functions, types,
+//! impls, classes), each taking the comments and attributes above it. As
+//! many leading items as fit are kept whole, and the ones that don't are
+//! listed by signature so the model still sees the shape of the file.
+
+use std::path::Path;
+
+use tree_sitter::{Node, Parser};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Language {
+    Rust,
+    Python,
+    Go,
+    JavaScript,
+    TypeScript,
+    Tsx,
+}
+
+impl Language {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "rs" => Some(Language::Rust),
+            "py" | "pyi" => Some(Language::Python),
+            "go" => Some(Language::Go),
+            "js" | "jsx" | "mjs" | "cjs" => Some(Language::JavaScript),
+            "ts" | "mts" | "cts" => Some(Language::TypeScript),
+            "tsx" => Some(Language::Tsx),
+            _ => None,
+        }
+    }
+
+    fn grammar(self) -> tree_sitter::Language {
+        match self {
+            Language::Rust => tree_sitter_rust::LANGUAGE.into(),
+            Language::Python => tree_sitter_python::LANGUAGE.into(),
+            Language::Go => tree_sitter_go::LANGUAGE.into(),
+            Language::JavaScript => tree_sitter_javascript::LANGUAGE.into(),
+            Language::TypeScript => tree_sitter_typescript::LANGUAGE_TYPESCRIPT.into(),
+            Language::Tsx => tree_sitter_typescript::LANGUAGE_TSX.into(),
+        }
+    }
+}
+
+/// Node kinds listed in an outline; other items (imports, statements) are
+/// kept or dropped with their neighbours but never outlined
+const SYMBOLS: &[&str] = &[
+    // Rust
+    "function_item", "function_signature_item", "struct_item", "enum_item", "union_item", "trait_item",
+    "impl_item", "mod_item", "type_item", "const_item", "static_item", "macro_definition",
+    // Python
+    "function_definition", "class_definition", "decorated_definition",
+    // Go
+    "function_declaration", "method_declaration", "type_declaration",
+    // JavaScript and TypeScript
+    "class_declaration", "abstract_class_declaration", "generator_function_declaration", "method_definition",
+    "interface_declaration", "type_alias_declaration", "enum_declaration", "export_statement", "lexical_declaration",
+];
+
+/// Items whose members are outlined under them
+const CONTAINERS: &[&str] = &["impl_item", "trait_item", "mod_item", "class_definition", "class_declaration", "abstract_class_declaration"];
+
+/// One top-level item: where it ends in the source, and its outline lines
+struct Item {
+    end: usize,
+    outline: String,
+}
+
+/// `source` cut to at most `max` bytes at item boundaries, followed by the
+/// signatures of the items left out. `None` when the file can't be parsed or
+/// even the outline alone is too long.
+pub fn fit(source: &str, language: Language, max: usize) -> Option<String> {
+    if source.len() <= max {
+        return Some(source.to_string());
+    }
+    let items = items(source, language)?;
+    let note = |omitted: usize| format!("[... {} more items omitted; their signatures:]\n", omitted);
+
+    // outline_after[k]: bytes to outline items k.. when the first k are kept
+    let mut outline_after = vec![0; items.len() + 1];
+    for k in (0..items.len()).rev() {
+        outline_after[k] = outline_after[k + 1] + items[k].outline.len();
+    }
+    (0..items.len()).rev().find_map(|kept| {
+        let prefix = kept.checked_sub(1).map(|last| source[..items[last].end].trim_end()).unwrap_or("");
+        let prefix_len = if prefix.is_empty() { 0 } else { prefix.len() + 1 };
+        if prefix_len + note(items.len() - kept).len() + outline_after[kept] > max {
+            return None;
+        }
+        let mut output = String::new();
+        if !prefix.is_empty() {
+            output.push_str(prefix);
+            output.push('\n');
+        }
+        output.push_str(&note(items.len() - kept));
+        items[kept..].iter().for_each(|item| output.push_str(&item.outline));
+        Some(output)
+    })
+}
+
+/// Signatures of every item in `source`, one per line with members indented
+/// under their impl or class; `None` when the file can't be parsed
+pub fn outline(source: &str, language: Language) -> Option<String> {
+    Some(items(source, language)?.into_iter().map(|item| item.outline).collect())
+}
+
+/// The top-level items of `source`. Comments and attributes belong to the
+/// item after them, and the last item runs to the end of the file.
+fn items(source: &str, language: Language) -> Option<Vec<Item>> {
+    let mut parser = Parser::new();
+    parser.set_language(&language.grammar()).ok()?;
+    let tree = parser.parse(source, None)?;
+    let root = tree.root_node();
+
+    let mut cursor = root.walk();
+    let mut items: Vec<Item> = root.named_children(&mut cursor)
+        .filter(|node| !node.kind().contains("comment") && !node.kind().contains("attribute_item"))
+        .map(|node| Item { end: node.end_byte(), outline: outline_node(node, source, 0) })
+        .collect();
+    if let Some(last) = items.last_mut() {
+        last.end = source.len();
+    }
+    Some(items)
+}
+
+/// Signature lines for `node` and, for containers, its members
+fn outline_node(node: Node, source: &str, depth: usize) -> String {
+    if !SYMBOLS.contains(&node.kind()) {
+        return String::new();
+    }
+    let mut output = format!("{}{}\n", "    ".repeat(depth), signature(node, source));
+    let inner = unwrap(node);
+    if let (true, Some(body)) = (CONTAINERS.contains(&inner.kind()), inner.child_by_field_name("body")) {
+        let mut cursor = body.walk();
+        for member in body.named_children(&mut cursor) {
+            output.push_str(&outline_node(member, source, depth + 1));
+        }
+    }
+    output
+}
+
+/// The declaration inside `export` or a Python decorator
+fn unwrap(node: Node) -> Node {
+    let field = match node.kind() {
+        "export_statement" => "declaration",
+        "decorated_definition" => "definition",
+        _ => return node,
+    };
+    node.child_by_field_name(field).unwrap_or(node)
+}
+
+/// `node` up to its body, on one line; items without a body are shown by
+/// their first line
+fn signature(node: Node, source: &str) -> String {
+    let inner = unwrap(node);
+    let start = if node.kind() == "decorated_definition" { inner.start_byte() } else { node.start_byte() };
+    let text = match inner.child_by_field_name("body") {
+        Some(body) => &source[start..body.start_byte()],
+        None => source[start..node.end_byte()].lines().next().unwrap_or_default(),
+    };
+    text.split_whitespace().collect::<Vec<_>>().join(" ").trim_end_matches('{').trim_end().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RUST: &str = r#"use std::fmt;
+
+/// A point
+#[derive(Debug)]
+pub struct Point {
+    x: i32,
+}
+
+impl Point {
+    pub fn new(x: i32) -> Self {
+        Self { x }
+    }
+
+    fn double(&self) -> i32 {
+        self.x * 2
+    }
+}
+
+pub fn distance(a: &Point,
+                b: &Point) -> i32 {
+    (a.x - b.x).abs()
+}
+"#;
+
+    #[test]
+    fn test_fit_keeps_whole_items() {
+        assert_eq!(fit(RUST, Language::Rust, RUST.len()).as_deref(), Some(RUST));
+
+        let fitted = fit(RUST, Language::Rust, 250).unwrap();
+        assert!(fitted.len() <= 250);
+        assert!(fitted.starts_with("use std::fmt;\n\n/// A point\n#[derive(Debug)]\npub struct Point {\n    x: i32,\n}\n"));
+        assert!(fitted.ends_with(
+            "[... 2 more items omitted; their signatures:]\nimpl Point\n    pub fn new(x: i32) -> Self\n    \
+             fn double(&self) -> i32\npub fn distance(a: &Point, b: &Point) -> i32\n"
+        ));
+        assert_eq!(fit(RUST, Language::Rust, 20), None);
+    }
+
+    #[test]
+    fn test_outline_other_languages() {
+        let python = "import os\n\n@cache\ndef load(path):\n    return open(path).read()\n\nclass Store:\n    def get(self, key):\n        return None\n";
+        let fitted = fit(python, Language::Python, 100).unwrap();
+        assert!(fitted.ends_with("def load(path):\nclass Store:\n    def get(self, key):\n"), "{}", fitted);
+
+        let typescript = "export function run(args: string[]): number {\n  const count = args.length;\n  console.log(`running ${count} steps`);\n  return count;\n}\n\nexport interface Options {\n  verbose: boolean;\n}\n";
+        let fitted = fit(typescript, Language::TypeScript, 120).unwrap();
+        assert!(fitted.ends_with("export function run(args: string[]): number\nexport interface Options\n"), "{}", fitted);
+    }
+
+    #[test]
+    fn test_outline() {
+        assert_eq!(
+            outline(RUST, Language::Rust).unwrap(),
+            "pub struct Point\nimpl Point\n    pub fn new(x: i32) -> Self\n    fn double(&self) -> i32\n\
+             pub fn distance(a: &Point, b: &Point) -> i32\n"
+        );
+        let go = "package main\n\ntype Server struct {\n\tport int\n}\n\nfunc (s *Server) Run() error {\n\treturn nil\n}\n";
+        assert_eq!(outline(go, Language::Go).unwrap(), "type Server struct\nfunc (s *Server) Run() error\n");
+    }
+
+    #[test]
+    fn test_language_from_path() {
+        assert_eq!(Language::from_path(Path::new("src/main.rs")), Some(Language::Rust));
+        assert_eq!(Language::from_path(Path::new("app.tsx")), Some(Language::Tsx));
+        assert_eq!(Language::from_path(Path::new("notes.md")), None);
+    }
+}
@@ -0,0 +1,158 @@
+//! An optional pass that shrinks context blocks before they're fitted to the
+//! budget, so more of what matters reaches a small context window. It is in
+//! the spirit of LLMLingua's prompt compression, with plain heuristics in
+//! place of a model: squash whitespace, keep repeated lines once, and drop
+//! stopwords. `[context.compress]` sets how far each kind goes; nothing is
+//! compressed by default. A block's first line, its header, is left alone.
+//! Only output and prose (`LOSSY_KINDS`) go past whitespace: in source or a
+//! diff, a repeated line or a dropped word changes what it says.
+
+use std::collections::HashMap;
+
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+
+use super::log;
+
+/// How far to compress one kind of context; each level includes the ones before it
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    #[default]
+    Off,
+    /// Trailing spaces, runs of spaces inside a line, and runs of blank lines
+    Whitespace,
+    /// Lines seen earlier in the block, timestamps aside, kept once with a count
+    Lines,
+    /// Common English words that carry little meaning ("the", "of", "is")
+    Words,
+}
+
+/// Kinds that can be compressed to `lines` and `words`; others stop at `whitespace`
+pub const LOSSY_KINDS: &[&str] = &["log", "exec", "stdin", "web"];
+
+/// Dropped by `Compression::Words`, matched whole and case-insensitively
+const STOPWORDS: &[&str] = &[
+    "a", "an", "the", "and", "or", "but", "of", "to", "in", "on", "at", "by", "for", "with", "from",
+    "as", "is", "are", "was", "were", "be", "been", "being", "that", "this", "these", "those", "it",
+    "its", "there", "then", "so", "very", "just", "also", "which", "will", "would", "can", "could",
+];
+
+/// How far a block of `kind` is compressed when `level` is asked for
+pub fn level_for(kind: &str, level: Compression) -> Compression {
+    if LOSSY_KINDS.contains(&kind) {
+        level
+    } else {
+        level.min(Compression::Whitespace)
+    }
+}
+
+/// `block` compressed to `level`
+pub fn compress(block: &str, level: Compression) -> String {
+    if level == Compression::Off {
+        return block.to_string();
+    }
+    let (header, body) = block.split_once('\n').unwrap_or((block, ""));
+    let mut lines: Vec<String> = Vec::new();
+    for line in body.lines() {
+        let line = squash(line);
+        if line.is_empty() && lines.last().is_some_and(|last| last.is_empty()) {
+            continue;
+        }
+        lines.push(line);
+    }
+    if level >= Compression::Lines {
+        lines = once_each(lines);
+    }
+    if level >= Compression::Words {
+        lines = lines.iter().map(|line| without_stopwords(line)).collect();
+    }
+
+    let mut compressed = header.to_string();
+    for line in &lines {
+        compressed.push('\n');
+        compressed.push_str(line);
+    }
+    if block.ends_with('\n') && !compressed.ends_with('\n') {
+        compressed.push('\n');
+    }
+    compressed
+}
+
+/// `line` with its indentation kept and every other run of whitespace made one space
+fn squash(line: &str) -> String {
+    let text = line.trim_start();
+    let mut squashed = line[..line.len() - text.len()].to_string();
+    squashed.push_str(&text.split_whitespace().collect::<Vec<_>>().join(" "));
+    if text.is_empty() {
+        squashed.clear();
+    }
+    squashed
+}
+
+/// The first of each set of lines that match apart from a timestamp, noting
+/// how many there were; blank lines are kept as they are
+fn once_each(lines: Vec<String>) -> Vec<String> {
+    let now = Local::now();
+    let keys: Vec<String> = lines.iter().map(|line| log::without_timestamp(line.trim(), now)).collect();
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for key in keys.iter().filter(|key| !key.is_empty()) {
+        *counts.entry(key.as_str()).or_default() += 1;
+    }
+    let mut kept = Vec::new();
+    for (line, key) in lines.iter().zip(&keys) {
+        match counts.get(key.as_str()).copied() {
+            Some(0) => {}
+            Some(count) if count > 1 => kept.push(format!("{} [seen {} times]", line, count)),
+            _ => kept.push(line.clone()),
+        }
+        if let Some(count) = counts.get_mut(key.as_str()) {
+            *count = 0;
+        }
+    }
+    kept
+}
+
+/// `line` without its stopwords, unless that would leave nothing
+fn without_stopwords(line: &str) -> String {
+    let text = line.trim_start();
+    let words: Vec<&str> = text.split(' ')
+        .filter(|word| !STOPWORDS.iter().any(|stopword| word.eq_ignore_ascii_case(stopword)))
+        .collect();
+    if words.is_empty() {
+        return line.to_string();
+    }
+    format!("{}{}", &line[..line.len() - text.len()], words.join(" "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_whitespace() {
+        let block = "File: notes.txt\nfirst   line  \n\n\n\n    indented\tline\n";
+        assert_eq!(compress(block, Compression::Whitespace), "File: notes.txt\nfirst line\n\n    indented line\n");
+        assert_eq!(compress(block, Compression::Off), block);
+    }
+
+    #[test]
+    fn test_repeated_lines_kept_once() {
+        let block = "Log: app.log (last 200 lines)\n\
+                     2024-05-01T10:00:00Z WARN pool exhausted\n\
+                     2024-05-01T10:00:01Z INFO request ok\n\
+                     2024-05-01T10:00:02Z WARN pool exhausted\n";
+        assert_eq!(
+            compress(block, Compression::Lines),
+            "Log: app.log (last 200 lines)\n\
+             2024-05-01T10:00:00Z WARN pool exhausted [seen 2 times]\n\
+             2024-05-01T10:00:01Z INFO request ok\n"
+        );
+    }
+
+    #[test]
+    fn test_stopwords() {
+        let block = "Web results for the question:\nThe cache is a map of the keys to an entry.\n  It is\n";
+        assert_eq!(compress(block, Compression::Words), "Web results for the question:\ncache map keys entry.\n  It is\n");
+    }
+}
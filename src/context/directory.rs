@@ -1,55 +1,213 @@
 use async_trait::async_trait;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use walkdir::WalkDir;
 
 use super::{ContextConfig, ContextData, ContextError, ContextProvider, ContextResult, ContextType};
-use super::{format_path_for_display, should_include_path, validate_size};
+use super::{format_path_for_display, should_include_path};
+
+/// Entries listed before the walk stops
+const DEFAULT_MAX_ENTRIES: usize = 5000;
+/// How long the walk may take; network filesystems can be very slow
+const DEFAULT_TIME_BUDGET: Duration = Duration::from_secs(2);
 
 pub struct DirectoryProvider {
     path: PathBuf,
     config: ContextConfig,
+    max_entries: usize,
+    time_budget: Duration,
+    follow_links: bool,
+}
+
+/// Why a listing stopped early
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Truncation {
+    Size,
+    Entries,
+    Time,
+    Cancelled,
+}
+
+/// Sets the flag when the walk's future is dropped, so the blocking walk stops too
+struct CancelOnDrop(Arc<AtomicBool>);
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
 }
 
 impl DirectoryProvider {
     pub fn new(path: PathBuf, config: ContextConfig) -> Self {
-        Self { path, config }
+        Self {
+            path,
+            config,
+            max_entries: DEFAULT_MAX_ENTRIES,
+            time_budget: DEFAULT_TIME_BUDGET,
+            follow_links: false,
+        }
+    }
+
+    /// List the contents of symlinked directories. Links that resolve outside
+    /// the root and links that loop back on themselves are listed but never
+    /// followed. Without this, symlinks are listed with their target only.
+    pub fn with_follow_links(mut self, follow_links: bool) -> Self {
+        self.follow_links = follow_links;
+        self
     }
 
-    fn format_directory_listing(&self) -> ContextResult<String> {
+    /// Stop after this many entries
+    pub fn with_max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = max_entries;
+        self
+    }
+
+    /// Stop once the walk has taken this long
+    pub fn with_time_budget(mut self, time_budget: Duration) -> Self {
+        self.time_budget = time_budget;
+        self
+    }
+
+    /// Walk the directory on a blocking thread. If this future is dropped the
+    /// walk stops at the next entry.
+    async fn format_directory_listing(&self) -> ContextResult<String> {
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let _guard = CancelOnDrop(cancelled.clone());
+
+        let walk = Walk {
+            path: self.path.clone(),
+            config: self.config.clone(),
+            max_entries: self.max_entries,
+            deadline: Instant::now() + self.time_budget,
+            follow_links: self.follow_links,
+            cancelled,
+        };
+        tokio::task::spawn_blocking(move || walk.run())
+            .await
+            .map_err(|e| ContextError::Other(format!("Directory listing failed: {}", e)))?
+    }
+}
+
+/// The state a blocking directory walk needs
+struct Walk {
+    path: PathBuf,
+    config: ContextConfig,
+    max_entries: usize,
+    deadline: Instant,
+    follow_links: bool,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Walk {
+    fn run(&self) -> ContextResult<String> {
         let mut output = String::new();
-        let mut total_size = 0;
 
         // Add current directory header
         output.push_str(&format!("Directory listing for {}:\n\n", format_path_for_display(&self.path)));
 
+        // Links are checked against the resolved root so `..` and nested links can't escape it
+        let root = self.path.canonicalize().unwrap_or_else(|_| self.path.clone());
+
         // Walk the directory
-        let walker = WalkDir::new(&self.path)
+        let mut walker = WalkDir::new(&self.path)
             .min_depth(1)
             .max_depth(self.config.max_depth.unwrap_or(1))
-            .follow_links(false);
+            .follow_links(self.follow_links)
+            .into_iter();
 
-        for entry in walker {
-            let entry = entry.map_err(|e| ContextError::Other(e.to_string()))?;
-            let path = entry.path().to_path_buf();
-
-            if !should_include_path(&path, &self.config) {
-                continue;
+        let mut entries = 0;
+        let mut truncation = None;
+        while let Some(entry) = walker.next() {
+            if self.cancelled.load(Ordering::Relaxed) {
+                truncation = Some(Truncation::Cancelled);
+                break;
+            }
+            if Instant::now() >= self.deadline {
+                truncation = Some(Truncation::Time);
+                break;
+            }
+            if entries >= self.max_entries {
+                truncation = Some(Truncation::Entries);
+                break;
             }
 
-            // Format the entry
-            let relative_path = path.strip_prefix(&self.path)
-                .map_err(|_| ContextError::InvalidPath(format_path_for_display(&path)))?;
-            
-            let entry_str = format!("{}\n", relative_path.display());
-            total_size += entry_str.len();
+            let entry_str = match entry {
+                Ok(entry) => {
+                    let path = entry.path();
+                    if !should_include_path(path, &self.config) {
+                        if entry.file_type().is_dir() {
+                            walker.skip_current_dir();
+                        }
+                        continue;
+                    }
 
-            // Check size before adding
-            validate_size(total_size, self.config.max_size, "Directory listing")?;
+                    let relative = self.relative(path)?.display();
+                    if !entry.path_is_symlink() {
+                        format!("{}\n", relative)
+                    } else if !self.follow_links || path.canonicalize().is_ok_and(|target| target.starts_with(&root)) {
+                        format!("{} -> {}\n", relative, link_target(path))
+                    } else {
+                        if entry.file_type().is_dir() {
+                            walker.skip_current_dir();
+                        }
+                        format!("{} -> {} (outside the directory, not followed)\n", relative, link_target(path))
+                    }
+                }
+                // Following links surfaces cycles and dangling links as errors
+                Err(e) => {
+                    let path = match e.path() {
+                        Some(path) if is_symlink(path) => path,
+                        _ => return Err(ContextError::Other(e.to_string())),
+                    };
+                    if !should_include_path(path, &self.config) {
+                        continue;
+                    }
+                    let note = if e.loop_ancestor().is_some() { "symlink cycle, not followed" } else { "broken link" };
+                    format!("{} -> {} ({})\n", self.relative(path)?.display(), link_target(path), note)
+                }
+            };
+
+            // Stop before the listing outgrows the size limit
+            if output.len() + entry_str.len() > self.config.max_size {
+                truncation = Some(Truncation::Size);
+                break;
+            }
             output.push_str(&entry_str);
+            entries += 1;
         }
 
+        if let Some(truncation) = truncation {
+            output.push_str(&truncation_marker(truncation, entries));
+        }
         Ok(output)
     }
+
+    fn relative<'a>(&self, path: &'a Path) -> ContextResult<&'a Path> {
+        path.strip_prefix(&self.path)
+            .map_err(|_| ContextError::InvalidPath(format_path_for_display(path)))
+    }
+}
+
+fn is_symlink(path: &Path) -> bool {
+    path.symlink_metadata().is_ok_and(|metadata| metadata.file_type().is_symlink())
+}
+
+fn link_target(path: &Path) -> String {
+    std::fs::read_link(path)
+        .map(|target| target.display().to_string())
+        .unwrap_or_else(|_| "?".to_string())
+}
+
+fn truncation_marker(truncation: Truncation, entries: usize) -> String {
+    let reason = match truncation {
+        Truncation::Size => "size limit reached",
+        Truncation::Entries => "entry limit reached",
+        Truncation::Time => "time limit reached",
+        Truncation::Cancelled => "cancelled",
+    };
+    format!("[truncated listing: {} after {} entries]\n", reason, entries)
 }
 
 #[async_trait]
@@ -59,7 +217,7 @@ impl ContextProvider for DirectoryProvider {
     }
 
     async fn get_context(&self) -> ContextResult<ContextData> {
-        let content = self.format_directory_listing()?;
+        let content = self.format_directory_listing().await?;
         
         Ok(ContextData {
             context_type: self.context_type(),
@@ -71,6 +229,7 @@ impl ContextProvider for DirectoryProvider {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::context::qignore::QIgnore;
     use std::fs;
     use tempfile::tempdir;
 
@@ -89,6 +248,7 @@ mod tests {
             max_size: 1024,
             include_hidden: false,
             max_depth: Some(2),
+            ..ContextConfig::default()
         };
 
         let provider = DirectoryProvider::new(base_path.to_path_buf(), config);
@@ -99,6 +259,24 @@ mod tests {
         assert!(!context.content.contains(".hidden"));
     }
 
+    #[tokio::test]
+    async fn test_qignore() {
+        let temp_dir = tempdir().unwrap();
+        let base_path = temp_dir.path();
+        fs::create_dir_all(base_path.join("vendor/lib")).unwrap();
+        fs::write(base_path.join("vendor/lib/mod.rs"), "content").unwrap();
+        fs::write(base_path.join("Cargo.lock"), "content").unwrap();
+        fs::write(base_path.join("main.rs"), "content").unwrap();
+        fs::write(base_path.join(".qignore"), "vendor/\n").unwrap();
+
+        let ignore = QIgnore::load(base_path, &["*.lock".to_string()]).unwrap();
+        let config = ContextConfig { max_depth: Some(3), ignore, ..ContextConfig::default() };
+        let content = DirectoryProvider::new(base_path.to_path_buf(), config).get_context().await.unwrap().content;
+
+        assert!(content.contains("main.rs\n"));
+        assert!(!content.contains("vendor") && !content.contains("Cargo.lock"));
+    }
+
     #[tokio::test]
     async fn test_size_limit() {
         let temp_dir = tempdir().unwrap();
@@ -113,11 +291,81 @@ mod tests {
             max_size: 50, // Very small limit
             include_hidden: false,
             max_depth: Some(1),
+            ..ContextConfig::default()
         };
 
         let provider = DirectoryProvider::new(base_path.to_path_buf(), config);
-        let result = provider.get_context().await;
+        let context = provider.get_context().await.unwrap();
+
+        assert!(context.content.contains("[truncated listing: size limit reached after 0 entries]"));
+    }
+
+    #[tokio::test]
+    async fn test_entry_limit() {
+        let temp_dir = tempdir().unwrap();
+        for i in 0..20 {
+            fs::write(temp_dir.path().join(format!("file{}.txt", i)), "content").unwrap();
+        }
+
+        let provider = DirectoryProvider::new(temp_dir.path().to_path_buf(), ContextConfig::default())
+            .with_max_entries(5);
+        let context = provider.get_context().await.unwrap();
+
+        assert_eq!(context.content.matches(".txt").count(), 5);
+        assert!(context.content.ends_with("[truncated listing: entry limit reached after 5 entries]\n"));
+    }
+
+    #[tokio::test]
+    async fn test_time_budget() {
+        let temp_dir = tempdir().unwrap();
+        fs::write(temp_dir.path().join("file.txt"), "content").unwrap();
+
+        let provider = DirectoryProvider::new(temp_dir.path().to_path_buf(), ContextConfig::default())
+            .with_time_budget(Duration::ZERO);
+        let context = provider.get_context().await.unwrap();
+
+        assert!(context.content.contains("[truncated listing: time limit reached after 0 entries]"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_symlinks_listed_not_followed_by_default() {
+        let temp_dir = tempdir().unwrap();
+        let base_path = temp_dir.path();
+        fs::create_dir(base_path.join("real")).unwrap();
+        fs::write(base_path.join("real/inner.txt"), "content").unwrap();
+        std::os::unix::fs::symlink(base_path.join("real"), base_path.join("link")).unwrap();
+
+        let provider = DirectoryProvider::new(base_path.to_path_buf(), ContextConfig::default());
+        let context = provider.get_context().await.unwrap();
+
+        assert!(context.content.contains(&format!("link -> {}\n", base_path.join("real").display())));
+        assert!(!context.content.contains("link/inner.txt"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_follow_links_stays_inside_root() {
+        let temp_dir = tempdir().unwrap();
+        let outside = tempdir().unwrap();
+        let base_path = temp_dir.path();
+        fs::create_dir(base_path.join("real")).unwrap();
+        fs::write(base_path.join("real/inner.txt"), "content").unwrap();
+        fs::write(outside.path().join("secret.txt"), "content").unwrap();
+        std::os::unix::fs::symlink(base_path.join("real"), base_path.join("link")).unwrap();
+        std::os::unix::fs::symlink(outside.path(), base_path.join("escape")).unwrap();
+        std::os::unix::fs::symlink(base_path, base_path.join("real/loop")).unwrap();
+        std::os::unix::fs::symlink(base_path.join("missing"), base_path.join("dangling")).unwrap();
+
+        let config = ContextConfig { max_depth: Some(5), ..ContextConfig::default() };
+        let provider = DirectoryProvider::new(base_path.to_path_buf(), config).with_follow_links(true);
+        let content = provider.get_context().await.unwrap().content;
 
-        assert!(matches!(result, Err(ContextError::TooLarge(_))));
+        assert!(content.contains("link/inner.txt\n"));
+        assert!(content.contains("(outside the directory, not followed)"));
+        assert!(!content.contains("secret.txt"));
+        assert!(content.contains("real/loop -> "));
+        assert!(content.contains("(symlink cycle, not followed)"));
+        assert!(content.contains("(broken link)"));
     }
 }
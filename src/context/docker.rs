@@ -0,0 +1,215 @@
+use async_trait::async_trait;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::process::Command;
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use super::{ContextConfig, ContextData, ContextError, ContextProvider, ContextResult, ContextType};
+use super::{format_path_for_display, validate_size};
+
+/// Log lines taken from the end of a container's output
+const LOG_LINES: usize = 100;
+/// Compose and image files read from the directory, in the order Compose looks for them
+const FILES: &[&str] = &["compose.yaml", "compose.yml", "docker-compose.yml", "docker-compose.yaml", "Dockerfile"];
+/// Bytes read from each file; a Compose file longer than this is unusual
+const MAX_FILE_BYTES: usize = 16 * 1024;
+
+lazy_static! {
+    /// `KEY: value`, `KEY=value`, `- KEY=value`, and `ENV KEY=value` where the key names a secret
+    static ref SECRET_SETTING: Regex = Regex::new(
+        r#"(?im)^(\s*(?:-\s*|(?:ENV|ARG)\s+)?["']?[\w.-]*(?:password|passwd|secret|token|api_?key|private_?key|credentials?)[\w.-]*["']?\s*[:=]\s*)\S.*$"#
+    ).expect("valid regex");
+    /// The password in a URL's `user:password@`
+    static ref URL_PASSWORD: Regex = Regex::new(r"(\b[a-zA-Z][\w+.-]*://[^/\s:@]+:)[^/\s@]+@").expect("valid regex");
+    /// Query parameters that carry credentials: `?key=`, `&token=`, `access_token=`, ...
+    static ref URL_SECRET_PARAM: Regex = Regex::new(
+        r"(?i)([?&](?:[\w.-]*(?:key|token|secret|password|passwd|signature)|sig|auth)=)[^&#\s]+"
+    ).expect("valid regex");
+}
+
+/// Containerized setup around a directory: running containers, the recent
+/// logs of one container, and the directory's Compose file and Dockerfile
+#[derive(Clone)]
+pub struct DockerProvider {
+    path: PathBuf,
+    container: Option<String>,
+    config: ContextConfig,
+}
+
+impl DockerProvider {
+    pub fn new(path: PathBuf, config: ContextConfig) -> Self {
+        Self { path, container: None, config }
+    }
+
+    /// Also include the last log lines of `container`
+    pub fn with_container(mut self, container: Option<String>) -> Self {
+        self.container = container;
+        self
+    }
+
+    fn format_summary(&self) -> ContextResult<String> {
+        let mut output = String::new();
+        match docker(&["ps", "--format", "table {{.Names}}\t{{.Image}}\t{{.Status}}\t{{.Ports}}"]) {
+            Ok(Some(ps)) => output.push_str(&format!("Running containers (docker ps):\n{}\n", ps.trim_end())),
+            Ok(None) => output.push_str("Docker is not installed (docker not found in PATH)\n"),
+            Err(error) => output.push_str(&format!("docker ps failed: {}\n", error)),
+        }
+
+        if let Some(container) = &self.container {
+            let logs = redact(&container_logs(container)?);
+            output.push_str(&format!("\nLast {} log lines of {} (stdout and stderr):\n{}\n", LOG_LINES, container, logs.trim_end()));
+        }
+
+        for name in FILES {
+            let path = self.path.join(name);
+            let Ok(text) = std::fs::read_to_string(&path) else {
+                continue;
+            };
+            let mut text = redact(&text);
+            if text.len() > MAX_FILE_BYTES {
+                let mut end = MAX_FILE_BYTES;
+                while !text.is_char_boundary(end) {
+                    end -= 1;
+                }
+                text.truncate(end);
+                text.push_str("\n[... cut]");
+            }
+            output.push_str(&format!("\n{}:\n{}\n", format_path_for_display(&path), text.trim_end()));
+        }
+
+        validate_size(output.len(), self.config.max_size, "Docker")?;
+        Ok(output)
+    }
+}
+
+/// A docker command's stdout, `None` when docker isn't installed, or its error
+fn docker(args: &[&str]) -> Result<Option<String>, String> {
+    let output = match Command::new("docker").args(args).output() {
+        Ok(output) => output,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.to_string()),
+    };
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(stderr.lines().next().unwrap_or("unknown error").trim().to_string());
+    }
+    Ok(Some(String::from_utf8_lossy(&output.stdout).into_owned()))
+}
+
+/// `docker logs` writes the container's stdout and stderr to its own; with
+/// timestamps the two can be merged back into the order they were written
+fn container_logs(container: &str) -> ContextResult<String> {
+    let tail = LOG_LINES.to_string();
+    let output = Command::new("docker")
+        .args(["logs", "--timestamps", "--tail", &tail, container])
+        .output()
+        .map_err(|e| match e.kind() {
+            ErrorKind::NotFound => ContextError::Other("Docker is not installed (docker not found in PATH)".to_string()),
+            _ => ContextError::Other(format!("Couldn't run docker logs: {}", e)),
+        })?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !output.status.success() {
+        return Err(ContextError::Other(format!(
+            "docker logs {} failed: {}",
+            container,
+            stderr.lines().next().unwrap_or("unknown error").trim()
+        )));
+    }
+    Ok(merge_logs(&String::from_utf8_lossy(&output.stdout), &stderr, LOG_LINES))
+}
+
+/// The last `lines` of two timestamped streams, in time order. Docker's
+/// timestamps are fixed-width RFC 3339, so they sort as text.
+fn merge_logs(stdout: &str, stderr: &str, lines: usize) -> String {
+    let mut merged: Vec<&str> = stdout.lines().chain(stderr.lines()).collect();
+    merged.sort_by_key(|line| line.split_once(' ').map(|(timestamp, _)| timestamp).unwrap_or(line));
+    let skip = merged.len().saturating_sub(lines);
+    merged[skip..].join("\n")
+}
+
+/// Blanks the values of settings named like passwords, tokens, and keys,
+/// and the credentials in URLs
+fn redact(text: &str) -> String {
+    let text = SECRET_SETTING.replace_all(text, "${1}[redacted]");
+    let text = URL_PASSWORD.replace_all(&text, "${1}[redacted]@");
+    URL_SECRET_PARAM.replace_all(&text, "${1}[redacted]").into_owned()
+}
+
+#[async_trait]
+impl ContextProvider for DockerProvider {
+    fn context_type(&self) -> ContextType {
+        ContextType::Docker
+    }
+
+    async fn get_context(&self) -> ContextResult<ContextData> {
+        let provider = self.clone();
+        let content = tokio::task::spawn_blocking(move || provider.format_summary())
+            .await
+            .map_err(|e| ContextError::Other(format!("Capturing Docker context failed: {}", e)))??;
+
+        Ok(ContextData {
+            context_type: self.context_type(),
+            content,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_redact() {
+        let compose = "services:\n  db:\n    environment:\n      POSTGRES_PASSWORD: hunter2\n      - API_KEY=abc123\n      POSTGRES_USER: app\n    image: postgres:16\n";
+        let redacted = redact(compose);
+        assert!(redacted.contains("POSTGRES_PASSWORD: [redacted]"));
+        assert!(redacted.contains("- API_KEY=[redacted]"));
+        assert!(redacted.contains("POSTGRES_USER: app"));
+        assert!(!redacted.contains("hunter2") && !redacted.contains("abc123"));
+        assert_eq!(redact("ENV GITHUB_TOKEN=ghp_x\nENV PORT=8080"), "ENV GITHUB_TOKEN=[redacted]\nENV PORT=8080");
+    }
+
+    #[test]
+    fn test_redact_urls() {
+        assert_eq!(
+            redact("      DATABASE_URL: postgres://app:hunter2@db:5432/app"),
+            "      DATABASE_URL: postgres://app:[redacted]@db:5432/app"
+        );
+        assert_eq!(
+            redact("GET https://maps.example.com/api?q=tokyo&key=AIzaSy123&format=json"),
+            "GET https://maps.example.com/api?q=tokyo&key=[redacted]&format=json"
+        );
+        assert_eq!(
+            redact("callback ?access_token=abc.def&expires=3600 and &TOKEN=xyz"),
+            "callback ?access_token=[redacted]&expires=3600 and &TOKEN=[redacted]"
+        );
+        // Nothing secret to blank
+        assert_eq!(redact("http://localhost:8080/health?verbose=1"), "http://localhost:8080/health?verbose=1");
+        assert_eq!(redact("git@github.com:me/app.git"), "git@github.com:me/app.git");
+    }
+
+    #[test]
+    fn test_merge_logs() {
+        let stdout = "2024-05-01T10:00:00.000000001Z started\n2024-05-01T10:00:02.000000000Z ready";
+        let stderr = "2024-05-01T10:00:01.000000000Z warning: slow disk";
+        assert_eq!(
+            merge_logs(stdout, stderr, 2),
+            "2024-05-01T10:00:01.000000000Z warning: slow disk\n2024-05-01T10:00:02.000000000Z ready"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_docker_context_includes_compose_file() {
+        let temp_dir = tempdir().unwrap();
+        std::fs::write(temp_dir.path().join("docker-compose.yml"), "services:\n  web:\n    image: nginx\n").unwrap();
+        std::fs::write(temp_dir.path().join("Dockerfile"), "FROM rust:1.80\n").unwrap();
+
+        let provider = DockerProvider::new(temp_dir.path().to_path_buf(), ContextConfig::default());
+        let content = provider.get_context().await.unwrap().content;
+        assert!(content.contains("docker-compose.yml:\nservices:"));
+        assert!(content.contains("Dockerfile:\nFROM rust:1.80"));
+    }
+}
@@ -0,0 +1,105 @@
+//! A one-line description of where q is running, added to the system prompt
+//! so answers default to the user's platform and shell.
+
+use std::env;
+use std::fs;
+use std::path::Path;
+
+/// The environment preamble for the current process, e.g.
+/// `Environment: macOS (aarch64), shell: fish, directory: q, git branch: main`
+pub fn preamble() -> String {
+    let cwd = env::current_dir().ok();
+    let directory = cwd.as_deref()
+        .and_then(Path::file_name)
+        .map(|name| name.to_string_lossy().to_string());
+    let branch = cwd.as_deref().and_then(git_branch);
+    format_preamble(os_name(), env::consts::ARCH, shell().as_deref(), directory.as_deref(), branch.as_deref())
+}
+
+fn format_preamble(
+    os: &str,
+    arch: &str,
+    shell: Option<&str>,
+    directory: Option<&str>,
+    branch: Option<&str>,
+) -> String {
+    let mut parts = vec![format!("{} ({})", os, arch)];
+    if let Some(shell) = shell {
+        parts.push(format!("shell: {}", shell));
+    }
+    if let Some(directory) = directory {
+        parts.push(format!("directory: {}", directory));
+    }
+    if let Some(branch) = branch {
+        parts.push(format!("git branch: {}", branch));
+    }
+    format!("Environment: {}", parts.join(", "))
+}
+
+fn os_name() -> &'static str {
+    match env::consts::OS {
+        "macos" => "macOS",
+        "linux" => "Linux",
+        "windows" => "Windows",
+        "freebsd" => "FreeBSD",
+        other => other,
+    }
+}
+
+/// The user's shell: `$SHELL` on Unix, PowerShell or cmd on Windows
+pub fn shell() -> Option<String> {
+    if let Ok(shell) = env::var("SHELL") {
+        return Path::new(&shell).file_name().map(|name| name.to_string_lossy().to_string());
+    }
+    if cfg!(windows) {
+        return Some(if env::var_os("PSModulePath").is_some() { "powershell" } else { "cmd" }.to_string());
+    }
+    None
+}
+
+/// The checked-out branch of the repository containing `start`, read from
+/// `.git/HEAD` so no `git` process is spawned. `None` outside a repository
+/// or on a detached HEAD.
+fn git_branch(start: &Path) -> Option<String> {
+    let git = start.ancestors().map(|dir| dir.join(".git")).find(|git| git.exists())?;
+    let git_dir = if git.is_file() {
+        // Worktrees and submodules: `.git` is a file pointing at the real directory
+        let pointer = fs::read_to_string(&git).ok()?;
+        let target = pointer.trim().strip_prefix("gitdir:")?.trim().to_string();
+        git.parent()?.join(target)
+    } else {
+        git
+    };
+    let head = fs::read_to_string(git_dir.join("HEAD")).ok()?;
+    head.trim().strip_prefix("ref: refs/heads/").map(String::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_format_preamble() {
+        assert_eq!(
+            format_preamble("macOS", "aarch64", Some("fish"), Some("q"), Some("main")),
+            "Environment: macOS (aarch64), shell: fish, directory: q, git branch: main"
+        );
+        assert_eq!(format_preamble("Linux", "x86_64", None, None, None), "Environment: Linux (x86_64)");
+    }
+
+    #[test]
+    fn test_git_branch() {
+        let temp_dir = TempDir::new().unwrap();
+        let nested = temp_dir.path().join("src/deep");
+        fs::create_dir_all(&nested).unwrap();
+        assert_eq!(git_branch(&nested), None);
+
+        fs::create_dir(temp_dir.path().join(".git")).unwrap();
+        fs::write(temp_dir.path().join(".git/HEAD"), "ref: refs/heads/feature/web\n").unwrap();
+        assert_eq!(git_branch(&nested), Some("feature/web".to_string()));
+
+        fs::write(temp_dir.path().join(".git/HEAD"), "4b825dc642cb6eb9a060e54bf8d69288fbee4904\n").unwrap();
+        assert_eq!(git_branch(&nested), None);
+    }
+}
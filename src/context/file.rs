@@ -1,9 +1,11 @@
 use async_trait::async_trait;
-use std::path::PathBuf;
+use encoding_rs::{Encoding, SHIFT_JIS, UTF_16BE, UTF_16LE, WINDOWS_1252};
+use std::path::{Path, PathBuf};
 use tokio::fs;
 
 use super::{ContextConfig, ContextData, ContextError, ContextProvider, ContextResult, ContextType};
 use super::{format_path_for_display, validate_size};
+use super::code::{self, Language};
 
 pub struct FileProvider {
     path: PathBuf,
@@ -29,6 +31,17 @@ impl FileProvider {
                 _ => ContextError::Io(e),
             })?;
 
+        // Binary files are noted rather than read, whatever their size
+        let head = read_head(&self.path).await?;
+        if let Some(kind) = binary_type(&head) {
+            return Ok(format!(
+                "File: {}\nBinary file skipped ({} bytes, type {})\n",
+                format_path_for_display(&self.path),
+                metadata.len(),
+                kind
+            ));
+        }
+
         // Check file size before reading
         validate_size(
             metadata.len() as usize,
@@ -36,16 +49,22 @@ impl FileProvider {
             "File content"
         )?;
 
-        // Read file content
-        let content = fs::read_to_string(&self.path)
+        // Read file content, decoding it when it isn't UTF-8
+        let bytes = fs::read(&self.path)
             .await
             .map_err(ContextError::Io)?;
+        let (content, encoding) = decode_text(&bytes);
+        let encoding_note = match encoding {
+            Some(encoding) => format!("\nEncoding: {}", encoding.name()),
+            None => String::new(),
+        };
 
         // Format the output with file information
         let output = format!(
-            "File: {}\nSize: {} bytes\n\nContent:\n{}\n",
+            "File: {}\nSize: {} bytes{}\n\nContent:\n{}\n",
             format_path_for_display(&self.path),
             metadata.len(),
+            encoding_note,
             content
         );
 
@@ -53,6 +72,104 @@ impl FileProvider {
     }
 }
 
+/// A file block from `FileProvider` cut to `max` bytes at item boundaries,
+/// with an outline of the rest, when it holds source code q can parse
+pub(crate) fn fit_block(block: &str, max: usize) -> Option<String> {
+    let path = block.lines().next()?.strip_prefix("File: ")?;
+    let language = Language::from_path(Path::new(path))?;
+    let (header, content) = block.split_once("\n\nContent:\n")?;
+    let header = format!("{}\n\nContent:\n", header);
+    let source = content.strip_suffix('\n').unwrap_or(content);
+    let fitted = code::fit(source, language, max.checked_sub(header.len())?)?;
+    Some(header + &fitted)
+}
+
+/// Bytes inspected to tell text from binary (git uses the same amount)
+const SNIFF_BYTES: usize = 8000;
+
+async fn read_head(path: &std::path::Path) -> ContextResult<Vec<u8>> {
+    use tokio::io::AsyncReadExt;
+
+    let file = fs::File::open(path).await.map_err(ContextError::Io)?;
+    let mut head = Vec::with_capacity(SNIFF_BYTES);
+    file.take(SNIFF_BYTES as u64)
+        .read_to_end(&mut head)
+        .await
+        .map_err(ContextError::Io)?;
+    Ok(head)
+}
+
+/// What a binary file is, or `None` for text. Text in UTF-16 also has NUL
+/// bytes, so those are recognized first.
+fn binary_type(head: &[u8]) -> Option<&'static str> {
+    const SIGNATURES: &[(&[u8], &str)] = &[
+        (b"\x89PNG\r\n\x1a\n", "PNG image"),
+        (b"\xff\xd8\xff", "JPEG image"),
+        (b"GIF8", "GIF image"),
+        (b"%PDF-", "PDF document"),
+        (b"PK\x03\x04", "ZIP archive"),
+        (b"\x1f\x8b", "gzip archive"),
+        (b"\x7fELF", "ELF executable"),
+        (b"\xcf\xfa\xed\xfe", "Mach-O executable"),
+        (b"\xca\xfe\xba\xbe", "Mach-O universal binary"),
+        (b"SQLite format 3\0", "SQLite database"),
+        (b"\0asm", "WebAssembly module"),
+    ];
+    if let Some((_, kind)) = SIGNATURES.iter().find(|(magic, _)| head.starts_with(magic)) {
+        return Some(kind);
+    }
+    if utf16_encoding(head).is_some() || !head.contains(&0) {
+        return None;
+    }
+    // "MZ" alone could start a text file
+    Some(if head.starts_with(b"MZ") { "Windows executable" } else { "unknown" })
+}
+
+/// UTF-16 from a byte order mark, or from NULs in every other byte (mostly-ASCII text)
+fn utf16_encoding(head: &[u8]) -> Option<&'static Encoding> {
+    if head.starts_with(b"\xff\xfe") {
+        return Some(UTF_16LE);
+    }
+    if head.starts_with(b"\xfe\xff") {
+        return Some(UTF_16BE);
+    }
+    let pairs = head.len() / 2;
+    if pairs < 2 {
+        return None;
+    }
+    let even_nuls = head.iter().step_by(2).filter(|&&b| b == 0).count();
+    let odd_nuls = head.iter().skip(1).step_by(2).filter(|&&b| b == 0).count();
+    if odd_nuls * 10 >= pairs * 4 && even_nuls * 20 < pairs {
+        Some(UTF_16LE)
+    } else if even_nuls * 10 >= pairs * 4 && odd_nuls * 20 < pairs {
+        Some(UTF_16BE)
+    } else {
+        None
+    }
+}
+
+/// Decode file contents, returning the encoding used when it wasn't UTF-8.
+/// Shift-JIS is only chosen when the text contains kana, since many Latin-1
+/// byte sequences are also valid Shift-JIS.
+fn decode_text(bytes: &[u8]) -> (String, Option<&'static Encoding>) {
+    let bytes = bytes.strip_prefix(b"\xef\xbb\xbf").unwrap_or(bytes);
+    // BOM-less UTF-16 of ASCII text is also valid UTF-8, so check it first
+    if let Some(encoding) = utf16_encoding(bytes) {
+        let (text, _, _) = encoding.decode(bytes);
+        return (text.into_owned(), Some(encoding));
+    }
+    if let Ok(text) = std::str::from_utf8(bytes) {
+        return (text.to_string(), None);
+    }
+    if let Some(text) = SHIFT_JIS.decode_without_bom_handling_and_without_replacement(bytes) {
+        if text.chars().any(|c| ('\u{3040}'..='\u{30ff}').contains(&c)) {
+            return (text.into_owned(), Some(SHIFT_JIS));
+        }
+    }
+    let (text, _) = WINDOWS_1252.decode_without_bom_handling(bytes);
+    (text.into_owned(), Some(WINDOWS_1252))
+}
+
 #[async_trait]
 impl ContextProvider for FileProvider {
     fn context_type(&self) -> ContextType {
@@ -86,6 +203,7 @@ mod tests {
             max_size: 1024,
             include_hidden: false,
             max_depth: None,
+            ..ContextConfig::default()
         };
 
         let provider = FileProvider::new(temp_file.path().to_path_buf(), config);
@@ -96,6 +214,47 @@ mod tests {
         assert!(context.content.contains("Size:"));
     }
 
+    #[tokio::test]
+    async fn test_binary_file_is_skipped() {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        temp_file.write_all(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR").unwrap();
+
+        // Binary files are reported even when larger than the limit
+        let config = ContextConfig { max_size: 4, ..ContextConfig::default() };
+        let provider = FileProvider::new(temp_file.path().to_path_buf(), config);
+        let context = provider.get_context().await.unwrap();
+
+        assert!(context.content.contains("Binary file skipped (16 bytes, type PNG image)"));
+        assert_eq!(binary_type(b"abc\0def"), Some("unknown"));
+        assert_eq!(binary_type(b"plain text"), None);
+    }
+
+    #[tokio::test]
+    async fn test_decodes_legacy_encodings() {
+        let (encoded, _, _) = SHIFT_JIS.encode("こんにちは、世界");
+        let mut temp_file = NamedTempFile::new().unwrap();
+        temp_file.write_all(&encoded).unwrap();
+
+        let provider = FileProvider::new(temp_file.path().to_path_buf(), ContextConfig::default());
+        let context = provider.get_context().await.unwrap();
+        assert!(context.content.contains("Encoding: Shift_JIS"));
+        assert!(context.content.contains("こんにちは、世界"));
+
+        assert_eq!(decode_text(b"caf\xe9 au lait"), ("café au lait".to_string(), Some(WINDOWS_1252)));
+        assert_eq!(decode_text(b"\xef\xbb\xbfhello"), ("hello".to_string(), None));
+    }
+
+    #[test]
+    fn test_decodes_utf16() {
+        let le: Vec<u8> = "hi there".encode_utf16().flat_map(|u| u.to_le_bytes()).collect();
+        assert_eq!(binary_type(&le), None);
+        assert_eq!(decode_text(&le), ("hi there".to_string(), Some(UTF_16LE)));
+
+        let mut be = b"\xfe\xff".to_vec();
+        be.extend("日本".encode_utf16().flat_map(|u| u.to_be_bytes()));
+        assert_eq!(decode_text(&be), ("日本".to_string(), Some(UTF_16BE)));
+    }
+
     #[tokio::test]
     async fn test_file_not_found() {
         let config = ContextConfig::default();
@@ -110,6 +269,11 @@ mod tests {
         let temp_file = NamedTempFile::new().unwrap();
         std::fs::set_permissions(temp_file.path(), Permissions::from_mode(0o000)).unwrap();
 
+        // Privileged users (e.g. root in containers) can read the file anyway
+        if std::fs::File::open(temp_file.path()).is_ok() {
+            return;
+        }
+
         let config = ContextConfig::default();
         let provider = FileProvider::new(temp_file.path().to_path_buf(), config);
         let result = provider.get_context().await;
@@ -127,6 +291,7 @@ mod tests {
             max_size: 100, // Small limit
             include_hidden: false,
             max_depth: None,
+            ..ContextConfig::default()
         };
 
         let provider = FileProvider::new(temp_file.path().to_path_buf(), config);
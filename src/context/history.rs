@@ -1,35 +1,67 @@
 use async_trait::async_trait;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use tokio::fs;
-use shellexpand::tilde;
 
 use super::{ContextConfig, ContextData, ContextError, ContextProvider, ContextResult, ContextType};
 use super::validate_size;
 
+/// Commands included, most recent first
+const MAX_ENTRIES: usize = 100;
+
+/// zsh escapes bytes that clash with its internal tokens as `0x83, byte ^ 0x20`
+const ZSH_META: u8 = 0x83;
+
 pub struct HistoryProvider {
     config: ContextConfig,
+    path: Option<PathBuf>,
+}
+
+/// How entries are written in a history file
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HistoryFormat {
+    /// zsh (plain or extended `: start:elapsed;command`), with bytes metafied
+    Zsh,
+    /// bash and anything else: the same lines, but bytes stored as they are
+    Plain,
+    /// PowerShell's PSReadLine `ConsoleHost_history.txt`
+    PowerShell,
+}
+
+impl HistoryFormat {
+    fn of(path: &Path) -> Self {
+        match path.file_name().and_then(|name| name.to_str()) {
+            Some(name) if name.eq_ignore_ascii_case("ConsoleHost_history.txt") => HistoryFormat::PowerShell,
+            Some(name) if name.contains("zsh") || name.trim_start_matches('.') == "zhistory" => HistoryFormat::Zsh,
+            _ => HistoryFormat::Plain,
+        }
+    }
 }
 
 impl HistoryProvider {
     pub fn new(config: ContextConfig) -> Self {
-        Self { config }
+        Self { config, path: None }
     }
 
-    fn get_history_path() -> ContextResult<PathBuf> {
-        let home = PathBuf::from(env!("HOME"));
-        let history_path = home.join(".zsh_history");
-        
-        if !history_path.exists() {
-            return Err(ContextError::History(
-                "Zsh history file not found".to_string()
-            ));
+    /// Create a provider that reads from an explicit history file
+    pub fn with_path(path: PathBuf, config: ContextConfig) -> Self {
+        Self { config, path: Some(path) }
+    }
+
+    fn get_history_path(&self) -> ContextResult<PathBuf> {
+        if let Some(path) = &self.path {
+            return Ok(path.clone());
         }
-        
-        Ok(history_path)
+
+        history_candidates()
+            .into_iter()
+            .find(|path| path.exists())
+            .ok_or_else(|| ContextError::History(
+                "No shell history found (looked for $HISTFILE, ~/.zsh_history, ~/.bash_history, and PowerShell's PSReadLine history)".to_string()
+            ))
     }
 
     async fn read_history(&self) -> ContextResult<String> {
-        let history_path = Self::get_history_path()?;
+        let history_path = self.get_history_path()?;
 
         // Check if we have permission to read
         let metadata = fs::metadata(&history_path)
@@ -48,30 +80,128 @@ impl HistoryProvider {
             "Shell history"
         )?;
 
-        // Read history file
-        let content = fs::read_to_string(&history_path)
+        // History files often hold bytes from other locales; decode lossily rather than fail
+        let bytes = fs::read(&history_path)
             .await
             .map_err(ContextError::Io)?;
 
-        // Parse and format history entries
+        let entries = parse_history(&bytes, HistoryFormat::of(&history_path));
         let mut output = String::from("Recent shell history:\n\n");
-        
-        // Process history entries
-        for line in content.lines().rev().take(100) {
-            // Skip empty lines
-            if line.trim().is_empty() {
-                continue;
-            }
+        for entry in entries.iter().rev().take(MAX_ENTRIES) {
+            output.push_str(entry);
+            output.push('\n');
+        }
 
-            // Parse Zsh history format
-            // Format: ": timestamp:duration;command"
-            if let Some(cmd) = line.split(';').last() {
-                output.push_str(&format!("{}\n", cmd.trim()));
+        Ok(output)
+    }
+}
+
+/// History files to try, in order
+fn history_candidates() -> Vec<PathBuf> {
+    let mut candidates = Vec::new();
+    if let Some(histfile) = std::env::var_os("HISTFILE") {
+        candidates.push(PathBuf::from(histfile));
+    }
+    let home = std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(PathBuf::from);
+    if let Some(home) = &home {
+        candidates.push(home.join(".zsh_history"));
+        candidates.push(home.join(".bash_history"));
+    }
+    let psreadline = Path::new("PowerShell").join("PSReadLine").join("ConsoleHost_history.txt");
+    if let Some(appdata) = std::env::var_os("APPDATA") {
+        candidates.push(PathBuf::from(appdata).join("Microsoft").join("Windows").join(psreadline));
+    } else if let Some(home) = &home {
+        // pwsh on macOS and Linux
+        candidates.push(home.join(".local/share/powershell/PSReadLine/ConsoleHost_history.txt"));
+    }
+    candidates
+}
+
+/// Commands from the raw bytes of a history file, oldest first
+pub fn parse_history(bytes: &[u8], format: HistoryFormat) -> Vec<String> {
+    match format {
+        HistoryFormat::Zsh => parse_zsh(&String::from_utf8_lossy(&unmetafy(bytes))),
+        HistoryFormat::Plain => parse_zsh(&String::from_utf8_lossy(bytes)),
+        HistoryFormat::PowerShell => parse_powershell(&String::from_utf8_lossy(bytes)),
+    }
+}
+
+/// Undo zsh's metafication so multibyte characters decode correctly
+fn unmetafy(bytes: &[u8]) -> Vec<u8> {
+    let mut output = Vec::with_capacity(bytes.len());
+    let mut iter = bytes.iter();
+    while let Some(&byte) = iter.next() {
+        match byte {
+            ZSH_META => {
+                if let Some(&next) = iter.next() {
+                    output.push(next ^ 0x20);
+                }
             }
+            _ => output.push(byte),
         }
+    }
+    output
+}
 
-        Ok(output)
+/// Commands from a zsh history file, oldest first. A line ending in `\`
+/// continues the command on the next line.
+fn parse_zsh(content: &str) -> Vec<String> {
+    join_continued_lines(content, '\\')
+        .into_iter()
+        // bash writes `#<timestamp>` lines when HISTTIMEFORMAT is set
+        .filter(|entry| !is_bash_timestamp(entry))
+        .map(|entry| strip_zsh_timestamp(&entry).trim().to_string())
+        .filter(|entry| !entry.is_empty())
+        .collect()
+}
+
+/// `: 1707000000:0;git status` -> `git status`, leaving plain lines alone
+fn strip_zsh_timestamp(line: &str) -> &str {
+    let Some(rest) = line.strip_prefix(": ") else {
+        return line;
+    };
+    match rest.split_once(';') {
+        Some((stamp, command)) if stamp.bytes().all(|b| b.is_ascii_digit() || b == b':') => command,
+        _ => line,
+    }
+}
+
+fn is_bash_timestamp(line: &str) -> bool {
+    line.strip_prefix('#')
+        .is_some_and(|rest| !rest.is_empty() && rest.bytes().all(|b| b.is_ascii_digit()))
+}
+
+/// Commands from PSReadLine history, oldest first. A line ending in a
+/// backtick continues the command on the next line.
+fn parse_powershell(content: &str) -> Vec<String> {
+    join_continued_lines(content, '`')
+        .into_iter()
+        .map(|entry| entry.trim().to_string())
+        .filter(|entry| !entry.is_empty())
+        .collect()
+}
+
+/// Split history into entries, joining lines that end in `marker` with the next
+fn join_continued_lines(content: &str, marker: char) -> Vec<String> {
+    let mut entries: Vec<String> = Vec::new();
+    let mut continuing = false;
+    for line in content.lines() {
+        let (text, continues) = match line.strip_suffix(marker) {
+            Some(text) => (text, true),
+            None => (line, false),
+        };
+        match entries.last_mut() {
+            Some(entry) if continuing => {
+                entry.push('\n');
+                entry.push_str(text);
+            }
+            _ => entries.push(text.to_string()),
+        }
+        continuing = continues;
     }
+    entries
 }
 
 #[async_trait]
@@ -112,14 +242,10 @@ mod tests {
             max_size: 1024,
             include_hidden: false,
             max_depth: None,
+            ..ContextConfig::default()
         };
 
-        let provider = HistoryProvider::new(config);
-        
-        // Temporarily override the history path for testing
-        std::env::set_var("HOME", temp_file.path().parent().unwrap());
-        std::fs::rename(temp_file.path(), temp_file.path().with_file_name(".zsh_history")).unwrap();
-
+        let provider = HistoryProvider::with_path(temp_file.path().to_path_buf(), config);
         let context = provider.get_context().await.unwrap();
 
         assert!(context.content.contains("ls -la"));
@@ -137,15 +263,69 @@ mod tests {
             max_size: 100, // Small limit
             include_hidden: false,
             max_depth: None,
+            ..ContextConfig::default()
         };
 
-        let provider = HistoryProvider::new(config);
-        
-        // Temporarily override the history path for testing
-        std::env::set_var("HOME", temp_file.path().parent().unwrap());
-        std::fs::rename(temp_file.path(), temp_file.path().with_file_name(".zsh_history")).unwrap();
-
+        let provider = HistoryProvider::with_path(temp_file.path().to_path_buf(), config);
         let result = provider.get_context().await;
         assert!(matches!(result, Err(ContextError::TooLarge(_))));
     }
+
+    #[test]
+    fn test_parse_zsh_edge_cases() {
+        let content = ": 1707000000:0;echo a; echo b\n: 1707000001:0;for f in *; do\\\n  echo $f\\\ndone\nplain command\n";
+        assert_eq!(parse_zsh(content), vec![
+            "echo a; echo b".to_string(),
+            "for f in *; do\n  echo $f\ndone".to_string(),
+            "plain command".to_string(),
+        ]);
+        assert_eq!(parse_zsh("#1707000000\nls\n"), vec!["ls".to_string()]);
+    }
+
+    #[test]
+    fn test_unmetafy() {
+        // "ア" is E3 82 A2; zsh stores the A2 byte as 83 82
+        let raw = [b'e', b'c', b'h', b'o', b' ', 0xE3, 0x82, ZSH_META, 0x82];
+        assert_eq!(String::from_utf8(unmetafy(&raw)).unwrap(), "echo ア");
+    }
+
+    #[test]
+    fn test_bash_bytes_are_not_unmetafied() {
+        // "ッ" is E3 83 83, which zsh would read as an escape
+        let raw = "echo ッ\n".as_bytes();
+        assert_eq!(HistoryFormat::of(Path::new("/home/me/.bash_history")), HistoryFormat::Plain);
+        assert_eq!(parse_history(raw, HistoryFormat::Plain), vec!["echo ッ".to_string()]);
+        assert_eq!(HistoryFormat::of(Path::new("/home/me/.zsh_history")), HistoryFormat::Zsh);
+        assert_eq!(HistoryFormat::of(Path::new("/home/me/.zhistory")), HistoryFormat::Zsh);
+    }
+
+    #[test]
+    fn test_parse_powershell() {
+        let content = "Get-ChildItem\r\nGet-Process |`\r\n  Sort-Object CPU\r\n\r\n";
+        assert_eq!(parse_powershell(content), vec![
+            "Get-ChildItem".to_string(),
+            "Get-Process |\n  Sort-Object CPU".to_string(),
+        ]);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_utf8_is_decoded_lossily() {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        temp_file.write_all(b": 1707000000:0;cat caf\xe9.txt\n: 1707000001:0;ls\n").unwrap();
+
+        let provider = HistoryProvider::with_path(temp_file.path().to_path_buf(), ContextConfig::default());
+        let content = provider.get_context().await.unwrap().content;
+        assert_eq!(content, "Recent shell history:\n\nls\ncat caf\u{FFFD}.txt\n");
+    }
+
+    #[tokio::test]
+    async fn test_powershell_history_file() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("ConsoleHost_history.txt");
+        std::fs::write(&path, "cd C:\\src\r\ncargo build\r\n").unwrap();
+
+        let provider = HistoryProvider::with_path(path, ContextConfig::default());
+        let content = provider.get_context().await.unwrap().content;
+        assert_eq!(content, "Recent shell history:\n\ncargo build\ncd C:\\src\n");
+    }
 }
@@ -0,0 +1,409 @@
+use async_trait::async_trait;
+use std::fs::File;
+use std::io::{ErrorKind, Read, Seek, SeekFrom};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use chrono::{DateTime, Datelike, Local, NaiveDateTime, TimeZone, Utc};
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use super::{format_path_for_display, ContextConfig, ContextData, ContextError, ContextProvider, ContextResult, ContextType};
+use crate::utils::format::strip_ansi;
+
+/// Lines taken when `--log` names only a path
+pub const DEFAULT_LINES: usize = 200;
+/// Bytes read from the end of the file; a tail or a recent window rarely needs more
+const MAX_READ_BYTES: u64 = 8 * 1024 * 1024;
+/// How far into a line a timestamp may start (after a level, PID, or `{"time":`)
+const TIMESTAMP_SEARCH_BYTES: usize = 64;
+
+lazy_static! {
+    /// `2024-05-01T10:00:00Z`, `2024-05-01 10:00:00,123 +0200`, and the like
+    static ref ISO_TIME: Regex = Regex::new(r"(\d{4}-\d{2}-\d{2})[T ](\d{2}:\d{2}:\d{2})(?:[.,]\d+)?(?:\s?(Z|[+-]\d{2}:?\d{2})\b)?").expect("valid regex");
+    /// Apache and nginx access logs: `[01/May/2024:10:00:00 +0000]`
+    static ref CLF_TIME: Regex = Regex::new(r"\[(\d{2}/[A-Za-z]{3}/\d{4}:\d{2}:\d{2}:\d{2} [+-]\d{4})\]").expect("valid regex");
+    /// Syslog: `May  1 10:00:00` at the start of a line, without a year
+    static ref SYSLOG_TIME: Regex = Regex::new(r"^([A-Z][a-z]{2}) +(\d{1,2}) (\d{2}:\d{2}:\d{2})").expect("valid regex");
+}
+
+/// Which part of a log `--log` includes
+#[derive(Debug, Clone, PartialEq)]
+pub enum LogWindow {
+    /// The last N lines
+    Lines(usize),
+    /// Lines stamped within this long before now
+    Since(Duration),
+}
+
+/// A `--log` argument: `path`, `path:N`, or `path:since=10m`
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogSpec {
+    pub path: PathBuf,
+    pub window: LogWindow,
+}
+
+impl FromStr for LogSpec {
+    type Err = String;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        // A suffix that isn't a window is part of the path (`C:\logs\app.log`)
+        let (path, window) = match spec.rsplit_once(':') {
+            Some((path, suffix)) if !path.is_empty() => match parse_window(suffix) {
+                Some(window) => (path, window?),
+                None => (spec, LogWindow::Lines(DEFAULT_LINES)),
+            },
+            _ => (spec, LogWindow::Lines(DEFAULT_LINES)),
+        };
+        if path.is_empty() {
+            return Err("expected a log file path".to_string());
+        }
+        Ok(Self { path: PathBuf::from(path), window })
+    }
+}
+
+/// `N` or `since=<duration>`; `None` when the suffix is neither
+fn parse_window(suffix: &str) -> Option<Result<LogWindow, String>> {
+    if let Some(duration) = suffix.strip_prefix("since=") {
+        return Some(parse_duration(duration).map(LogWindow::Since));
+    }
+    if !suffix.is_empty() && suffix.chars().all(|c| c.is_ascii_digit()) {
+        return Some(match suffix.parse::<usize>() {
+            Ok(0) | Err(_) => Err(format!("'{}' is not a line count above zero", suffix)),
+            Ok(lines) => Ok(LogWindow::Lines(lines)),
+        });
+    }
+    None
+}
+
+/// `30s`, `10m`, `2h`, or `1d`
+fn parse_duration(text: &str) -> Result<Duration, String> {
+    let invalid = || format!("'{}' is not a duration like 30s, 10m, 2h, or 1d", text);
+    let split = text.find(|c: char| !c.is_ascii_digit()).ok_or_else(invalid)?;
+    let amount: u64 = text[..split].parse().map_err(|_| invalid())?;
+    let unit = match &text[split..] {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return Err(invalid()),
+    };
+    Ok(Duration::from_secs(amount * unit))
+}
+
+fn describe_window(window: &LogWindow) -> String {
+    match window {
+        LogWindow::Lines(lines) => format!("last {} lines", lines),
+        LogWindow::Since(duration) => {
+            let seconds = duration.as_secs();
+            let (amount, unit) = [(24 * 60 * 60, "d"), (60 * 60, "h"), (60, "m")].into_iter()
+                .find(|(unit, _)| seconds % unit == 0 && seconds >= *unit)
+                .map(|(size, unit)| (seconds / size, unit))
+                .unwrap_or((seconds, "s"));
+            format!("lines from the last {}{}", amount, unit)
+        }
+    }
+}
+
+/// A log file, tailed or cut to a recent time window, with terminal escape
+/// codes removed and runs of repeated lines collapsed to one with a count.
+/// When the budget is tight, the oldest lines go first.
+#[derive(Clone)]
+pub struct LogProvider {
+    spec: LogSpec,
+    config: ContextConfig,
+}
+
+impl LogProvider {
+    pub fn new(spec: LogSpec, config: ContextConfig) -> Self {
+        Self { spec, config }
+    }
+
+    fn format_log(&self, now: DateTime<Local>) -> ContextResult<String> {
+        let path = &self.spec.path;
+        let (text, cut) = read_tail(path)?;
+        let text = strip_ansi(&text);
+        let lines: Vec<&str> = text.lines().collect();
+
+        let (selected, mut note) = select(&lines, &self.spec.window, now);
+        if cut && selected.len() == lines.len() {
+            note = note.or(Some(format!("only the last {} MiB of the file were read", MAX_READ_BYTES / (1024 * 1024))));
+        }
+        let collapsed = collapse(&selected);
+
+        let mut output = format!(
+            "Log: {} ({}; {} of {} lines)\n",
+            format_path_for_display(path),
+            describe_window(&self.spec.window),
+            selected.len(),
+            lines.len()
+        );
+        if let Some(note) = note {
+            output.push_str(&format!("[{}]\n", note));
+        }
+        for line in collapsed {
+            output.push_str(&line);
+            output.push('\n');
+        }
+        Ok(fit_block(&output, self.config.max_size).unwrap_or(output))
+    }
+}
+
+/// The end of a file, at most `MAX_READ_BYTES`, starting on a whole line; and
+/// whether anything before it was skipped
+fn read_tail(path: &Path) -> ContextResult<(String, bool)> {
+    let mut file = File::open(path).map_err(|e| match e.kind() {
+        ErrorKind::NotFound => ContextError::FileNotFound(path.to_path_buf()),
+        ErrorKind::PermissionDenied => ContextError::PermissionDenied(path.to_path_buf()),
+        _ => ContextError::Io(e),
+    })?;
+    let start = file.metadata()?.len().saturating_sub(MAX_READ_BYTES);
+    file.seek(SeekFrom::Start(start))?;
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes)?;
+    if start > 0 {
+        let newline = bytes.iter().position(|b| *b == b'\n').map(|i| i + 1).unwrap_or(0);
+        bytes.drain(..newline);
+    }
+    Ok((String::from_utf8_lossy(&bytes).into_owned(), start > 0))
+}
+
+/// The lines in `window`, and a note when it couldn't be applied as asked.
+/// Lines without a timestamp, like stack trace frames, belong to the
+/// stamped line above them.
+fn select<'a>(lines: &[&'a str], window: &LogWindow, now: DateTime<Local>) -> (Vec<&'a str>, Option<String>) {
+    let tail = |count: usize| lines[lines.len().saturating_sub(count)..].to_vec();
+    let since = match window {
+        LogWindow::Lines(count) => return (tail(*count), None),
+        LogWindow::Since(since) => *since,
+    };
+    let cutoff = chrono::Duration::from_std(since).ok()
+        .and_then(|since| now.with_timezone(&Utc).checked_sub_signed(since))
+        .unwrap_or(DateTime::<Utc>::MIN_UTC);
+
+    let mut current = None;
+    let mut seen_timestamp = false;
+    let mut selected = Vec::new();
+    for line in lines {
+        if let Some((time, _)) = timestamp(line, now) {
+            current = Some(time);
+            seen_timestamp = true;
+        }
+        if current.is_some_and(|time| time >= cutoff) {
+            selected.push(*line);
+        }
+    }
+    if !seen_timestamp {
+        return (tail(DEFAULT_LINES), Some(format!("no timestamps recognized, so this is the last {} lines", DEFAULT_LINES)));
+    }
+    (selected, None)
+}
+
+/// When a line was written, and where its timestamp is, for the formats
+/// most logs use: ISO 8601, the Apache/nginx access log format, and syslog.
+/// Times without a zone are local.
+fn timestamp(line: &str, now: DateTime<Local>) -> Option<(DateTime<Utc>, Range<usize>)> {
+    if let Some(captures) = ISO_TIME.captures(line).filter(|captures| captures.get(0).is_some_and(|m| m.start() <= TIMESTAMP_SEARCH_BYTES)) {
+        let naive = NaiveDateTime::parse_from_str(&format!("{} {}", &captures[1], &captures[2]), "%Y-%m-%d %H:%M:%S").ok()?;
+        let time = match captures.get(3).map(|offset| offset.as_str().replace(':', "")) {
+            Some(offset) if offset == "Z" => Utc.from_utc_datetime(&naive),
+            Some(offset) => DateTime::parse_from_str(&format!("{} {}", naive.format("%Y-%m-%d %H:%M:%S"), offset), "%Y-%m-%d %H:%M:%S %z")
+                .ok()?
+                .with_timezone(&Utc),
+            None => Local.from_local_datetime(&naive).earliest()?.with_timezone(&Utc),
+        };
+        return Some((time, captures.get(0)?.range()));
+    }
+    if let Some(captures) = CLF_TIME.captures(line).filter(|captures| captures.get(0).is_some_and(|m| m.start() <= TIMESTAMP_SEARCH_BYTES)) {
+        let time = DateTime::parse_from_str(&captures[1], "%d/%b/%Y:%H:%M:%S %z").ok()?;
+        return Some((time.with_timezone(&Utc), captures.get(0)?.range()));
+    }
+    let captures = SYSLOG_TIME.captures(line)?;
+    let parse = |year: i32| {
+        let text = format!("{} {} {} {}", year, &captures[1], &captures[2], &captures[3]);
+        NaiveDateTime::parse_from_str(&text, "%Y %b %d %H:%M:%S").ok()
+            .and_then(|naive| Local.from_local_datetime(&naive).earliest())
+    };
+    // Syslog has no year: a date ahead of now is from last year
+    let time = parse(now.year()).filter(|time| *time <= now + chrono::Duration::days(1))
+        .or_else(|| parse(now.year() - 1))?;
+    Some((time.with_timezone(&Utc), captures.get(0)?.range()))
+}
+
+/// Runs of lines that are the same apart from their timestamp, as the first
+/// line of the run and a count
+fn collapse(lines: &[&str]) -> Vec<String> {
+    let now = Local::now();
+    let key = |line: &str| without_timestamp(line, now);
+    let mut collapsed: Vec<String> = Vec::new();
+    let mut run: Option<(String, &str, usize)> = None;
+    for line in lines {
+        let line_key = key(line);
+        match &mut run {
+            Some((run_key, _, count)) if *run_key == line_key => *count += 1,
+            _ => {
+                if let Some((_, first, count)) = run.take() {
+                    collapsed.push(repeated(first, count));
+                }
+                run = Some((line_key, line, 1));
+            }
+        }
+    }
+    if let Some((_, first, count)) = run {
+        collapsed.push(repeated(first, count));
+    }
+    collapsed
+}
+
+/// `line` with its timestamp, if it has one, taken out
+pub(super) fn without_timestamp(line: &str, now: DateTime<Local>) -> String {
+    match timestamp(line, now) {
+        Some((_, range)) => format!("{}{}", &line[..range.start], &line[range.end..]),
+        None => line.to_string(),
+    }
+}
+
+fn repeated(line: &str, count: usize) -> String {
+    if count == 1 {
+        line.to_string()
+    } else {
+        format!("{} [repeated {} times]", line, count)
+    }
+}
+
+/// A log block from `LogProvider` cut to `max` bytes by dropping its oldest
+/// lines, since the end of a log is usually what a question is about
+pub(crate) fn fit_block(block: &str, max: usize) -> Option<String> {
+    if block.len() <= max {
+        return Some(block.to_string());
+    }
+    let (header, body) = block.split_once('\n')?;
+    header.strip_prefix("Log: ")?;
+    let note = |omitted: usize| format!("[... {} earlier bytes of the log omitted]\n", omitted);
+    let available = max.checked_sub(header.len() + 1 + note(body.len()).len())?;
+    let mut start = body.len().saturating_sub(available);
+    while !body.is_char_boundary(start) {
+        start += 1;
+    }
+    let start = match body[start..].find('\n') {
+        Some(newline) if start > 0 && !body[..start].ends_with('\n') => start + newline + 1,
+        _ => start,
+    };
+    Some(format!("{}\n{}{}", header, note(start), &body[start..]))
+}
+
+#[async_trait]
+impl ContextProvider for LogProvider {
+    fn context_type(&self) -> ContextType {
+        ContextType::Log(self.spec.path.clone())
+    }
+
+    async fn get_context(&self) -> ContextResult<ContextData> {
+        let provider = self.clone();
+        let content = tokio::task::spawn_blocking(move || provider.format_log(Local::now()))
+            .await
+            .map_err(|e| ContextError::Other(format!("Reading the log failed: {}", e)))??;
+
+        Ok(ContextData {
+            context_type: self.context_type(),
+            content,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn local(text: &str) -> DateTime<Local> {
+        Local.from_local_datetime(&NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S").unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_parse_spec() {
+        let spec: LogSpec = "app.log".parse().unwrap();
+        assert_eq!(spec, LogSpec { path: PathBuf::from("app.log"), window: LogWindow::Lines(DEFAULT_LINES) });
+        assert_eq!("app.log:50".parse::<LogSpec>().unwrap().window, LogWindow::Lines(50));
+        assert_eq!("/var/log/app.log:since=10m".parse::<LogSpec>().unwrap().window, LogWindow::Since(Duration::from_secs(600)));
+        assert_eq!("C:\\logs\\app.log".parse::<LogSpec>().unwrap().path, PathBuf::from("C:\\logs\\app.log"));
+        assert!("app.log:0".parse::<LogSpec>().is_err());
+        assert!("app.log:since=soon".parse::<LogSpec>().is_err());
+    }
+
+    #[test]
+    fn test_timestamp_formats() {
+        let now = local("2024-05-01 12:00:00");
+        let (time, range) = timestamp("2024-05-01T10:00:00.123Z ERROR boom", now).unwrap();
+        assert_eq!(time.to_rfc3339(), "2024-05-01T10:00:00+00:00");
+        assert_eq!(range, 0..24);
+        let (time, _) = timestamp("INFO 2024-05-01 10:00:00,5 +02:00 started", now).unwrap();
+        assert_eq!(time.to_rfc3339(), "2024-05-01T08:00:00+00:00");
+        let (time, _) = timestamp("10.0.0.1 - - [01/May/2024:10:00:00 +0000] \"GET / HTTP/1.1\" 200", now).unwrap();
+        assert_eq!(time.to_rfc3339(), "2024-05-01T10:00:00+00:00");
+        let (time, _) = timestamp("Dec 31 23:59:00 host sshd[1]: accepted", now).unwrap();
+        assert_eq!(time.with_timezone(&Local).year(), 2023);
+        assert!(timestamp("    at com.example.Main(Main.java:10)", now).is_none());
+    }
+
+    #[test]
+    fn test_select_since_keeps_continuation_lines() {
+        let now = local("2024-05-01 12:00:00");
+        let lines = [
+            "2024-05-01 11:00:00 old",
+            "2024-05-01 11:55:00 ERROR failed",
+            "  at frame one",
+            "2024-05-01 11:59:00 retrying",
+        ];
+        let (selected, note) = select(&lines, &LogWindow::Since(Duration::from_secs(600)), now);
+        assert_eq!(selected, &lines[1..]);
+        assert_eq!(note, None);
+
+        let (selected, note) = select(&["no", "times"], &LogWindow::Since(Duration::from_secs(60)), now);
+        assert_eq!(selected, ["no", "times"]);
+        assert!(note.unwrap().contains("no timestamps"));
+    }
+
+    #[test]
+    fn test_collapse() {
+        let lines = [
+            "2024-05-01T10:00:00Z WARN retry",
+            "2024-05-01T10:00:01Z WARN retry",
+            "2024-05-01T10:00:02Z WARN retry",
+            "2024-05-01T10:00:03Z ok",
+            "plain",
+            "plain",
+        ];
+        assert_eq!(collapse(&lines), [
+            "2024-05-01T10:00:00Z WARN retry [repeated 3 times]",
+            "2024-05-01T10:00:03Z ok",
+            "plain [repeated 2 times]",
+        ]);
+    }
+
+    #[test]
+    fn test_fit_block_keeps_newest_lines() {
+        let block = format!("Log: app.log (last 200 lines; 100 of 100 lines)\n{}", (0..100).map(|i| format!("line {}\n", i)).collect::<String>());
+        let fitted = fit_block(&block, 200).unwrap();
+        assert!(fitted.len() <= 200);
+        assert!(fitted.starts_with("Log: app.log"));
+        assert!(fitted.contains("earlier bytes of the log omitted]\nline "));
+        assert!(fitted.ends_with("line 99\n"));
+    }
+
+    #[tokio::test]
+    async fn test_log_context_strips_ansi() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("app.log");
+        std::fs::write(&path, "\x1b[32mINFO\x1b[0m start\n\x1b[31mERROR\x1b[0m boom\nlast\n").unwrap();
+
+        let spec: LogSpec = format!("{}:2", path.display()).parse().unwrap();
+        let content = LogProvider::new(spec, ContextConfig::default()).get_context().await.unwrap().content;
+        assert!(content.contains("(last 2 lines; 2 of 3 lines)\nERROR boom\nlast\n"));
+
+        let missing = LogProvider::new("missing.log".parse().unwrap(), ContextConfig::default()).get_context().await;
+        assert!(matches!(missing, Err(ContextError::FileNotFound(_))));
+    }
+}
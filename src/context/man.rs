@@ -0,0 +1,222 @@
+use async_trait::async_trait;
+use std::env;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+use sha2::{Digest, Sha256};
+
+use super::{ContextConfig, ContextData, ContextError, ContextProvider, ContextResult, ContextType};
+
+/// Man pages run long; the synopsis and options come first
+const MAX_DOC_BYTES: usize = 24 * 1024;
+/// How long `<cmd> --help` may run before it's killed
+const HELP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The installed documentation for a command: `man <cmd>` (as `col -b`
+/// would print it) or else `<cmd> --help`, so answers match the local
+/// version's flags. Captures are cached per binary and refreshed when it
+/// changes.
+#[derive(Clone)]
+pub struct ManProvider {
+    command: String,
+    cache_dir: Option<PathBuf>,
+    config: ContextConfig,
+}
+
+impl ManProvider {
+    pub fn new(command: String, config: ContextConfig) -> Self {
+        Self { command, cache_dir: None, config }
+    }
+
+    /// Cache captured documentation under `dir`
+    pub fn with_cache_dir(mut self, dir: PathBuf) -> Self {
+        self.cache_dir = Some(dir);
+        self
+    }
+
+    fn documentation(&self) -> ContextResult<String> {
+        let name = self.command.as_str();
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || "._+-".contains(c)) {
+            return Err(ContextError::InvalidPath(format!("'{}' is not a command name", name)));
+        }
+        let binary = find_in_path(name)
+            .ok_or_else(|| ContextError::Other(format!("{} is not installed (not found in PATH)", name)))?;
+        let cache_file = self.cache_dir.as_ref()
+            .and_then(|dir| cache_key(&binary).map(|key| dir.join(format!("{}-{}.txt", name, key))));
+
+        let text = match cache_file.as_ref().and_then(|file| std::fs::read_to_string(file).ok()) {
+            Some(text) => text,
+            None => {
+                let (source, text) = man_page(name).map(|text| ("man page", text))
+                    .or_else(|| help_output(&binary).map(|text| ("--help", text)))
+                    .ok_or_else(|| ContextError::Other(format!("{} has no man page or --help output", name)))?;
+                let text = format!(
+                    "Documentation for `{}` ({}, installed at {}):\n{}",
+                    name,
+                    source,
+                    binary.display(),
+                    truncate(&text, MAX_DOC_BYTES)
+                );
+                // The cache is disposable; a failed write only costs a recapture
+                if let Some(file) = &cache_file {
+                    let _ = file.parent().map(std::fs::create_dir_all);
+                    let _ = std::fs::write(file, &text);
+                }
+                text
+            }
+        };
+        Ok(truncate(&text, self.config.max_size))
+    }
+}
+
+/// The first executable called `name` on `PATH`
+fn find_in_path(name: &str) -> Option<PathBuf> {
+    let extensions: &[&str] = if cfg!(windows) { &[".exe", ".cmd", ".bat"] } else { &[""] };
+    env::split_paths(&env::var_os("PATH")?)
+        .flat_map(|dir| extensions.iter().map(move |extension| dir.join(format!("{}{}", name, extension))))
+        .find(|path| path.is_file())
+}
+
+/// Identifies the installed binary: a new version gets a new key
+fn cache_key(binary: &Path) -> Option<String> {
+    let metadata = std::fs::metadata(binary).ok()?;
+    let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?.as_secs();
+    let digest = Sha256::digest(format!("{}\0{}\0{}", binary.display(), modified, metadata.len()));
+    Some(digest.iter().take(8).map(|b| format!("{:02x}", b)).collect())
+}
+
+fn man_page(name: &str) -> Option<String> {
+    let output = Command::new("man")
+        .arg(name)
+        .env("MANPAGER", "cat")
+        .env("PAGER", "cat")
+        .env("MANWIDTH", "100")
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    let text = strip_overstrike(&String::from_utf8_lossy(&output.stdout));
+    (output.status.success() && !text.trim().is_empty()).then_some(text)
+}
+
+/// `<binary> --help`, from stdout or, for tools that print usage there, stderr
+fn help_output(binary: &Path) -> Option<String> {
+    let mut child = Command::new(binary)
+        .arg("--help")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .ok()?;
+    // Read both pipes while waiting so long help text can't block the child
+    let read = |pipe: Option<Box<dyn Read + Send>>| std::thread::spawn(move || {
+        let mut text = String::new();
+        if let Some(mut pipe) = pipe {
+            let mut bytes = Vec::new();
+            let _ = pipe.read_to_end(&mut bytes);
+            text = String::from_utf8_lossy(&bytes).into_owned();
+        }
+        text
+    });
+    let stdout = read(child.stdout.take().map(|pipe| Box::new(pipe) as Box<dyn Read + Send>));
+    let stderr = read(child.stderr.take().map(|pipe| Box::new(pipe) as Box<dyn Read + Send>));
+
+    let started = Instant::now();
+    while child.try_wait().ok()?.is_none() {
+        if started.elapsed() > HELP_TIMEOUT {
+            let _ = child.kill();
+            let _ = child.wait();
+            return None;
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    [stdout, stderr].into_iter()
+        .filter_map(|reader| reader.join().ok())
+        .find(|text| !text.trim().is_empty())
+}
+
+/// Removes the backspace overstrikes man uses for bold and underline, as `col -b` does
+fn strip_overstrike(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    for c in text.chars() {
+        if c == '\u{8}' {
+            output.pop();
+        } else {
+            output.push(c);
+        }
+    }
+    output
+}
+
+/// `text` cut to `max` bytes at a line boundary, with a note when cut
+fn truncate(text: &str, max: usize) -> String {
+    if text.len() <= max {
+        return text.to_string();
+    }
+    let mut end = max;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    let end = text[..end].rfind('\n').map(|newline| newline + 1).unwrap_or(end);
+    format!("{}[... {} more bytes of documentation]\n", &text[..end], text.len() - end)
+}
+
+#[async_trait]
+impl ContextProvider for ManProvider {
+    fn context_type(&self) -> ContextType {
+        ContextType::Man(self.command.clone())
+    }
+
+    async fn get_context(&self) -> ContextResult<ContextData> {
+        let provider = self.clone();
+        let content = tokio::task::spawn_blocking(move || provider.documentation())
+            .await
+            .map_err(|e| ContextError::Other(format!("Capturing documentation failed: {}", e)))??;
+
+        Ok(ContextData {
+            context_type: self.context_type(),
+            content,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_strip_overstrike() {
+        assert_eq!(strip_overstrike("N\u{8}NA\u{8}AM\u{8}ME\u{8}E\n_\u{8}f_\u{8}i_\u{8}l_\u{8}e"), "NAME\nfile");
+    }
+
+    #[test]
+    fn test_truncate_at_line() {
+        assert_eq!(truncate("short\n", 100), "short\n");
+        assert_eq!(truncate("line one\nline two\nline three\n", 20), "line one\nline two\n[... 11 more bytes of documentation]\n");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_help_is_cached() {
+        let temp_dir = tempdir().unwrap();
+        let provider = ManProvider::new("sh".to_string(), ContextConfig::default())
+            .with_cache_dir(temp_dir.path().to_path_buf());
+        let Ok(context) = provider.get_context().await else {
+            return; // neither man nor sh --help here
+        };
+        assert!(context.content.starts_with("Documentation for `sh` ("));
+
+        let cached = std::fs::read_dir(temp_dir.path()).unwrap().next().unwrap().unwrap().path();
+        std::fs::write(&cached, "Documentation for `sh` (cached)\n").unwrap();
+        assert_eq!(provider.get_context().await.unwrap().content, "Documentation for `sh` (cached)\n");
+    }
+
+    #[tokio::test]
+    async fn test_rejects_non_commands() {
+        let provider = ManProvider::new("rm -rf".to_string(), ContextConfig::default());
+        assert!(matches!(provider.get_context().await, Err(ContextError::InvalidPath(_))));
+    }
+}
@@ -1,10 +1,24 @@
 use async_trait::async_trait;
 use thiserror::Error;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
+pub mod assembler;
+pub mod cargo;
+pub mod code;
+pub mod compress;
 pub mod directory;
+pub mod docker;
+pub mod environment;
 pub mod file;
+pub mod git;
 pub mod history;
+pub mod log;
+pub mod man;
+pub mod outline;
+pub mod output;
+pub mod qignore;
+pub mod system;
+pub mod web;
 
 #[derive(Error, Debug)]
 pub enum ContextError {
@@ -26,6 +40,9 @@ pub enum ContextError {
     #[error("Context too large: {0}")]
     TooLarge(String),
 
+    #[error("Not allowed by [context] allow: {0}")]
+    NotAllowed(String),
+
     #[error("Other error: {0}")]
     Other(String),
 }
@@ -37,6 +54,42 @@ pub enum ContextType {
     History,
     Directory,
     File(PathBuf),
+    Log(PathBuf),
+    Stdin,
+    Exec(String),
+    Man(String),
+    Outline(PathBuf),
+    Repo,
+    Cargo,
+    Docker,
+    Diff,
+    System,
+    Web(String),
+}
+
+/// Context kind names used in `[context]` config, in default priority order
+pub const CONTEXT_KINDS: &[&str] = &["file", "stdin", "exec", "log", "man", "outline", "diff", "repo", "cargo", "docker", "history", "directory", "web", "system"];
+
+impl ContextType {
+    /// Name of this kind of context in `[context]` config
+    pub fn name(&self) -> &'static str {
+        match self {
+            ContextType::History => "history",
+            ContextType::Directory => "directory",
+            ContextType::File(_) => "file",
+            ContextType::Log(_) => "log",
+            ContextType::Stdin => "stdin",
+            ContextType::Exec(_) => "exec",
+            ContextType::Man(_) => "man",
+            ContextType::Outline(_) => "outline",
+            ContextType::Repo => "repo",
+            ContextType::Cargo => "cargo",
+            ContextType::Docker => "docker",
+            ContextType::Diff => "diff",
+            ContextType::System => "system",
+            ContextType::Web(_) => "web",
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -63,6 +116,8 @@ pub struct ContextConfig {
     pub include_hidden: bool,
     /// Maximum depth for directory traversal
     pub max_depth: Option<usize>,
+    /// `.qignore` and `[context] ignore` patterns
+    pub ignore: qignore::QIgnore,
 }
 
 impl Default for ContextConfig {
@@ -71,12 +126,13 @@ impl Default for ContextConfig {
             max_size: 1024 * 1024, // 1MB
             include_hidden: false,
             max_depth: Some(3),
+            ignore: qignore::QIgnore::default(),
         }
     }
 }
 
 /// Helper function to check if a path should be included based on config
-pub fn should_include_path(path: &PathBuf, config: &ContextConfig) -> bool {
+pub fn should_include_path(path: &Path, config: &ContextConfig) -> bool {
     if !config.include_hidden {
         if let Some(file_name) = path.file_name() {
             if let Some(file_name_str) = file_name.to_str() {
@@ -86,7 +142,7 @@ pub fn should_include_path(path: &PathBuf, config: &ContextConfig) -> bool {
             }
         }
     }
-    true
+    config.ignore.is_empty() || !config.ignore.is_ignored(path, path.is_dir())
 }
 
 /// Helper function to validate context size
@@ -102,6 +158,6 @@ pub fn validate_size(size: usize, max_size: usize, context_type: &str) -> Contex
 }
 
 /// Helper function to format file paths for display
-pub fn format_path_for_display(path: &PathBuf) -> String {
+pub fn format_path_for_display(path: &Path) -> String {
     path.to_string_lossy().to_string()
 }
@@ -0,0 +1,129 @@
+use async_trait::async_trait;
+use std::path::{Path, PathBuf};
+
+use ignore::WalkBuilder;
+
+use super::code::{self, Language};
+use super::{ContextConfig, ContextData, ContextError, ContextProvider, ContextResult, ContextType};
+use super::{format_path_for_display, should_include_path};
+
+/// Files larger than this are listed without an outline; they are usually generated
+const MAX_FILE_BYTES: u64 = 1024 * 1024;
+
+/// A map of the modules, types, and function signatures under a path, for
+/// questions about a codebase's structure rather than its details. Files
+/// ignored by git or `.qignore` are left out.
+pub struct OutlineProvider {
+    path: PathBuf,
+    config: ContextConfig,
+}
+
+impl OutlineProvider {
+    pub fn new(path: PathBuf, config: ContextConfig) -> Self {
+        Self { path, config }
+    }
+
+    async fn format_outline(&self) -> ContextResult<String> {
+        if !self.path.exists() {
+            return Err(ContextError::FileNotFound(self.path.clone()));
+        }
+        let path = self.path.clone();
+        let config = self.config.clone();
+        tokio::task::spawn_blocking(move || build_outline(&path, &config))
+            .await
+            .map_err(|e| ContextError::Other(format!("Outline failed: {}", e)))?
+    }
+}
+
+fn build_outline(root: &Path, config: &ContextConfig) -> ContextResult<String> {
+    let mut files: Vec<(PathBuf, Language)> = WalkBuilder::new(root)
+        .hidden(!config.include_hidden)
+        .build()
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_some_and(|kind| kind.is_file()))
+        .filter(|entry| should_include_path(entry.path(), config))
+        .filter_map(|entry| Language::from_path(entry.path()).map(|language| (entry.into_path(), language)))
+        .collect();
+    files.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    let mut output = format!("Outline of {}:\n", format_path_for_display(root));
+    for (index, (path, language)) in files.iter().enumerate() {
+        let relative = path.strip_prefix(root).ok()
+            .filter(|relative| !relative.as_os_str().is_empty())
+            .unwrap_or(path);
+        let mut section = format!("\n{}\n", format_path_for_display(relative));
+        let source = match std::fs::metadata(path) {
+            Ok(metadata) if metadata.len() <= MAX_FILE_BYTES => std::fs::read_to_string(path).ok(),
+            _ => None,
+        };
+        match source.and_then(|source| code::outline(&source, *language)) {
+            Some(outline) => outline.lines().for_each(|line| section.push_str(&format!("    {}\n", line))),
+            None => section.push_str("    (not outlined)\n"),
+        }
+
+        if output.len() + section.len() > config.max_size {
+            output.push_str(&format!("\n[outline truncated: {} more files]\n", files.len() - index));
+            break;
+        }
+        output.push_str(&section);
+    }
+    Ok(output)
+}
+
+#[async_trait]
+impl ContextProvider for OutlineProvider {
+    fn context_type(&self) -> ContextType {
+        ContextType::Outline(self.path.clone())
+    }
+
+    async fn get_context(&self) -> ContextResult<ContextData> {
+        let content = self.format_outline().await?;
+
+        Ok(ContextData {
+            context_type: self.context_type(),
+            content,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::qignore::QIgnore;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_outline_of_directory() {
+        let temp_dir = tempdir().unwrap();
+        let base_path = temp_dir.path();
+        fs::create_dir_all(base_path.join("src/api")).unwrap();
+        fs::create_dir_all(base_path.join("vendor")).unwrap();
+        fs::write(base_path.join("src/main.rs"), "fn main() {\n    run();\n}\n").unwrap();
+        fs::write(base_path.join("src/api/client.py"), "class Client:\n    def send(self, body):\n        pass\n").unwrap();
+        fs::write(base_path.join("src/notes.md"), "# Notes\n").unwrap();
+        fs::write(base_path.join("vendor/lib.rs"), "pub fn vendored() {}\n").unwrap();
+
+        let ignore = QIgnore::load(base_path, &["vendor/".to_string()]).unwrap();
+        let config = ContextConfig { ignore, ..ContextConfig::default() };
+        let content = OutlineProvider::new(base_path.to_path_buf(), config).get_context().await.unwrap().content;
+
+        assert!(content.ends_with(
+            "\nsrc/api/client.py\n    class Client:\n        def send(self, body):\n\nsrc/main.rs\n    fn main()\n"
+        ), "{}", content);
+        assert!(!content.contains("vendored") && !content.contains("notes.md"));
+    }
+
+    #[tokio::test]
+    async fn test_outline_size_limit() {
+        let temp_dir = tempdir().unwrap();
+        for i in 0..10 {
+            fs::write(temp_dir.path().join(format!("m{}.rs", i)), "pub fn exported() {}\n").unwrap();
+        }
+
+        let config = ContextConfig { max_size: 120, ..ContextConfig::default() };
+        let content = OutlineProvider::new(temp_dir.path().to_path_buf(), config).get_context().await.unwrap().content;
+        assert!(content.len() < 160);
+        assert!(content.contains("more files]"));
+    }
+}
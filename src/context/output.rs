@@ -0,0 +1,252 @@
+use async_trait::async_trait;
+use std::io::Read;
+use std::process::Command;
+
+use super::{ContextConfig, ContextData, ContextError, ContextProvider, ContextResult, ContextType};
+use crate::utils::format::strip_ansi;
+
+/// Bytes of cleaned output kept; build logs are mostly noise past this
+pub const MAX_OUTPUT_BYTES: usize = 64 * 1024;
+/// Lines longer than this (minified code, base64, progress dumps) keep only their ends
+const MAX_LINE_BYTES: usize = 1024;
+/// One part in this many of a cut goes to the start of the output, the rest to the end
+const HEAD_SHARE: usize = 4;
+
+/// Where the output came from
+#[derive(Clone)]
+enum Source {
+    /// Text piped to q
+    Stdin(String),
+    /// A command q runs itself (`--exec`)
+    Exec(String),
+}
+
+/// Piped input or a command's output, cleaned for a prompt: escape codes
+/// and progress-bar redraws removed, repeated lines collapsed, long lines
+/// shortened, and the middle cut when it's too long, since the start (what
+/// ran) and the end (how it failed) matter most
+#[derive(Clone)]
+pub struct OutputProvider {
+    source: Source,
+    config: ContextConfig,
+}
+
+impl OutputProvider {
+    /// Text read from stdin
+    pub fn stdin(input: String, config: ContextConfig) -> Self {
+        Self { source: Source::Stdin(input), config }
+    }
+
+    /// The output of `command`, run with the shell
+    pub fn exec(command: String, config: ContextConfig) -> Self {
+        Self { source: Source::Exec(command), config }
+    }
+
+    fn format_output(&self) -> ContextResult<String> {
+        let max = self.config.max_size.min(MAX_OUTPUT_BYTES);
+        Ok(match &self.source {
+            Source::Stdin(input) => format!("Piped input:\n{}", clean(input, max)),
+            Source::Exec(command) => {
+                let (output, status) = run(command)?;
+                format!("Output of `{}` ({}):\n{}", command, status, clean(&output, max))
+            }
+        })
+    }
+}
+
+/// Whether stdin has input to read: a pipe or a file. A terminal is left for
+/// prompts like --pick, and /dev/null or a socket (as under cron, systemd,
+/// or an editor's task runner) would block or read nothing.
+#[cfg(unix)]
+pub fn has_piped_stdin() -> bool {
+    use std::os::fd::AsFd;
+    std::io::stdin().as_fd().try_clone_to_owned()
+        .and_then(|fd| std::fs::File::from(fd).metadata())
+        .is_ok_and(|metadata| is_input(metadata.file_type()))
+}
+
+#[cfg(not(unix))]
+pub fn has_piped_stdin() -> bool {
+    use std::io::IsTerminal;
+    !std::io::stdin().is_terminal()
+}
+
+#[cfg(unix)]
+fn is_input(file_type: std::fs::FileType) -> bool {
+    use std::os::unix::fs::FileTypeExt;
+    file_type.is_fifo() || file_type.is_file()
+}
+
+/// All of stdin, as text
+pub fn read_stdin() -> ContextResult<String> {
+    let mut bytes = Vec::new();
+    std::io::stdin().lock().read_to_end(&mut bytes)?;
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// `command`'s stdout and stderr, interleaved as a terminal would show them,
+/// and how it exited
+fn run(command: &str) -> ContextResult<(String, String)> {
+    let (shell, flag, script) = if cfg!(windows) {
+        ("cmd", "/C", format!("({}) 2>&1", command))
+    } else {
+        ("sh", "-c", format!("{{ {}\n}} 2>&1", command))
+    };
+    let output = Command::new(shell)
+        .args([flag, &script])
+        .output()
+        .map_err(|e| ContextError::Other(format!("Couldn't run {}: {}", command, e)))?;
+    let status = match output.status.code() {
+        Some(0) => "exit status 0".to_string(),
+        Some(code) => format!("failed with exit status {}", code),
+        None => "killed by a signal".to_string(),
+    };
+    Ok((String::from_utf8_lossy(&output.stdout).into_owned(), status))
+}
+
+/// Output made fit for a prompt, at most `max` bytes
+pub(crate) fn clean(text: &str, max: usize) -> String {
+    let text = strip_ansi(text);
+    let mut lines: Vec<(String, usize)> = Vec::new();
+    for line in text.lines() {
+        // A carriage return redraws the line, as progress bars do; keep the last drawing
+        let line = shorten(line.rsplit('\r').find(|part| !part.trim().is_empty()).unwrap_or(""));
+        match lines.last_mut() {
+            Some((last, count)) if *last == line => *count += 1,
+            _ => lines.push((line, 1)),
+        }
+    }
+    let mut cleaned = String::new();
+    for (line, count) in lines {
+        cleaned.push_str(&line);
+        if count > 1 {
+            cleaned.push_str(&format!(" [repeated {} times]", count));
+        }
+        cleaned.push('\n');
+    }
+    truncate_middle(&cleaned, max)
+}
+
+/// A line over `MAX_LINE_BYTES` as its start and end around a note
+fn shorten(line: &str) -> String {
+    if line.len() <= MAX_LINE_BYTES {
+        return line.to_string();
+    }
+    let mut head = MAX_LINE_BYTES / 2;
+    while !line.is_char_boundary(head) {
+        head -= 1;
+    }
+    let mut tail = line.len() - MAX_LINE_BYTES / 4;
+    while !line.is_char_boundary(tail) {
+        tail += 1;
+    }
+    format!("{}[... {} bytes ...]{}", &line[..head], tail - head, &line[tail..])
+}
+
+/// `text` cut to `max` bytes by dropping whole lines from the middle, keeping
+/// a quarter of the budget for the start and the rest for the end
+pub(crate) fn truncate_middle(text: &str, max: usize) -> String {
+    if text.len() <= max {
+        return text.to_string();
+    }
+    let marker = |lines: usize, bytes: usize| format!("[... {} lines ({} bytes) omitted ...]\n", lines, bytes);
+    let budget = max.saturating_sub(marker(text.len(), text.len()).len());
+    let head_budget = budget / HEAD_SHARE;
+
+    let mut head = head_budget;
+    while !text.is_char_boundary(head) {
+        head -= 1;
+    }
+    let head = text[..head].rfind('\n').map(|i| i + 1).unwrap_or(0);
+    let mut tail = text.len() - (budget - head_budget);
+    while !text.is_char_boundary(tail) {
+        tail += 1;
+    }
+    let tail = if text[..tail].ends_with('\n') {
+        tail
+    } else {
+        text[tail..].find('\n').map(|i| tail + i + 1).unwrap_or(text.len())
+    };
+    let omitted = &text[head..tail];
+    format!("{}{}{}", &text[..head], marker(omitted.lines().count(), omitted.len()), &text[tail..])
+}
+
+/// A block from `OutputProvider` cut to `max` bytes from the middle, keeping
+/// its header line
+pub(crate) fn fit_block(block: &str, max: usize) -> Option<String> {
+    let (header, body) = block.split_once('\n')?;
+    if !header.starts_with("Piped input:") && !header.starts_with("Output of `") {
+        return None;
+    }
+    let body_max = max.checked_sub(header.len() + 1)?;
+    Some(format!("{}\n{}", header, truncate_middle(body, body_max)))
+}
+
+#[async_trait]
+impl ContextProvider for OutputProvider {
+    fn context_type(&self) -> ContextType {
+        match &self.source {
+            Source::Stdin(_) => ContextType::Stdin,
+            Source::Exec(command) => ContextType::Exec(command.clone()),
+        }
+    }
+
+    async fn get_context(&self) -> ContextResult<ContextData> {
+        let provider = self.clone();
+        let content = tokio::task::spawn_blocking(move || provider.format_output())
+            .await
+            .map_err(|e| ContextError::Other(format!("Capturing output failed: {}", e)))??;
+
+        Ok(ContextData {
+            context_type: self.context_type(),
+            content,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_is_input() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        assert!(is_input(file.path().metadata().unwrap().file_type()));
+        assert!(!is_input(std::fs::metadata("/dev/null").unwrap().file_type()));
+        assert!(!is_input(std::env::temp_dir().metadata().unwrap().file_type()));
+    }
+
+    #[test]
+    fn test_clean() {
+        let output = "\x1b[32mCompiling\x1b[0m q\nDownloading 10%\rDownloading 55%\rDownloading 100%\nwarning: unused\nwarning: unused\nwarning: unused\nerror: failed\n";
+        assert_eq!(
+            clean(output, 1024),
+            "Compiling q\nDownloading 100%\nwarning: unused [repeated 3 times]\nerror: failed\n"
+        );
+        let long = "x".repeat(5000);
+        let shortened = clean(&long, 10_000);
+        assert!(shortened.len() < 1024 && shortened.contains("[... "));
+    }
+
+    #[test]
+    fn test_truncate_middle_keeps_both_ends() {
+        let text: String = (0..1000).map(|i| format!("line {}\n", i)).collect();
+        let cut = truncate_middle(&text, 400);
+        assert!(cut.len() <= 400, "{}", cut.len());
+        assert!(cut.starts_with("line 0\nline 1\n"));
+        assert!(cut.ends_with("line 999\n"));
+        assert!(cut.contains(" lines ("));
+        assert_eq!(truncate_middle("short\n", 400), "short\n");
+    }
+
+    #[tokio::test]
+    async fn test_exec_context() {
+        if cfg!(windows) {
+            return;
+        }
+        let provider = OutputProvider::exec("echo out; echo err >&2; exit 3".to_string(), ContextConfig::default());
+        let content = provider.get_context().await.unwrap().content;
+        assert_eq!(content, "Output of `echo out; echo err >&2; exit 3` (failed with exit status 3):\nout\nerr\n");
+    }
+}
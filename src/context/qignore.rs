@@ -0,0 +1,101 @@
+//! `.qignore`: gitignore-style patterns for paths kept out of gathered
+//! context even when git tracks them, such as fixtures, vendored code, and
+//! lockfiles.
+//!
+//! Patterns come from the nearest `.qignore` at or above the directory being
+//! gathered, relative to that file, and from `[context] ignore` in the config,
+//! relative to the directory itself. Providers that walk files ask the same
+//! `QIgnore` through `should_include_path`.
+
+use std::path::{Path, PathBuf};
+
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+
+use super::{ContextError, ContextResult};
+
+pub const QIGNORE_FILE: &str = ".qignore";
+
+#[derive(Debug, Clone, Default)]
+pub struct QIgnore {
+    matchers: Vec<Gitignore>,
+}
+
+impl QIgnore {
+    /// The rules for gathering context under `dir`
+    pub fn load(dir: &Path, patterns: &[String]) -> ContextResult<Self> {
+        let mut matchers = Vec::new();
+        if let Some(file) = find_qignore(dir) {
+            let root = file.parent().unwrap_or(dir);
+            let mut builder = GitignoreBuilder::new(root);
+            if let Some(e) = builder.add(&file) {
+                return Err(invalid(&file.display().to_string(), e));
+            }
+            matchers.push(builder.build().map_err(|e| invalid(QIGNORE_FILE, e))?);
+        }
+        if !patterns.is_empty() {
+            let mut builder = GitignoreBuilder::new(dir);
+            for pattern in patterns {
+                builder.add_line(None, pattern).map_err(|e| invalid("[context] ignore", e))?;
+            }
+            matchers.push(builder.build().map_err(|e| invalid("[context] ignore", e))?);
+        }
+        Ok(Self { matchers })
+    }
+
+    /// Whether `path`, or a directory above it, matches a pattern that isn't
+    /// negated with `!`. Paths outside a rule set's directory never match it.
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        self.matchers.iter().any(|matcher| {
+            path.starts_with(matcher.path())
+                && matcher.matched_path_or_any_parents(path, is_dir).is_ignore()
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.matchers.is_empty()
+    }
+}
+
+/// The nearest `.qignore` in `dir` or its parents
+fn find_qignore(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .map(|dir| dir.join(QIGNORE_FILE))
+        .find(|path| path.is_file())
+}
+
+fn invalid(source: &str, e: ignore::Error) -> ContextError {
+    ContextError::Other(format!("Invalid pattern in {}: {}", source, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_qignore_patterns() {
+        let temp_dir = tempdir().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("app/src")).unwrap();
+        fs::write(root.join(".qignore"), "*.lock\nvendor/\n/fixtures\n!keep.lock\n").unwrap();
+
+        let rules = QIgnore::load(&root.join("app"), &["*.snap".to_string()]).unwrap();
+        assert!(rules.is_ignored(&root.join("app/Cargo.lock"), false));
+        assert!(!rules.is_ignored(&root.join("app/keep.lock"), false));
+        assert!(rules.is_ignored(&root.join("app/vendor/lib.rs"), false));
+        assert!(rules.is_ignored(&root.join("fixtures"), true));
+        assert!(!rules.is_ignored(&root.join("app/fixtures"), true));
+        assert!(rules.is_ignored(&root.join("app/src/ui.snap"), false));
+        assert!(!rules.is_ignored(&root.join("app/src/main.rs"), false));
+        assert!(!rules.is_ignored(Path::new("/elsewhere/x.snap"), false));
+    }
+
+    #[test]
+    fn test_no_rules() {
+        let temp_dir = tempdir().unwrap();
+        let rules = QIgnore::load(temp_dir.path(), &[]).unwrap();
+        assert!(!rules.is_ignored(&temp_dir.path().join("Cargo.lock"), false));
+        assert!(QIgnore::load(temp_dir.path(), &["{a,b".to_string()]).is_err());
+    }
+}
@@ -0,0 +1,173 @@
+use async_trait::async_trait;
+use sysinfo::{Disks, System};
+
+use super::{ContextConfig, ContextData, ContextError, ContextProvider, ContextResult, ContextType};
+use super::validate_size;
+
+/// Processes listed by memory use
+const TOP_PROCESSES: usize = 5;
+
+/// A snapshot of the machine for troubleshooting questions (`--sys`)
+pub struct SystemProvider {
+    config: ContextConfig,
+}
+
+impl SystemProvider {
+    pub fn new(config: ContextConfig) -> Self {
+        Self { config }
+    }
+
+    fn format_system_info(&self) -> String {
+        let mut sys = System::new();
+        sys.refresh_memory();
+        sys.refresh_cpu();
+        sys.refresh_processes();
+
+        let mut output = String::from("System information:\n");
+
+        let os = [System::name(), System::os_version()]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join(" ");
+        output.push_str(&format!(
+            "OS: {} (kernel {}, {})\n",
+            if os.is_empty() { std::env::consts::OS.to_string() } else { os },
+            System::kernel_version().unwrap_or_else(|| "unknown".to_string()),
+            std::env::consts::ARCH
+        ));
+        output.push_str(&format!("Uptime: {}\n", format_duration(System::uptime())));
+
+        let cores = sys.cpus().len();
+        match sys.cpus().first().map(|cpu| cpu.brand().trim()).filter(|brand| !brand.is_empty()) {
+            Some(brand) => output.push_str(&format!("CPU: {} cores ({})\n", cores, brand)),
+            None => output.push_str(&format!("CPU: {} cores\n", cores)),
+        }
+        if cfg!(unix) {
+            let load = System::load_average();
+            output.push_str(&format!("Load average: {:.2} {:.2} {:.2}\n", load.one, load.five, load.fifteen));
+        }
+
+        output.push_str(&format!(
+            "Memory: {} used of {} ({} available)\n",
+            format_bytes(sys.used_memory()),
+            format_bytes(sys.total_memory()),
+            format_bytes(sys.available_memory())
+        ));
+        if sys.total_swap() > 0 {
+            output.push_str(&format!(
+                "Swap: {} used of {}\n",
+                format_bytes(sys.used_swap()),
+                format_bytes(sys.total_swap())
+            ));
+        }
+
+        let disks = Disks::new_with_refreshed_list();
+        if !disks.list().is_empty() {
+            output.push_str("Disks:\n");
+            for disk in disks.list() {
+                output.push_str(&format!(
+                    "  {}: {} free of {}\n",
+                    disk.mount_point().display(),
+                    format_bytes(disk.available_space()),
+                    format_bytes(disk.total_space())
+                ));
+            }
+        }
+
+        let mut processes: Vec<_> = sys.processes().values().collect();
+        processes.sort_by_key(|process| std::cmp::Reverse(process.memory()));
+        if !processes.is_empty() {
+            output.push_str("Top processes by memory:\n");
+            for process in processes.into_iter().take(TOP_PROCESSES) {
+                output.push_str(&format!(
+                    "  {} (pid {}): {}\n",
+                    process.name(),
+                    process.pid(),
+                    format_bytes(process.memory())
+                ));
+            }
+        }
+
+        output
+    }
+}
+
+/// Bytes in binary units with one decimal (e.g. `15.8 GiB`)
+pub(crate) fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+fn format_duration(seconds: u64) -> String {
+    let (days, hours, minutes) = (seconds / 86_400, seconds % 86_400 / 3_600, seconds % 3_600 / 60);
+    if days > 0 {
+        format!("{}d {}h {}m", days, hours, minutes)
+    } else {
+        format!("{}h {}m", hours, minutes)
+    }
+}
+
+#[async_trait]
+impl ContextProvider for SystemProvider {
+    fn context_type(&self) -> ContextType {
+        ContextType::System
+    }
+
+    async fn get_context(&self) -> ContextResult<ContextData> {
+        // sysinfo reads /proc (or the platform equivalent) synchronously
+        let provider = SystemProvider::new(self.config.clone());
+        let content = tokio::task::spawn_blocking(move || provider.format_system_info())
+            .await
+            .map_err(|e| ContextError::Other(format!("Failed to read system information: {}", e)))?;
+        validate_size(content.len(), self.config.max_size, "System information")?;
+
+        Ok(ContextData {
+            context_type: self.context_type(),
+            content,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(1536), "1.5 KiB");
+        assert_eq!(format_bytes(16 * 1024 * 1024 * 1024), "16.0 GiB");
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(3_720), "1h 2m");
+        assert_eq!(format_duration(90_061), "1d 1h 1m");
+    }
+
+    #[tokio::test]
+    async fn test_system_context() {
+        let context = SystemProvider::new(ContextConfig::default()).get_context().await.unwrap();
+        assert!(context.content.starts_with("System information:\n"));
+        assert!(context.content.contains("Memory: "));
+        assert!(context.content.contains("CPU: "));
+    }
+
+    #[tokio::test]
+    async fn test_system_context_respects_size_limit() {
+        let config = ContextConfig { max_size: 10, ..ContextConfig::default() };
+        let result = SystemProvider::new(config).get_context().await;
+        assert!(matches!(result, Err(ContextError::TooLarge(_))));
+    }
+}
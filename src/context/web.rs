@@ -0,0 +1,243 @@
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::Deserialize;
+use std::time::Duration;
+
+use super::{ContextConfig, ContextData, ContextError, ContextProvider, ContextResult, ContextType};
+use super::validate_size;
+use crate::config::types::{SearchEngine, WebConfig};
+
+const BRAVE_API_URL: &str = "https://api.search.brave.com/res/v1/web/search";
+const SEARCH_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// A web search result added to the prompt
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchResult {
+    pub title: String,
+    pub url: String,
+    pub snippet: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct SearxngResponse {
+    #[serde(default)]
+    results: Vec<SearxngResult>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SearxngResult {
+    title: String,
+    url: String,
+    #[serde(default)]
+    content: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct BraveResponse {
+    #[serde(default)]
+    web: Option<BraveResults>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BraveResults {
+    #[serde(default)]
+    results: Vec<BraveResult>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BraveResult {
+    title: String,
+    url: String,
+    #[serde(default)]
+    description: String,
+}
+
+/// Searches the web for the prompt through the `[web]` search API, for
+/// `--web` when the provider's built-in search isn't used
+pub struct WebProvider {
+    query: String,
+    web: WebConfig,
+    api_key: Option<String>,
+    api_url: Option<String>,
+    config: ContextConfig,
+}
+
+impl WebProvider {
+    pub fn new(query: String, web: WebConfig, api_key: Option<String>, config: ContextConfig) -> Self {
+        Self { query, web, api_key, api_url: None, config }
+    }
+
+    /// Override the Brave endpoint (for testing)
+    pub fn with_api_url(mut self, url: String) -> Self {
+        self.api_url = Some(url);
+        self
+    }
+
+    pub async fn search(&self) -> ContextResult<Vec<SearchResult>> {
+        let client = Client::builder()
+            .timeout(SEARCH_TIMEOUT)
+            .build()
+            .map_err(|e| ContextError::Other(format!("Failed to create HTTP client: {}", e)))?;
+
+        let mut results = match self.web.engine {
+            SearchEngine::Searxng => self.search_searxng(&client).await?,
+            SearchEngine::Brave => self.search_brave(&client).await?,
+        };
+        results.truncate(self.web.results);
+        Ok(results)
+    }
+
+    async fn search_searxng(&self, client: &Client) -> ContextResult<Vec<SearchResult>> {
+        let base = self.web.url.as_deref()
+            .ok_or_else(|| ContextError::Other("[web] engine = \"searxng\" needs a url".to_string()))?;
+        let response = client
+            .get(format!("{}/search", base.trim_end_matches('/')))
+            .query(&[("q", self.query.as_str()), ("format", "json")])
+            .send()
+            .await
+            .map_err(|e| ContextError::Other(format!("Web search failed: {}", e)))?;
+        if !response.status().is_success() {
+            return Err(ContextError::Other(format!("Web search failed: SearxNG returned {}", response.status())));
+        }
+        let body: SearxngResponse = response.json()
+            .await
+            .map_err(|e| ContextError::Other(format!("Failed to parse search results: {}", e)))?;
+        Ok(body.results.into_iter()
+            .map(|r| SearchResult { title: r.title, url: r.url, snippet: r.content })
+            .collect())
+    }
+
+    async fn search_brave(&self, client: &Client) -> ContextResult<Vec<SearchResult>> {
+        let key = self.api_key.as_deref()
+            .ok_or_else(|| ContextError::Other("Brave Search needs BRAVE_API_KEY".to_string()))?;
+        let count = self.web.results.to_string();
+        let response = client
+            .get(self.api_url.as_deref().unwrap_or(BRAVE_API_URL))
+            .header("X-Subscription-Token", key)
+            .header("Accept", "application/json")
+            .query(&[("q", self.query.as_str()), ("count", count.as_str())])
+            .send()
+            .await
+            .map_err(|e| ContextError::Other(format!("Web search failed: {}", e)))?;
+        if !response.status().is_success() {
+            return Err(ContextError::Other(format!("Web search failed: Brave returned {}", response.status())));
+        }
+        let body: BraveResponse = response.json()
+            .await
+            .map_err(|e| ContextError::Other(format!("Failed to parse search results: {}", e)))?;
+        Ok(body.web.map(|web| web.results).unwrap_or_default().into_iter()
+            .map(|r| SearchResult { title: r.title, url: r.url, snippet: r.description })
+            .collect())
+    }
+}
+
+/// Numbered results, with an instruction to cite them by number
+pub fn format_results(query: &str, results: &[SearchResult]) -> String {
+    let mut output = format!("Web search results for \"{}\":\n", query);
+    for (i, result) in results.iter().enumerate() {
+        output.push_str(&format!("[{}] {}\n{}\n{}\n", i + 1, result.title, result.url, result.snippet.trim()));
+    }
+    output.push_str("\nUse these results for anything time-sensitive. Cite them as [n] and list the URLs you used at the end.\n");
+    output
+}
+
+#[async_trait]
+impl ContextProvider for WebProvider {
+    fn context_type(&self) -> ContextType {
+        ContextType::Web(self.query.clone())
+    }
+
+    async fn get_context(&self) -> ContextResult<ContextData> {
+        let results = self.search().await?;
+        if results.is_empty() {
+            return Err(ContextError::Other(format!("No web results for \"{}\"", self.query)));
+        }
+        let content = format_results(&self.query, &results);
+        validate_size(content.len(), self.config.max_size, "Web search")?;
+
+        Ok(ContextData {
+            context_type: self.context_type(),
+            content,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+    use wiremock::matchers::{header, method, path, query_param};
+
+    fn web(engine: SearchEngine, url: Option<String>) -> WebConfig {
+        WebConfig { engine, url, results: 2 }
+    }
+
+    #[tokio::test]
+    async fn test_searxng_search() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/search"))
+            .and(query_param("q", "rust release"))
+            .and(query_param("format", "json"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "results": [
+                    {"title": "Rust 1.80", "url": "https://blog.rust-lang.org/1", "content": "Released"},
+                    {"title": "Rust 1.79", "url": "https://blog.rust-lang.org/2", "content": "Older"},
+                    {"title": "Rust 1.78", "url": "https://blog.rust-lang.org/3", "content": "Oldest"}
+                ]
+            })))
+            .mount(&mock_server)
+            .await;
+
+        let provider = WebProvider::new(
+            "rust release".to_string(),
+            web(SearchEngine::Searxng, Some(format!("{}/", mock_server.uri()))),
+            None,
+            ContextConfig::default(),
+        );
+        let context = provider.get_context().await.unwrap();
+        assert!(context.content.contains("[1] Rust 1.80\nhttps://blog.rust-lang.org/1\nReleased"));
+        assert!(context.content.contains("[2] Rust 1.79"));
+        assert!(!context.content.contains("Rust 1.78"));
+    }
+
+    #[tokio::test]
+    async fn test_brave_search() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(header("X-Subscription-Token", "brave-key"))
+            .and(query_param("q", "weather"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "web": {"results": [
+                    {"title": "Forecast", "url": "https://example.com", "description": "Sunny"}
+                ]}
+            })))
+            .mount(&mock_server)
+            .await;
+
+        let provider = WebProvider::new(
+            "weather".to_string(),
+            web(SearchEngine::Brave, None),
+            Some("brave-key".to_string()),
+            ContextConfig::default(),
+        ).with_api_url(mock_server.uri());
+        let results = provider.search().await.unwrap();
+        assert_eq!(results, vec![SearchResult {
+            title: "Forecast".to_string(),
+            url: "https://example.com".to_string(),
+            snippet: "Sunny".to_string(),
+        }]);
+    }
+
+    #[tokio::test]
+    async fn test_brave_requires_key() {
+        let provider = WebProvider::new(
+            "weather".to_string(),
+            web(SearchEngine::Brave, None),
+            None,
+            ContextConfig::default(),
+        );
+        assert!(provider.search().await.unwrap_err().to_string().contains("BRAVE_API_KEY"));
+    }
+}
@@ -1,30 +1,44 @@
-use std::time::Duration;
-use cached::{TimedCache, Cached};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use cached::{SizedCache, Cached};
+use sha2::{Digest, Sha256};
 use std::sync::Mutex;
 
+use super::crypto::{self, Cipher};
+
 /// Cache for storing query responses
+///
+/// Entries are evicted least-recently-used once `size` is reached and
+/// expire `ttl` after insertion.
 pub struct QueryCache {
-    cache: Mutex<TimedCache<String, String>>,
+    cache: Mutex<SizedCache<String, (Instant, String)>>,
+    ttl: Duration,
 }
 
 impl QueryCache {
     /// Create a new query cache with the specified size and TTL
     pub fn new(size: usize, ttl: Duration) -> Self {
         Self {
-            cache: Mutex::new(TimedCache::with_lifespan_and_capacity(
-                ttl.as_secs() as u64,
-                size,
-            )),
+            cache: Mutex::new(SizedCache::with_size(size.max(1))),
+            ttl,
         }
     }
 
     /// Get a cached response for a query
     pub fn get(&self, query: &str) -> Option<String> {
-        self.cache
-            .lock()
-            .expect("Failed to lock cache")
-            .cache_get(&query.to_string())
-            .cloned()
+        let mut cache = self.cache.lock().expect("Failed to lock cache");
+        let key = query.to_string();
+
+        match cache.cache_get(&key) {
+            Some((inserted, response)) if inserted.elapsed() < self.ttl => Some(response.clone()),
+            Some(_) => {
+                cache.cache_remove(&key);
+                None
+            }
+            None => None,
+        }
     }
 
     /// Insert a response into the cache
@@ -32,7 +46,7 @@ impl QueryCache {
         self.cache
             .lock()
             .expect("Failed to lock cache")
-            .cache_set(query, response);
+            .cache_set(query, (Instant::now(), response));
     }
 
     /// Clear the cache
@@ -57,6 +71,79 @@ impl QueryCache {
     }
 }
 
+/// Responses kept between runs, one file per prompt under
+/// `CachePaths::responses_dir`. A file is named by the SHA-256 of `scope`
+/// (the provider, model, and settings that shape an answer) and the prompt,
+/// and expires `ttl` after it was written. With a cipher, responses are
+/// sealed like the history log; ones it can't open count as missing.
+#[derive(Debug, Clone)]
+pub struct DiskCache {
+    dir: PathBuf,
+    scope: String,
+    ttl: Duration,
+    cipher: Option<Cipher>,
+}
+
+impl DiskCache {
+    pub fn new(dir: PathBuf, scope: impl Into<String>, ttl: Duration) -> Self {
+        Self { dir, scope: scope.into(), ttl, cipher: None }
+    }
+
+    /// Seal responses with `cipher` and open sealed ones when reading
+    pub fn with_cipher(mut self, cipher: Cipher) -> Self {
+        self.cipher = Some(cipher);
+        self
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    fn path(&self, query: &str) -> PathBuf {
+        let mut hasher = Sha256::new();
+        hasher.update(self.scope.as_bytes());
+        hasher.update([0]);
+        hasher.update(query.as_bytes());
+        let name: String = hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect();
+        self.dir.join(name)
+    }
+
+    /// The stored response for a query, unless it is missing or expired
+    pub async fn get(&self, query: &str) -> Option<String> {
+        self.get_aged(query).await.map(|(response, _)| response)
+    }
+
+    /// `get`, with how long ago the response was stored
+    pub async fn get_aged(&self, query: &str) -> Option<(String, Duration)> {
+        let path = self.path(query);
+        let age = tokio::fs::metadata(&path).await.ok()?.modified().ok()?.elapsed().ok()?;
+        if age >= self.ttl {
+            return None;
+        }
+        let stored = tokio::fs::read_to_string(&path).await.ok()?;
+        let response = match &self.cipher {
+            Some(cipher) => cipher.open(&stored)?,
+            // Sealed by a run that had the key; this one can't use it
+            None if crypto::is_sealed(&stored) => return None,
+            None => stored,
+        };
+        Some((response, age))
+    }
+
+    /// Store a response, replacing the file whole so a concurrent reader
+    /// never sees half of it
+    pub fn insert(&self, query: &str, response: &str) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let path = self.path(query);
+        let partial = path.with_extension(format!("{}.tmp", std::process::id()));
+        match &self.cipher {
+            Some(cipher) => fs::write(&partial, cipher.seal(response))?,
+            None => fs::write(&partial, response)?,
+        }
+        fs::rename(&partial, &path)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -112,4 +199,35 @@ mod tests {
         assert_eq!(cache.get("query2"), Some("response2".to_string()));
         assert_eq!(cache.get("query3"), Some("response3".to_string()));
     }
+
+    #[tokio::test]
+    async fn test_disk_cache() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let cache = DiskCache::new(dir.path().join("responses"), "openai/gpt-4o", Duration::from_secs(60));
+        assert_eq!(cache.get("test query").await, None);
+
+        cache.insert("test query", "test response").unwrap();
+        assert_eq!(cache.get("test query").await, Some("test response".to_string()));
+        // Another model's answer to the same prompt is kept apart
+        let other = DiskCache::new(dir.path().join("responses"), "gemini/gemini-2.5-flash", Duration::from_secs(60));
+        assert_eq!(other.get("test query").await, None);
+
+        let expired = DiskCache::new(dir.path().join("responses"), "openai/gpt-4o", Duration::ZERO);
+        assert_eq!(expired.get("test query").await, None);
+    }
+
+    #[tokio::test]
+    async fn test_sealed_disk_cache() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let cache = DiskCache::new(dir.path().to_path_buf(), "openai/gpt-4o", Duration::from_secs(60))
+            .with_cipher(Cipher::new(&[7; 32]));
+        cache.insert("test query", "a secret answer").unwrap();
+        let stored = fs::read_dir(dir.path()).unwrap().next().unwrap().unwrap().path();
+        assert!(!fs::read_to_string(stored).unwrap().contains("secret"));
+        assert_eq!(cache.get("test query").await.as_deref(), Some("a secret answer"));
+
+        // Without the key it's a miss, not ciphertext
+        let unsealed = DiskCache::new(dir.path().to_path_buf(), "openai/gpt-4o", Duration::from_secs(60));
+        assert_eq!(unsealed.get("test query").await, None);
+    }
 }
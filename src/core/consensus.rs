@@ -0,0 +1,134 @@
+//! Consensus answers: several models answer the same prompt, then a judge
+//! model merges their answers into one and notes where they disagreed
+
+use futures::future::join_all;
+
+use super::{CoreError, CoreResult, QueryEngine};
+
+/// Tokens the judge's prompt may take when `[consensus]` doesn't say
+pub const DEFAULT_JUDGE_TOKENS: usize = 32_000;
+/// Characters counted per token when budgeting the judge's prompt. Three is
+/// the pessimistic end of `pricing::estimate_tokens`, so the prompt errs short.
+const CHARS_PER_TOKEN: usize = 3;
+/// One part in this many of the budget may go to the question; with context
+/// it can be long, and the answers matter more to the judge
+const QUESTION_SHARE: usize = 4;
+/// Room left for the `[... cut N characters ...]` note in a shortened text
+const CUT_MARKER_CHARS: usize = 40;
+
+const JUDGE_INSTRUCTIONS: &str = "Several models answered the same question. Merge their answers into one \
+answer that keeps what they agree on and settles what they disagree on, preferring what is correct over \
+what most of them said. Write it as a direct answer to the question, without mentioning the models. Then \
+add a section headed `### Disagreements` listing each point where the answers differ: what each model \
+said, which you kept, and why. Write `None` under it when they agree. Text marked [... cut ...] was \
+shortened to fit.";
+
+/// A model answering for the panel
+pub struct Panelist {
+    /// `provider/model`, as the judge and the user see it
+    pub label: String,
+    pub engine: QueryEngine,
+}
+
+/// Every panelist's answer or error, and the judge's merged answer
+pub struct Consensus {
+    pub answers: Vec<(String, Result<String, String>)>,
+    pub merged: String,
+}
+
+/// Ask every panelist `prompt` at once, then have `judge` merge the answers
+/// in a prompt of at most `max_tokens`. Panelists that fail are left out;
+/// at least two answers are needed for there to be anything to reconcile.
+pub async fn consensus(panel: &mut [Panelist], judge: &mut QueryEngine, prompt: &str, max_tokens: usize) -> CoreResult<Consensus> {
+    let answers = join_all(panel.iter_mut().map(|panelist| async move {
+        let answer = panelist.engine.query(prompt).await.map_err(|e| e.to_string());
+        (panelist.label.clone(), answer)
+    })).await;
+
+    let answered: Vec<(&str, &str)> = answers.iter()
+        .filter_map(|(label, answer)| Some((label.as_str(), answer.as_deref().ok()?)))
+        .collect();
+    if answered.len() < 2 {
+        return Err(CoreError::Other(format!(
+            "consensus needs at least two answers, but only {} of {} models answered",
+            answered.len(),
+            answers.len()
+        )));
+    }
+    let merged = judge.query(&judge_prompt(prompt, &answered, max_tokens)).await?;
+    Ok(Consensus { answers, merged })
+}
+
+/// The judge's prompt, cut to about `max_tokens`: the question gets up to a
+/// quarter of the budget and the answers split the rest, so one long answer
+/// can't crowd out the others
+fn judge_prompt(prompt: &str, answers: &[(&str, &str)], max_tokens: usize) -> String {
+    let labels: usize = answers.iter().map(|(label, _)| label.len() + 20).sum();
+    let budget = (max_tokens * CHARS_PER_TOKEN).saturating_sub(JUDGE_INSTRUCTIONS.len() + labels + 20);
+
+    let question = shorten(prompt, budget / QUESTION_SHARE);
+    let lengths: Vec<usize> = answers.iter().map(|(_, answer)| answer.chars().count()).collect();
+    let shares = fair_shares(&lengths, budget.saturating_sub(question.chars().count()));
+
+    let mut judge_prompt = format!("{}\n\nQuestion:\n{}\n", JUDGE_INSTRUCTIONS, question);
+    for ((label, answer), share) in answers.iter().zip(shares) {
+        judge_prompt.push_str(&format!("\nAnswer from {}:\n{}\n", label, shorten(answer, share)));
+    }
+    judge_prompt
+}
+
+/// `total` split between texts of `lengths`: short texts keep all of theirs,
+/// and the rest is shared evenly between the long ones
+fn fair_shares(lengths: &[usize], total: usize) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..lengths.len()).collect();
+    order.sort_by_key(|&i| lengths[i]);
+    let mut shares = vec![0; lengths.len()];
+    let mut left = total;
+    for (placed, &i) in order.iter().enumerate() {
+        shares[i] = lengths[i].min(left / (lengths.len() - placed));
+        left -= shares[i];
+    }
+    shares
+}
+
+/// `text` in at most `max` characters, keeping its start and end: a prompt
+/// with context ends with the question, and an answer with its conclusion
+fn shorten(text: &str, max: usize) -> String {
+    let chars = text.chars().count();
+    if chars <= max {
+        return text.to_string();
+    }
+    let keep = max.saturating_sub(CUT_MARKER_CHARS);
+    let head: String = text.chars().take(keep / 2).collect();
+    let tail: String = text.chars().skip(chars - keep / 2).collect();
+    format!("{}\n[... cut {} characters ...]\n{}", head, chars - keep / 2 * 2, tail)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fair_shares() {
+        assert_eq!(fair_shares(&[100, 5000, 20], 1000), [100, 880, 20]);
+        assert_eq!(fair_shares(&[5000, 5000], 1000), [500, 500]);
+        assert_eq!(fair_shares(&[10, 10], 1000), [10, 10]);
+    }
+
+    #[test]
+    fn test_shorten() {
+        assert_eq!(shorten("short", 10), "short");
+        let text = format!("{}{}", "a".repeat(50), "b".repeat(50));
+        assert_eq!(shorten(&text, 50), "aaaaa\n[... cut 90 characters ...]\nbbbbb");
+    }
+
+    #[test]
+    fn test_judge_prompt_fits_budget() {
+        let long = "word ".repeat(50_000);
+        let answers = [("openai/gpt-4o", long.as_str()), ("gemini/gemini-2.5-flash", "Use tar -xzf.")];
+        let prompt = judge_prompt(&long, &answers, 10_000);
+        assert!(prompt.chars().count() <= 10_000 * CHARS_PER_TOKEN, "{}", prompt.len());
+        assert!(prompt.contains("\nAnswer from gemini/gemini-2.5-flash:\nUse tar -xzf.\n"));
+        assert!(prompt.contains("### Disagreements"));
+    }
+}
@@ -0,0 +1,81 @@
+//! Encryption at rest for files q accumulates (the history log).
+//!
+//! Each record is sealed on its own with XChaCha20-Poly1305 under a random
+//! nonce, so encrypted records can be appended to a file without rewriting it
+//! and plaintext written before encryption was turned on stays readable.
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+
+/// Marks an encrypted record; the rest is base64 of the nonce and ciphertext
+const PREFIX: &str = "enc:v1:";
+const NONCE_LEN: usize = 24;
+pub const KEY_LEN: usize = 32;
+
+#[derive(Clone)]
+pub struct Cipher(XChaCha20Poly1305);
+
+/// Never shows the key
+impl std::fmt::Debug for Cipher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Cipher(..)")
+    }
+}
+
+impl Cipher {
+    pub fn new(key: &[u8; KEY_LEN]) -> Self {
+        Self(XChaCha20Poly1305::new(key.into()))
+    }
+
+    /// A new random key
+    pub fn generate_key() -> [u8; KEY_LEN] {
+        XChaCha20Poly1305::generate_key(&mut OsRng).into()
+    }
+
+    /// `plaintext` sealed as a single line of text
+    pub fn seal(&self, plaintext: &str) -> String {
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self.0.encrypt(&nonce, plaintext.as_bytes())
+            .expect("XChaCha20-Poly1305 encryption doesn't fail for in-memory buffers");
+        let mut sealed = nonce.to_vec();
+        sealed.extend(ciphertext);
+        format!("{}{}", PREFIX, STANDARD.encode(sealed))
+    }
+
+    /// The plaintext of a sealed line, or `None` if it was sealed with another
+    /// key or has been tampered with
+    pub fn open(&self, line: &str) -> Option<String> {
+        let sealed = STANDARD.decode(line.strip_prefix(PREFIX)?).ok()?;
+        if sealed.len() < NONCE_LEN {
+            return None;
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        let plaintext = self.0.decrypt(XNonce::from_slice(nonce), ciphertext).ok()?;
+        String::from_utf8(plaintext).ok()
+    }
+}
+
+pub fn is_sealed(line: &str) -> bool {
+    line.starts_with(PREFIX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seal_and_open() {
+        let cipher = Cipher::new(&[7; KEY_LEN]);
+        let sealed = cipher.seal("{\"prompt\":\"deploy key?\"}");
+        assert!(is_sealed(&sealed) && !sealed.contains("deploy"));
+        assert_ne!(sealed, cipher.seal("{\"prompt\":\"deploy key?\"}"));
+        assert_eq!(cipher.open(&sealed).as_deref(), Some("{\"prompt\":\"deploy key?\"}"));
+
+        assert_eq!(Cipher::new(&[8; KEY_LEN]).open(&sealed), None);
+        let mut tampered = sealed.clone();
+        tampered.replace_range(sealed.len() - 4.., "AAA=");
+        assert_eq!(cipher.open(&tampered), None);
+        assert_eq!(cipher.open("plain text"), None);
+    }
+}
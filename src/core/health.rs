@@ -0,0 +1,149 @@
+//! Recent provider outages, kept in a small JSON file under the state dir
+//! (`DataPaths::provider_health`) so the next invocation knows about them.
+//!
+//! Only failures that say the provider itself is in trouble count: timeouts,
+//! 5xx and overload replies, and connection errors. A provider that has
+//! failed `FAILURES_TO_FAIL_FAST` times in a row, the last within
+//! `OUTAGE_WINDOW`, is failing; any answer from it clears the record.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use super::CoreError;
+use crate::api::ApiError;
+
+/// How long a failure is remembered
+pub const OUTAGE_WINDOW: Duration = Duration::from_secs(10 * 60);
+/// Consecutive failures before a provider counts as failing
+pub const FAILURES_TO_FAIL_FAST: u32 = 2;
+
+/// A provider's run of failures
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Outage {
+    pub failures: u32,
+    /// Seconds since the epoch of the latest failure
+    pub last: u64,
+    /// The latest failure's message
+    pub reason: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Health {
+    #[serde(default)]
+    providers: BTreeMap<String, Outage>,
+}
+
+impl Health {
+    /// The recorded outages; a missing or unreadable file means none
+    pub fn load(path: &Path) -> Self {
+        fs::read_to_string(path).ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, serde_json::to_string(self)?)
+    }
+
+    /// Note how a query to `provider` ended: a failure that points at the
+    /// provider extends its outage, an answer ends it. Returns whether
+    /// anything changed.
+    pub fn record(&mut self, provider: &str, error: Option<&CoreError>, now: u64) -> bool {
+        match error {
+            None => self.providers.remove(provider).is_some(),
+            Some(error) if is_outage(error) => {
+                let failures = match self.providers.get(provider) {
+                    Some(outage) if within_window(outage, now) => outage.failures + 1,
+                    _ => 1,
+                };
+                self.providers.insert(provider.to_string(), Outage { failures, last: now, reason: error.to_string() });
+                true
+            }
+            Some(_) => false,
+        }
+    }
+
+    /// The provider's outage, if it has been failing recently
+    pub fn failing(&self, provider: &str, now: u64) -> Option<&Outage> {
+        self.providers.get(provider)
+            .filter(|outage| outage.failures >= FAILURES_TO_FAIL_FAST && within_window(outage, now))
+    }
+}
+
+fn within_window(outage: &Outage, now: u64) -> bool {
+    now.saturating_sub(outage.last) < OUTAGE_WINDOW.as_secs()
+}
+
+/// Whether `error` says the provider is down or struggling, rather than
+/// something about this request or key
+pub fn is_outage(error: &CoreError) -> bool {
+    matches!(
+        error,
+        CoreError::Api(ApiError::Timeout | ApiError::ServerOverloaded(_) | ApiError::Network(_))
+    )
+}
+
+/// Seconds since the epoch
+pub fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn timeout() -> CoreError {
+        CoreError::Api(ApiError::Timeout)
+    }
+
+    #[test]
+    fn test_failing_after_repeated_outages() {
+        let mut health = Health::default();
+        assert!(health.record("openai", Some(&timeout()), 1000));
+        assert!(health.failing("openai", 1000).is_none());
+        health.record("openai", Some(&CoreError::Api(ApiError::ServerOverloaded("503".to_string()))), 1060);
+        let outage = health.failing("openai", 1100).unwrap();
+        assert_eq!(outage.failures, 2);
+        assert!(outage.reason.contains("overloaded"));
+
+        // Forgotten once the window passes, and cleared by an answer
+        assert!(health.failing("openai", 1060 + OUTAGE_WINDOW.as_secs()).is_none());
+        assert!(health.record("openai", None, 1200));
+        assert!(health.failing("openai", 1200).is_none());
+    }
+
+    #[test]
+    fn test_only_provider_trouble_counts() {
+        let mut health = Health::default();
+        assert!(!health.record("openai", Some(&CoreError::Api(ApiError::InvalidKey)), 1000));
+        assert!(!health.record("openai", Some(&CoreError::Api(ApiError::RateLimit)), 1000));
+        assert!(!health.record("openai", None, 1000));
+
+        // An old failure starts the count over
+        health.record("gemini", Some(&timeout()), 1000);
+        health.record("gemini", Some(&timeout()), 1000 + OUTAGE_WINDOW.as_secs());
+        assert!(health.failing("gemini", 1000 + OUTAGE_WINDOW.as_secs()).is_none());
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("provider-health.json");
+        assert!(Health::load(&path).failing("groq", 0).is_none());
+
+        let mut health = Health::default();
+        health.record("groq", Some(&timeout()), 50);
+        health.record("groq", Some(&timeout()), 60);
+        health.save(&path).unwrap();
+        assert_eq!(Health::load(&path).failing("groq", 70).map(|outage| outage.failures), Some(2));
+
+        fs::write(&path, "not json").unwrap();
+        assert!(Health::load(&path).failing("groq", 70).is_none());
+    }
+}
@@ -0,0 +1,353 @@
+//! The query history log: one JSON record per line at `DataPaths::history_log`.
+//!
+//! Answered queries are appended as they happen, and `q feedback` appends a
+//! rating for the latest one rather than rewriting the file, so the log is
+//! only ever appended to. With a cipher each line is sealed on its own (see
+//! `core::crypto`); lines that don't parse or can't be opened are skipped.
+
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use super::crypto::{self, Cipher};
+use super::retention::{self, Pruned};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Record {
+    Query(QueryRecord),
+    Feedback(FeedbackRecord),
+}
+
+/// One answered query. `prompt` is what was asked, without the gathered context.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueryRecord {
+    /// Milliseconds since the epoch when the answer arrived, also the record's ID
+    pub id: u64,
+    pub provider: String,
+    pub model: String,
+    /// The `q ask` preset, if one was used
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preset: Option<String>,
+    pub prompt: String,
+    pub response: String,
+}
+
+/// A rating for the query with ID `query`; a later rating replaces an earlier one
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FeedbackRecord {
+    pub query: u64,
+    pub rating: Rating,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Rating {
+    Up,
+    Down,
+}
+
+impl Rating {
+    /// 👍 or 👎, or a word for either
+    pub fn parse(text: &str) -> Option<Self> {
+        match text.trim().trim_end_matches('\u{fe0f}').to_lowercase().as_str() {
+            "👍" | "up" | "good" | "+1" | "yes" => Some(Rating::Up),
+            "👎" | "down" | "bad" | "-1" | "no" => Some(Rating::Down),
+            _ => None,
+        }
+    }
+}
+
+/// Queries and ratings for one model or preset
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Tally {
+    pub queries: usize,
+    pub up: usize,
+    pub down: usize,
+}
+
+impl Tally {
+    /// Share of rated answers that were rated up
+    pub fn satisfaction(&self) -> Option<f64> {
+        let rated = self.up + self.down;
+        (rated > 0).then(|| self.up as f64 / rated as f64)
+    }
+}
+
+pub struct HistoryLog {
+    path: PathBuf,
+    cipher: Option<Cipher>,
+}
+
+impl HistoryLog {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path, cipher: None }
+    }
+
+    /// Seal new records with `cipher` and open sealed ones when reading
+    pub fn with_cipher(mut self, cipher: Cipher) -> Self {
+        self.cipher = Some(cipher);
+        self
+    }
+
+    /// Add a record, creating the log (readable only by the user) if needed
+    pub fn append(&self, record: &Record) -> io::Result<()> {
+        let mut line = self.seal(serde_json::to_string(record)?);
+        line.push('\n');
+        private_file(OpenOptions::new().create(true).append(true))
+            .open(&self.path)?
+            .write_all(line.as_bytes())
+    }
+
+    /// Every record in the log, oldest first; empty if there is no log yet
+    pub fn records(&self) -> io::Result<Vec<Record>> {
+        Ok(self.lines()?.iter().filter_map(|line| self.parse(line)).collect())
+    }
+
+    /// The most recently answered query
+    pub fn last_query(&self) -> io::Result<Option<QueryRecord>> {
+        Ok(self.records()?.into_iter().rev().find_map(|record| match record {
+            Record::Query(query) => Some(query),
+            Record::Feedback(_) => None,
+        }))
+    }
+
+    /// Seal every plaintext line, replacing the log in one rename.
+    /// Returns how many lines were sealed.
+    pub fn encrypt_existing(&self) -> io::Result<usize> {
+        if self.cipher.is_none() {
+            return Err(io::Error::other("no encryption key"));
+        }
+        let lines = self.lines()?;
+        let plaintext = lines.iter().filter(|line| !crypto::is_sealed(line)).count();
+        if plaintext == 0 {
+            return Ok(0);
+        }
+
+        self.rewrite(lines.into_iter().map(|line| if crypto::is_sealed(&line) { line } else { self.seal(line) }))?;
+        Ok(plaintext)
+    }
+
+    /// Drop records older than `max_age` at `now_id` (a `now_id()` timestamp),
+    /// then the oldest until the log is at most `max_bytes`. A rating is as old
+    /// as the query it rates; lines that can't be read are only dropped for size.
+    pub fn prune(&self, max_age: Option<Duration>, max_bytes: Option<u64>, now_id: u64) -> io::Result<Pruned> {
+        let lines = self.lines()?;
+        let entries: Vec<(Option<Duration>, u64)> = lines.iter()
+            .map(|line| {
+                let id = self.parse(line).map(|record| match record {
+                    Record::Query(query) => query.id,
+                    Record::Feedback(feedback) => feedback.query,
+                });
+                (id.map(|id| Duration::from_millis(now_id.saturating_sub(id))), line.len() as u64 + 1)
+            })
+            .collect();
+        let expired = retention::expired_entries(&entries, max_age, max_bytes);
+        if expired.is_empty() {
+            return Ok(Pruned::default());
+        }
+
+        let pruned = Pruned {
+            removed: expired.len(),
+            bytes: expired.iter().map(|&index| entries[index].1).sum(),
+        };
+        self.rewrite(lines.into_iter().enumerate()
+            .filter(|(index, _)| expired.binary_search(index).is_err())
+            .map(|(_, line)| line))?;
+        Ok(pruned)
+    }
+
+    /// Replace the log with `lines` in one rename
+    fn rewrite(&self, lines: impl Iterator<Item = String>) -> io::Result<()> {
+        let output: String = lines.map(|line| line + "\n").collect();
+        let temp = self.path.with_extension("jsonl.tmp");
+        private_file(OpenOptions::new().create(true).write(true).truncate(true))
+            .open(&temp)?
+            .write_all(output.as_bytes())?;
+        fs::rename(&temp, &self.path)
+    }
+
+    fn lines(&self) -> io::Result<Vec<String>> {
+        match fs::read_to_string(&self.path) {
+            Ok(text) => Ok(text.lines().filter(|line| !line.trim().is_empty()).map(str::to_string).collect()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn seal(&self, json: String) -> String {
+        match &self.cipher {
+            Some(cipher) => cipher.seal(&json),
+            None => json,
+        }
+    }
+
+    fn parse(&self, line: &str) -> Option<Record> {
+        if crypto::is_sealed(line) {
+            serde_json::from_str(&self.cipher.as_ref()?.open(line)?).ok()
+        } else {
+            serde_json::from_str(line).ok()
+        }
+    }
+}
+
+/// `options` creating files readable only by the user on unix
+pub(crate) fn private_file(options: &mut OpenOptions) -> &mut OpenOptions {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options
+}
+
+/// An exclusive lock on `path`, a file beside the data it guards, held until
+/// the returned file is dropped. Blocks while another run holds it.
+pub(crate) fn lock(path: &Path) -> io::Result<File> {
+    let file = private_file(OpenOptions::new().create(true).truncate(false).write(true)).open(path)?;
+    file.lock()?;
+    Ok(file)
+}
+
+/// Milliseconds since the epoch, for `QueryRecord::id`
+pub fn now_id() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or_default()
+}
+
+/// Queries and their latest ratings grouped by `key`, busiest group first.
+/// Queries `key` returns `None` for are left out.
+pub fn tally(records: &[Record], key: impl Fn(&QueryRecord) -> Option<String>) -> Vec<(String, Tally)> {
+    let ratings: HashMap<u64, Rating> = records.iter()
+        .filter_map(|record| match record {
+            Record::Feedback(feedback) => Some((feedback.query, feedback.rating)),
+            Record::Query(_) => None,
+        })
+        .collect();
+
+    let mut groups: HashMap<String, Tally> = HashMap::new();
+    for record in records {
+        let Record::Query(query) = record else { continue };
+        let Some(group) = key(query) else { continue };
+        let tally = groups.entry(group).or_default();
+        tally.queries += 1;
+        match ratings.get(&query.id) {
+            Some(Rating::Up) => tally.up += 1,
+            Some(Rating::Down) => tally.down += 1,
+            None => {}
+        }
+    }
+
+    let mut groups: Vec<(String, Tally)> = groups.into_iter().collect();
+    groups.sort_by(|(a, x), (b, y)| y.queries.cmp(&x.queries).then_with(|| a.cmp(b)));
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn query(id: u64, model: &str, preset: Option<&str>) -> Record {
+        Record::Query(QueryRecord {
+            id,
+            provider: "openai".to_string(),
+            model: model.to_string(),
+            preset: preset.map(str::to_string),
+            prompt: "prompt".to_string(),
+            response: "response".to_string(),
+        })
+    }
+
+    fn feedback(query: u64, rating: Rating) -> Record {
+        Record::Feedback(FeedbackRecord { query, rating, comment: None })
+    }
+
+    #[test]
+    fn test_append_and_read() {
+        let temp_dir = TempDir::new().unwrap();
+        let log = HistoryLog::new(temp_dir.path().join("history.jsonl"));
+        assert_eq!(log.last_query().unwrap(), None);
+
+        log.append(&query(1, "gpt-4o", None)).unwrap();
+        log.append(&query(2, "gpt-4o-mini", Some("sql"))).unwrap();
+        log.append(&feedback(2, Rating::Up)).unwrap();
+        fs::write(
+            temp_dir.path().join("history.jsonl"),
+            fs::read_to_string(temp_dir.path().join("history.jsonl")).unwrap() + "not json\n",
+        ).unwrap();
+
+        assert_eq!(log.records().unwrap().len(), 3);
+        assert_eq!(log.last_query().unwrap().map(|query| query.id), Some(2));
+    }
+
+    #[test]
+    fn test_encrypted_log() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("history.jsonl");
+        HistoryLog::new(path.clone()).append(&query(1, "gpt-4o", None)).unwrap();
+
+        let log = HistoryLog::new(path.clone()).with_cipher(Cipher::new(&[3; crypto::KEY_LEN]));
+        log.append(&query(2, "gpt-4o", Some("sql"))).unwrap();
+        assert!(!fs::read_to_string(&path).unwrap().lines().nth(1).unwrap().contains("gpt-4o"));
+        assert_eq!(log.records().unwrap().len(), 2);
+        // Without the key, sealed records are skipped
+        assert_eq!(HistoryLog::new(path.clone()).records().unwrap().len(), 1);
+
+        assert_eq!(log.encrypt_existing().unwrap(), 1);
+        assert_eq!(log.encrypt_existing().unwrap(), 0);
+        assert!(!fs::read_to_string(&path).unwrap().contains("gpt-4o"));
+        assert_eq!(log.last_query().unwrap().map(|query| query.id), Some(2));
+    }
+
+    #[test]
+    fn test_prune() {
+        let temp_dir = TempDir::new().unwrap();
+        let log = HistoryLog::new(temp_dir.path().join("history.jsonl"));
+        let day = 24 * 60 * 60 * 1000;
+        log.append(&query(day, "gpt-4o", None)).unwrap();
+        log.append(&query(20 * day, "gpt-4o", None)).unwrap();
+        log.append(&feedback(day, Rating::Up)).unwrap();
+        log.append(&query(29 * day, "gpt-4o", None)).unwrap();
+
+        let pruned = log.prune(Some(Duration::from_millis(14 * day)), None, 30 * day).unwrap();
+        assert_eq!(pruned.removed, 2);
+        assert_eq!(log.records().unwrap(), vec![query(20 * day, "gpt-4o", None), query(29 * day, "gpt-4o", None)]);
+
+        let size = fs::metadata(temp_dir.path().join("history.jsonl")).unwrap().len();
+        assert_eq!(log.prune(None, Some(size - 1), 30 * day).unwrap().removed, 1);
+        assert_eq!(log.last_query().unwrap().map(|query| query.id), Some(29 * day));
+        assert_eq!(log.prune(Some(Duration::ZERO), None, 0).unwrap(), Pruned::default());
+    }
+
+    #[test]
+    fn test_tally_uses_latest_rating() {
+        let records = vec![
+            query(1, "gpt-4o", None),
+            query(2, "gpt-4o", Some("sql")),
+            query(3, "gemini-2.0-flash", Some("sql")),
+            feedback(1, Rating::Down),
+            feedback(1, Rating::Up),
+            feedback(2, Rating::Down),
+        ];
+
+        let by_model = tally(&records, |query| Some(query.model.clone()));
+        assert_eq!(by_model[0], ("gpt-4o".to_string(), Tally { queries: 2, up: 1, down: 1 }));
+        assert_eq!(by_model[1].1.satisfaction(), None);
+        let by_preset = tally(&records, |query| query.preset.clone());
+        assert_eq!(by_preset, vec![("sql".to_string(), Tally { queries: 2, up: 0, down: 1 })]);
+    }
+
+    #[test]
+    fn test_parse_rating() {
+        assert_eq!(Rating::parse("👍"), Some(Rating::Up));
+        assert_eq!(Rating::parse("👎\u{fe0f}"), Some(Rating::Down));
+        assert_eq!(Rating::parse("Good"), Some(Rating::Up));
+        assert_eq!(Rating::parse("meh"), None);
+    }
+}
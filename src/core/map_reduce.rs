@@ -0,0 +1,630 @@
+//! Map-reduce over input too big for one prompt (`--map-reduce`): the input
+//! is split into parts at line boundaries, each part is asked the question on
+//! its own, and the notes from the parts are combined in a last prompt. When
+//! the notes are too long to combine at once, groups of them are condensed
+//! first (each note on its own when no two fit together), as many rounds as
+//! it takes; notes that stop getting shorter are refused.
+//!
+//! Parts are read `jobs` at a time, under a bar counting the parts read and
+//! failed with an ETA, plus a line for each part in flight. A part that still
+//! fails after the engine's retries is tried again in a later round, after
+//! the others; parts already read aren't asked again.
+//!
+//! With a cache, each part's notes are stored under the hashes of the part's
+//! content and of what it was asked (the question, the rest of the context,
+//! and the input's header), scoped to the model. Parts are cut where their
+//! content says (see `split`), so after an edit to a large input only the
+//! parts around the edit are asked again.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::stream::{self, StreamExt};
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use sha2::{Digest, Sha256};
+
+use super::cache::DiskCache;
+use super::{CoreError, CoreResult, QueryEngine};
+
+/// What a part with nothing on the question is asked to answer; such parts
+/// are left out of the combining prompt
+pub const NOTHING_RELEVANT: &str = "Nothing relevant.";
+/// Room kept in each prompt for the instructions around the part or notes
+const INSTRUCTION_BYTES: usize = 512;
+/// The smallest part worth a query; with less room than this left for the
+/// input, map-reduce refuses rather than overrun the budget
+const MIN_PART_BYTES: usize = 512;
+/// Rounds of reading before parts that keep failing fail the query
+const ROUNDS: u32 = 3;
+/// Pause before the second round of reading; the failures that survive the
+/// engine's retries tend to be rate limits
+pub const ROUND_DELAY: Duration = Duration::from_secs(5);
+/// How long a part's notes are kept in the cache
+pub const CACHE_TTL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+/// One line in this many ends a part once the part is three quarters full
+const ANCHOR_ODDS: u64 = 8;
+
+/// A context block too big to send whole
+#[derive(Debug, Clone, PartialEq)]
+pub struct Input {
+    /// The block's first line, e.g. `File: app.log`, repeated with every part
+    pub header: String,
+    pub body: String,
+}
+
+impl Input {
+    pub fn new(block: &str) -> Self {
+        let (header, body) = block.split_once('\n').unwrap_or((block, ""));
+        Self { header: header.to_string(), body: body.to_string() }
+    }
+}
+
+/// How the parts are read
+#[derive(Clone)]
+pub struct Options {
+    /// Size of each prompt
+    pub max_bytes: usize,
+    /// Parts read at once
+    pub jobs: usize,
+    /// Pause before the second round, doubling for each one after
+    pub round_delay: Duration,
+    pub show_progress: bool,
+    /// Where the notes on parts are kept between runs, scoped to the model
+    pub cache: Option<Arc<DiskCache>>,
+}
+
+/// A part's notes, labeled with where they came from
+struct Note {
+    label: String,
+    text: String,
+}
+
+/// A part to read and the prompt that asks it the question
+struct Part {
+    label: String,
+    prompt: String,
+    /// The hashes of what it's asked and of its content, for the cache
+    key: String,
+}
+
+/// Read every part of `inputs` with `question` and the rest of the `context`,
+/// and return the prompt that combines the notes, small enough that with
+/// `context` in front of it (see `with_context`) it's at most about
+/// `options.max_bytes`. `status` hears about each stage.
+pub async fn map_reduce(
+    engine: &QueryEngine,
+    question: &str,
+    context: &str,
+    inputs: &[Input],
+    options: &Options,
+    status: impl Fn(&str),
+) -> CoreResult<String> {
+    let mut parts = Vec::new();
+    for input in inputs {
+        let pieces = split(&input.body, part_bytes(question, context, &input.header, options.max_bytes)?);
+        status(&format!("Reading {} in {} parts, {} at a time", input.header, pieces.len(), options.jobs.max(1)));
+        let asked = digest(&[question, context, &input.header]);
+        for (index, piece) in pieces.iter().enumerate() {
+            parts.push(Part {
+                label: format!("{}, part {} of {}", input.header, index + 1, pieces.len()),
+                prompt: map_prompt(question, context, input, index, pieces.len(), piece),
+                key: format!("{}-{}", asked, digest(&[piece])),
+            });
+        }
+    }
+
+    let display = Display::new(options.show_progress, parts.len());
+    let texts = read(engine, &parts, options, &display, &status).await;
+    display.overall.finish_and_clear();
+    let mut notes: Vec<Note> = parts.into_iter()
+        .zip(texts?)
+        .map(|(part, text)| Note { label: part.label, text })
+        .collect();
+
+    let read = notes.len();
+    notes.retain(|note| !note.text.trim().starts_with(NOTHING_RELEVANT.trim_end_matches('.')));
+    if notes.is_empty() {
+        return Ok(format!(
+            "The input below was read in {} parts, and none of them had anything on the question. Say so, \
+             and answer what you can without it.\n\nQuestion: {}",
+            read, question
+        ));
+    }
+    // The notes share the final prompt with the rest of the context
+    let reduce_bytes = options.max_bytes.saturating_sub(with_context(context, "").len());
+    let mut size = reduce_prompt(question, &notes, read).len();
+    while size > reduce_bytes {
+        let groups = group(notes, options.max_bytes.saturating_sub(question.len() + INSTRUCTION_BYTES));
+        // When no two notes fit a prompt together, each is condensed on its own
+        // to its share of the final prompt
+        let alone = groups.iter().all(|group| group.len() == 1);
+        let share = alone.then(|| reduce_bytes.saturating_sub(INSTRUCTION_BYTES + question.len()) / groups.len());
+        let total = groups.len();
+        notes = Vec::new();
+        for (index, group) in groups.into_iter().enumerate() {
+            if group.len() == 1 && !alone {
+                notes.extend(group);
+                continue;
+            }
+            let label = match group.len() {
+                1 => group[0].label.clone(),
+                n => format!("{} to {}", group[0].label, group[n - 1].label),
+            };
+            let prompt = condense_prompt(question, &group, share);
+            let key = digest(&[&prompt]);
+            let text = match cached(options, &key).await {
+                Some(text) => text,
+                None => {
+                    let bar = display.line(&format!("Combining notes, group {} of {}", index + 1, total));
+                    let text = engine.query_in(&prompt, &bar).await;
+                    bar.finish_and_clear();
+                    store(options, &key, text?)
+                }
+            };
+            notes.push(Note { label, text });
+        }
+        let condensed = reduce_prompt(question, &notes, read).len();
+        if condensed >= size {
+            return Err(CoreError::Other(format!(
+                "--map-reduce couldn't fit the notes on {} parts into the {}-byte budget: they still take {} bytes \
+                 after condensing. Send less context alongside it, or raise [context] max_bytes.",
+                read, reduce_bytes, condensed
+            )));
+        }
+        size = condensed;
+    }
+    Ok(reduce_prompt(question, &notes, read))
+}
+
+/// Each part's answer, reading `jobs` parts at once and giving parts that
+/// fail more rounds. Fails when some part fails every round.
+async fn read(engine: &QueryEngine, parts: &[Part], options: &Options, display: &Display, status: &impl Fn(&str)) -> CoreResult<Vec<String>> {
+    let mut texts: Vec<Option<String>> = Vec::new();
+    for part in parts {
+        texts.push(cached(options, &part.key).await);
+    }
+    let unchanged = texts.iter().flatten().count();
+    if unchanged > 0 {
+        display.bars.suspend(|| status(&format!("{} of {} parts are unchanged since an earlier run", unchanged, parts.len())));
+        display.overall.inc(unchanged as u64);
+    }
+
+    let mut failures: Vec<(usize, CoreError)> = Vec::new();
+    for round in 0..ROUNDS {
+        let pending: Vec<usize> = (0..parts.len()).filter(|&index| texts[index].is_none()).collect();
+        if pending.is_empty() {
+            break;
+        }
+        if round > 0 {
+            display.bars.suspend(|| status(&format!("Trying the {} failed part{} again", pending.len(), if pending.len() == 1 { "" } else { "s" })));
+            tokio::time::sleep(options.round_delay * 2u32.pow(round - 1)).await;
+        }
+        let results: Vec<(usize, CoreResult<String>)> = stream::iter(pending)
+            .map(|index| async move {
+                let bar = display.line(&parts[index].label);
+                let result = engine.query_in(&parts[index].prompt, &bar).await;
+                bar.finish_and_clear();
+                display.read(result.is_ok(), round > 0);
+                (index, result)
+            })
+            .buffer_unordered(options.jobs.max(1))
+            .collect()
+            .await;
+        failures.clear();
+        for (index, result) in results {
+            match result {
+                Ok(text) => texts[index] = Some(store(options, &parts[index].key, text)),
+                Err(e) => failures.push((index, e)),
+            }
+        }
+    }
+
+    if failures.is_empty() {
+        return Ok(texts.into_iter().flatten().collect());
+    }
+    failures.sort_by_key(|(index, _)| *index);
+    if failures.len() == parts.len() {
+        // Nothing got through, so the cause (a bad key, say) is likely the same for all
+        return Err(failures.swap_remove(0).1);
+    }
+    let listed: Vec<String> = failures.iter()
+        .map(|(index, e)| format!("{} ({})", parts[*index].label, e))
+        .collect();
+    Err(CoreError::Other(format!(
+        "{} of {} parts couldn't be read after {} rounds: {}",
+        failures.len(),
+        parts.len(),
+        ROUNDS,
+        listed.join("; ")
+    )))
+}
+
+/// The notes stored under `key`, if there's a cache and they're in it
+async fn cached(options: &Options, key: &str) -> Option<String> {
+    options.cache.as_ref()?.get(key).await
+}
+
+/// Keep `text` under `key` for later runs; a cache that can't be written
+/// only costs those runs a query
+fn store(options: &Options, key: &str, text: String) -> String {
+    if let Some(cache) = &options.cache {
+        let _ = cache.insert(key, &text);
+    }
+    text
+}
+
+/// The hex SHA-256 of `texts`, each ended by a NUL so their bounds count
+fn digest(texts: &[&str]) -> String {
+    let mut hasher = Sha256::new();
+    for text in texts {
+        hasher.update(text.as_bytes());
+        hasher.update([0]);
+    }
+    hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// The map step's progress: a bar for the parts read, with the failures and
+/// an ETA, and a line under it for each query in flight
+struct Display {
+    bars: MultiProgress,
+    overall: ProgressBar,
+    failed: AtomicUsize,
+}
+
+impl Display {
+    fn new(show: bool, parts: usize) -> Self {
+        let bars = if show { MultiProgress::new() } else { MultiProgress::with_draw_target(ProgressDrawTarget::hidden()) };
+        let overall = bars.add(ProgressBar::new(parts as u64));
+        overall.set_style(
+            ProgressStyle::with_template("{bar:30} {pos}/{len} parts read{msg}, ETA {eta}").expect("valid progress template"),
+        );
+        Self { bars, overall, failed: AtomicUsize::new(0) }
+    }
+
+    /// A spinner line for one query, removed once it's finished and cleared
+    fn line(&self, label: &str) -> ProgressBar {
+        let bar = self.bars.add(ProgressBar::new_spinner());
+        bar.set_style(ProgressStyle::with_template("  {spinner} {prefix}: {msg}").expect("valid progress template"));
+        bar.set_prefix(label.to_string());
+        bar.enable_steady_tick(Duration::from_millis(120));
+        bar
+    }
+
+    /// Count a part read, or failed; `retried` parts had failed before
+    fn read(&self, ok: bool, retried: bool) {
+        let failed = match (ok, retried) {
+            (true, false) => self.failed.load(Ordering::SeqCst),
+            (true, true) => self.failed.fetch_sub(1, Ordering::SeqCst) - 1,
+            (false, false) => self.failed.fetch_add(1, Ordering::SeqCst) + 1,
+            (false, true) => self.failed.load(Ordering::SeqCst),
+        };
+        if ok {
+            self.overall.inc(1);
+        }
+        self.overall.set_message(if failed == 0 { String::new() } else { format!(", {} failed", failed) });
+    }
+}
+
+/// How much of an input goes in each part: what the budget leaves after the
+/// question, the rest of the context, the input's header, and the
+/// instructions. An error when that's too little to be worth reading.
+pub fn part_bytes(question: &str, context: &str, header: &str, max_bytes: usize) -> CoreResult<usize> {
+    let taken = with_context(context, "").len() + question.len() + header.len() + INSTRUCTION_BYTES;
+    match max_bytes.checked_sub(taken) {
+        Some(room) if room >= MIN_PART_BYTES => Ok(room),
+        _ => Err(CoreError::Other(format!(
+            "--map-reduce has no room to read {}: the question and the rest of the context take {} of the \
+             {}-byte budget. Send less context alongside it, or raise [context] max_bytes.",
+            header, taken, max_bytes
+        ))),
+    }
+}
+
+/// `prompt` with `context` in front of it, as the final query sends it
+pub fn with_context(context: &str, prompt: &str) -> String {
+    if context.is_empty() {
+        prompt.to_string()
+    } else {
+        format!("Context:\n{}\nPrompt: {}", context.trim(), prompt)
+    }
+}
+
+/// `text` in pieces of at most `max` bytes. Once a piece is three quarters
+/// full it ends after the next line whose hash picks it (one in
+/// `ANCHOR_ODDS`), so the cuts depend on the lines around them rather than
+/// on everything before: an edit moves the cuts near it, and the pieces
+/// after those come out the same. A line longer than `max` is cut at
+/// character boundaries.
+pub fn split(text: &str, max: usize) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut end = 0;
+    for line in text.split_inclusive('\n') {
+        if end > start && end + line.len() - start > max {
+            parts.push(&text[start..end]);
+            start = end;
+        }
+        end += line.len();
+        while end - start > max {
+            let mut cut = start + max;
+            while !text.is_char_boundary(cut) {
+                cut -= 1;
+            }
+            if cut == start {
+                cut += text[start..].chars().next().map_or(0, char::len_utf8);
+            }
+            parts.push(&text[start..cut]);
+            start = cut;
+        }
+        if (end - start) * 4 >= max * 3 && anchor(line) {
+            parts.push(&text[start..end]);
+            start = end;
+        }
+    }
+    if end > start || parts.is_empty() {
+        parts.push(&text[start..end]);
+    }
+    parts
+}
+
+/// Whether a piece may end after `line`: FNV-1a of the line picks one in `ANCHOR_ODDS`
+fn anchor(line: &str) -> bool {
+    let hash = line.trim_end().bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    });
+    hash % ANCHOR_ODDS == 0
+}
+
+fn map_prompt(question: &str, context: &str, input: &Input, index: usize, total: usize, part: &str) -> String {
+    let mut prompt = String::new();
+    if !context.trim().is_empty() {
+        prompt.push_str(&format!("Context:\n{}\n\n", context.trim()));
+    }
+    prompt.push_str(&format!(
+        "This is part {} of {} of an input too long to read at once.\n{}\n{}\n\nQuestion: {}\n\n\
+         Answer from this part alone, quoting the lines that matter; the answers from all parts are \
+         combined later. If nothing in this part bears on the question, reply only \"{}\"",
+        index + 1, total, input.header, part.trim_end(), question, NOTHING_RELEVANT
+    ));
+    prompt
+}
+
+/// Ask for `notes` as one set of notes, in at most `limit` bytes if given
+fn condense_prompt(question: &str, notes: &[Note], limit: Option<usize>) -> String {
+    let mut prompt = String::from(
+        "These are notes taken on consecutive parts of an input too long to read at once. Condense them \
+         into one set of notes, keeping every detail and quote that bears on the question.",
+    );
+    match limit {
+        Some(limit) => prompt.push_str(&format!(" Keep it under {} bytes, leaving out the least important details first.\n", limit)),
+        None => prompt.push('\n'),
+    }
+    for note in notes {
+        prompt.push_str(&format!("\nNotes on {}:\n{}\n", note.label, note.text.trim()));
+    }
+    prompt.push_str(&format!("\nQuestion: {}", question));
+    prompt
+}
+
+fn reduce_prompt(question: &str, notes: &[Note], read: usize) -> String {
+    let mut prompt = format!(
+        "An input too long to read at once was read in {} parts, with notes taken on each. Answer the \
+         question from the notes below as if you had read all of it; parts that aren't listed had \
+         nothing on the question.\n",
+        read
+    );
+    for note in notes {
+        prompt.push_str(&format!("\nNotes on {}:\n{}\n", note.label, note.text.trim()));
+    }
+    prompt.push_str(&format!("\nQuestion: {}", question));
+    prompt
+}
+
+/// Consecutive notes in groups of at most `max` bytes; a note bigger than
+/// that is a group of its own
+fn group(notes: Vec<Note>, max: usize) -> Vec<Vec<Note>> {
+    let mut groups: Vec<Vec<Note>> = Vec::new();
+    let mut size = 0;
+    for note in notes {
+        let bytes = note.label.len() + note.text.len();
+        match groups.last_mut() {
+            Some(last) if size + bytes <= max => last.push(note),
+            _ => {
+                groups.push(vec![note]);
+                size = 0;
+            }
+        }
+        size += bytes;
+    }
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{ApiError, LLMApi, StreamingResponse};
+    use crate::core::QueryConfig;
+    use std::sync::{Arc, Mutex};
+
+    /// Answers `Nothing relevant.` unless the prompt has an error line.
+    /// Prompts with `flaky` in them fail the first `failures` times.
+    #[derive(Default)]
+    struct Reader {
+        prompts: Mutex<Vec<String>>,
+        flaky: &'static str,
+        failures: AtomicUsize,
+        /// Bytes of detail added to each note on an error line
+        detail: usize,
+    }
+
+    #[async_trait::async_trait]
+    impl LLMApi for Reader {
+        fn model(&self) -> &str {
+            "test-model"
+        }
+
+        async fn send_query(&self, prompt: &str) -> Result<String, ApiError> {
+            self.prompts.lock().unwrap().push(prompt.to_string());
+            if !self.flaky.is_empty() && prompt.contains(self.flaky) && self.failures.load(Ordering::SeqCst) > 0 {
+                self.failures.fetch_sub(1, Ordering::SeqCst);
+                return Err(ApiError::Other("flaky".to_string()));
+            }
+            if prompt.starts_with("These are notes") {
+                let found: Vec<&str> = prompt.lines().filter(|line| line.starts_with("Found:")).collect();
+                return Ok(format!("Condensed: {}", found.join("; ")));
+            }
+            Ok(match prompt.lines().find(|line| line.starts_with("ERROR")) {
+                Some(line) => format!("Found: {}\n{}", line, "d".repeat(self.detail)),
+                None => NOTHING_RELEVANT.to_string(),
+            })
+        }
+
+        async fn send_streaming_query(&self, _prompt: &str) -> Result<StreamingResponse, ApiError> {
+            Err(ApiError::Other("not streamed".to_string()))
+        }
+
+        async fn validate_key(&self) -> Result<(), ApiError> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_split_at_lines() {
+        assert_eq!(split("aaa\nbbb\nccc\n", 8), ["aaa\nbbb\n", "ccc\n"]);
+        assert_eq!(split("abcdefgh", 3), ["abc", "def", "gh"]);
+        assert_eq!(split("ééé", 3), ["é", "é", "é"]);
+        assert_eq!(split("", 3), [""]);
+    }
+
+    fn reader(api: Arc<Reader>) -> QueryEngine {
+        QueryEngine::new(api, QueryConfig { show_progress: false, max_retries: 0, ..QueryConfig::default() })
+    }
+
+    fn input() -> Input {
+        let body: String = (0..300).map(|i| if i == 250 { "ERROR disk full\n".to_string() } else { format!("INFO line {:03}\n", i) }).collect();
+        Input::new(&format!("Log: app.log\n{}", body))
+    }
+
+    #[tokio::test]
+    async fn test_prompts_fit_the_budget_with_context() {
+        let api = Arc::new(Reader::default());
+        let context = format!("File: notes.txt\n{}", "the deploy runs nightly\n".repeat(30));
+        let prompt = map_reduce(&reader(api.clone()), "Why did it stop?", &context, &[input()], &options(), |_| {})
+            .await
+            .unwrap();
+        let prompts = api.prompts.lock().unwrap().clone();
+        assert!(prompts.len() > 4);
+        assert!(prompts.iter().all(|prompt| prompt.len() <= 2048 && prompt.contains("nightly")));
+        assert!(with_context(&context, &prompt).len() <= 2048);
+
+        // Context that leaves no room for the input is refused up front
+        let crowded = "x".repeat(1500);
+        let error = map_reduce(&reader(api.clone()), "Why?", &crowded, &[input()], &options(), |_| {}).await.unwrap_err();
+        assert!(error.to_string().contains("--map-reduce has no room to read Log: app.log"), "{}", error);
+    }
+
+    fn options() -> Options {
+        Options { max_bytes: 2048, jobs: 2, round_delay: Duration::ZERO, show_progress: false, cache: None }
+    }
+
+    #[tokio::test]
+    async fn test_parts_read_and_notes_combined() {
+        let api = Arc::new(Reader::default());
+        let engine = reader(api.clone());
+        let statuses = Mutex::new(Vec::new());
+        let prompt = map_reduce(&engine, "Why did it stop?", "", &[input()], &options(), |status| statuses.lock().unwrap().push(status.to_string()))
+            .await
+            .unwrap();
+
+        assert_eq!(statuses.into_inner().unwrap(), ["Reading Log: app.log in 4 parts, 2 at a time"]);
+        let prompts = api.prompts.lock().unwrap();
+        assert!(prompts.iter().all(|prompt| prompt.len() <= 2048 && prompt.contains("\nLog: app.log\n")));
+        assert!(prompt.contains("read in 4 parts"));
+        // Only the part with the error line has notes
+        assert_eq!(prompt.matches("\nNotes on Log: app.log, part ").count(), 1);
+        assert!(prompt.contains(" of 4:\nFound: ERROR disk full\n"));
+        assert!(prompt.ends_with("Question: Why did it stop?"));
+    }
+
+    #[tokio::test]
+    async fn test_only_failed_parts_are_read_again() {
+        let api = Arc::new(Reader { flaky: "INFO line 000", failures: AtomicUsize::new(1), ..Reader::default() });
+        let engine = reader(api.clone());
+        let statuses = Mutex::new(Vec::new());
+        let prompt = map_reduce(&engine, "Why?", "", &[input()], &options(), |status| statuses.lock().unwrap().push(status.to_string()))
+            .await
+            .unwrap();
+        assert!(prompt.contains("Found: ERROR disk full"));
+        assert_eq!(statuses.into_inner().unwrap()[1], "Trying the 1 failed part again");
+        assert_eq!(api.prompts.lock().unwrap().len(), 5);
+
+        // A part that fails every round fails the query, naming it
+        let api = Arc::new(Reader { flaky: "INFO line 000", failures: AtomicUsize::new(ROUNDS as usize), ..Reader::default() });
+        let error = map_reduce(&reader(api), "Why?", "", &[input()], &options(), |_| {}).await.unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Other error: 1 of 4 parts couldn't be read after 3 rounds: Log: app.log, part 1 of 4 (API error: API error: flaky)"
+        );
+    }
+
+    #[test]
+    fn test_split_cuts_follow_content() {
+        let original: String = (0..400).map(|i| format!("INFO request {:03} served\n", i)).collect();
+        let edited = original.replace("INFO request 010 served\n", "WARN request 010 was slow, 2500 ms\n");
+        let (before, after) = (split(&original, 1000), split(&edited, 1000));
+        assert!(before.iter().all(|part| part.len() <= 1000));
+        assert_ne!(before[0], after[0]);
+        // The cuts after the edit line up again, so later parts are unchanged
+        let unchanged = before.iter().filter(|part| after.contains(part)).count();
+        assert!(unchanged >= before.len() - 2, "{} of {} parts unchanged", unchanged, before.len());
+    }
+
+    #[tokio::test]
+    async fn test_unchanged_parts_come_from_the_cache() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let options = Options {
+            cache: Some(Arc::new(DiskCache::new(dir.path().to_path_buf(), "test-model", CACHE_TTL))),
+            ..options()
+        };
+        let api = Arc::new(Reader::default());
+        map_reduce(&reader(api.clone()), "Why?", "", &[input()], &options, |_| {}).await.unwrap();
+        assert_eq!(api.prompts.lock().unwrap().len(), 4);
+
+        // A new engine, as in a later run, asks only about the edited part
+        let edited = Input { body: input().body.replace("INFO line 010", "INFO line ten"), ..input() };
+        let api = Arc::new(Reader::default());
+        let statuses = Mutex::new(Vec::new());
+        let prompt = map_reduce(&reader(api.clone()), "Why?", "", &[edited], &options, |status| statuses.lock().unwrap().push(status.to_string()))
+            .await
+            .unwrap();
+        assert_eq!(api.prompts.lock().unwrap().len(), 1);
+        assert_eq!(statuses.into_inner().unwrap()[1], "3 of 4 parts are unchanged since an earlier run");
+        assert!(prompt.contains("Found: ERROR disk full"));
+    }
+
+    #[tokio::test]
+    async fn test_notes_too_big_to_group_are_condensed_alone() {
+        // Two notes, each over half the budget, can't share a prompt
+        let api = Arc::new(Reader { detail: 1200, ..Reader::default() });
+        let body: String = (0..300)
+            .map(|i| if i == 10 || i == 250 { format!("ERROR disk {} full\n", i) } else { format!("INFO line {:03}\n", i) })
+            .collect();
+        let input = Input::new(&format!("Log: app.log\n{}", body));
+        let prompt = map_reduce(&reader(api.clone()), "Why?", "", &[input], &options(), |_| {}).await.unwrap();
+        assert!(prompt.len() <= 2048, "{} bytes", prompt.len());
+        assert!(prompt.contains("Condensed: Found: ERROR disk 10 full"));
+        assert!(prompt.contains("Condensed: Found: ERROR disk 250 full"));
+        let prompts = api.prompts.lock().unwrap();
+        assert_eq!(prompts.iter().filter(|prompt| prompt.starts_with("These are notes")).count(), 2);
+        assert!(prompts.iter().all(|prompt| prompt.len() <= 2048));
+    }
+
+    #[test]
+    fn test_group() {
+        let note = |text: &str| Note { label: "p".to_string(), text: text.to_string() };
+        let groups = group(vec![note("aaaa"), note("bbbb"), note("cccccccccccc"), note("d")], 10);
+        let sizes: Vec<usize> = groups.iter().map(Vec::len).collect();
+        assert_eq!(sizes, [2, 1, 1]);
+    }
+}
@@ -1,12 +1,35 @@
 pub mod cache;
+pub mod consensus;
+pub mod crypto;
+pub mod health;
+pub mod history;
+pub mod map_reduce;
+pub mod queue;
+pub mod retention;
 pub mod retry;
+pub mod schedule;
+pub mod stream;
 
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
+use futures::future::{BoxFuture, FutureExt, Shared};
+use futures::{Stream, StreamExt};
 use indicatif::ProgressBar;
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
 
-use crate::api::LLMApi;
+use crate::api::{ApiError, LLMApi, StreamingResponse};
 use crate::cli::args::Verbosity;
+use cache::{DiskCache, QueryCache};
+use retry::{with_policy, RetryEvent, RetryPolicy};
+
+/// Characters of the interrupted answer quoted when asking the model to continue
+const RESUME_TAIL_CHARS: usize = 500;
+/// How long `settle` waits on background refreshes before letting q exit
+const REFRESH_WAIT: Duration = Duration::from_secs(2);
 
 #[derive(Debug, thiserror::Error)]
 pub enum CoreError {
@@ -19,12 +42,46 @@ pub enum CoreError {
     #[error("Retry error: {0}")]
     Retry(String),
 
+    #[error("Cancelled")]
+    Cancelled,
+
     #[error("Other error: {0}")]
     Other(String),
 }
 
 pub type CoreResult<T> = Result<T, CoreError>;
 
+/// Response tokens from `QueryEngine::query_streaming`
+pub type TokenStream = Pin<Box<dyn Stream<Item = CoreResult<String>> + Send>>;
+
+/// What to do when a stream breaks after some of the answer has arrived.
+/// Failures before the first token are always retried.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StreamRecovery {
+    /// Report the error, keeping the partial answer on screen
+    #[default]
+    Fail,
+    /// Stream the rest by asking the model to continue from the last few lines
+    Resume,
+    /// Fetch the rest the same way, but from the non-streaming endpoint
+    Buffered,
+}
+
+/// Where answers are cached (`settings.response_cache`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheMode {
+    /// Within one run only, e.g. for follow-up questions
+    #[default]
+    Memory,
+    /// On disk too, answering repeated prompts from there until the TTL passes
+    Disk,
+    /// On disk, answering from there at once; an answer past half the TTL
+    /// is also asked again in the background to refresh the cache
+    Race,
+}
+
 #[derive(Debug, Clone)]
 pub struct QueryConfig {
     pub max_retries: u32,
@@ -33,7 +90,14 @@ pub struct QueryConfig {
     pub max_cache_size: usize,
     pub retry_delay: Duration,
     pub max_retry_delay: Duration,
+    /// Stop retrying once this much time has passed since the first attempt
+    pub retry_budget: Option<Duration>,
     pub verbosity: Verbosity,
+    /// Serve repeated prompts from the response cache (`--no-cache` turns this off)
+    pub use_cache: bool,
+    pub stream_recovery: StreamRecovery,
+    /// How the disk cache, when there is one, is consulted
+    pub cache_mode: CacheMode,
 }
 
 impl Default for QueryConfig {
@@ -45,41 +109,753 @@ impl Default for QueryConfig {
             max_cache_size: 1000,
             retry_delay: Duration::from_secs(1),
             max_retry_delay: Duration::from_secs(30),
+            retry_budget: Some(Duration::from_secs(120)),
             verbosity: Verbosity::default(),
+            use_cache: true,
+            stream_recovery: StreamRecovery::default(),
+            cache_mode: CacheMode::default(),
         }
     }
 }
 
+/// Called before each retry, e.g. to show the attempt in a spinner
+pub type RetryListener = Arc<dyn Fn(&RetryEvent) + Send + Sync>;
+
+/// A request every caller asking the same prompt meanwhile waits on
+type SharedRequest = Shared<BoxFuture<'static, Result<String, Arc<CoreError>>>>;
+
 pub struct QueryEngine {
     client: Arc<dyn LLMApi>,
     config: QueryConfig,
-    progress: Option<ProgressBar>,
+    recorder: Recorder,
+    retry_listener: Option<RetryListener>,
+    /// Requests still refreshing the disk cache after a cached answer
+    refreshes: Mutex<Vec<JoinHandle<()>>>,
+    /// Buffered requests waiting on the provider, by prompt
+    pending: Arc<Mutex<HashMap<String, SharedRequest>>>,
 }
 
 impl QueryEngine {
     pub fn new(client: Arc<dyn LLMApi>, config: QueryConfig) -> Self {
+        let recorder = Recorder {
+            cache: Arc::new(QueryCache::new(config.max_cache_size, config.cache_ttl)),
+            disk: None,
+            enabled: config.use_cache,
+        };
         Self {
             client,
             config,
-            progress: None,
+            recorder,
+            retry_listener: None,
+            refreshes: Mutex::new(Vec::new()),
+            pending: Arc::new(Mutex::new(HashMap::new())),
         }
     }
 
-    pub async fn query(&mut self, prompt: &str) -> CoreResult<String> {
-        let progress = self.create_progress_bar();
+    /// Keep answers in `disk` between runs as `cache_mode` says; with
+    /// `CacheMode::Memory` it isn't used
+    pub fn with_disk_cache(mut self, disk: DiskCache) -> Self {
+        if self.config.cache_mode != CacheMode::Memory {
+            self.recorder.disk = Some(Arc::new(disk));
+        }
+        self
+    }
+
+    /// Give background refreshes of the disk cache up to `REFRESH_WAIT` to
+    /// finish. Any still running then are left behind, and their entries
+    /// simply expire as usual.
+    pub async fn settle(&self) {
+        let refreshes = std::mem::take(&mut *self.refreshes.lock().expect("Failed to lock refreshes"));
+        let _ = tokio::time::timeout(REFRESH_WAIT, futures::future::join_all(refreshes)).await;
+    }
+
+    /// Report retries to `listener`; buffered queries also show them in their spinner
+    pub fn set_retry_listener(&mut self, listener: RetryListener) {
+        self.retry_listener = Some(listener);
+    }
+
+    /// The model answering queries
+    pub fn model(&self) -> &str {
+        self.client.model()
+    }
+
+    /// The provider's ID for the last request sent; `None` after a cache hit
+    /// or when the provider doesn't report one
+    pub fn last_request_id(&self) -> Option<String> {
+        self.client.last_request_id()
+    }
+
+    /// The provider's `system_fingerprint` for the last request sent, if any
+    pub fn last_system_fingerprint(&self) -> Option<String> {
+        self.client.last_system_fingerprint()
+    }
+
+    /// The answer to `prompt`: from the cache when it has one, otherwise from
+    /// the provider. Identical prompts asked at the same time share one request.
+    pub async fn query(&self, prompt: &str) -> CoreResult<String> {
+        self.query_in(prompt, &self.create_progress_bar()).await
+    }
+
+    /// `query`, showing its progress in `progress` rather than a spinner of
+    /// its own, such as one line of a `MultiProgress`
+    pub async fn query_in(&self, prompt: &str, progress: &ProgressBar) -> CoreResult<String> {
+        if let Some(response) = self.recorder.cached(prompt) {
+            return Ok(response);
+        }
+        match self.config.cache_mode {
+            CacheMode::Race if self.recorder.on_disk() => return self.race(prompt, progress).await,
+            CacheMode::Disk => {
+                if let Some(response) = self.recorder.cached_on_disk(prompt).await {
+                    return Ok(response);
+                }
+            }
+            _ => {}
+        }
+
         progress.set_message("Generating...");
+        let response = answer_of(self.in_flight(prompt, Some(progress.clone()))).await;
+        progress.finish_and_clear();
+        Ok(self.recorder.finish(prompt, response?))
+    }
+
+    /// `CacheMode::Race`: a cached answer is returned at once, and one past
+    /// half its TTL is asked again to refresh the cache (see `settle`);
+    /// otherwise the provider's answer is awaited. Reading the disk is quick
+    /// next to a request, so it goes first rather than paying for a request
+    /// on every hit.
+    async fn race(&self, prompt: &str, progress: &ProgressBar) -> CoreResult<String> {
+        if let Some((response, stale)) = self.recorder.cached_on_disk_aged(prompt).await {
+            if stale {
+                self.refresh_with(prompt, tokio::spawn(answer_of(self.in_flight(prompt, None))));
+            }
+            return Ok(response);
+        }
+        progress.set_message("Generating...");
+        let response = answer_of(self.in_flight(prompt, Some(progress.clone()))).await;
+        progress.finish_and_clear();
+        Ok(self.recorder.finish(prompt, response?))
+    }
+
+    /// The provider's answer to `prompt`, joining the request already in
+    /// flight for the same prompt (the cache key) if there is one. The
+    /// request owns what it needs, so it can outlive the call that started
+    /// it; retries show in `progress` as well as the retry listener.
+    fn in_flight(&self, prompt: &str, progress: Option<ProgressBar>) -> SharedRequest {
+        let mut pending = self.pending.lock().expect("Failed to lock pending requests");
+        if let Some(request) = pending.get(prompt) {
+            return request.clone();
+        }
+
+        let client = self.client.clone();
+        let asked = self.client.clone();
+        let policy = RetryPolicy::from_config(&self.config);
+        let listener = self.retry_listener.clone();
+        let finished = self.pending.clone();
+        let key = prompt.to_string();
+        let request = async move {
+            let response = with_policy(
+                || {
+                    let client = client.clone();
+                    let prompt = key.clone();
+                    async move { client.send_query(&prompt).await.map_err(CoreError::Api) }
+                },
+                &policy,
+                || asked.last_retry_after(),
+                |event| {
+                    if let Some(progress) = &progress {
+                        progress.set_message(retry_message(event));
+                    }
+                    if let Some(listener) = &listener {
+                        listener(event);
+                    }
+                },
+            ).await;
+            finished.lock().expect("Failed to lock pending requests").remove(&key);
+            response.map_err(Arc::new)
+        };
+        let request = request.boxed().shared();
+        pending.insert(prompt.to_string(), request.clone());
+        request
+    }
+
+    /// Store what `request` answers for `prompt` once it arrives
+    fn refresh_with(&self, prompt: &str, request: JoinHandle<CoreResult<String>>) {
+        let recorder = self.recorder.clone();
+        let prompt = prompt.to_string();
+        let refresh = tokio::spawn(async move {
+            if let Ok(Ok(response)) = request.await {
+                recorder.finish(&prompt, response);
+            }
+        });
+        self.refreshes.lock().expect("Failed to lock refreshes").push(refresh);
+    }
+
+    /// `n` independent answers to compare. They bypass the cache, which
+    /// would hand back the same answer every time.
+    pub async fn query_n(&self, prompt: &str, n: usize) -> CoreResult<Vec<String>> {
+        let progress = self.create_progress_bar();
+        progress.set_message(format!("Generating {} variations...", n));
 
-        let response = self.client.send_query(prompt)
-            .await
-            .map_err(CoreError::Api)?;
+        let client = self.client.clone();
+        let responses = with_policy(
+            || {
+                let client = client.clone();
+                async move { client.send_query_n(prompt, n).await.map_err(CoreError::Api) }
+            },
+            &RetryPolicy::from_config(&self.config),
+            || self.client.last_retry_after(),
+            |event| {
+                progress.set_message(retry_message(event));
+                self.notify_retry(event);
+            },
+        ).await;
 
         progress.finish_and_clear();
+        responses
+    }
+
+    /// Stream the response, handing each chunk to `on_chunk` as it arrives,
+    /// and return the full text. A cached response arrives as one chunk.
+    pub async fn query_stream<F>(&mut self, prompt: &str, mut on_chunk: F) -> CoreResult<String>
+    where
+        F: FnMut(&str),
+    {
+        let mut tokens = self.query_streaming(prompt, CancellationToken::new()).await?;
+        let mut response = String::new();
+        while let Some(chunk) = tokens.next().await {
+            let text = chunk?;
+            on_chunk(&text);
+            response.push_str(&text);
+        }
         Ok(response)
     }
 
-    fn create_progress_bar(&self) -> ProgressBar {
+    /// The response as a token stream, served from the cache when possible and
+    /// retried if it fails before the first token. A stream that breaks later
+    /// is handled per `stream_recovery`. Cancelling `cancel` ends the stream
+    /// with `CoreError::Cancelled`; a stream that runs to the end is recorded
+    /// like a buffered response.
+    pub async fn query_streaming(&self, prompt: &str, cancel: CancellationToken) -> CoreResult<TokenStream> {
+        if let Some(response) = self.recorder.cached(prompt) {
+            return Ok(Box::pin(futures::stream::once(async { Ok(response) })));
+        }
+        // Reading the disk is quick next to a request, so streams check it
+        // first; in race mode a stale answer is still refreshed behind it
+        if self.recorder.on_disk() {
+            if let Some((response, stale)) = self.recorder.cached_on_disk_aged(prompt).await {
+                if stale && self.config.cache_mode == CacheMode::Race {
+                    self.refresh_with(prompt, tokio::spawn(answer_of(self.in_flight(prompt, None))));
+                }
+                return Ok(Box::pin(futures::stream::once(async { Ok(response) })));
+            }
+        }
+
+        let client = self.client.clone();
+        let policy = RetryPolicy::from_config(&self.config);
+        let connect = with_policy(
+            || {
+                let client = client.clone();
+                async move { client.send_streaming_query(prompt).await.map_err(CoreError::Api) }
+            },
+            &policy,
+            || self.client.last_retry_after(),
+            |event| self.notify_retry(event),
+        );
+        let inner = tokio::select! {
+            biased;
+            _ = cancel.cancelled() => return Err(CoreError::Cancelled),
+            inner = connect => inner?,
+        };
+
+        let state = StreamState {
+            inner,
+            cancel,
+            client: self.client.clone(),
+            config: self.config.clone(),
+            prompt: prompt.to_string(),
+            response: String::new(),
+            recorder: self.recorder.clone(),
+            retry_listener: self.retry_listener.clone(),
+            started: std::time::Instant::now(),
+            attempts: 1,
+            resumed: false,
+            done: false,
+        };
+        Ok(Box::pin(futures::stream::unfold(state, StreamState::next)))
+    }
+
+    fn notify_retry(&self, event: &RetryEvent) {
+        if let Some(listener) = &self.retry_listener {
+            listener(event);
+        }
+    }
+
+    /// A spinner, or a hidden bar when `show_progress` is off
+    pub(crate) fn create_progress_bar(&self) -> ProgressBar {
+        if !self.config.show_progress {
+            return ProgressBar::hidden();
+        }
+
         let pb = ProgressBar::new_spinner();
         pb.enable_steady_tick(Duration::from_millis(120));
         pb
     }
 }
+
+/// Post-processing shared by the buffered and streaming paths, so a
+/// streamed answer is stored exactly like a buffered one
+#[derive(Clone)]
+struct Recorder {
+    cache: Arc<QueryCache>,
+    disk: Option<Arc<DiskCache>>,
+    enabled: bool,
+}
+
+impl Recorder {
+    fn cached(&self, prompt: &str) -> Option<String> {
+        if !self.enabled {
+            return None;
+        }
+        self.cache.get(prompt)
+    }
+
+    fn on_disk(&self) -> bool {
+        self.enabled && self.disk.is_some()
+    }
+
+    /// The disk cache's answer, kept in memory too for the rest of the run
+    async fn cached_on_disk(&self, prompt: &str) -> Option<String> {
+        self.cached_on_disk_aged(prompt).await.map(|(response, _)| response)
+    }
+
+    /// `cached_on_disk`, and whether the answer is past half its TTL
+    async fn cached_on_disk_aged(&self, prompt: &str) -> Option<(String, bool)> {
+        let disk = self.disk.as_ref().filter(|_| self.enabled)?;
+        let (response, age) = disk.get_aged(prompt).await?;
+        self.cache.insert(prompt.to_string(), response.clone());
+        Some((response, age >= disk.ttl() / 2))
+    }
+
+    fn finish(&self, prompt: &str, response: String) -> String {
+        if self.enabled && !response.trim().is_empty() {
+            self.cache.insert(prompt.to_string(), response.clone());
+            // A cache that can't be written only costs the next run a request
+            if let Some(disk) = &self.disk {
+                let _ = disk.insert(prompt, &response);
+            }
+        }
+        response
+    }
+}
+
+/// A shared request's answer. Its last waiter gets the error itself; others
+/// get a copy.
+async fn answer_of(request: SharedRequest) -> CoreResult<String> {
+    request.await.map_err(|error| Arc::try_unwrap(error).unwrap_or_else(|error| duplicate(&error)))
+}
+
+/// A copy of `error` for another waiter. Network errors can't be copied, so
+/// theirs keeps only the message.
+fn duplicate(error: &CoreError) -> CoreError {
+    match error {
+        CoreError::Api(error) => CoreError::Api(match error {
+            ApiError::Network(e) => ApiError::Other(e.to_string()),
+            ApiError::RateLimit => ApiError::RateLimit,
+            ApiError::InvalidKey => ApiError::InvalidKey,
+            ApiError::ModelNotFound(message) => ApiError::ModelNotFound(message.clone()),
+            ApiError::ContextLengthExceeded(message) => ApiError::ContextLengthExceeded(message.clone()),
+            ApiError::QuotaExceeded(message) => ApiError::QuotaExceeded(message.clone()),
+            ApiError::ContentFiltered(message) => ApiError::ContentFiltered(message.clone()),
+            ApiError::ServerOverloaded(message) => ApiError::ServerOverloaded(message.clone()),
+            ApiError::Timeout => ApiError::Timeout,
+            ApiError::Other(message) => ApiError::Other(message.clone()),
+        }),
+        CoreError::Cache(message) => CoreError::Cache(message.clone()),
+        CoreError::Retry(message) => CoreError::Retry(message.clone()),
+        CoreError::Cancelled => CoreError::Cancelled,
+        CoreError::Other(message) => CoreError::Other(message.clone()),
+    }
+}
+
+
+/// Drives a token stream: retries failures before the first token, recovers
+/// from later ones per `stream_recovery`, stops on cancellation, and records
+/// the full response once the provider finishes
+struct StreamState {
+    inner: StreamingResponse,
+    cancel: CancellationToken,
+    client: Arc<dyn LLMApi>,
+    config: QueryConfig,
+    prompt: String,
+    response: String,
+    recorder: Recorder,
+    retry_listener: Option<RetryListener>,
+    started: std::time::Instant,
+    attempts: u32,
+    resumed: bool,
+    done: bool,
+}
+
+impl StreamState {
+    async fn next(mut self) -> Option<(CoreResult<String>, Self)> {
+        loop {
+            if self.done {
+                return None;
+            }
+            let chunk = tokio::select! {
+                biased;
+                _ = self.cancel.cancelled() => {
+                    self.done = true;
+                    return Some((Err(CoreError::Cancelled), self));
+                }
+                chunk = self.inner.next() => chunk,
+            };
+            match chunk {
+                Some(Ok(text)) => {
+                    self.response.push_str(&text);
+                    return Some((Ok(text), self));
+                }
+                Some(Err(e)) => match self.recover(e).await {
+                    // Reconnected before anything was shown
+                    Ok(None) => continue,
+                    Ok(Some(seam)) => {
+                        self.response.push_str(&seam);
+                        return Some((Ok(seam), self));
+                    }
+                    Err(e) => {
+                        self.done = true;
+                        return Some((Err(e), self));
+                    }
+                },
+                None => {
+                    self.recorder.finish(&self.prompt, std::mem::take(&mut self.response));
+                    return None;
+                }
+            }
+        }
+    }
+
+    /// Replace the broken stream. Returns the note marking the seam when the
+    /// answer continues after a partial response.
+    async fn recover(&mut self, error: ApiError) -> CoreResult<Option<String>> {
+        if self.response.is_empty() {
+            let error = CoreError::Api(error);
+            let policy = RetryPolicy::from_config(&self.config);
+            let Some(delay) = policy.next_delay(&error, self.attempts, self.started, self.client.last_retry_after()) else {
+                return Err(error);
+            };
+            self.attempts += 1;
+            if let Some(listener) = &self.retry_listener {
+                listener(&RetryEvent {
+                    attempt: self.attempts,
+                    max_attempts: policy.max_retries,
+                    delay,
+                    reason: error.to_string(),
+                });
+            }
+            tokio::select! {
+                biased;
+                _ = self.cancel.cancelled() => return Err(CoreError::Cancelled),
+                _ = tokio::time::sleep(delay) => {}
+            }
+            // A failed reconnect surfaces as the next chunk and is retried in turn
+            self.inner = match self.client.send_streaming_query(&self.prompt).await {
+                Ok(inner) => inner,
+                Err(e) => Box::pin(futures::stream::once(async { Err(e) })),
+            };
+            return Ok(None);
+        }
+
+        if self.resumed || self.config.stream_recovery == StreamRecovery::Fail {
+            return Err(CoreError::Api(error));
+        }
+        self.resumed = true;
+        let prompt = continuation_prompt(&self.prompt, &self.response);
+        match self.config.stream_recovery {
+            StreamRecovery::Resume => {
+                self.inner = self.client.send_streaming_query(&prompt).await?;
+                Ok(Some("\n\n[connection lost, continuing]\n\n".to_string()))
+            }
+            _ => {
+                let rest = self.client.send_query(&prompt).await?;
+                self.inner = Box::pin(futures::stream::once(async { Ok(rest) }));
+                Ok(Some("\n\n[connection lost, the rest was fetched without streaming]\n\n".to_string()))
+            }
+        }
+    }
+}
+
+/// Spinner text for an upcoming retry
+pub fn retry_message(event: &RetryEvent) -> String {
+    format!(
+        "Retrying in {:.1}s (attempt {}/{}): {}",
+        event.delay.as_secs_f64(),
+        event.attempt,
+        event.max_attempts,
+        event.reason
+    )
+}
+
+/// Ask for the rest of an answer that was cut off after `partial`
+fn continuation_prompt(prompt: &str, partial: &str) -> String {
+    let skip = partial.chars().count().saturating_sub(RESUME_TAIL_CHARS);
+    let tail: String = partial.chars().skip(skip).collect();
+    format!(
+        "{}\n\nYour previous answer was cut off. Continue from exactly where it stopped, \
+         without repeating anything. It ended with:\n{}",
+        prompt, tail
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{ApiError, StreamingResponse};
+    use futures::stream;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Answers every prompt with "Hello, world!" after `delay`, counting requests
+    #[derive(Default)]
+    struct CountingApi {
+        requests: AtomicUsize,
+        delay: Duration,
+    }
+
+    #[async_trait::async_trait]
+    impl LLMApi for CountingApi {
+        fn model(&self) -> &str {
+            "test-model"
+        }
+
+        async fn send_query(&self, _prompt: &str) -> Result<String, ApiError> {
+            self.requests.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(self.delay).await;
+            Ok("Hello, world!".to_string())
+        }
+
+        async fn send_streaming_query(&self, _prompt: &str) -> Result<StreamingResponse, ApiError> {
+            self.requests.fetch_add(1, Ordering::SeqCst);
+            let chunks = vec![Ok("Hello, ".to_string()), Ok("world!".to_string())];
+            Ok(Box::pin(stream::iter(chunks)))
+        }
+
+        async fn validate_key(&self) -> Result<(), ApiError> {
+            Ok(())
+        }
+    }
+
+    fn engine(api: Arc<CountingApi>, use_cache: bool) -> QueryEngine {
+        QueryEngine::new(api, QueryConfig { show_progress: false, use_cache, ..QueryConfig::default() })
+    }
+
+    #[tokio::test]
+    async fn test_streamed_response_is_cached() {
+        let api = Arc::new(CountingApi::default());
+        let mut engine = engine(api.clone(), true);
+
+        let mut chunks = Vec::new();
+        let streamed = engine.query_stream("hi", |chunk| chunks.push(chunk.to_string())).await.unwrap();
+        assert_eq!(streamed, "Hello, world!");
+        assert_eq!(chunks, vec!["Hello, ", "world!"]);
+
+        // Both paths read the entry the stream stored
+        assert_eq!(engine.query("hi").await.unwrap(), "Hello, world!");
+        let mut replayed = Vec::new();
+        engine.query_stream("hi", |chunk| replayed.push(chunk.to_string())).await.unwrap();
+        assert_eq!(replayed, vec!["Hello, world!"]);
+        assert_eq!(api.requests.load(Ordering::SeqCst), 1);
+    }
+
+    fn disk_engine(api: Arc<CountingApi>, dir: &std::path::Path, cache_mode: CacheMode) -> QueryEngine {
+        let config = QueryConfig { show_progress: false, cache_mode, ..QueryConfig::default() };
+        QueryEngine::new(api, config).with_disk_cache(DiskCache::new(dir.to_path_buf(), "test-model", Duration::from_secs(60)))
+    }
+
+    #[tokio::test]
+    async fn test_disk_cache_outlives_the_engine() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let api = Arc::new(CountingApi::default());
+        disk_engine(api.clone(), dir.path(), CacheMode::Disk).query("hi").await.unwrap();
+
+        let mut engine = disk_engine(api.clone(), dir.path(), CacheMode::Disk);
+        assert_eq!(engine.query("hi").await.unwrap(), "Hello, world!");
+        assert_eq!(engine.query_stream("hi", |_| {}).await.unwrap(), "Hello, world!");
+        assert_eq!(api.requests.load(Ordering::SeqCst), 1);
+
+        // Memory mode leaves the disk alone
+        let engine = disk_engine(api.clone(), dir.path(), CacheMode::Memory);
+        engine.query("hi").await.unwrap();
+        assert_eq!(api.requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_race_answers_from_disk_and_refreshes() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let disk = DiskCache::new(dir.path().to_path_buf(), "test-model", Duration::from_secs(60));
+        disk.insert("hi", "Cached hello").unwrap();
+
+        // A fresh answer costs nothing
+        let api = Arc::new(CountingApi { delay: Duration::from_millis(200), ..CountingApi::default() });
+        let engine = disk_engine(api.clone(), dir.path(), CacheMode::Race);
+        assert_eq!(engine.query("hi").await.unwrap(), "Cached hello");
+        engine.settle().await;
+        assert_eq!(api.requests.load(Ordering::SeqCst), 0);
+
+        // One past half its TTL is refreshed behind the answer
+        let stored = std::fs::read_dir(dir.path()).unwrap().next().unwrap().unwrap().path();
+        let written = std::time::SystemTime::now() - Duration::from_secs(40);
+        std::fs::File::options().write(true).open(stored).unwrap().set_modified(written).unwrap();
+        let engine = disk_engine(api.clone(), dir.path(), CacheMode::Race);
+        assert_eq!(engine.query("hi").await.unwrap(), "Cached hello");
+        engine.settle().await;
+        assert_eq!(api.requests.load(Ordering::SeqCst), 1);
+        assert_eq!(disk.get("hi").await.as_deref(), Some("Hello, world!"));
+
+        // Without a cached answer the provider's is awaited
+        assert_eq!(engine.query("hello").await.unwrap(), "Hello, world!");
+        assert_eq!(disk.get("hello").await.as_deref(), Some("Hello, world!"));
+    }
+
+    #[tokio::test]
+    async fn test_identical_queries_in_flight_share_a_request() {
+        let api = Arc::new(CountingApi { delay: Duration::from_millis(50), ..CountingApi::default() });
+        let engine = engine(api.clone(), false);
+
+        let (first, second) = futures::future::join(engine.query("hi"), engine.query("hi")).await;
+        assert_eq!(first.unwrap(), "Hello, world!");
+        assert_eq!(second.unwrap(), "Hello, world!");
+        assert_eq!(api.requests.load(Ordering::SeqCst), 1);
+
+        // Once it's answered, the same prompt is a new request (there's no cache here)
+        let (_, _) = futures::future::join(engine.query("hi"), engine.query("hello")).await;
+        assert_eq!(api.requests.load(Ordering::SeqCst), 3);
+        assert!(engine.pending.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_no_cache() {
+        let api = Arc::new(CountingApi::default());
+        let mut engine = engine(api.clone(), false);
+
+        engine.query("hi").await.unwrap();
+        engine.query_stream("hi", |_| {}).await.unwrap();
+        assert_eq!(api.requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_stream_is_not_cached() {
+        let api = Arc::new(CountingApi::default());
+        let engine = engine(api.clone(), true);
+        let cancel = CancellationToken::new();
+
+        let mut tokens = engine.query_streaming("hi", cancel.clone()).await.unwrap();
+        assert_eq!(tokens.next().await.unwrap().unwrap(), "Hello, ");
+        cancel.cancel();
+        assert!(matches!(tokens.next().await, Some(Err(CoreError::Cancelled))));
+        assert!(tokens.next().await.is_none());
+
+        // Only a complete response is recorded
+        engine.query_streaming("hi", CancellationToken::new()).await.unwrap().count().await;
+        assert_eq!(api.requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_before_start() {
+        let engine = engine(Arc::new(CountingApi::default()), true);
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        assert!(matches!(engine.query_streaming("hi", cancel).await, Err(CoreError::Cancelled)));
+    }
+
+    /// Plays back scripted streams in order, recording the prompts it was sent
+    struct ScriptedApi {
+        streams: std::sync::Mutex<Vec<Vec<Result<String, ApiError>>>>,
+        prompts: std::sync::Mutex<Vec<String>>,
+    }
+
+    impl ScriptedApi {
+        fn new(mut streams: Vec<Vec<Result<String, ApiError>>>) -> Self {
+            streams.reverse();
+            Self { streams: std::sync::Mutex::new(streams), prompts: std::sync::Mutex::new(Vec::new()) }
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl LLMApi for ScriptedApi {
+        fn model(&self) -> &str {
+            "test-model"
+        }
+
+        async fn send_query(&self, prompt: &str) -> Result<String, ApiError> {
+            self.prompts.lock().unwrap().push(prompt.to_string());
+            Ok("world!".to_string())
+        }
+
+        async fn send_streaming_query(&self, prompt: &str) -> Result<StreamingResponse, ApiError> {
+            self.prompts.lock().unwrap().push(prompt.to_string());
+            let chunks = self.streams.lock().unwrap().pop().expect("no more scripted streams");
+            Ok(Box::pin(stream::iter(chunks)))
+        }
+
+        async fn validate_key(&self) -> Result<(), ApiError> {
+            Ok(())
+        }
+    }
+
+    fn scripted_engine(api: Arc<ScriptedApi>, stream_recovery: StreamRecovery) -> QueryEngine {
+        QueryEngine::new(api, QueryConfig {
+            show_progress: false,
+            retry_delay: Duration::from_millis(1),
+            stream_recovery,
+            ..QueryConfig::default()
+        })
+    }
+
+    fn rate_limited() -> Result<String, ApiError> {
+        Err(ApiError::RateLimit)
+    }
+
+    #[tokio::test]
+    async fn test_retry_before_first_token() {
+        let api = Arc::new(ScriptedApi::new(vec![
+            vec![rate_limited()],
+            vec![Ok("Hello, ".to_string()), Ok("world!".to_string())],
+        ]));
+        let mut engine = scripted_engine(api.clone(), StreamRecovery::Fail);
+
+        assert_eq!(engine.query_stream("hi", |_| {}).await.unwrap(), "Hello, world!");
+        assert_eq!(api.prompts.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_mid_stream_failure() {
+        let api = Arc::new(ScriptedApi::new(vec![vec![Ok("Hello, ".to_string()), rate_limited()]]));
+        let mut engine = scripted_engine(api, StreamRecovery::Fail);
+        assert!(matches!(engine.query_stream("hi", |_| {}).await, Err(CoreError::Api(ApiError::RateLimit))));
+    }
+
+    #[tokio::test]
+    async fn test_mid_stream_resume() {
+        let api = Arc::new(ScriptedApi::new(vec![
+            vec![Ok("Hello, ".to_string()), rate_limited()],
+            vec![Ok("world!".to_string())],
+        ]));
+        let mut engine = scripted_engine(api.clone(), StreamRecovery::Resume);
+
+        let response = engine.query_stream("hi", |_| {}).await.unwrap();
+        assert_eq!(response, "Hello, \n\n[connection lost, continuing]\n\nworld!");
+        let prompts = api.prompts.lock().unwrap();
+        assert!(prompts[1].starts_with("hi\n\nYour previous answer was cut off."));
+        assert!(prompts[1].ends_with("It ended with:\nHello, "));
+    }
+
+    #[tokio::test]
+    async fn test_mid_stream_buffered_fallback() {
+        let api = Arc::new(ScriptedApi::new(vec![vec![Ok("Hello, ".to_string()), rate_limited()]]));
+        let mut engine = scripted_engine(api, StreamRecovery::Buffered);
+
+        let response = engine.query_stream("hi", |_| {}).await.unwrap();
+        assert!(response.starts_with("Hello, \n\n[connection lost, the rest was fetched without streaming]"));
+        assert!(response.ends_with("world!"));
+    }
+}
@@ -0,0 +1,187 @@
+//! Prompts saved for later (`q queue add`), at `DataPaths::queue`: one JSON
+//! entry per line, in the order they were added, sealed like the history log
+//! when it is encrypted. An entry stays until its answer has been shown; an
+//! answer that arrives while nobody is looking (the flush after another
+//! query) is kept in the entry until `q queue flush` prints it. A prompt the
+//! provider refused keeps the reason, and stays until it's removed.
+//!
+//! Changes take a lock (`queue.jsonl.lock`) so two runs can't lose each
+//! other's entries, and lines this run can't read are kept as they are.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use super::crypto::{self, Cipher};
+use super::history::{self, private_file};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Queued {
+    /// Milliseconds since the epoch when it was added, also its ID
+    pub id: u64,
+    pub prompt: String,
+    /// The provider and model picked when it was added, else the config's at flush time
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub answer: Option<Answer>,
+    /// Why the provider refused it the last time it was sent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failed: Option<String>,
+}
+
+/// An answer that hasn't been shown yet
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Answer {
+    pub provider: String,
+    pub model: String,
+    pub response: String,
+}
+
+pub struct Queue {
+    path: PathBuf,
+    cipher: Option<Cipher>,
+}
+
+impl Queue {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path, cipher: None }
+    }
+
+    /// Seal entries with `cipher` and open sealed ones when reading
+    pub fn with_cipher(mut self, cipher: Cipher) -> Self {
+        self.cipher = Some(cipher);
+        self
+    }
+
+    /// Add an entry at the end, creating the file (readable only by the user) if needed
+    pub fn add(&self, entry: &Queued) -> io::Result<()> {
+        let mut line = self.seal(serde_json::to_string(entry)?);
+        line.push('\n');
+        let _lock = self.lock()?;
+        private_file(OpenOptions::new().create(true).append(true))
+            .open(&self.path)?
+            .write_all(line.as_bytes())
+    }
+
+    /// Every entry, oldest first; empty if nothing has been queued. Lines that
+    /// can't be read, e.g. sealed with another key, are left out.
+    pub fn entries(&self) -> io::Result<Vec<Queued>> {
+        Ok(self.lines()?.iter().filter_map(|line| self.parse(line)).collect())
+    }
+
+    /// Replace the entry with `entry`'s ID, or drop it when `entry` is `None`.
+    /// Entries added meanwhile by another run, and lines that can't be read,
+    /// are kept.
+    pub fn update(&self, id: u64, entry: Option<&Queued>) -> io::Result<()> {
+        let _lock = self.lock()?;
+        let mut output = String::new();
+        for line in self.lines()? {
+            let line = match self.parse(&line) {
+                Some(queued) if queued.id == id => match entry {
+                    Some(entry) => self.seal(serde_json::to_string(entry)?),
+                    None => continue,
+                },
+                _ => line,
+            };
+            output.push_str(&line);
+            output.push('\n');
+        }
+        let temp = self.path.with_extension("jsonl.tmp");
+        private_file(OpenOptions::new().create(true).write(true).truncate(true))
+            .open(&temp)?
+            .write_all(output.as_bytes())?;
+        fs::rename(&temp, &self.path)
+    }
+
+    /// Drop every entry, including lines that can't be read
+    pub fn clear(&self) -> io::Result<()> {
+        let _lock = self.lock()?;
+        match fs::remove_file(&self.path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    fn lock(&self) -> io::Result<File> {
+        history::lock(&self.path.with_extension("jsonl.lock"))
+    }
+
+    fn lines(&self) -> io::Result<Vec<String>> {
+        match fs::read_to_string(&self.path) {
+            Ok(text) => Ok(text.lines().filter(|line| !line.trim().is_empty()).map(str::to_string).collect()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn seal(&self, json: String) -> String {
+        match &self.cipher {
+            Some(cipher) => cipher.seal(&json),
+            None => json,
+        }
+    }
+
+    fn parse(&self, line: &str) -> Option<Queued> {
+        if crypto::is_sealed(line) {
+            serde_json::from_str(&self.cipher.as_ref()?.open(line)?).ok()
+        } else {
+            serde_json::from_str(line).ok()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn queued(id: u64, prompt: &str) -> Queued {
+        Queued { id, prompt: prompt.to_string(), provider: None, model: None, answer: None, failed: None }
+    }
+
+    #[test]
+    fn test_add_answer_and_drop() {
+        let dir = TempDir::new().unwrap();
+        let queue = Queue::new(dir.path().join("queue.jsonl"));
+        assert!(queue.entries().unwrap().is_empty());
+
+        queue.add(&queued(1, "first")).unwrap();
+        queue.add(&Queued { model: Some("gpt-4o".to_string()), ..queued(2, "second") }).unwrap();
+        let answered = Queued {
+            answer: Some(Answer { provider: "openai".to_string(), model: "gpt-4o".to_string(), response: "done".to_string() }),
+            ..queued(1, "first")
+        };
+        queue.update(1, Some(&answered)).unwrap();
+        assert_eq!(queue.entries().unwrap()[0], answered);
+
+        queue.update(1, None).unwrap();
+        let entries = queue.entries().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].model.as_deref(), Some("gpt-4o"));
+    }
+
+    #[test]
+    fn test_sealed_entries() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("queue.jsonl");
+        let queue = Queue::new(path.clone()).with_cipher(Cipher::new(&[7; 32]));
+        queue.add(&queued(1, "a secret prompt")).unwrap();
+        assert!(!fs::read_to_string(&path).unwrap().contains("secret"));
+        assert_eq!(queue.entries().unwrap(), vec![queued(1, "a secret prompt")]);
+
+        // Without the key the entry can't be read, but changes keep it
+        let unsealed = Queue::new(path.clone());
+        assert!(unsealed.entries().unwrap().is_empty());
+        unsealed.add(&queued(2, "plain")).unwrap();
+        unsealed.update(2, None).unwrap();
+        assert_eq!(queue.entries().unwrap(), vec![queued(1, "a secret prompt")]);
+
+        queue.clear().unwrap();
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,117 @@
+//! Pruning local stores to their `[retention]` limits.
+//!
+//! Directory stores (sessions, the cache) are pruned a file at a time by
+//! modification time; the history log is pruned a record at a time by
+//! `HistoryLog::prune`. Either way, entries past the age limit go first and
+//! then the oldest remaining ones until the store fits its size limit.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use walkdir::WalkDir;
+
+/// What pruning one store removed
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Pruned {
+    /// Files or records
+    pub removed: usize,
+    pub bytes: u64,
+}
+
+/// Delete files under `dir` last modified more than `max_age` before `now`,
+/// then the oldest until the rest total at most `max_bytes`. A missing
+/// directory has nothing to prune.
+pub fn prune_dir(dir: &Path, max_age: Option<Duration>, max_bytes: Option<u64>, now: SystemTime) -> io::Result<Pruned> {
+    let mut files: Vec<(SystemTime, u64, PathBuf)> = Vec::new();
+    for entry in WalkDir::new(dir).into_iter() {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) if e.io_error().map(io::Error::kind) == Some(io::ErrorKind::NotFound) => continue,
+            Err(e) => return Err(e.into()),
+        };
+        if entry.file_type().is_file() {
+            let metadata = entry.metadata()?;
+            files.push((metadata.modified()?, metadata.len(), entry.into_path()));
+        }
+    }
+    files.sort();
+
+    let entries: Vec<(Option<Duration>, u64)> = files.iter()
+        .map(|(modified, size, _)| (now.duration_since(*modified).ok(), *size))
+        .collect();
+    let mut pruned = Pruned::default();
+    for index in expired_entries(&entries, max_age, max_bytes) {
+        let (_, size, path) = &files[index];
+        fs::remove_file(path)?;
+        pruned.removed += 1;
+        pruned.bytes += size;
+    }
+    Ok(pruned)
+}
+
+/// Indexes of the entries to drop from a store ordered oldest first, given
+/// each entry's age (`None` for entries with no known age, which are kept by
+/// the age limit) and size
+pub fn expired_entries(entries: &[(Option<Duration>, u64)], max_age: Option<Duration>, max_bytes: Option<u64>) -> Vec<usize> {
+    let mut drop: Vec<bool> = entries.iter()
+        .map(|(age, _)| matches!((age, max_age), (Some(age), Some(max)) if age > &max))
+        .collect();
+    if let Some(max_bytes) = max_bytes {
+        let mut total: u64 = entries.iter().zip(&drop).filter(|(_, dropped)| !**dropped).map(|((_, size), _)| size).sum();
+        for (index, (_, size)) in entries.iter().enumerate() {
+            if total <= max_bytes {
+                break;
+            }
+            if !drop[index] {
+                drop[index] = true;
+                total -= size;
+            }
+        }
+    }
+    drop.iter().enumerate().filter(|(_, dropped)| **dropped).map(|(index, _)| index).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+    fn file(dir: &Path, name: &str, size: usize, age: Duration) {
+        let path = dir.join(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, vec![b'x'; size]).unwrap();
+        fs::File::options().write(true).open(&path).unwrap()
+            .set_modified(SystemTime::now() - age).unwrap();
+    }
+
+    #[test]
+    fn test_prune_dir() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        file(dir, "old", 10, DAY * 40);
+        file(dir, "nested/older", 10, DAY * 50);
+        file(dir, "week", 100, DAY * 7);
+        file(dir, "day", 100, DAY);
+        file(dir, "new", 100, Duration::ZERO);
+
+        let pruned = prune_dir(dir, Some(DAY * 30), Some(250), SystemTime::now()).unwrap();
+        assert_eq!(pruned, Pruned { removed: 3, bytes: 120 });
+        assert!(dir.join("day").exists() && dir.join("new").exists() && !dir.join("week").exists());
+
+        assert_eq!(prune_dir(dir, None, None, SystemTime::now()).unwrap(), Pruned::default());
+        assert_eq!(prune_dir(&dir.join("missing"), Some(DAY), None, SystemTime::now()).unwrap(), Pruned::default());
+    }
+
+    #[test]
+    fn test_expired_entries() {
+        let entries = [(Some(DAY * 9), 10), (None, 10), (Some(DAY * 2), 10), (Some(DAY), 10)];
+        assert_eq!(expired_entries(&entries, Some(DAY * 7), None), vec![0]);
+        assert_eq!(expired_entries(&entries, Some(DAY * 7), Some(20)), vec![0, 1]);
+        assert_eq!(expired_entries(&entries, None, Some(15)), vec![0, 1, 2]);
+        assert!(expired_entries(&entries, None, None).is_empty());
+    }
+}
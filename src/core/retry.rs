@@ -1,9 +1,84 @@
-use std::time::Duration;
-use backoff::{ExponentialBackoff, backoff::Backoff};
-use super::{CoreError, CoreResult};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::{Duration, Instant, SystemTime};
+use crate::api::ApiError;
+use super::{CoreError, CoreResult, QueryConfig};
+
+/// Rate limits back off this many times longer than transient failures
+const RATE_LIMIT_MULTIPLIER: u32 = 4;
+
+/// How failed requests are retried
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Attempts in total, including the first
+    pub max_retries: u32,
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+    /// Give up once retrying would run past this much time since the first attempt
+    pub budget: Option<Duration>,
+}
+
+/// A retry about to happen, for progress display
+#[derive(Debug, Clone)]
+pub struct RetryEvent {
+    /// The attempt about to start (2 for the first retry)
+    pub attempt: u32,
+    pub max_attempts: u32,
+    pub delay: Duration,
+    pub reason: String,
+}
+
+/// How an error should be retried
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ErrorClass {
+    /// Bad request or key: retrying can't help
+    Fatal,
+    /// Dropped connections and the like: retry soon
+    Transient,
+    /// 429: back off for longer
+    RateLimited,
+}
+
+impl RetryPolicy {
+    pub fn from_config(config: &QueryConfig) -> Self {
+        Self {
+            max_retries: config.max_retries,
+            initial_delay: config.retry_delay,
+            max_delay: config.max_retry_delay,
+            budget: config.retry_budget,
+        }
+    }
+
+    /// How long to wait before retrying after failure number `attempt` with
+    /// `error`, or `None` when it shouldn't be retried. Delays grow
+    /// exponentially and use full jitter so concurrent clients spread out.
+    pub fn delay(&self, error: &CoreError, attempt: u32) -> Option<Duration> {
+        let initial = match classify(error) {
+            ErrorClass::Fatal => return None,
+            ErrorClass::Transient => self.initial_delay,
+            ErrorClass::RateLimited => self.initial_delay.saturating_mul(RATE_LIMIT_MULTIPLIER),
+        };
+        Some(full_jitter(backoff_delay(attempt, initial, self.max_delay)))
+    }
+
+    /// The delay before the next attempt, or `None` when `attempts` have
+    /// been used up, the error is fatal, or the time budget would be exceeded.
+    /// `asked` is the provider's `Retry-After`, waited out at least; a wait
+    /// longer than `max_delay` isn't worth retrying for.
+    pub fn next_delay(&self, error: &CoreError, attempts: u32, started: Instant, asked: Option<Duration>) -> Option<Duration> {
+        if attempts >= self.max_retries || asked.is_some_and(|asked| asked > self.max_delay) {
+            return None;
+        }
+        let delay = self.delay(error, attempts)?.max(asked.unwrap_or_default());
+        match self.budget {
+            Some(budget) if started.elapsed() + delay > budget => None,
+            _ => Some(delay),
+        }
+    }
+}
 
 pub async fn with_retry<T, F, Fut>(
-    mut f: F,
+    f: F,
     max_retries: u32,
     initial_delay: Duration,
     max_delay: Duration,
@@ -12,61 +87,84 @@ where
     F: FnMut() -> Fut,
     Fut: std::future::Future<Output = CoreResult<T>>,
 {
-    let mut backoff = ExponentialBackoff {
-        initial_interval: initial_delay,
-        max_interval: max_delay,
-        multiplier: 2.0,
-        max_elapsed_time: None,
-        ..ExponentialBackoff::default()
-    };
+    let policy = RetryPolicy { max_retries, initial_delay, max_delay, budget: None };
+    with_policy(f, &policy, || None, |_| {}).await
+}
 
+/// Run `f` until it succeeds or `policy` gives up, calling `on_retry` before
+/// each retry. `asked` is how long the provider said to wait after the last
+/// failure (`LLMApi::last_retry_after`).
+pub async fn with_policy<T, F, Fut, A, N>(mut f: F, policy: &RetryPolicy, asked: A, mut on_retry: N) -> CoreResult<T>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = CoreResult<T>>,
+    A: Fn() -> Option<Duration>,
+    N: FnMut(&RetryEvent),
+{
+    let started = Instant::now();
     let mut attempt = 0;
     loop {
         match f().await {
             Ok(value) => return Ok(value),
             Err(err) => {
                 attempt += 1;
-                if attempt >= max_retries || !should_retry(&err) {
+                let Some(delay) = policy.next_delay(&err, attempt, started, asked()) else {
                     return Err(err);
-                }
-
-                if let Some(delay) = backoff.next_backoff() {
-                    tokio::time::sleep(delay).await;
-                } else {
-                    return Err(CoreError::Retry(format!(
-                        "Max retries ({}) exceeded",
-                        max_retries
-                    )));
-                }
+                };
+                on_retry(&RetryEvent {
+                    attempt: attempt + 1,
+                    max_attempts: policy.max_retries,
+                    delay,
+                    reason: err.to_string(),
+                });
+                tokio::time::sleep(delay).await;
             }
         }
     }
 }
 
-fn should_retry(error: &CoreError) -> bool {
+/// Doubling from `initial` for each earlier failure, capped at `max`
+fn backoff_delay(attempt: u32, initial: Duration, max: Duration) -> Duration {
+    initial.saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1))).min(max)
+}
+
+/// A random duration between zero and `max`
+fn full_jitter(max: Duration) -> Duration {
+    // Good enough randomness for spreading retries without pulling in an RNG
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u128(SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_nanos());
+    let fraction = (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64;
+    max.mul_f64(fraction)
+}
+
+fn classify(error: &CoreError) -> ErrorClass {
     match error {
-        CoreError::Api(api_error) => api_error.is_retryable(),
-        CoreError::Cache(_) => true,
-        CoreError::Retry(_) => true,
-        CoreError::Other(_) => false,
+        // An overloaded provider needs the same room as a rate limit
+        CoreError::Api(ApiError::RateLimit | ApiError::ServerOverloaded(_)) => ErrorClass::RateLimited,
+        CoreError::Api(api_error) if api_error.is_retryable() => ErrorClass::Transient,
+        CoreError::Api(_) => ErrorClass::Fatal,
+        CoreError::Cache(_) | CoreError::Retry(_) => ErrorClass::Transient,
+        CoreError::Cancelled | CoreError::Other(_) => ErrorClass::Fatal,
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::api::ApiError;
+    use std::sync::atomic::{AtomicU32, Ordering};
 
     #[tokio::test]
     async fn test_retry_success_after_failure() {
-        let mut attempts = 0;
+        let attempts = AtomicU32::new(0);
         let result = with_retry(
-            || async {
-                attempts += 1;
-                if attempts < 2 {
-                    Err(CoreError::Retry("Test retry".to_string()))
-                } else {
-                    Ok("success")
+            || {
+                let attempts = &attempts;
+                async move {
+                    if attempts.fetch_add(1, Ordering::SeqCst) + 1 < 2 {
+                        Err(CoreError::Retry("Test retry".to_string()))
+                    } else {
+                        Ok("success")
+                    }
                 }
             },
             3,
@@ -76,16 +174,19 @@ mod tests {
         .await;
 
         assert!(result.is_ok());
-        assert_eq!(attempts, 2);
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
     }
 
     #[tokio::test]
     async fn test_retry_max_attempts_exceeded() {
-        let mut attempts = 0;
-        let result = with_retry(
-            || async {
-                attempts += 1;
-                Err(CoreError::Retry("Test retry".to_string()))
+        let attempts = AtomicU32::new(0);
+        let result: CoreResult<()> = with_retry(
+            || {
+                let attempts = &attempts;
+                async move {
+                    attempts.fetch_add(1, Ordering::SeqCst);
+                    Err(CoreError::Retry("Test retry".to_string()))
+                }
             },
             2,
             Duration::from_millis(1),
@@ -94,6 +195,78 @@ mod tests {
         .await;
 
         assert!(result.is_err());
-        assert_eq!(attempts, 2);
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+    }
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy {
+            max_retries: 5,
+            initial_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(10),
+            budget: None,
+        }
+    }
+
+    #[test]
+    fn test_delay_depends_on_error_class() {
+        let policy = policy();
+        assert_eq!(policy.delay(&CoreError::Api(ApiError::InvalidKey), 1), None);
+        assert_eq!(policy.delay(&CoreError::Api(ApiError::Other("bad request".to_string())), 1), None);
+
+        for attempt in 1..=4 {
+            let transient = policy.delay(&CoreError::Retry("reset".to_string()), attempt).unwrap();
+            assert!(transient <= Duration::from_millis(100) * 2u32.pow(attempt - 1));
+            let rate_limited = policy.delay(&CoreError::Api(ApiError::RateLimit), attempt).unwrap();
+            assert!(rate_limited <= Duration::from_millis(400) * 2u32.pow(attempt - 1));
+        }
+        assert!(policy.delay(&CoreError::Api(ApiError::RateLimit), 20).unwrap() <= Duration::from_secs(10));
+    }
+
+    #[test]
+    fn test_budget_stops_retries() {
+        let policy = RetryPolicy { budget: Some(Duration::ZERO), ..policy() };
+        let error = CoreError::Retry("reset".to_string());
+        let started = Instant::now() - Duration::from_millis(1);
+        assert_eq!(policy.next_delay(&error, 1, started, None), None);
+        assert_eq!(RetryPolicy { budget: None, ..policy }.next_delay(&error, 5, started, None), None);
+    }
+
+    #[test]
+    fn test_retry_after_is_the_minimum_delay() {
+        let policy = policy();
+        let error = CoreError::Api(ApiError::RateLimit);
+        let started = Instant::now();
+        for _ in 0..20 {
+            let delay = policy.next_delay(&error, 1, started, Some(Duration::from_secs(3))).unwrap();
+            assert!(delay >= Duration::from_secs(3));
+        }
+        // Longer than the longest delay: give up rather than wait
+        assert_eq!(policy.next_delay(&error, 1, started, Some(Duration::from_secs(60))), None);
+    }
+
+    #[tokio::test]
+    async fn test_retry_events() {
+        let attempts = AtomicU32::new(0);
+        let mut events = Vec::new();
+        let policy = RetryPolicy { initial_delay: Duration::from_millis(1), ..policy() };
+        let result = with_policy(
+            || {
+                let attempts = &attempts;
+                async move {
+                    if attempts.fetch_add(1, Ordering::SeqCst) < 2 {
+                        Err(CoreError::Api(ApiError::RateLimit))
+                    } else {
+                        Ok("success")
+                    }
+                }
+            },
+            &policy,
+            || None,
+            |event| events.push((event.attempt, event.max_attempts)),
+        )
+        .await;
+
+        assert_eq!(result.unwrap(), "success");
+        assert_eq!(events, vec![(2, 5), (3, 5)]);
     }
 }
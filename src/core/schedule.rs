@@ -0,0 +1,143 @@
+//! Crontab schedules for `q cron` and `q schedule`, and when each saved
+//! schedule last ran, kept in a small JSON file under the state dir
+//! (`DataPaths::schedule_runs`) so `q schedule run` can tell which are due.
+//!
+//! A schedule is due when one of its times falls after its last run; runs
+//! missed while the machine was off or asleep are made up once, not once each.
+
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+
+use chrono::{DateTime, TimeZone};
+use croner::errors::CronError;
+use croner::parser::{CronParser, Seconds, Year};
+use croner::Cron;
+use serde::{Deserialize, Serialize};
+
+use super::history::{self, private_file};
+
+/// A crontab schedule: five fields or an @nickname, no seconds or years
+pub fn parse(schedule: &str) -> Result<Cron, CronError> {
+    CronParser::builder()
+        .seconds(Seconds::Disallowed)
+        .year(Year::Disallowed)
+        .build()
+        .parse(schedule)
+}
+
+/// Whether `cron` has a time after `last` and no later than `now`
+pub fn is_due<Tz: TimeZone>(cron: &Cron, last: &DateTime<Tz>, now: &DateTime<Tz>) -> bool {
+    cron.find_next_occurrence(last, false).is_ok_and(|next| next <= *now)
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Runs {
+    /// Seconds since the epoch of each schedule's last run, by name
+    #[serde(default)]
+    last: BTreeMap<String, i64>,
+}
+
+impl Runs {
+    /// The recorded runs; a missing or unreadable file means none
+    pub fn load(path: &Path) -> Self {
+        fs::read_to_string(path).ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    /// Change the runs recorded at `path` with `change`, holding a lock so an
+    /// overlapping `q schedule run` sees the result rather than racing it
+    pub fn update<T>(path: &Path, change: impl FnOnce(&mut Runs) -> T) -> io::Result<T> {
+        let _lock = history::lock(&path.with_extension("json.lock"))?;
+        let mut runs = Runs::load(path);
+        let result = change(&mut runs);
+        runs.save(path)?;
+        Ok(result)
+    }
+
+    /// Replace the file in one rename, so a reader never sees half of it
+    fn save(&self, path: &Path) -> io::Result<()> {
+        let temp = path.with_extension("json.tmp");
+        private_file(OpenOptions::new().create(true).write(true).truncate(true))
+            .open(&temp)?
+            .write_all(serde_json::to_string(self)?.as_bytes())?;
+        fs::rename(&temp, path)
+    }
+
+    pub fn last(&self, name: &str) -> Option<i64> {
+        self.last.get(name).copied()
+    }
+
+    pub fn record(&mut self, name: &str, at: i64) {
+        self.last.insert(name.to_string(), at);
+    }
+
+    pub fn forget(&mut self, name: &str) {
+        self.last.remove(name);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse() {
+        assert!(parse("0 7 * * 1-5").is_ok());
+        assert!(parse("*/15 9-17 * * MON-FRI").is_ok());
+        assert!(parse("@weekly").is_ok());
+        assert!(parse("0 0 7 * * 1-5").is_err());
+        assert!(parse("0 25 * * *").is_err());
+        assert!(parse("every day").is_err());
+    }
+
+    #[test]
+    fn test_is_due() {
+        let cron = parse("0 8 * * *").unwrap();
+        let at = |day, hour, minute| Utc.with_ymd_and_hms(2026, 10, day, hour, minute, 0).unwrap();
+        assert!(!is_due(&cron, &at(16, 8, 0), &at(16, 23, 59)));
+        assert!(is_due(&cron, &at(16, 8, 0), &at(17, 8, 0)));
+        // Days missed while asleep still make one run due
+        assert!(is_due(&cron, &at(12, 9, 0), &at(16, 7, 0)));
+    }
+
+    #[test]
+    fn test_runs_round_trip() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("schedule-runs.json");
+        assert_eq!(Runs::load(&path).last("news"), None);
+
+        Runs::update(&path, |runs| {
+            runs.record("news", 1_790_000_000);
+            runs.record("tips", 1_790_000_100);
+        }).unwrap();
+        let last = Runs::update(&path, |runs| {
+            runs.forget("tips");
+            runs.last("news")
+        }).unwrap();
+        assert_eq!(last, Some(1_790_000_000));
+        let runs = Runs::load(&path);
+        assert_eq!(runs.last("news"), Some(1_790_000_000));
+        assert_eq!(runs.last("tips"), None);
+    }
+
+    #[test]
+    fn test_overlapping_updates_are_not_lost() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("schedule-runs.json");
+        std::thread::scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|| {
+                    for _ in 0..25 {
+                        Runs::update(&path, |runs| runs.record("count", runs.last("count").unwrap_or(0) + 1)).unwrap();
+                    }
+                });
+            }
+        });
+        assert_eq!(Runs::load(&path).last("count"), Some(200));
+    }
+}
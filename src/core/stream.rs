@@ -0,0 +1,341 @@
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+use std::time::Duration;
+use colored::*;
+use futures::StreamExt;
+use indicatif::ProgressStyle;
+use tokio_util::sync::CancellationToken;
+
+use crate::api::LLMApi;
+use crate::utils::output;
+use super::{retry_message, CoreError, CoreResult, QueryConfig, QueryEngine};
+
+/// Somewhere a streamed answer goes. Each sink sees every chunk as it
+/// arrives and formats it its own way; one that fails is warned about and
+/// dropped without stopping the others.
+pub trait Sink {
+    /// What the sink writes to, for warnings
+    fn name(&self) -> String;
+
+    fn write(&mut self, chunk: &str) -> io::Result<()>;
+
+    /// The stream ended with `response`; `complete` is false when it was
+    /// cancelled or failed partway
+    fn finish(&mut self, _response: &str, _complete: bool) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Tokens written to stdout as they arrive, through the run's `OutputWriter`
+pub struct Terminal;
+
+impl Sink for Terminal {
+    fn name(&self) -> String {
+        "stdout".to_string()
+    }
+
+    fn write(&mut self, chunk: &str) -> io::Result<()> {
+        output::chunk(chunk);
+        Ok(())
+    }
+
+    fn finish(&mut self, _response: &str, _complete: bool) -> io::Result<()> {
+        output::end_answer();
+        Ok(())
+    }
+}
+
+/// The answer as plain text in a file (`-o`), written as it arrives to
+/// `FILE.partial` and renamed over `FILE` once it's complete, so a failed or
+/// cancelled answer leaves what was there and keeps what came beside it
+pub struct FileSink {
+    path: PathBuf,
+    partial: PathBuf,
+    file: File,
+}
+
+impl FileSink {
+    pub fn create(path: &Path) -> io::Result<Self> {
+        let mut partial = path.as_os_str().to_owned();
+        partial.push(".partial");
+        let partial = PathBuf::from(partial);
+        Ok(Self { path: path.to_path_buf(), file: File::create(&partial)?, partial })
+    }
+}
+
+impl Sink for FileSink {
+    fn name(&self) -> String {
+        self.path.display().to_string()
+    }
+
+    fn write(&mut self, chunk: &str) -> io::Result<()> {
+        self.file.write_all(chunk.as_bytes())
+    }
+
+    fn finish(&mut self, response: &str, complete: bool) -> io::Result<()> {
+        if !response.is_empty() && !response.ends_with('\n') {
+            self.file.write_all(b"\n")?;
+        }
+        self.file.flush()?;
+        if complete {
+            std::fs::rename(&self.partial, &self.path)
+        } else if response.is_empty() {
+            std::fs::remove_file(&self.partial)
+        } else {
+            Err(io::Error::other(format!("the answer didn't finish; what came is in {}", self.partial.display())))
+        }
+    }
+}
+
+impl Drop for FileSink {
+    /// A query that failed before its answer began leaves no partial file
+    fn drop(&mut self) {
+        if self.partial.metadata().is_ok_and(|metadata| metadata.len() == 0) {
+            let _ = std::fs::remove_file(&self.partial);
+        }
+    }
+}
+
+/// The whole answer copied to the clipboard once it's complete (`--copy`)
+pub struct Clipboard;
+
+impl Sink for Clipboard {
+    fn name(&self) -> String {
+        "clipboard".to_string()
+    }
+
+    fn write(&mut self, _chunk: &str) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn finish(&mut self, response: &str, complete: bool) -> io::Result<()> {
+        if complete {
+            copy_to_clipboard(response.trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+/// `on_finish` called with the complete answer, e.g. to store it in the history log
+pub struct OnFinish<F: FnMut(&str)>(pub F);
+
+impl<F: FnMut(&str)> Sink for OnFinish<F> {
+    fn name(&self) -> String {
+        "history".to_string()
+    }
+
+    fn write(&mut self, _chunk: &str) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn finish(&mut self, response: &str, complete: bool) -> io::Result<()> {
+        if complete {
+            (self.0)(response);
+        }
+        Ok(())
+    }
+}
+
+/// Copy `text` with the platform's clipboard command: pbcopy, clip, or on
+/// Linux wl-copy, xclip, or xsel, whichever is installed
+fn copy_to_clipboard(text: &str) -> io::Result<()> {
+    let candidates: &[(&str, &[&str])] = if cfg!(target_os = "macos") {
+        &[("pbcopy", &[])]
+    } else if cfg!(windows) {
+        &[("clip", &[])]
+    } else {
+        &[("wl-copy", &[]), ("xclip", &["-selection", "clipboard"]), ("xsel", &["--clipboard", "--input"])]
+    };
+    for (program, args) in candidates {
+        let mut child = match Command::new(program).args(*args).stdin(Stdio::piped()).stdout(Stdio::null()).stderr(Stdio::null()).spawn() {
+            Ok(child) => child,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(text.as_bytes())?;
+        }
+        let status = child.wait()?;
+        return if status.success() {
+            Ok(())
+        } else {
+            Err(io::Error::other(format!("{} failed with {}", program, status)))
+        };
+    }
+    let names: Vec<&str> = candidates.iter().map(|(program, _)| *program).collect();
+    Err(io::Error::other(format!("no clipboard command found (tried {})", names.join(", "))))
+}
+
+/// Give each sink `chunk`, dropping any that fail
+fn fan_out(sinks: &mut Vec<Box<dyn Sink + '_>>, chunk: &str) {
+    sinks.retain_mut(|sink| match sink.write(chunk) {
+        Ok(()) => true,
+        Err(e) => {
+            eprintln!("{}", format!("Stopped writing to {}: {}", sink.name(), e).yellow());
+            false
+        }
+    });
+}
+
+/// Tell each sink the answer is over
+pub fn finish(sinks: &mut [Box<dyn Sink + '_>], response: &str, complete: bool) {
+    for sink in sinks {
+        if let Err(e) = sink.finish(response, complete) {
+            eprintln!("{}", format!("Couldn't write to {}: {}", sink.name(), e).yellow());
+        }
+    }
+}
+
+/// Hand a buffered answer to `sinks` as a single chunk
+pub fn deliver(mut sinks: Vec<Box<dyn Sink + '_>>, response: &str) {
+    fan_out(&mut sinks, response);
+    finish(&mut sinks, response, true);
+}
+
+/// Stream a response to stdout token by token and return the full text
+pub async fn handle_streaming_response(api: Arc<dyn LLMApi>, prompt: &str) -> CoreResult<String> {
+    let mut engine = QueryEngine::new(api, QueryConfig::default());
+    print_stream(&mut engine, prompt).await
+}
+
+/// Stream `prompt` through `engine` to stdout, with a spinner until the
+/// first token, and return the full text. Ctrl-C stops the stream.
+pub async fn print_stream(engine: &mut QueryEngine, prompt: &str) -> CoreResult<String> {
+    stream_to(engine, prompt, vec![Box::new(Terminal)]).await
+}
+
+/// Stream `prompt` through `engine` to every sink at once, with a spinner
+/// until the first token, and return the full text. Ctrl-C stops the stream.
+pub async fn stream_to(engine: &mut QueryEngine, prompt: &str, mut sinks: Vec<Box<dyn Sink + '_>>) -> CoreResult<String> {
+    let spinner = engine.create_progress_bar();
+    spinner.set_style(
+        ProgressStyle::default_spinner()
+            .template("{spinner:.green} {msg}")
+            .unwrap_or_else(|_| ProgressStyle::default_spinner()),
+    );
+    spinner.set_message(format!("Generating... ({})", engine.model()).dimmed().to_string());
+    spinner.enable_steady_tick(Duration::from_millis(100));
+    engine.set_retry_listener({
+        let spinner = spinner.clone();
+        Arc::new(move |event| spinner.set_message(retry_message(event).dimmed().to_string()))
+    });
+
+    let cancel = CancellationToken::new();
+    let interrupt = {
+        let cancel = cancel.clone();
+        tokio::spawn(async move {
+            if tokio::signal::ctrl_c().await.is_ok() {
+                cancel.cancel();
+            }
+        })
+    };
+
+    let mut response = String::new();
+    let mut result = Ok(());
+    match engine.query_streaming(prompt, cancel).await {
+        Ok(mut tokens) => {
+            while let Some(chunk) = tokens.next().await {
+                match chunk {
+                    Ok(text) => {
+                        if response.is_empty() {
+                            spinner.finish_and_clear();
+                        }
+                        response.push_str(&text);
+                        fan_out(&mut sinks, &text);
+                    }
+                    Err(e) => {
+                        result = Err(e);
+                        break;
+                    }
+                }
+            }
+        }
+        Err(e) => result = Err(e),
+    }
+    interrupt.abort();
+    spinner.finish_and_clear();
+
+    finish(&mut sinks, &response, result.is_ok());
+    match result {
+        Err(CoreError::Cancelled) => {
+            eprintln!("{}", "(cancelled)".dimmed());
+            Err(CoreError::Cancelled)
+        }
+        Err(e) => Err(e),
+        Ok(()) => Ok(response),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    /// Fails after its first chunk
+    struct Flaky(Vec<String>);
+
+    impl Sink for Flaky {
+        fn name(&self) -> String {
+            "flaky".to_string()
+        }
+
+        fn write(&mut self, chunk: &str) -> io::Result<()> {
+            if !self.0.is_empty() {
+                return Err(io::Error::other("disk full"));
+            }
+            self.0.push(chunk.to_string());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_fan_out_drops_failing_sinks() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("answer.md");
+        let mut finished = Vec::new();
+        {
+            let mut sinks: Vec<Box<dyn Sink + '_>> = vec![
+                Box::new(Flaky(Vec::new())),
+                Box::new(FileSink::create(&path).unwrap()),
+                Box::new(OnFinish(|response: &str| finished.push(response.to_string()))),
+            ];
+            fan_out(&mut sinks, "Use ");
+            fan_out(&mut sinks, "tar -xzf");
+            assert_eq!(sinks.len(), 2);
+            finish(&mut sinks, "Use tar -xzf", true);
+        }
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "Use tar -xzf\n");
+        assert_eq!(finished, ["Use tar -xzf"]);
+    }
+
+    #[test]
+    fn test_file_sink_keeps_the_old_file_until_complete() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("answer.md");
+        std::fs::write(&path, "earlier answer\n").unwrap();
+
+        let mut sinks: Vec<Box<dyn Sink>> = vec![Box::new(FileSink::create(&path).unwrap())];
+        fan_out(&mut sinks, "Use t");
+        finish(&mut sinks, "Use t", false);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "earlier answer\n");
+        assert_eq!(std::fs::read_to_string(temp_dir.path().join("answer.md.partial")).unwrap(), "Use t\n");
+
+        let mut sinks: Vec<Box<dyn Sink>> = vec![Box::new(FileSink::create(&path).unwrap())];
+        fan_out(&mut sinks, "Use tar");
+        finish(&mut sinks, "Use tar", true);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "Use tar\n");
+        assert!(!temp_dir.path().join("answer.md.partial").exists());
+    }
+
+    #[test]
+    fn test_on_finish_skips_incomplete_answers() {
+        let mut called = false;
+        let mut sinks: Vec<Box<dyn Sink + '_>> = vec![Box::new(OnFinish(|_: &str| called = true))];
+        finish(&mut sinks, "Use t", false);
+        drop(sinks);
+        assert!(!called);
+    }
+}
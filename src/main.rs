@@ -1,23 +1,14 @@
-use clap::Parser;
-
-mod cli;
-mod config;
-mod utils;
-mod api;
-mod context;
-mod commands;
-mod core;
-
-use cli::args::Cli;
+use q::cli::args::Cli;
+use q::utils::errors::present;
 
 #[tokio::main]
 async fn main() {
-    // Parse command line arguments
-    let cli = Cli::parse();
+    // Parse command line arguments, filling unset flags from the config
+    let cli = Cli::parse_with_config();
 
     // Handle the result of running the CLI
     if let Err(err) = cli.run().await {
-        eprintln!("Error: {}", err);
+        eprintln!("{}", present(&err));
         std::process::exit(1);
     }
 }
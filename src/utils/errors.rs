@@ -1,5 +1,7 @@
 use thiserror::Error;
 
+use super::i18n;
+
 #[derive(Error, Debug)]
 pub enum QError {
     #[error("CLI error: {0}")]
@@ -42,3 +44,82 @@ impl From<&str> for QError {
         QError::Unknown(err.to_string())
     }
 }
+
+/// Failures worth a next step, matched on the messages `ApiError`, reqwest,
+/// and the config layer produce; by the time an error reaches `main` it is
+/// text. The hints themselves are the `hint-*` messages in `locales/`.
+const HINTS: &[(&[&str], &str)] = &[
+    (&["Invalid API key"], "hint-invalid-key"),
+    (&["Prompt is too long for the model"], "hint-context-length"),
+    (&["Quota exceeded"], "hint-quota"),
+    (&["Model not found"], "hint-model-not-found"),
+    (&["Rate limit exceeded"], "hint-rate-limit"),
+    (&["Provider is overloaded"], "hint-overloaded"),
+    (&["Request timed out"], "hint-timeout"),
+    (&["Blocked by the provider's content filter"], "hint-content-filter"),
+    (&["dns error", "failed to lookup address", "error trying to connect", "Connection refused"], "hint-network"),
+];
+
+/// What to try next for `err`, when it is a failure users commonly hit
+pub fn hint(err: &QError) -> Option<String> {
+    let message = err.to_string();
+    HINTS.iter()
+        .find(|(patterns, _)| patterns.iter().any(|pattern| message.contains(pattern)))
+        .map(|(_, id)| i18n::message(id))
+}
+
+/// The error in the user's language: its kind's label around the detail,
+/// which stays as the code wrote it
+fn describe(err: &QError) -> String {
+    let (id, detail) = match err {
+        QError::Cli(e) => ("error-cli", e.to_string()),
+        QError::Config(detail) => ("error-config", detail.clone()),
+        QError::Api(detail) => ("error-api", detail.clone()),
+        QError::Io(e) => ("error-io", e.to_string()),
+        QError::Context(detail) => ("error-context", detail.clone()),
+        QError::Command(detail) => ("error-command", detail.clone()),
+        QError::Core(detail) => ("error-core", detail.clone()),
+        QError::Usage(detail) => ("error-usage", detail.clone()),
+        QError::Unknown(detail) => ("error-unknown", detail.clone()),
+    };
+    i18n::message_with(id, &[("detail", &detail)])
+}
+
+/// The error as shown to the user, followed by a hint when one applies
+pub fn present(err: &QError) -> String {
+    let error = format!("{}: {}", i18n::message("error"), describe(err));
+    match hint(err) {
+        Some(hint) => format!("{}\n{}: {}", error, i18n::message("hint"), hint),
+        None => error,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::ApiError;
+
+    #[test]
+    fn test_hints() {
+        let err = QError::Core(format!("Query failed: {}", ApiError::InvalidKey));
+        assert!(hint(&err).unwrap().contains("q set-key"));
+
+        let err = QError::Core(format!("Query failed: {}", ApiError::ContextLengthExceeded("8192 tokens".to_string())));
+        assert!(hint(&err).unwrap().contains("larger context window"));
+
+        let err = QError::Api("error sending request for url (https://api.openai.com/): error trying to connect: dns error: failed to lookup address information".to_string());
+        assert!(hint(&err).unwrap().contains("proxy"));
+
+        assert_eq!(hint(&QError::Usage("No commits in HEAD".to_string())), None);
+    }
+
+    #[test]
+    fn test_present() {
+        let err = QError::Core(format!("Query failed: {}", ApiError::Timeout));
+        assert_eq!(
+            present(&err),
+            "Error: Core error: Query failed: Request timed out\nHint: Try again; a shorter question or less context also helps slow models."
+        );
+        assert_eq!(present(&QError::Usage("bad".to_string())), "Error: Usage error: bad");
+    }
+}
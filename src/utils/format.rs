@@ -1,6 +1,22 @@
 use colored::*;
+use lazy_static::lazy_static;
+use regex::Regex;
+
+lazy_static! {
+    /// A markdown reference definition, as written by `api::append_citations`
+    static ref REFERENCE: Regex = Regex::new(r#"^\[(\d+)\]:\s+(\S+)(?:\s+"(.*)")?\s*$"#).expect("valid regex");
+    /// Terminal escape sequences: CSI (colors, cursor moves), OSC (titles, links), and two-byte escapes
+    static ref ANSI_ESCAPE: Regex = Regex::new(r"\x1b\[[0-?]*[ -/]*[@-~]|\x1b\][^\x07\x1b]*(?:\x07|\x1b\\)|\x1b[@-Z\\-_]").expect("valid regex");
+}
+
+/// `text` without terminal escape sequences, as captured output reads in a pager
+pub fn strip_ansi(text: &str) -> String {
+    ANSI_ESCAPE.replace_all(text, "").into_owned()
+}
 
 pub fn format_markdown(text: &str) -> String {
+    let hyperlinks = hyperlinks_supported();
+
     let mut result = String::new();
     let mut in_code_block = false;
     let mut code_block_content = String::new();
@@ -20,14 +36,18 @@ pub fn format_markdown(text: &str) -> String {
         if in_code_block {
             code_block_content.push_str(line);
             code_block_content.push('\n');
-        } else if line.starts_with("**") && line.ends_with("**") {
+        } else if let Some(content) = line.strip_prefix("**").and_then(|rest| rest.strip_suffix("**")) {
             // Bold text
-            let content = &line[2..line.len()-2];
             result.push_str(&content.bold().to_string());
             result.push('\n');
-        } else if line.starts_with("* ") {
+        } else if let Some(caps) = REFERENCE.captures(line) {
+            // Citation footnote
+            let title = caps.get(3).map(|m| m.as_str()).filter(|t| !t.is_empty());
+            result.push_str(&format_reference(&caps[1], &caps[2], title, hyperlinks));
+            result.push('\n');
+        } else if let Some(item) = line.strip_prefix("* ") {
             // List item
-            result.push_str(&format!("• {}\n", &line[2..]).yellow().to_string());
+            result.push_str(&format!("• {}\n", item).yellow().to_string());
         } else {
             // Normal text
             result.push_str(line);
@@ -42,3 +62,75 @@ pub fn format_markdown(text: &str) -> String {
 
     result
 }
+
+/// `[n] Title` linked to the URL with an OSC 8 hyperlink, or `[n] Title - URL`
+/// when the terminal can't show links
+fn format_reference(number: &str, url: &str, title: Option<&str>, hyperlinks: bool) -> String {
+    let label = format!("[{}]", number).dimmed();
+    match (title, hyperlinks) {
+        (Some(title), true) => format!("{} {}", label, hyperlink(url, title)),
+        (None, true) => format!("{} {}", label, hyperlink(url, url)),
+        (Some(title), false) => format!("{} {} - {}", label, title, url.underline()),
+        (None, false) => format!("{} {}", label, url.underline()),
+    }
+}
+
+fn hyperlink(url: &str, text: &str) -> String {
+    format!("\x1b]8;;{}\x1b\\{}\x1b]8;;\x1b\\", url, text.underline())
+}
+
+/// Whether stdout is a terminal known to support OSC 8 hyperlinks. Terminals
+/// that don't would print the escape codes, so unknown ones get plain URLs.
+fn hyperlinks_supported() -> bool {
+    use std::io::IsTerminal;
+
+    if !std::io::stdout().is_terminal() || std::env::var_os("NO_COLOR").is_some() {
+        return false;
+    }
+    if let Ok(program) = std::env::var("TERM_PROGRAM") {
+        if ["iTerm.app", "WezTerm", "vscode", "ghostty", "Hyper"].contains(&program.as_str()) {
+            return true;
+        }
+    }
+    let vte_version = std::env::var("VTE_VERSION").ok().and_then(|v| v.parse::<u32>().ok());
+    vte_version.is_some_and(|v| v >= 5000)
+        || std::env::var_os("KITTY_WINDOW_ID").is_some()
+        || std::env::var_os("WT_SESSION").is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_ansi() {
+        assert_eq!(strip_ansi("\x1b[1;31merror\x1b[0m: \x1b[2Kfailed"), "error: failed");
+        assert_eq!(strip_ansi("\x1b]8;;https://a.example\x1b\\link\x1b]8;;\x1b\\ \x1b]0;title\x07done"), "link done");
+        assert_eq!(strip_ansi("plain [text]"), "plain [text]");
+    }
+
+    #[test]
+    fn test_lone_asterisks_are_text() {
+        colored::control::set_override(false);
+        assert_eq!(format_markdown("**\n***\n**bold**\n"), "**\n***\nbold\n");
+    }
+
+    #[test]
+    fn test_reference_definitions_become_footnotes() {
+        colored::control::set_override(false);
+        let output = format_markdown("Rust 1.80 is out [1].\n\n[1]: https://blog.rust-lang.org \"Rust Blog\"\n[2]: https://example.com\n");
+        assert_eq!(
+            output,
+            "Rust 1.80 is out [1].\n\n[1] Rust Blog - https://blog.rust-lang.org\n[2] https://example.com\n"
+        );
+    }
+
+    #[test]
+    fn test_hyperlink_reference() {
+        colored::control::set_override(false);
+        assert_eq!(
+            format_reference("1", "https://a.example", Some("A"), true),
+            "[1] \x1b]8;;https://a.example\x1b\\A\x1b]8;;\x1b\\"
+        );
+    }
+}
@@ -0,0 +1,227 @@
+//! Translated help, errors, and status lines. Messages live in Fluent files
+//! under `locales/`, one per language, compiled into the binary; English is
+//! the fallback for any message a translation leaves out. Help text stays in
+//! the clap doc comments in English, and a translation only overrides it
+//! (`help-q--<arg>`, `help-q-<subcommand>`). The language is `[settings]
+//! language` when set, otherwise the first of LC_ALL, LC_MESSAGES, and LANG.
+
+use std::sync::OnceLock;
+
+use clap::{Arg, ArgAction, Command};
+use fluent_bundle::concurrent::FluentBundle;
+use fluent_bundle::{FluentArgs, FluentResource};
+use unic_langid::LanguageIdentifier;
+
+const ENGLISH_MESSAGES: &str = include_str!("../../locales/en.ftl");
+const JAPANESE_MESSAGES: &str = include_str!("../../locales/ja.ftl");
+
+static LANGUAGE: OnceLock<Language> = OnceLock::new();
+static ENGLISH: OnceLock<FluentBundle<FluentResource>> = OnceLock::new();
+static JAPANESE: OnceLock<FluentBundle<FluentResource>> = OnceLock::new();
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Language {
+    #[default]
+    English,
+    Japanese,
+}
+
+impl Language {
+    /// Codes accepted by `[settings] language`
+    pub const CODES: &'static [&'static str] = &["en", "ja"];
+
+    /// The language for a code like `ja` or a locale like `ja_JP.UTF-8`;
+    /// `None` for languages q has no messages in
+    pub fn parse(locale: &str) -> Option<Self> {
+        let code = locale.split(['_', '-', '.', '@']).next().unwrap_or_default();
+        match code.to_ascii_lowercase().as_str() {
+            "en" | "c" | "posix" => Some(Language::English),
+            "ja" => Some(Language::Japanese),
+            _ => None,
+        }
+    }
+
+    /// The configured language, else the one the locale environment names
+    pub fn detect(configured: Option<&str>) -> Self {
+        configured.and_then(Self::parse)
+            .or_else(|| {
+                ["LC_ALL", "LC_MESSAGES", "LANG"].iter()
+                    .filter_map(|var| std::env::var(var).ok())
+                    .find(|value| !value.is_empty())
+                    .and_then(|value| Self::parse(&value))
+            })
+            .unwrap_or_default()
+    }
+
+    fn bundle(self) -> &'static FluentBundle<FluentResource> {
+        match self {
+            Language::English => ENGLISH.get_or_init(|| bundle("en", ENGLISH_MESSAGES)),
+            Language::Japanese => JAPANESE.get_or_init(|| bundle("ja", JAPANESE_MESSAGES)),
+        }
+    }
+}
+
+fn bundle(code: &str, messages: &str) -> FluentBundle<FluentResource> {
+    let language: LanguageIdentifier = code.parse().expect("language code");
+    let mut bundle = FluentBundle::new_concurrent(vec![language]);
+    // Isolation marks around arguments show up as stray characters in terminals
+    bundle.set_use_isolating(false);
+    let resource = FluentResource::try_new(messages.to_string()).expect("locale files parse");
+    bundle.add_resource(resource).expect("locale files have no duplicate messages");
+    bundle
+}
+
+/// Use `language` for the rest of the run. Only the first call takes effect;
+/// until then messages are in English.
+pub fn init(language: Language) {
+    let _ = LANGUAGE.set(language);
+}
+
+pub fn language() -> Language {
+    LANGUAGE.get().copied().unwrap_or_default()
+}
+
+/// The message `id` in `language`, if that language has it
+fn lookup(language: Language, id: &str, args: Option<&FluentArgs>) -> Option<String> {
+    let bundle = language.bundle();
+    let pattern = bundle.get_message(id)?.value()?;
+    let mut errors = Vec::new();
+    Some(bundle.format_pattern(pattern, args, &mut errors).into_owned())
+}
+
+/// The message `id` with `args` filled in, in the current language or else English
+pub fn message_with(id: &str, args: &[(&str, &str)]) -> String {
+    let mut fluent_args = FluentArgs::new();
+    for (name, value) in args {
+        fluent_args.set(*name, value.to_string());
+    }
+    lookup(language(), id, Some(&fluent_args))
+        .or_else(|| lookup(Language::English, id, Some(&fluent_args)))
+        .unwrap_or_else(|| id.to_string())
+}
+
+/// The message `id` in the current language or else English
+pub fn message(id: &str) -> String {
+    message_with(id, &[])
+}
+
+/// `cmd` with its help in the current language: every about and argument
+/// help the translation has, and the headings and built-in flags
+pub fn localize_command(cmd: Command) -> Command {
+    match language() {
+        Language::English => cmd,
+        language => localize(cmd, "q", language, true),
+    }
+}
+
+fn localize(mut cmd: Command, path: &str, language: Language, top: bool) -> Command {
+    let text = |id: &str| lookup(language, id, None);
+    if let Some(about) = text(&format!("help-{}", path)) {
+        cmd = cmd.about(about);
+    }
+    let ids: Vec<String> = cmd.get_arguments().map(|arg| arg.get_id().to_string()).collect();
+    for id in ids {
+        if let Some(help) = text(&format!("help-{}--{}", path, id)) {
+            cmd = cmd.mut_arg(&id, |arg| arg.help(help));
+        }
+    }
+
+    // clap's own flags and headings, which its templates can't translate
+    let heading = |id: &str| text(id).unwrap_or_default();
+    cmd = cmd.disable_help_flag(true)
+        .arg(Arg::new("help").short('h').long("help").action(ArgAction::Help).help(heading("help-flag")));
+    if top {
+        cmd = cmd.disable_version_flag(true)
+            .arg(Arg::new("version").short('V').long("version").action(ArgAction::Version).help(heading("version-flag")));
+    }
+    let mut template = format!("{{about-with-newline}}\n{} {{usage}}\n", heading("help-usage"));
+    if cmd.get_positionals().next().is_some() {
+        template.push_str(&format!("\n{}\n{{positionals}}\n", heading("help-arguments")));
+    }
+    template.push_str(&format!("\n{}\n{{options}}\n", heading("help-options")));
+    if cmd.has_subcommands() {
+        template.push_str(&format!("\n{}\n{{subcommands}}\n", heading("help-commands")));
+    }
+    cmd = cmd.help_template(template);
+
+    let names: Vec<String> = cmd.get_subcommands().map(|sub| sub.get_name().to_string()).collect();
+    for name in names {
+        let sub_path = format!("{}-{}", path, name);
+        cmd = cmd.mut_subcommand(&name, |sub| localize(sub, &sub_path, language, false));
+    }
+    cmd
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::args::Cli;
+    use clap::CommandFactory;
+    use std::collections::BTreeSet;
+
+    fn ids(messages: &str) -> BTreeSet<String> {
+        messages.lines()
+            .filter(|line| line.starts_with(|c: char| c.is_ascii_alphabetic()))
+            .filter_map(|line| Some(line.split_once(" =")?.0.to_string()))
+            .collect()
+    }
+
+    /// Every help key a translation may use, from the real command tree
+    fn help_ids(cmd: &Command, path: &str, ids: &mut BTreeSet<String>) {
+        ids.insert(format!("help-{}", path));
+        for arg in cmd.get_arguments() {
+            ids.insert(format!("help-{}--{}", path, arg.get_id()));
+        }
+        for sub in cmd.get_subcommands() {
+            help_ids(sub, &format!("{}-{}", path, sub.get_name()), ids);
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Language::parse("ja_JP.UTF-8"), Some(Language::Japanese));
+        assert_eq!(Language::parse("en-GB"), Some(Language::English));
+        assert_eq!(Language::parse("C.UTF-8"), Some(Language::English));
+        assert_eq!(Language::parse("fr_FR"), None);
+        assert_eq!(Language::detect(Some("ja")), Language::Japanese);
+    }
+
+    #[test]
+    fn test_messages() {
+        assert_eq!(message("no-prompt"), "No prompt provided. Use --help for usage information.");
+        assert_eq!(
+            lookup(Language::Japanese, "status-provider", Some(&FluentArgs::from_iter([("provider", "openai"), ("model", "gpt-4o")]))),
+            Some("プロバイダー: openai、モデル: gpt-4o".to_string())
+        );
+        assert_eq!(message("no-such-message"), "no-such-message");
+    }
+
+    #[test]
+    fn test_translations_match_english_and_the_commands() {
+        let english = ids(ENGLISH_MESSAGES);
+        let mut known = BTreeSet::new();
+        help_ids(&Cli::command(), "q", &mut known);
+        for id in ids(JAPANESE_MESSAGES) {
+            assert!(
+                english.contains(&id) || known.contains(&id),
+                "ja.ftl has '{}', which is neither an English message nor a command or argument",
+                id
+            );
+        }
+        for id in &english {
+            assert!(lookup(Language::Japanese, id, None).is_some(), "ja.ftl is missing '{}'", id);
+        }
+    }
+
+    #[test]
+    fn test_localized_help() {
+        let mut cmd = localize(Cli::command(), "q", Language::Japanese, true);
+        let help = cmd.render_help().to_string();
+        assert!(help.contains("使い方:"), "{}", help);
+        assert!(help.contains("オプション:"));
+        assert!(help.contains("LLM に質問する CLI ツール"));
+
+        let mut watch = cmd.find_subcommand_mut("watch").unwrap().clone();
+        assert!(watch.render_help().to_string().contains("監視するファイル"));
+    }
+}
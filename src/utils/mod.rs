@@ -1,4 +1,7 @@
 pub mod errors;
 pub mod format;
+pub mod i18n;
+pub mod output;
+pub mod safety;
 
 pub use format::format_markdown;
@@ -0,0 +1,469 @@
+//! Where q's stdout goes. Handlers and the streaming pipeline write through
+//! the `OutputWriter` chosen once from the flags (`--json`, `--porcelain`,
+//! `-o` with a subcommand, `--quiet`) instead of printing directly, so every
+//! output mode lives here and output can be captured in tests. Status lines
+//! and prompts still go to stderr, and prompts only when the writer allows
+//! them (`can_prompt`).
+
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{self, IsTerminal, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+
+use serde::Serialize;
+
+use super::errors::{hint, QError};
+use super::format::{format_markdown, strip_ansi};
+
+/// The `--porcelain` format's version, the first record of every run. It
+/// changes only when a record's meaning does; new kinds of records may be
+/// added without a change, so readers should skip keys they don't know.
+pub const PORCELAIN_VERSION: u32 = 1;
+
+static WRITER: OnceLock<Box<dyn OutputWriter>> = OnceLock::new();
+
+pub trait OutputWriter: Send + Sync {
+    /// A model's complete answer, as Markdown
+    fn answer(&self, markdown: &str);
+
+    /// Any other output, as is: listings, reports, generated commands
+    fn text(&self, text: &str);
+
+    /// Part of an answer as it streams in
+    fn chunk(&self, text: &str);
+
+    /// The streamed answer is complete
+    fn end_answer(&self) {}
+
+    /// Everything has been written
+    fn finish(&self) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// The run failed with `error`, which is also shown on stderr
+    fn error(&self, _error: &QError) {}
+
+    /// Whether q may stop to ask something on the terminal
+    fn allows_prompts(&self) -> bool {
+        true
+    }
+}
+
+/// Use `writer` for the rest of the run. Only the first call takes effect.
+pub fn init(writer: Box<dyn OutputWriter>) {
+    let _ = WRITER.set(writer);
+}
+
+/// The writer chosen by `init`, or the terminal
+fn writer() -> &'static dyn OutputWriter {
+    WRITER.get_or_init(|| Box::new(Terminal::default())).as_ref()
+}
+
+/// Write a model's answer
+pub fn answer(markdown: &str) {
+    writer().answer(markdown);
+}
+
+/// Write `text` and a newline
+pub fn line(text: impl Display) {
+    writer().text(&format!("{}\n", text));
+}
+
+/// Write `text` as is
+pub fn text(text: impl Display) {
+    writer().text(&text.to_string());
+}
+
+/// Write part of a streamed answer
+pub fn chunk(text: &str) {
+    writer().chunk(text);
+}
+
+/// End a streamed answer
+pub fn end_answer() {
+    writer().end_answer();
+}
+
+/// Finish writing, e.g. print the collected JSON
+pub fn finish() -> io::Result<()> {
+    writer().finish()
+}
+
+/// Note that the run failed
+pub fn error(error: &QError) {
+    writer().error(error);
+}
+
+/// Whether q can ask a question and read the reply: stdin is a terminal and
+/// the output mode isn't one for scripts (`--porcelain`)
+pub fn can_prompt() -> bool {
+    writer().allows_prompts() && io::stdin().is_terminal()
+}
+
+/// Colored, rendered output for a terminal, or for a pipe when colors are off
+#[derive(Default)]
+pub struct Terminal {
+    /// A streamed answer hasn't ended its last line yet
+    mid_line: AtomicBool,
+}
+
+impl OutputWriter for Terminal {
+    fn answer(&self, markdown: &str) {
+        println!("{}", format_markdown(markdown));
+    }
+
+    fn text(&self, text: &str) {
+        print!("{}", text);
+    }
+
+    fn chunk(&self, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        let mut stdout = io::stdout();
+        let _ = stdout.write_all(text.as_bytes());
+        let _ = stdout.flush();
+        self.mid_line.store(!text.ends_with('\n'), Ordering::Relaxed);
+    }
+
+    fn end_answer(&self) {
+        if self.mid_line.swap(false, Ordering::Relaxed) {
+            println!();
+        }
+    }
+}
+
+/// Plain text without colors, with answers as their Markdown source: what
+/// `-o FILE` writes for subcommands, and what tests capture
+pub struct Plain<W: Write + Send> {
+    out: Mutex<W>,
+    mid_line: AtomicBool,
+}
+
+impl<W: Write + Send> Plain<W> {
+    pub fn new(out: W) -> Self {
+        Self { out: Mutex::new(out), mid_line: AtomicBool::new(false) }
+    }
+
+    fn write(&self, text: &str) {
+        if let Ok(mut out) = self.out.lock() {
+            let _ = out.write_all(strip_ansi(text).as_bytes());
+        }
+    }
+}
+
+impl Plain<File> {
+    /// Output written to `path`
+    pub fn file(path: &Path) -> io::Result<Self> {
+        Ok(Self::new(File::create(path)?))
+    }
+}
+
+impl Plain<Vec<u8>> {
+    /// Everything written so far
+    pub fn contents(&self) -> String {
+        self.out.lock().map(|out| String::from_utf8_lossy(&out).into_owned()).unwrap_or_default()
+    }
+}
+
+impl<W: Write + Send> OutputWriter for Plain<W> {
+    fn answer(&self, markdown: &str) {
+        self.write(&format!("{}\n", markdown.trim_end()));
+    }
+
+    fn text(&self, text: &str) {
+        self.write(text);
+    }
+
+    fn chunk(&self, text: &str) {
+        if !text.is_empty() {
+            self.write(text);
+            self.mid_line.store(!text.ends_with('\n'), Ordering::Relaxed);
+        }
+    }
+
+    fn end_answer(&self) {
+        if self.mid_line.swap(false, Ordering::Relaxed) {
+            self.write("\n");
+        }
+    }
+
+    fn finish(&self) -> io::Result<()> {
+        self.out.lock().map_err(|_| io::Error::other("output lock poisoned"))?.flush()
+    }
+}
+
+/// Everything collected into one JSON object printed at the end (`--json`):
+/// `{"answers": [...], "output": "..."}`, each left out when empty
+#[derive(Default)]
+pub struct Json {
+    collected: Mutex<Collected>,
+}
+
+#[derive(Default, Serialize)]
+struct Collected {
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    answers: Vec<String>,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    output: String,
+    /// The answer being streamed
+    #[serde(skip)]
+    streaming: Option<String>,
+}
+
+impl Collected {
+    fn end_answer(&mut self) {
+        if let Some(answer) = self.streaming.take() {
+            self.answers.push(answer.trim_end().to_string());
+        }
+    }
+}
+
+impl Json {
+    fn to_json(&self) -> String {
+        let mut collected = self.collected.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        collected.end_answer();
+        serde_json::to_string_pretty(&*collected).unwrap_or_default()
+    }
+
+    fn with(&self, f: impl FnOnce(&mut Collected)) {
+        if let Ok(mut collected) = self.collected.lock() {
+            f(&mut collected);
+        }
+    }
+}
+
+impl OutputWriter for Json {
+    fn answer(&self, markdown: &str) {
+        self.with(|collected| {
+            collected.end_answer();
+            collected.answers.push(markdown.trim_end().to_string());
+        });
+    }
+
+    fn text(&self, text: &str) {
+        self.with(|collected| collected.output.push_str(&strip_ansi(text)));
+    }
+
+    fn chunk(&self, text: &str) {
+        self.with(|collected| collected.streaming.get_or_insert_with(String::new).push_str(text));
+    }
+
+    fn end_answer(&self) {
+        self.with(Collected::end_answer);
+    }
+
+    fn finish(&self) -> io::Result<()> {
+        println!("{}", self.to_json());
+        Ok(())
+    }
+}
+
+/// Stable records for scripts and launchers (`--porcelain`), one per line and
+/// flushed as soon as it's complete: `key: value`, or with `json` a JSON
+/// object `{"key": "value"}`. The records:
+///
+/// - `version`: `PORCELAIN_VERSION`, always first
+/// - `answer`: a line of an answer's Markdown; `answer-end` (the answer's
+///   number) follows each answer
+/// - `output`: a line of any other output, without colors
+/// - `error` and `hint`: why the run failed and what to try
+/// - `status`: `ok` or `error`, always last
+pub struct Porcelain<W: Write + Send> {
+    state: Mutex<PorcelainState<W>>,
+    json: bool,
+}
+
+struct PorcelainState<W> {
+    out: W,
+    /// The unfinished last line of the answer being streamed
+    answer: Option<String>,
+    /// The unfinished last line of other output
+    output: String,
+    answers: usize,
+}
+
+impl<W: Write + Send> Porcelain<W> {
+    pub fn new(out: W, json: bool) -> Self {
+        let state = PorcelainState { out, answer: None, output: String::new(), answers: 0 };
+        let porcelain = Self { state: Mutex::new(state), json };
+        porcelain.with(|state| porcelain.record(state, "version", &PORCELAIN_VERSION.to_string()));
+        porcelain
+    }
+
+    fn with(&self, f: impl FnOnce(&mut PorcelainState<W>)) {
+        let mut state = self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        f(&mut state);
+    }
+
+    fn record(&self, state: &mut PorcelainState<W>, key: &str, value: &str) {
+        let line = if self.json {
+            serde_json::json!({ key: value }).to_string()
+        } else if value.is_empty() {
+            format!("{}:", key)
+        } else {
+            format!("{}: {}", key, value)
+        };
+        let _ = writeln!(state.out, "{}", line);
+        let _ = state.out.flush();
+    }
+
+    /// A record for each complete line at the start of `pending`, leaving the rest
+    fn lines(&self, state: &mut PorcelainState<W>, key: &str, pending: &mut String) {
+        while let Some(end) = pending.find('\n') {
+            let line: String = pending.drain(..=end).collect();
+            self.record(state, key, line.trim_end_matches(['\n', '\r']));
+        }
+    }
+
+    fn close_answer(&self, state: &mut PorcelainState<W>) {
+        if let Some(mut rest) = state.answer.take() {
+            self.lines(state, "answer", &mut rest);
+            if !rest.is_empty() {
+                self.record(state, "answer", &rest);
+            }
+            state.answers += 1;
+            self.record(state, "answer-end", &state.answers.to_string());
+        }
+    }
+
+    /// Records for what's still unfinished, before the last ones
+    fn flush(&self, state: &mut PorcelainState<W>) {
+        self.close_answer(state);
+        let rest = std::mem::take(&mut state.output);
+        if !rest.is_empty() {
+            self.record(state, "output", &rest);
+        }
+    }
+}
+
+impl Porcelain<Vec<u8>> {
+    /// Everything written so far
+    pub fn contents(&self) -> String {
+        self.state.lock().map(|state| String::from_utf8_lossy(&state.out).into_owned()).unwrap_or_default()
+    }
+}
+
+impl<W: Write + Send> OutputWriter for Porcelain<W> {
+    fn answer(&self, markdown: &str) {
+        self.with(|state| {
+            self.close_answer(state);
+            state.answer = Some(format!("{}\n", markdown.trim_end()));
+            self.close_answer(state);
+        });
+    }
+
+    fn text(&self, text: &str) {
+        self.with(|state| {
+            let mut pending = std::mem::take(&mut state.output);
+            pending.push_str(&strip_ansi(text));
+            self.lines(state, "output", &mut pending);
+            state.output = pending;
+        });
+    }
+
+    fn chunk(&self, text: &str) {
+        self.with(|state| {
+            let mut pending = state.answer.take().unwrap_or_default();
+            pending.push_str(text);
+            self.lines(state, "answer", &mut pending);
+            state.answer = Some(pending);
+        });
+    }
+
+    fn end_answer(&self) {
+        self.with(|state| self.close_answer(state));
+    }
+
+    fn finish(&self) -> io::Result<()> {
+        self.with(|state| {
+            self.flush(state);
+            self.record(state, "status", "ok");
+        });
+        Ok(())
+    }
+
+    fn error(&self, error: &QError) {
+        self.with(|state| {
+            self.flush(state);
+            self.record(state, "error", &error.to_string().replace('\n', " "));
+            if let Some(hint) = hint(error) {
+                self.record(state, "hint", &hint);
+            }
+            self.record(state, "status", "error");
+        });
+    }
+
+    fn allows_prompts(&self) -> bool {
+        false
+    }
+}
+
+/// Nothing on stdout (`--quiet`)
+pub struct Quiet;
+
+impl OutputWriter for Quiet {
+    fn answer(&self, _markdown: &str) {}
+
+    fn text(&self, _text: &str) {}
+
+    fn chunk(&self, _text: &str) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use colored::*;
+
+    #[test]
+    fn test_plain_strips_colors_and_ends_streamed_lines() {
+        let plain = Plain::new(Vec::new());
+        plain.text(&format!("{}\n", "3 keys".green()));
+        plain.chunk("Use ");
+        plain.chunk("tar");
+        plain.end_answer();
+        plain.answer("**Done**\n\n");
+        assert_eq!(plain.contents(), "3 keys\nUse tar\n**Done**\n");
+    }
+
+    #[test]
+    fn test_json_collects_answers_and_output() {
+        let json = Json::default();
+        json.text("provider: openai\n");
+        json.chunk("Use ");
+        json.chunk("tar -xzf\n");
+        json.end_answer();
+        json.answer("Second answer");
+        assert_eq!(
+            json.to_json(),
+            "{\n  \"answers\": [\n    \"Use tar -xzf\",\n    \"Second answer\"\n  ],\n  \"output\": \"provider: openai\\n\"\n}"
+        );
+        assert_eq!(Json::default().to_json(), "{}");
+    }
+
+    #[test]
+    fn test_porcelain_records() {
+        let porcelain = Porcelain::new(Vec::new(), false);
+        porcelain.text(&format!("{}\npartial", "3 keys".green()));
+        porcelain.chunk("Use ");
+        porcelain.chunk("tar\n\n-x");
+        porcelain.end_answer();
+        porcelain.answer("Second\n");
+        porcelain.finish().unwrap();
+        assert_eq!(
+            porcelain.contents(),
+            "version: 1\noutput: 3 keys\nanswer: Use tar\nanswer:\nanswer: -x\nanswer-end: 1\n\
+             answer: Second\nanswer-end: 2\noutput: partial\nstatus: ok\n"
+        );
+        assert!(!porcelain.allows_prompts());
+
+        let porcelain = Porcelain::new(Vec::new(), true);
+        porcelain.error(&QError::Usage("No prompt given".to_string()));
+        assert_eq!(
+            porcelain.contents(),
+            "{\"version\":\"1\"}\n{\"error\":\"Usage error: No prompt given\"}\n{\"status\":\"error\"}\n"
+        );
+    }
+}
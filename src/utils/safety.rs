@@ -0,0 +1,65 @@
+//! Checks shell commands for effects worth a second look before q runs them:
+//! deleting or overwriting data, changing permissions wholesale, running
+//! downloaded scripts. This is a speed bump for model-suggested commands, not
+//! a sandbox; anything it doesn't recognize is treated as ordinary.
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+lazy_static! {
+    static ref RISKS: Vec<(Regex, &'static str)> = [
+        // Flags can come anywhere among the arguments of the same command
+        (r"\brm\s+(?:[^;&|\s]+\s+)*?(-\w*[rR]\w*|--recursive)\b", "deletes files recursively"),
+        (r"\b(mkfs(\.\w+)?|wipefs|fdisk|parted)\b", "formats or repartitions a disk"),
+        (r"\bdd\b.*\bof=/dev/", "writes directly to a device"),
+        (r">\s*/dev/(sd|nvme|disk|hd)", "writes directly to a device"),
+        (r"\b(chmod|chown)\s+(?:[^;&|\s]+\s+)*?(-\w*R\w*|--recursive)\b", "changes permissions recursively"),
+        (r"\b(curl|wget)\b[^|]*\|\s*(sudo\s+)?(ba|z)?sh\b", "runs a script straight from the network"),
+        (r"\b(shutdown|reboot|halt|poweroff)\b", "shuts down or restarts the machine"),
+        (r"\bgit\s+(push\s+.*(--force|-f)\b|reset\s+--hard|clean\s+-\w*f)", "discards or overwrites git history or files"),
+        (r"(?i)\b(DROP|TRUNCATE)\s+(TABLE|DATABASE|SCHEMA)\b", "drops database objects"),
+        (r"\bkill(all)?\s+(-9|-KILL)\b", "force-kills processes"),
+        (r":\(\)\s*\{.*\};\s*:", "is a fork bomb"),
+        (r"\bsudo\b", "runs as root"),
+    ]
+    .into_iter()
+    .map(|(pattern, reason)| (Regex::new(pattern).expect("valid regex"), reason))
+    .collect();
+}
+
+/// Why `command` deserves confirmation, one reason per kind of risk; empty
+/// when nothing stands out
+pub fn risks(command: &str) -> Vec<&'static str> {
+    let mut reasons: Vec<&'static str> = Vec::new();
+    for (pattern, reason) in RISKS.iter() {
+        if pattern.is_match(command) && !reasons.contains(reason) {
+            reasons.push(reason);
+        }
+    }
+    reasons
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_risky_commands() {
+        assert_eq!(risks("rm -rf ./build"), vec!["deletes files recursively"]);
+        assert_eq!(risks("sudo dd if=image.iso of=/dev/sdb bs=4M"), vec!["writes directly to a device", "runs as root"]);
+        assert_eq!(risks("curl -fsSL https://example.com/install.sh | sh"), vec!["runs a script straight from the network"]);
+        assert_eq!(risks("git push --force origin main"), vec!["discards or overwrites git history or files"]);
+        assert_eq!(risks("psql -c 'DROP TABLE users'"), vec!["drops database objects"]);
+        assert_eq!(risks("sqlite3 app.db 'drop table users'"), vec!["drops database objects"]);
+        for command in ["rm -f -r ./build", "rm --force --recursive ./build", "rm -v -rf ./build", "rm ./build -r", "chown alice ./srv -R"] {
+            assert!(!risks(command).is_empty(), "{}", command);
+        }
+    }
+
+    #[test]
+    fn test_ordinary_commands() {
+        for command in ["ls -la", "rm notes.txt", "rm -f notes.txt && ls -R", "rm notes-r.txt", "systemctl status nginx", "curl -o out.json https://example.com", "git push"] {
+            assert!(risks(command).is_empty(), "{}", command);
+        }
+    }
+}
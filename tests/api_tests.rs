@@ -26,7 +26,7 @@ async fn test_openai_query() {
     let client = OpenAIClient::builder("test_key".to_string())
         .with_api_url(format!("{}/v1/chat/completions", mock_server.uri()))
         .with_config(ModelConfig::default())
-        .build();
+        .build().unwrap();
 
     let result = client.send_query("test prompt").await;
     assert!(result.is_ok(), "Query failed: {}", result.unwrap_err());
@@ -57,7 +57,7 @@ async fn test_openai_streaming() {
     let client = Arc::new(OpenAIClient::builder("test_key".to_string())
         .with_api_url(format!("{}/v1/chat/completions", mock_server.uri()))
         .with_config(ModelConfig::default())
-        .build());
+        .build().unwrap());
 
     let result = client.send_streaming_query("test prompt").await;
     assert!(result.is_ok(), "Streaming query failed to start");
@@ -96,7 +96,7 @@ async fn test_invalid_key() {
     let client = OpenAIClient::builder("invalid_key".to_string())
         .with_api_url(format!("{}/v1/chat/completions", mock_server.uri()))
         .with_config(ModelConfig::default())
-        .build();
+        .build().unwrap();
 
     let result = client.send_query("test prompt").await;
     assert!(matches!(result, Err(ApiError::InvalidKey)));
@@ -119,7 +119,7 @@ async fn test_rate_limit() {
     let client = OpenAIClient::builder("test_key".to_string())
         .with_api_url(format!("{}/v1/chat/completions", mock_server.uri()))
         .with_config(ModelConfig::default())
-        .build();
+        .build().unwrap();
 
     let result = client.send_query("test prompt").await;
     assert!(matches!(result, Err(ApiError::RateLimit)));
@@ -147,7 +147,7 @@ async fn test_streaming_error() {
     let client = Arc::new(OpenAIClient::builder("test_key".to_string())
         .with_api_url(format!("{}/v1/chat/completions", mock_server.uri()))
         .with_config(ModelConfig::default())
-        .build());
+        .build().unwrap());
 
     let result = client.send_streaming_query("test prompt").await;
     assert!(result.is_ok(), "Streaming query failed to start");
@@ -1,9 +1,29 @@
 use assert_cmd::Command;
 use predicates::prelude::*;
+use tempfile::TempDir;
+
+fn isolated_command(config_home: &TempDir) -> Command {
+    let mut cmd = Command::cargo_bin("q").unwrap();
+    cmd.env("XDG_CONFIG_HOME", config_home.path())
+        .env("XDG_DATA_HOME", config_home.path().join("data"))
+        .env("XDG_CACHE_HOME", config_home.path().join("cache"))
+        .env("XDG_STATE_HOME", config_home.path().join("state"))
+        .env_remove("OPENAI_API_KEY")
+        .env_remove("GEMINI_API_KEY")
+        .env_remove("OPENROUTER_API_KEY")
+        .env_remove("GROQ_API_KEY")
+        .env_remove("MISTRAL_API_KEY")
+        // Messages in English whatever the machine's locale
+        .env("LANG", "C")
+        .env_remove("LC_ALL")
+        .env_remove("LC_MESSAGES");
+    cmd
+}
 
 #[test]
 fn test_help() {
-    let mut cmd = Command::cargo_bin("q").unwrap();
+    let config_home = TempDir::new().unwrap();
+    let mut cmd = isolated_command(&config_home);
     cmd.arg("--help")
         .assert()
         .success()
@@ -11,6 +31,27 @@ fn test_help() {
         .stdout(predicate::str::contains("Usage:"));
 }
 
+#[test]
+fn test_japanese_help_and_errors() {
+    let config_home = TempDir::new().unwrap();
+    let mut cmd = isolated_command(&config_home);
+    cmd.env("LANG", "ja_JP.UTF-8")
+        .arg("--help")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("使い方:"))
+        .stdout(predicate::str::contains("LLM に質問する CLI ツール"));
+
+    // [settings] language wins over LANG
+    std::fs::create_dir_all(config_home.path().join("q")).unwrap();
+    std::fs::write(config_home.path().join("q/config.toml"), "[settings]\nlanguage = \"ja\"\n").unwrap();
+    let mut cmd = isolated_command(&config_home);
+    cmd.args(["template", "show", "missing"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("エラー: 使い方のエラー: No template named 'missing'"));
+}
+
 #[test]
 fn test_version() {
     let mut cmd = Command::cargo_bin("q").unwrap();
@@ -31,8 +72,9 @@ fn test_invalid_command() {
 
 #[test]
 fn test_set_key_command() {
-    let mut cmd = Command::cargo_bin("q").unwrap();
-    cmd.args(["set-key", "openai", "sk-test1234567890abcdefghijklmnopqrstuvwxyz"])
+    let config_home = TempDir::new().unwrap();
+    let mut cmd = isolated_command(&config_home);
+    cmd.args(["set-key", "openai", "sk-test1234567890abcdefghijklmnopqrstuvwxyz", "--no-verify"])
         .assert()
         .success()
         .stdout(predicate::str::contains("API key for openai has been set successfully"));
@@ -40,9 +82,866 @@ fn test_set_key_command() {
 
 #[test]
 fn test_direct_prompt() {
-    let mut cmd = Command::cargo_bin("q").unwrap();
+    // Without a configured key the query is rejected before any network call
+    let config_home = TempDir::new().unwrap();
+    let mut cmd = isolated_command(&config_home);
     cmd.arg("test prompt")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("API key not found"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_self_update_check() {
+    use wiremock::matchers::method;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "tag_name": "v99.0.0",
+            "assets": []
+        })))
+        .mount(&server)
+        .await;
+
+    let temp_dir = TempDir::new().unwrap();
+    let mut cmd = isolated_command(&temp_dir);
+    cmd.env("Q_RELEASES_URL", server.uri())
+        .args(["self-update", "--check"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Update available"))
+        .stdout(predicate::str::contains("99.0.0"));
+}
+
+#[test]
+fn test_commands_list_without_update() {
+    let temp_dir = TempDir::new().unwrap();
+    // Updates stay out until releases publish signed bundles
+    isolated_command(&temp_dir)
+        .args(["commands", "update"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("unrecognized subcommand"));
+
+    let mut cmd = isolated_command(&temp_dir);
+    cmd.args(["commands", "list"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Built-in command database only"))
+        .stdout(predicate::str::contains("hyperfine"));
+}
+
+#[test]
+fn test_alias_add_run_remove() {
+    let temp_dir = TempDir::new().unwrap();
+    isolated_command(&temp_dir)
+        .args(["alias", "add", "bench", "--", "--cmd", "benchmark a command"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Saved alias bench: q --cmd 'benchmark a command'"));
+    isolated_command(&temp_dir)
+        .args(["alias", "list"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("bench  q --cmd 'benchmark a command'"));
+
+    // Arguments after the alias are added to its own
+    isolated_command(&temp_dir)
+        .args(["bench", "--why"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("hyperfine"))
+        .stdout(predicate::str::contains("Why:"));
+
+    isolated_command(&temp_dir)
+        .args(["alias", "add", "commit", "--", "--cmd"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("is a q subcommand"));
+    isolated_command(&temp_dir).args(["alias", "remove", "bench"]).assert().success();
+    isolated_command(&temp_dir)
+        .args(["alias", "remove", "bench"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Unknown alias 'bench'"));
+}
+
+#[test]
+fn test_audio_rejects_unknown_format() {
+    let temp_dir = TempDir::new().unwrap();
+    let note = temp_dir.path().join("note.txt");
+    std::fs::write(&note, "not audio").unwrap();
+
+    let mut cmd = isolated_command(&temp_dir);
+    cmd.env("OPENAI_API_KEY", "sk-test1234567890abcdefghijklmnopqrstuvwxyz")
+        .args(["-P", "openai", "--audio"])
+        .arg(&note)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Unsupported audio format"));
+}
+
+#[test]
+fn test_quiet_requires_speak() {
+    let mut cmd = Command::cargo_bin("q").unwrap();
+    cmd.args(["--quiet", "hello"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--speak"));
+}
+
+#[test]
+fn test_eval_checks_the_file() {
+    let config_home = TempDir::new().unwrap();
+    let eval_file = config_home.path().join("eval.toml");
+    std::fs::write(&eval_file, "[a]\nmodel = \"gpt-4o-mini\"\n[b]\nmodel = \"gpt-4o\"\n").unwrap();
+
+    isolated_command(&config_home)
+        .arg("eval")
+        .arg(&eval_file)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("has no [[case]] entries"));
+}
+
+#[test]
+fn test_feedback_needs_an_answer() {
+    let config_home = TempDir::new().unwrap();
+
+    isolated_command(&config_home)
+        .args(["feedback", "👍"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No answers in the history log to rate yet"));
+
+    isolated_command(&config_home)
+        .args(["feedback", "meh"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("'meh' isn't a rating"));
+
+    isolated_command(&config_home)
+        .args(["history", "stats"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("No queries in the history log yet"));
+}
+
+#[test]
+fn test_json_and_file_output() {
+    let config_home = TempDir::new().unwrap();
+
+    isolated_command(&config_home)
+        .args(["--json", "history", "stats"])
+        .assert()
+        .success()
+        .stdout("{\n  \"output\": \"No queries in the history log yet\\n\"\n}\n");
+
+    let path = config_home.path().join("stats.txt");
+    isolated_command(&config_home)
+        .args(["history", "stats", "-o"])
+        .arg(&path)
+        .assert()
+        .success()
+        .stdout("");
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "No queries in the history log yet\n");
+}
+
+#[test]
+fn test_watch_needs_a_file_and_rejects_json() {
+    let config_home = TempDir::new().unwrap();
+    isolated_command(&config_home)
+        .args(["watch", "review this file"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--file <FILE>"));
+    isolated_command(&config_home)
+        .args(["watch", "-F", "Cargo.toml", "review this file", "--json"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("drop --json"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_template_settings_and_overrides() {
+    use wiremock::matchers::{body_partial_json, body_string_contains, method};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(body_partial_json(serde_json::json!({
+            "model": "gpt-4o-mini",
+            "temperature": 0.5,
+            "max_tokens": 300
+        })))
+        .and(body_string_contains(r#"Translate into Japanese:\n\nGood morning"#))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "choices": [{"message": {"role": "assistant", "content": "おはようございます"}}]
+        })))
+        .mount(&server)
+        .await;
+
+    let config_home = TempDir::new().unwrap();
+    let templates = config_home.path().join("q").join("templates");
+    std::fs::create_dir_all(&templates).unwrap();
+    std::fs::write(
+        templates.join("translate-ja.md"),
+        "+++\nprovider = \"openai\"\nmodel = \"gpt-4o\"\ntemperature = 0.5\nmax_tokens = 300\n+++\nTranslate into Japanese:\n\n{input}\n",
+    ).unwrap();
+
+    // The model comes from the command line; the rest from the template
+    isolated_command(&config_home)
+        .env("OPENAI_API_KEY", "test-key")
+        .env("Q_OPENAI_API_URL", server.uri())
+        .args(["-t", "translate-ja", "-M", "gpt-4o-mini", "--no-follow-up", "--incognito", "Good morning"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("おはようございます"));
+
+    isolated_command(&config_home)
+        .args(["-t", "translate-jp", "Good morning"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("did you mean 'translate-ja'?"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_template_install_list_show() {
+    use sha2::{Digest, Sha256};
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    let text = "+++\ndescription = \"Review a change\"\n+++\nReview this:\n\n{input}\n";
+    let checksum: String = Sha256::digest(text.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect();
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/aa5a3"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "files": {"review.md": {"content": text, "truncated": false, "raw_url": "unused"}}
+        })))
+        .mount(&server)
+        .await;
+
+    let config_home = TempDir::new().unwrap();
+    let install = |sha256: &str| {
+        let mut cmd = isolated_command(&config_home);
+        cmd.env("Q_GISTS_URL", server.uri()).args(["template", "install", "gist:aa5a3"]);
+        if !sha256.is_empty() {
+            cmd.args(["--sha256", sha256]);
+        }
+        cmd.assert()
+    };
+    install("").failure().stderr(predicate::str::contains(format!("--sha256 {}", checksum)));
+    install(&"0".repeat(64)).failure().stderr(predicate::str::contains("Checksum mismatch"));
+    install(&checksum).success().stdout(predicate::str::contains("Installed template review"));
+    install(&checksum).failure().stderr(predicate::str::contains("already installed"));
+
+    isolated_command(&config_home)
+        .args(["template", "list"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("review  Review a change"));
+    isolated_command(&config_home)
+        .args(["template", "show", "review"])
+        .assert()
+        .success()
+        .stdout(predicate::str::diff(text));
+}
+
+#[test]
+fn test_stdin_as_prompt() {
+    let config_home = TempDir::new().unwrap();
+    // Piped text with no prompt is the prompt, so q gets as far as needing a key
+    isolated_command(&config_home)
+        .write_stdin("summarize: the build is green again\n")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("API key not found"));
+    isolated_command(&config_home)
+        .write_stdin("  \n")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No prompt provided"));
+    isolated_command(&config_home)
+        .arg("--no-stdin")
+        .write_stdin("summarize: the build is green again\n")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No prompt provided"));
+}
+
+#[test]
+fn test_prompt_file() {
+    let config_home = TempDir::new().unwrap();
+    isolated_command(&config_home)
+        .args(["--prompt-file", "-"])
+        .write_stdin("--force: what does it skip?\n")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("API key not found"));
+    isolated_command(&config_home)
+        .args(["--prompt-file", "prompt.txt", "another prompt"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+}
+
+#[test]
+fn test_prompt_after_separator() {
+    let config_home = TempDir::new().unwrap();
+    isolated_command(&config_home)
+        .args(["--", "-v means verbose, right?"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("API key not found"));
+    isolated_command(&config_home)
+        .arg("-")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("put the prompt after `--`"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_failing_provider_fails_fast() {
+    use wiremock::matchers::method;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(503).set_body_json(serde_json::json!({
+            "error": {"message": "The server is overloaded", "code": "overloaded"}
+        })))
+        .mount(&server)
+        .await;
+
+    let config_home = TempDir::new().unwrap();
+    let query = || {
+        let mut cmd = isolated_command(&config_home);
+        cmd.env("OPENAI_API_KEY", "test-key")
+            .env("Q_OPENAI_API_URL", server.uri())
+            .args(["-P", "openai", "--retries", "0", "--no-stdin", "--no-follow-up", "hello"]);
+        cmd
+    };
+    for _ in 0..2 {
+        query().assert().failure().stderr(predicate::str::contains("failed the last").not());
+    }
+    query()
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("openai failed the last 2 times"))
+        .stderr(predicate::str::contains("trying once without retries"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_map_reduce_reads_oversized_file_in_parts() {
+    use wiremock::matchers::{body_string_contains, method};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    let reply = |content: &str| {
+        ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "choices": [{"message": {"role": "assistant", "content": content}}]
+        }))
+    };
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(body_string_contains("was read in 4 parts"))
+        .respond_with(reply("The deploy stopped because the disk filled up."))
+        .with_priority(1)
+        .expect(2)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(body_string_contains("ERROR disk full"))
+        .respond_with(reply("Found: ERROR disk full"))
+        .with_priority(2)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .respond_with(reply("Nothing relevant."))
+        .with_priority(3)
+        .mount(&server)
+        .await;
+
+    let config_home = TempDir::new().unwrap();
+    let config_dir = config_home.path().join("q");
+    std::fs::create_dir_all(&config_dir).unwrap();
+    std::fs::write(config_dir.join("config.toml"), "[context]\nmax_bytes = 2048\n").unwrap();
+    let log = config_home.path().join("deploy.log");
+    let lines: String = (0..300)
+        .map(|i| if i == 250 { "ERROR disk full\n".to_string() } else { format!("INFO step {:03}\n", i) })
+        .collect();
+    std::fs::write(&log, lines).unwrap();
+
+    let query = || {
+        let mut cmd = isolated_command(&config_home);
+        cmd.env("OPENAI_API_KEY", "test-key")
+            .env("Q_OPENAI_API_URL", server.uri())
+            .args(["-P", "openai", "--map-reduce", "--no-stdin", "--no-follow-up", "-F"])
+            .arg(&log)
+            .arg("Why did the deploy stop?");
+        cmd
+    };
+    query()
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("the disk filled up"))
+        .stderr(predicate::str::contains("deploy.log in 4 parts, 4 at a time"));
+
+    // The notes on each part are kept, so asking again only combines them
+    query()
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("4 of 4 parts are unchanged since an earlier run"));
+    let requests = server.received_requests().await.unwrap();
+    assert_eq!(requests.len(), 4 + 1 + 1);
+}
+
+#[tokio::test]
+async fn test_porcelain_records_answers_and_errors() {
+    use wiremock::matchers::method;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "choices": [{"message": {"role": "assistant", "content": "Use **tar**:\n\n    tar -xzf a.tgz"}}]
+        })))
+        .mount(&server)
+        .await;
+
+    let config_home = TempDir::new().unwrap();
+    isolated_command(&config_home)
+        .env("OPENAI_API_KEY", "test-key")
+        .env("Q_OPENAI_API_URL", server.uri())
+        .args(["--porcelain", "-P", "openai", "--no-stdin", "how do I unpack a.tgz?"])
+        .assert()
+        .success()
+        .stdout("version: 1\nanswer: Use **tar**:\nanswer:\nanswer:     tar -xzf a.tgz\nanswer-end: 1\nstatus: ok\n");
+
+    // Failures are records too, after the message on stderr
+    isolated_command(&config_home)
+        .args(["--porcelain", "--json", "-P", "openai", "--no-stdin", "hi"])
+        .assert()
+        .failure()
+        .stdout(predicate::str::starts_with("{\"version\":\"1\"}\n{\"error\":"))
+        .stdout(predicate::str::ends_with("{\"status\":\"error\"}\n"));
+}
+
+#[tokio::test]
+async fn test_queue_holds_prompts_until_a_query_gets_through() {
+    use wiremock::matchers::method;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "choices": [{"message": {"role": "assistant", "content": "Answered."}}]
+        })))
+        .expect(3)
+        .mount(&server)
+        .await;
+
+    let config_home = TempDir::new().unwrap();
+    let config_dir = config_home.path().join("q");
+    std::fs::create_dir_all(&config_dir).unwrap();
+    std::fs::write(config_dir.join("config.toml"), "[settings]\nauto_flush_queue = true\n").unwrap();
+    for prompt in ["first question", "second question"] {
+        isolated_command(&config_home)
+            .args(["-P", "openai", "queue", "add", prompt])
+            .assert()
+            .success()
+            .stdout(predicate::str::starts_with("Queued"));
+    }
+
+    // Nothing listens on port 9, so the prompts stay queued
+    isolated_command(&config_home)
+        .env("OPENAI_API_KEY", "test-key")
+        .env("Q_OPENAI_API_URL", "http://127.0.0.1:9")
+        .args(["--retries", "0", "queue", "flush"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Still offline"))
+        .stderr(predicate::str::contains("2 prompts stay queued"));
+
+    // A query that gets through answers them too, keeping the answers for flush
+    isolated_command(&config_home)
+        .env("OPENAI_API_KEY", "test-key")
+        .env("Q_OPENAI_API_URL", server.uri())
+        .args(["-P", "openai", "--no-stdin", "hi"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("2 queued prompts were answered"));
+    isolated_command(&config_home)
+        .args(["queue", "list"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("first question  answered by openai/"));
+
+    isolated_command(&config_home)
+        .args(["queue", "flush"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("first question (openai/"))
+        .stdout(predicate::str::contains("second question (openai/"))
+        .stdout(predicate::str::contains("Answered."));
+    isolated_command(&config_home)
+        .args(["queue", "list"])
+        .assert()
+        .success()
+        .stdout("Nothing queued\n");
+}
+
+#[tokio::test]
+async fn test_queue_skips_refused_prompts() {
+    use wiremock::matchers::{body_string_contains, method};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(body_string_contains("no-such-model"))
+        .respond_with(ResponseTemplate::new(404).set_body_json(serde_json::json!({
+            "error": {"message": "The model `no-such-model` does not exist"}
+        })))
+        .with_priority(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "choices": [{"message": {"role": "assistant", "content": "Answered."}}]
+        })))
+        .mount(&server)
+        .await;
+
+    let config_home = TempDir::new().unwrap();
+    isolated_command(&config_home)
+        .args(["-P", "openai", "-M", "no-such-model", "queue", "add", "first question"])
+        .assert()
+        .success();
+    isolated_command(&config_home)
+        .args(["-P", "openai", "queue", "add", "second question"])
+        .assert()
+        .success();
+
+    // The refused prompt doesn't hold up the one after it
+    isolated_command(&config_home)
+        .env("OPENAI_API_KEY", "test-key")
+        .env("Q_OPENAI_API_URL", server.uri())
+        .args(["--retries", "0", "queue", "flush"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("second question (openai/"))
+        .stderr(predicate::str::contains("Couldn't send queued prompt 1"))
+        .stderr(predicate::str::contains("1 failed prompt stays queued"));
+    isolated_command(&config_home)
+        .args(["queue", "list"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("first question  failed:"));
+
+    isolated_command(&config_home)
+        .args(["queue", "remove", "2"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("There's no queued prompt 2"));
+    isolated_command(&config_home)
+        .args(["queue", "remove", "1"])
+        .assert()
+        .success()
+        .stdout("Removed first question\n");
+
+    isolated_command(&config_home)
+        .args(["queue", "add", "third question"])
+        .assert()
+        .success();
+    isolated_command(&config_home).args(["queue", "clear"]).assert().success();
+    isolated_command(&config_home)
+        .args(["queue", "list"])
+        .assert()
+        .success()
+        .stdout("Nothing queued\n");
+}
+
+#[tokio::test]
+async fn test_schedule_add_run_and_remove() {
+    use wiremock::matchers::method;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "choices": [{"message": {"role": "assistant", "content": "Try `git switch -`."}}]
+        })))
+        .expect(2)
+        .mount(&server)
+        .await;
+
+    let config_home = TempDir::new().unwrap();
+    let tips = config_home.path().join("tips.md");
+    isolated_command(&config_home)
+        .args(["-P", "openai", "schedule", "add", "a git tip", "--cron", "0 8 * * *", "--save"])
+        .arg(&tips)
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with("Saved schedule a-git-tip:"))
+        .stderr(predicate::str::contains("schedule run"));
+    isolated_command(&config_home)
+        .args(["schedule", "add", "a tip", "--cron", "every morning"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("is not a valid crontab schedule"));
+    // Only the prompt is saved, so flags it would lose are refused
+    isolated_command(&config_home)
+        .args(["--web", "schedule", "add", "today's news", "--cron", "0 8 * * *"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--web isn't saved with a schedule"));
+    isolated_command(&config_home)
+        .args(["schedule", "list"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("a-git-tip  0 8 * * *  next "));
+
+    // Just added, so nothing is due yet
+    isolated_command(&config_home)
+        .env("OPENAI_API_KEY", "test-key")
+        .env("Q_OPENAI_API_URL", server.uri())
+        .args(["schedule", "run"])
+        .assert()
+        .success()
+        .stdout("");
+
+    isolated_command(&config_home)
+        .env("OPENAI_API_KEY", "test-key")
+        .env("Q_OPENAI_API_URL", server.uri())
+        .args(["schedule", "run", "a-git-tip"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Try `git switch -`."));
+
+    // Last run two days ago: 08:00 has come since, so it's due
+    let two_days_ago = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs() - 2 * 24 * 60 * 60;
+    std::fs::write(
+        config_home.path().join("state/q/schedule-runs.json"),
+        serde_json::json!({"last": {"a-git-tip": two_days_ago}}).to_string(),
+    ).unwrap();
+    isolated_command(&config_home)
+        .env("OPENAI_API_KEY", "test-key")
+        .env("Q_OPENAI_API_URL", server.uri())
+        .args(["schedule", "run"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("a-git-tip ("));
+    let saved = std::fs::read_to_string(&tips).unwrap();
+    assert_eq!(saved.matches("## a-git-tip (").count(), 2);
+    assert!(saved.contains("Try `git switch -`."));
+
+    isolated_command(&config_home).args(["schedule", "remove", "a-git-tip"]).assert().success();
+    isolated_command(&config_home)
+        .args(["schedule", "list"])
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with("No schedules."));
+}
+
+#[tokio::test]
+async fn test_serve_answers_json_rpc_on_stdio() {
+    use wiremock::matchers::{body_string_contains, method};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(body_string_contains("fn main() {}"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "choices": [{"message": {"role": "assistant", "content": "It does nothing."}}]
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let config_home = TempDir::new().unwrap();
+    let requests = [
+        r#"{"jsonrpc": "2.0", "id": 1, "method": "query", "params": {"prompt": "What does this do?", "provider": "openai", "context": {"text": "fn main() {}"}}}"#,
+        r#"{"jsonrpc": "2.0", "id": 2, "method": "rewrite"}"#,
+        r#"{"jsonrpc": "2.0", "id": 3, "method": "shutdown"}"#,
+    ];
+    let output = isolated_command(&config_home)
+        .env("OPENAI_API_KEY", "test-key")
+        .env("Q_OPENAI_API_URL", server.uri())
+        .args(["serve", "--stdio"])
+        .write_stdin(requests.join("\n") + "\n")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    // Replies come as requests finish, so they're matched up by id
+    let replies: Vec<serde_json::Value> = String::from_utf8(output.stdout).unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    let reply = |id: i64| replies.iter().find(|reply| reply["id"] == id).unwrap().clone();
+    assert_eq!(reply(1)["result"]["answer"], "It does nothing.");
+    assert_eq!(reply(1)["result"]["provider"], "openai");
+    assert_eq!(reply(2)["error"]["code"], -32601);
+    assert_eq!(reply(3)["result"], serde_json::Value::Null);
+}
+
+#[tokio::test]
+async fn test_serve_http_answers_openai_clients_and_templates() {
+    use std::io::{BufRead, BufReader};
+    use std::process::Stdio;
+    use wiremock::matchers::{body_string_contains, method};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    let reply = |content: &str| {
+        ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "choices": [{"message": {"role": "assistant", "content": content}}]
+        }))
+    };
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(body_string_contains("Answer in French."))
+        .and(body_string_contains("gpt-4o-mini"))
+        .respond_with(reply("Bonjour !"))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(body_string_contains("Fix the spelling: teh"))
+        .respond_with(reply("the"))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let config_home = TempDir::new().unwrap();
+    let templates = config_home.path().join("q").join("templates");
+    std::fs::create_dir_all(&templates).unwrap();
+    std::fs::write(templates.join("spelling.md"), "+++\ndescription = \"Fix spelling\"\n+++\nFix the spelling: {input}\n").unwrap();
+
+    // Port 0 picks a free port, which q reports on stderr
+    let mut child = std::process::Command::new(assert_cmd::cargo::cargo_bin("q"))
+        .env("XDG_CONFIG_HOME", config_home.path())
+        .env("XDG_DATA_HOME", config_home.path().join("data"))
+        .env("XDG_CACHE_HOME", config_home.path().join("cache"))
+        .env("XDG_STATE_HOME", config_home.path().join("state"))
+        .env("LANG", "C")
+        .env("OPENAI_API_KEY", "test-key")
+        .env("Q_OPENAI_API_URL", server.uri())
+        .args(["-P", "openai", "serve", "--http", "127.0.0.1:0"])
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stderr = BufReader::new(child.stderr.take().unwrap());
+    let mut line = String::new();
+    while !line.contains("listening on ") {
+        line.clear();
+        assert!(stderr.read_line(&mut line).unwrap() > 0, "q serve exited");
+    }
+    let base = line.trim().rsplit(' ').next().unwrap().to_string();
+    let token = std::fs::read_to_string(config_home.path().join("state/q/serve-token")).unwrap();
+
+    // Nothing is answered without the token
+    let client = reqwest::Client::new();
+    let refused = client.post(format!("{}/q/context", base))
+        .body(r#"{"context": {"history": true}}"#)
+        .send().await.unwrap();
+    assert_eq!(refused.status(), 401);
+
+    let response: serde_json::Value = client.post(format!("{}/v1/chat/completions", base))
+        .bearer_auth(&token)
+        .json(&serde_json::json!({
+            "model": "openai/gpt-4o-mini",
+            "messages": [{"role": "system", "content": "Answer in French."}, {"role": "user", "content": "Hello"}]
+        }))
+        .send().await.unwrap()
+        .json().await.unwrap();
+    assert_eq!(response["choices"][0]["message"]["content"], "Bonjour !");
+    assert_eq!(response["model"], "openai/gpt-4o-mini");
+
+    let listed: serde_json::Value = client.get(format!("{}/q/templates", base)).bearer_auth(&token).send().await.unwrap().json().await.unwrap();
+    assert_eq!(listed["templates"][0]["description"], "Fix spelling");
+    let answered: serde_json::Value = client.post(format!("{}/q/templates/spelling", base))
+        .bearer_auth(&token)
+        .json(&serde_json::json!({"input": "teh"}))
+        .send().await.unwrap()
+        .json().await.unwrap();
+    assert_eq!(answered["answer"], "the");
+
+    // A page on another site can't use it
+    let refused = client.post(format!("{}/q/query", base))
+        .header("Origin", "https://example.com")
+        .bearer_auth(&token)
+        .body(r#"{"prompt": "hi"}"#)
+        .send().await.unwrap();
+    assert_eq!(refused.status(), 403);
+    let missing = client.get(format!("{}/v1/embeddings", base)).bearer_auth(&token).send().await.unwrap();
+    assert_eq!(missing.status(), 404);
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+}
+
+#[test]
+fn test_fix_build_respects_context_allow() {
+    let config_home = TempDir::new().unwrap();
+    let config_dir = config_home.path().join("q");
+    std::fs::create_dir_all(&config_dir).unwrap();
+    std::fs::write(config_dir.join("config.toml"), "[context.allow]\nfile = [\"gemini\"]\n").unwrap();
+
+    // Refused before the build runs or a key is looked up
+    let mut cmd = isolated_command(&config_home);
+    cmd.args(["-P", "openai", "fix-build", "sh", "-c", "echo 'src/main.rs:1:1: error'; exit 1"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("file context may only be sent to gemini, not openai"))
+        .stderr(predicate::str::contains("Running").not());
+}
+
+#[test]
+fn test_sample_and_schema_files_respect_context_allow() {
+    let config_home = TempDir::new().unwrap();
+    let config_dir = config_home.path().join("q");
+    std::fs::create_dir_all(&config_dir).unwrap();
+    std::fs::write(config_dir.join("config.toml"), "[context.allow]\nfile = [\"gemini\"]\n").unwrap();
+    let data = config_home.path().join("data");
+    std::fs::write(&data, "{}").unwrap();
+
+    // Refused before the file is read or a key is looked up
+    for (command, flag) in [("jq", "--sample"), ("sql", "--schema")] {
+        isolated_command(&config_home)
+            .args(["-P", "openai", command, flag])
+            .arg(&data)
+            .arg("count the rows")
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("file context may only be sent to gemini, not openai"));
+    }
+}
+
+#[test]
+fn test_invalid_project_config_is_warned_about_once() {
+    let config_home = TempDir::new().unwrap();
+    let project = TempDir::new().unwrap();
+    std::fs::write(project.path().join(".q.toml"), "[context\n").unwrap();
+
+    let output = isolated_command(&config_home)
+        .current_dir(project.path())
+        .args(["-P", "openai", "-M", "gpt-4o", "--dry-run", "--no-stdin", "hello"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(stderr.matches("Ignoring the project config").count(), 1, "{}", stderr);
+}
+
+#[test]
+fn test_dry_run_needs_no_key() {
+    let config_home = TempDir::new().unwrap();
+    isolated_command(&config_home)
+        .args(["-P", "openai", "-M", "gpt-4o", "--dry-run", "--no-stdin", "explain CRDTs"])
         .assert()
         .success()
-        .stdout(predicate::str::contains("Query handling will be implemented"));
+        .stderr(predicate::str::contains("Dry run: nothing was sent"))
+        .stderr(predicate::str::contains("projected cost: $"));
 }
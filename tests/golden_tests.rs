@@ -0,0 +1,113 @@
+//! Golden-output tests: run the binary against a mock OpenAI server and
+//! compare what users see with the snapshots in `tests/snapshots`. A change
+//! to formatting or CLI output shows up as a snapshot diff to review
+//! (`cargo insta review`) rather than slipping through.
+
+use assert_cmd::Command;
+use tempfile::TempDir;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+const ANSWER: &str = "To extract into another directory:\n\n```bash\ntar -xzf archive.tar.gz -C /tmp/out\n```\n\n**Tip**: add `-v` to list files.\n";
+
+fn isolated_command(config_home: &TempDir) -> Command {
+    let mut cmd = Command::cargo_bin("q").unwrap();
+    cmd.env("XDG_CONFIG_HOME", config_home.path())
+        .env("XDG_DATA_HOME", config_home.path().join("data"))
+        .env("XDG_CACHE_HOME", config_home.path().join("cache"))
+        .env("XDG_STATE_HOME", config_home.path().join("state"))
+        .env("NO_COLOR", "1")
+        .env_remove("CLICOLOR_FORCE")
+        .env_remove("GEMINI_API_KEY")
+        .env_remove("OPENROUTER_API_KEY")
+        .env_remove("GROQ_API_KEY")
+        .env_remove("MISTRAL_API_KEY")
+        .env("LANG", "C")
+        .env_remove("LC_ALL")
+        .env_remove("LC_MESSAGES");
+    cmd
+}
+
+/// `q -P openai --no-stdin <args>` answered by `server`
+fn query(config_home: &TempDir, server: &MockServer, args: &[&str]) -> Command {
+    let mut cmd = isolated_command(config_home);
+    cmd.env("OPENAI_API_KEY", "sk-test1234567890abcdefghijklmnopqrstuvwxyz")
+        .env("Q_OPENAI_API_URL", format!("{}/v1/chat/completions", server.uri()))
+        .args(["-P", "openai", "--no-stdin", "--no-follow-up"])
+        .args(args);
+    cmd
+}
+
+async fn mock_answer(body: serde_json::Value) -> MockServer {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(body))
+        .mount(&server)
+        .await;
+    server
+}
+
+fn stdout(cmd: &mut Command) -> String {
+    let output = cmd.output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+fn stderr(cmd: &mut Command) -> String {
+    let output = cmd.output().unwrap();
+    assert!(!output.status.success());
+    String::from_utf8(output.stderr).unwrap()
+}
+
+#[tokio::test]
+async fn test_markdown_answer() {
+    let config_home = TempDir::new().unwrap();
+    let server = mock_answer(serde_json::json!({ "choices": [{ "message": { "content": ANSWER } }] })).await;
+    insta::assert_snapshot!(stdout(&mut query(&config_home, &server, &["How do I extract a tarball elsewhere?"])));
+}
+
+#[tokio::test]
+async fn test_json_answer() {
+    let config_home = TempDir::new().unwrap();
+    let server = mock_answer(serde_json::json!({ "choices": [{ "message": { "content": ANSWER } }] })).await;
+    insta::assert_snapshot!(stdout(&mut query(&config_home, &server, &["--json", "How do I extract a tarball elsewhere?"])));
+}
+
+#[tokio::test]
+async fn test_streamed_answer() {
+    let config_home = TempDir::new().unwrap();
+    let server = MockServer::start().await;
+    let events: String = ["Use ", "`tar -xzf`", " with ", "`-C dir`."]
+        .iter()
+        .map(|chunk| format!("data: {}\n\n", serde_json::json!({ "choices": [{ "delta": { "content": chunk } }] })))
+        .chain(["data: [DONE]\n\n".to_string()])
+        .collect();
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(events).append_header("content-type", "text/event-stream"))
+        .mount(&server)
+        .await;
+    insta::assert_snapshot!(stdout(&mut query(&config_home, &server, &["--stream", "How do I extract a tarball elsewhere?"])));
+}
+
+#[tokio::test]
+async fn test_invalid_key_error() {
+    let config_home = TempDir::new().unwrap();
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(401).set_body_json(serde_json::json!({
+            "error": { "message": "Incorrect API key provided", "type": "invalid_request_error", "code": "invalid_api_key" }
+        })))
+        .mount(&server)
+        .await;
+    insta::assert_snapshot!(stderr(&mut query(&config_home, &server, &["--retries", "0", "hello"])));
+}
+
+#[test]
+fn test_missing_key_error() {
+    let config_home = TempDir::new().unwrap();
+    let mut cmd = isolated_command(&config_home);
+    cmd.env_remove("OPENAI_API_KEY").args(["-P", "openai", "--no-stdin", "hello"]);
+    insta::assert_snapshot!(stderr(&mut cmd));
+}
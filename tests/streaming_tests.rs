@@ -1,6 +1,5 @@
 use futures::{stream, StreamExt};
 use std::sync::Arc;
-use tokio;
 
 use q::api::{ApiError, LLMApi, StreamingResponse};
 use q::core::stream::handle_streaming_response;